@@ -0,0 +1,194 @@
+//! Packs files into tar archives and unpacks them again
+//!
+//! Only what group archives in Secrets and `tuckr export` need is supported: regular files,
+//! directories and symlinks, which any tar can extract. Archives are only ever kept in memory since
+//! their contents are secrets.
+
+use std::io::{Cursor, Read};
+use std::path::{Component, PathBuf};
+use tar::{Builder, EntryType, Header};
+use zeroize::Zeroizing;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntryKind {
+    File,
+    /// stored for directories that are empty, the others are created along with their files
+    Dir,
+    /// where the symlink points to, it isn't followed
+    Symlink(PathBuf),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    /// relative to the archive's root
+    pub path: PathBuf,
+    pub kind: EntryKind,
+    pub mode: u32,
+    /// seconds since the unix epoch, 0 if it isn't known
+    pub mtime: u64,
+    pub contents: Zeroizing<Vec<u8>>,
+}
+
+/// Packs the entries into a tar archive
+pub fn pack(entries: &[Entry]) -> Result<Zeroizing<Vec<u8>>, String> {
+    let mut archive = Builder::new(Vec::new());
+
+    for entry in entries {
+        let mut header = Header::new_ustar();
+        header.set_mode(entry.mode & 0o7777);
+        header.set_mtime(entry.mtime);
+        let appended = match &entry.kind {
+            EntryKind::File => {
+                header.set_entry_type(EntryType::Regular);
+                header.set_size(entry.contents.len() as u64);
+                archive.append_data(&mut header, &entry.path, entry.contents.as_slice())
+            }
+            EntryKind::Dir => {
+                header.set_entry_type(EntryType::Directory);
+                header.set_size(0);
+                archive.append_data(&mut header, &entry.path, std::io::empty())
+            }
+            EntryKind::Symlink(target) => {
+                header.set_entry_type(EntryType::Symlink);
+                header.set_size(0);
+                archive.append_link(&mut header, &entry.path, target)
+            }
+        };
+        appended.map_err(|err| format!("{}: {err}", entry.path.display()))?;
+    }
+
+    let archive = archive.into_inner().map_err(|err| err.to_string())?;
+    Ok(Zeroizing::new(archive))
+}
+
+/// Unpacks the regular files, directories and symlinks of a tar archive, everything else is skipped
+///
+/// Paths that would end up outside of where the archive is unpacked are rejected, including the
+/// ones that go through a symlink in the archive
+pub fn unpack(archive: &[u8]) -> Result<Vec<Entry>, String> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut archive = tar::Archive::new(Cursor::new(archive));
+
+    for entry in archive.entries().map_err(|err| err.to_string())? {
+        let mut entry = entry.map_err(|err| err.to_string())?;
+        let kind = match entry.header().entry_type() {
+            EntryType::Regular => EntryKind::File,
+            EntryType::Directory => EntryKind::Dir,
+            EntryType::Symlink => match entry.link_name().map_err(|err| err.to_string())? {
+                Some(target) => EntryKind::Symlink(target.into_owned()),
+                None => continue,
+            },
+            _ => continue,
+        };
+
+        let path: PathBuf = entry
+            .path()
+            .map_err(|err| err.to_string())?
+            .components()
+            .collect();
+        let is_contained = path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        let through_symlink = entries.iter().any(|unpacked| {
+            matches!(unpacked.kind, EntryKind::Symlink(_))
+                && path.starts_with(&unpacked.path)
+                && path != unpacked.path
+        });
+        if !is_contained || through_symlink || path.as_os_str().is_empty() {
+            return Err(format!(
+                "{}: the path leaves the archive's directory",
+                path.display()
+            ));
+        }
+
+        let header = entry.header();
+        let mode = header.mode().map_err(|err| err.to_string())?;
+        let mtime = header.mtime().map_err(|err| err.to_string())?;
+        let size = header.size().map_err(|err| err.to_string())?;
+
+        let mut contents = Zeroizing::new(Vec::new());
+        entry
+            .read_to_end(&mut contents)
+            .map_err(|err| err.to_string())?;
+        if contents.len() as u64 != size {
+            return Err("the tar archive is truncated".into());
+        }
+
+        entries.push(Entry {
+            path,
+            kind,
+            mode,
+            mtime,
+            contents,
+        });
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, contents: &[u8]) -> Entry {
+        Entry {
+            path: path.into(),
+            kind: EntryKind::File,
+            mode: 0o600,
+            mtime: 1_700_000_000,
+            contents: Zeroizing::new(contents.to_vec()),
+        }
+    }
+
+    #[test]
+    fn pack_and_unpack_archives() {
+        let long_path = format!("{}/{}", "dir".repeat(50), "file".repeat(20));
+        let entries = vec![
+            entry(".ssh/id_ed25519", b"key"),
+            entry(".config/empty", b""),
+            entry(&long_path, &[0xff; 1000]),
+            entry(&"a".repeat(300), b"too long for ustar"),
+            Entry {
+                kind: EntryKind::Dir,
+                ..entry(".local/share/empty", b"")
+            },
+            Entry {
+                kind: EntryKind::Symlink(format!("../{}", "b".repeat(200)).into()),
+                ..entry(".config/link", b"")
+            },
+        ];
+
+        let archive = pack(&entries).unwrap();
+        assert_eq!(archive.len() % 512, 0);
+        assert_eq!(unpack(&archive).unwrap(), entries);
+
+        // cut off in the middle of the first file's contents
+        assert!(unpack(&archive[..512 + 2]).is_err());
+    }
+
+    #[test]
+    fn reject_paths_outside_of_the_archive() {
+        for path in ["../.bashrc", "/etc/passwd", "dir/../../.bashrc"] {
+            // tar::Builder refuses to write these paths in the first place
+            let mut header = Header::new_ustar();
+            header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_size(0);
+            header.set_cksum();
+            let mut archive = header.as_bytes().to_vec();
+            archive.extend([0; 1024]);
+
+            assert!(unpack(&archive).is_err(), "{path} was unpacked");
+        }
+
+        // files can't be unpacked to where a symlink in the archive points to
+        let through_symlink = pack(&[
+            Entry {
+                kind: EntryKind::Symlink("/etc".into()),
+                ..entry("config", b"")
+            },
+            entry("config/passwd", b"root::0:0::/:/bin/sh"),
+        ])
+        .unwrap();
+        assert!(unpack(&through_symlink).is_err());
+    }
+}
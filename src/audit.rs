@@ -0,0 +1,116 @@
+//! Keeps an audit log of hook runs
+//!
+//! When an audit log is set (with `tuckr set --audit-log <file>` or `audit_log` in tuckr.toml),
+//! every `tuckr set` appends a report with the hooks that were run, how long they took,
+//! their exit codes and the sha256 of the scripts at the time they were run.
+//! Each report is written as a single line of JSON so that the log can be read as JSON Lines.
+
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HookRun {
+    pub group: String,
+    /// one of `pre`, `post` or `rm`
+    pub kind: String,
+    pub script: PathBuf,
+    /// None if the script could not be read
+    pub sha256: Option<String>,
+    /// None if the script could not be started or was terminated by a signal
+    pub exit_code: Option<i32>,
+    pub duration_ms: u128,
+    /// where the script's output was logged to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunReport {
+    /// seconds since the unix epoch
+    started: u64,
+    finished: u64,
+    profile: Option<String>,
+    groups: Vec<String>,
+    succeeded: bool,
+    pub hooks: Vec<HookRun>,
+}
+
+impl RunReport {
+    pub fn new(profile: Option<String>, groups: &[String]) -> Self {
+        Self {
+            started: now(),
+            finished: 0,
+            profile,
+            groups: groups.to_vec(),
+            succeeded: true,
+            hooks: Vec::new(),
+        }
+    }
+
+    /// Marks the run as finished and appends the report to the audit log
+    pub fn finish(mut self, succeeded: bool, audit_log: &Path) -> Result<(), String> {
+        self.finished = now();
+        self.succeeded = succeeded;
+
+        let mut report = serde_json::to_string(&self).map_err(|e| e.to_string())?;
+        report.push('\n');
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(audit_log)
+            .and_then(|mut log| log.write_all(report.as_bytes()))
+            .map_err(|e| format!("{}: {e}", audit_log.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn reports_are_appended_as_json_lines() {
+        let log = std::env::temp_dir().join(format!(
+            "tuckr_audit-{}.jsonl",
+            std::thread::current().name().unwrap()
+        ));
+        _ = fs::remove_file(&log);
+
+        for _ in 0..2 {
+            let mut report = RunReport::new(None, &["zsh".into()]);
+            report.hooks.push(HookRun {
+                group: "zsh".into(),
+                kind: "pre".into(),
+                script: "pre.sh".into(),
+                sha256: None,
+                exit_code: Some(0),
+                duration_ms: 1,
+                log: None,
+            });
+            report.finish(true, &log).unwrap();
+        }
+
+        let log_content = fs::read_to_string(&log).unwrap();
+        let reports: Vec<serde_json::Value> = log_content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0]["hooks"][0]["exit_code"], 0);
+        assert_eq!(reports[1]["succeeded"], true);
+
+        fs::remove_file(log).unwrap();
+    }
+}
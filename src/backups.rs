@@ -0,0 +1,594 @@
+//! Backs up dotfiles before they get overridden
+//!
+//! Every time `tuckr add --force` replaces conflicting files, the conflicting files are moved
+//! into a backup set at dotfiles/.backups/<timestamp>/ instead of being deleted.
+//! Each backup set contains a manifest.json that records where every file originally lived,
+//! so that `tuckr backups restore <backup>` can put them back.
+//!
+//! Old backup sets are removed according to the `[backups]` retention limits in tuckr.toml whenever
+//! a new one is created, or on `tuckr backups prune`.
+
+use crate::config::{self, BackupsConfig};
+use crate::dotfiles;
+use crate::error;
+use crate::errors::{self, TuckrError};
+use crate::history;
+use crate::plan::{self, Step};
+use crate::{fileops, preflight, stats};
+use owo_colors::OwoColorize;
+use owo_colors::Stream::{Stderr, Stdout};
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    /// seconds since the unix epoch when the backup set was created
+    created: u64,
+    files: Vec<BackupEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BackupEntry {
+    /// where the file was before it got backed up
+    original: PathBuf,
+    /// where the file is stored, relative to the backup set directory
+    backup: PathBuf,
+}
+
+pub const BACKUPS_DIR: &str = ".backups";
+
+/// Returns the path to dotfiles/.backups
+pub fn get_backups_dir(profile: Option<String>) -> Result<PathBuf, errors::Error> {
+    Ok(dotfiles::get_dotfiles_path(profile)?.join(BACKUPS_DIR))
+}
+
+/// A set of files that were backed up during a single tuckr invocation
+pub struct BackupSet {
+    path: PathBuf,
+    manifest: Manifest,
+}
+
+impl BackupSet {
+    /// Creates a new backup set named after the current time
+    ///
+    /// The backup set's directory is only created once the first file is backed up
+    pub fn try_new(profile: Option<String>) -> Result<Self, errors::Error> {
+        let backups_dir = get_backups_dir(profile)?;
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| e.to_string())?
+            .as_secs();
+
+        // makes sure that invocations within the same second don't share a backup set
+        let mut path = backups_dir.join(created.to_string());
+        let mut suffix = 1;
+        while path.exists() {
+            path = backups_dir.join(format!("{created}-{suffix}"));
+            suffix += 1;
+        }
+
+        Ok(Self {
+            path,
+            manifest: Manifest {
+                created,
+                files: Vec::new(),
+            },
+        })
+    }
+
+    /// Moves `file` into the backup set and records its original location in the manifest
+    ///
+    /// Returns where the file was moved to
+    pub fn backup(&mut self, dry_run: bool, file: &Path) -> Result<PathBuf, errors::Error> {
+        // mirrors the original path inside the backup set, e.g. /home/user/.zshrc -> files/home/user/.zshrc
+        let relative_path: PathBuf = file
+            .components()
+            .filter(|c| matches!(c, Component::Normal(_)))
+            .collect();
+        let backup = Path::new("files").join(relative_path);
+        let backup_path = self.path.join(&backup);
+
+        if dry_run {
+            if !plan::record(Step::Backup {
+                file: file.to_path_buf(),
+            }) {
+                eprintln!(
+                    "{} `{}` into `{}`",
+                    "backing up".if_supports_color(Stderr, |t| t.yellow()),
+                    file.display(),
+                    backup_path.display()
+                );
+            }
+            return Ok(backup_path);
+        }
+
+        // the backups might be decrypted secrets so they must never be committed
+        let backups_dir = self.path.parent().unwrap();
+        if !backups_dir.exists()
+            && let Some(dotfiles_dir) = backups_dir.parent()
+        {
+            history::exclude_locally(dotfiles_dir);
+        }
+
+        fs::create_dir_all(backup_path.parent().unwrap()).map_err(|e| e.to_string())?;
+        fileops::move_path(file, &backup_path)
+            .map_err(|e| error!("failed_to_backup", file = file.display(), err = e))?;
+
+        self.manifest.files.push(BackupEntry {
+            original: file.to_path_buf(),
+            backup,
+        });
+
+        // the manifest is saved after every file so that a crash never leaves a file untracked
+        self.save()?;
+        Ok(backup_path)
+    }
+
+    /// Forgets the files that were moved back out of the backup set, e.g. by a rollback
+    ///
+    /// The backup set is deleted once it has no files left
+    pub fn prune(&mut self) -> Result<(), String> {
+        let files = self.manifest.files.len();
+        self.manifest
+            .files
+            .retain(|entry| fs::symlink_metadata(self.path.join(&entry.backup)).is_ok());
+
+        if files == self.manifest.files.len() {
+            return Ok(());
+        }
+
+        match self.is_empty() {
+            true => fs::remove_dir_all(&self.path).map_err(|e| e.to_string()),
+            false => self.save(),
+        }
+    }
+
+    /// Returns true if no files have been backed up
+    pub fn is_empty(&self) -> bool {
+        self.manifest.files.is_empty()
+    }
+
+    /// Returns the name used to reference the backup set on `tuckr backups restore`
+    pub fn name(&self) -> String {
+        self.path.file_name().unwrap().to_str().unwrap().into()
+    }
+
+    /// Returns how many bytes the backup set takes up
+    fn size(&self) -> u64 {
+        fn dir_size(dir: &Path) -> u64 {
+            let Ok(entries) = dir.read_dir() else {
+                return 0;
+            };
+
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| match entry.file_type() {
+                    Ok(file_type) if file_type.is_dir() => dir_size(&entry.path()),
+                    _ => entry.metadata().map(|m| m.len()).unwrap_or_default(),
+                })
+                .sum()
+        }
+
+        dir_size(&self.path)
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let manifest = serde_json::to_string_pretty(&self.manifest).map_err(|e| e.to_string())?;
+        fs::write(self.path.join(MANIFEST_FILE), manifest).map_err(|e| e.to_string())
+    }
+
+    fn load(path: PathBuf) -> Result<Self, String> {
+        let manifest = fs::read_to_string(path.join(MANIFEST_FILE)).map_err(|e| e.to_string())?;
+        let manifest = serde_json::from_str(&manifest).map_err(|e| e.to_string())?;
+        Ok(Self { path, manifest })
+    }
+}
+
+/// Returns all the backup sets sorted from oldest to newest
+fn get_backup_sets(profile: Option<String>) -> Result<Vec<BackupSet>, String> {
+    let backups_dir = get_backups_dir(profile)?;
+    let Ok(dir) = backups_dir.read_dir() else {
+        return Ok(Vec::new());
+    };
+
+    let mut backup_sets: Vec<_> = dir
+        .filter_map(|entry| BackupSet::load(entry.ok()?.path()).ok())
+        .collect();
+    backup_sets.sort_by(|a, b| {
+        a.manifest
+            .created
+            .cmp(&b.manifest.created)
+            .then_with(|| a.name().cmp(&b.name()))
+    });
+
+    Ok(backup_sets)
+}
+
+/// Returns the indices of the backup sets that break the retention limits
+///
+/// `backup_sets` holds the creation time and size of each set sorted from oldest to newest,
+/// the newest one is never expired
+fn expired_backup_sets(backup_sets: &[(u64, u64)], limits: &BackupsConfig, now: u64) -> Vec<usize> {
+    let Some(newest) = backup_sets.len().checked_sub(1) else {
+        return Vec::new();
+    };
+
+    let keep_from = limits
+        .keep_last
+        .map_or(0, |keep| backup_sets.len().saturating_sub(keep.max(1)));
+    let mut expired: Vec<usize> = (0..newest)
+        .filter(|&idx| {
+            let (created, _) = backup_sets[idx];
+            idx < keep_from
+                || limits
+                    .max_age
+                    .is_some_and(|age| now.saturating_sub(created) > age)
+        })
+        .collect();
+
+    if let Some(max_size) = limits.max_size {
+        let mut total: u64 = (0..backup_sets.len())
+            .filter(|idx| !expired.contains(idx))
+            .map(|idx| backup_sets[idx].1)
+            .sum();
+
+        for (idx, (_, size)) in backup_sets[..newest].iter().enumerate() {
+            if total <= max_size {
+                break;
+            }
+            if !expired.contains(&idx) {
+                total -= size;
+                expired.push(idx);
+            }
+        }
+        expired.sort_unstable();
+    }
+
+    expired
+}
+
+/// Removes the backup sets that break the retention limits
+///
+/// Returns the removed backup sets' names and how many bytes they took up
+fn prune_backup_sets(
+    profile: Option<String>,
+    dry_run: bool,
+    limits: &BackupsConfig,
+) -> Result<(Vec<String>, u64), String> {
+    let backup_sets = get_backup_sets(profile)?;
+    let sizes: Vec<_> = backup_sets
+        .iter()
+        .map(|set| (set.manifest.created, set.size()))
+        .collect();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| e.to_string())?
+        .as_secs();
+
+    let mut pruned = Vec::new();
+    let mut freed = 0;
+    for idx in expired_backup_sets(&sizes, limits, now) {
+        let backup_set = &backup_sets[idx];
+        if dry_run {
+            eprintln!(
+                "{} `{}`",
+                "removing".if_supports_color(Stderr, |t| t.red()),
+                backup_set.path.display()
+            );
+        } else {
+            fs::remove_dir_all(&backup_set.path)
+                .map_err(|e| format!("{}: {e}", backup_set.path.display()))?;
+        }
+        pruned.push(backup_set.name());
+        freed += sizes[idx].1;
+    }
+
+    Ok((pruned, freed))
+}
+
+/// Removes the backup sets that break the retention limits in tuckr.toml, if there are any
+///
+/// Called after a new backup set was created, failing to remove old backups is only reported
+pub fn apply_retention(profile: Option<String>) {
+    let limits = &config::get().backups;
+    if limits.is_unlimited() {
+        return;
+    }
+
+    match prune_backup_sets(profile, false, limits) {
+        Ok((pruned, freed)) if !pruned.is_empty() => println!(
+            "{}",
+            t!(
+                "info.backups_pruned",
+                count = pruned.len(),
+                size = preflight::format_size(freed)
+            )
+            .if_supports_color(Stdout, |t| t.yellow())
+        ),
+        Ok(_) => (),
+        Err(err) => errors::print(err),
+    }
+}
+
+fn print_backup_sets(backup_sets: &[BackupSet]) {
+    println!("{}:", t!("info.x_available", x = "Backups"));
+    for backup_set in backup_sets {
+        println!(
+            "\t{} ({})",
+            backup_set.name(),
+            t!("info.x_files", count = backup_set.manifest.files.len())
+        );
+    }
+}
+
+/// Lists the backup sets with when they were created and how much space they take up
+pub fn list_cmd(profile: Option<String>) -> Result<(), TuckrError> {
+    let backup_sets = match get_backup_sets(profile) {
+        Ok(sets) => sets,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::CouldntFindDotfiles);
+        }
+    };
+
+    if backup_sets.is_empty() {
+        println!(
+            "{}",
+            t!("errors.no_x_setup_yet", x = "backups").if_supports_color(Stdout, |t| t.yellow())
+        );
+        return Ok(());
+    }
+
+    let mut total = 0;
+    println!("{}:", t!("info.x_available", x = "Backups"));
+    for backup_set in &backup_sets {
+        let size = backup_set.size();
+        total += size;
+        println!(
+            "\t{} {} ({}, {})",
+            backup_set.name(),
+            stats::format_date(backup_set.manifest.created / stats::SECS_PER_DAY)
+                .if_supports_color(Stdout, |t| t.dimmed()),
+            t!("info.x_files", count = backup_set.manifest.files.len()),
+            preflight::format_size(size)
+        );
+    }
+    println!(
+        "{}",
+        t!("info.backups_total", size = preflight::format_size(total))
+    );
+
+    Ok(())
+}
+
+/// Removes old backup sets, the limits that aren't supplied are taken from tuckr.toml
+pub fn prune_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    limits: BackupsConfig,
+) -> Result<(), TuckrError> {
+    let configured = &config::get().backups;
+    let limits = BackupsConfig {
+        keep_last: limits.keep_last.or(configured.keep_last),
+        max_age: limits.max_age.or(configured.max_age),
+        max_size: limits.max_size.or(configured.max_size),
+    };
+
+    if limits.is_unlimited() {
+        errors::print(error!("no_backup_limits"));
+        return Err(TuckrError::Failed);
+    }
+
+    match prune_backup_sets(profile, dry_run, &limits) {
+        Ok((pruned, _)) if pruned.is_empty() => {
+            println!("{}", t!("info.no_backups_to_prune"));
+            Ok(())
+        }
+        Ok(_) if dry_run => Ok(()),
+        Ok((pruned, freed)) => {
+            println!(
+                "{}",
+                t!(
+                    "info.backups_pruned",
+                    count = pruned.len(),
+                    size = preflight::format_size(freed)
+                )
+                .if_supports_color(Stdout, |t| t.yellow())
+            );
+            Ok(())
+        }
+        Err(err) => {
+            errors::print(err);
+            Err(TuckrError::Failed)
+        }
+    }
+}
+
+/// Lists the available backups or restores the files from one of them
+pub fn restore_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    backup: Option<String>,
+) -> Result<(), TuckrError> {
+    let backup_sets = match get_backup_sets(profile) {
+        Ok(sets) => sets,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::CouldntFindDotfiles);
+        }
+    };
+
+    let Some(backup) = backup else {
+        if backup_sets.is_empty() {
+            println!(
+                "{}",
+                t!("errors.no_x_setup_yet", x = "backups")
+                    .if_supports_color(Stdout, |t| t.yellow())
+            );
+        } else {
+            print_backup_sets(&backup_sets);
+        }
+        return Ok(());
+    };
+
+    let Some(mut backup_set) = backup_sets.into_iter().find(|set| set.name() == backup) else {
+        errors::print(error!("no_backup_called", backup = backup));
+        return Err(TuckrError::NoSuchFileOrDir);
+    };
+
+    let mut not_restored = Vec::new();
+    // files are restored in reverse so that the most recent backup of a path always wins
+    while let Some(entry) = backup_set.manifest.files.pop() {
+        let backup_path = backup_set.path.join(&entry.backup);
+
+        // the file might have been replaced by a tuckr symlink which is safe to remove,
+        // anything else is user data and should not be touched
+        if entry.original.is_symlink() {
+            if dry_run {
+                eprintln!(
+                    "{} `{}`",
+                    "removing".if_supports_color(Stderr, |t| t.red()),
+                    entry.original.display()
+                );
+            } else if let Err(err) = fs::remove_file(&entry.original) {
+                errors::print(err);
+                not_restored.push(entry);
+                continue;
+            }
+        } else if entry.original.exists() {
+            eprintln!(
+                "{}",
+                t!("warn.not_restoring_x", file = entry.original.display())
+                    .if_supports_color(Stderr, |t| t.yellow())
+            );
+            not_restored.push(entry);
+            continue;
+        }
+
+        if dry_run {
+            eprintln!(
+                "{} `{}` to `{}`",
+                "restoring".if_supports_color(Stderr, |t| t.green()),
+                backup_path.display(),
+                entry.original.display()
+            );
+            continue;
+        }
+
+        let restored = match entry.original.parent() {
+            Some(parent) => fs::create_dir_all(parent),
+            None => Ok(()),
+        }
+        .and_then(|_| fileops::move_path(&backup_path, &entry.original));
+
+        if let Err(err) = restored {
+            errors::print(format!("{}: {err}", entry.original.display()));
+            not_restored.push(entry);
+        }
+    }
+
+    if dry_run {
+        return Ok(());
+    }
+
+    if not_restored.is_empty() {
+        if let Err(err) = fs::remove_dir_all(&backup_set.path) {
+            errors::print(format!("{}: {err}", backup_set.path.display()));
+            return Err(TuckrError::Failed);
+        }
+        return Ok(());
+    }
+
+    // keeps track of whatever could not be restored so it can be retried later
+    not_restored.reverse();
+    backup_set.manifest.files = not_restored;
+    if let Err(err) = backup_set.save() {
+        errors::print(err);
+    }
+
+    Err(TuckrError::Failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backup_and_restore_file() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let target_dir = std::env::temp_dir().join(format!(
+            "tuckr_backup_test-{}",
+            std::thread::current().name().unwrap()
+        ));
+        fs::create_dir_all(&target_dir).unwrap();
+
+        let file = target_dir.join(".zshrc");
+        fs::write(&file, "export EDITOR=vim").unwrap();
+
+        let mut backup_set = BackupSet::try_new(None).unwrap();
+        backup_set.backup(false, &file).unwrap();
+        assert!(!file.exists());
+        assert!(!backup_set.is_empty());
+
+        restore_cmd(None, false, Some(backup_set.name())).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "export EDITOR=vim");
+        assert!(!backup_set.path.exists());
+
+        fs::remove_dir_all(target_dir).unwrap();
+        fs::remove_dir_all(dotfiles_dir).unwrap();
+    }
+
+    #[test]
+    fn retention_limits() {
+        const DAY: u64 = 24 * 60 * 60;
+        let now = 100 * DAY;
+        // created, size
+        let sets = [
+            (10 * DAY, 300),
+            (50 * DAY, 200),
+            (90 * DAY, 100),
+            (99 * DAY, 400),
+        ];
+
+        assert!(expired_backup_sets(&sets, &BackupsConfig::default(), now).is_empty());
+        assert!(expired_backup_sets(&[], &BackupsConfig::default(), now).is_empty());
+
+        let keep_last = BackupsConfig {
+            keep_last: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(expired_backup_sets(&sets, &keep_last, now), [0, 1]);
+
+        let max_age = BackupsConfig {
+            max_age: Some(30 * DAY),
+            ..Default::default()
+        };
+        assert_eq!(expired_backup_sets(&sets, &max_age, now), [0, 1]);
+
+        let max_size = BackupsConfig {
+            max_size: Some(600),
+            ..Default::default()
+        };
+        assert_eq!(expired_backup_sets(&sets, &max_size, now), [0, 1]);
+
+        // the most recent backup set is kept no matter what
+        let strict = BackupsConfig {
+            keep_last: Some(0),
+            max_age: Some(0),
+            max_size: Some(0),
+        };
+        assert_eq!(expired_backup_sets(&sets, &strict, now), [0, 1, 2]);
+
+        let combined = BackupsConfig {
+            keep_last: Some(3),
+            max_size: Some(500),
+            ..Default::default()
+        };
+        assert_eq!(expired_backup_sets(&sets, &combined, now), [0, 1]);
+    }
+}
@@ -0,0 +1,152 @@
+//! Handles Ctrl-C
+//!
+//! While tuckr is deploying or removing groups, Ctrl-C only asks it to stop once the current file
+//! or hook is done, so that the group that was being worked on can be rolled back instead of being
+//! left half deployed. Everywhere else, e.g. while waiting at a prompt, tuckr exits right away
+//! after restoring the terminal, removing what was registered with [`remove_on_interrupt`] first.
+
+use crate::error;
+use crate::errors::{self, TuckrError};
+use owo_colors::OwoColorize;
+use owo_colors::Stream::Stderr;
+use rust_i18n::t;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+static REPORTED: AtomicBool = AtomicBool::new(false);
+/// how many parts of tuckr are currently deferring cancellation
+static DEFERRED: AtomicUsize = AtomicUsize::new(0);
+/// printed by the signal handler which can't allocate to translate it
+static MESSAGE: OnceLock<String> = OnceLock::new();
+
+#[cfg(target_family = "unix")]
+static TERMINAL: OnceLock<libc::termios> = OnceLock::new();
+
+/// files and directories that the signal handler removes, kept as C strings since it can't allocate
+#[cfg(target_family = "unix")]
+static REMOVE_ON_INTERRUPT: Mutex<Vec<std::ffi::CString>> = Mutex::new(Vec::new());
+
+/// Installs the Ctrl-C handler
+///
+/// On Windows Ctrl-C keeps terminating tuckr right away
+pub fn install_handler() {
+    _ = MESSAGE.set(format!(
+        "\n{}\n",
+        t!("warn.cancelling").if_supports_color(Stderr, |t| t.yellow())
+    ));
+
+    #[cfg(target_family = "unix")]
+    {
+        // SAFETY: termios is plain data that tcgetattr fills in, it's only kept if that succeeded
+        unsafe {
+            let mut terminal = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut terminal) == 0 {
+                _ = TERMINAL.set(terminal);
+            }
+        }
+
+        // SAFETY: the handler only touches atomics and calls async-signal-safe functions.
+        // SA_RESTART is left out so that reads that are waiting on the terminal get interrupted
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut());
+        }
+    }
+}
+
+#[cfg(target_family = "unix")]
+extern "C" fn on_interrupt(_: libc::c_int) {
+    // pressing Ctrl-C a second time gives up on stopping gracefully
+    if DEFERRED.load(Ordering::SeqCst) > 0 && !CANCELLED.swap(true, Ordering::SeqCst) {
+        if let Some(message) = MESSAGE.get() {
+            // SAFETY: write is async-signal-safe and the message outlives the call
+            unsafe { libc::write(libc::STDERR_FILENO, message.as_ptr().cast(), message.len()) };
+        }
+        return;
+    }
+
+    // SAFETY: tcsetattr, unlink, rmdir and _exit are async-signal-safe. The paths are only
+    // removed if the lock isn't held, so that the handler never waits for the code it interrupted
+    unsafe {
+        if let Some(terminal) = TERMINAL.get() {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, terminal);
+        }
+        if let Ok(paths) = REMOVE_ON_INTERRUPT.try_lock() {
+            // the files that were registered after their directory are removed before it
+            for path in paths.iter().rev() {
+                if libc::unlink(path.as_ptr()) != 0 {
+                    libc::rmdir(path.as_ptr());
+                }
+            }
+        }
+        // the shell's convention of 128 + SIGINT, what `TuckrError::Cancelled` exits with as well
+        libc::_exit(130);
+    }
+}
+
+/// Removes the file or directory if tuckr exits because of Ctrl-C before it was removed otherwise,
+/// e.g. decrypted files that must not be left behind. Directories are only removed once they're
+/// empty, so their files have to be registered too
+///
+/// On Windows Ctrl-C terminates tuckr right away, so nothing can be removed
+pub fn remove_on_interrupt(path: &Path) {
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        if let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) {
+            REMOVE_ON_INTERRUPT.lock().unwrap().push(path);
+        }
+    }
+    #[cfg(not(target_family = "unix"))]
+    let _ = path;
+}
+
+/// Forgets a path registered with [`remove_on_interrupt`] once it was removed
+pub fn forget_on_interrupt(path: &Path) {
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        REMOVE_ON_INTERRUPT
+            .lock()
+            .unwrap()
+            .retain(|registered| registered.as_bytes() != path.as_os_str().as_bytes());
+    }
+    #[cfg(not(target_family = "unix"))]
+    let _ = path;
+}
+
+/// Defers cancellation until the returned guard is dropped
+pub struct Deferred(());
+
+impl Drop for Deferred {
+    fn drop(&mut self) {
+        DEFERRED.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub fn defer() -> Deferred {
+    DEFERRED.fetch_add(1, Ordering::SeqCst);
+    Deferred(())
+}
+
+/// Returns true if Ctrl-C was pressed while cancellation was deferred
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// Returns an error if tuckr was cancelled, the cancellation is only reported once
+pub fn check() -> Result<(), TuckrError> {
+    if !is_cancelled() {
+        return Ok(());
+    }
+
+    if !REPORTED.swap(true, Ordering::SeqCst) {
+        errors::print(error!("cancelled"));
+    }
+
+    Err(TuckrError::Cancelled)
+}
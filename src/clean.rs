@@ -0,0 +1,196 @@
+//! Cleans up the symlinks that groups left behind after they were renamed or removed
+//!
+//! `tuckr clean` looks for symlinks that point into a dotfiles directory, of any profile, at a
+//! file that no longer exists. The files recorded in the state are checked if there are any,
+//! otherwise the target directory is searched instead. Since it can be large, it's only searched
+//! `--depth` directories deep and symlinked directories aren't walked into.
+
+use crate::dotfiles;
+use crate::errors::{self, TuckrError};
+use crate::interactive;
+use crate::state::{self, State};
+use owo_colors::OwoColorize;
+use owo_colors::Stream::{Stderr, Stdout};
+use rust_i18n::t;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// how many directories deep the target directory is searched when nothing is recorded in the state
+pub const DEFAULT_DEPTH: usize = 4;
+
+/// Checks whether a directory's name is one of a dotfiles directory, e.g. `.dotfiles` or `dotfiles_work`
+fn is_dotfiles_dir(name: &OsStr) -> bool {
+    name.to_str()
+        .is_some_and(|name| name.trim_start_matches('.').starts_with("dotfiles"))
+}
+
+/// Returns true if `path` is a symlink into a dotfiles directory whose file no longer exists
+fn is_orphaned_symlink(path: &Path) -> bool {
+    let Ok(source) = fs::read_link(path) else {
+        return false;
+    };
+    // relative symlinks are relative to the directory they're in
+    let source = match path.parent() {
+        Some(dir) => dir.join(source),
+        None => source,
+    };
+
+    source
+        .components()
+        .any(|component| is_dotfiles_dir(component.as_os_str()))
+        && source.symlink_metadata().is_err()
+}
+
+/// Searches `dir` for orphaned symlinks, at most `depth` directories deep
+fn scan(dir: &Path, depth: usize, orphaned: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        if file_type.is_symlink() {
+            if is_orphaned_symlink(&path) {
+                orphaned.push(path);
+            }
+        } else if file_type.is_dir() && depth > 1 && !is_dotfiles_dir(&entry.file_name()) {
+            scan(&path, depth - 1, orphaned);
+        }
+    }
+}
+
+/// Returns the orphaned symlinks, the state is used if anything's recorded in it
+fn find_orphaned_symlinks(state: &State, target_dir: &Path, depth: usize) -> Vec<PathBuf> {
+    if !state.deployed.is_empty() {
+        return state
+            .deployed
+            .keys()
+            .filter(|path| is_orphaned_symlink(path))
+            .cloned()
+            .collect();
+    }
+
+    let mut orphaned = Vec::new();
+    scan(target_dir, depth, &mut orphaned);
+    orphaned.sort();
+    orphaned
+}
+
+/// Lists the symlinks into the dotfiles whose files no longer exist and removes them once confirmed
+pub fn clean_cmd(profile: Option<String>, dry_run: bool, depth: usize) -> Result<(), TuckrError> {
+    let mut state = state::load_or_report(profile)?;
+    let target_dir = dotfiles::get_dotfiles_target_dir_path().map_err(|err| {
+        errors::print(err);
+        TuckrError::Failed
+    })?;
+
+    let orphaned = find_orphaned_symlinks(&state, &target_dir, depth);
+    if orphaned.is_empty() {
+        println!(
+            "{}",
+            t!("info.no_orphaned_symlinks").if_supports_color(Stdout, |t| t.green())
+        );
+        return Ok(());
+    }
+
+    println!("{}:", t!("info.orphaned_symlinks"));
+    for link in &orphaned {
+        println!(
+            "\t{} -> {}",
+            link.display().if_supports_color(Stdout, |t| t.yellow()),
+            fs::read_link(link).unwrap_or_default().display()
+        );
+    }
+
+    if dry_run {
+        for link in &orphaned {
+            eprintln!(
+                "{} `{}`",
+                "removing".if_supports_color(Stderr, |t| t.red()),
+                link.display()
+            );
+        }
+        return Ok(());
+    }
+
+    if !interactive::confirm(format!("\n{}", t!("warn.want_to_proceed"))) {
+        return Ok(());
+    }
+
+    let mut failed = false;
+    let mut removed = 0;
+    for link in &orphaned {
+        match fs::remove_file(link) {
+            Ok(()) => {
+                state.deployed.remove(link);
+                state.links.remove(link);
+                removed += 1;
+            }
+            Err(err) => {
+                errors::print(format!("{}: {err}", link.display()));
+                failed = true;
+            }
+        }
+    }
+
+    if let Err(err) = state.save() {
+        errors::print(err);
+        failed = true;
+    }
+
+    println!(
+        "{}",
+        t!("info.orphaned_symlinks_removed", count = removed)
+            .if_supports_color(Stdout, |t| t.green())
+    );
+
+    if failed {
+        Err(TuckrError::Failed)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_orphaned_symlinks_in_target() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let target_dir = dotfiles_dir.with_file_name("target");
+        let nested = target_dir.join(".config").join("app");
+        fs::create_dir_all(&nested).unwrap();
+        fs::create_dir_all(dotfiles_dir.join("Configs").join("app")).unwrap();
+
+        let kept = dotfiles_dir.join("Configs").join("app").join("kept");
+        fs::write(&kept, "").unwrap();
+        let deleted = dotfiles_dir.join("Configs").join("old").join("deleted");
+
+        crate::fileops::symlink(&kept, &target_dir.join("kept")).unwrap();
+        crate::fileops::symlink(&deleted, &target_dir.join("deleted")).unwrap();
+        crate::fileops::symlink(&deleted, &nested.join("deleted")).unwrap();
+        // only symlinks into the dotfiles are tuckr's to clean up
+        crate::fileops::symlink(Path::new("/nonexistent/file"), &target_dir.join("other")).unwrap();
+
+        let state = State::default();
+        assert_eq!(
+            find_orphaned_symlinks(&state, &target_dir, DEFAULT_DEPTH),
+            [
+                target_dir.join(".config/app/deleted"),
+                target_dir.join("deleted")
+            ]
+        );
+        assert_eq!(
+            find_orphaned_symlinks(&state, &target_dir, 1),
+            [target_dir.join("deleted")]
+        );
+
+        fs::remove_dir_all(dotfiles_dir.parent().unwrap()).unwrap();
+    }
+}
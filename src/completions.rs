@@ -0,0 +1,325 @@
+//! Shell completions
+//!
+//! The scripts printed by `tuckr completions` are generated by clap_complete from the same clap
+//! definitions that parse the arguments, so commands and flags never go out of date. Bash, zsh and
+//! fish also get a function on top that completes arguments taking groups or profiles with the
+//! ones in the dotfiles, which it asks the hidden `tuckr __complete` for.
+
+use crate::config;
+use crate::dotfiles::{self, DotfileType};
+use crate::errors::TuckrError;
+use crate::fileops;
+use clap::{Arg, ArgAction, Command, ValueEnum};
+use clap_complete::Shell;
+use std::collections::BTreeSet;
+use std::fs;
+
+/// What `tuckr __complete` lists
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Names {
+    Groups,
+    Profiles,
+}
+
+// the commands taking groups are filled in for {every_word}, {first_word} and {options}, values
+// that use delimiters, e.g. `-e zsh,nvim`, are completed after their last comma
+const BASH_SCRIPT: &str = r#"
+# group and profile names come from the dotfiles
+_tuckr_dotfiles() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    local profile=() takes_groups="" names i
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${COMP_WORDS[i]}" in
+            -p | --profile) profile=(--profile "${COMP_WORDS[i + 1]}") ;;
+            --profile=*) profile=("${COMP_WORDS[i]}") ;;
+        esac
+        if [[ " {every_word} " == *" ${COMP_WORDS[i]} "* ]] ||
+            [[ " {first_word} " == *" ${COMP_WORDS[i]} "* && $i -eq $((COMP_CWORD - 1)) ]]; then
+            takes_groups=1
+        fi
+    done
+
+    if [[ "$prev" == -p || "$prev" == --profile ]]; then
+        names="$(tuckr __complete profiles 2>/dev/null)"
+    elif [[ " {options} " == *" $prev "* || (-n "$takes_groups" && "$cur" != -*) ]]; then
+        names="$(tuckr "${profile[@]}" __complete groups 2>/dev/null)"
+    else
+        _tuckr "$@"
+        return
+    fi
+
+    local prefix=""
+    [[ "$cur" == *,* ]] && prefix="${cur%,*},"
+    local IFS=$'
+'
+    COMPREPLY=($(compgen -P "$prefix" -W "$names" -- "${cur##*,}"))
+}
+
+complete -F _tuckr_dotfiles -o bashdefault -o default tuckr
+"#;
+
+const ZSH_SCRIPT: &str = r#"
+# group and profile names come from the dotfiles
+functions[_tuckr_clap]=$functions[_tuckr]
+_tuckr() {
+    local -a profile names
+    local i takes_groups
+    for ((i = 2; i < CURRENT; i++)); do
+        case "${words[i]}" in
+            -p | --profile) profile=(--profile "${words[i + 1]}") ;;
+            --profile=*) profile=("${words[i]}") ;;
+        esac
+        if [[ " {every_word} " == *" ${words[i]} "* ]] ||
+            [[ " {first_word} " == *" ${words[i]} "* && $i -eq $((CURRENT - 1)) ]]; then
+            takes_groups=1
+        fi
+    done
+
+    if [[ "${words[CURRENT - 1]}" == (-p|--profile) ]]; then
+        names=(${(f)"$(tuckr __complete profiles 2>/dev/null)"})
+    elif [[ " {options} " == *" ${words[CURRENT - 1]} "* ]] ||
+        [[ -n "$takes_groups" && "${words[CURRENT]}" != -* ]]; then
+        names=(${(f)"$(tuckr $profile __complete groups 2>/dev/null)"})
+    else
+        _tuckr_clap "$@"
+        return
+    fi
+
+    compset -P '*,'
+    compadd -a names
+}
+
+if [ "$funcstack[1]" = "_tuckr" ]; then
+    _tuckr "$@"
+else
+    compdef _tuckr tuckr
+fi
+"#;
+
+const FISH_SCRIPT: &str = r#"
+# group and profile names come from the dotfiles
+function __tuckr_dotfiles
+    set -l words (commandline -opc)
+    set -l profile
+    for i in (seq 2 (count $words))
+        switch $words[$i]
+            case -p --profile
+                set profile --profile $words[(math $i + 1)]
+            case '--profile=*'
+                set profile $words[$i]
+        end
+    end
+
+    set -l prefix (string match -r '.*,' -- (commandline -ct))
+    for name in (tuckr $profile __complete $argv 2>/dev/null)
+        echo $prefix$name
+    end
+end
+
+complete -c tuckr -n '__fish_seen_subcommand_from {every_word} {first_word}' -f -a '(__tuckr_dotfiles groups)'
+complete -c tuckr -s p -l profile -x -a '(__tuckr_dotfiles profiles)'
+"#;
+
+/// Returns the groups in the dotfiles, along with the base groups of conditional groups and the bundles
+fn group_names(profile: Option<String>) -> BTreeSet<String> {
+    let mut groups: BTreeSet<String> = config::get()
+        .bundles
+        .keys()
+        .map(|bundle| format!("@{bundle}"))
+        .collect();
+    for dtype in [
+        DotfileType::Configs,
+        DotfileType::Hooks,
+        DotfileType::Secrets,
+    ] {
+        let entries = dotfiles::get_group_dirs(profile.clone(), dtype)
+            .into_iter()
+            .flat_map(fs::read_dir)
+            .flatten();
+
+        for entry in entries.flatten().filter(|entry| entry.path().is_dir()) {
+            let group = entry.file_name().to_string_lossy().into_owned();
+            groups.insert(dotfiles::group_without_target(&group).to_string());
+            groups.insert(group);
+        }
+    }
+
+    groups
+}
+
+fn is_group_arg(arg: &Arg) -> bool {
+    matches!(arg.get_id().as_str(), "group" | "groups")
+        || arg
+            .get_value_names()
+            .is_some_and(|names| names.iter().any(|name| name == "group"))
+}
+
+/// The arguments that are completed with group names
+#[derive(Debug, Default, PartialEq)]
+struct GroupArgs {
+    /// commands whose positional arguments are all groups
+    every_word: BTreeSet<String>,
+    /// commands whose first positional argument is a group
+    first_word: BTreeSet<String>,
+    /// options that take groups
+    options: BTreeSet<String>,
+}
+
+impl GroupArgs {
+    fn find(cmd: &Command, found: &mut GroupArgs) {
+        for arg in cmd.get_arguments().filter(|arg| is_group_arg(arg)) {
+            if !arg.is_positional() {
+                found
+                    .options
+                    .extend(arg.get_long().map(|long| format!("--{long}")));
+                found
+                    .options
+                    .extend(arg.get_short().map(|short| format!("-{short}")));
+                continue;
+            }
+
+            let names = std::iter::once(cmd.get_name()).chain(cmd.get_visible_aliases());
+            let takes_many = matches!(arg.get_action(), ArgAction::Append)
+                || arg.get_num_args().is_some_and(|num| num.max_values() > 1);
+            match (takes_many, arg.get_index()) {
+                (true, _) => found.every_word.extend(names.map(String::from)),
+                (false, Some(1)) => found.first_word.extend(names.map(String::from)),
+                _ => (),
+            }
+        }
+
+        for sub in cmd.get_subcommands() {
+            GroupArgs::find(sub, found);
+        }
+    }
+
+    /// Fills in the arguments that take groups in the script
+    fn fill_in(&self, script: &str) -> String {
+        let join = |names: &BTreeSet<String>| names.iter().cloned().collect::<Vec<_>>().join(" ");
+        script
+            .replace("{every_word}", &join(&self.every_word))
+            .replace("{first_word}", &join(&self.first_word))
+            .replace("{options}", &join(&self.options))
+    }
+}
+
+/// Returns the completion script for the shell, with group and profile names for bash, zsh and fish
+fn script(mut cli: Command, shell: Shell) -> String {
+    // propagates the global options to the subcommands
+    cli.build();
+
+    let mut generated = Vec::new();
+    clap_complete::generate(shell, &mut cli, "tuckr", &mut generated);
+    let mut script = String::from_utf8_lossy(&generated).into_owned();
+
+    let mut group_args = GroupArgs::default();
+    GroupArgs::find(&cli, &mut group_args);
+    let dotfiles = match shell {
+        Shell::Bash => BASH_SCRIPT,
+        Shell::Zsh => {
+            // the function is only registered once it's wrapped
+            if let Some(registration) = script.rfind("if [ \"$funcstack[1]\" = \"_tuckr\" ]") {
+                script.truncate(registration);
+            }
+            ZSH_SCRIPT
+        }
+        Shell::Fish => FISH_SCRIPT,
+        _ => return script,
+    };
+
+    script + &group_args.fill_in(dotfiles)
+}
+
+/// Prints the completion script for a shell
+pub fn completions_cmd(cli: Command, shell: Shell) -> Result<(), TuckrError> {
+    print!("{}", script(cli, shell));
+    Ok(())
+}
+
+/// Prints the group or profile names, one per line, called by the completion scripts
+pub fn complete_cmd(profile: Option<String>, names: Names) -> Result<(), TuckrError> {
+    let names = match names {
+        Names::Groups => group_names(profile),
+        Names::Profiles => fileops::get_profiles().into_iter().collect(),
+    };
+
+    for name in names {
+        println!("{name}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cli() -> Command {
+        let mut cli = Command::new("tuckr")
+            .arg(Arg::new("profile").short('p').long("profile").global(true))
+            .subcommand(
+                Command::new("add")
+                    .visible_alias("a")
+                    .about("Deploy dotfiles")
+                    .arg(Arg::new("groups").value_name("group").num_args(1..))
+                    .arg(
+                        Arg::new("exclude")
+                            .short('e')
+                            .long("exclude")
+                            .value_name("group"),
+                    ),
+            )
+            .subcommand(
+                Command::new("edit")
+                    .arg(Arg::new("group"))
+                    .arg(Arg::new("file")),
+            );
+        cli.build();
+        cli
+    }
+
+    #[test]
+    fn find_group_args() {
+        let mut found = GroupArgs::default();
+        GroupArgs::find(&cli(), &mut found);
+        assert_eq!(
+            found,
+            GroupArgs {
+                every_word: BTreeSet::from(["a".into(), "add".into()]),
+                first_word: BTreeSet::from(["edit".into()]),
+                options: BTreeSet::from(["--exclude".into(), "-e".into()]),
+            }
+        );
+
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = script(cli(), shell);
+            assert!(script.contains(" __complete "), "{shell}");
+            assert!(!script.contains("{every_word}"), "{shell}");
+        }
+        // zsh registers the function once it's wrapped
+        assert_eq!(
+            script(cli(), Shell::Zsh).matches("compdef _tuckr").count(),
+            1
+        );
+        assert!(!script(cli(), Shell::PowerShell).contains("__complete"));
+    }
+
+    #[test]
+    fn complete_group_names() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        for group in ["Configs/zsh", "Configs/nvim_linux", "Hooks/rust"] {
+            fs::create_dir_all(dotfiles_dir.join(group)).unwrap();
+        }
+
+        assert_eq!(
+            group_names(None),
+            BTreeSet::from(["nvim", "nvim_linux", "rust", "zsh"].map(String::from))
+        );
+        // the profile has no dotfiles of its own but shares the hooks
+        assert_eq!(
+            group_names(Some("zsh".into())),
+            BTreeSet::from(["rust".to_string()])
+        );
+
+        fs::remove_dir_all(dotfiles_dir).unwrap();
+    }
+}
@@ -0,0 +1,811 @@
+//! Loads the user's configuration
+//!
+//! The configuration is read from $TUCKR_HOME/tuckr.toml, or from the system's config directory
+//! (e.g. ~/.config/tuckr.toml) when $TUCKR_HOME is not set.
+//! Environment variables and command line arguments always take precedence over it.
+
+use crate::dotfiles;
+use crate::error;
+use crate::errors;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const CONFIG_FILE: &str = "tuckr.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// directory that dotfiles are deployed to, --target and $TUCKR_TARGET override it
+    pub target: Option<PathBuf>,
+
+    /// groups that are ignored unless they're explicitly requested
+    pub exclude: Vec<String>,
+
+    /// which group's file is deployed when groups with different base groups deploy the same file,
+    /// highest priority first, conditional groups are ranked along with their base group
+    pub priority: Vec<String>,
+
+    /// program used to run hooks, hooks are executed directly if it's not set
+    pub hook_shell: Option<String>,
+
+    /// environment variables that hooks are run with, e.g. `EDITOR = "nvim"`
+    pub hook_env: BTreeMap<String, String>,
+
+    /// how many times a hook that failed is run again before giving up, e.g. for flaky downloads
+    pub hook_retries: u32,
+
+    /// program used to deploy what needs root, sudo or doas if it's not set
+    pub escalate_with: Option<String>,
+
+    /// merge tool used by `tuckr resolve` when `--with` isn't given, e.g. meld, vimdiff or code
+    pub merge_tool: Option<String>,
+
+    /// file that reports of the hooks run by `tuckr set` get appended to
+    pub audit_log: Option<PathBuf>,
+
+    /// commits the changes that push, pop, encrypt, secrets rekey, add --adopt and from-stow make to the dotfiles, same as `--commit`
+    pub auto_commit: bool,
+
+    /// symlinks dotfiles with paths relative to the symlinks, same as `--relative`, so that they
+    /// keep working when the home directory is mounted somewhere else (containers, NFS, chroots)
+    pub relative_symlinks: bool,
+
+    /// asks what to do with every file that's in the way of a dotfile, same as `--interactive`
+    pub ask_on_conflict: bool,
+
+    /// how dotfiles are deployed unless a group says otherwise
+    pub mode: DeployMode,
+
+    /// never creates symlinks, every group is copied whatever its mode is,
+    /// for machines where symlinks are forbidden by policy (e.g. roaming profiles and network homes)
+    pub no_symlinks: bool,
+
+    /// $HOME is shared between operating systems, e.g. mounted on both Linux and macOS, so the
+    /// files of platform specific groups are deployed with the platform in their name
+    pub shared_home: bool,
+
+    /// keeps local statistics of how tuckr is used, they're never uploaded
+    pub stats: bool,
+
+    /// whether the output is colored, "auto" colors it when it goes to a terminal and $NO_COLOR isn't set
+    pub colors: Colors,
+
+    /// directories whose target doesn't exist are symlinked as a whole instead of file by file,
+    /// it's on unless it's set to false
+    pub fold: Option<bool>,
+
+    /// how secrets are encrypted unless a group says otherwise
+    pub secrets: SecretsConfig,
+
+    /// settings that only apply to a specific group, e.g. `[groups.nvim]`
+    pub groups: BTreeMap<String, GroupConfig>,
+
+    /// directories that groups are deployed to instead of the target, e.g. `sshd = "/etc/ssh"`
+    pub targets: BTreeMap<String, PathBuf>,
+
+    /// groups that are deployed together when `@<bundle>` is used instead of a group,
+    /// e.g. `desktop = ["sway", "waybar", "kitty"]`, bundles can contain other bundles
+    pub bundles: BTreeMap<String, Vec<String>>,
+
+    /// how long the files backed up when overriding conflicts are kept
+    pub backups: BackupsConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecretsConfig {
+    pub backend: SecretsBackend,
+    /// look the password up in the OS keyring before asking for it
+    pub keyring: bool,
+    pub age: AgeConfig,
+    pub gpg: GpgConfig,
+}
+
+/// Backup sets that break any of the limits are removed, oldest first, the most recent one is always kept
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupsConfig {
+    /// how many backup sets are kept
+    pub keep_last: Option<usize>,
+    /// seconds that backup sets are kept for, written as e.g. "90d" or "1y"
+    #[serde(deserialize_with = "deserialize_age")]
+    pub max_age: Option<u64>,
+    /// bytes that all backup sets can take up together, written as e.g. "500M" or "2G"
+    #[serde(deserialize_with = "deserialize_size")]
+    pub max_size: Option<u64>,
+}
+
+impl BackupsConfig {
+    /// Returns true if backups are kept forever
+    pub fn is_unlimited(&self) -> bool {
+        self.keep_last.is_none() && self.max_age.is_none() && self.max_size.is_none()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AgeConfig {
+    /// public keys that secrets are encrypted to, every machine can have its own
+    pub recipients: Vec<String>,
+    /// file with this machine's private key, defaults to ~/.config/age/keys.txt
+    pub identity: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GpgConfig {
+    /// key ids or emails that secrets are encrypted to
+    pub recipients: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretsBackend {
+    /// XChaCha20Poly1305 with a key derived from a password
+    #[default]
+    Password,
+    Age,
+    Gpg,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GroupConfig {
+    pub mode: Option<DeployMode>,
+
+    /// backend the group's secrets are encrypted with
+    pub secrets: Option<SecretsBackend>,
+
+    /// machines the group is deployed to, it's deployed everywhere if empty
+    pub hosts: Vec<String>,
+
+    /// what has to be in place before `tuckr set` deploys the group
+    pub preflight: Option<Preflight>,
+
+    /// environment variables that have to be set for `add '*'` to deploy the group, e.g. WAYLAND_DISPLAY
+    pub requires_env: Vec<String>,
+
+    /// programs that have to be in $PATH for `add '*'` to deploy the group, or `wayland`, `x11` and `systemd`
+    pub requires: Vec<String>,
+
+    /// the dotfiles are the source of truth (e.g. fonts and wallpapers), so whatever is deployed
+    /// in their place is overwritten instead of being reported as a conflict
+    pub asset: Option<bool>,
+
+    /// whether the group's directories are symlinked as a whole when their target doesn't exist
+    pub fold: Option<bool>,
+
+    /// permissions of the deployed files by their path in the group, glob patterns such as "bin/*" can be used
+    #[serde(deserialize_with = "deserialize_file_modes")]
+    pub permissions: BTreeMap<String, FileMode>,
+
+    /// program used to run the group's hooks instead of the global hook_shell, e.g. "pwsh"
+    pub hook_shell: Option<String>,
+
+    /// environment variables that the group's hooks are run with on top of the global hook_env
+    pub hook_env: BTreeMap<String, String>,
+
+    /// how many times the group's hooks are run again when they fail by their file name, taking
+    /// precedence over the global hook_retries, glob patterns such as "pre*" can be used
+    pub hook_retries: BTreeMap<String, u32>,
+}
+
+/// Permissions a deployed file is given
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileMode {
+    /// an octal mode, e.g. "600"
+    Exact(u32),
+    /// "+x" makes the file executable by whoever can read it, "-x" by nobody
+    Executable(bool),
+}
+
+impl FileMode {
+    /// Returns the mode a file with `mode` should have
+    pub fn apply(self, mode: u32) -> u32 {
+        match self {
+            FileMode::Exact(exact) => (mode & !0o7777) | exact,
+            FileMode::Executable(true) => mode | ((mode & 0o444) >> 2),
+            FileMode::Executable(false) => mode & !0o111,
+        }
+    }
+}
+
+/// Parses the permissions of a file, an octal mode such as "600" or "0755", or "+x" and "-x"
+pub fn parse_file_mode(mode: &str) -> Result<FileMode, String> {
+    match mode.trim() {
+        "+x" => Ok(FileMode::Executable(true)),
+        "-x" => Ok(FileMode::Executable(false)),
+        octal => u32::from_str_radix(octal, 8)
+            .ok()
+            .filter(|mode| *mode <= 0o7777)
+            .map(FileMode::Exact)
+            .ok_or_else(|| {
+                format!("invalid permissions `{mode}`, expected e.g. \"600\" or \"+x\"")
+            }),
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Preflight {
+    /// bytes that have to be free where the dotfiles are deployed, written as e.g. "500M" or "2G"
+    #[serde(deserialize_with = "deserialize_size")]
+    pub free_space: Option<u64>,
+    /// programs that have to be in $PATH
+    pub commands: Vec<String>,
+    /// `host:port` addresses that have to accept connections
+    pub reachable: Vec<String>,
+    /// paths that have to be writable, relative paths are relative to the target directory
+    pub writable: Vec<PathBuf>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeployMode {
+    #[default]
+    Symlink,
+    /// for systems that can't use symlinks, e.g. NFS homes or Windows without developer mode
+    Copy,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Colors {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl Colors {
+    /// Makes the output follow the setting, "auto" leaves it to owo-colors' detection
+    pub fn apply(self) {
+        match self {
+            Colors::Auto => owo_colors::unset_override(),
+            Colors::Always => owo_colors::set_override(true),
+            Colors::Never => owo_colors::set_override(false),
+        }
+    }
+}
+
+/// Parses a size in bytes with an optional binary unit, e.g. "500M" is 500 MiB
+pub fn parse_size(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let digits = size
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(digits);
+
+    let shift = match unit.trim().trim_end_matches("iB").trim_end_matches('B') {
+        "" => 0,
+        "K" | "k" => 10,
+        "M" | "m" => 20,
+        "G" | "g" => 30,
+        "T" | "t" => 40,
+        _ => {
+            return Err(format!(
+                "invalid size `{size}`, expected e.g. \"500M\" or \"2G\""
+            ));
+        }
+    };
+
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(1 << shift))
+        .ok_or_else(|| format!("invalid size `{size}`, expected e.g. \"500M\" or \"2G\""))
+}
+
+/// Parses an age in seconds with an optional unit, e.g. "90d" is 90 days
+pub fn parse_age(age: &str) -> Result<u64, String> {
+    let age = age.trim();
+    let digits = age.find(|c: char| !c.is_ascii_digit()).unwrap_or(age.len());
+    let (number, unit) = age.split_at(digits);
+
+    let seconds = match unit.trim() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        "y" => 365 * 24 * 60 * 60,
+        _ => {
+            return Err(format!(
+                "invalid age `{age}`, expected e.g. \"90d\" or \"1y\""
+            ));
+        }
+    };
+
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(seconds))
+        .ok_or_else(|| format!("invalid age `{age}`, expected e.g. \"90d\" or \"1y\""))
+}
+
+/// Formats an age in seconds with the largest unit that fits it exactly, e.g. 86400 is "1d"
+pub fn format_age(age: u64) -> String {
+    let units = [
+        ("y", 365 * 24 * 60 * 60),
+        ("w", 7 * 24 * 60 * 60),
+        ("d", 24 * 60 * 60),
+        ("h", 60 * 60),
+        ("m", 60),
+    ];
+
+    match units
+        .into_iter()
+        .find(|(_, seconds)| age > 0 && age.is_multiple_of(*seconds))
+    {
+        Some((unit, seconds)) => format!("{}{unit}", age / seconds),
+        None => format!("{age}s"),
+    }
+}
+
+fn deserialize_age<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u64>, D::Error> {
+    let age = String::deserialize(deserializer)?;
+    parse_age(&age).map(Some).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_size<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u64>, D::Error> {
+    let size = String::deserialize(deserializer)?;
+    parse_size(&size)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+fn deserialize_file_modes<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, FileMode>, D::Error> {
+    BTreeMap::<String, String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(file, mode)| Ok((file, parse_file_mode(&mode)?)))
+        .collect::<Result<_, String>>()
+        .map_err(serde::de::Error::custom)
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Returns the path to tuckr.toml
+pub fn get_config_path() -> Option<PathBuf> {
+    match std::env::var("TUCKR_HOME") {
+        Ok(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join(CONFIG_FILE)),
+        _ => Some(dirs::config_dir()?.join(CONFIG_FILE)),
+    }
+}
+
+/// Expands a leading `~` to the user's home directory
+pub fn expand_home(path: PathBuf) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(path), Some(home)) => home.join(path),
+        _ => path,
+    }
+}
+
+impl Config {
+    /// Returns how a group should be deployed, conditional groups fall back to their base group's settings
+    pub fn deploy_mode(&self, group: &str) -> DeployMode {
+        if self.no_symlinks {
+            return DeployMode::Copy;
+        }
+
+        [group, dotfiles::group_without_target(group)]
+            .into_iter()
+            .find_map(|group| self.groups.get(group)?.mode)
+            .unwrap_or(self.mode)
+    }
+
+    /// Returns the backend a group's secrets are encrypted with, conditional groups fall back to their base group's settings
+    pub fn secrets_backend(&self, group: &str) -> SecretsBackend {
+        [group, dotfiles::group_without_target(group)]
+            .into_iter()
+            .find_map(|group| self.groups.get(group)?.secrets)
+            .unwrap_or(self.secrets.backend)
+    }
+
+    /// Returns the directory the group is deployed to if it has its own, conditional groups fall back to their base group's
+    pub fn group_target(&self, group: &str) -> Option<&PathBuf> {
+        [group, dotfiles::group_without_target(group)]
+            .into_iter()
+            .find_map(|group| self.targets.get(group))
+    }
+
+    /// Returns true if the group is an asset group, conditional groups fall back to their base group's settings
+    pub fn is_asset(&self, group: &str) -> bool {
+        [group, dotfiles::group_without_target(group)]
+            .into_iter()
+            .find_map(|group| self.groups.get(group)?.asset)
+            .unwrap_or_default()
+    }
+
+    /// Returns true if the group's directories are symlinked as a whole when their target doesn't exist,
+    /// conditional groups fall back to their base group's settings
+    pub fn folds(&self, group: &str) -> bool {
+        [group, dotfiles::group_without_target(group)]
+            .into_iter()
+            .find_map(|group| self.groups.get(group)?.fold)
+            .or(self.fold)
+            .unwrap_or(true)
+    }
+
+    /// Returns the permissions a group's file should be deployed with, `file` is its path in the group,
+    /// conditional groups fall back to their base group's settings
+    ///
+    /// A file's own entry takes precedence over glob patterns, of which the longest one that matches wins
+    pub fn file_mode(&self, group: &str, file: &Path) -> Option<FileMode> {
+        let file = file.to_string_lossy().replace('\\', "/");
+        [group, dotfiles::group_without_target(group)]
+            .into_iter()
+            .find_map(|group| {
+                let permissions = &self.groups.get(group)?.permissions;
+                permissions.get(&file).copied().or_else(|| {
+                    permissions
+                        .iter()
+                        .filter(|(pattern, _)| dotfiles::glob_matches(pattern, &file))
+                        .max_by_key(|(pattern, _)| pattern.len())
+                        .map(|(_, mode)| *mode)
+                })
+            })
+    }
+
+    /// Returns the program a group's hooks are run with, conditional groups fall back to their base group's
+    /// and then to the global hook_shell
+    pub fn hook_shell(&self, group: &str) -> Option<&str> {
+        [group, dotfiles::group_without_target(group)]
+            .into_iter()
+            .find_map(|group| self.groups.get(group)?.hook_shell.as_deref())
+            .or(self.hook_shell.as_deref())
+    }
+
+    /// Returns the environment variables a group's hooks are run with,
+    /// a conditional group's variables take precedence over its base group's and those over the global ones
+    pub fn hook_env(&self, group: &str) -> BTreeMap<&str, &str> {
+        let group_envs = [dotfiles::group_without_target(group), group]
+            .into_iter()
+            .filter_map(|group| self.groups.get(group))
+            .map(|group| &group.hook_env);
+
+        std::iter::once(&self.hook_env)
+            .chain(group_envs)
+            .flatten()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect()
+    }
+
+    /// Returns how many times a group's hook is run again when it fails, `hook` is its file name,
+    /// conditional groups fall back to their base group's settings and then to the global hook_retries
+    ///
+    /// A hook's own entry takes precedence over glob patterns, of which the longest one that matches wins
+    pub fn hook_retries(&self, group: &str, hook: &str) -> u32 {
+        [group, dotfiles::group_without_target(group)]
+            .into_iter()
+            .find_map(|group| {
+                let retries = &self.groups.get(group)?.hook_retries;
+                retries.get(hook).copied().or_else(|| {
+                    retries
+                        .iter()
+                        .filter(|(pattern, _)| dotfiles::glob_matches(pattern, hook))
+                        .max_by_key(|(pattern, _)| pattern.len())
+                        .map(|(_, retries)| *retries)
+                })
+            })
+            .unwrap_or(self.hook_retries)
+    }
+
+    /// Replaces the bundles in `groups`, e.g. `@desktop`, with the groups they contain
+    ///
+    /// Groups are only kept once, in the order they first appear in
+    pub fn expand_bundles(&self, groups: &[String]) -> Result<Vec<String>, errors::Error> {
+        let mut expanded = Vec::new();
+        self.expand_bundles_into(groups, &mut Vec::new(), &mut expanded)?;
+        Ok(expanded)
+    }
+
+    /// `expanding` are the bundles whose groups are being expanded, so that bundles that contain
+    /// themselves are caught
+    fn expand_bundles_into<'a>(
+        &'a self,
+        groups: &'a [String],
+        expanding: &mut Vec<&'a str>,
+        expanded: &mut Vec<String>,
+    ) -> Result<(), errors::Error> {
+        for group in groups {
+            let Some(bundle) = group.strip_prefix('@') else {
+                if !expanded.contains(group) {
+                    expanded.push(group.clone());
+                }
+                continue;
+            };
+
+            let Some(members) = self.bundles.get(bundle) else {
+                return Err(error!("no_bundle", bundle = bundle));
+            };
+
+            if expanding.contains(&bundle) {
+                return Err(format!("bundle `{bundle}` contains itself").into());
+            }
+
+            expanding.push(bundle);
+            self.expand_bundles_into(members, expanding, expanded)?;
+            expanding.pop();
+        }
+
+        Ok(())
+    }
+
+    fn parse(config: &str) -> Result<Self, String> {
+        let mut config: Config = toml::from_str(config).map_err(|e| e.to_string())?;
+        config.target = config.target.map(expand_home);
+        config.audit_log = config.audit_log.map(expand_home);
+        config.secrets.age.identity = config.secrets.age.identity.map(expand_home);
+        for target in config.targets.values_mut() {
+            *target = expand_home(std::mem::take(target));
+        }
+        for preflight in config
+            .groups
+            .values_mut()
+            .filter_map(|g| g.preflight.as_mut())
+        {
+            preflight.writable = preflight.writable.drain(..).map(expand_home).collect();
+        }
+
+        // bundles that contain themselves or bundles that don't exist are caught before they're used
+        for bundle in config.bundles.keys() {
+            config.expand_bundles(&[format!("@{bundle}")])?;
+        }
+
+        Ok(config)
+    }
+}
+
+/// Loads the configuration file, a missing file is the same as an empty configuration
+///
+/// Must be called before anything calls `get()` otherwise the default configuration is used
+pub fn load() -> Result<(), errors::Error> {
+    let Some(path) = get_config_path() else {
+        return Ok(());
+    };
+
+    let config = match fs::read_to_string(&path) {
+        Ok(config) => Config::parse(&config)
+            .map_err(|e| error!("invalid_config", file = path.display(), err = e))?,
+        Err(_) => Config::default(),
+    };
+
+    _ = CONFIG.set(config);
+    Ok(())
+}
+
+/// Returns the loaded configuration
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// Adds the groups excluded by the configuration to `exclude`
+///
+/// Groups that were explicitly requested in `groups` are never excluded by the configuration
+pub fn with_excluded_groups(groups: &[String], exclude: &[String]) -> Vec<String> {
+    let mut excluded = exclude.to_vec();
+    excluded.extend(
+        get()
+            .exclude
+            .iter()
+            .filter(|pattern| {
+                !exclude.contains(pattern)
+                    && !groups
+                        .iter()
+                        .any(|group| dotfiles::is_excluded(group, std::slice::from_ref(pattern)))
+            })
+            .cloned(),
+    );
+    excluded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        let config = Config::parse(
+            r#"
+            target = "~/sandbox"
+            exclude = ["work", "gaming"]
+            hook_shell = "bash"
+            escalate_with = "doas"
+            auto_commit = true
+            hook_retries = 1
+
+            [secrets]
+            backend = "age"
+
+            [secrets.age]
+            recipients = ["age1laptop", "age1desktop"]
+            identity = "~/.age/keys.txt"
+
+            [groups.nvim]
+            mode = "copy"
+
+            [groups.work]
+            secrets = "gpg"
+
+            [groups.fonts]
+            asset = true
+            fold = false
+
+            [groups.ssh.permissions]
+            ".ssh/config" = "600"
+            ".ssh/*" = "644"
+            "bin/*" = "+x"
+
+            [hook_env]
+            EDITOR = "nvim"
+            THEME = "dark"
+
+            [groups.sway]
+            hook_shell = "zsh"
+            requires_env = ["WAYLAND_DISPLAY"]
+            requires = ["systemd", "swaymsg"]
+
+            [groups.sway.hook_env]
+            THEME = "light"
+
+            [groups.rust.hook_retries]
+            "pre*" = 2
+            "pre_rustup.sh" = 5
+
+            [groups.sway_linux.hook_env]
+            DISPLAY = ":0"
+
+            [targets]
+            sshd = "/etc/ssh"
+            fonts = "~/.local/share/fonts"
+
+            [groups.rust.preflight]
+            free_space = "2G"
+            commands = ["curl"]
+            writable = ["~/.cargo"]
+
+            [backups]
+            keep_last = 20
+            max_age = "90d"
+
+            [bundles]
+            desktop = ["sway", "waybar", "@terminal"]
+            terminal = ["kitty", "zsh", "sway"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.target,
+            Some(dirs::home_dir().unwrap().join("sandbox"))
+        );
+        assert_eq!(config.exclude, ["work", "gaming"]);
+        assert_eq!(config.hook_shell.as_deref(), Some("bash"));
+        assert_eq!(config.hook_shell("sway_linux"), Some("zsh"));
+        assert_eq!(config.hook_shell("nvim"), Some("bash"));
+        assert_eq!(
+            config.hook_env("sway_linux"),
+            BTreeMap::from([("DISPLAY", ":0"), ("EDITOR", "nvim"), ("THEME", "light")])
+        );
+        assert_eq!(
+            config.hook_env("nvim"),
+            BTreeMap::from([("EDITOR", "nvim"), ("THEME", "dark")])
+        );
+        assert_eq!(config.hook_retries("rust_linux", "pre_rustup.sh"), 5);
+        assert_eq!(config.hook_retries("rust", "pre_cargo.sh"), 2);
+        assert_eq!(config.hook_retries("rust", "post.sh"), 1);
+        assert_eq!(config.hook_retries("nvim", "pre.sh"), 1);
+        assert_eq!(config.escalate_with.as_deref(), Some("doas"));
+        assert!(config.auto_commit);
+        assert_eq!(config.deploy_mode("nvim"), DeployMode::Copy);
+        assert_eq!(config.deploy_mode("nvim_linux"), DeployMode::Copy);
+        assert_eq!(config.deploy_mode("zsh"), DeployMode::Symlink);
+        assert_eq!(config.secrets.age.recipients, ["age1laptop", "age1desktop"]);
+        assert_eq!(
+            config.secrets.age.identity,
+            Some(dirs::home_dir().unwrap().join(".age").join("keys.txt"))
+        );
+        assert_eq!(config.secrets_backend("work_linux"), SecretsBackend::Gpg);
+        assert_eq!(config.secrets_backend("ssh"), SecretsBackend::Age);
+        assert!(config.is_asset("fonts_linux"));
+        assert!(!config.is_asset("nvim"));
+        assert!(!config.folds("fonts_linux"));
+        assert!(config.folds("nvim"));
+        assert_eq!(
+            config.file_mode("ssh_linux", Path::new(".ssh/config")),
+            Some(FileMode::Exact(0o600))
+        );
+        assert_eq!(
+            config.file_mode("ssh", Path::new(".ssh/known_hosts")),
+            Some(FileMode::Exact(0o644))
+        );
+        assert_eq!(
+            config.file_mode("ssh", Path::new("bin/tunnel")),
+            Some(FileMode::Executable(true))
+        );
+        assert_eq!(config.file_mode("ssh", Path::new(".bashrc")), None);
+        assert_eq!(FileMode::Executable(true).apply(0o100640), 0o100750);
+        assert_eq!(FileMode::Executable(false).apply(0o755), 0o644);
+        assert_eq!(FileMode::Exact(0o600).apply(0o100644), 0o100600);
+        assert_eq!(
+            config.group_target("sshd_linux"),
+            Some(&PathBuf::from("/etc/ssh"))
+        );
+        assert_eq!(
+            config.group_target("fonts"),
+            Some(&dirs::home_dir().unwrap().join(".local/share/fonts"))
+        );
+        assert_eq!(config.group_target("nvim"), None);
+        assert_eq!(config.backups.keep_last, Some(20));
+        assert_eq!(config.backups.max_age, Some(90 * 24 * 60 * 60));
+        assert_eq!(config.backups.max_size, None);
+        assert_eq!(
+            config
+                .expand_bundles(&["nvim".into(), "@desktop".into()])
+                .unwrap(),
+            ["nvim", "sway", "waybar", "kitty", "zsh"]
+        );
+        assert!(config.expand_bundles(&["@laptop".into()]).is_err());
+
+        assert_eq!(config.groups["sway"].requires_env, ["WAYLAND_DISPLAY"]);
+        assert_eq!(config.groups["sway"].requires, ["systemd", "swaymsg"]);
+
+        let preflight = config.groups["rust"].preflight.as_ref().unwrap();
+        assert_eq!(preflight.free_space, Some(2 << 30));
+        assert_eq!(preflight.commands, ["curl"]);
+        assert_eq!(
+            preflight.writable,
+            [dirs::home_dir().unwrap().join(".cargo")]
+        );
+
+        let config = Config::parse("").unwrap();
+        assert!(config.target.is_none() && config.exclude.is_empty());
+        assert_eq!(config.secrets_backend("ssh"), SecretsBackend::Password);
+        assert!(config.backups.is_unlimited());
+
+        assert!(Config::parse("targte = \"/tmp\"").is_err());
+        assert!(Config::parse("[groups.rust.preflight]\nfree_space = \"2X\"").is_err());
+        assert!(Config::parse("[groups.ssh.permissions]\n\".ssh/config\" = \"rw\"").is_err());
+        assert!(Config::parse("[bundles]\na = [\"@b\"]\nb = [\"nvim\", \"@a\"]").is_err());
+
+        let config = Config::parse("no_symlinks = true\n[groups.zsh]\nmode = \"symlink\"").unwrap();
+        assert_eq!(config.deploy_mode("zsh"), DeployMode::Copy);
+    }
+
+    #[test]
+    fn parse_sizes() {
+        assert_eq!(parse_size("1024"), Ok(1024));
+        assert_eq!(parse_size("500M"), Ok(500 << 20));
+        assert_eq!(parse_size("2 GiB"), Ok(2 << 30));
+        assert_eq!(parse_size("1k"), Ok(1024));
+        assert!(parse_size("").is_err());
+        assert!(parse_size("1.5G").is_err());
+        assert!(parse_size("99999999999T").is_err());
+    }
+
+    #[test]
+    fn parse_ages() {
+        assert_eq!(parse_age("60"), Ok(60));
+        assert_eq!(parse_age("12h"), Ok(12 * 60 * 60));
+        assert_eq!(parse_age("90d"), Ok(90 * 24 * 60 * 60));
+        assert_eq!(parse_age("2 w"), Ok(14 * 24 * 60 * 60));
+        assert!(parse_age("").is_err());
+        assert!(parse_age("3 months").is_err());
+        assert_eq!(parse_age("30m"), Ok(30 * 60));
+
+        assert_eq!(format_age(0), "0s");
+        assert_eq!(format_age(90), "90s");
+        assert_eq!(format_age(30 * 60), "30m");
+        assert_eq!(format_age(36 * 60 * 60), "36h");
+        assert_eq!(format_age(14 * 24 * 60 * 60), "2w");
+    }
+}
@@ -0,0 +1,447 @@
+//! Keeps the dotfiles up to date in the background
+//!
+//! `tuckr daemon --auto-update <interval>` fetches the dotfiles every interval and fast-forwards
+//! them to the newest commit upstream. The deployed groups that changed are added again, unless
+//! that would override conflicting files: those are kept pending in the state and a desktop
+//! notification says how to resolve them, after which they're added on the next update.
+//!
+//! With `--grace-period`, commits are only applied once they're older than it so that a bad
+//! commit can be reverted before it reaches every machine.
+//!
+//! With `--run-hooks` the groups are set up with `tuckr set` instead. The daemon is the outer tuckr
+//! of the tuckrs it runs (see `nested`), which is how it learns about the hooks that failed.
+
+use crate::audit::{HookRun, RunReport};
+use crate::config;
+use crate::dotfiles;
+use crate::error;
+use crate::errors::{self, TuckrError};
+use crate::history;
+use crate::lock;
+use crate::nested;
+use crate::state;
+use crate::symlinks;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::{Stderr, Stdout};
+use rust_i18n::t;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Changes the groups that are waiting to be deployed, they're kept in the state so that they
+/// aren't forgotten when the daemon is restarted
+///
+/// The caller has to hold the lock so that no other tuckr saves the state in the meantime
+fn update_pending(
+    profile: &Option<String>,
+    change: impl FnOnce(&mut BTreeSet<String>),
+) -> Result<BTreeSet<String>, String> {
+    // the state is loaded every time since the tuckr that adds the groups saves it as well
+    let mut state = state::State::load(profile.clone())?;
+    change(&mut state.pending_updates);
+    state.save()?;
+    Ok(state.pending_updates)
+}
+
+#[derive(Clone, Copy)]
+enum Urgency {
+    Normal,
+    /// something that needs the user to step in
+    Critical,
+}
+
+/// What the user was already notified about, so that it isn't repeated on every update
+#[derive(Default)]
+struct Notified {
+    /// groups that can't be deployed because of conflicts
+    conflicts: BTreeSet<String>,
+    /// groups that failed to be deployed
+    failed: BTreeSet<String>,
+}
+
+/// Shows a desktop notification if there's a program for it, it's always printed as well
+fn notify(message: &str, urgency: Urgency) {
+    match urgency {
+        Urgency::Normal => println!("{}", message.if_supports_color(Stdout, |t| t.green())),
+        Urgency::Critical => eprintln!("{}", message.if_supports_color(Stderr, |t| t.yellow())),
+    }
+
+    let mut command = match std::env::consts::OS {
+        "macos" => {
+            let mut command = Command::new("osascript");
+            let script = format!("display notification {message:?} with title \"tuckr\"");
+            command.args(["-e", &script]);
+            command
+        }
+        "windows" => return,
+        _ => {
+            let mut command = Command::new("notify-send");
+            let urgency = match urgency {
+                Urgency::Normal => "--urgency=normal",
+                Urgency::Critical => "--urgency=critical",
+            };
+            command.args(["--app-name=tuckr", urgency, "tuckr", message]);
+            command
+        }
+    };
+
+    // there's no notification daemon on headless machines, the message was printed anyway
+    _ = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// Returns the newest commit upstream that's at least `grace_period` seconds old, None if there's none
+fn find_update(dotfiles_dir: &Path, grace_period: u64) -> Result<Option<String>, String> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    let until = format!("--until=@{}", now.saturating_sub(grace_period));
+
+    let commit = history::git(
+        dotfiles_dir,
+        &["rev-list", "-1", &until, "@{upstream}"],
+        None,
+    )?;
+    let commit = commit.trim();
+    if commit.is_empty() {
+        return Ok(None);
+    }
+
+    // nothing's new if HEAD already has the commit
+    let is_applied = history::git(
+        dotfiles_dir,
+        &["merge-base", "--is-ancestor", commit, "HEAD"],
+        None,
+    )
+    .is_ok();
+
+    Ok((!is_applied).then(|| commit.to_string()))
+}
+
+/// Runs tuckr itself, e.g. to add the groups that changed
+pub fn run_tuckr(profile: &Option<String>, dry_run: bool, args: &[&str]) -> Result<bool, String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let mut command = Command::new(exe);
+    nested::prepare_hook(&mut command);
+    dotfiles::pass_target_dir(&mut command);
+    if let Some(profile) = profile {
+        command.args(["--profile", profile]);
+    }
+    if dry_run {
+        command.arg("--dry-run");
+    }
+
+    let status = command
+        .args(args)
+        .stdin(Stdio::null())
+        .status()
+        .map_err(|e| e.to_string())?;
+    Ok(status.success())
+}
+
+/// Returns the hooks that the tuckrs run by the daemon ran since the last call
+fn take_hook_runs() -> Vec<HookRun> {
+    nested::take_operations()
+        .into_iter()
+        .flat_map(|operation| operation.hooks)
+        .collect()
+}
+
+/// Notifies about the hooks that failed, they're added to the audit log as well since the nested
+/// `tuckr set` leaves that to the daemon
+fn report_hooks(profile: &Option<String>, groups: &[String], succeeded: bool, hooks: Vec<HookRun>) {
+    let failed: Vec<_> = hooks
+        .iter()
+        .filter(|run| run.exit_code != Some(0))
+        .map(|run| {
+            let script = run.script.file_name().unwrap_or_default().to_string_lossy();
+            let exit_code = match run.exit_code {
+                Some(code) => code.to_string(),
+                None => "-".into(),
+            };
+            format!(
+                "{}/{script} ({})",
+                run.group,
+                t!("errors.exit_code_x", code = exit_code)
+            )
+        })
+        .collect();
+    if !failed.is_empty() {
+        notify(
+            &t!("warn.daemon_hooks_failed", hooks = failed.join(", ")),
+            Urgency::Critical,
+        );
+    }
+
+    if let Some(audit_log) = &config::get().audit_log {
+        let mut report = RunReport::new(profile.clone(), groups);
+        report.hooks = hooks;
+        if let Err(err) = report.finish(succeeded, audit_log) {
+            errors::print(err);
+        }
+    }
+}
+
+/// Pulls what's new upstream and deploys the changed groups that don't conflict
+fn update(
+    profile: &Option<String>,
+    dry_run: bool,
+    grace_period: u64,
+    run_hooks: bool,
+    notified: &mut Notified,
+) -> Result<(), String> {
+    let dotfiles_dir = dotfiles::get_dotfiles_path(profile.clone())?;
+
+    history::git(&dotfiles_dir, &["fetch", "--quiet"], None)?;
+    if let Some(commit) = find_update(&dotfiles_dir, grace_period)? {
+        // nothing can be deployed between checking the deployment and merging, the lock is
+        // released before the tuckrs run below take it
+        let _lock = lock::acquire(Some(None))?;
+
+        // the deployment has to be checked before pulling since files that were deleted upstream would be gone
+        let deployed = history::deployed_groups(profile.clone(), &dotfiles_dir)?;
+        let changed: BTreeSet<_> = history::changed_groups(&dotfiles_dir, "HEAD", &commit)?
+            .into_iter()
+            .filter(|group| deployed.contains(group))
+            .collect();
+
+        if dry_run {
+            let groups: Vec<_> = changed.into_iter().collect();
+            println!(
+                "{}",
+                t!(
+                    "info.daemon_would_update",
+                    commit = &commit[..7],
+                    groups = groups.join(", ")
+                )
+            );
+            return Ok(());
+        }
+
+        // local commits or changes are never merged automatically
+        if let Err(err) = history::git(&dotfiles_dir, &["merge", "--ff-only", &commit], None) {
+            notify(
+                &t!("warn.daemon_cant_fast_forward", err = err),
+                Urgency::Critical,
+            );
+            return Ok(());
+        }
+
+        notify(
+            &t!("info.daemon_updated", commit = &commit[..7]),
+            Urgency::Normal,
+        );
+        update_pending(profile, |pending| pending.extend(changed))?;
+    }
+
+    let pending = state::State::load(profile.clone())?.pending_updates;
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let conflicting = symlinks::conflicting_groups(profile.clone())
+        .map_err(|_| error!("daemon_status_failed"))?;
+    let (conflicting, groups): (BTreeSet<_>, BTreeSet<_>) = pending
+        .into_iter()
+        .partition(|group| conflicting.contains(group));
+
+    // conflicts that were resolved can be notified about again if they come back
+    notified
+        .conflicts
+        .retain(|group| conflicting.contains(group));
+    let new_conflicts: Vec<_> = conflicting
+        .difference(&notified.conflicts)
+        .cloned()
+        .collect();
+    if !new_conflicts.is_empty() {
+        let groups = new_conflicts.join(" ");
+        notify(
+            &t!(
+                "warn.daemon_conflicts",
+                groups = new_conflicts.join(", "),
+                cmd = format!("tuckr add --force {groups}")
+            ),
+            Urgency::Critical,
+        );
+        notified.conflicts.extend(new_conflicts);
+    }
+
+    if groups.is_empty() {
+        return Ok(());
+    }
+
+    let mut args = vec![if run_hooks { "set" } else { "add" }];
+    args.extend(groups.iter().map(String::as_str));
+    let deployed = run_tuckr(profile, dry_run, &args)?;
+    if run_hooks && !dry_run {
+        let groups: Vec<_> = groups.iter().cloned().collect();
+        report_hooks(profile, &groups, deployed, take_hook_runs());
+    }
+
+    if deployed {
+        notified.failed.retain(|group| !groups.contains(group));
+
+        // symlinks to files that were removed or moved upstream are removed or re-pointed
+        let mut args = vec!["status", "--fix"];
+        args.extend(groups.iter().map(String::as_str));
+        run_tuckr(profile, dry_run, &args)?;
+
+        let _lock = lock::acquire(Some(None))?;
+        update_pending(profile, |pending| {
+            pending.retain(|group| !groups.contains(group))
+        })?;
+        return Ok(());
+    }
+
+    // the groups are tried again on the next update, e.g. when another tuckr held the lock
+    let new_failures: Vec<_> = groups.difference(&notified.failed).cloned().collect();
+    if !new_failures.is_empty() {
+        let groups = new_failures.join(" ");
+        notify(
+            &t!(
+                "warn.daemon_deploy_failed",
+                groups = new_failures.join(", "),
+                cmd = format!("tuckr {} {groups}", args[0])
+            ),
+            Urgency::Critical,
+        );
+        notified.failed.extend(new_failures);
+    }
+
+    Ok(())
+}
+
+/// Updates the dotfiles every `interval` seconds until tuckr is stopped
+///
+/// grace_period: commits are only applied once they're older than this many seconds
+/// run_hooks: the groups are set up with their hooks instead of just being added
+pub fn daemon_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    interval: u64,
+    grace_period: u64,
+    run_hooks: bool,
+) -> Result<(), TuckrError> {
+    if interval == 0 {
+        errors::print(error!("invalid_interval"));
+        return Err(TuckrError::Failed);
+    }
+
+    // git must never wait for credentials that nobody is there to type in
+    // SAFETY: no other threads are running yet
+    unsafe { std::env::set_var("GIT_TERMINAL_PROMPT", "0") };
+
+    if let Err(err) = dotfiles::get_dotfiles_path(profile.clone()) {
+        errors::print(err);
+        return Err(TuckrError::CouldntFindDotfiles);
+    }
+
+    println!(
+        "{}",
+        t!(
+            "info.daemon_started",
+            interval = config::format_age(interval)
+        )
+    );
+    if grace_period > 0 {
+        println!(
+            "{}",
+            t!(
+                "info.daemon_grace_period",
+                grace_period = config::format_age(grace_period)
+            )
+        );
+    }
+
+    let mut notified = Notified::default();
+    loop {
+        if let Err(err) = update(&profile, dry_run, grace_period, run_hooks, &mut notified) {
+            errors::print(err);
+        }
+
+        thread::sleep(Duration::from_secs(interval));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn git(dir: &Path, args: &[&str], date: &str) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=tuckr", "-c", "user.email=tuckr@localhost"])
+            .args(args)
+            .env("GIT_AUTHOR_DATE", date)
+            .env("GIT_COMMITTER_DATE", date)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[test]
+    fn find_updates_past_the_grace_period() {
+        let dir = std::env::temp_dir()
+            .join("tuckr")
+            .join("daemon")
+            .join(std::process::id().to_string());
+        _ = fs::remove_dir_all(&dir);
+        let remote = dir.join("remote");
+        let local = dir.join("local");
+        fs::create_dir_all(&remote).unwrap();
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let day_ago = format!("@{} +0000", now - 24 * 60 * 60);
+        let hour_ago = format!("@{} +0000", now - 60 * 60);
+
+        git(&remote, &["init", "-q"], &day_ago);
+        git(
+            &remote,
+            &["commit", "-q", "--allow-empty", "-m", "a"],
+            &day_ago,
+        );
+        git(
+            &dir,
+            &["clone", "-q", remote.to_str().unwrap(), "local"],
+            &day_ago,
+        );
+        assert_eq!(find_update(&local, 0), Ok(None));
+
+        git(
+            &remote,
+            &["commit", "-q", "--allow-empty", "-m", "b"],
+            &day_ago,
+        );
+        git(
+            &remote,
+            &["commit", "-q", "--allow-empty", "-m", "c"],
+            &hour_ago,
+        );
+        git(&local, &["fetch", "-q"], &hour_ago);
+        let rev = |rev: &str| {
+            history::git(&remote, &["rev-parse", rev], None)
+                .unwrap()
+                .trim()
+                .to_string()
+        };
+
+        assert_eq!(find_update(&local, 0), Ok(Some(rev("HEAD"))));
+        assert_eq!(find_update(&local, 2 * 60 * 60), Ok(Some(rev("HEAD~1"))));
+        assert_eq!(find_update(&local, 2 * 24 * 60 * 60), Ok(None));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,175 @@
+//! Keeps renamed commands and flags working
+//!
+//! When a command or flag is renamed, its old name is added here instead of being kept around in
+//! the CLI. The command line is rewritten to the new name before it's parsed and a warning says
+//! what to use instead, so that scripts keep working until they're updated. With `--strict-cli`
+//! using an old name is an error, for scripts that want to find out about them right away.
+
+use crate::error;
+use crate::errors::{self, TuckrError};
+use owo_colors::OwoColorize;
+use owo_colors::Stream::Stderr;
+use rust_i18n::t;
+use std::ffi::OsString;
+
+/// A command that was renamed, e.g. a command that was moved under another one
+struct RenamedCommand {
+    old: &'static [&'static str],
+    new: &'static [&'static str],
+}
+
+/// A flag of a command that was renamed
+struct RenamedFlag {
+    /// the command the flag belongs to
+    command: &'static [&'static str],
+    old: &'static str,
+    new: &'static str,
+}
+
+const COMMANDS: &[RenamedCommand] = &[RenamedCommand {
+    old: &["restore"],
+    new: &["backups", "restore"],
+}];
+
+const FLAGS: &[RenamedFlag] = &[];
+
+/// An old name that was used, with what it was replaced with
+#[derive(Debug, PartialEq)]
+pub struct Usage {
+    old: String,
+    new: String,
+}
+
+/// Returns the index of the subcommand, global options that take a value are skipped along with it
+///
+/// valued_options: the global options that take a value, e.g. `--profile` and `-p`
+fn find_subcommand(args: &[OsString], valued_options: &[String]) -> Option<usize> {
+    let mut i = 1;
+    while let Some(arg) = args.get(i) {
+        let arg = arg.to_str()?;
+        if arg == "--" {
+            return None;
+        }
+        if !arg.starts_with('-') {
+            return Some(i);
+        }
+
+        // the value is either part of the argument, e.g. --profile=work or -pwork, or the next one
+        let takes_next = valued_options.iter().any(|option| option == arg);
+        i += if takes_next { 2 } else { 1 };
+    }
+    None
+}
+
+/// Replaces the old names in the command line with the new ones, returns what was replaced
+pub fn rewrite(args: &mut Vec<OsString>, valued_options: &[String]) -> Vec<Usage> {
+    let mut used = Vec::new();
+    let Some(start) = find_subcommand(args, valued_options) else {
+        return used;
+    };
+
+    let matches = |names: &[&str], args: &[OsString]| {
+        names.len() <= args.len() && names.iter().zip(args).all(|(name, arg)| arg == name)
+    };
+
+    for renamed in COMMANDS {
+        if matches(renamed.old, &args[start..]) {
+            args.splice(
+                start..start + renamed.old.len(),
+                renamed.new.iter().map(OsString::from),
+            );
+            used.push(Usage {
+                old: format!("tuckr {}", renamed.old.join(" ")),
+                new: format!("tuckr {}", renamed.new.join(" ")),
+            });
+            break;
+        }
+    }
+
+    for renamed in FLAGS {
+        if !matches(renamed.command, &args[start..]) {
+            continue;
+        }
+
+        let flags = start + renamed.command.len();
+        let end = args[flags..]
+            .iter()
+            .position(|arg| arg == "--")
+            .map_or(args.len(), |end| flags + end);
+        for arg in &mut args[flags..end] {
+            let Some(value) = arg.to_str().and_then(|arg| arg.strip_prefix(renamed.old)) else {
+                continue;
+            };
+            if !value.is_empty() && !value.starts_with('=') {
+                continue;
+            }
+
+            *arg = format!("{}{value}", renamed.new).into();
+            used.push(Usage {
+                old: renamed.old.into(),
+                new: renamed.new.into(),
+            });
+        }
+    }
+
+    used
+}
+
+/// Warns about the old names that were used, with `strict` they fail the command instead
+pub fn report(used: &[Usage], strict: bool) -> Result<(), TuckrError> {
+    for usage in used {
+        match strict {
+            true => errors::print(error!("deprecated_usage", old = usage.old, new = usage.new)),
+            false => eprintln!(
+                "{}",
+                t!("warn.deprecated_usage", old = usage.old, new = usage.new)
+                    .if_supports_color(Stderr, |t| t.yellow())
+            ),
+        }
+    }
+
+    match strict && !used.is_empty() {
+        true => Err(TuckrError::Failed),
+        false => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &str) -> Vec<OsString> {
+        args.split_whitespace().map(OsString::from).collect()
+    }
+
+    #[test]
+    fn renamed_commands_are_rewritten() {
+        let valued_options = ["--profile".to_string(), "-p".to_string()];
+
+        let mut command = args("tuckr -p work --dry-run restore 2024-01-01");
+        let used = rewrite(&mut command, &valued_options);
+        assert_eq!(
+            command,
+            args("tuckr -p work --dry-run backups restore 2024-01-01")
+        );
+        assert_eq!(
+            used,
+            [Usage {
+                old: "tuckr restore".into(),
+                new: "tuckr backups restore".into()
+            }]
+        );
+
+        // only the subcommand is renamed, not a group or profile that happens to have its name
+        for unchanged in [
+            "tuckr backups restore",
+            "tuckr --profile restore add restore",
+            "tuckr add restore",
+            "tuckr -- restore",
+        ] {
+            let mut command = args(unchanged);
+            assert!(rewrite(&mut command, &valued_options).is_empty());
+            assert_eq!(command, args(unchanged));
+        }
+    }
+}
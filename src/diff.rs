@@ -0,0 +1,918 @@
+//! Shows how deployed dotfiles differ from the ones in dotfiles/Configs
+//!
+//! Symlinked dotfiles can't differ from their source, but copies, rendered templates and
+//! files that were unlinked and edited can. These are shown as unified diffs where the
+//! dotfiles repo is the old side and the deployed file is the new side.
+//!
+//! Copies and rendered templates that were edited on the target since they were deployed, which is
+//! told by the hash recorded in the state when they were deployed, can be resolved with
+//! `--overwrite`, which deploys the dotfile over the edits, or `--pull-back`, which writes the edits
+//! to the dotfile.
+//!
+//! Conflicting files whose contents diverged from their dotfile can be merged with an external
+//! merge tool by `tuckr resolve`, the result is written to the dotfile which is then deployed.
+
+use crate::backups::{self, BackupSet};
+use crate::config;
+use crate::dotfiles::{self, Dotfile};
+use crate::error;
+use crate::errors::{self, TuckrError};
+use crate::fileops;
+use crate::state;
+use crate::symlinks;
+use crate::templates;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::{Stderr, Stdout};
+use rust_i18n::t;
+use std::fs;
+use std::path::{self, Path, PathBuf};
+use std::process::Command;
+
+/// Lines of unchanged content shown around each change
+const CONTEXT_LINES: usize = 3;
+
+/// Width of the +/- bar of `--stat`
+const STAT_WIDTH: usize = 40;
+
+/// Merge tools that can be given by name, whatever is saved on the `{dotfile}` side is kept
+const MERGE_TOOLS: [(&str, &str); 4] = [
+    ("meld", "meld {dotfile} {target}"),
+    ("vimdiff", "vimdiff {dotfile} {target}"),
+    ("nvim", "nvim -d {dotfile} {target}"),
+    ("code", "code --wait --diff {target} {dotfile}"),
+];
+
+/// How copies that were modified on the target are brought back in line with their dotfiles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DriftResolution {
+    /// the dotfile is deployed again, the edited copy is backed up
+    Overwrite,
+    /// the edits are written to the dotfile
+    PullBack,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Edit<'a> {
+    Equal(&'a str),
+    Delete(&'a str),
+    Insert(&'a str),
+}
+
+/// Computes the shortest list of edits that turns `old` into `new` with Myers' algorithm
+fn diff_lines<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<Edit<'a>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let offset = n + m;
+    let idx = |k: isize| (k + offset) as usize;
+
+    // v[k] is the furthest x reached on diagonal k, it's saved on every step to backtrack the path later
+    let mut v = vec![0; 2 * (n + m) as usize + 2];
+    let mut trace = Vec::new();
+
+    'search: for d in 0..=offset {
+        trace.push(v.clone());
+
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+                v[idx(k + 1)]
+            } else {
+                v[idx(k - 1)] + 1
+            };
+            let mut y = x - k;
+
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+
+            v[idx(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+
+        let prev_k = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[idx(prev_k)];
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            edits.push(Edit::Equal(old[x as usize]));
+        }
+
+        if d > 0 {
+            if x == prev_x {
+                y -= 1;
+                edits.push(Edit::Insert(new[y as usize]));
+            } else {
+                x -= 1;
+                edits.push(Edit::Delete(old[x as usize]));
+            }
+        }
+    }
+
+    edits.reverse();
+    edits
+}
+
+/// A group of changes and the unchanged lines around them
+#[derive(Debug, PartialEq)]
+struct Hunk<'a> {
+    old_start: usize,
+    old_len: usize,
+    new_start: usize,
+    new_len: usize,
+    edits: &'a [Edit<'a>],
+}
+
+/// Splits the edits into hunks with `context` unchanged lines around the changes
+fn hunks<'a>(edits: &'a [Edit<'a>], context: usize) -> Vec<Hunk<'a>> {
+    let changes: Vec<usize> = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Equal(_)))
+        .map(|(i, _)| i)
+        .collect();
+
+    // ranges of edits that end up in the same hunk, changes close to each other are merged
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for change in changes {
+        let start = change.saturating_sub(context);
+        let end = (change + context + 1).min(edits.len());
+
+        match ranges.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    let (mut old_line, mut new_line, mut pos) = (0, 0, 0);
+    let mut advance = |to: usize| {
+        for edit in &edits[pos..to] {
+            match edit {
+                Edit::Equal(_) => {
+                    old_line += 1;
+                    new_line += 1;
+                }
+                Edit::Delete(_) => old_line += 1,
+                Edit::Insert(_) => new_line += 1,
+            }
+        }
+        pos = to;
+        (old_line, new_line)
+    };
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            let (old_start, new_start) = advance(start);
+            let (old_end, new_end) = advance(end);
+            let (old_len, new_len) = (old_end - old_start, new_end - new_start);
+
+            // empty ranges point at the line before them
+            Hunk {
+                old_start: if old_len == 0 {
+                    old_start
+                } else {
+                    old_start + 1
+                },
+                old_len,
+                new_start: if new_len == 0 {
+                    new_start
+                } else {
+                    new_start + 1
+                },
+                new_len,
+                edits: &edits[start..end],
+            }
+        })
+        .collect()
+}
+
+/// Prints a line of a hunk, lines missing a newline are marked like diff does
+fn print_line(prefix: char, line: &str, color: fn(&str) -> String) {
+    match line.strip_suffix('\n') {
+        Some(line) => println!("{}", color(&format!("{prefix}{line}"))),
+        None => {
+            println!("{}", color(&format!("{prefix}{line}")));
+            println!("\\ No newline at end of file");
+        }
+    }
+}
+
+fn print_unified(source: &Path, target: &Path, edits: &[Edit]) {
+    println!(
+        "{}",
+        format!("--- {}", source.display()).if_supports_color(Stdout, |t| t.bold())
+    );
+    println!(
+        "{}",
+        format!("+++ {}", target.display()).if_supports_color(Stdout, |t| t.bold())
+    );
+
+    for hunk in hunks(edits, CONTEXT_LINES) {
+        println!(
+            "{}",
+            format!(
+                "@@ -{},{} +{},{} @@",
+                hunk.old_start, hunk.old_len, hunk.new_start, hunk.new_len
+            )
+            .if_supports_color(Stdout, |t| t.cyan())
+        );
+
+        for edit in hunk.edits {
+            match edit {
+                Edit::Equal(line) => print_line(' ', line, |l| l.to_string()),
+                Edit::Delete(line) => print_line('-', line, |l| {
+                    l.if_supports_color(Stdout, |t| t.red()).to_string()
+                }),
+                Edit::Insert(line) => print_line('+', line, |l| {
+                    l.if_supports_color(Stdout, |t| t.green()).to_string()
+                }),
+            }
+        }
+    }
+}
+
+/// How a deployed dotfile differs from its source
+struct FileDiff {
+    target: PathBuf,
+    insertions: usize,
+    deletions: usize,
+}
+
+/// Reads what the dotfile should look like when deployed, templates are rendered
+fn read_source(dotfile: &Dotfile) -> Result<Vec<u8>, String> {
+    if dotfile.is_template() {
+        templates::render_file(&dotfile.path, dotfile.dotfiles_dir()).map(String::into_bytes)
+    } else {
+        fs::read(&dotfile.path).map_err(|e| format!("{}: {e}", dotfile.path.display()))
+    }
+}
+
+/// Compares a dotfile with its deployed file, printing the diff unless `stat` is set
+///
+/// Returns None if they're the same or if the dotfile is not deployed
+fn diff_dotfile(dotfile: &Dotfile, stat: bool) -> Result<Option<FileDiff>, String> {
+    let target = dotfile.to_target_path()?;
+    if !target.exists() {
+        return Ok(None);
+    }
+
+    // symlinked files, or files inside of symlinked directories, are the source itself
+    if let (Ok(source), Ok(deployed)) = (fs::canonicalize(&dotfile.path), fs::canonicalize(&target))
+        && source == deployed
+    {
+        return Ok(None);
+    }
+
+    let source = read_source(dotfile)?;
+    let deployed = fs::read(&target).map_err(|e| format!("{}: {e}", target.display()))?;
+    if source == deployed {
+        return Ok(None);
+    }
+
+    let (Ok(source), Ok(deployed)) = (String::from_utf8(source), String::from_utf8(deployed))
+    else {
+        if !stat {
+            println!(
+                "{}",
+                t!(
+                    "info.binary_files_differ",
+                    a = dotfile.path.display(),
+                    b = target.display()
+                )
+            );
+        }
+        return Ok(Some(FileDiff {
+            target,
+            insertions: 0,
+            deletions: 0,
+        }));
+    };
+
+    let old: Vec<_> = source.split_inclusive('\n').collect();
+    let new: Vec<_> = deployed.split_inclusive('\n').collect();
+    let edits = diff_lines(&old, &new);
+
+    if !stat {
+        print_unified(&dotfile.path, &target, &edits);
+    }
+
+    Ok(Some(FileDiff {
+        target,
+        insertions: edits
+            .iter()
+            .filter(|e| matches!(e, Edit::Insert(_)))
+            .count(),
+        deletions: edits
+            .iter()
+            .filter(|e| matches!(e, Edit::Delete(_)))
+            .count(),
+    }))
+}
+
+/// Prints how the file that's in the way of a dotfile differs from it, for `tuckr add --interactive`
+pub fn print_conflict(dotfile: &Dotfile) {
+    match diff_dotfile(dotfile, false) {
+        Ok(Some(_)) => (),
+        Ok(None) => println!("{}", t!("info.conflict_is_identical")),
+        Err(err) => errors::print(err),
+    }
+}
+
+fn print_stat(diffs: &[FileDiff]) {
+    let name_width = diffs
+        .iter()
+        .map(|diff| diff.target.display().to_string().len())
+        .max()
+        .unwrap_or_default();
+    let most_changes = diffs
+        .iter()
+        .map(|diff| diff.insertions + diff.deletions)
+        .max()
+        .unwrap_or_default();
+
+    for diff in diffs {
+        let changes = diff.insertions + diff.deletions;
+
+        // bars are scaled down so that the biggest change fits in STAT_WIDTH
+        let scale = |n: usize| {
+            if most_changes <= STAT_WIDTH {
+                n
+            } else {
+                (n * STAT_WIDTH).div_ceil(most_changes)
+            }
+        };
+
+        let changes = if changes == 0 {
+            "Bin".to_string()
+        } else {
+            format!(
+                "{changes} {}{}",
+                "+".repeat(scale(diff.insertions))
+                    .if_supports_color(Stdout, |t| t.green()),
+                "-".repeat(scale(diff.deletions))
+                    .if_supports_color(Stdout, |t| t.red())
+            )
+        };
+
+        println!(
+            " {:name_width$} | {changes}",
+            diff.target.display().to_string()
+        );
+    }
+
+    println!(
+        " {}",
+        t!(
+            "info.diff_stat_summary",
+            files = diffs.len(),
+            insertions = diffs.iter().map(|diff| diff.insertions).sum::<usize>(),
+            deletions = diffs.iter().map(|diff| diff.deletions).sum::<usize>()
+        )
+    );
+}
+
+/// Deploys the dotfile over a copy that was modified on the target, returns the hash of the new copy
+fn overwrite_copy(
+    dry_run: bool,
+    dotfile: &Dotfile,
+    target: &Path,
+    backup_set: &mut BackupSet,
+) -> Result<String, errors::Error> {
+    let source = read_source(dotfile)?;
+    backup_set.backup(dry_run, target)?;
+
+    if dry_run {
+        eprintln!(
+            "{} `{}` to `{}`",
+            "copying".if_supports_color(Stderr, |t| t.green()),
+            dotfile.path.display(),
+            target.display()
+        );
+        return Ok(fileops::hash_bytes(source));
+    }
+
+    // plain copies are copied again so that they keep the dotfile's permissions
+    match dotfile.is_template() {
+        true => fs::write(target, &source),
+        false => fs::copy(&dotfile.path, target).map(|_| ()),
+    }
+    .map_err(|e| format!("{}: {e}", target.display()))?;
+
+    Ok(fileops::hash_bytes(source))
+}
+
+/// Writes the edits of a copy that was modified on the target to its dotfile, returns the hash of the copy
+fn pull_back_copy(
+    dry_run: bool,
+    dotfile: &Dotfile,
+    target: &Path,
+) -> Result<String, errors::Error> {
+    // the rendered file can't be written back to the template it came from
+    if dotfile.is_template() {
+        return Err(error!(
+            "pull_back_template",
+            file = target.display(),
+            template = dotfile.path.display()
+        ));
+    }
+
+    if dry_run {
+        eprintln!(
+            "{} `{}` to `{}`",
+            "copying".if_supports_color(Stderr, |t| t.green()),
+            target.display(),
+            dotfile.path.display()
+        );
+    } else {
+        fs::copy(target, &dotfile.path).map_err(|e| format!("{}: {e}", dotfile.path.display()))?;
+    }
+
+    fileops::hash_file(target).map_err(|e| format!("{}: {e}", target.display()).into())
+}
+
+/// Resolves the copies of the dotfiles that were modified on the target
+fn resolve_modified(
+    profile: Option<String>,
+    dry_run: bool,
+    dotfiles: &[Dotfile],
+    resolution: DriftResolution,
+) -> Result<(), TuckrError> {
+    let mut state = state::load_or_report(profile.clone())?;
+    let mut backup_set = BackupSet::try_new(profile.clone()).map_err(|err| {
+        errors::print(err);
+        TuckrError::CouldntFindDotfiles
+    })?;
+
+    let mut failed = false;
+    let mut resolved = 0;
+    for dotfile in dotfiles {
+        let Ok(target) = dotfile.to_target_path() else {
+            continue;
+        };
+        let Some(copy) = state.copies.get_mut(&target) else {
+            continue;
+        };
+        if copy.source != dotfile.path || !copy.was_modified(&target) {
+            continue;
+        }
+
+        let hash = match resolution {
+            DriftResolution::Overwrite => {
+                overwrite_copy(dry_run, dotfile, &target, &mut backup_set)
+            }
+            DriftResolution::PullBack => pull_back_copy(dry_run, dotfile, &target),
+        };
+        match hash {
+            Ok(hash) => {
+                copy.hash = Some(hash);
+                resolved += 1;
+            }
+            Err(err) => {
+                errors::print(err);
+                failed = true;
+            }
+        }
+    }
+
+    if resolved == 0 && !failed {
+        println!(
+            "{}",
+            t!("info.no_modified_copies").if_supports_color(Stdout, |t| t.green())
+        );
+        return Ok(());
+    }
+
+    if !dry_run && let Err(err) = state.save() {
+        errors::print(err);
+        failed = true;
+    }
+
+    let resolved = match resolution {
+        DriftResolution::Overwrite => t!("info.modified_copies_overwritten", count = resolved),
+        DriftResolution::PullBack => t!("info.modified_copies_pulled_back", count = resolved),
+    };
+    println!("{}", resolved.if_supports_color(Stdout, |t| t.green()));
+
+    if !backup_set.is_empty() {
+        println!(
+            "{}",
+            t!("info.conflicts_backed_up", backup = backup_set.name())
+                .if_supports_color(Stdout, |t| t.yellow())
+        );
+        backups::apply_retention(profile);
+    }
+
+    if failed {
+        Err(TuckrError::Failed)
+    } else {
+        Ok(())
+    }
+}
+
+/// Shows the differences between the dotfiles and what's deployed
+///
+/// groups: the groups to compare, every group is compared if none are given.
+/// Conditional groups are included along with their base group
+/// resolution: instead of showing them, the copies that were modified on the target are resolved
+///
+/// Exits with a failure if any differences are found
+pub fn diff_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    groups: Option<Vec<String>>,
+    stat: bool,
+    resolution: Option<DriftResolution>,
+) -> Result<(), TuckrError> {
+    let configs_dir = match dotfiles::get_dotfiles_path(profile.clone()) {
+        Ok(dir) => dir.join("Configs"),
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::CouldntFindDotfiles);
+        }
+    };
+
+    let Ok(entries) = fs::read_dir(&configs_dir) else {
+        errors::print(error!("x_doesnt_exist", x = configs_dir.display()));
+        return Err(TuckrError::CouldntFindDotfiles);
+    };
+
+    let mut group_names: Vec<String> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|group| dotfiles::group_is_valid_target(group))
+        .collect();
+    group_names.sort();
+
+    if let Some(groups) = &groups {
+        let invalid_groups: Vec<_> = groups
+            .iter()
+            .filter(|group| {
+                !group_names
+                    .iter()
+                    .any(|g| g == *group || dotfiles::group_without_target(g) == *group)
+            })
+            .collect();
+
+        if !invalid_groups.is_empty() {
+            for group in invalid_groups {
+                errors::print(error!("x_doesnt_exist", x = group));
+            }
+            return Err(TuckrError::NoSuchFileOrDir);
+        }
+
+        group_names.retain(|g| {
+            groups
+                .iter()
+                .any(|group| g == group || dotfiles::group_without_target(g) == group)
+        });
+    }
+
+    let mut dotfiles = Vec::new();
+    for group in group_names {
+        let group = Dotfile::try_from(configs_dir.join(group)).unwrap();
+        dotfiles.extend(
+            group
+                .try_iter()
+                .unwrap()
+                .filter(|dotfile| !dotfile.path.is_dir()),
+        );
+    }
+
+    if let Some(resolution) = resolution {
+        return resolve_modified(profile, dry_run, &dotfiles, resolution);
+    }
+
+    let mut diffs = Vec::new();
+    for dotfile in &dotfiles {
+        match diff_dotfile(dotfile, stat) {
+            Ok(Some(diff)) => diffs.push(diff),
+            Ok(None) => (),
+            Err(err) => errors::print(err),
+        }
+    }
+
+    if diffs.is_empty() {
+        return Ok(());
+    }
+
+    if stat {
+        print_stat(&diffs);
+    }
+
+    Err(TuckrError::Failed)
+}
+
+/// Returns the command that opens both versions of a file in a merge tool
+///
+/// Tools that aren't known by name are run as given with the `{dotfile}` and `{target}` placeholders
+/// replaced, both files are appended to the command if it has neither of them
+fn merge_command(tool: &str, dotfile: &Path, target: &Path) -> Vec<String> {
+    let command = MERGE_TOOLS
+        .iter()
+        .find(|(name, _)| *name == tool)
+        .map_or(tool, |(_, command)| command);
+
+    let mut args: Vec<String> = command.split_whitespace().map(String::from).collect();
+    if !command.contains("{dotfile}") && !command.contains("{target}") {
+        args.extend(["{dotfile}".into(), "{target}".into()]);
+    }
+
+    args.into_iter()
+        .map(|arg| {
+            arg.replace("{dotfile}", &dotfile.to_string_lossy())
+                .replace("{target}", &target.to_string_lossy())
+        })
+        .collect()
+}
+
+/// Lets a merge tool merge the dotfile's contents with the conflicting file's
+///
+/// Both versions are copied into a temporary directory so that nothing is changed if the tool fails,
+/// returns the dotfile's side if it was changed and the target's side otherwise
+fn merge(tool: &str, dotfile: &[u8], target: &[u8], name: &Path) -> Result<Vec<u8>, errors::Error> {
+    let merge_dir = std::env::temp_dir().join(format!("tuckr-resolve-{}", std::process::id()));
+    let dotfile_path = merge_dir.join("dotfile").join(name);
+    let target_path = merge_dir.join("target").join(name);
+
+    let run = || -> Result<Vec<u8>, errors::Error> {
+        for (path, contents) in [(&dotfile_path, dotfile), (&target_path, target)] {
+            fs::create_dir_all(path.parent().unwrap())
+                .and_then(|_| fs::write(path, contents))
+                .map_err(|e| format!("{}: {e}", path.display()))?;
+        }
+
+        let command = merge_command(tool, &dotfile_path, &target_path);
+        let status = Command::new(&command[0])
+            .args(&command[1..])
+            .status()
+            .map_err(|err| error!("couldnt_run_merge_tool", tool = command[0], err_msg = err))?;
+        if !status.success() {
+            return Err(error!("merge_tool_failed"));
+        }
+
+        let merged_dotfile = fs::read(&dotfile_path).map_err(|e| e.to_string())?;
+        let merged_target = fs::read(&target_path).map_err(|e| e.to_string())?;
+        if merged_dotfile == dotfile && merged_target != target {
+            return Ok(merged_target);
+        }
+        Ok(merged_dotfile)
+    };
+
+    let merged = run();
+    _ = fs::remove_dir_all(&merge_dir);
+    merged
+}
+
+/// Resolves a conflict by merging the conflicting file with its dotfile in a merge tool
+///
+/// The merged contents are written to the dotfile, then the conflicting file is backed up
+/// and the dotfile is deployed in its place, so that both sides end up the same
+pub fn resolve_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    path: &Path,
+    tool: Option<String>,
+) -> Result<(), TuckrError> {
+    let target = path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+
+    let Some(dotfile) = symlinks::conflicting_dotfile(profile.clone(), &target)? else {
+        errors::print(error!("resolve_not_a_conflict", file = target.display()));
+        return Err(TuckrError::NoSuchFileOrDir);
+    };
+
+    if dotfile.path.is_dir() || target.is_dir() {
+        errors::print(error!("resolve_directory", file = target.display()));
+        return Err(TuckrError::Failed);
+    }
+
+    // the rendered file can't be merged back into the template it came from
+    if dotfile.is_template() {
+        errors::print(error!(
+            "resolve_template",
+            file = target.display(),
+            template = dotfile.path.display()
+        ));
+        return Err(TuckrError::Failed);
+    }
+
+    let read = |path: &Path| {
+        fs::read(path).map_err(|e| {
+            errors::print(format!("{}: {e}", path.display()));
+            TuckrError::Failed
+        })
+    };
+    let source = read(&dotfile.path)?;
+    let deployed = read(&target)?;
+
+    if source == deployed {
+        println!("{}", t!("info.resolve_identical", file = target.display()));
+        return symlinks::replace_conflict(profile, dry_run, &dotfile);
+    }
+
+    let Some(tool) = tool.or_else(|| config::get().merge_tool.clone()) else {
+        errors::print(error!("resolve_no_tool"));
+        return Err(TuckrError::Failed);
+    };
+
+    if dry_run {
+        eprintln!(
+            "{} `{}`",
+            "merging with".if_supports_color(Stderr, |t| t.green()),
+            merge_command(&tool, &dotfile.path, &target).join(" ")
+        );
+        eprintln!(
+            "{} `{}`",
+            "writing".if_supports_color(Stderr, |t| t.green()),
+            dotfile.path.display()
+        );
+        return symlinks::replace_conflict(profile, dry_run, &dotfile);
+    }
+
+    let name = target.file_name().map_or(Path::new("file"), Path::new);
+    let merged = merge(&tool, &source, &deployed, name).map_err(|err| {
+        errors::print(err);
+        TuckrError::Failed
+    })?;
+
+    if merged != source
+        && let Err(err) = fs::write(&dotfile.path, &merged)
+    {
+        errors::print(format!("{}: {err}", dotfile.path.display()));
+        return Err(TuckrError::Failed);
+    }
+
+    symlinks::replace_conflict(profile, dry_run, &dotfile)?;
+    println!(
+        "{}",
+        t!(
+            "info.resolved",
+            file = target.display(),
+            group = dotfile.group_name
+        )
+        .if_supports_color(Stdout, |t| t.green())
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unified_diff_hunks() {
+        let old: Vec<_> = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\n"
+            .split_inclusive('\n')
+            .collect();
+        let new: Vec<_> = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk"
+            .split_inclusive('\n')
+            .collect();
+
+        let edits = diff_lines(&old, &new);
+        assert_eq!(
+            edits
+                .iter()
+                .filter(|e| !matches!(e, Edit::Equal(_)))
+                .collect::<Vec<_>>(),
+            [
+                &Edit::Delete("b\n"),
+                &Edit::Insert("B\n"),
+                &Edit::Insert("k"),
+            ]
+        );
+
+        let edit_hunks = hunks(&edits, 3);
+        assert_eq!(edit_hunks.len(), 2);
+        assert_eq!(
+            (
+                edit_hunks[0].old_start,
+                edit_hunks[0].old_len,
+                edit_hunks[0].new_start,
+                edit_hunks[0].new_len
+            ),
+            (1, 5, 1, 5)
+        );
+        assert_eq!(
+            (
+                edit_hunks[1].old_start,
+                edit_hunks[1].old_len,
+                edit_hunks[1].new_start,
+                edit_hunks[1].new_len
+            ),
+            (8, 3, 8, 4)
+        );
+
+        assert!(hunks(&diff_lines(&old, &old), 3).is_empty());
+        assert_eq!(
+            diff_lines(&[], &["a\n"]),
+            [Edit::Insert("a\n")],
+            "diffing against an empty file"
+        );
+    }
+
+    #[test]
+    fn merge_tool_commands() {
+        let (dotfile, target) = (Path::new("/a/.zshrc"), Path::new("/b/.zshrc"));
+        assert_eq!(
+            merge_command("code", dotfile, target),
+            ["code", "--wait", "--diff", "/b/.zshrc", "/a/.zshrc"]
+        );
+        assert_eq!(
+            merge_command("kdiff3 -o {dotfile} {dotfile} {target}", dotfile, target),
+            ["kdiff3", "-o", "/a/.zshrc", "/a/.zshrc", "/b/.zshrc"]
+        );
+        assert_eq!(
+            merge_command("mymerge --gui", dotfile, target),
+            ["mymerge", "--gui", "/a/.zshrc", "/b/.zshrc"]
+        );
+    }
+
+    #[test]
+    fn resolve_modified_copies() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let file = dotfiles_dir
+            .join("Configs")
+            .join("DriftGroup")
+            .join(".tuckr_drift_test");
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(&file, "repo").unwrap();
+        let target = Dotfile::try_from(file.clone())
+            .unwrap()
+            .to_target_path()
+            .unwrap();
+
+        let groups = vec!["DriftGroup".to_string()];
+        symlinks::add_cmd(None, false, false, true, &groups, &[], false, false, false).unwrap();
+        let was_modified =
+            || state::State::load(None).unwrap().copies[&target].was_modified(&target);
+        assert!(!was_modified());
+
+        // the edits made on the target end up in the dotfile
+        fs::write(&target, "local").unwrap();
+        assert!(was_modified());
+        let pull_back = Some(DriftResolution::PullBack);
+        diff_cmd(None, false, Some(groups.clone()), false, pull_back).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "local");
+        assert!(!was_modified());
+
+        // the dotfile is deployed over them
+        fs::write(&target, "edited again").unwrap();
+        let overwrite = Some(DriftResolution::Overwrite);
+        diff_cmd(None, false, Some(groups), false, overwrite).unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "local");
+        assert!(!was_modified());
+
+        let unknown = Some(vec!["NoSuchGroup".to_string()]);
+        assert!(matches!(
+            diff_cmd(None, false, unknown, false, None),
+            Err(TuckrError::NoSuchFileOrDir)
+        ));
+
+        fs::remove_file(&target).unwrap();
+        fs::remove_dir_all(dotfiles_dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolve_conflict_with_merge_tool() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let file = dotfiles_dir
+            .join("Configs")
+            .join("ResolveGroup")
+            .join(".tuckr_resolve_test");
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(&file, "repo").unwrap();
+        let target = Dotfile::try_from(file.clone())
+            .unwrap()
+            .to_target_path()
+            .unwrap();
+        fs::write(&target, "local").unwrap();
+
+        // the tool keeps the target's version by copying it over the dotfile's
+        resolve_cmd(None, false, &target, Some("cp {target} {dotfile}".into())).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "local");
+        assert_eq!(fs::read_link(&target).unwrap(), file);
+
+        // it's no longer a conflict once it's deployed
+        assert!(resolve_cmd(None, false, &target, Some("false".into())).is_err());
+
+        fs::remove_file(&target).unwrap();
+        fs::remove_dir_all(dotfiles_dir).unwrap();
+    }
+}
@@ -1,15 +1,27 @@
 //! Contains utilities to handle dotfiles
 
+use crate::backups;
+use crate::config;
 use crate::dotfiles;
+use crate::error;
+use crate::errors;
 use crate::fileops;
+use crate::hooklog;
+use crate::profiles;
+use crate::secrets;
+use crate::state;
+use crate::templates;
+use ignore::Match;
+use ignore::gitignore::Gitignore;
 use owo_colors::OwoColorize;
+use owo_colors::Stream::Stderr;
 use rust_i18n::t;
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
+use std::fs;
+use std::path::{self, Component};
 use std::path::{Path, PathBuf};
-use std::{
-    path::{self, Component},
-    process,
-};
+use std::sync::{Arc, Mutex, OnceLock};
 
 pub const VALID_TARGETS: &[&str] = &[
     // default target_os values
@@ -28,21 +40,71 @@ pub const VALID_TARGETS: &[&str] = &[
     "_windows",
 ];
 
+/// Separates the hostname from groups that are only meant for a specific machine, e.g. `nvim%worklaptop`
+const HOST_SEPARATOR: char = '%';
+
+/// Splits a group into the group without its hostname and the hostname it targets
+fn split_host(group: &str) -> (&str, Option<&str>) {
+    match group.rsplit_once(HOST_SEPARATOR) {
+        Some((group, host)) => (group, Some(host)),
+        None => (group, None),
+    }
+}
+
 /// Returns the priority number for the group
 /// A higher number means a higher priority
 pub fn get_group_priority(group: impl AsRef<str>) -> usize {
-    let group = group.as_ref();
-    let target = group.split('_').last().unwrap_or(group);
+    let (group, host) = split_host(group.as_ref());
+    let target = group.split('_').next_back().unwrap_or(group);
 
     // priority is in order of specificity
     // the more os specific target has higher priority
-    match target {
+    let os_priority = match target {
         "unix" | "windows" => 1,
         _ if !group_ends_with_target_name(group) => 0,
         _ => 2,
+    };
+
+    // a single machine is more specific than any os
+    match host {
+        Some(_) => os_priority + 3,
+        None => os_priority,
     }
 }
 
+/// Orders groups by which one's file is deployed when they deploy the same file, the first one wins
+///
+/// Groups are ranked by the `priority` list in tuckr.toml, then by get_group_priority and then by
+/// name so that the same group wins whatever order the groups are added in
+fn collision_rank<'a>(
+    group: &'a str,
+    priority: &[String],
+) -> (usize, std::cmp::Reverse<usize>, &'a str) {
+    let rank = priority
+        .iter()
+        .position(|g| g == group)
+        .or_else(|| {
+            priority
+                .iter()
+                .position(|g| g == group_without_target(group))
+        })
+        .unwrap_or(usize::MAX);
+
+    (rank, std::cmp::Reverse(get_group_priority(group)), group)
+}
+
+/// Returns true if the group's file is deployed instead of the other group's when both deploy the same file
+pub fn wins_collision(group: &str, other: &str) -> bool {
+    let priority = &config::get().priority;
+    collision_rank(group, priority) < collision_rank(other, priority)
+}
+
+/// Returns true if the `priority` list in tuckr.toml decides which of the groups wins a collision
+pub fn collision_is_configured(group: &str, other: &str) -> bool {
+    let priority = &config::get().priority;
+    collision_rank(group, priority).0 != collision_rank(other, priority).0
+}
+
 /// Returns the index of the group with the highest priority in the `targets`
 pub fn get_highest_priority_target_idx(targets: &[impl AsRef<str>]) -> Option<usize> {
     if targets.is_empty() {
@@ -64,43 +126,34 @@ pub fn get_highest_priority_target_idx(targets: &[impl AsRef<str>]) -> Option<us
     Some(highest_idx)
 }
 
-/// Exit codes
-pub enum ReturnCode {
-    /// Couldn't find the dotfiles directory
-    CouldntFindDotfiles = 2,
-    /// No Configs/Hooks/Secrets folder setup
-    NoSetupFolder = 3,
-    /// Referenced file does not exist in the current directory
-    NoSuchFileOrDir = 4,
-    /// Failed to encrypt referenced file
-    EncryptionFailed = 5,
-    /// Failed to decrypt referenced file
-    DecryptionFailed = 6,
-}
-
-impl From<ReturnCode> for process::ExitCode {
-    fn from(value: ReturnCode) -> Self {
-        Self::from(value as u8)
-    }
-}
-
 pub fn get_dotfile_profile_from_path<T: AsRef<Path>>(file: T) -> Option<String> {
-    let file: &Path = file.as_ref();
-    let file = file.to_str().unwrap();
+    let components: Vec<_> = file.as_ref().components().collect();
+    let is_dtype_dir = |component: Option<&Component>| match component {
+        Some(Component::Normal(dir_name)) => ["Configs", "Hooks", "Secrets"]
+            .iter()
+            .any(|dtype_dir| dir_name == dtype_dir),
+        _ => false,
+    };
 
-    const DIRNAME: &str = "dotfiles_";
-    let dotfiles_start = file.find(DIRNAME)?;
+    // the dotfiles directory is either the parent of Configs, Hooks or Secrets or the last
+    // component, as long as it isn't a group that happens to be named like a profile
+    components.iter().enumerate().find_map(|(idx, component)| {
+        let Component::Normal(dir_name) = component else {
+            return None;
+        };
 
-    let file = &file[dotfiles_start + DIRNAME.len()..];
-    let dotfiles_end = file.find(std::path::MAIN_SEPARATOR);
+        let profile = dir_name
+            .to_str()?
+            .trim_start_matches('.')
+            .strip_prefix("dotfiles_")?;
 
-    Some(
-        match dotfiles_end {
-            Some(end) => &file[..end],
-            None => file,
-        }
-        .into(),
-    )
+        let is_dotfiles_dir = match components.get(idx + 1) {
+            Some(next) => is_dtype_dir(Some(next)),
+            None => !is_dtype_dir(idx.checked_sub(1).and_then(|prev| components.get(prev))),
+        };
+
+        is_dotfiles_dir.then(|| profile.to_string())
+    })
 }
 
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
@@ -111,13 +164,13 @@ pub struct Dotfile {
 }
 
 impl TryFrom<path::PathBuf> for Dotfile {
-    type Error = String;
+    type Error = errors::Error;
 
     /// Returns Ok if the path is pointing to a group within $TUCKR_HOME
     fn try_from(value: path::PathBuf) -> Result<Self, Self::Error> {
         /// returns the path for the group the file belongs to.
         /// an error is returned if the file does not belong to dotfiles
-        pub fn to_group_path(file_path: &path::PathBuf) -> Result<path::PathBuf, String> {
+        pub fn to_group_path(file_path: &path::PathBuf) -> Result<path::PathBuf, errors::Error> {
             let dotfiles_dir = get_dotfiles_path(get_dotfile_profile_from_path(file_path))?;
             let configs_dir = dotfiles_dir.join("Configs");
             let hooks_dir = dotfiles_dir.join("Hooks");
@@ -143,9 +196,7 @@ impl TryFrom<path::PathBuf> for Dotfile {
                     .next()
                     .unwrap()
                 else {
-                    return Err(
-                        t!("errors.failed_to_get_group_relative_to_dotfiles_dir").into_owned()
-                    );
+                    return Err(error!("failed_to_get_group_relative_to_dotfiles_dir"));
                 };
 
                 dotfile_root_dir.join(group_relpath)
@@ -157,7 +208,11 @@ impl TryFrom<path::PathBuf> for Dotfile {
         let group_path = to_group_path(&value)?;
 
         Ok(Dotfile {
-            group_name: group_path.file_name().unwrap().to_str().unwrap().into(),
+            group_name: group_path
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned(),
             path: value,
             group_path,
         })
@@ -165,10 +220,21 @@ impl TryFrom<path::PathBuf> for Dotfile {
 }
 
 pub fn group_ends_with_target_name(group: &str) -> bool {
-    VALID_TARGETS.iter().any(|target| group.ends_with(target))
+    let (group, host) = split_host(group);
+    host.is_some() || VALID_TARGETS.iter().any(|target| group.ends_with(target))
+}
+
+/// Returns the platform suffix of a conditional group, e.g. `_linux` for `zsh_linux` and `zsh_linux%laptop`
+pub fn platform_suffix(group: &str) -> Option<&str> {
+    let (group, _) = split_host(group);
+    VALID_TARGETS
+        .iter()
+        .find(|target| group.ends_with(*target))
+        .map(|target| &group[group.len() - target.len()..])
 }
 
 pub fn group_without_target(group: &str) -> &str {
+    let (group, _) = split_host(group);
     for target in VALID_TARGETS {
         if let Some(base_group) = group.strip_suffix(target) {
             return base_group;
@@ -178,21 +244,180 @@ pub fn group_without_target(group: &str) -> &str {
     group
 }
 
+/// Checks whether `text` matches a glob pattern
+///
+/// `*` matches any sequence of characters and `?` matches a single character
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // where the last `*` was and the text position it's matching up to, used to backtrack
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Checks whether a group is excluded, exclusions can be glob patterns
+///
+/// Excluding a group also excludes its conditional groups
+pub fn is_excluded(group: &str, exclude: &[String]) -> bool {
+    exclude.iter().any(|pattern| {
+        glob_matches(pattern, group) || glob_matches(pattern, group_without_target(group))
+    })
+}
+
+/// Returns the name of the machine tuckr is running on
+pub fn get_hostname() -> Option<&'static str> {
+    static HOSTNAME: OnceLock<Option<String>> = OnceLock::new();
+
+    HOSTNAME
+        .get_or_init(|| {
+            #[cfg(target_family = "unix")]
+            {
+                let mut hostname = [0u8; 256];
+                // SAFETY: the buffer's length is passed along so gethostname never writes past it
+                if unsafe { libc::gethostname(hostname.as_mut_ptr().cast(), hostname.len()) } != 0 {
+                    return None;
+                }
+
+                let len = hostname
+                    .iter()
+                    .position(|&c| c == 0)
+                    .unwrap_or(hostname.len());
+                String::from_utf8(hostname[..len].to_vec()).ok()
+            }
+
+            #[cfg(target_family = "windows")]
+            {
+                env::var("COMPUTERNAME").ok()
+            }
+        })
+        .as_deref()
+}
+
+/// Returns true if `target_host` refers to `hostname`
+///
+/// Hostnames are case insensitive and the domain can be left out, e.g. `laptop` matches `laptop.example.com`
+fn host_matches(target_host: &str, hostname: &str) -> bool {
+    let short_hostname = hostname.split('.').next().unwrap_or(hostname);
+    target_host.eq_ignore_ascii_case(hostname) || target_host.eq_ignore_ascii_case(short_hostname)
+}
+
+/// Returns true if the group is allowed to be deployed to `hostname`
+///
+/// Groups can either be restricted to a host with a suffix (e.g. `nvim%laptop`)
+/// or by listing hosts for the group in the config, e.g. `[groups.nvim] hosts = ["laptop"]`
+fn group_is_valid_host(group: &str, hostname: Option<&str>) -> bool {
+    let (base_group, host) = split_host(group);
+    let config_hosts = config::get()
+        .groups
+        .get(group_without_target(base_group))
+        .map(|group| group.hosts.as_slice())
+        .unwrap_or_default();
+
+    let is_hostname = |host: &str| hostname.is_some_and(|hostname| host_matches(host, hostname));
+
+    host.is_none_or(is_hostname)
+        && (config_hosts.is_empty() || config_hosts.iter().any(|host| is_hostname(host)))
+}
+
 /// Returns true if a group with specified name can be used by current platform.
 /// Checks if a group should be linked on current platform. For unconditional
 /// groups, this function returns true; for conditional groups, this function
-/// returns true when group suffix matches current target_os or target_family.
+/// returns true when group suffix matches current target_os or target_family
+/// and the current hostname if the group targets a specific machine.
 pub fn group_is_valid_target(group: &str) -> bool {
+    let hostname = get_hostname();
+    if !group_is_valid_host(group, hostname) {
+        tracing::debug!(group, ?hostname, "skipping group, it's for another host");
+        return false;
+    }
+
+    let (base_group, _) = split_host(group);
+
     // Gets the current OS and OS family
     let current_target_os = format!("_{}", env::consts::OS);
     let current_target_family = format!("_{}", env::consts::FAMILY);
 
     // returns true if a group has no suffix or its suffix matches the current OS
-    if group_ends_with_target_name(group) {
-        group.ends_with(&current_target_os) || group.ends_with(&current_target_family)
-    } else {
-        true
+    let Some(target) = VALID_TARGETS
+        .iter()
+        .find(|target| base_group.ends_with(*target))
+    else {
+        tracing::trace!(group, "the group has no platform, it's deployed everywhere");
+        return true;
+    };
+
+    let is_valid =
+        base_group.ends_with(&current_target_os) || base_group.ends_with(&current_target_family);
+    match is_valid {
+        true => tracing::trace!(group, platform = target, "the group is for this platform"),
+        false => tracing::debug!(
+            group,
+            platform = target,
+            os = env::consts::OS,
+            family = env::consts::FAMILY,
+            "skipping group, it targets another platform"
+        ),
     }
+    is_valid
+}
+
+/// Returns true if there's a machine where both groups are deployed,
+/// e.g. `nvim_unix` and `nvim_linux` are both deployed on linux while `nvim_unix` and `nvim_windows` never are
+pub fn can_deploy_together(group: &str, other: &str) -> bool {
+    fn target(group: &str) -> (Option<&str>, Option<&str>) {
+        let (group, host) = split_host(group);
+        let os = VALID_TARGETS
+            .iter()
+            .find(|target| group.ends_with(*target))
+            .map(|target| &target[1..]);
+        (os, host)
+    }
+
+    let family = |os: &str| match os {
+        "windows" => Some("windows"),
+        "none" => None,
+        _ => Some("unix"),
+    };
+
+    let (os, host) = target(group);
+    let (other_os, other_host) = target(other);
+
+    let same_os = match (os, other_os) {
+        (Some(os), Some(other_os)) => {
+            os == other_os || family(os) == Some(other_os) || family(other_os) == Some(os)
+        }
+        _ => true,
+    };
+    let same_host = match (host, other_host) {
+        (Some(host), Some(other_host)) => host.eq_ignore_ascii_case(other_host),
+        _ => true,
+    };
+
+    same_os && same_host
 }
 
 impl Dotfile {
@@ -203,49 +428,138 @@ impl Dotfile {
 
     /// Checks whether the current groups is targetting the root path aka `/`
     pub fn targets_root(&self) -> bool {
-        let root_dir = get_dotfiles_path(get_dotfile_profile_from_path(&self.group_path))
-            .unwrap()
-            .join("Configs")
-            .join("Root");
-        self.group_path.starts_with(root_dir)
+        self.group_name == "Root"
+            && self
+                .group_path
+                .parent()
+                .is_some_and(|dir| dir.ends_with(DotfileType::Configs.dir_name()))
     }
 
-    /// Converts a path string from dotfiles/Configs to where they should be
-    /// deployed on $TUCKR_TARGET
-    pub fn to_target_path(&self) -> Result<PathBuf, String> {
-        // uses join("") so that the path appends / or \ depending on platform
-        let dotfiles_configs_path = get_dotfiles_path(get_dotfile_profile_from_path(&self.path))
-            .unwrap()
-            .join("Configs")
-            .join("");
+    /// Checks whether the dotfile is a template that gets rendered instead of symlinked
+    pub fn is_template(&self) -> bool {
+        templates::is_template(&self.path)
+    }
 
-        let group_path = {
-            let dotfiles_configs_path = dotfiles_configs_path.to_str().unwrap();
+    /// Returns the dotfile's path relative to its group, which is also where it is relative to the target
+    pub fn relative_path(&self) -> &Path {
+        self.path.strip_prefix(&self.group_path).unwrap()
+    }
+
+    /// Returns the dotfiles directory the dotfile's group is in, i.e. the parent of its Configs, Hooks or Secrets
+    pub fn dotfiles_dir(&self) -> &Path {
+        self.group_path
+            .ancestors()
+            .nth(2)
+            .unwrap_or(&self.group_path)
+    }
+
+    /// Returns the directory the dotfile's group is deployed to
+    ///
+    /// Groups can have their own in tuckr.toml's `[targets]` or in a `.tuckr-target` file inside of them,
+    /// relative ones are relative to $TUCKR_TARGET and they can start with a variable such as
+    /// `$XDG_DATA_HOME`, see [`expand_path_vars`]. The Root group is deployed to `/`
+    pub fn target_dir(&self) -> Result<PathBuf, errors::Error> {
+        if self.targets_root() {
+            return Ok(get_root_target_dir_path());
+        }
+
+        let target_dir = get_dotfiles_target_dir_path()?;
+        let is_config = self
+            .group_path
+            .parent()
+            .is_some_and(|dir| dir.ends_with(DotfileType::Configs.dir_name()));
+        if !is_config {
+            return Ok(target_dir);
+        }
 
-            let dotfile_path = self.path.to_str().unwrap();
-            let dotfile_path = dotfile_path.strip_prefix(dotfiles_configs_path).unwrap();
+        let group_target = config::get()
+            .group_target(&self.group_name)
+            .cloned()
+            .or_else(|| read_target_file(&self.group_path));
+
+        // expanded targets are already inside of the sandbox
+        if let Some(dir) = &group_target
+            && has_path_vars(dir)
+        {
+            let expanded = expand_path_vars(dir, &target_dir)?;
+            return Ok(target_dir.join(expanded));
+        }
+
+        Ok(match group_target {
+            Some(dir) if dir.is_absolute() => match SANDBOX_DIR.get() {
+                Some(sandbox) => sandbox.join(strip_root(&dir)),
+                None => dir,
+            },
+            Some(dir) => target_dir.join(dir),
+            None => target_dir,
+        })
+    }
+
+    /// Returns the suffix that's added to the names of the group's files when $HOME is shared
+    /// between operating systems, so that each of them gets its own version of the platform's files
+    ///
+    /// Only platform specific groups that are deployed to $TUCKR_TARGET have one
+    pub fn target_suffix(&self) -> Option<&str> {
+        let is_config = self
+            .group_path
+            .parent()
+            .is_some_and(|dir| dir.ends_with(DotfileType::Configs.dir_name()));
+        if !config::get().shared_home || !is_config || self.targets_root() {
+            return None;
+        }
 
-            match dotfile_path.split_once(path::MAIN_SEPARATOR) {
-                Some(path) => path.1,
-                None => dotfile_path,
+        let suffix = platform_suffix(&self.group_name)?;
+        let has_own_target = config::get().group_target(&self.group_name).is_some()
+            || read_target_file(&self.group_path).is_some();
+        (!has_own_target).then_some(suffix)
+    }
+
+    /// Converts a path from dotfiles/Configs to where it should be deployed on $TUCKR_TARGET
+    ///
+    /// Directories named after a variable, e.g. `$XDG_CONFIG_HOME` or `%APPDATA%`, are deployed to
+    /// the directory that the variable stands for on this platform, see [`expand_path_vars`]
+    pub fn to_target_path(&self) -> Result<PathBuf, errors::Error> {
+        let relative_path = self.relative_path();
+        let target_path = match has_path_vars(relative_path) {
+            true => {
+                let target_dir = get_dotfiles_target_dir_path()?;
+                let expanded = expand_path_vars(relative_path, &target_dir)?;
+                self.target_dir()?.join(expanded)
             }
+            false => self.target_dir()?.join(relative_path),
         };
 
-        let target_path = if self.targets_root() {
-            path::PathBuf::from(path::MAIN_SEPARATOR_STR)
+        // templates are deployed without their .tmpl extension
+        let mut target_path = if self.is_template() {
+            target_path.with_extension("")
         } else {
-            get_dotfiles_target_dir_path()?
+            target_path
+        };
+
+        // files with a platform suffix are deployed without it, e.g. config_linux.toml as config.toml
+        if let Some(suffix) = file_platform_suffix(&self.path)
+            && self
+                .group_path
+                .parent()
+                .is_some_and(|dir| dir.ends_with(DotfileType::Configs.dir_name()))
+        {
+            target_path = remove_platform_suffix(&target_path, suffix);
+        }
+
+        if let Some(suffix) = self.target_suffix()
+            && !self.path.is_dir()
+        {
+            target_path = add_suffix(&target_path, suffix);
         }
-        .join(group_path);
 
         Ok(target_path)
     }
 
     /// Creates an iterator that walks the directory
     /// Returns none if the Dotfile is not a directory, since it would not be walkable
-    pub fn try_iter(&self) -> Result<DotfileIter, String> {
+    pub fn try_iter(&self) -> Result<DotfileIter, errors::Error> {
         if !self.path.is_dir() {
-            Err(t!("errors.not_a_dir", directory = self.path.display()).into_owned())
+            Err(error!("not_a_dir", directory = self.path.display()))
         } else {
             Ok(DotfileIter(fileops::DirWalk::new(self.path.clone())))
         }
@@ -268,17 +582,18 @@ impl Iterator for DotfileIter {
 ///
 /// When run on a unit test it returns a temporary directory for testing purposes.
 /// this testing directory is unique to the thread it's running on,
-/// so different unit tests cannot interact with the other's dotfiles directory
-pub fn get_dotfiles_path(profile: Option<String>) -> Result<path::PathBuf, String> {
+/// so different unit tests cannot interact with the other's dotfiles directory.
+/// Profiles get their own directory next to it
+pub fn get_dotfiles_path(profile: Option<String>) -> Result<path::PathBuf, errors::Error> {
     let dotfiles_dir = match profile {
         Some(ref profile) => format!("dotfiles_{profile}"),
         None => "dotfiles".into(),
     };
 
-    if let Ok(dir) = std::env::var("TUCKR_HOME") {
-        if !dir.is_empty() {
-            return Ok(PathBuf::from(dir).join(dotfiles_dir));
-        }
+    if let Ok(dir) = std::env::var("TUCKR_HOME")
+        && !dir.is_empty()
+    {
+        return Ok(PathBuf::from(dir).join(dotfiles_dir));
     }
 
     let (home_dotfiles, config_dotfiles) = {
@@ -287,7 +602,7 @@ pub fn get_dotfiles_path(profile: Option<String>) -> Result<path::PathBuf, Strin
 
         (
             home_dotfiles.join(format!(".{dotfiles_dir}")),
-            config_dotfiles.join(dotfiles_dir),
+            config_dotfiles.join(&dotfiles_dir),
         )
     };
 
@@ -297,7 +612,7 @@ pub fn get_dotfiles_path(profile: Option<String>) -> Result<path::PathBuf, Strin
         // cargo-test names each threads with the name of the unit test that is running on it.
         Ok(std::env::temp_dir()
             .join(format!("tuckr-{}", std::thread::current().name().unwrap()))
-            .join("dotfiles"))
+            .join(dotfiles_dir))
     } else if config_dotfiles.exists() {
         Ok(config_dotfiles)
     } else if home_dotfiles.exists() {
@@ -307,60 +622,645 @@ pub fn get_dotfiles_path(profile: Option<String>) -> Result<path::PathBuf, Strin
             Some(profile) => format!("tuckr -p {profile} init"),
             None => "tuckr init".into(),
         };
-        Err(format!(
-            "{}\n{}",
-            t!("errors.couldnt_find_dotfiles_dir").yellow(),
-            t!(
-                "errors.make_sure_dir_exists_or_run",
-                dir = config_dotfiles.display(),
-                cmd = init_cmd
-            )
+        Err(errors::Error::new(
+            "couldnt_find_dotfiles_dir",
+            format!(
+                "{}\n{}",
+                t!("errors.couldnt_find_dotfiles_dir").if_supports_color(Stderr, |t| t.yellow()),
+                t!(
+                    "errors.make_sure_dir_exists_or_run",
+                    dir = config_dotfiles.display(),
+                    cmd = init_cmd
+                )
+            ),
         ))
     }
 }
 
+/// File in a profile's dotfiles directory that names the profile it's layered on top of
+pub const PROFILE_FILE: &str = "profile.toml";
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ProfileFile {
+    /// the profile whose groups are used when this one doesn't have them,
+    /// `default` for the dotfiles without a profile
+    base: Option<String>,
+}
+
+/// Returns the dotfiles directories that a profile's groups are looked up in, its own comes first
+///
+/// A profile is layered on top of another one with `base = "<profile>"` in its profile.toml
+/// (`base = "default"` for the dotfiles without a profile) and bases can have bases of their own.
+/// A group is deployed from the first directory that has it, so a profile's group replaces the
+/// base's group of the same name as a whole instead of being merged with it.
+pub fn get_dotfiles_search_path(profile: Option<String>) -> Result<Vec<PathBuf>, errors::Error> {
+    let mut search_path = vec![get_dotfiles_path(profile)?];
+
+    loop {
+        let file = search_path.last().unwrap().join(PROFILE_FILE);
+        let Ok(contents) = fs::read_to_string(&file) else {
+            break;
+        };
+        let profile_file: ProfileFile =
+            toml::from_str(&contents).map_err(|err| format!("{}: {err}", file.display()))?;
+        let Some(base) = profile_file.base else {
+            break;
+        };
+
+        let base_dir = get_dotfiles_path(profiles::to_profile(&base))?;
+        if search_path.contains(&base_dir) {
+            return Err(error!("profile_base_cycle", profile = base));
+        }
+        search_path.push(base_dir);
+    }
+
+    Ok(search_path)
+}
+
 /// removes the $HOME from path
 pub fn get_target_basepath(target: &path::Path) -> Option<PathBuf> {
     let target_dir = get_dotfiles_target_dir_path().ok()?;
     Some(target.strip_prefix(target_dir).ok()?.into())
 }
 
+/// Removes the directory the group is deployed to from path, e.g. `/etc/ssh/sshd_config` -> `sshd_config`
+/// for a group that's deployed to /etc/ssh
+pub fn get_group_basepath(group_dir: &Path, target: &Path) -> Option<PathBuf> {
+    let target_dir = Dotfile::try_from(group_dir.to_path_buf())
+        .ok()?
+        .target_dir()
+        .ok()?;
+    Some(target.strip_prefix(target_dir).ok()?.into())
+}
+
+/// A file inside of a group with the directory that the group is deployed to
+pub const TARGET_FILE: &str = ".tuckr-target";
+
+/// A file or directory in the dotfiles that tuckr only keeps for this machine
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MachineLocal {
+    /// a file at the top of the dotfiles
+    File(&'static str),
+    /// a directory at the top of the dotfiles
+    Dir(&'static str),
+    /// files anywhere in the dotfiles whose names end with it
+    Suffix(&'static str),
+}
+
+/// What's left out of git and of `tuckr export`, along with why it's only meant for this machine
+pub const MACHINE_LOCAL: &[(MachineLocal, &str)] = &[
+    (
+        MachineLocal::File(state::STATE_FILE),
+        "what was deployed on this machine",
+    ),
+    (
+        MachineLocal::Dir(backups::BACKUPS_DIR),
+        "files that were replaced by `tuckr add --force`, they might be decrypted secrets",
+    ),
+    (MachineLocal::Dir(hooklog::LOGS_DIR), "output of the hooks"),
+    (
+        MachineLocal::Suffix(secrets::REKEYED_SUFFIX),
+        "secrets that were left behind by `tuckr secrets rekey`",
+    ),
+    (
+        MachineLocal::Suffix(secrets::OLD_SUFFIX),
+        "secrets that `tuckr secrets rekey` couldn't put back",
+    ),
+];
+
+impl MachineLocal {
+    /// Returns the gitignore pattern that matches it, `prefix` is where the dotfiles are in the
+    /// repository
+    pub fn gitignore_pattern(&self, prefix: &str) -> String {
+        match self {
+            MachineLocal::File(name) => format!("/{prefix}{name}"),
+            MachineLocal::Dir(name) => format!("/{prefix}{name}/"),
+            MachineLocal::Suffix(suffix) if prefix.is_empty() => format!("*{suffix}"),
+            MachineLocal::Suffix(suffix) => format!("/{prefix}**/*{suffix}"),
+        }
+    }
+
+    /// Returns the git pathspec that leaves it out, relative to the dotfiles
+    pub fn exclude_pathspec(&self) -> String {
+        match self {
+            MachineLocal::File(name) | MachineLocal::Dir(name) => format!(":(exclude){name}"),
+            // `*` also matches slashes in pathspecs
+            MachineLocal::Suffix(suffix) => format!(":(exclude)*{suffix}"),
+        }
+    }
+
+    /// Returns true if the path, relative to the dotfiles, is it or is inside of it
+    pub fn matches(&self, path: &Path) -> bool {
+        match self {
+            MachineLocal::File(name) | MachineLocal::Dir(name) => {
+                path.components().next() == Some(Component::Normal(name.as_ref()))
+            }
+            MachineLocal::Suffix(suffix) => path
+                .file_name()
+                .is_some_and(|file| file.to_string_lossy().ends_with(suffix)),
+        }
+    }
+}
+
+/// Lists files that aren't deployed in gitignore syntax, either at the root of the dotfiles or in a group
+pub const IGNORE_FILE: &str = ".tuckrignore";
+
+/// Reads the .tuckrignore in the directory, each one is only read once
+fn read_ignore_file(dir: &Path) -> Option<Arc<Gitignore>> {
+    static IGNORE_FILES: Mutex<BTreeMap<PathBuf, Option<Arc<Gitignore>>>> =
+        Mutex::new(BTreeMap::new());
+
+    IGNORE_FILES
+        .lock()
+        .unwrap()
+        .entry(dir.to_path_buf())
+        .or_insert_with(|| {
+            let file = dir.join(IGNORE_FILE);
+            if !file.is_file() {
+                return None;
+            }
+
+            // the lines that could be parsed are still used
+            let (ignore, err) = Gitignore::new(&file);
+            if let Some(err) = err {
+                eprintln!(
+                    "{}",
+                    t!("warn.invalid_ignore_file", file = file.display(), err = err)
+                        .if_supports_color(Stderr, |t| t.yellow())
+                );
+            }
+            Some(Arc::new(ignore))
+        })
+        .clone()
+}
+
+/// Checks whether a file in dotfiles/Configs is matched by its group's .tuckrignore or the dotfiles'
+///
+/// The group's .tuckrignore takes precedence, so it can bring back files with `!`
+pub fn is_ignored_by_ignore_file(file: &Path) -> bool {
+    // groups might have directories called Configs of their own
+    let Some(configs_dir) = file
+        .ancestors()
+        .skip(1)
+        .filter(|dir| dir.file_name() == Some("Configs".as_ref()))
+        .last()
+    else {
+        return false;
+    };
+    let Some(dotfiles_dir) = configs_dir.parent() else {
+        return false;
+    };
+    let group_dir = file
+        .ancestors()
+        .find(|dir| dir.parent() == Some(configs_dir))
+        .unwrap();
+
+    if group_dir != file
+        && let Some(ignore) = read_ignore_file(group_dir)
+    {
+        match ignore.matched_path_or_any_parents(file, file.is_dir()) {
+            Match::Ignore(_) => return true,
+            Match::Whitelist(_) => return false,
+            Match::None => (),
+        }
+    }
+
+    read_ignore_file(dotfiles_dir).is_some_and(|ignore| {
+        ignore
+            .matched_path_or_any_parents(file, file.is_dir())
+            .is_ignore()
+    })
+}
+
+/// Checks whether a directory in dotfiles/Configs has files that are ignored by a .tuckrignore
+/// or files with a platform suffix
+///
+/// These directories can't be symlinked since the ignored files would come along with them, and
+/// the platform specific ones would keep their suffix
+pub fn contains_ignored_files(dir: &Path) -> bool {
+    dir.is_dir()
+        && fileops::DirWalk::new(dir)
+            .ignore(|_| false)
+            .any(|file| is_ignored_by_ignore_file(&file) || file_platform_suffix(&file).is_some())
+}
+
+/// Returns the platform suffix of a file, e.g. `_linux` for `config_linux.toml` and `.zshrc_linux`
+///
+/// Like [`add_suffix`] it comes before the extension, templates are looked at without their .tmpl
+fn file_platform_suffix(file: &Path) -> Option<&'static str> {
+    if file.is_dir() {
+        return None;
+    }
+
+    let name = match templates::is_template(file) {
+        true => file.file_stem()?,
+        false => file.file_name()?,
+    };
+    let stem = Path::new(name).file_stem()?.to_str()?;
+    VALID_TARGETS
+        .iter()
+        .find(|target| stem.len() > target.len() && stem.ends_with(*target))
+        .copied()
+}
+
+/// Removes a file's platform suffix from its name, e.g. config_linux.toml becomes config.toml
+fn remove_platform_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+        return path.to_path_buf();
+    };
+
+    let mut name = stem.strip_suffix(suffix).unwrap_or(stem).to_string();
+    if let Some(extension) = path.extension() {
+        name.push('.');
+        name.push_str(&extension.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+/// Checks whether a file in dotfiles/Configs isn't deployed on this platform because of its suffix
+///
+/// That's the case for files meant for other platforms, e.g. `config_windows.toml` on linux, and for
+/// files that have a more specific variant next to them, e.g. `config.toml` when there's a
+/// `config_linux.toml`. They're ranked the same way as conditional groups, see [`get_group_priority`]
+pub fn is_other_platforms_file(file: &Path) -> bool {
+    let in_configs = file
+        .ancestors()
+        .skip(2)
+        .any(|dir| dir.file_name() == Some("Configs".as_ref()));
+    if !in_configs || file.is_dir() {
+        return false;
+    }
+
+    let current = [
+        format!("_{}", std::env::consts::OS),
+        format!("_{}", std::env::consts::FAMILY),
+    ];
+    let suffix = file_platform_suffix(file);
+    if let Some(suffix) = suffix
+        && !current.iter().any(|target| target == suffix)
+    {
+        return true;
+    }
+
+    // the name the file is deployed with, which its variants share
+    let deployed = match templates::is_template(file) {
+        true => file.with_extension(""),
+        false => file.to_path_buf(),
+    };
+    let deployed = match suffix {
+        Some(suffix) => remove_platform_suffix(&deployed, suffix),
+        None => deployed,
+    };
+
+    let priority = get_group_priority(suffix.unwrap_or_default());
+    current
+        .iter()
+        .filter(|target| get_group_priority(target) > priority)
+        .any(|target| {
+            let variant = add_suffix(&deployed, target);
+            let mut template = variant.clone().into_os_string();
+            template.push(".");
+            template.push(templates::TEMPLATE_EXTENSION);
+            variant.exists() || Path::new(&template).exists()
+        })
+}
+
+/// Adds a suffix to a file's name before its extension, e.g. .zshrc_linux and init_linux.lua
+fn add_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let Some(stem) = path.file_stem() else {
+        return path.to_path_buf();
+    };
+
+    let mut name = stem.to_os_string();
+    name.push(suffix);
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+/// Returns the name of the variable that a path component stands for: `$NAME`, `${NAME}` or `%NAME%`
+fn path_var(component: &str) -> Option<&str> {
+    let name = match component.strip_prefix('$') {
+        Some(name) => name
+            .strip_prefix('{')
+            .and_then(|name| name.strip_suffix('}'))
+            .unwrap_or(name),
+        None => component.strip_prefix('%')?.strip_suffix('%')?,
+    };
+
+    let is_valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    is_valid.then_some(name)
+}
+
+/// Returns true if any of the path's components is a variable
+fn has_path_vars(path: &Path) -> bool {
+    path.components().any(|component| match component {
+        Component::Normal(name) => name.to_str().and_then(path_var).is_some(),
+        _ => false,
+    })
+}
+
+/// Returns the directory a variable stands for on this platform
+///
+/// The XDG and Windows directories fall back to where the platform keeps them when they aren't set,
+/// so that e.g. `$XDG_CONFIG_HOME` is `~/Library/Application Support` on macOS and `%APPDATA%`
+/// is `~/.config` on Linux. Any other variable has to be set.
+fn path_var_value(name: &str) -> Option<PathBuf> {
+    if let Some(value) = env::var_os(name).filter(|value| !value.is_empty()) {
+        return Some(value.into());
+    }
+
+    match name {
+        "HOME" | "USERPROFILE" => dirs::home_dir(),
+        "XDG_CONFIG_HOME" | "APPDATA" => dirs::config_dir(),
+        "XDG_DATA_HOME" => dirs::data_dir(),
+        "XDG_CACHE_HOME" => dirs::cache_dir(),
+        "XDG_STATE_HOME" => dirs::state_dir().or_else(dirs::data_local_dir),
+        "LOCALAPPDATA" => dirs::data_local_dir(),
+        _ => None,
+    }
+}
+
+/// Replaces the components of the path that are variables with the directories they stand for
+///
+/// Directories inside of the home directory are moved into `target_dir` so that they follow
+/// $TUCKR_TARGET like the rest of the dotfiles do, the others stay where they are unless dotfiles
+/// are deployed into a sandbox. The path is absolute once a variable was expanded.
+pub fn expand_path_vars(path: &Path, target_dir: &Path) -> Result<PathBuf, errors::Error> {
+    let home_dir = dirs::home_dir();
+    let mut expanded = PathBuf::new();
+    for component in path.components() {
+        let Some(name) = component.as_os_str().to_str().and_then(path_var) else {
+            expanded.push(component);
+            continue;
+        };
+
+        let Some(value) = path_var_value(name) else {
+            return Err(error!(
+                "path_var_not_set",
+                var = name,
+                path = path.display()
+            ));
+        };
+
+        let value = match home_dir
+            .as_ref()
+            .and_then(|home| value.strip_prefix(home).ok())
+        {
+            Some(in_home) => target_dir.join(in_home),
+            None => match SANDBOX_DIR.get() {
+                Some(sandbox) => sandbox.join(strip_root(&value)),
+                None => value,
+            },
+        };
+        expanded.push(value);
+    }
+
+    Ok(expanded)
+}
+
+/// Reads the directory a group is deployed to from its .tuckr-target file, each group's is only read once
+fn read_target_file(group_path: &Path) -> Option<PathBuf> {
+    static TARGET_FILES: Mutex<BTreeMap<PathBuf, Option<PathBuf>>> = Mutex::new(BTreeMap::new());
+
+    TARGET_FILES
+        .lock()
+        .unwrap()
+        .entry(group_path.to_path_buf())
+        .or_insert_with(|| {
+            let target = fs::read_to_string(group_path.join(TARGET_FILE)).ok()?;
+            let target = target.lines().next()?.trim();
+            (!target.is_empty()).then(|| config::expand_home(target.into()))
+        })
+        .clone()
+}
+
+/// When set dotfiles are deployed to it instead of $TUCKR_TARGET, set by `--target`
+static TARGET_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Deploys dotfiles to `dir` for this invocation, taking precedence over $TUCKR_TARGET and tuckr.toml
+pub fn set_target_dir(dir: &Path) -> Result<(), String> {
+    let dir = path::absolute(dir).map_err(|e| format!("{}: {e}", dir.display()))?;
+    TARGET_DIR
+        .set(dir)
+        .map_err(|_| "target directory has already been set".to_string())
+}
+
+/// Returns the target directory given by `--target` or $TUCKR_TARGET, if any
+pub fn custom_target_dir() -> Option<PathBuf> {
+    if let Some(dir) = TARGET_DIR.get() {
+        return Some(dir.clone());
+    }
+
+    match std::env::var("TUCKR_TARGET") {
+        Ok(dir) if !dir.is_empty() => Some(dir.into()),
+        _ => None,
+    }
+}
+
+/// Makes a tuckr run by this one, e.g. by the daemon or `tuckr ui`, deploy to the same target
+/// directory, since unlike $TUCKR_TARGET `--target` isn't inherited
+pub fn pass_target_dir(command: &mut std::process::Command) {
+    if let Some(dir) = custom_target_dir() {
+        command.env("TUCKR_TARGET", dir);
+    }
+}
+
 pub fn get_dotfiles_target_dir_path() -> Result<PathBuf, String> {
     #[cfg(test)]
     {
         unsafe { std::env::remove_var("TUCKR_TARGET") };
     }
 
-    if let Ok(dir) = std::env::var("TUCKR_TARGET") {
-        if !dir.is_empty() {
-            return Ok(dir.into());
+    let target_dir = match custom_target_dir() {
+        Some(dir) => dir,
+        None => match &config::get().target {
+            Some(dir) => dir.clone(),
+            None => dirs::home_dir().ok_or("No destination directory was found.")?,
+        },
+    };
+
+    Ok(match SANDBOX_DIR.get() {
+        Some(sandbox) => sandbox.join(strip_root(&target_dir)),
+        None => target_dir,
+    })
+}
+
+/// Returns the directory that the Root group is deployed to
+pub fn get_root_target_dir_path() -> PathBuf {
+    match SANDBOX_DIR.get() {
+        Some(sandbox) => sandbox.clone(),
+        None => PathBuf::from(path::MAIN_SEPARATOR_STR),
+    }
+}
+
+/// When set every dotfile is deployed inside of it as if it was the filesystem's root
+static SANDBOX_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Removes the root and prefix (e.g. `C:`) from a path, turning it into a relative path
+pub fn strip_root(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|c| matches!(c, Component::Normal(_)))
+        .collect()
+}
+
+/// Deploys dotfiles into `dir` instead of the real filesystem
+///
+/// The directory has to be empty so that deploying to it never runs into conflicts
+///
+/// dry_run: the directory isn't created if it doesn't exist, nothing is written to it anyway
+pub fn set_sandbox_dir(dir: &Path, dry_run: bool) -> Result<(), errors::Error> {
+    if dir.exists() {
+        let Ok(mut entries) = dir.read_dir() else {
+            return Err(error!("not_a_dir", directory = dir.display()));
+        };
+
+        if entries.next().is_some() {
+            return Err(error!("sandbox_not_empty", dir = dir.display()));
         }
+    } else if !dry_run {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
     }
 
-    dirs::home_dir().ok_or("No destination directory was found.".into())
+    let dir = path::absolute(dir).map_err(|e| e.to_string())?;
+    SANDBOX_DIR
+        .set(dir)
+        .map_err(|_| "sandbox directory has already been set".into())
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum DotfileType {
     Configs,
     Secrets,
     Hooks,
 }
 
+impl DotfileType {
+    /// Returns the name of the directory in the dotfiles that holds this type of dotfile
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            DotfileType::Configs => "Configs",
+            DotfileType::Secrets => "Secrets",
+            DotfileType::Hooks => "Hooks",
+        }
+    }
+}
+
+/// Returns the existing directories that the groups of <dtype> are in, the profile's comes first
+///
+/// They're looked up along the profile's search path, see [`get_dotfiles_search_path`].
+/// Profiles also fall back to the dotfiles without a profile for the Hooks and Secrets they don't
+/// have, so the ones that every profile shares don't have to be copied into each of them
+pub fn get_group_dirs(profile: Option<String>, dtype: DotfileType) -> Vec<PathBuf> {
+    let mut search_path = match get_dotfiles_search_path(profile.clone()) {
+        Ok(search_path) => search_path,
+        Err(_) => get_dotfiles_path(profile.clone()).into_iter().collect(),
+    };
+
+    if profile.is_some()
+        && matches!(dtype, DotfileType::Hooks | DotfileType::Secrets)
+        && let Ok(shared) = get_dotfiles_path(None)
+        && !search_path.contains(&shared)
+    {
+        search_path.push(shared);
+    }
+
+    search_path
+        .into_iter()
+        .map(|dir| dir.join(dtype.dir_name()))
+        .filter(|dir| dir.is_dir())
+        .collect()
+}
+
+/// Returns the directory of a group of <dtype>, the profile's own group is picked over a shared one
+pub fn find_group_dir(profile: Option<String>, dtype: DotfileType, group: &str) -> Option<PathBuf> {
+    get_group_dirs(profile, dtype)
+        .into_iter()
+        .map(|dir| dir.join(group))
+        .find(|group_dir| group_dir.exists())
+}
+
 /// Returns if a config has been setup for <group> on <dtype>
 pub fn dotfile_contains(profile: Option<String>, dtype: DotfileType, group: &str) -> bool {
-    let target_dir = match dtype {
-        DotfileType::Configs => "Configs",
-        DotfileType::Secrets => "Secrets",
-        DotfileType::Hooks => "Hooks",
-    };
+    get_group_dirs(profile, dtype).iter().any(|dir| {
+        dir.join(group).exists()
+            || dtype == DotfileType::Secrets && secrets::find_archive(dir, group).is_some()
+    })
+}
 
-    let Ok(dotfiles_dir) = get_dotfiles_path(profile) else {
-        return false;
-    };
+/// Expands the glob patterns in `groups` into the groups they match in the `dtypes` directories
+///
+/// Only groups that target the current platform are picked and conditional groups are left out when
+/// their base group is picked too, since it already brings them along.
+/// `*` is kept as is because it's handled by the commands themselves, and so are the patterns that
+/// don't match anything so that they're reported as missing groups.
+pub fn expand_groups(
+    profile: Option<String>,
+    dtypes: &[DotfileType],
+    groups: &[String],
+) -> Vec<String> {
+    if get_dotfiles_path(profile.clone()).is_err() {
+        return groups.to_vec();
+    }
 
-    let group_src = dotfiles_dir.join(target_dir).join(group);
-    group_src.exists()
+    let existing_groups: BTreeSet<String> = dtypes
+        .iter()
+        .flat_map(|&dtype| {
+            get_group_dirs(profile.clone(), dtype)
+                .into_iter()
+                .filter_map(|dir| fs::read_dir(dir).ok())
+                .flatten()
+                .flatten()
+                .map(move |entry| (dtype, entry))
+        })
+        .filter_map(|(dtype, entry)| {
+            let name = entry.file_name().into_string().ok()?;
+            match entry.path().is_dir() {
+                true => Some(name),
+                // groups in Secrets might only have an archive
+                false if dtype == DotfileType::Secrets => {
+                    secrets::archive_group(&name).map(str::to_string)
+                }
+                false => None,
+            }
+        })
+        .filter(|group| group_is_valid_target(group))
+        .collect();
+
+    let mut expanded = Vec::new();
+    for group in groups {
+        let is_pattern = group.contains(['*', '?']);
+        if group == "*" || !is_pattern {
+            expanded.push(group.clone());
+            continue;
+        }
+
+        let matches: Vec<_> = existing_groups
+            .iter()
+            .filter(|existing| glob_matches(group, existing))
+            .collect();
+
+        if matches.is_empty() {
+            expanded.push(group.clone());
+            continue;
+        }
+
+        for existing in &matches {
+            let base_group = group_without_target(existing);
+            let brought_by_base_group =
+                base_group != existing.as_str() && matches.iter().any(|g| *g == base_group);
+
+            if !brought_by_base_group && !expanded.contains(*existing) {
+                expanded.push((*existing).clone());
+            }
+        }
+    }
+
+    expanded
 }
 
 /// Returns all groups in the slice that don't have a corresponding directory in dotfiles/{Configs,Hooks,Secrets}
@@ -430,7 +1330,11 @@ pub fn is_valid_groupname(group: impl AsRef<str>) -> Result<(), String> {
 
 #[cfg(test)]
 mod tests {
-    use crate::dotfiles::{Dotfile, get_dotfiles_path};
+    use crate::dotfiles::{
+        Dotfile, DotfileType, PROFILE_FILE, dotfile_contains, expand_groups, find_group_dir,
+        get_dotfiles_path, get_dotfiles_search_path, get_group_dirs,
+    };
+    use std::fs;
 
     #[test]
     fn dotfile_to_target_path() {
@@ -440,12 +1344,332 @@ mod tests {
             .join("zsh")
             .join(".zshrc");
 
+        let dotfile = Dotfile::try_from(group).unwrap();
+        assert_eq!(dotfile.relative_path(), std::path::Path::new(".zshrc"));
         assert_eq!(
-            Dotfile::try_from(group).unwrap().to_target_path().unwrap(),
+            dotfile.to_target_path().unwrap(),
             dirs::home_dir().unwrap().join(".zshrc")
         );
     }
 
+    #[test]
+    fn sandbox_dir_must_be_empty() {
+        let sandbox = std::env::temp_dir().join(format!(
+            "tuckr_sandbox-{}",
+            std::thread::current().name().unwrap()
+        ));
+        fs::create_dir_all(&sandbox).unwrap();
+        fs::write(sandbox.join("file"), "").unwrap();
+
+        assert!(super::set_sandbox_dir(&sandbox, false).is_err());
+
+        fs::remove_dir_all(sandbox).unwrap();
+    }
+
+    #[test]
+    fn strip_root_from_path() {
+        let path = std::path::Path::new(std::path::MAIN_SEPARATOR_STR)
+            .join("home")
+            .join("tuckr");
+        assert_eq!(
+            super::strip_root(&path),
+            std::path::Path::new("home").join("tuckr")
+        );
+    }
+
+    #[test]
+    fn shared_home_suffixes() {
+        use std::path::Path;
+
+        assert_eq!(super::platform_suffix("zsh_linux"), Some("_linux"));
+        assert_eq!(super::platform_suffix("zsh_macos%laptop"), Some("_macos"));
+        assert_eq!(super::platform_suffix("zsh%laptop"), None);
+        assert_eq!(super::platform_suffix("zsh"), None);
+
+        assert_eq!(
+            super::add_suffix(Path::new("/home/tuckr/.zshrc"), "_linux"),
+            Path::new("/home/tuckr/.zshrc_linux")
+        );
+        assert_eq!(
+            super::add_suffix(Path::new(".config/nvim/init.lua"), "_macos"),
+            Path::new(".config/nvim/init_macos.lua")
+        );
+    }
+
+    #[test]
+    fn hostname_conditional_groups() {
+        assert_eq!(super::group_without_target("nvim%laptop"), "nvim");
+        assert_eq!(super::group_without_target("nvim_linux%laptop"), "nvim");
+        assert!(super::group_ends_with_target_name("nvim%laptop"));
+
+        assert!(super::group_is_valid_host("nvim%laptop", Some("laptop")));
+        assert!(super::group_is_valid_host(
+            "nvim%laptop",
+            Some("LAPTOP.example.com")
+        ));
+        assert!(!super::group_is_valid_host("nvim%laptop", Some("desktop")));
+        assert!(!super::group_is_valid_host("nvim%laptop", None));
+        assert!(super::group_is_valid_host("nvim", Some("desktop")));
+
+        // the host specific group always wins over the os specific ones
+        let groups = ["nvim", "nvim_linux", "nvim%laptop", "nvim_unix"];
+        assert_eq!(super::get_highest_priority_target_idx(&groups), Some(2));
+        assert!(
+            super::get_group_priority("nvim_linux%laptop")
+                > super::get_group_priority("nvim%laptop")
+        );
+    }
+
+    #[test]
+    fn collision_winners() {
+        let rank = |group| super::collision_rank(group, &[]);
+        assert!(rank("bash") < rank("zsh"));
+        assert!(rank("zsh_linux") < rank("bash"));
+
+        let priority = ["zsh".to_string(), "fish".to_string()];
+        let rank = |group| super::collision_rank(group, &priority);
+        assert!(rank("zsh") < rank("bash"));
+        assert!(rank("zsh%laptop") < rank("fish_linux"));
+        assert!(rank("fish") < rank("bash_linux"));
+    }
+
+    #[test]
+    fn groups_deployed_together() {
+        assert!(super::can_deploy_together("nvim", "nvim_windows"));
+        assert!(super::can_deploy_together("nvim_unix", "nvim_linux"));
+        assert!(super::can_deploy_together("nvim_macos", "nvim_unix%laptop"));
+        assert!(!super::can_deploy_together("nvim_unix", "nvim_windows"));
+        assert!(!super::can_deploy_together("nvim_linux", "nvim_macos"));
+        assert!(!super::can_deploy_together("nvim%laptop", "nvim%desktop"));
+        assert!(!super::can_deploy_together("nvim_none", "nvim_unix"));
+    }
+
+    #[test]
+    fn group_target_file() {
+        let configs_dir = get_dotfiles_path(None).unwrap().join("Configs");
+        let fonts = configs_dir.join("Fonts");
+        fs::create_dir_all(&fonts).unwrap();
+        fs::write(fonts.join(super::TARGET_FILE), ".local/share/fonts\n").unwrap();
+
+        let target_dir = super::get_dotfiles_target_dir_path().unwrap();
+        let font = Dotfile::try_from(fonts.join("font.ttf")).unwrap();
+        assert_eq!(
+            font.to_target_path().unwrap(),
+            target_dir.join(".local/share/fonts/font.ttf")
+        );
+        assert_eq!(
+            super::get_group_basepath(&fonts, &target_dir.join(".local/share/fonts/font.ttf")),
+            Some("font.ttf".into())
+        );
+        assert!(crate::fileops::is_ignored_file(
+            fonts.join(super::TARGET_FILE)
+        ));
+
+        #[cfg(target_family = "unix")]
+        {
+            let sshd = configs_dir.join("Sshd");
+            fs::create_dir_all(&sshd).unwrap();
+            fs::write(sshd.join(super::TARGET_FILE), "/etc/ssh").unwrap();
+            let config = Dotfile::try_from(sshd.join("sshd_config")).unwrap();
+            assert_eq!(
+                config.to_target_path().unwrap(),
+                std::path::Path::new("/etc/ssh/sshd_config")
+            );
+        }
+
+        fs::remove_dir_all(get_dotfiles_path(None).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn expand_variables_in_paths() {
+        use std::path::Path;
+
+        assert_eq!(super::path_var("$XDG_CONFIG_HOME"), Some("XDG_CONFIG_HOME"));
+        assert_eq!(super::path_var("${XDG_DATA_HOME}"), Some("XDG_DATA_HOME"));
+        assert_eq!(super::path_var("%APPDATA%"), Some("APPDATA"));
+        assert_eq!(super::path_var("$1"), None);
+        assert_eq!(super::path_var("100%"), None);
+        assert_eq!(super::path_var(".config"), None);
+
+        let home = dirs::home_dir().unwrap();
+        let target_dir = Path::new("/sandbox");
+        let config_dir = dirs::config_dir().unwrap();
+        let expected = match config_dir.strip_prefix(&home) {
+            Ok(in_home) => target_dir.join(in_home),
+            Err(_) => config_dir,
+        };
+        assert_eq!(
+            super::expand_path_vars(Path::new("%APPDATA%/nvim/init.lua"), target_dir).unwrap(),
+            expected.join("nvim").join("init.lua")
+        );
+        assert!(
+            super::expand_path_vars(Path::new("$TUCKR_UNSET_VARIABLE/file"), target_dir).is_err()
+        );
+
+        let configs_dir = get_dotfiles_path(None).unwrap().join("Configs");
+        let nvim = configs_dir
+            .join("nvim")
+            .join("$XDG_CONFIG_HOME")
+            .join("nvim");
+        fs::create_dir_all(&nvim).unwrap();
+        let init = Dotfile::try_from(nvim.join("init.lua")).unwrap();
+        let target_dir = super::get_dotfiles_target_dir_path().unwrap();
+        assert_eq!(
+            init.to_target_path().unwrap(),
+            super::expand_path_vars(Path::new("$XDG_CONFIG_HOME/nvim/init.lua"), &target_dir)
+                .unwrap()
+        );
+
+        fs::remove_dir_all(get_dotfiles_path(None).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn ignore_files() {
+        let dotfiles_dir = get_dotfiles_path(None).unwrap();
+        let configs_dir = dotfiles_dir.join("Configs");
+        let nvim = configs_dir.join("nvim");
+        fs::create_dir_all(nvim.join("lua")).unwrap();
+        fs::write(
+            dotfiles_dir.join(super::IGNORE_FILE),
+            "*.swp\n__pycache__/\n",
+        )
+        .unwrap();
+        fs::write(nvim.join(super::IGNORE_FILE), "!keep.swp\n/local.lua\n").unwrap();
+
+        let is_ignored = |file: &str| crate::fileops::is_ignored_file(configs_dir.join(file));
+        assert!(!is_ignored("nvim/init.lua"));
+        assert!(is_ignored("nvim/.init.lua.swp"));
+        assert!(is_ignored("zsh/.zshrc.swp"));
+        assert!(is_ignored("nvim/lua/__pycache__/plugin.pyc"));
+        assert!(is_ignored("nvim/.tuckrignore"));
+        // the group's .tuckrignore takes precedence over the dotfiles'
+        assert!(!is_ignored("nvim/keep.swp"));
+        assert!(is_ignored("nvim/local.lua"));
+        assert!(!is_ignored("nvim/lua/local.lua"));
+        assert!(!is_ignored("zsh/local.lua"));
+
+        fs::remove_dir_all(dotfiles_dir).unwrap();
+    }
+
+    #[test]
+    fn platform_specific_files() {
+        let dotfiles_dir = get_dotfiles_path(None).unwrap();
+        let app = dotfiles_dir.join("Configs").join("app");
+        fs::create_dir_all(&app).unwrap();
+
+        let os = std::env::consts::OS;
+        let other_os = if os == "macos" { "linux" } else { "macos" };
+        let files = [
+            "config.toml".to_string(),
+            "config_unix.toml".to_string(),
+            format!("config_{os}.toml"),
+            format!("config_{other_os}.toml"),
+            "theme.toml".to_string(),
+            format!("theme_{other_os}.toml"),
+        ];
+        for file in &files {
+            fs::write(app.join(file), "").unwrap();
+        }
+
+        let is_ignored = |file: &str| crate::fileops::is_ignored_file(app.join(file));
+        assert!(is_ignored("config.toml"));
+        assert!(is_ignored("config_unix.toml"));
+        assert!(!is_ignored(&format!("config_{os}.toml")));
+        assert!(is_ignored(&format!("config_{other_os}.toml")));
+        assert!(!is_ignored("theme.toml"));
+        assert!(is_ignored(&format!("theme_{other_os}.toml")));
+
+        let config = Dotfile::try_from(app.join(format!("config_{os}.toml"))).unwrap();
+        assert_eq!(
+            config.to_target_path().unwrap(),
+            super::get_dotfiles_target_dir_path()
+                .unwrap()
+                .join("config.toml")
+        );
+        assert!(super::contains_ignored_files(&app));
+
+        fs::remove_dir_all(dotfiles_dir).unwrap();
+    }
+
+    #[test]
+    fn profiles_share_hooks_and_secrets() {
+        let work = Some("work".to_string());
+        let shared_dir = get_dotfiles_path(None).unwrap();
+        let work_dir = get_dotfiles_path(work.clone()).unwrap();
+        let groups = [
+            shared_dir.join("Hooks").join("shared_hooks"),
+            shared_dir.join("Hooks").join("overridden_hooks"),
+            shared_dir.join("Configs").join("shared_configs"),
+            work_dir.join("Hooks").join("overridden_hooks"),
+        ];
+        for group in &groups {
+            fs::create_dir_all(group).unwrap();
+        }
+
+        assert_eq!(
+            find_group_dir(work.clone(), DotfileType::Hooks, "shared_hooks"),
+            Some(groups[0].clone())
+        );
+        assert_eq!(
+            find_group_dir(work.clone(), DotfileType::Hooks, "overridden_hooks"),
+            Some(groups[3].clone())
+        );
+        assert!(!dotfile_contains(
+            work.clone(),
+            DotfileType::Configs,
+            "shared_configs"
+        ));
+        assert_eq!(
+            get_group_dirs(None, DotfileType::Hooks),
+            [shared_dir.join("Hooks")]
+        );
+
+        for group in &groups {
+            fs::remove_dir_all(group).unwrap();
+        }
+    }
+
+    #[test]
+    fn profiles_are_layered_on_their_base() {
+        let work = Some("work".to_string());
+        let shared_dir = get_dotfiles_path(None).unwrap();
+        let work_dir = get_dotfiles_path(work.clone()).unwrap();
+        let groups = [
+            shared_dir.join("Configs").join("zsh"),
+            shared_dir.join("Configs").join("git"),
+            work_dir.join("Configs").join("git"),
+        ];
+        for group in &groups {
+            fs::create_dir_all(group).unwrap();
+        }
+
+        // configs aren't shared unless the profile is layered on top of another one
+        assert!(!dotfile_contains(work.clone(), DotfileType::Configs, "zsh"));
+
+        fs::write(work_dir.join(PROFILE_FILE), "base = \"default\"").unwrap();
+        assert_eq!(
+            get_dotfiles_search_path(work.clone()).unwrap(),
+            [work_dir.clone(), shared_dir.clone()]
+        );
+        assert_eq!(
+            find_group_dir(work.clone(), DotfileType::Configs, "zsh"),
+            Some(groups[0].clone())
+        );
+        assert_eq!(
+            find_group_dir(work.clone(), DotfileType::Configs, "git"),
+            Some(groups[2].clone())
+        );
+        assert_eq!(
+            expand_groups(work.clone(), &[DotfileType::Configs], &["*i*".into()]),
+            ["git"]
+        );
+
+        fs::write(shared_dir.join(PROFILE_FILE), "base = \"work\"").unwrap();
+        assert!(get_dotfiles_search_path(work.clone()).is_err());
+
+        fs::remove_dir_all(shared_dir.parent().unwrap()).unwrap();
+    }
+
     #[test]
     fn dotfile_targets_root() {
         let dotfiles_dir = super::get_dotfiles_path(None).unwrap().join("Configs");
@@ -455,6 +1679,12 @@ mod tests {
 
         let nonroot_dotfile = super::Dotfile::try_from(dotfiles_dir.join("Zsh")).unwrap();
         assert!(!nonroot_dotfile.targets_root());
+
+        let root_hook = super::get_dotfiles_path(None)
+            .unwrap()
+            .join("Hooks")
+            .join("Root");
+        assert!(!super::Dotfile::try_from(root_hook).unwrap().targets_root());
     }
 
     #[test]
@@ -509,6 +1739,30 @@ mod tests {
         );
         assert_eq!(super::get_dotfile_profile_from_path(no_profile_dir), None,);
         assert_eq!(super::get_dotfile_profile_from_path(invalid_dir), None,);
+
+        let hidden_profile_dir = dirs::home_dir()
+            .unwrap()
+            .join(".dotfiles_work")
+            .join("Hooks");
+        let unrelated_dir = dirs::home_dir()
+            .unwrap()
+            .join("my_dotfiles_backup")
+            .join("dotfiles")
+            .join("Configs");
+        let group_named_like_profile = dirs::config_dir()
+            .unwrap()
+            .join("dotfiles")
+            .join("Configs")
+            .join("dotfiles_helper");
+        assert_eq!(
+            super::get_dotfile_profile_from_path(hidden_profile_dir),
+            Some("work".into())
+        );
+        assert_eq!(super::get_dotfile_profile_from_path(unrelated_dir), None);
+        assert_eq!(
+            super::get_dotfile_profile_from_path(group_named_like_profile),
+            None
+        );
     }
 
     #[test]
@@ -526,4 +1780,61 @@ mod tests {
             assert_eq!(super::get_group_priority(group), expected_priority);
         }
     }
+
+    #[test]
+    fn exclude_groups() {
+        assert!(super::glob_matches("nvim", "nvim"));
+        assert!(super::glob_matches("*_old", "zsh_old"));
+        assert!(super::glob_matches("*", ""));
+        assert!(super::glob_matches("n?im*", "nvim_linux"));
+        assert!(super::glob_matches("a*b*c", "aXbYbZc"));
+        assert!(!super::glob_matches("*_old", "zsh_older"));
+        assert!(!super::glob_matches("nvim", "nvim_linux"));
+
+        let exclude = ["*_old".to_string(), "nvim".to_string()];
+        assert!(super::is_excluded("zsh_old", &exclude));
+        assert!(super::is_excluded("nvim_linux", &exclude));
+        assert!(!super::is_excluded("zsh", &exclude));
+    }
+
+    #[test]
+    fn expand_group_patterns() {
+        let dotfiles_dir = get_dotfiles_path(None).unwrap();
+        let (own_target, other_target) = match std::env::consts::FAMILY {
+            "windows" => ("nvim_windows", "nvim_unix"),
+            _ => ("nvim_unix", "nvim_windows"),
+        };
+        for group in ["nvim", own_target, other_target, "nvim_old", "zsh"] {
+            fs::create_dir_all(dotfiles_dir.join("Configs").join(group)).unwrap();
+        }
+        fs::create_dir_all(dotfiles_dir.join("Hooks").join("nvidia")).unwrap();
+
+        let expand = |dtypes: &[super::DotfileType], groups: &[&str]| {
+            let groups: Vec<String> = groups.iter().map(|g| g.to_string()).collect();
+            super::expand_groups(None, dtypes, &groups)
+        };
+
+        let configs = [super::DotfileType::Configs];
+        assert_eq!(
+            expand(&configs, &["nvim*", "zsh"]),
+            ["nvim", "nvim_old", "zsh"]
+        );
+        let (own_pattern, other_pattern) = (
+            own_target.replace("nvim", "*"),
+            other_target.replace("nvim", "*"),
+        );
+        assert_eq!(expand(&configs, &[&own_pattern]), [own_target]);
+        // groups for other platforms are never picked
+        assert_eq!(expand(&configs, &[&other_pattern]), [other_pattern]);
+        assert_eq!(expand(&configs, &["*", "x?"]), ["*", "x?"]);
+        assert_eq!(
+            expand(
+                &[super::DotfileType::Configs, super::DotfileType::Hooks],
+                &["nv*"]
+            ),
+            ["nvidia", "nvim", "nvim_old"]
+        );
+
+        fs::remove_dir_all(dotfiles_dir).unwrap();
+    }
 }
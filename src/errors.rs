@@ -0,0 +1,343 @@
+//! Stable codes for the errors that tuckr reports
+//!
+//! Error messages are translated, so scripts can't rely on them. Instead every error has a code
+//! that never changes, e.g. `E_CONFLICT_FILE_EXISTS`, which `--format json` prints along with the
+//! message as a JSON object per line on stderr. `tuckr errors list` lists all of them.
+//!
+//! Errors are created with [`error!`], which translates the message and attaches the code of its
+//! key in the catalog, and are passed around as an [`Error`] until they're printed with [`print`].
+//! Anything else that's printed, e.g. errors from the filesystem, is `E_OTHER`.
+//!
+//! Commands print their errors as they come across them and then fail with a [`TuckrError`] that
+//! says how they failed, which the binary turns into its exit code.
+
+use crate::progress;
+use crate::table::{self, Format};
+use owo_colors::OwoColorize;
+use owo_colors::Stream::Stderr;
+use rust_i18n::t;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::fmt::{self, Display};
+use tabled::Tabled;
+
+/// How a command failed, the errors themselves have already been printed by then
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum TuckrError {
+    #[error("couldn't find the dotfiles directory")]
+    CouldntFindDotfiles,
+    #[error("no Configs, Hooks or Secrets directory is set up")]
+    NoSetupFolder,
+    #[error("no such file or directory")]
+    NoSuchFileOrDir,
+    #[error("failed to encrypt the files")]
+    EncryptionFailed,
+    #[error("failed to decrypt the files")]
+    DecryptionFailed,
+    #[error("the configuration file could not be parsed")]
+    InvalidConfig,
+    /// `tuckr verify-repo` or `tuckr doctor` found problems that break deploying the dotfiles
+    #[error("the dotfiles have problems that break deploying them")]
+    RepoHasErrors,
+    /// `tuckr verify-repo` or `tuckr doctor` only found problems that don't break deploying the dotfiles
+    #[error("the dotfiles have problems")]
+    RepoHasWarnings,
+    /// the preflight checks of the groups that `tuckr set` was going to deploy failed
+    #[error("the preflight checks failed")]
+    PreflightFailed,
+    /// another tuckr is already changing the dotfiles
+    #[error("another tuckr is already running")]
+    Locked,
+    /// files in the way of the dotfiles kept groups from being deployed
+    #[error("conflicting files were found")]
+    Conflicts,
+    /// a hook exited with an error
+    #[error("a hook failed")]
+    HookFailed,
+    /// some of the groups were deployed and some weren't
+    #[error("only some of the groups were deployed")]
+    PartialDeploy,
+    /// Ctrl-C was pressed
+    #[error("cancelled")]
+    Cancelled,
+    /// a command run on tuckr's behalf, e.g. as administrator, exited with this code
+    #[error("the command exited with {0}")]
+    Exited(u8),
+    #[error("the command failed")]
+    Failed,
+}
+
+/// code of the errors that don't come from any of the templates, e.g. errors from the filesystem
+pub const OTHER: &str = "E_OTHER";
+
+/// every error's code along with the key of its message in the `errors` section of the locales,
+/// codes can be added but never changed nor removed since wrappers rely on them
+pub const CATALOG: &[(&str, &str)] = &[
+    ("E_DOTFILES_NOT_FOUND", "couldnt_find_dotfiles_dir"),
+    ("E_DOTFILES_EXIST", "dotfiles_already_exist"),
+    ("E_NO_SETUP_DIR", "no_dir_setup_for_x"),
+    ("E_NOT_FOUND", "x_doesnt_exist"),
+    ("E_NOT_A_DIRECTORY", "not_a_dir"),
+    ("E_NOT_A_DOTFILE", "not_a_tuckr_dotfile"),
+    ("E_NOT_IN_TARGET", "not_in_target"),
+    ("E_TARGET_MISSING", "target_dir_missing"),
+    ("E_PATH_VAR_NOT_SET", "path_var_not_set"),
+    ("E_GROUP_NOT_FOUND", "no_group"),
+    ("E_GROUPS_NOT_FOUND", "following_groups_dont_exist"),
+    ("E_NO_BUNDLE", "no_bundle"),
+    ("E_GROUP_EMPTY", "no_dotfiles_for_group"),
+    ("E_GROUP_EXISTS", "group_already_exists"),
+    (
+        "E_GROUP_PATH",
+        "failed_to_get_group_relative_to_dotfiles_dir",
+    ),
+    ("E_GROUP_NOT_DEPLOYED", "group_is_not_deployed"),
+    ("E_INVALID_GROUP", "got_invalid_group"),
+    ("E_CONFLICT_FILE_EXISTS", "conflict_file_exists"),
+    ("E_LINK_FAILED", "failed_to_link_file"),
+    ("E_SYMLINK_FAILED", "failed_to_symlink_x"),
+    ("E_COPY_FAILED", "failed_to_copy_x"),
+    ("E_UNFOLD_FAILED", "failed_to_unfold"),
+    ("E_SANDBOX_NOT_EMPTY", "sandbox_not_empty"),
+    ("E_SYMLINKS_DISABLED", "symlinks_disabled"),
+    ("E_SYMLINKS_NOT_ALLOWED", "symlinks_not_allowed"),
+    ("E_DIR_NOT_WRITABLE", "dir_not_writable"),
+    ("E_ROLLED_BACK", "deploy_rolled_back"),
+    ("E_ROLLBACK_FAILED", "failed_to_roll_back"),
+    ("E_CANCELLED", "cancelled"),
+    ("E_LOCKED", "tuckr_is_running"),
+    ("E_LOCK_FAILED", "couldnt_lock"),
+    ("E_HOOKS_UNREADABLE", "could_not_read_hooks"),
+    ("E_HOOK_NONZERO", "hook_nonzero"),
+    ("E_PREFLIGHT_FAILED", "preflight_failed"),
+    ("E_NOT_ENOUGH_SPACE", "not_enough_space"),
+    ("E_COMMAND_NOT_FOUND", "command_not_found"),
+    ("E_NOT_WRITABLE", "not_writable"),
+    ("E_UNREACHABLE", "unreachable"),
+    ("E_WRONG_PASSWORD", "wrong_password"),
+    ("E_PASSWORDS_DONT_MATCH", "passwords_dont_match"),
+    ("E_EMPTY_PASSWORD", "empty_password"),
+    ("E_REKEY_VERIFICATION_FAILED", "rekey_verification_failed"),
+    ("E_SECRET_NOT_IN_TARGET", "secret_not_in_target"),
+    ("E_NO_RECIPIENTS", "no_recipients"),
+    ("E_KEYRING_UNSUPPORTED", "keyring_unsupported"),
+    ("E_KEYRING_NO_PASSWORD", "no_keyring_password"),
+    ("E_BACKUP_FAILED", "failed_to_backup"),
+    ("E_BACKUP_NOT_FOUND", "no_backup_called"),
+    ("E_NO_BACKUP_LIMITS", "no_backup_limits"),
+    ("E_NO_ESCALATION_PROGRAM", "no_escalation_program"),
+    ("E_ESCALATION_FAILED", "escalation_failed"),
+    ("E_NOTHING_TO_ELEVATE", "nothing_to_elevate"),
+    ("E_WINDOWS_ONLY", "elevate_windows_only"),
+    ("E_UNSUPPORTED_PLATFORM", "not_supported_on_this_platform"),
+    ("E_NOT_A_TERMINAL", "ui_needs_terminal"),
+    ("E_INVALID_REVISION", "invalid_revision"),
+    ("E_GIT_FAILED", "git_failed"),
+    ("E_COMMIT_FAILED", "failed_to_commit"),
+    ("E_STASH_INCOMPLETE", "stash_not_fully_deployed"),
+    ("E_INVALID_EXPERIMENT", "invalid_experiment"),
+    ("E_ALREADY_TRYING", "already_trying_group"),
+    ("E_NOT_TRYING", "not_trying_group"),
+    ("E_EDITOR_FAILED", "couldnt_open_editor"),
+    ("E_DAEMON_STATUS_FAILED", "daemon_status_failed"),
+    ("E_INVALID_INTERVAL", "invalid_interval"),
+    ("E_EXPORT_DIR_NOT_EMPTY", "export_dir_not_empty"),
+    ("E_IMPORT_SOURCE_MISSING", "import_source_missing"),
+    ("E_NOT_A_CONFLICT", "resolve_not_a_conflict"),
+    ("E_RESOLVE_DIRECTORY", "resolve_directory"),
+    ("E_RESOLVE_TEMPLATE", "resolve_template"),
+    ("E_NO_MERGE_TOOL", "resolve_no_tool"),
+    ("E_MERGE_TOOL_NOT_RUN", "couldnt_run_merge_tool"),
+    ("E_MERGE_TOOL_FAILED", "merge_tool_failed"),
+    ("E_INVALID_CONFIG", "invalid_config"),
+    ("E_NO_PACKAGE_MANAGER", "no_package_manager"),
+    ("E_UNKNOWN_PACKAGE_MANAGER", "unknown_package_manager"),
+    ("E_PACKAGES_FAILED", "failed_to_install_packages"),
+    ("E_PROFILE_CYCLE", "profile_base_cycle"),
+    ("E_PULL_BACK_TEMPLATE", "pull_back_template"),
+    ("E_NOT_A_HOOK", "not_a_hook"),
+    ("E_DEPRECATED", "deprecated_usage"),
+];
+
+/// An error message along with its code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    code: &'static str,
+    message: String,
+}
+
+impl Error {
+    /// Attaches the code of the catalog's `key` to the message that was translated from it,
+    /// use [`error!`] instead of calling this directly
+    pub fn new(key: &str, message: impl Into<String>) -> Self {
+        Error {
+            code: code_of(key),
+            message: message.into(),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// Puts what caused the error on the line above its message
+    pub fn caused_by(self, cause: impl Display) -> Self {
+        Error {
+            message: format!("{cause}\n{}", self.message),
+            ..self
+        }
+    }
+
+    /// Puts what the error is about in front of its message, e.g. the file it happened to
+    pub fn prefixed(self, prefix: impl Display) -> Self {
+        Error {
+            message: format!("{prefix}: {}", self.message),
+            ..self
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error {
+            code: OTHER,
+            message,
+        }
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        message.to_string().into()
+    }
+}
+
+impl From<&String> for Error {
+    fn from(message: &String) -> Self {
+        message.clone().into()
+    }
+}
+
+impl From<Cow<'_, str>> for Error {
+    fn from(message: Cow<'_, str>) -> Self {
+        message.into_owned().into()
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        err.to_string().into()
+    }
+}
+
+impl From<Error> for String {
+    fn from(err: Error) -> Self {
+        err.message
+    }
+}
+
+/// Translates the error message of a key in the `errors` section of the locales and attaches its code
+///
+/// e.g. `error!("no_group", group = group)`
+#[macro_export]
+macro_rules! error {
+    ($key:literal $(, $($args:tt)*)?) => {
+        $crate::errors::Error::new(
+            $key,
+            rust_i18n::t!(concat!("errors.", $key) $(, $($args)*)?),
+        )
+    };
+}
+
+/// Returns the code of the key in the `errors` section of the locales
+fn code_of(key: &str) -> &'static str {
+    CATALOG
+        .iter()
+        .find(|(_, catalog_key)| *catalog_key == key)
+        .map_or(OTHER, |(code, _)| code)
+}
+
+/// Prints an error, or a JSON object with its code and message with `--format json`
+pub fn print(err: impl Into<Error>) {
+    let err = err.into();
+    print_as(err.code, &err.message);
+}
+
+/// Prints an error that's known to have the code
+fn print_as(code: &str, message: &str) {
+    #[derive(Serialize)]
+    struct Error<'a> {
+        code: &'a str,
+        message: String,
+    }
+
+    // errors go above the progress bar that's being shown
+    progress::suspend(|| match table::format() {
+        Format::Json => eprintln!(
+            "{}",
+            serde_json::to_string(&Error {
+                code,
+                message: table::strip_colors(message),
+            })
+            .unwrap()
+        ),
+        _ => eprintln!("{}", message.if_supports_color(Stderr, |t| t.red())),
+    })
+}
+
+/// Lists every error code along with its message
+pub fn list_cmd() -> Result<(), TuckrError> {
+    #[derive(Tabled)]
+    struct ErrorRow {
+        #[tabled(rename = "Code")]
+        code: &'static str,
+        #[tabled(rename = "Message")]
+        message: String,
+    }
+
+    let rows: Vec<_> = CATALOG
+        .iter()
+        .map(|(code, key)| ErrorRow {
+            code,
+            message: t!(format!("errors.{key}")).into_owned(),
+        })
+        .collect();
+    table::print(&rows);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_have_stable_codes() {
+        assert_eq!(
+            crate::error!("no_group", group = "nvim").code(),
+            "E_GROUP_NOT_FOUND"
+        );
+        let err = crate::error!("failed_to_link_file", file = "~/.zshrc").prefixed("zsh");
+        assert_eq!(err.code(), "E_LINK_FAILED");
+        assert!(err.to_string().starts_with("zsh: "));
+        assert_eq!(Error::from("permission denied (os error 13)").code(), OTHER);
+
+        // every code is unique and has a message
+        let mut codes: Vec<_> = CATALOG.iter().map(|(code, _)| code).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), CATALOG.len());
+        for (_, key) in CATALOG {
+            let key = format!("errors.{key}");
+            assert_ne!(t!(&key), key);
+        }
+    }
+}
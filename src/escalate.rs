@@ -0,0 +1,675 @@
+//! Deploys what needs root with sudo or doas
+//!
+//! Symlinks that go where the user can't write to, e.g. the Root group's files in /etc, are put
+//! off instead of failing. Once everything else is deployed they're written to a plan file and
+//! `tuckr --apply-plan <file>` is run with sudo or doas, so that only that part runs as root and
+//! the password is only asked for once.
+//!
+//! Windows has no such program, instead the permissions are checked before creating symlinks so
+//! that the error says how to get them. The command that failed is saved and `tuckr elevate` runs
+//! it again as administrator through UAC, with `tuckr --run-elevated <file>` in between to bring
+//! along the working directory and tuckr's environment variables and to collect the output.
+
+use crate::config;
+use crate::error;
+use crate::errors::{self, TuckrError};
+use crate::fileops;
+use crate::transaction::{self, Transaction};
+use owo_colors::OwoColorize;
+use owo_colors::Stream::{Stderr, Stdout};
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// programs that are tried in order when `escalate_with` isn't set in tuckr.toml
+const PROGRAMS: [&str; 2] = ["sudo", "doas"];
+
+/// A change that has to be made as root
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Operation {
+    /// symlinks `source` to `target`, creating the target's missing parents
+    Symlink { source: PathBuf, target: PathBuf },
+    /// removes a symlink
+    Remove { path: PathBuf },
+}
+
+static PLAN: Mutex<Vec<Operation>> = Mutex::new(Vec::new());
+
+/// Set when a symlink couldn't be created for lack of permissions on Windows
+static NEEDS_ELEVATION: AtomicBool = AtomicBool::new(false);
+
+/// A command to run as administrator along with what it needs from the tuckr that asked for it
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct ElevateRequest {
+    cwd: PathBuf,
+    args: Vec<String>,
+    /// tuckr's environment variables, e.g. $TUCKR_HOME, they aren't passed on by UAC
+    env: Vec<(String, String)>,
+}
+
+impl ElevateRequest {
+    fn new(args: Vec<String>) -> Result<Self, String> {
+        Ok(Self {
+            cwd: std::env::current_dir().map_err(|e| e.to_string())?,
+            args,
+            env: std::env::vars()
+                .filter(|(var, _)| var.starts_with("TUCKR_"))
+                .collect(),
+        })
+    }
+}
+
+/// Returns true if tuckr is running as root
+#[cfg(target_family = "unix")]
+pub fn is_root() -> bool {
+    // SAFETY: geteuid can't fail
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn is_root() -> bool {
+    false
+}
+
+/// Returns true if the path can only be created or removed as root
+#[cfg(target_family = "unix")]
+pub fn needs_privileges(path: &Path) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+
+    if is_root() {
+        return false;
+    }
+
+    // the path's missing parents are created in the closest directory that exists
+    let Some(dir) = path.ancestors().skip(1).find(|dir| dir.exists()) else {
+        return false;
+    };
+    let Ok(dir) = std::ffi::CString::new(dir.as_os_str().as_bytes()) else {
+        return false;
+    };
+
+    // SAFETY: dir is a valid nul terminated string
+    unsafe { libc::access(dir.as_ptr(), libc::W_OK) != 0 }
+}
+
+/// Files are only deployed as root on unix-like systems
+#[cfg(not(target_family = "unix"))]
+pub fn needs_privileges(_path: &Path) -> bool {
+    false
+}
+
+/// Checks that a symlink can be created at `target` before trying to
+///
+/// Windows only lets administrators create symlinks unless Developer Mode is on, and the target's
+/// directory might not let the user add files to it. The error says how to get the permissions.
+#[cfg(target_family = "windows")]
+pub fn check_link_permissions(target: &Path) -> Result<(), String> {
+    if !symlinks_allowed() {
+        NEEDS_ELEVATION.store(true, Ordering::Relaxed);
+        return Err(error!("symlinks_not_allowed", file = target.display()));
+    }
+
+    // the target's missing parents are created in the closest directory that exists
+    if let Some(dir) = target.ancestors().skip(1).find(|dir| dir.exists())
+        && !can_add_files(dir)
+    {
+        NEEDS_ELEVATION.store(true, Ordering::Relaxed);
+        return Err(error!(
+            "dir_not_writable",
+            file = target.display(),
+            dir = dir.display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Symlinks are only checked on Windows, elsewhere what needs root is deferred
+#[cfg(not(target_family = "windows"))]
+pub fn check_link_permissions(_target: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+/// Returns true if the user can create symlinks, found out once by creating one in the temporary directory
+#[cfg(target_family = "windows")]
+pub fn symlinks_allowed() -> bool {
+    static ALLOWED: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+    const ERROR_PRIVILEGE_NOT_HELD: i32 = 1314;
+
+    *ALLOWED.get_or_init(|| {
+        let dir = std::env::temp_dir().join(format!("tuckr-symlink-check-{}", std::process::id()));
+        let allowed = fs::create_dir_all(&dir)
+            .and_then(|_| std::os::windows::fs::symlink_file(dir.join("target"), dir.join("link")))
+            .map_or_else(
+                |err| err.raw_os_error() != Some(ERROR_PRIVILEGE_NOT_HELD),
+                |_| true,
+            );
+        _ = fs::remove_dir_all(&dir);
+        allowed
+    })
+}
+
+/// Symlinks can always be created outside of Windows
+#[cfg(not(target_family = "windows"))]
+pub fn symlinks_allowed() -> bool {
+    true
+}
+
+/// Returns true if the directory's ACL lets the user add files and directories to it
+///
+/// The directory is opened asking for just those rights, which Windows checks against its ACL
+#[cfg(target_family = "windows")]
+fn can_add_files(dir: &Path) -> bool {
+    use std::os::windows::fs::OpenOptionsExt;
+    const FILE_ADD_FILE: u32 = 0x2;
+    const FILE_ADD_SUBDIRECTORY: u32 = 0x4;
+    // needed to open directories
+    const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+
+    fs::OpenOptions::new()
+        .access_mode(FILE_ADD_FILE | FILE_ADD_SUBDIRECTORY)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+        .open(dir)
+        .map_or_else(
+            |err| err.kind() != std::io::ErrorKind::PermissionDenied,
+            |_| true,
+        )
+}
+
+/// Returns true if a symlink couldn't be created for lack of permissions
+pub fn needs_elevation() -> bool {
+    NEEDS_ELEVATION.load(Ordering::Relaxed)
+}
+
+/// Where the command that failed for lack of permissions is saved for `tuckr elevate`
+///
+/// There's nowhere to save it without a cache directory, a shared one would let others pick what's run
+fn failed_command_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("tuckr").join("elevate.json"))
+}
+
+/// Creates a new directory that only this user can get into, with a name that can't be guessed
+///
+/// It's made in tuckr's cache directory, or in the temporary directory if there's none.
+/// Creating it fails if the path is already taken, so nobody can have put anything in it beforehand
+fn private_dir(prefix: &str) -> std::io::Result<PathBuf> {
+    let parent = match dirs::cache_dir() {
+        Some(dir) => {
+            let dir = dir.join("tuckr");
+            fs::create_dir_all(&dir)?;
+            dir
+        }
+        None => std::env::temp_dir(),
+    };
+    let dir = parent.join(format!(
+        "{prefix}-{}-{:016x}",
+        std::process::id(),
+        rand::random::<u64>()
+    ));
+
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(target_family = "unix")]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(&dir)?;
+    Ok(dir)
+}
+
+fn write_request(path: &Path, request: &ElevateRequest) -> Result<(), String> {
+    let contents = serde_json::to_string_pretty(request).map_err(|e| e.to_string())?;
+    fs::create_dir_all(path.parent().unwrap())
+        .and_then(|_| fs::write(path, contents))
+        .map_err(|e| format!("{}: {e}", path.display()))
+}
+
+fn read_request(path: &Path) -> Result<ElevateRequest, String> {
+    fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|request| serde_json::from_str(&request).map_err(|e| e.to_string()))
+        .map_err(|err| format!("{}: {err}", path.display()))
+}
+
+/// Saves this tuckr's command so that `tuckr elevate` can run it again as administrator
+pub fn save_failed_command() -> Result<(), String> {
+    let request = ElevateRequest::new(std::env::args().skip(1).collect())?;
+    let Some(path) = failed_command_path() else {
+        return Err(t!("errors.no_cache_dir").into_owned());
+    };
+    write_request(&path, &request)
+}
+
+/// Runs a command as administrator, the last one that failed for lack of permissions if none is given
+///
+/// Only Windows needs this, elsewhere what needs root is deployed with sudo or doas on its own
+pub fn elevate_cmd(dry_run: bool, args: Vec<String>) -> Result<(), TuckrError> {
+    let failed_command = failed_command_path();
+    let (request, saved) = match (args.is_empty(), &failed_command) {
+        (true, Some(failed_command)) if failed_command.exists() => {
+            (read_request(failed_command), true)
+        }
+        (true, _) => {
+            errors::print(error!("nothing_to_elevate"));
+            return Err(TuckrError::Failed);
+        }
+        (false, _) => (ElevateRequest::new(args), false),
+    };
+
+    let request = request.map_err(|err| {
+        errors::print(err);
+        TuckrError::Failed
+    })?;
+
+    if dry_run {
+        eprintln!(
+            "{} `tuckr {}` in `{}`",
+            "elevating".if_supports_color(Stderr, |t| t.yellow()),
+            request.args.join(" "),
+            request.cwd.display()
+        );
+        return Ok(());
+    }
+
+    if !cfg!(target_family = "windows") {
+        errors::print(error!("elevate_windows_only"));
+        return Err(TuckrError::Failed);
+    }
+
+    println!(
+        "{}",
+        t!("info.elevating", command = request.args.join(" "))
+            .if_supports_color(Stdout, |t| t.yellow())
+    );
+
+    let request_dir = private_dir("elevate").map_err(|err| {
+        errors::print(err);
+        TuckrError::Failed
+    })?;
+    let request_file = request_dir.join("request.json");
+    let log_file = request_file.with_extension("log");
+    write_request(&request_file, &request).map_err(|err| {
+        _ = fs::remove_dir_all(&request_dir);
+        errors::print(err);
+        TuckrError::Failed
+    })?;
+
+    let exe = std::env::current_exe().map_err(|err| {
+        errors::print(err);
+        TuckrError::Failed
+    })?;
+
+    // single quotes are escaped by doubling them in powershell, paths can't have double quotes on Windows
+    let quote = |path: &Path| path.display().to_string().replace('\'', "''");
+    let status = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command"])
+        .arg(format!(
+            "$p = Start-Process -FilePath '{}' -ArgumentList '--run-elevated \"{}\"' -Verb RunAs -Wait -PassThru -WindowStyle Hidden; exit $p.ExitCode",
+            quote(&exe),
+            quote(&request_file)
+        ))
+        .status();
+
+    // the elevated tuckr runs in a window of its own, so its output is shown here
+    if let Ok(output) = fs::read_to_string(&log_file) {
+        print!("{output}");
+    }
+    _ = fs::remove_dir_all(&request_dir);
+
+    match status {
+        Ok(status) if status.success() => {
+            if saved && let Some(failed_command) = failed_command {
+                _ = fs::remove_file(failed_command);
+            }
+            Ok(())
+        }
+        Ok(_) => Err(TuckrError::Failed),
+        Err(err) => {
+            errors::print(format!("powershell: {err}"));
+            Err(TuckrError::Failed)
+        }
+    }
+}
+
+/// Runs the command written by the tuckr that ran this one as administrator
+///
+/// Its output goes to a log file next to the request for the tuckr that asked for it to show
+pub fn run_elevated_cmd(request_file: &Path) -> Result<(), TuckrError> {
+    let request = read_request(request_file).map_err(|err| {
+        errors::print(err);
+        TuckrError::Failed
+    })?;
+
+    let log = fs::File::create(request_file.with_extension("log"))
+        .and_then(|log| Ok((log.try_clone()?, log)))
+        .map_err(|err| {
+            errors::print(err);
+            TuckrError::Failed
+        })?;
+
+    let status = std::env::current_exe().and_then(|exe| {
+        Command::new(exe)
+            .args(&request.args)
+            .current_dir(&request.cwd)
+            .envs(request.env)
+            .stdout(log.0)
+            .stderr(log.1)
+            .status()
+    });
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(TuckrError::Exited(status.code().unwrap_or(1) as u8)),
+        Err(err) => {
+            errors::print(err);
+            Err(TuckrError::Failed)
+        }
+    }
+}
+
+/// Puts off a change until the plan is applied as root
+pub fn defer(operation: Operation) {
+    PLAN.lock().unwrap().push(operation);
+}
+
+/// Forgets the changes that were put off, e.g. when what didn't need root was rolled back
+pub fn discard() {
+    PLAN.lock().unwrap().clear();
+}
+
+/// Returns the program used to run tuckr as root
+pub fn find_program() -> Option<String> {
+    if let Some(program) = &config::get().escalate_with {
+        return Some(program.clone());
+    }
+
+    PROGRAMS
+        .into_iter()
+        .find(|program| which(program))
+        .map(String::from)
+}
+
+fn which(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+}
+
+/// Applies the changes that were put off as root, does nothing if there aren't any
+pub fn apply_deferred() -> Result<(), errors::Error> {
+    let plan = std::mem::take(&mut *PLAN.lock().unwrap());
+    if plan.is_empty() {
+        return Ok(());
+    }
+
+    let Some(program) = find_program() else {
+        return Err(error!("no_escalation_program", count = plan.len()));
+    };
+
+    let plan_dir = private_dir("plan").map_err(|e| e.to_string())?;
+    let plan_file = plan_dir.join("plan.json");
+    if let Err(err) = write_plan(&plan_file, &plan) {
+        _ = fs::remove_dir_all(&plan_dir);
+        return Err(err.into());
+    }
+
+    println!(
+        "{}",
+        t!("info.escalating", count = plan.len(), program = program)
+            .if_supports_color(Stdout, |t| t.yellow())
+    );
+
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let mut command = Command::new(&program);
+    command.arg(exe);
+    if !transaction::rollback_enabled() {
+        command.arg("--no-rollback");
+    }
+    let status = command.arg("--apply-plan").arg(&plan_file).status();
+    _ = fs::remove_dir_all(&plan_dir);
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(_) => Err(error!("escalation_failed")),
+        Err(err) => Err(format!("{program}: {err}").into()),
+    }
+}
+
+/// Writes the plan so that only its owner can read or change it
+///
+/// The file must not exist yet, so a file or symlink put there by someone else is never written to
+fn write_plan(path: &Path, plan: &[Operation]) -> Result<(), String> {
+    let contents = serde_json::to_string_pretty(plan).map_err(|e| e.to_string())?;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(target_family = "unix")]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map_err(|e| format!("{}: {e}", path.display()))
+}
+
+fn apply(operation: &Operation, transaction: &mut Transaction) -> std::io::Result<()> {
+    match operation {
+        Operation::Symlink { source, target } => {
+            if let Some(parent) = target.parent() {
+                transaction.create_dir_all(parent)?;
+            }
+            fileops::symlink(source, target)?;
+            transaction.created(target);
+        }
+        Operation::Remove { path } => {
+            // only tuckr's symlinks are ever removed
+            if !path.is_symlink() {
+                return Err(std::io::Error::other("not a symlink"));
+            }
+            transaction.remove(path)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the uid of the user that ran sudo or doas, or this tuckr's own if it wasn't run by them
+#[cfg(target_family = "unix")]
+fn invoking_uid() -> u32 {
+    if let Some(uid) = std::env::var("SUDO_UID")
+        .ok()
+        .and_then(|uid| uid.parse().ok())
+    {
+        return uid;
+    }
+
+    if let Ok(user) = std::env::var("DOAS_USER")
+        && let Ok(user) = std::ffi::CString::new(user)
+    {
+        // SAFETY: user is a valid nul terminated string and the entry is read before any other call
+        let uid = unsafe {
+            let passwd = libc::getpwnam(user.as_ptr());
+            (!passwd.is_null()).then(|| (*passwd).pw_uid)
+        };
+        if let Some(uid) = uid {
+            return uid;
+        }
+    }
+
+    // SAFETY: getuid can't fail
+    unsafe { libc::getuid() }
+}
+
+/// Reads a plan, refusing it unless it's a file of the user that asked for it that only they can change
+///
+/// Anything else could have been written by someone else to get tuckr to run it as root
+fn read_plan(plan_file: &Path) -> Result<String, String> {
+    let mut options = fs::OpenOptions::new();
+    options.read(true);
+    #[cfg(target_family = "unix")]
+    std::os::unix::fs::OpenOptionsExt::custom_flags(&mut options, libc::O_NOFOLLOW);
+    let mut file = options.open(plan_file).map_err(|e| e.to_string())?;
+
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = file.metadata().map_err(|e| e.to_string())?;
+        if !metadata.is_file() || metadata.uid() != invoking_uid() || metadata.mode() & 0o022 != 0 {
+            return Err(t!("errors.unsafe_plan").into_owned());
+        }
+    }
+
+    let mut plan = String::new();
+    std::io::Read::read_to_string(&mut file, &mut plan).map_err(|e| e.to_string())?;
+    Ok(plan)
+}
+
+/// Applies a plan written by the tuckr that ran this one with sudo or doas
+///
+/// The whole plan is rolled back if any of it fails, unless `--no-rollback` is used
+pub fn apply_plan_cmd(plan_file: &Path) -> Result<(), TuckrError> {
+    let plan: Vec<Operation> = match read_plan(plan_file)
+        .and_then(|plan| serde_json::from_str(&plan).map_err(|e| e.to_string()))
+    {
+        Ok(plan) => plan,
+        Err(err) => {
+            errors::print(format!("{}: {err}", plan_file.display()));
+            return Err(TuckrError::Failed);
+        }
+    };
+
+    let mut transaction = Transaction::new();
+    for operation in &plan {
+        if let Err(err) = apply(operation, &mut transaction) {
+            let path = match operation {
+                Operation::Symlink { target, .. } => target,
+                Operation::Remove { path } => path,
+            };
+            errors::print(format!("{}: {err}", path.display()));
+
+            if transaction::rollback_enabled() {
+                for err in transaction.rollback() {
+                    errors::print(error!("failed_to_roll_back", err = err));
+                }
+            }
+            transaction.commit();
+            return Err(TuckrError::Failed);
+        }
+    }
+
+    transaction.commit();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_plan() {
+        let dir = std::env::temp_dir()
+            .join("tuckr")
+            .join("escalate")
+            .join(std::process::id().to_string());
+        _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("source");
+        fs::write(&source, "dotfile").unwrap();
+
+        let plan = [
+            Operation::Symlink {
+                source: source.clone(),
+                target: dir.join("etc/app/config"),
+            },
+            Operation::Symlink {
+                source: source.clone(),
+                target: dir.join("link"),
+            },
+        ];
+        let plan_file = dir.join("plan.json");
+        write_plan(&plan_file, &plan).unwrap();
+        apply_plan_cmd(&plan_file).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("etc/app/config")).unwrap(),
+            "dotfile"
+        );
+
+        // removing a file that isn't a symlink fails and undoes the rest of the plan
+        let plan = [
+            Operation::Remove {
+                path: dir.join("link"),
+            },
+            Operation::Remove {
+                path: source.clone(),
+            },
+        ];
+        fs::remove_file(&plan_file).unwrap();
+        write_plan(&plan_file, &plan).unwrap();
+        assert!(apply_plan_cmd(&plan_file).is_err());
+        assert!(dir.join("link").is_symlink());
+        assert!(source.exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn unsafe_plans_are_refused() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = private_dir("plan-test").unwrap();
+        let plan_file = dir.join("plan.json");
+        let target = dir.join("link");
+        let plan = [Operation::Symlink {
+            source: dir.clone(),
+            target: target.clone(),
+        }];
+
+        // plans are never written over something that's already there
+        write_plan(&plan_file, &plan).unwrap();
+        assert!(write_plan(&plan_file, &plan).is_err());
+        assert_eq!(
+            fs::metadata(&dir).unwrap().permissions().mode() & 0o777,
+            0o700
+        );
+
+        // others could have changed a plan they can write to
+        fs::set_permissions(&plan_file, fs::Permissions::from_mode(0o666)).unwrap();
+        assert!(apply_plan_cmd(&plan_file).is_err());
+        assert!(!target.is_symlink());
+
+        // or pointed a symlink at a plan of theirs
+        fs::set_permissions(&plan_file, fs::Permissions::from_mode(0o600)).unwrap();
+        let symlink = dir.join("symlink.json");
+        std::os::unix::fs::symlink(&plan_file, &symlink).unwrap();
+        assert!(apply_plan_cmd(&symlink).is_err());
+        assert!(!target.is_symlink());
+
+        apply_plan_cmd(&plan_file).unwrap();
+        assert!(target.is_symlink());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn elevate_request() {
+        let dir = std::env::temp_dir().join(format!("tuckr-elevate-test-{}", std::process::id()));
+        let request_file = dir.join("elevate.json");
+
+        let request = ElevateRequest::new(vec!["add".into(), "Root".into()]).unwrap();
+        assert_eq!(request.cwd, std::env::current_dir().unwrap());
+        assert!(request.env.iter().all(|(var, _)| var.starts_with("TUCKR_")));
+
+        write_request(&request_file, &request).unwrap();
+        assert_eq!(read_request(&request_file).unwrap(), request);
+
+        assert!(elevate_cmd(true, vec!["add".into()]).is_ok());
+        #[cfg(not(target_family = "windows"))]
+        assert!(elevate_cmd(false, vec!["add".into()]).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,405 @@
+//! Tries out groups from other git branches
+//!
+//! `tuckr try <group>@<branch>` checks the branch out into a git worktree and symlinks the group's
+//! files from there in place of the current deployment. The experiment is recorded in the state so
+//! that the rest of tuckr leaves the group alone until `tuckr try --end` removes the worktree's
+//! symlinks and deploys the group from the dotfiles again.
+//!
+//! Files are symlinked one by one so the worktree can be edited and committed to like any other checkout.
+
+use crate::config;
+use crate::dotfiles::{self, Dotfile};
+use crate::error;
+use crate::errors::{self, TuckrError};
+use crate::fileops::{self, DirWalk};
+use crate::history;
+use crate::state::{self, Experiment, State};
+use crate::symlinks;
+use crate::templates;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::{Stderr, Stdout};
+use rust_i18n::t;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Splits `<group>@<branch>`
+fn parse_experiment(experiment: &str) -> Option<(&str, &str)> {
+    match experiment.split_once('@') {
+        Some((group, branch)) if !group.is_empty() && !branch.is_empty() => Some((group, branch)),
+        _ => None,
+    }
+}
+
+/// Returns where the worktree for an experiment is checked out to
+///
+/// Worktrees are kept outside of the dotfiles so that they're never picked up as dotfiles
+fn get_worktree_path(dotfiles_dir: &Path, group: &str) -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("tuckr")
+        .join("try")
+        .join(dotfiles_dir.file_name().unwrap())
+        .join(group)
+}
+
+fn save(state: &State) -> Result<(), TuckrError> {
+    state.save().map_err(|err| {
+        errors::print(err);
+        TuckrError::Failed
+    })
+}
+
+/// Returns the group and its conditional groups that are in the worktree, highest priority first
+fn get_worktree_groups(worktree_configs: &Path, group: &str) -> Result<Vec<String>, errors::Error> {
+    let mut groups: Vec<String> = fs::read_dir(worktree_configs)
+        .map_err(|e| format!("{}: {e}", worktree_configs.display()))?
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|g| {
+            (g == group || dotfiles::group_without_target(g) == group)
+                && dotfiles::group_is_valid_target(g)
+        })
+        .collect();
+
+    if groups.is_empty() {
+        return Err(error!("x_doesnt_exist", x = group));
+    }
+
+    groups.sort_by_key(|g| std::cmp::Reverse(dotfiles::get_group_priority(g)));
+    Ok(groups)
+}
+
+/// Symlinks the groups' files from the worktree's Configs, returns the symlinks that were created
+///
+/// Files of groups that come first take precedence like conditional groups do with `tuckr add`
+fn symlink_from_worktree(
+    dotfiles_dir: &Path,
+    worktree_configs: &Path,
+    groups: &[String],
+) -> Vec<PathBuf> {
+    let mut symlinked = Vec::new();
+    let mut targets = BTreeSet::new();
+
+    for group in groups {
+        for file in DirWalk::new(worktree_configs.join(group)) {
+            if file.is_dir() {
+                continue;
+            }
+
+            if templates::is_template(&file) {
+                eprintln!(
+                    "{}",
+                    t!("warn.skipping_template", file = file.display())
+                        .if_supports_color(Stderr, |t| t.yellow())
+                );
+                continue;
+            }
+
+            // the file is mapped as if it was in the dotfiles to find out where it's deployed to
+            let relative_path = file.strip_prefix(worktree_configs).unwrap();
+            let target = match Dotfile::try_from(dotfiles_dir.join("Configs").join(relative_path))
+                .and_then(|dotfile| dotfile.to_target_path())
+            {
+                Ok(target) => target,
+                Err(err) => {
+                    errors::print(err);
+                    continue;
+                }
+            };
+
+            if !targets.insert(target.clone()) {
+                continue;
+            }
+
+            if target.exists() || target.is_symlink() {
+                eprintln!(
+                    "{} `{}` as it already exists",
+                    "ignoring".if_supports_color(Stderr, |t| t.yellow()),
+                    target.display()
+                );
+                continue;
+            }
+
+            let result = match target.parent() {
+                Some(parent) => fs::create_dir_all(parent),
+                None => Ok(()),
+            }
+            .and_then(|_| fileops::symlink(&file, &target));
+
+            match result {
+                Ok(_) => symlinked.push(target),
+                Err(err) => errors::print(format!("{}: {err}", target.display())),
+            }
+        }
+    }
+
+    symlinked
+}
+
+/// Deploys a group from another branch in place of its current deployment
+pub fn try_cmd(profile: Option<String>, dry_run: bool, experiment: &str) -> Result<(), TuckrError> {
+    let Some((group, branch)) = parse_experiment(experiment) else {
+        eprintln!("{}", error!("invalid_experiment", experiment = experiment));
+        return Err(TuckrError::Failed);
+    };
+
+    // experiments are symlinked into the branch's worktree
+    if config::get().no_symlinks {
+        errors::print(error!("symlinks_disabled"));
+        return Err(TuckrError::Failed);
+    }
+
+    let dotfiles_dir = match dotfiles::get_dotfiles_path(profile.clone()) {
+        Ok(dir) => dir,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::CouldntFindDotfiles);
+        }
+    };
+
+    if let Some(experiment) = state::load_or_report(profile.clone())?
+        .experiments
+        .get(group)
+    {
+        errors::print(error!(
+            "already_trying_group",
+            group = group,
+            branch = experiment.branch
+        ));
+        return Err(TuckrError::Failed);
+    }
+
+    let worktree = get_worktree_path(&dotfiles_dir, group);
+
+    if dry_run {
+        eprintln!(
+            "{} `{branch}` to `{}`",
+            "checking out".if_supports_color(Stderr, |t| t.green()),
+            worktree.display()
+        );
+        eprintln!(
+            "{} `{group}` from `{branch}`",
+            "symlinking".if_supports_color(Stderr, |t| t.green())
+        );
+        return Ok(());
+    }
+
+    fn report_err(err: impl Into<errors::Error>) -> TuckrError {
+        errors::print(err);
+        TuckrError::Failed
+    }
+
+    let copied = symlinks::get_deployed_groups(profile.clone())?
+        .into_iter()
+        .filter(|(g, _)| g == group || dotfiles::group_without_target(g) == group)
+        .map(|(_, copied)| copied)
+        .reduce(|a, b| a || b);
+
+    // the dotfiles might be a subdirectory of the repository
+    let prefix =
+        history::git(&dotfiles_dir, &["rev-parse", "--show-prefix"], None).map_err(report_err)?;
+    history::git(
+        &dotfiles_dir,
+        &["worktree", "add", worktree.to_str().unwrap(), branch],
+        None,
+    )
+    .map_err(report_err)?;
+
+    let remove_worktree = || {
+        _ = history::git(
+            &dotfiles_dir,
+            &["worktree", "remove", worktree.to_str().unwrap()],
+            None,
+        );
+    };
+
+    let worktree_configs = worktree.join(prefix.trim()).join("Configs");
+    let groups = match get_worktree_groups(&worktree_configs, group) {
+        Ok(groups) => groups,
+        Err(err) => {
+            remove_worktree();
+            return Err(report_err(err));
+        }
+    };
+
+    if copied.is_some()
+        && let Err(code) = symlinks::remove_cmd(profile.clone(), false, &[group.into()], &[])
+    {
+        remove_worktree();
+        return Err(code);
+    }
+
+    let files = symlink_from_worktree(&dotfiles_dir, &worktree_configs, &groups);
+
+    // removing the group saves the state so it has to be loaded afterwards
+    let mut state = state::load_or_report(profile)?;
+    state.experiments.insert(
+        group.into(),
+        Experiment {
+            branch: branch.into(),
+            worktree,
+            files,
+            copied,
+        },
+    );
+    save(&state)?;
+
+    println!(
+        "{}",
+        t!(
+            "info.trying_group",
+            group = group,
+            branch = branch,
+            cmd = "tuckr try --end"
+        )
+        .if_supports_color(Stdout, |t| t.green())
+    );
+    Ok(())
+}
+
+/// Ends experiments and deploys their groups from the dotfiles again, every experiment is ended if no group is given
+///
+/// Worktrees with uncommitted changes are kept so that nothing is lost
+pub fn end_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    group: Option<String>,
+) -> Result<(), TuckrError> {
+    let dotfiles_dir = match dotfiles::get_dotfiles_path(profile.clone()) {
+        Ok(dir) => dir,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::CouldntFindDotfiles);
+        }
+    };
+
+    let mut state = state::load_or_report(profile.clone())?;
+
+    let groups: Vec<String> = match group {
+        Some(group) if state.experiments.contains_key(&group) => vec![group],
+        Some(group) => {
+            errors::print(error!("not_trying_group", group = group));
+            return Err(TuckrError::Failed);
+        }
+        None => state.experiments.keys().cloned().collect(),
+    };
+
+    if groups.is_empty() {
+        println!(
+            "{}",
+            t!("info.no_experiments").if_supports_color(Stdout, |t| t.yellow())
+        );
+        return Ok(());
+    }
+
+    for group in groups {
+        let experiment = state.experiments[&group].clone();
+
+        for file in &experiment.files {
+            let points_to_worktree = fs::read_link(file)
+                .is_ok_and(|original| original.starts_with(&experiment.worktree));
+            if !points_to_worktree {
+                continue;
+            }
+
+            if dry_run {
+                eprintln!(
+                    "{} `{}`",
+                    "removing".if_supports_color(Stderr, |t| t.red()),
+                    file.display()
+                );
+            } else if let Err(err) = fs::remove_file(file) {
+                errors::print(format!("{}: {err}", file.display()));
+            }
+        }
+
+        if dry_run {
+            eprintln!(
+                "{} `{}`",
+                "removing worktree".if_supports_color(Stderr, |t| t.red()),
+                experiment.worktree.display()
+            );
+            continue;
+        }
+
+        if let Err(err) = history::git(
+            &dotfiles_dir,
+            &["worktree", "remove", experiment.worktree.to_str().unwrap()],
+            None,
+        ) {
+            eprintln!(
+                "{}",
+                t!(
+                    "warn.worktree_was_kept",
+                    worktree = experiment.worktree.display(),
+                    err_msg = err
+                )
+                .if_supports_color(Stderr, |t| t.yellow())
+            );
+        }
+
+        state.experiments.remove(&group);
+        save(&state)?;
+
+        if let Some(copy) = experiment.copied {
+            symlinks::add_cmd(
+                profile.clone(),
+                false,
+                false,
+                copy,
+                std::slice::from_ref(&group),
+                &[],
+                false,
+                false,
+                true,
+            )?;
+
+            // deploying the group saves the state so it has to be loaded again
+            state = state::load_or_report(profile.clone())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists the groups that are being tried out, only for `groups` if any are given
+pub fn print_experiments(state: &State, groups: Option<&[String]>) {
+    let experiments: Vec<_> = state
+        .experiments
+        .iter()
+        .filter(|(group, _)| groups.is_none_or(|groups| groups.contains(group)))
+        .collect();
+
+    if experiments.is_empty() {
+        return;
+    }
+
+    println!("{}:", t!("info.experimental_groups"));
+    for (group, experiment) in experiments {
+        println!(
+            "\t{} ({})",
+            group.if_supports_color(Stdout, |t| t.yellow()),
+            experiment.branch
+        );
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_experiments() {
+        assert_eq!(parse_experiment("nvim@rewrite"), Some(("nvim", "rewrite")));
+        assert_eq!(
+            parse_experiment("nvim@feature/lua"),
+            Some(("nvim", "feature/lua"))
+        );
+        assert_eq!(parse_experiment("nvim"), None);
+        assert_eq!(parse_experiment("nvim@"), None);
+        assert_eq!(parse_experiment("@rewrite"), None);
+    }
+}
@@ -0,0 +1,404 @@
+//! Exports the dotfiles for other dotfile managers
+//!
+//! `tuckr to-stow <dir>` turns every group in Configs into a GNU Stow package, which is laid out
+//! like the group but with templates rendered and the names files are deployed with. Since stow
+//! packages and groups share their layout, the packages can be moved back into Configs as groups.
+//!
+//! With `--chezmoi` the groups deployed on this machine are merged into a chezmoi source directory
+//! instead, whose file names carry what chezmoi needs to know, e.g. `.ssh/config` -> `private_dot_ssh/config`.
+//!
+//! Neither of them gets the secrets, since they'd be written unencrypted, or the hooks.
+
+use crate::dotfiles::{self, Dotfile, DotfileType};
+use crate::error;
+use crate::errors::{self, TuckrError};
+use crate::fileops::{self, DirWalk};
+use crate::templates;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::{Stderr, Stdout};
+use rust_i18n::t;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Prefixes chezmoi gives a meaning to, names starting with them have to be marked as literal
+const CHEZMOI_PREFIXES: &[&str] = &[
+    "after_",
+    "before_",
+    "create_",
+    "dot_",
+    "empty_",
+    "encrypted_",
+    "exact_",
+    "executable_",
+    "external_",
+    "literal_",
+    "modify_",
+    "once_",
+    "onchange_",
+    "private_",
+    "readonly_",
+    "remove_",
+    "run_",
+    "symlink_",
+];
+
+#[cfg(target_family = "unix")]
+fn mode(path: &Path) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.permissions().mode())
+        .unwrap_or(0o644)
+}
+
+#[cfg(not(target_family = "unix"))]
+fn mode(_path: &Path) -> u32 {
+    0o644
+}
+
+/// Returns the name chezmoi expects in its source directory for a file or directory of the target,
+/// `source` is the file in the dotfiles that its attributes are taken from
+fn chezmoi_name(name: &str, source: Option<&Path>) -> String {
+    let mut attributes = String::new();
+    if let Some(source) = source {
+        let mode = mode(source);
+        if mode & 0o077 == 0 {
+            attributes.push_str("private_");
+        }
+        if !source.is_dir() {
+            if source.metadata().is_ok_and(|m| m.len() == 0) {
+                attributes.push_str("empty_");
+            }
+            if mode & 0o111 != 0 {
+                attributes.push_str("executable_");
+            }
+        }
+    }
+
+    let name = match name.strip_prefix('.') {
+        Some(name) => format!("{attributes}dot_{name}"),
+        None if CHEZMOI_PREFIXES.iter().any(|p| name.starts_with(p)) => {
+            format!("{attributes}literal_{name}")
+        }
+        None => format!("{attributes}{name}"),
+    };
+
+    // chezmoi would render these as its own templates
+    match name.ends_with(".tmpl") || name.ends_with(".literal") {
+        true => format!("{name}.literal"),
+        false => name,
+    }
+}
+
+/// Returns where a dotfile goes in the chezmoi source directory, None if it isn't deployed to $TUCKR_TARGET
+fn chezmoi_path(dotfile: &Dotfile, target_dir: &Path) -> Option<PathBuf> {
+    let target = dotfile.to_target_path().ok()?;
+    let relative = target.strip_prefix(target_dir).ok()?;
+
+    // the group's own target dir has no counterpart in the dotfiles, e.g. `.config/nvim` for `[targets] nvim = ".config/nvim"`
+    let group_depth = relative
+        .components()
+        .count()
+        .saturating_sub(dotfile.relative_path().components().count());
+    let mut source = dotfile.group_path.clone();
+    let mut path = PathBuf::new();
+    for (i, component) in relative.components().enumerate() {
+        let Component::Normal(name) = component else {
+            return None;
+        };
+        let name = name.to_str()?;
+
+        let is_last = i + 1 == relative.components().count();
+        let source = match i >= group_depth {
+            true if is_last => Some(dotfile.path.as_path()),
+            true => {
+                source.push(name);
+                Some(source.as_path())
+            }
+            false => None,
+        };
+        path.push(chezmoi_name(name, source));
+    }
+
+    Some(path)
+}
+
+/// Writes a dotfile to `dest`, templates are written rendered
+fn export_file(dotfile: &Dotfile, dest: &Path, dry_run: bool) -> Result<(), String> {
+    if dry_run {
+        eprintln!(
+            "{} `{}`",
+            "creating".if_supports_color(Stderr, |t| t.green()),
+            dest.display()
+        );
+        return Ok(());
+    }
+
+    let with_path = |e: std::io::Error| format!("{}: {e}", dest.display());
+    if let Some(dir) = dest.parent() {
+        fs::create_dir_all(dir).map_err(with_path)?;
+    }
+
+    if !dotfile.is_template() {
+        return fs::copy(&dotfile.path, dest).map(|_| ()).map_err(with_path);
+    }
+
+    let rendered = templates::render_file(&dotfile.path, dotfile.dotfiles_dir())?;
+    fs::write(dest, rendered).map_err(with_path)?;
+    let permissions = dotfile.path.metadata().map_err(with_path)?.permissions();
+    fs::set_permissions(dest, permissions).map_err(with_path)
+}
+
+/// Returns the names of the groups of a dotfile type that aren't excluded
+fn group_names(dotfiles_dir: &Path, dtype: DotfileType, exclude: &[String]) -> Vec<String> {
+    let mut groups: Vec<_> = fs::read_dir(dotfiles_dir.join(dtype.dir_name()))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| !fileops::is_ignored_file(entry.path()))
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .map(|name| match dtype {
+            DotfileType::Secrets => crate::secrets::archive_group(&name)
+                .map(str::to_string)
+                .unwrap_or(name),
+            _ => name,
+        })
+        .filter(|group| !dotfiles::is_excluded(group, exclude))
+        .collect();
+    groups.sort();
+    groups.dedup();
+    groups
+}
+
+/// Returns the files of a group that get deployed
+fn group_files(group_path: &Path) -> Vec<Dotfile> {
+    DirWalk::new(group_path)
+        .ignore(|f| fileops::is_ignored_file(f))
+        .sorted(true)
+        .filter(|file| !file.is_dir())
+        .filter_map(|file| Dotfile::try_from(file).ok())
+        .collect()
+}
+
+/// Exports every group in Configs as a GNU Stow package, or as a chezmoi source directory with `chezmoi`
+pub fn to_stow_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    dir: &Path,
+    chezmoi: bool,
+    exclude: &[String],
+) -> Result<(), TuckrError> {
+    let dotfiles_dir = match dotfiles::get_dotfiles_path(profile) {
+        Ok(dir) => dir,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::CouldntFindDotfiles);
+        }
+    };
+    let target_dir = match dotfiles::get_dotfiles_target_dir_path() {
+        Ok(dir) => dir,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::Failed);
+        }
+    };
+
+    // exporting never overwrites anything
+    if fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_some()) {
+        errors::print(error!("export_dir_not_empty", dir = dir.display()));
+        return Err(TuckrError::Failed);
+    }
+
+    let configs_dir = dotfiles_dir.join(DotfileType::Configs.dir_name());
+    let mut groups = group_names(&dotfiles_dir, DotfileType::Configs, exclude);
+    groups.retain(|group| configs_dir.join(group).is_dir());
+    if chezmoi {
+        // chezmoi deploys a single tree, so only what this machine would deploy fits into it
+        groups.retain(|group| dotfiles::group_is_valid_target(group));
+    }
+
+    let mut files: BTreeMap<PathBuf, Dotfile> = BTreeMap::new();
+    let mut exported_groups = Vec::new();
+    // the packages that this machine would deploy, and how the ones with their own target are deployed
+    let mut stow_groups = Vec::new();
+    let mut hints = Vec::new();
+    for group in &groups {
+        let group_path = configs_dir.join(group);
+        let Ok(group_dotfile) = Dotfile::try_from(group_path.clone()) else {
+            continue;
+        };
+
+        if chezmoi {
+            let is_outside_target = group_dotfile
+                .target_dir()
+                .is_ok_and(|dir| !dir.starts_with(&target_dir));
+            if is_outside_target {
+                eprintln!(
+                    "{}",
+                    t!(
+                        "warn.export_skipped_target",
+                        group = group,
+                        dir = target_dir.display()
+                    )
+                    .if_supports_color(Stderr, |t| t.yellow())
+                );
+                continue;
+            }
+
+            for dotfile in group_files(&group_path) {
+                let Some(path) = chezmoi_path(&dotfile, &target_dir) else {
+                    continue;
+                };
+                let dest = dir.join(path);
+                let is_taken = files
+                    .get(&dest)
+                    .is_some_and(|other| !dotfiles::wins_collision(group, &other.group_name));
+                if !is_taken {
+                    files.insert(dest, dotfile);
+                }
+            }
+        } else {
+            // stow deploys every package to the same directory unless it's told otherwise
+            let group_target_dir = group_dotfile.target_dir().unwrap_or(target_dir.clone());
+            let is_deployed_here = dotfiles::group_is_valid_target(group);
+            if is_deployed_here && group_target_dir == target_dir {
+                stow_groups.push(group.as_str());
+            } else if is_deployed_here {
+                let cmd = format!(
+                    "stow -d {} -t {} {group}",
+                    dir.display(),
+                    group_target_dir.display()
+                );
+                hints.push(t!(
+                    "info.stow_group_target",
+                    group = group,
+                    dir = group_target_dir.display(),
+                    cmd = cmd
+                ));
+            }
+
+            for dotfile in group_files(&group_path) {
+                let Some(relative) = dotfile.to_target_path().ok().and_then(|target| {
+                    Some(target.strip_prefix(&group_target_dir).ok()?.to_path_buf())
+                }) else {
+                    continue;
+                };
+                files.insert(dir.join(group).join(relative), dotfile);
+            }
+        }
+
+        exported_groups.push(group.as_str());
+    }
+
+    let mut failed = false;
+    for (dest, dotfile) in &files {
+        if let Err(err) = export_file(dotfile, dest, dry_run) {
+            errors::print(err);
+            failed = true;
+        }
+    }
+
+    let skipped = |dtype| group_names(&dotfiles_dir, dtype, exclude).join(", ");
+    let secrets = skipped(DotfileType::Secrets);
+    if !secrets.is_empty() {
+        let warning = t!("warn.export_skipped_secrets", groups = secrets);
+        eprintln!("{}", warning.if_supports_color(Stderr, |t| t.yellow()));
+    }
+    let hooks = skipped(DotfileType::Hooks);
+    if !hooks.is_empty() {
+        eprintln!(
+            "{}",
+            t!("warn.export_skipped_hooks", groups = hooks)
+                .if_supports_color(Stderr, |t| t.yellow())
+        );
+    }
+
+    println!(
+        "{}",
+        t!(
+            "info.exported_groups",
+            count = exported_groups.len(),
+            dir = dir.display()
+        )
+        .if_supports_color(Stdout, |t| t.green())
+    );
+    if chezmoi {
+        let cmd = format!("chezmoi --source {} apply", dir.display());
+        println!("{}", t!("info.chezmoi_how_to_deploy", cmd = cmd));
+    } else {
+        if !stow_groups.is_empty() {
+            let cmd = format!(
+                "stow -d {} -t {} {}",
+                dir.display(),
+                target_dir.display(),
+                stow_groups.join(" ")
+            );
+            println!("{}", t!("info.stow_how_to_deploy", cmd = cmd));
+        }
+        for hint in hints {
+            println!("{hint}");
+        }
+    }
+
+    match failed {
+        true => Err(TuckrError::Failed),
+        false => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chezmoi_names() {
+        assert_eq!(chezmoi_name(".zshrc", None), "dot_zshrc");
+        assert_eq!(chezmoi_name("config", None), "config");
+        assert_eq!(chezmoi_name("run_me.sh", None), "literal_run_me.sh");
+        assert_eq!(chezmoi_name("init.tmpl", None), "init.tmpl.literal");
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn export_to_stow_and_chezmoi() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let configs = dotfiles_dir.join("Configs");
+        let ssh_dir = configs.join("ssh").join(".ssh");
+        fs::create_dir_all(&ssh_dir).unwrap();
+        fs::create_dir_all(configs.join("git")).unwrap();
+        fs::create_dir_all(dotfiles_dir.join("Hooks").join("git")).unwrap();
+        fs::write(ssh_dir.join("config"), "Host *").unwrap();
+        fs::set_permissions(&ssh_dir, fs::Permissions::from_mode(0o700)).unwrap();
+        fs::write(
+            configs.join("git").join(".gitconfig.tmpl"),
+            "name = {{ name }}",
+        )
+        .unwrap();
+        fs::write(dotfiles_dir.join("vars.toml"), "name = \"tuckr\"").unwrap();
+
+        let out = dotfiles_dir.parent().unwrap().join("export");
+        _ = fs::remove_dir_all(&out);
+
+        let stow = out.join("stow");
+        to_stow_cmd(None, false, &stow, false, &[]).unwrap();
+        assert_eq!(
+            fs::read_to_string(stow.join("git").join(".gitconfig")).unwrap(),
+            "name = tuckr"
+        );
+        assert!(stow.join("ssh").join(".ssh").join("config").is_file());
+        assert!(!stow.join("git").join(".gitconfig.tmpl").exists());
+
+        // exports never overwrite anything
+        assert!(to_stow_cmd(None, false, &stow, false, &[]).is_err());
+
+        let chezmoi = out.join("chezmoi");
+        to_stow_cmd(None, false, &chezmoi, true, &["git".to_string()]).unwrap();
+        assert!(chezmoi.join("private_dot_ssh").join("config").is_file());
+        assert!(!chezmoi.join("dot_gitconfig").exists());
+
+        fs::remove_dir_all(&out).unwrap();
+        fs::remove_dir_all(&dotfiles_dir).unwrap();
+    }
+}
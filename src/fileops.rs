@@ -2,19 +2,40 @@
 //!
 //! Contains functions to create the base directories and to convert users from stow to tuckr
 
-use crate::dotfiles::{self, ReturnCode};
+use crate::config;
+use crate::dotfiles::{self, Dotfile, DotfileType};
+use crate::error;
+use crate::errors::{self, TuckrError};
+use crate::history;
+use crate::hooks;
+use crate::interactive;
+use crate::secrets;
+use crate::symlinks::{self, DeployState};
+use crate::table;
 use owo_colors::OwoColorize;
+use owo_colors::Stream::{Stderr, Stdout};
 use rust_i18n::t;
-use std::collections::HashSet;
-use std::io::Write;
+use std::collections::{BTreeMap, HashSet};
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
-use std::process::ExitCode;
-use std::{fs, path};
-use tabled::object::Segment;
-use tabled::{Alignment, Modify, Table, Tabled};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, OnceLock};
+use std::{fs, path, thread};
+use tabled::Tabled;
+
+pub fn is_ignored_file(file: impl AsRef<Path>) -> bool {
+    let path = file.as_ref();
+    let file = path.file_name().unwrap().to_str().unwrap();
+
+    // these tell tuckr where the group is deployed to and what to leave out, they aren't deployed themselves
+    if file == dotfiles::TARGET_FILE || file == dotfiles::IGNORE_FILE {
+        return true;
+    }
 
-fn is_ignored_file(file: impl AsRef<Path>) -> bool {
-    let file = file.as_ref().file_name().unwrap().to_str().unwrap();
+    if dotfiles::is_ignored_by_ignore_file(path) || dotfiles::is_other_platforms_file(path) {
+        return true;
+    }
 
     fn is_ignored_file(ignored_files: &[&str], file: &str) -> bool {
         ignored_files.contains(&file)
@@ -100,22 +121,148 @@ fn is_ignored_file(file: impl AsRef<Path>) -> bool {
     false
 }
 
+/// A directory that couldn't be read while walking through it
+#[derive(Debug)]
+pub struct WalkError {
+    pub path: PathBuf,
+    pub err: std::io::Error,
+}
+
+impl std::fmt::Display for WalkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.err)
+    }
+}
+
+/// Walks through a directory recursively, directories always come before the files inside of them
+///
+/// By default garbage files (see `is_ignored_file`) are skipped and symlinked directories are walked into.
+/// Directories that can't be read make the walk panic unless errors are sent to a channel with `errors`
 pub struct DirWalk {
-    queue: Vec<path::PathBuf>,
+    root: Option<PathBuf>,
+    queue: Vec<(PathBuf, usize)>,
+    max_depth: Option<usize>,
+    ignore: Arc<dyn Fn(&Path) -> bool + Send + Sync>,
+    follow_symlinks: bool,
+    sorted: bool,
+    errors: Option<Sender<WalkError>>,
 }
 
 impl DirWalk {
     pub fn new(dir_path: impl AsRef<Path>) -> Self {
-        let dir_path = dir_path.as_ref();
-        let dir = fs::read_dir(dir_path).unwrap_or_else(|_| {
-            panic!(
-                "{}",
-                t!("errors.x_doesnt_exist", x = dir_path.to_str().unwrap())
-            )
-        });
-
         Self {
-            queue: dir.map(|f| f.unwrap().path()).collect(),
+            root: Some(dir_path.as_ref().to_path_buf()),
+            queue: Vec::new(),
+            max_depth: None,
+            ignore: Arc::new(|file| is_ignored_file(file)),
+            follow_symlinks: true,
+            sorted: false,
+            errors: None,
+        }
+    }
+
+    /// Only walks `depth` directories deep, a depth of 1 only returns the directory's entries
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Skips the files the matcher returns true for, this replaces the default garbage file matcher
+    ///
+    /// The contents of ignored directories are skipped as well
+    pub fn ignore(mut self, matcher: impl Fn(&Path) -> bool + Send + Sync + 'static) -> Self {
+        self.ignore = Arc::new(matcher);
+        self
+    }
+
+    /// Whether symlinks to directories are walked into
+    pub fn follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    /// Returns the entries of every directory sorted by their name
+    pub fn sorted(mut self, sorted: bool) -> Self {
+        self.sorted = sorted;
+        self
+    }
+
+    /// Sends the directories that couldn't be read to a channel instead of panicking
+    pub fn errors(mut self, errors: Sender<WalkError>) -> Self {
+        self.errors = Some(errors);
+        self
+    }
+
+    /// Walks the directory on up to `jobs` threads, every entry of the directory is walked on its own thread
+    ///
+    /// The files are returned in the same order as they would be by iterating
+    pub fn par_walk(mut self, jobs: usize) -> Vec<PathBuf> {
+        self.read_root();
+
+        // the queue is popped from the back so it's reversed to keep the iteration order
+        let entries: Vec<_> = self.queue.drain(..).rev().collect();
+        let walker = &self;
+
+        par_map(jobs, &entries, |entry| {
+            DirWalk {
+                root: None,
+                queue: vec![entry.clone()],
+                max_depth: walker.max_depth,
+                ignore: walker.ignore.clone(),
+                follow_symlinks: walker.follow_symlinks,
+                sorted: walker.sorted,
+                errors: walker.errors.clone(),
+            }
+            .collect::<Vec<_>>()
+        })
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+
+    fn read_root(&mut self) {
+        if let Some(root) = self.root.take() {
+            self.push_entries(&root, 1);
+        }
+    }
+
+    /// Queues the entries of a directory, they're at `depth` from the root
+    fn push_entries(&mut self, dir: &Path, depth: usize) {
+        if self.max_depth.is_some_and(|max_depth| depth > max_depth) {
+            return;
+        }
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) => return self.report(dir, err),
+        };
+
+        let mut files = Vec::new();
+        for entry in entries {
+            match entry {
+                Ok(entry) => files.push(entry.path()),
+                Err(err) => self.report(dir, err),
+            }
+        }
+
+        // the queue is popped from the back so the files are sorted in reverse
+        if self.sorted {
+            files.sort_unstable_by(|a, b| b.cmp(a));
+        }
+
+        self.queue
+            .extend(files.into_iter().map(|file| (file, depth)));
+    }
+
+    fn report(&self, path: &Path, err: std::io::Error) {
+        let err = WalkError {
+            path: path.to_path_buf(),
+            err,
+        };
+
+        match &self.errors {
+            Some(errors) => _ = errors.send(err),
+            None => panic!("{err}"),
         }
     }
 }
@@ -124,45 +271,449 @@ impl Iterator for DirWalk {
     type Item = path::PathBuf;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let curr_file = self.queue.pop()?;
+        self.read_root();
+
+        loop {
+            let (curr_file, depth) = self.queue.pop()?;
 
-        if is_ignored_file(&curr_file) {
-            return self.next();
+            if (self.ignore)(&curr_file) {
+                continue;
+            }
+
+            let is_dir = curr_file.is_dir() && (self.follow_symlinks || !curr_file.is_symlink());
+            if is_dir {
+                self.push_entries(&curr_file, depth + 1);
+            }
+
+            return Some(curr_file);
         }
+    }
+}
 
-        if curr_file.is_dir() {
-            for file in fs::read_dir(&curr_file).unwrap() {
-                let file = file.unwrap();
-                self.queue.push(file.path());
+static RELATIVE_SYMLINKS: OnceLock<bool> = OnceLock::new();
+
+/// Dotfiles are symlinked with paths relative to their symlinks from now on, set with `--relative`
+pub fn use_relative_symlinks() {
+    _ = RELATIVE_SYMLINKS.set(true);
+}
+
+/// Returns true if dotfiles are symlinked with relative paths, with `--relative` or
+/// `relative_symlinks = true` in tuckr.toml
+pub fn relative_symlinks() -> bool {
+    RELATIVE_SYMLINKS.get().copied().unwrap_or_default() || config::get().relative_symlinks
+}
+
+/// Removes the `.` and `..` of a path without looking at the filesystem
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            path::Component::CurDir => (),
+            path::Component::ParentDir => {
+                normalized.pop();
             }
+            component => normalized.push(component),
         }
+    }
+    normalized
+}
 
-        Some(curr_file)
+/// Returns `path` relative to `dir`, e.g. `../.config/dotfiles/Configs/zsh/.zshrc` from $HOME/.local
+///
+/// None is returned when one can't be reached from the other, e.g. when they're on different drives
+fn relative_path(path: &Path, dir: &Path) -> Option<PathBuf> {
+    let path = normalize_path(path);
+    let dir = normalize_path(dir);
+    if path.components().next() != dir.components().next() {
+        return None;
     }
+
+    let mut path_components = path.components().peekable();
+    let mut dir_components = dir.components().peekable();
+    while let (Some(component), Some(dir_component)) =
+        (path_components.peek(), dir_components.peek())
+        && component == dir_component
+    {
+        path_components.next();
+        dir_components.next();
+    }
+
+    let mut relative: PathBuf = dir_components.map(|_| path::Component::ParentDir).collect();
+    relative.extend(path_components);
+    Some(relative)
 }
 
-/// Creates the necessary files and folders for a tuckr directory if they don't exist
-pub fn init_cmd(profile: Option<String>, dry_run: bool) -> Result<(), ExitCode> {
-    let dotfiles_dir = if cfg!(test) {
-        dotfiles::get_dotfiles_path(None).unwrap()
-    } else {
-        let dotfiles_dir_name = match profile {
-            Some(profile) => "dotfiles_".to_string() + profile.as_str(),
-            None => "dotfiles".to_string(),
-        };
-        dirs::config_dir().unwrap().join(dotfiles_dir_name)
+/// Symlinks a dotfile to `link`, with a path relative to the link's directory when relative
+/// symlinks are used so that they keep working wherever the home directory is mounted
+///
+/// The path is worked out from where both of them really are, since the system resolves the `..`
+/// of the symlink from the directory it's really in
+pub fn symlink_dotfile(dotfile: &Path, link: &Path) -> std::io::Result<()> {
+    let real = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let source = match (relative_symlinks(), link.parent()) {
+        (true, Some(dir)) => {
+            relative_path(&real(dotfile), &real(dir)).unwrap_or_else(|| dotfile.to_path_buf())
+        }
+        _ => dotfile.to_path_buf(),
     };
 
+    symlink(&source, link)
+}
+
+/// Returns where a symlink points to, relative symlinks are resolved from the directory they're in
+pub fn read_link(link: &Path) -> std::io::Result<PathBuf> {
+    let source = fs::read_link(link)?;
+    if source.is_absolute() {
+        return Ok(source);
+    }
+
+    let dir = link.parent().unwrap_or(Path::new("."));
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    Ok(normalize_path(&dir.join(source)))
+}
+
+/// Returns true if `link` is a symlink to `dotfile`, whether it's an absolute or a relative one
+pub fn links_to(link: &Path, dotfile: &Path) -> bool {
+    let Ok(source) = read_link(link) else {
+        return false;
+    };
+
+    source == dotfile
+        || dotfile
+            .canonicalize()
+            .is_ok_and(|dotfile| source == dotfile)
+}
+
+/// Creates a symlink at `link` pointing to `original`
+pub fn symlink(original: &Path, link: &Path) -> std::io::Result<()> {
+    #[cfg(target_family = "unix")]
+    {
+        std::os::unix::fs::symlink(original, link)
+    }
+
+    #[cfg(target_family = "windows")]
+    {
+        if original.is_dir() {
+            std::os::windows::fs::symlink_dir(original, link)
+        } else {
+            std::os::windows::fs::symlink_file(original, link)
+        }
+    }
+}
+
+/// Creates a directory junction at `link` pointing to `original`
+///
+/// Unlike symlinks, junctions can be created on Windows without Developer Mode
+#[cfg(target_family = "windows")]
+pub fn junction(original: &Path, link: &Path) -> std::io::Result<()> {
+    // the standard library can't create junctions, mklink is part of every Windows
+    let output = std::process::Command::new("cmd")
+        .arg("/C")
+        .arg("mklink")
+        .arg("/J")
+        .arg(link)
+        .arg(original)
+        .output()?;
+
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(std::io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        )),
+    }
+}
+
+/// Junctions only exist on Windows
+#[cfg(not(target_family = "windows"))]
+pub fn junction(_original: &Path, _link: &Path) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// Returns true if both paths are the same file, e.g. because one is a hardlink to the other
+pub fn is_same_file(path: &Path, other: &Path) -> bool {
+    same_file::is_same_file(path, other).unwrap_or(false)
+}
+
+/// Moves a file, directory or symlink from `src` to `dest`
+///
+/// Falls back to copying and deleting when a rename is not possible (e.g. `src` and `dest` are on different filesystems)
+pub fn move_path(src: &Path, dest: &Path) -> std::io::Result<()> {
+    if fs::rename(src, dest).is_ok() {
+        return Ok(());
+    }
+
+    move_by_copying(src, dest)
+}
+
+/// Copies `src` to `dest` as it is and removes `src` once all of it was copied
+///
+/// Garbage files are copied too and symlinks stay symlinks, since what's moved is the user's and
+/// nothing of it may get lost
+fn move_by_copying(src: &Path, dest: &Path) -> std::io::Result<()> {
+    if src.is_symlink() {
+        copy_symlink(src, dest)?;
+        return fs::remove_file(src);
+    }
+
+    if !src.is_dir() {
+        fs::copy(src, dest)?;
+        return fs::remove_file(src);
+    }
+
+    let (walk_errors, unreadable_dirs) = mpsc::channel();
+    fs::create_dir_all(dest)?;
+    for file in DirWalk::new(src)
+        .ignore(|_| false)
+        .follow_symlinks(false)
+        .errors(walk_errors)
+    {
+        let dest_file = dest.join(file.strip_prefix(src).unwrap());
+        if file.is_symlink() {
+            copy_symlink(&file, &dest_file)?;
+        } else if file.is_dir() {
+            fs::create_dir_all(dest_file)?;
+        } else {
+            fs::copy(&file, dest_file)?;
+        }
+    }
+
+    // what couldn't be read wasn't copied, so src has to stay
+    if let Ok(err) = unreadable_dirs.try_recv() {
+        return Err(std::io::Error::new(err.err.kind(), err.to_string()));
+    }
+
+    fs::remove_dir_all(src)
+}
+
+/// Creates a symlink at `dest` that points to where the symlink at `src` points to
+fn copy_symlink(src: &Path, dest: &Path) -> std::io::Result<()> {
+    let link = fs::read_link(src)?;
+
+    #[cfg(target_family = "unix")]
+    {
+        std::os::unix::fs::symlink(link, dest)
+    }
+
+    #[cfg(target_family = "windows")]
+    {
+        if src.is_dir() {
+            std::os::windows::fs::symlink_dir(link, dest)
+        } else {
+            std::os::windows::fs::symlink_file(link, dest)
+        }
+    }
+}
+
+/// Returns the sha256 of some content as a hex string
+pub fn hash_bytes(content: impl AsRef<[u8]>) -> String {
+    use sha2::{Digest, Sha256};
+
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Returns the sha256 of a file's content as a hex string
+pub fn hash_file(path: &Path) -> std::io::Result<String> {
+    Ok(hash_bytes(fs::read(path)?))
+}
+
+/// Number of threads used to walk and deploy dotfiles, set with `--jobs`
+static JOBS: OnceLock<usize> = OnceLock::new();
+
+pub fn set_jobs(jobs: NonZeroUsize) {
+    _ = JOBS.set(jobs.get());
+}
+
+/// Returns how many threads should be used, defaults to the number of CPUs
+pub fn jobs() -> usize {
+    *JOBS.get_or_init(|| thread::available_parallelism().map_or(1, NonZeroUsize::get))
+}
+
+/// Calls `func` on every item using up to `jobs` threads
+///
+/// The results are returned in the same order as the items so that output stays deterministic
+pub fn par_map<T, R, F>(jobs: usize, items: &[T], func: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let jobs = jobs.min(items.len());
+    if jobs <= 1 {
+        return items.iter().map(func).collect();
+    }
+
+    let next_item = AtomicUsize::new(0);
+    let worker = || {
+        let mut results = Vec::new();
+        loop {
+            let idx = next_item.fetch_add(1, Ordering::Relaxed);
+            let Some(item) = items.get(idx) else {
+                return results;
+            };
+            results.push((idx, func(item)));
+        }
+    };
+
+    let mut results: Vec<(usize, R)> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs).map(|_| scope.spawn(worker)).collect();
+
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|err| std::panic::resume_unwind(err))
+            })
+            .collect()
+    });
+
+    results.sort_unstable_by_key(|(idx, _)| *idx);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Returns where a new dotfiles directory should be created
+pub fn get_new_dotfiles_path(profile: Option<String>) -> PathBuf {
+    if cfg!(test) {
+        return dotfiles::get_dotfiles_path(None).unwrap();
+    }
+
+    let dotfiles_dir_name = match profile {
+        Some(profile) => "dotfiles_".to_string() + profile.as_str(),
+        None => "dotfiles".to_string(),
+    };
+
+    match std::env::var("TUCKR_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir).join(dotfiles_dir_name),
+        _ => dirs::config_dir().unwrap().join(dotfiles_dir_name),
+    }
+}
+
+/// tuckr.toml written by `tuckr init --config`, every setting is left at its default
+const STARTER_CONFIG: &str = r#"# every setting is optional, see "Configuration" in tuckr's README for all of them
+
+# where dotfiles get deployed to, defaults to your home directory
+# target = "~"
+
+# groups that are skipped unless you explicitly ask for them
+# exclude = ["work"]
+
+# groups that are deployed together with `tuckr add @desktop`
+# [bundles]
+# desktop = ["sway", "waybar", "kitty"]
+
+# how secrets are encrypted, "password", "age" or "gpg"
+# [secrets]
+# backend = "password"
+"#;
+
+/// Returns the .gitignore written by `tuckr init --gitignore`, which leaves out what tuckr keeps
+/// in the dotfiles for this machine only
+fn gitignore() -> String {
+    dotfiles::MACHINE_LOCAL
+        .iter()
+        .map(|(local, why)| format!("# {why}\n{}\n", local.gitignore_pattern("")))
+        .collect()
+}
+
+/// What `tuckr init` creates besides the dotfiles directory
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InitOptions {
+    /// makes the dotfiles a git repository unless they're already in one
+    pub git: bool,
+    /// writes a starter tuckr.toml unless there already is one
+    pub config: bool,
+    /// writes a .gitignore for what's only meant for this machine
+    pub gitignore: bool,
+}
+
+/// Writes `contents` to `path` unless it already exists
+fn create_file(dry_run: bool, path: &Path, contents: &str) -> Result<(), TuckrError> {
+    if path.exists() {
+        println!(
+            "{}",
+            t!("info.x_already_exists", x = path.display())
+                .if_supports_color(Stdout, |t| t.yellow())
+        );
+        return Ok(());
+    }
+
+    if dry_run {
+        eprintln!(
+            "{} file `{}`",
+            "creating".if_supports_color(Stderr, |t| t.green()),
+            path.display()
+        );
+        return Ok(());
+    }
+
+    if let Some(parent) = path.parent()
+        && let Err(e) = fs::create_dir_all(parent)
+    {
+        errors::print(e);
+        return Err(TuckrError::Failed);
+    }
+
+    fs::write(path, contents).map_err(|e| {
+        errors::print(format!("{}: {e}", path.display()));
+        TuckrError::Failed
+    })
+}
+
+/// Creates the necessary files and folders for a tuckr directory if they don't exist
+pub fn init_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    options: InitOptions,
+) -> Result<(), TuckrError> {
+    let dotfiles_dir = get_new_dotfiles_path(profile);
+
     for dir in [
         dotfiles_dir.join("Configs"),
         dotfiles_dir.join("Hooks"),
         dotfiles_dir.join("Secrets"),
     ] {
         if dry_run {
-            eprintln!("{} directory `{}`", "creating".green(), dir.display())
+            eprintln!(
+                "{} directory `{}`",
+                "creating".if_supports_color(Stderr, |t| t.green()),
+                dir.display()
+            )
         } else if let Err(e) = fs::create_dir_all(dir) {
-            eprintln!("{}", e.red());
-            return Err(ExitCode::FAILURE);
+            errors::print(e);
+            return Err(TuckrError::Failed);
+        }
+    }
+
+    if options.gitignore {
+        create_file(dry_run, &dotfiles_dir.join(".gitignore"), &gitignore())?;
+    }
+
+    if options.config
+        && let Some(config_path) = config::get_config_path()
+    {
+        create_file(dry_run, &config_path, STARTER_CONFIG)?;
+    }
+
+    // dotfiles that are already inside of a repository, e.g. one for the whole home directory, are left in it
+    if options.git {
+        let in_repo = history::git(&dotfiles_dir, &["rev-parse", "--show-toplevel"], None);
+        match in_repo {
+            Ok(repo) => println!(
+                "{}",
+                t!("info.already_in_git_repo", repo = repo.trim())
+                    .if_supports_color(Stdout, |t| t.yellow())
+            ),
+            Err(_) if dry_run => eprintln!(
+                "{} git repository in `{}`",
+                "creating".if_supports_color(Stderr, |t| t.green()),
+                dotfiles_dir.display()
+            ),
+            Err(_) => {
+                if let Err(e) = history::git(&dotfiles_dir, &["init"], None) {
+                    errors::print(e);
+                    return Err(TuckrError::Failed);
+                }
+            }
         }
     }
 
@@ -172,7 +723,68 @@ pub fn init_cmd(profile: Option<String>, dry_run: bool) -> Result<(), ExitCode>
             "info.dotfiles_created_at",
             location = dotfiles_dir.to_str().unwrap()
         )
-        .green()
+        .if_supports_color(Stdout, |t| t.green())
+    );
+
+    Ok(())
+}
+
+/// Creates a new group with an empty directory in Configs and in Hooks
+///
+/// Fails if the group's name isn't valid on every platform or if the group already has both
+pub fn new_group_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    group: &str,
+) -> Result<(), TuckrError> {
+    if let Err(err) = dotfiles::is_valid_groupname(group) {
+        errors::print(err);
+        return Err(TuckrError::Failed);
+    }
+
+    let dotfiles_dir = match dotfiles::get_dotfiles_path(profile) {
+        Ok(dir) => dir,
+        Err(e) => {
+            errors::print(e);
+            return Err(TuckrError::CouldntFindDotfiles);
+        }
+    };
+
+    let missing_dirs: Vec<_> = [DotfileType::Configs, DotfileType::Hooks]
+        .into_iter()
+        .map(|dtype| dotfiles_dir.join(dtype.dir_name()).join(group))
+        .filter(|dir| !dir.exists())
+        .collect();
+
+    if missing_dirs.is_empty() {
+        errors::print(error!("group_already_exists", group = group));
+        return Err(TuckrError::Failed);
+    }
+
+    for dir in missing_dirs {
+        if dry_run {
+            eprintln!(
+                "{} directory `{}`",
+                "creating".if_supports_color(Stderr, |t| t.green()),
+                dir.display()
+            );
+        } else if let Err(e) = fs::create_dir_all(&dir) {
+            errors::print(format!("{}: {e}", dir.display()));
+            return Err(TuckrError::Failed);
+        }
+    }
+
+    let configs_dir = dotfiles_dir
+        .join(DotfileType::Configs.dir_name())
+        .join(group);
+    println!(
+        "{}",
+        t!(
+            "info.group_created",
+            group = group,
+            dir = configs_dir.display()
+        )
+        .if_supports_color(Stdout, |t| t.green())
     );
 
     Ok(())
@@ -184,12 +796,12 @@ pub fn push_cmd(
     group: String,
     files: &[String],
     assume_yes: bool,
-) -> Result<(), ExitCode> {
+) -> Result<(), TuckrError> {
     let dotfiles_dir = match dotfiles::get_dotfiles_path(profile) {
         Ok(dir) => dir.join("Configs").join(group),
         Err(e) => {
-            eprintln!("{e}");
-            return Err(ReturnCode::CouldntFindDotfiles.into());
+            errors::print(e);
+            return Err(TuckrError::CouldntFindDotfiles);
         }
     };
 
@@ -197,30 +809,25 @@ pub fn push_cmd(
     for file in files {
         let file = PathBuf::from(file);
         if !file.exists() {
-            eprintln!("{}", t!("errors.x_doesnt_exist", x = file.display()).red());
+            errors::print(error!("x_doesnt_exist", x = file.display()));
             any_file_failed = true;
             continue;
         }
 
         let file = path::absolute(file).unwrap();
-        let target_file = dotfiles_dir.join(dotfiles::get_target_basepath(&file).unwrap());
+        let target_file =
+            dotfiles_dir.join(dotfiles::get_group_basepath(&dotfiles_dir, &file).unwrap());
 
-        if target_file.exists() && !assume_yes {
-            print!(
-                "{} {}. {} ",
+        if target_file.exists()
+            && !assume_yes
+            && !interactive::confirm(format!(
+                "{} {}. {}",
                 target_file.to_str().unwrap(),
                 t!("errors.already_exists"),
                 t!("warn.want_to_override")
-            );
-
-            std::io::stdout().flush().unwrap();
-            let mut confirmation = String::new();
-            std::io::stdin().read_line(&mut confirmation).unwrap();
-
-            let confirmed = matches!(confirmation.trim().to_lowercase().as_str(), "y" | "yes");
-            if !confirmed {
-                continue;
-            }
+            ))
+        {
+            continue;
         }
 
         #[inline]
@@ -229,13 +836,13 @@ pub fn push_cmd(
                 if !target_parent_dir.exists() {
                     eprintln!(
                         "{} parent directory `{}`",
-                        "creating".yellow(),
+                        "creating".if_supports_color(Stderr, |t| t.yellow()),
                         target_parent_dir.display()
                     );
                 }
                 eprintln!(
                     "{} `{}` to `{}`",
-                    "copying".green(),
+                    "copying".if_supports_color(Stderr, |t| t.green()),
                     file.display(),
                     target_file.display()
                 );
@@ -257,14 +864,15 @@ pub fn push_cmd(
             }
 
             if !f.exists() {
-                eprintln!("{}", t!("errors.x_doesnt_exist", x = f.display()).red());
+                errors::print(error!("x_doesnt_exist", x = f.display()));
                 any_file_failed = true;
                 continue;
             }
 
             let file = path::absolute(f).unwrap();
 
-            let target_file = dotfiles_dir.join(dotfiles::get_target_basepath(&file).unwrap());
+            let target_file =
+                dotfiles_dir.join(dotfiles::get_group_basepath(&dotfiles_dir, &file).unwrap());
             let target_parent_file = target_file.parent().unwrap();
 
             push_file(
@@ -277,7 +885,7 @@ pub fn push_cmd(
     }
 
     if any_file_failed {
-        Err(ReturnCode::NoSuchFileOrDir.into())
+        Err(TuckrError::NoSuchFileOrDir)
     } else {
         Ok(())
     }
@@ -288,12 +896,12 @@ pub fn pop_cmd(
     dry_run: bool,
     groups: &[String],
     assume_yes: bool,
-) -> Result<(), ExitCode> {
+) -> Result<(), TuckrError> {
     let dotfiles_dir = match dotfiles::get_dotfiles_path(profile) {
         Ok(dir) => dir.join("Configs"),
         Err(e) => {
-            eprintln!("{e}");
-            return Err(ReturnCode::CouldntFindDotfiles.into());
+            errors::print(e);
+            return Err(TuckrError::CouldntFindDotfiles);
         }
     };
 
@@ -315,30 +923,29 @@ pub fn pop_cmd(
 
     if !invalid_groups.is_empty() {
         for group in invalid_groups {
-            eprintln!("{}", t!("errors.x_doesnt_exist", x = group).red());
+            errors::print(error!("x_doesnt_exist", x = group));
         }
 
-        return Err(ReturnCode::NoSuchFileOrDir.into());
+        return Err(TuckrError::NoSuchFileOrDir);
     }
 
     if !assume_yes {
         println!("{}:", t!("info.groups_will_be_removed"));
         for group in groups {
-            println!("\t{}", group.yellow());
-        }
-        print!("\n{} ", t!("warn.want_to_proceed"));
-        std::io::stdout().flush().unwrap();
-        let mut confirmation = String::new();
-        std::io::stdin().read_line(&mut confirmation).unwrap();
-        let confirmed = matches!(confirmation.trim().to_lowercase().as_str(), "y" | "yes");
-        if !confirmed {
+            println!("\t{}", group.if_supports_color(Stdout, |t| t.yellow()));
+        }
+        if !interactive::confirm(format!("\n{}", t!("warn.want_to_proceed"))) {
             return Ok(());
         }
     }
 
     for group_path in valid_groups {
         if dry_run {
-            eprintln!("{} `{}`", "removing".red(), group_path.display());
+            eprintln!(
+                "{} `{}`",
+                "removing".if_supports_color(Stderr, |t| t.red()),
+                group_path.display()
+            );
             continue;
         }
 
@@ -348,141 +955,522 @@ pub fn pop_cmd(
     Ok(())
 }
 
-pub fn ls_hooks_cmd(profile: Option<String>) -> Result<(), ExitCode> {
-    let dir = match dotfiles::get_dotfiles_path(profile) {
-        Ok(dir) => dir.join("Hooks"),
-        Err(err) => {
-            eprintln!("{err}");
-            return Err(ReturnCode::CouldntFindDotfiles.into());
+/// Returns the file in the group that should be edited, creating it if it doesn't exist yet
+///
+/// file: a path relative to the group's root or the path it's deployed to
+///
+/// If the file it would be deployed to already exists and isn't managed by tuckr it is adopted into
+/// the group and symlinked back, so that the file that's edited is the one that's being used
+fn get_edit_path(dry_run: bool, group_dir: &Path, file: &Path) -> Result<PathBuf, String> {
+    let relative_path = if file.is_absolute() {
+        dotfiles::get_group_basepath(group_dir, file).ok_or_else(|| {
+            let target_dir = Dotfile::try_from(group_dir.to_path_buf())
+                .and_then(|group| group.target_dir())
+                .unwrap_or_default();
+            error!(
+                "not_in_target",
+                file = file.display(),
+                target = target_dir.display()
+            )
+        })?
+    } else {
+        file.to_path_buf()
+    };
+
+    let group_file = group_dir.join(&relative_path);
+    if group_file.exists() {
+        return Ok(group_file);
+    }
+
+    // templates are deployed without their extension so they're looked up by it too
+    let mut template = group_file.clone().into_os_string();
+    template.push(".tmpl");
+    let template = PathBuf::from(template);
+    if template.exists() {
+        return Ok(template);
+    }
+
+    let target = dotfiles::Dotfile::try_from(group_file.clone())?.to_target_path()?;
+    let adopt = target.exists() && !target.is_symlink();
+
+    if dry_run {
+        if adopt {
+            eprintln!(
+                "{} `{}` to `{}`",
+                "adopting".if_supports_color(Stderr, |t| t.yellow()),
+                target.display(),
+                group_file.display()
+            );
+        } else {
+            eprintln!(
+                "{} `{}`",
+                "creating".if_supports_color(Stderr, |t| t.green()),
+                group_file.display()
+            );
+        }
+        return Ok(group_file);
+    }
+
+    let with_path = |err: std::io::Error| format!("{}: {err}", group_file.display());
+    fs::create_dir_all(group_file.parent().unwrap()).map_err(with_path)?;
+
+    if adopt {
+        move_path(&target, &group_file).map_err(with_path)?;
+        symlink(&group_file, &target).map_err(|err| format!("{}: {err}", target.display()))?;
+    } else {
+        fs::File::create(&group_file).map_err(with_path)?;
+    }
+
+    Ok(group_file)
+}
+
+/// Returns the user's editor along with its arguments
+fn get_editor() -> Vec<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_default();
+
+    let editor: Vec<String> = editor.split_whitespace().map(String::from).collect();
+    if !editor.is_empty() {
+        return editor;
+    }
+
+    if cfg!(target_family = "windows") {
+        vec!["notepad".into()]
+    } else {
+        vec!["vi".into()]
+    }
+}
+
+/// Opens a group or one of its files with $EDITOR
+///
+/// Files are always opened from the dotfiles so that it's clear which file is being edited
+pub fn edit_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    group: &str,
+    file: Option<String>,
+) -> Result<(), TuckrError> {
+    let group_dir = match dotfiles::get_dotfiles_path(profile) {
+        Ok(dir) => dir.join("Configs").join(group),
+        Err(e) => {
+            errors::print(e);
+            return Err(TuckrError::CouldntFindDotfiles);
+        }
+    };
+
+    let path = match file {
+        Some(file) => {
+            let file = PathBuf::from(file);
+            // paths to deployed files are usually given relative to the current directory
+            let file = match path::absolute(&file) {
+                Ok(absolute)
+                    if file.is_relative()
+                        && file.exists()
+                        && dotfiles::get_group_basepath(&group_dir, &absolute).is_some() =>
+                {
+                    absolute
+                }
+                _ => file,
+            };
+
+            get_edit_path(dry_run, &group_dir, &file).map_err(|err| {
+                errors::print(err);
+                TuckrError::Failed
+            })?
+        }
+
+        None if group_dir.is_dir() => group_dir,
+
+        None => {
+            errors::print(error!("no_group", group = group));
+            return Err(TuckrError::NoSuchFileOrDir);
         }
     };
 
-    if !dir.exists() {
-        eprintln!("{}", t!("errors.no_dir_setup_for_x", x = "Hooks").red());
-        return Err(ReturnCode::NoSetupFolder.into());
+    let editor = get_editor();
+
+    if dry_run {
+        eprintln!(
+            "{} `{}` with `{}`",
+            "opening".if_supports_color(Stderr, |t| t.green()),
+            path.display(),
+            editor.join(" ")
+        );
+        return Ok(());
+    }
+
+    let status = std::process::Command::new(&editor[0])
+        .args(&editor[1..])
+        .arg(&path)
+        .status();
+
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(_) => Err(TuckrError::Failed),
+        Err(err) => {
+            errors::print(error!(
+                "couldnt_open_editor",
+                editor = editor[0],
+                err_msg = err
+            ));
+            Err(TuckrError::Failed)
+        }
+    }
+}
+
+pub fn ls_hooks_cmd(profile: Option<String>) -> Result<(), TuckrError> {
+    if let Err(err) = dotfiles::get_dotfiles_path(profile.clone()) {
+        errors::print(err);
+        return Err(TuckrError::CouldntFindDotfiles);
+    }
+
+    let dirs = dotfiles::get_group_dirs(profile, DotfileType::Hooks);
+    if dirs.is_empty() {
+        errors::print(error!("no_dir_setup_for_x", x = "Hooks"));
+        return Err(TuckrError::NoSetupFolder);
     }
 
     #[derive(Tabled)]
-    struct ListRow<'a> {
+    struct ListRow {
         #[tabled(rename = "Group")]
         group: String,
         #[tabled(rename = "Prehook")]
-        pre_hook: &'a str,
+        pre_hook: String,
         #[tabled(rename = "Posthook")]
-        post_hook: &'a str,
+        post_hook: String,
         #[tabled(rename = "Remove")]
-        rm_hook: &'a str,
+        rm_hook: String,
     }
 
-    let dir = fs::read_dir(dir).unwrap();
     let mut rows = Vec::new();
+    let mut seen = HashSet::new();
 
-    let true_symbol = "✓".green().to_string();
-    let false_symbol = "✗".red().to_string();
-
-    for hook in dir {
+    for hook in dirs.iter().flat_map(|dir| fs::read_dir(dir).unwrap()) {
         let hook_dir = hook.unwrap();
         let hook_name = hook_dir.file_name();
         let group = hook_name.to_str().unwrap().to_string();
+        // a profile's own hooks shadow the shared ones
+        if !seen.insert(group.clone()) {
+            continue;
+        }
 
-        let mut hook_entry = ListRow {
-            group,
-            pre_hook: &false_symbol,
-            post_hook: &false_symbol,
-            rm_hook: &false_symbol,
-        };
-
+        let (mut pre_hook, mut post_hook, mut rm_hook) = (false, false, false);
         for hook in fs::read_dir(hook_dir.path()).unwrap() {
-            let hook = hook.unwrap().file_name();
-            let hook = hook.to_str().unwrap();
-            if hook.starts_with("pre") {
-                hook_entry.pre_hook = &true_symbol;
-            } else if hook.starts_with("post") {
-                hook_entry.post_hook = &true_symbol;
-            } else if hook.starts_with("rm") {
-                hook_entry.rm_hook = &true_symbol;
+            match hooks::hook_kind(&hook.unwrap().path()) {
+                Some("pre") => pre_hook = true,
+                Some("post") => post_hook = true,
+                Some("rm" | "teardown") => rm_hook = true,
+                _ => (),
             }
         }
 
-        rows.push(hook_entry);
+        rows.push(ListRow {
+            group,
+            pre_hook: table::check(pre_hook),
+            post_hook: table::check(post_hook),
+            rm_hook: table::check(rm_hook),
+        });
     }
 
     if rows.is_empty() {
-        println!("{}", "No hooks have been set up yet.".to_string().yellow());
+        println!(
+            "{}",
+            "No hooks have been set up yet."
+                .to_string()
+                .if_supports_color(Stdout, |t| t.yellow())
+        );
         return Ok(());
     }
+    rows.sort_by(|row, other| row.group.cmp(&other.group));
 
-    use tabled::{Margin, Style};
-
-    let mut hooks_list = Table::new(rows);
-    hooks_list
-        .with(Style::rounded())
-        .with(Margin::new(4, 4, 1, 1))
-        .with(Modify::new(Segment::new(1.., 1..)).with(Alignment::center()));
-    println!("{hooks_list}");
+    table::print(&rows);
 
     Ok(())
 }
 
-pub fn ls_secrets_cmd(profile: Option<String>) -> Result<(), ExitCode> {
-    let secrets_dir = dotfiles::get_dotfiles_path(profile)
-        .unwrap()
-        .join("Secrets");
+/// Everything `tuckr ls groups` shows about a group
+#[derive(Debug, PartialEq)]
+struct GroupInfo {
+    name: String,
+    /// which of Configs, Hooks and Secrets the group is in
+    dtypes: Vec<DotfileType>,
+    valid_target: bool,
+    /// None if the group isn't in Configs so there's nothing to deploy
+    state: Option<DeployState>,
+    files: usize,
+}
+
+/// Collects every group in the dotfiles along with where it's set up and how much of it is deployed
+fn get_groups_info(dotfiles_dir: &Path, states: &BTreeMap<String, DeployState>) -> Vec<GroupInfo> {
+    let (tx, rx) = mpsc::channel();
+    let mut groups: BTreeMap<String, GroupInfo> = BTreeMap::new();
+
+    for dtype in [
+        DotfileType::Configs,
+        DotfileType::Hooks,
+        DotfileType::Secrets,
+    ] {
+        let Ok(dir) = dotfiles_dir.join(dtype.dir_name()).read_dir() else {
+            continue;
+        };
 
-    let Ok(secrets) = secrets_dir.read_dir() else {
-        eprintln!("{}", t!("errors.no_dir_setup_for_x", x = "Secrets").red());
-        return Err(ReturnCode::NoSetupFolder.into());
+        for group_dir in dir.flatten() {
+            let group_dir = group_dir.path();
+            if !group_dir.is_dir() || is_ignored_file(&group_dir) {
+                continue;
+            }
+
+            let name = group_dir
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned();
+            let files = DirWalk::new(&group_dir)
+                .errors(tx.clone())
+                .filter(|file| !file.is_dir())
+                .count();
+
+            let info = groups.entry(name.clone()).or_insert_with(|| GroupInfo {
+                valid_target: dotfiles::group_is_valid_target(&name),
+                name,
+                dtypes: Vec::new(),
+                state: None,
+                files: 0,
+            });
+
+            if dtype == DotfileType::Configs {
+                info.state = Some(
+                    states
+                        .get(&info.name)
+                        .copied()
+                        .unwrap_or(DeployState::NotDeployed),
+                );
+            }
+            info.dtypes.push(dtype);
+            info.files += files;
+        }
+    }
+
+    drop(tx);
+    for err in rx {
+        errors::print(err.to_string());
+    }
+
+    groups.into_values().collect()
+}
+
+/// Lists every group with where it's set up, whether it targets this machine and how much of it is deployed
+///
+/// Filters are combined, so `--not-deployed --invalid-target` shows groups that can't be deployed here
+pub fn ls_groups_cmd(
+    profile: Option<String>,
+    deployed: bool,
+    not_deployed: bool,
+    invalid_target: bool,
+) -> Result<(), TuckrError> {
+    let dotfiles_dir = match dotfiles::get_dotfiles_path(profile.clone()) {
+        Ok(dir) => dir,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::CouldntFindDotfiles);
+        }
     };
 
-    let secrets: Vec<_> = secrets.collect();
+    let states = symlinks::get_deploy_states(profile)?;
+    let groups = get_groups_info(&dotfiles_dir, &states);
+    if groups.is_empty() {
+        println!(
+            "{}",
+            t!("errors.no_x_setup_yet", x = "groups").if_supports_color(Stdout, |t| t.yellow())
+        );
+        return Ok(());
+    }
+
+    let groups: Vec<_> = groups
+        .into_iter()
+        .filter(|group| !deployed || group.state == Some(DeployState::Deployed))
+        .filter(|group| {
+            !not_deployed
+                || matches!(
+                    group.state,
+                    Some(DeployState::NotDeployed | DeployState::Partial)
+                )
+        })
+        .filter(|group| !invalid_target || !group.valid_target)
+        .collect();
+
+    if groups.is_empty() {
+        println!(
+            "{}",
+            t!("info.no_matching_groups").if_supports_color(Stdout, |t| t.yellow())
+        );
+        return Ok(());
+    }
+
+    #[derive(Tabled)]
+    struct ListRow {
+        #[tabled(rename = "Group")]
+        group: String,
+        #[tabled(rename = "Configs")]
+        configs: String,
+        #[tabled(rename = "Hooks")]
+        hooks: String,
+        #[tabled(rename = "Secrets")]
+        secrets: String,
+        #[tabled(rename = "Valid Target")]
+        valid_target: String,
+        #[tabled(rename = "State")]
+        state: String,
+        #[tabled(rename = "Files")]
+        files: usize,
+    }
+
+    let rows: Vec<_> = groups
+        .into_iter()
+        .map(|group| ListRow {
+            configs: table::check(group.dtypes.contains(&DotfileType::Configs)),
+            hooks: table::check(group.dtypes.contains(&DotfileType::Hooks)),
+            secrets: table::check(group.dtypes.contains(&DotfileType::Secrets)),
+            valid_target: table::check(group.valid_target),
+            state: match group.state {
+                Some(DeployState::Deployed) => "deployed"
+                    .if_supports_color(Stdout, |t| t.green())
+                    .to_string(),
+                Some(DeployState::Partial) => "partial"
+                    .if_supports_color(Stdout, |t| t.yellow())
+                    .to_string(),
+                Some(DeployState::NotDeployed) => "not deployed"
+                    .if_supports_color(Stdout, |t| t.red())
+                    .to_string(),
+                None => "-".into(),
+            },
+            group: group.name,
+            files: group.files,
+        })
+        .collect();
+
+    table::print(&rows);
+
+    Ok(())
+}
+
+pub fn ls_secrets_cmd(profile: Option<String>) -> Result<(), TuckrError> {
+    let dirs = dotfiles::get_group_dirs(profile, DotfileType::Secrets);
+    if dirs.is_empty() {
+        errors::print(error!("no_dir_setup_for_x", x = "Secrets"));
+        return Err(TuckrError::NoSetupFolder);
+    }
+
+    let secrets: Vec<_> = dirs
+        .iter()
+        .flat_map(|dir| dir.read_dir().unwrap())
+        .collect();
 
     if secrets.is_empty() {
-        eprintln!("{}", t!("errors.no_x_setup_yet", x = "secrets").yellow());
-        return Err(ExitCode::FAILURE);
+        eprintln!(
+            "{}",
+            t!("errors.no_x_setup_yet", x = "secrets").if_supports_color(Stderr, |t| t.yellow())
+        );
+        return Err(TuckrError::Failed);
     }
 
-    println!("{}:", t!("info.x_available", x = "Secrets"));
-    for secret in secrets {
-        let secret = secret.unwrap();
-        println!("\t{}", secret.file_name().to_str().unwrap());
+    #[derive(Tabled)]
+    struct ListRow {
+        #[tabled(rename = "Group")]
+        group: String,
+        #[tabled(rename = "Archive")]
+        archive: String,
+        #[tabled(rename = "Files")]
+        files: String,
+        #[tabled(rename = "Backend")]
+        backend: String,
     }
 
+    let mut rows: Vec<_> = secrets
+        .into_iter()
+        .map(|secret| {
+            let secret = secret.unwrap();
+            let name = secret.file_name().to_string_lossy().into_owned();
+            let path = secret.path();
+
+            // archives are encrypted as a whole so their files can't be counted without decrypting them
+            let (group, archive, files) = match secrets::archive_group(&name) {
+                Some(group) if path.is_file() => (group.to_string(), true, "-".into()),
+                _ if path.is_dir() => {
+                    let files = DirWalk::new(&path).filter(|file| !file.is_dir()).count();
+                    (name, false, files.to_string())
+                }
+                _ => (name, false, "1".into()),
+            };
+
+            ListRow {
+                backend: format!("{:?}", config::get().secrets_backend(&group)).to_lowercase(),
+                archive: table::check(archive),
+                group,
+                files,
+            }
+        })
+        .collect();
+    // the sort is stable so the profile's own secrets shadow the shared ones
+    rows.sort_by(|row, other| row.group.cmp(&other.group));
+    rows.dedup_by(|row, previous| row.group == previous.group);
+
+    table::print(&rows);
+
     Ok(())
 }
 
-pub fn ls_profiles_cmd() -> Result<(), ExitCode> {
+/// Returns the profiles of the dotfiles directories found in the places tuckr looks for them
+pub fn get_profiles() -> HashSet<String> {
     let home_dir = dirs::home_dir().unwrap();
     let config_dir = dirs::config_dir().unwrap();
-    let custom_target_dir = std::env::var("TUCKR_TARGET");
+    let custom_target_dir = dotfiles::custom_target_dir();
+    let custom_tuckr_home = std::env::var("TUCKR_HOME");
 
-    let profiles = {
-        let mut available_profiles = HashSet::new();
+    let mut available_profiles = HashSet::new();
 
-        let dirs = {
-            let mut dirs = vec![home_dir, config_dir];
-            if let Ok(target) = custom_target_dir {
-                dirs.push(target.into());
-            }
-            dirs
+    let dirs = {
+        let mut dirs = vec![home_dir, config_dir];
+        if let Some(target) = custom_target_dir {
+            dirs.push(target);
+        }
+        if let Ok(tuckr_home) = custom_tuckr_home {
+            dirs.push(tuckr_home.into());
+        }
+        dirs
+    };
+
+    for dir in dirs {
+        let Ok(dir) = dir.read_dir() else {
+            continue;
         };
 
-        for dir in dirs {
-            for file in dir.read_dir().unwrap() {
-                let file = file.unwrap();
+        for file in dir {
+            let file = file.unwrap();
+            if !file.path().is_dir() {
+                continue;
+            }
 
-                let Some(profile) = dotfiles::get_dotfile_profile_from_path(file.path()) else {
-                    continue;
-                };
+            let Some(profile) = dotfiles::get_dotfile_profile_from_path(file.path()) else {
+                continue;
+            };
 
-                available_profiles.insert(profile);
-            }
+            available_profiles.insert(profile);
         }
+    }
 
-        available_profiles
-    };
+    available_profiles
+}
+
+pub fn ls_profiles_cmd() -> Result<(), TuckrError> {
+    let profiles = get_profiles();
 
     if profiles.is_empty() {
-        println!("{}", t!("errors.no_x_setup_yet", x = "profiles").yellow());
+        println!(
+            "{}",
+            t!("errors.no_x_setup_yet", x = "profiles").if_supports_color(Stdout, |t| t.yellow())
+        );
         return Ok(());
     }
 
@@ -494,12 +1482,12 @@ pub fn ls_profiles_cmd() -> Result<(), ExitCode> {
     Ok(())
 }
 
-pub fn groupis_cmd(profile: Option<String>, files: &[String]) -> Result<(), ExitCode> {
+pub fn groupis_cmd(profile: Option<String>, files: &[String]) -> Result<(), TuckrError> {
     let dotfiles_dir = match dotfiles::get_dotfiles_path(profile) {
         Ok(path) => path,
         Err(e) => {
-            eprintln!("{e}");
-            return Err(ReturnCode::NoSetupFolder.into());
+            errors::print(e);
+            return Err(TuckrError::NoSetupFolder);
         }
     }
     .join("Configs");
@@ -521,13 +1509,13 @@ pub fn groupis_cmd(profile: Option<String>, files: &[String]) -> Result<(), Exit
         let mut file_path = match PathBuf::from(file).canonicalize() {
             Ok(fp) => fp,
             Err(err) => {
-                eprintln!("{err}");
+                errors::print(err);
                 continue;
             }
         };
 
         if !file_path.exists() {
-            eprintln!("{}", t!("errors.x_doesnt_exist", x = file).red());
+            errors::print(error!("x_doesnt_exist", x = file));
             continue;
         }
 
@@ -539,7 +1527,7 @@ pub fn groupis_cmd(profile: Option<String>, files: &[String]) -> Result<(), Exit
         while !file_path.is_symlink() {
             // continuosly go up a directory trying to find where the symlink is
             if !file_path.pop() {
-                eprintln!("{}", t!("errors.not_a_tuckr_dotfile", file = file).red());
+                errors::print(error!("not_a_tuckr_dotfile", file = file));
                 continue 'next_file;
             }
         }
@@ -556,7 +1544,7 @@ pub fn groupis_cmd(profile: Option<String>, files: &[String]) -> Result<(), Exit
             let dotfile = match dotfiles::Dotfile::try_from(dotfile_path) {
                 Ok(dotfile) => dotfile,
                 Err(err) => {
-                    eprintln!("{err}");
+                    errors::print(err);
                     continue;
                 }
             };
@@ -571,6 +1559,8 @@ pub fn groupis_cmd(profile: Option<String>, files: &[String]) -> Result<(), Exit
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::io::Write;
+    use std::sync::mpsc;
 
     #[must_use = "must be used before every test is conducted"]
     struct FileopsTest {
@@ -608,13 +1598,136 @@ mod tests {
         }
     }
 
+    #[test]
+    fn create_new_group() {
+        let ft = FileopsTest::start();
+
+        assert!(new_group_cmd(None, false, "bad/name").is_err());
+        assert!(new_group_cmd(None, true, "nvim").is_ok());
+        assert!(!ft.dotfiles_dir.join("Configs").join("nvim").exists());
+
+        new_group_cmd(None, false, "nvim").unwrap();
+        assert!(ft.dotfiles_dir.join("Configs").join("nvim").is_dir());
+        assert!(ft.dotfiles_dir.join("Hooks").join("nvim").is_dir());
+        assert!(new_group_cmd(None, false, "nvim").is_err());
+    }
+
+    #[test]
+    fn walk_directories() {
+        let ft = FileopsTest::start();
+        let dir = ft.dotfiles_dir.join("walk");
+        fs::create_dir_all(dir.join("b").join("c")).unwrap();
+        for file in ["a", "b/file", "b/c/file", "z"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        symlink(&dir.join("b"), &dir.join("link")).unwrap();
+
+        let walk = |walker: DirWalk| -> Vec<String> {
+            walker
+                .map(|f| f.strip_prefix(&dir).unwrap().to_str().unwrap().to_owned())
+                .collect()
+        };
+
+        assert_eq!(
+            walk(DirWalk::new(&dir).sorted(true).follow_symlinks(false)),
+            ["a", "b", "b/c", "b/c/file", "b/file", "link", "z"]
+        );
+        assert_eq!(
+            walk(DirWalk::new(&dir).sorted(true).max_depth(2)),
+            [
+                "a",
+                "b",
+                "b/c",
+                "b/file",
+                "link",
+                "link/c",
+                "link/file",
+                "z"
+            ]
+        );
+        assert_eq!(
+            walk(
+                DirWalk::new(&dir)
+                    .sorted(true)
+                    .ignore(|f| f.ends_with("b") || f.ends_with("link"))
+            ),
+            ["a", "z"]
+        );
+
+        let walker = || DirWalk::new(&dir).sorted(true);
+        assert_eq!(
+            walker().par_walk(4),
+            walker().collect::<Vec<_>>(),
+            "walking in parallel changed the order"
+        );
+
+        let (errors, unreadable_dirs) = mpsc::channel();
+        assert_eq!(DirWalk::new(dir.join("missing")).errors(errors).count(), 0);
+        assert!(
+            unreadable_dirs
+                .try_recv()
+                .unwrap()
+                .path
+                .ends_with("missing")
+        );
+    }
+
+    #[test]
+    fn par_map_keeps_order() {
+        let items: Vec<usize> = (0..1000).collect();
+        let squares: Vec<usize> = items.iter().map(|i| i * i).collect();
+
+        assert_eq!(par_map(8, &items, |i| i * i), squares);
+        assert_eq!(par_map(1, &items, |i| i * i), squares);
+        assert!(par_map(8, &[] as &[usize], |i| *i).is_empty());
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn symlinks_relative_to_their_dir() {
+        assert_eq!(
+            relative_path(
+                Path::new("/home/user/.config/dotfiles/Configs/zsh/.zshrc"),
+                Path::new("/home/user")
+            ),
+            Some(PathBuf::from(".config/dotfiles/Configs/zsh/.zshrc"))
+        );
+        assert_eq!(
+            relative_path(
+                Path::new("/home/user/.config/dotfiles/Configs/git/.config/git/config"),
+                Path::new("/home/user/.config/git/../git")
+            ),
+            Some(PathBuf::from("../dotfiles/Configs/git/.config/git/config"))
+        );
+
+        let ft = FileopsTest::start();
+        let dotfile = ft.dotfiles_dir.join("Configs").join("zsh").join(".zshrc");
+        fs::create_dir_all(dotfile.parent().unwrap()).unwrap();
+        fs::write(&dotfile, "").unwrap();
+
+        let absolute = ft.target_dir.join("absolute");
+        let relative = ft.target_dir.join("relative");
+        symlink(&dotfile, &absolute).unwrap();
+        let source = relative_path(
+            &dotfile.canonicalize().unwrap(),
+            &ft.target_dir.canonicalize().unwrap(),
+        )
+        .unwrap();
+        symlink(&source, &relative).unwrap();
+
+        assert!(fs::read_link(&relative).unwrap().is_relative());
+        assert!(links_to(&absolute, &dotfile));
+        assert!(links_to(&relative, &dotfile));
+        assert!(!links_to(&relative, &ft.dotfiles_dir.join("Configs")));
+    }
+
     #[test]
     fn push_files() {
         let ft = FileopsTest::start();
 
         let file_path = ft.target_dir.join("test");
         let mut file = fs::File::create(&file_path).unwrap();
-        file.write("this is a test".as_bytes()).unwrap();
+        file.write_all("this is a test".as_bytes()).unwrap();
 
         let pushed_file = ft
             .dotfiles_dir
@@ -638,7 +1751,7 @@ mod tests {
             pushed_file.exists() && first_pushed_content == fs::read_to_string(&file_path).unwrap()
         );
 
-        file.write("something something".as_bytes()).unwrap();
+        file.write_all("something something".as_bytes()).unwrap();
         super::push_cmd(
             None,
             false,
@@ -661,12 +1774,12 @@ mod tests {
         let dir1 = ft.target_dir.join("dir1");
         fs::create_dir_all(&dir1).unwrap();
         let mut file1 = fs::File::create(dir1.join("file")).unwrap();
-        file1.write("test".as_bytes()).unwrap();
+        file1.write_all("test".as_bytes()).unwrap();
 
         let dir2 = ft.target_dir.join("dir2");
         fs::create_dir_all(&dir2).unwrap();
         let mut file2 = fs::File::create(dir2.join("file")).unwrap();
-        file2.write("test".as_bytes()).unwrap();
+        file2.write_all("test".as_bytes()).unwrap();
 
         // never used because it is empty
         fs::create_dir_all(ft.target_dir.join("dir3")).unwrap();
@@ -698,7 +1811,7 @@ mod tests {
 
         fs::create_dir_all(&ft.target_dir).unwrap();
         let mut file = fs::File::create(ft.target_dir.join("file")).unwrap();
-        file.write("test".as_bytes()).unwrap();
+        file.write_all("test".as_bytes()).unwrap();
 
         let group_dir = ft
             .dotfiles_dir
@@ -720,9 +1833,124 @@ mod tests {
         assert!(!group_dir.exists());
     }
 
+    #[test]
+    fn resolve_files_to_edit() {
+        let ft = FileopsTest::start();
+        let group_dir = ft.dotfiles_dir.join("Configs").join("test");
+        let target_basepath = dotfiles::get_target_basepath(&ft.target_dir).unwrap();
+
+        // new files are created in the group
+        let new_file = get_edit_path(false, &group_dir, &target_basepath.join("new")).unwrap();
+        assert_eq!(new_file, group_dir.join(&target_basepath).join("new"));
+        assert!(new_file.is_file());
+
+        // untracked files are adopted and symlinked back
+        let untracked = ft.target_dir.join("untracked");
+        fs::write(&untracked, "untracked").unwrap();
+        let adopted = get_edit_path(false, &group_dir, &untracked).unwrap();
+        assert_eq!(adopted, group_dir.join(&target_basepath).join("untracked"));
+        assert_eq!(fs::read_to_string(&adopted).unwrap(), "untracked");
+        assert_eq!(fs::read_link(&untracked).unwrap(), adopted);
+
+        // templates are found by the name they're deployed with
+        let template = group_dir.join(&target_basepath).join("config.tmpl");
+        fs::write(&template, "{{ tuckr.os }}").unwrap();
+        assert_eq!(
+            get_edit_path(false, &group_dir, &target_basepath.join("config")).unwrap(),
+            template
+        );
+
+        assert!(get_edit_path(false, &group_dir, Path::new("/not/in/target")).is_err());
+    }
+
+    #[test]
+    fn collect_groups_info() {
+        let ft = FileopsTest::start();
+        let other_target = match std::env::consts::FAMILY {
+            "windows" => "zsh_unix",
+            _ => "zsh_windows",
+        };
+
+        let configs = ft.dotfiles_dir.join("Configs");
+        fs::create_dir_all(configs.join("nvim").join(".config").join("nvim")).unwrap();
+        fs::write(
+            configs
+                .join("nvim")
+                .join(".config")
+                .join("nvim")
+                .join("init.lua"),
+            "",
+        )
+        .unwrap();
+        fs::write(configs.join("nvim").join(".vimrc"), "").unwrap();
+        fs::create_dir_all(configs.join(other_target)).unwrap();
+        fs::create_dir_all(ft.dotfiles_dir.join("Hooks").join("nvim")).unwrap();
+        fs::write(
+            ft.dotfiles_dir.join("Hooks").join("nvim").join("pre.sh"),
+            "",
+        )
+        .unwrap();
+        fs::create_dir_all(ft.dotfiles_dir.join("Secrets").join("ssh")).unwrap();
+
+        let states = BTreeMap::from([("nvim".to_string(), DeployState::Partial)]);
+        let groups = get_groups_info(&ft.dotfiles_dir, &states);
+
+        assert_eq!(
+            groups,
+            [
+                GroupInfo {
+                    name: "nvim".into(),
+                    dtypes: vec![DotfileType::Configs, DotfileType::Hooks],
+                    valid_target: true,
+                    state: Some(DeployState::Partial),
+                    files: 3,
+                },
+                GroupInfo {
+                    name: "ssh".into(),
+                    dtypes: vec![DotfileType::Secrets],
+                    valid_target: true,
+                    state: None,
+                    files: 0,
+                },
+                GroupInfo {
+                    name: other_target.into(),
+                    dtypes: vec![DotfileType::Configs],
+                    valid_target: false,
+                    state: Some(DeployState::NotDeployed),
+                    files: 0,
+                },
+            ]
+        );
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn move_directory_by_copying() {
+        let ft = FileopsTest::start();
+        let src = ft.target_dir.join("conflict");
+        let dest = ft.dotfiles_dir.join("moved");
+        fs::create_dir_all(src.join("nested")).unwrap();
+        fs::write(src.join("nested").join("config~"), "backup").unwrap();
+        fs::write(src.join(".directory"), "kde").unwrap();
+        symlink(Path::new("nested"), &src.join("link")).unwrap();
+
+        move_by_copying(&src, &dest).unwrap();
+
+        assert!(!src.exists());
+        assert_eq!(
+            fs::read_to_string(dest.join("nested").join("config~")).unwrap(),
+            "backup"
+        );
+        assert!(dest.join(".directory").exists());
+        assert_eq!(
+            fs::read_link(dest.join("link")).unwrap(),
+            Path::new("nested")
+        );
+    }
+
     #[test]
     fn ignore_garbage_files() {
-        assert!(is_ignored_file("asdfadsfaf") == false);
+        assert!(!is_ignored_file("asdfadsfaf"));
 
         if cfg!(target_os = "macos") {
             assert!(is_ignored_file(".DS_Store"));
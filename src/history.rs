@@ -0,0 +1,840 @@
+//! Uses the git history of the dotfiles repository
+//!
+//! - `tuckr status --at <rev>` compares the deployed dotfiles with what the repository prescribed at a revision
+//! - `tuckr bisect <group>` drives git bisect to find the commit that broke a group
+//! - commands that change the dotfiles can commit their changes with `--commit` or `auto_commit` in tuckr.toml
+//! - `tuckr clone`, `tuckr sync` and `tuckr push-remote` keep the dotfiles in sync with their remote
+//!   (`tuckr daemon` does it on a schedule, see daemon.rs)
+//!
+//! git is used through its command line so that any repository it can read works,
+//! the dotfiles directory can either be the repository itself or be inside of it.
+
+use crate::config;
+use crate::dotfiles::{self, Dotfile};
+use crate::error;
+use crate::errors::{self, TuckrError};
+use crate::fileops::{self, DirWalk};
+use crate::state;
+use crate::symlinks;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::{Stderr, Stdout};
+use rust_i18n::t;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Runs git inside of `dir` and returns what it printed
+pub fn git(dir: &Path, args: &[&str], stdin: Option<&str>) -> Result<String, String> {
+    let mut git = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        // git's messages are parsed so they must not be translated
+        .env("LC_ALL", "C")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("git: {e}"))?;
+
+    if let Some(stdin) = stdin {
+        git.stdin
+            .take()
+            .unwrap()
+            .write_all(stdin.as_bytes())
+            .map_err(|e| format!("git: {e}"))?;
+    }
+
+    let output = git.wait_with_output().map_err(|e| format!("git: {e}"))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Adds what tuckr only keeps in the dotfiles for this machine to the repository's
+/// .git/info/exclude so that it can't be committed by accident, even without the .gitignore
+/// that `tuckr init` writes
+///
+/// Nothing is done if the dotfiles aren't in a git repository, failing to write the exclude
+/// file isn't an error either since it only guards against mistakes
+pub fn exclude_locally(dotfiles_dir: &Path) {
+    let args = ["rev-parse", "--git-path", "info/exclude", "--show-prefix"];
+    let Ok(output) = git(dotfiles_dir, &args, None) else {
+        return;
+    };
+    let mut lines = output.lines();
+    let Some(exclude_path) = lines.next().map(|path| dotfiles_dir.join(path)) else {
+        return;
+    };
+    // the dotfiles can be in a subdirectory of the repository
+    let prefix = lines.next().unwrap_or_default();
+
+    let excludes = std::fs::read_to_string(&exclude_path).unwrap_or_default();
+    let missing: String = dotfiles::MACHINE_LOCAL
+        .iter()
+        .map(|(local, _)| local.gitignore_pattern(prefix))
+        .filter(|pattern| !excludes.lines().any(|line| line.trim() == pattern))
+        .map(|pattern| pattern + "\n")
+        .collect();
+    if missing.is_empty() {
+        return;
+    }
+
+    let separator = if excludes.is_empty() || excludes.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    let written = std::fs::create_dir_all(exclude_path.parent().unwrap()).and_then(|_| {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&exclude_path)?
+            .write_all(format!("{separator}{missing}").as_bytes())
+    });
+
+    if let Err(err) = written {
+        tracing::debug!(path = %exclude_path.display(), %err, "couldn't exclude the machine-local files from git");
+    }
+}
+
+/// Runs git with the terminal attached so that its progress and prompts (e.g. for credentials) are shown
+fn git_interactive(dir: Option<&Path>, args: &[&str]) -> Result<(), errors::Error> {
+    let mut git = Command::new("git");
+    if let Some(dir) = dir {
+        git.arg("-C").arg(dir);
+    }
+
+    let status = git.args(args).status().map_err(|e| format!("git: {e}"))?;
+    if !status.success() {
+        return Err(error!("git_failed", cmd = format!("git {}", args[0])));
+    }
+
+    Ok(())
+}
+
+/// Parses the output of `git ls-tree -r -z` into the path and object id of each file
+fn parse_ls_tree(ls_tree: &str) -> Vec<(PathBuf, String)> {
+    ls_tree
+        .split('\0')
+        .filter_map(|entry| {
+            // entries look like: <mode> SP <type> SP <object> TAB <path>
+            let (info, path) = entry.split_once('\t')?;
+            let mut info = info.split(' ');
+            let (_mode, kind, object) = (info.next()?, info.next()?, info.next()?);
+
+            (kind == "blob").then(|| (PathBuf::from(path), object.to_string()))
+        })
+        .collect()
+}
+
+/// Where each dotfile ends up on the system along with the id of its content
+type Deployment = BTreeMap<PathBuf, (String, String)>;
+
+/// Adds the dotfile to the deployment unless a dotfile with a higher priority targets the same path
+fn insert_dotfile(deployment: &mut Deployment, groups: &[String], file: PathBuf, object: String) {
+    let is_ignored =
+        |c: Component| matches!(c, Component::Normal(name) if fileops::is_ignored_file(name));
+    if file.components().any(is_ignored) {
+        return;
+    }
+
+    let Ok(dotfile) = Dotfile::try_from(file) else {
+        return;
+    };
+
+    let base_group = dotfiles::group_without_target(&dotfile.group_name);
+    if !dotfile.is_valid_target() || (!groups.is_empty() && !groups.iter().any(|g| g == base_group))
+    {
+        return;
+    }
+
+    let Ok(target) = dotfile.to_target_path() else {
+        return;
+    };
+
+    let replaces_existing = deployment.get(&target).is_none_or(|(group, _)| {
+        dotfiles::get_group_priority(&dotfile.group_name) > dotfiles::get_group_priority(group)
+    });
+
+    if replaces_existing {
+        deployment.insert(target, (dotfile.group_name, object));
+    }
+}
+
+/// Returns what the dotfiles at `rev` would deploy
+fn get_deployment_at(
+    dotfiles_dir: &Path,
+    rev: &str,
+    groups: &[String],
+) -> Result<Deployment, String> {
+    let commit = format!("{rev}^{{commit}}");
+    git(
+        dotfiles_dir,
+        &["rev-parse", "--verify", "--quiet", &commit],
+        None,
+    )
+    .map_err(|_| error!("invalid_revision", rev = rev))?;
+
+    // paths are relative to the dotfiles directory since git is run from within it
+    let ls_tree = git(
+        dotfiles_dir,
+        &["ls-tree", "-r", "-z", rev, "--", "Configs"],
+        None,
+    )?;
+
+    let mut deployment = Deployment::new();
+    for (path, object) in parse_ls_tree(&ls_tree) {
+        insert_dotfile(&mut deployment, groups, dotfiles_dir.join(path), object);
+    }
+
+    Ok(deployment)
+}
+
+/// Returns what is currently deployed from the dotfiles, copies that were modified count as changed content
+fn get_current_deployment(
+    profile: Option<String>,
+    dotfiles_dir: &Path,
+    groups: &[String],
+) -> Result<Deployment, String> {
+    let configs_dir = dotfiles_dir.join("Configs");
+    if !configs_dir.exists() {
+        return Ok(Deployment::new());
+    }
+
+    let copies = state::State::load(profile)?.copies;
+
+    let mut deployed = Vec::new();
+    for file in DirWalk::new(&configs_dir) {
+        if file.is_dir() {
+            continue;
+        }
+
+        let Ok(target) = Dotfile::try_from(file.clone()).and_then(|f| f.to_target_path()) else {
+            continue;
+        };
+
+        // symlinks might be on any of the target's parent directories so the paths are resolved
+        let is_symlinked = target.canonicalize().ok() == file.canonicalize().ok();
+        let is_copied = copies
+            .get(&target)
+            .is_some_and(|copy| copy.source == file && target.exists());
+
+        if is_symlinked || is_copied {
+            deployed.push((file, target));
+        }
+    }
+
+    // the deployed files are hashed the same way git does so they can be compared to the revision's files
+    let targets: Vec<_> = deployed
+        .iter()
+        .map(|(_, target)| target.to_str().unwrap())
+        .collect();
+    let objects = if targets.is_empty() {
+        String::new()
+    } else {
+        git(
+            dotfiles_dir,
+            &["hash-object", "--stdin-paths"],
+            Some(&targets.join("\n")),
+        )?
+    };
+
+    let mut deployment = Deployment::new();
+    for ((file, _), object) in deployed.into_iter().zip(objects.lines()) {
+        insert_dotfile(&mut deployment, groups, file, object.into());
+    }
+
+    Ok(deployment)
+}
+
+/// Prints how the deployed dotfiles differ from what the dotfiles prescribed at `rev`
+pub fn status_at_cmd(
+    profile: Option<String>,
+    rev: &str,
+    groups: &[String],
+) -> Result<(), TuckrError> {
+    let dotfiles_dir = match dotfiles::get_dotfiles_path(profile.clone()) {
+        Ok(dir) => dir,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::CouldntFindDotfiles);
+        }
+    };
+
+    fn report_err(err: impl Into<errors::Error>) -> TuckrError {
+        errors::print(err);
+        TuckrError::Failed
+    }
+
+    let before = get_deployment_at(&dotfiles_dir, rev, groups).map_err(report_err)?;
+    let now = get_current_deployment(profile, &dotfiles_dir, groups).map_err(report_err)?;
+
+    let targets: BTreeSet<_> = before.keys().chain(now.keys()).collect();
+    let mut changed = false;
+
+    for target in targets {
+        let line = match (before.get(target), now.get(target)) {
+            (Some(_), None) => format!("- {}", target.display())
+                .if_supports_color(Stdout, |t| t.red())
+                .to_string(),
+            (None, Some(_)) => format!("+ {}", target.display())
+                .if_supports_color(Stdout, |t| t.green())
+                .to_string(),
+            (Some((_, before)), Some((_, now))) if before != now => {
+                format!("~ {}", target.display())
+                    .if_supports_color(Stdout, |t| t.yellow())
+                    .to_string()
+            }
+            _ => continue,
+        };
+
+        if !changed {
+            println!("{}:", t!("info.changes_since_x", rev = rev));
+            changed = true;
+        }
+        println!("\t{line}");
+    }
+
+    if !changed {
+        println!(
+            "{}",
+            t!("info.no_changes_since_x", rev = rev).if_supports_color(Stdout, |t| t.green())
+        );
+        return Ok(());
+    }
+
+    Err(TuckrError::Failed)
+}
+
+/// Answers to a bisect step
+#[derive(Debug, PartialEq)]
+enum BisectAnswer {
+    Good,
+    Bad,
+    Skip,
+    Quit,
+}
+
+impl BisectAnswer {
+    fn parse(answer: &str) -> Option<Self> {
+        match answer.trim().to_lowercase().as_str() {
+            "g" | "good" => Some(Self::Good),
+            "b" | "bad" => Some(Self::Bad),
+            "s" | "skip" => Some(Self::Skip),
+            "q" | "quit" => Some(Self::Quit),
+            _ => None,
+        }
+    }
+}
+
+fn ask_bisect_answer(group: &str, commit: &str) -> BisectAnswer {
+    loop {
+        print!(
+            "{} [g]ood/[b]ad/[s]kip/[q]uit: ",
+            t!(
+                "info.is_group_working_at",
+                group = group,
+                commit = commit.trim()
+            )
+        );
+        std::io::stdout()
+            .flush()
+            .expect("Could not print to stdout");
+
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer).unwrap_or_default() == 0 {
+            // stdin was closed so nobody is left to answer
+            return BisectAnswer::Quit;
+        }
+
+        if let Some(answer) = BisectAnswer::parse(&answer) {
+            return answer;
+        }
+    }
+}
+
+/// Finds the commit that broke a group by bisecting the commits that touched it
+///
+/// Every candidate revision is checked out and the group is redeployed so that it can be tried out
+/// before telling whether it's good or bad
+pub fn bisect_cmd(
+    profile: Option<String>,
+    group: &str,
+    good: &str,
+    bad: &str,
+) -> Result<(), TuckrError> {
+    let dotfiles_dir = match dotfiles::get_dotfiles_path(profile.clone()) {
+        Ok(dir) => dir,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::CouldntFindDotfiles);
+        }
+    };
+
+    if !dotfiles::dotfile_contains(profile.clone(), dotfiles::DotfileType::Configs, group) {
+        errors::print(error!("no_group", group = group));
+        return Err(TuckrError::NoSuchFileOrDir);
+    }
+
+    // only the commits that touched the group or one of its conditional groups are tested
+    let paths: Vec<String> = DirWalk::new(dotfiles_dir.join("Configs"))
+        .max_depth(1)
+        .sorted(true)
+        .filter_map(|dir| dir.file_name()?.to_str().map(String::from))
+        .filter(|name| dotfiles::group_without_target(name) == group)
+        .map(|name| format!("Configs/{name}"))
+        .collect();
+
+    let groups = [group.to_string()];
+    let remove_group = || symlinks::remove_cmd(profile.clone(), false, &groups, &[]);
+    let add_group = || {
+        symlinks::add_cmd(
+            profile.clone(),
+            false,
+            false,
+            false,
+            &groups,
+            &[],
+            false,
+            false,
+            true,
+        )
+    };
+
+    // the group is removed before every checkout, otherwise the symlinks of files
+    // that don't exist at the next revision would be left behind
+    remove_group()?;
+
+    let mut args = vec!["bisect", "start", bad, good, "--"];
+    args.extend(paths.iter().map(String::as_str));
+
+    let mut output = git(&dotfiles_dir, &args, None);
+    let result = loop {
+        let step = match output {
+            Ok(step) => step,
+            Err(err) => {
+                errors::print(err);
+                break Err(TuckrError::Failed);
+            }
+        };
+
+        if step.contains("first bad commit") {
+            println!("{}", step.trim());
+            break Ok(());
+        }
+
+        println!(
+            "{}",
+            step.lines()
+                .next()
+                .unwrap_or_default()
+                .if_supports_color(Stdout, |t| t.yellow())
+        );
+
+        // a failed step must not return early, the bisect still has to be reset below
+        if let Err(err) = add_group() {
+            break Err(err);
+        }
+        let commit = git(&dotfiles_dir, &["log", "-1", "--format=%h %s"], None).unwrap_or_default();
+        let answer = ask_bisect_answer(group, &commit);
+        if let Err(err) = remove_group() {
+            break Err(err);
+        }
+
+        output = match answer {
+            BisectAnswer::Good => git(&dotfiles_dir, &["bisect", "good"], None),
+            BisectAnswer::Bad => git(&dotfiles_dir, &["bisect", "bad"], None),
+            BisectAnswer::Skip => git(&dotfiles_dir, &["bisect", "skip"], None),
+            BisectAnswer::Quit => break Ok(()),
+        };
+    };
+
+    // goes back to where the repository was before bisecting
+    if let Err(err) = git(&dotfiles_dir, &["bisect", "reset"], None) {
+        errors::print(err);
+    }
+    add_group()?;
+
+    result
+}
+
+/// Commits the changes made to `paths`, which are relative to the dotfiles directory
+///
+/// Only these paths are committed so anything else the user staged is left alone.
+/// Returns false if there was nothing to commit
+fn commit_changes(dotfiles_dir: &Path, paths: &[String], message: &str) -> Result<bool, String> {
+    // git refuses pathspecs that don't match anything, e.g. an untracked group that was popped
+    let paths: Vec<&str> = paths
+        .iter()
+        .map(String::as_str)
+        .filter(|path| {
+            // magic pathspecs such as :(exclude) never fail to match
+            path.starts_with(':')
+                || dotfiles_dir.join(path).exists()
+                || git(dotfiles_dir, &["ls-files", "--", path], None)
+                    .is_ok_and(|files| !files.is_empty())
+        })
+        .collect();
+
+    if paths.is_empty() {
+        return Ok(false);
+    }
+
+    git(
+        dotfiles_dir,
+        &[&["add", "--all", "--"], &paths[..]].concat(),
+        None,
+    )?;
+
+    let staged = git(
+        dotfiles_dir,
+        &[&["diff", "--cached", "--name-only", "--"], &paths[..]].concat(),
+        None,
+    )?;
+    if staged.is_empty() {
+        return Ok(false);
+    }
+
+    git(
+        dotfiles_dir,
+        &[
+            &["commit", "--quiet", "--message", message, "--"],
+            &paths[..],
+        ]
+        .concat(),
+        None,
+    )?;
+
+    Ok(true)
+}
+
+/// Commits the changes a command made to the dotfiles when asked to by `--commit` or the configuration
+///
+/// paths: what the command changed, relative to the dotfiles directory
+pub fn auto_commit(
+    profile: Option<String>,
+    dry_run: bool,
+    commit: bool,
+    paths: &[String],
+    message: &str,
+) -> Result<(), TuckrError> {
+    if dry_run || !(commit || config::get().auto_commit) {
+        return Ok(());
+    }
+
+    let committed = dotfiles::get_dotfiles_path(profile)
+        .and_then(|dotfiles_dir| commit_changes(&dotfiles_dir, paths, message).map_err(Into::into));
+
+    match committed {
+        Ok(true) => {
+            println!(
+                "{}",
+                t!("info.committed_changes", message = message)
+                    .if_supports_color(Stdout, |t| t.green())
+            );
+            Ok(())
+        }
+        Ok(false) => Ok(()),
+        Err(err) => {
+            errors::print(error!("failed_to_commit", err_msg = err));
+            Err(TuckrError::Failed)
+        }
+    }
+}
+
+/// Clones a dotfiles repository to where `tuckr init` would've created the dotfiles
+pub fn clone_cmd(profile: Option<String>, dry_run: bool, url: &str) -> Result<(), TuckrError> {
+    let dotfiles_dir = fileops::get_new_dotfiles_path(profile);
+
+    if dotfiles_dir
+        .read_dir()
+        .is_ok_and(|mut dir| dir.next().is_some())
+    {
+        errors::print(error!(
+            "dotfiles_already_exist",
+            dir = dotfiles_dir.display()
+        ));
+        return Err(TuckrError::Failed);
+    }
+
+    if dry_run {
+        eprintln!(
+            "{} `{url}` to `{}`",
+            "cloning".if_supports_color(Stderr, |t| t.green()),
+            dotfiles_dir.display()
+        );
+        return Ok(());
+    }
+
+    if let Err(err) = git_interactive(None, &["clone", url, dotfiles_dir.to_str().unwrap()]) {
+        errors::print(err);
+        return Err(TuckrError::Failed);
+    }
+
+    println!(
+        "{}",
+        t!(
+            "info.dotfiles_cloned_to",
+            location = dotfiles_dir.display(),
+            cmd = "tuckr status"
+        )
+        .if_supports_color(Stdout, |t| t.green())
+    );
+
+    Ok(())
+}
+
+/// Returns the groups that the output of `git diff --name-only -z` touched in Configs
+fn get_changed_groups(diff: &str) -> BTreeSet<String> {
+    diff.split('\0')
+        .filter_map(|file| {
+            let mut components = Path::new(file).components();
+            // files directly inside of Configs don't belong to any group
+            match (components.next()?, components.next()?, components.next()) {
+                (Component::Normal(dir), Component::Normal(group), Some(_)) if dir == "Configs" => {
+                    Some(group.to_string_lossy().into_owned())
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Returns the groups that are currently deployed from the dotfiles
+pub fn deployed_groups(
+    profile: Option<String>,
+    dotfiles_dir: &Path,
+) -> Result<BTreeSet<String>, String> {
+    Ok(get_current_deployment(profile, dotfiles_dir, &[])?
+        .into_values()
+        .map(|(group, _)| group)
+        .collect())
+}
+
+/// Returns the groups whose files in Configs changed between two commits
+pub fn changed_groups(
+    dotfiles_dir: &Path,
+    before: &str,
+    after: &str,
+) -> Result<BTreeSet<String>, String> {
+    let diff = git(
+        dotfiles_dir,
+        &[
+            "diff",
+            "--name-only",
+            "--relative",
+            "-z",
+            before,
+            after,
+            "--",
+            "Configs",
+        ],
+        None,
+    )?;
+    Ok(get_changed_groups(&diff))
+}
+
+/// Pulls the dotfiles from their remote and reports which of the deployed groups changed
+///
+/// dry_run: only fetches and reports what pulling would change
+pub fn sync_cmd(profile: Option<String>, dry_run: bool) -> Result<(), TuckrError> {
+    let dotfiles_dir = match dotfiles::get_dotfiles_path(profile.clone()) {
+        Ok(dir) => dir,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::CouldntFindDotfiles);
+        }
+    };
+
+    fn report_err(err: impl Into<errors::Error>) -> TuckrError {
+        errors::print(err);
+        TuckrError::Failed
+    }
+
+    // the deployment has to be checked before pulling since files that were deleted upstream would be gone
+    let deployed_groups = deployed_groups(profile, &dotfiles_dir).map_err(report_err)?;
+
+    let rev_parse = |rev: &str| {
+        git(&dotfiles_dir, &["rev-parse", "--verify", rev], None)
+            .map(|commit| commit.trim().to_string())
+    };
+
+    let before = rev_parse("HEAD").map_err(report_err)?;
+    let after = if dry_run {
+        git_interactive(Some(&dotfiles_dir), &["fetch"]).map_err(report_err)?;
+        rev_parse("@{upstream}").map_err(report_err)?
+    } else {
+        git_interactive(Some(&dotfiles_dir), &["pull"]).map_err(report_err)?;
+        rev_parse("HEAD").map_err(report_err)?
+    };
+
+    if before == after {
+        println!(
+            "{}",
+            t!("info.dotfiles_up_to_date").if_supports_color(Stdout, |t| t.green())
+        );
+        return Ok(());
+    }
+
+    let (deployed, not_deployed): (Vec<_>, Vec<_>) = changed_groups(&dotfiles_dir, &before, &after)
+        .map_err(report_err)?
+        .into_iter()
+        .partition(|group| deployed_groups.contains(group));
+
+    if !deployed.is_empty() {
+        println!("{}:", t!("info.deployed_groups_that_changed"));
+        for group in &deployed {
+            println!("\t{}", group.if_supports_color(Stdout, |t| t.yellow()));
+        }
+        println!();
+    }
+
+    if !not_deployed.is_empty() {
+        println!("{}:", t!("info.other_groups_that_changed"));
+        for group in &not_deployed {
+            println!("\t{group}");
+        }
+        println!();
+    }
+
+    if !deployed.is_empty() {
+        println!(
+            "{}",
+            t!("info.learn_what_changed", cmd = "tuckr status <group...>")
+        );
+    }
+
+    Ok(())
+}
+
+/// Commits everything that changed in the dotfiles and pushes it to the remote
+///
+/// Machine-local files such as the state and backups are never committed
+pub fn push_remote_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    message: Option<String>,
+) -> Result<(), TuckrError> {
+    let dotfiles_dir = match dotfiles::get_dotfiles_path(profile) {
+        Ok(dir) => dir,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::CouldntFindDotfiles);
+        }
+    };
+
+    fn report_err(err: impl Into<errors::Error>) -> TuckrError {
+        errors::print(err);
+        TuckrError::Failed
+    }
+
+    let mut paths = vec![".".to_string()];
+    paths.extend(
+        dotfiles::MACHINE_LOCAL
+            .iter()
+            .map(|(local, _)| local.exclude_pathspec()),
+    );
+
+    if dry_run {
+        let mut args = vec!["status", "--short", "--"];
+        args.extend(paths.iter().map(String::as_str));
+        print!("{}", git(&dotfiles_dir, &args, None).map_err(report_err)?);
+        return Ok(());
+    }
+
+    let message = message.unwrap_or_else(|| match dotfiles::get_hostname() {
+        Some(hostname) => format!("Update dotfiles from {hostname}"),
+        None => "Update dotfiles".into(),
+    });
+
+    if commit_changes(&dotfiles_dir, &paths, &message).map_err(report_err)? {
+        println!(
+            "{}",
+            t!("info.committed_changes", message = message)
+                .if_supports_color(Stdout, |t| t.green())
+        );
+    }
+
+    git_interactive(Some(&dotfiles_dir), &["push"]).map_err(report_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_git_ls_tree() {
+        let ls_tree = "100644 blob 8a1218a1024a212bb3db30becd860315f9f3ac52\tConfigs/zsh/.zshrc\0\
+                       120000 blob 2a1d8ee4b3ecb3bd5a17e5e25d3a1e5e0a0e2a0b\tConfigs/nvim/init.lua\0\
+                       160000 commit 5a1d8ee4b3ecb3bd5a17e5e25d3a1e5e0a0e2a0b\tConfigs/plugin\0";
+
+        assert_eq!(
+            parse_ls_tree(ls_tree),
+            [
+                (
+                    PathBuf::from("Configs/zsh/.zshrc"),
+                    "8a1218a1024a212bb3db30becd860315f9f3ac52".to_string()
+                ),
+                (
+                    PathBuf::from("Configs/nvim/init.lua"),
+                    "2a1d8ee4b3ecb3bd5a17e5e25d3a1e5e0a0e2a0b".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn changed_groups_from_diff() {
+        let diff = "Configs/zsh/.zshrc\0Configs/zsh_linux/.zprofile\0Configs/zsh/.zshenv\0\
+                    Configs/nvim/init.lua\0Configs/README.md\0";
+
+        assert_eq!(
+            get_changed_groups(diff).into_iter().collect::<Vec<_>>(),
+            ["nvim", "zsh", "zsh_linux"]
+        );
+    }
+
+    #[test]
+    fn exclude_machine_local_files() {
+        let repo = dotfiles::get_dotfiles_path(None)
+            .unwrap()
+            .with_file_name("repo");
+        let dotfiles_dir = repo.join("dotfiles");
+        std::fs::create_dir_all(&dotfiles_dir).unwrap();
+        git(&repo, &["init", "-q"], None).unwrap();
+
+        exclude_locally(&dotfiles_dir);
+        exclude_locally(&dotfiles_dir);
+
+        let excludes = std::fs::read_to_string(repo.join(".git/info/exclude")).unwrap();
+        let excludes: Vec<_> = excludes
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .collect();
+        assert_eq!(
+            excludes,
+            [
+                "/dotfiles/.tuckr-state.json",
+                "/dotfiles/.backups/",
+                "/dotfiles/.logs/",
+                "/dotfiles/**/*.tuckr-rekey",
+                "/dotfiles/**/*.tuckr-old",
+            ]
+        );
+
+        std::fs::remove_dir_all(repo.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn parse_bisect_answers() {
+        assert_eq!(BisectAnswer::parse("g\n"), Some(BisectAnswer::Good));
+        assert_eq!(BisectAnswer::parse("BAD"), Some(BisectAnswer::Bad));
+        assert_eq!(BisectAnswer::parse(" skip "), Some(BisectAnswer::Skip));
+        assert_eq!(BisectAnswer::parse("q"), Some(BisectAnswer::Quit));
+        assert_eq!(BisectAnswer::parse("maybe"), None);
+    }
+}
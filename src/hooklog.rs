@@ -0,0 +1,215 @@
+//! Captures the output of hooks
+//!
+//! Instead of being interleaved with tuckr's own output, what hooks print is written to
+//! `$TUCKR_HOME/.logs/<group>/<timestamp>.log` (or `~/.local/share/tuckr/logs` when $TUCKR_HOME is
+//! not set) while a spinner shows which hook is running. Hooks of a group that run within the
+//! same second share a log. `--show-hook-output` prints the output as it comes as well.
+
+use crate::interactive;
+use crate::nested;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::{Stderr, Stdout};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// where the logs are kept in $TUCKR_HOME
+pub const LOGS_DIR: &str = ".logs";
+
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+static SHOW_OUTPUT: OnceLock<bool> = OnceLock::new();
+
+/// Prints the hooks' output as it comes besides logging it, set with `--show-hook-output`
+pub fn show_output() {
+    _ = SHOW_OUTPUT.set(true);
+}
+
+fn showing_output() -> bool {
+    SHOW_OUTPUT.get().copied().unwrap_or_default()
+}
+
+fn get_logs_dir() -> Option<PathBuf> {
+    match std::env::var("TUCKR_HOME") {
+        Ok(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join(LOGS_DIR)),
+        _ => Some(dirs::data_local_dir()?.join("tuckr").join("logs")),
+    }
+}
+
+/// Opens the log of the group's hooks and marks where the script's output starts
+fn open_log(logs_dir: &Path, group: &str, script: &Path) -> io::Result<(PathBuf, File)> {
+    let started = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    let path = logs_dir.join(group).join(format!("{started}.log"));
+
+    fs::create_dir_all(path.parent().unwrap())?;
+    let mut log = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(log, "==> {} <==", script.display())?;
+    Ok((path, log))
+}
+
+/// Copies one of the hook's output streams into the log, and to tuckr's own if it's shown
+fn copy_output(
+    mut output: impl Read,
+    log: Arc<Mutex<File>>,
+    mut echo: Option<impl Write>,
+) -> io::Result<()> {
+    let mut buf = [0; 8192];
+    loop {
+        let read = match output.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+
+        log.lock().unwrap().write_all(&buf[..read])?;
+        if let Some(echo) = &mut echo {
+            echo.write_all(&buf[..read])?;
+            echo.flush()?;
+        }
+    }
+}
+
+fn run_in(
+    logs_dir: &Path,
+    command: &mut Command,
+    group: &str,
+    script: &Path,
+) -> io::Result<(ExitStatus, PathBuf)> {
+    let (log_path, log) = open_log(logs_dir, group, script)?;
+    let log = Arc::new(Mutex::new(log));
+
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let show = showing_output();
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+    let copiers = [
+        thread::spawn({
+            let log = log.clone();
+            move || copy_output(stdout, log, show.then(io::stdout))
+        }),
+        thread::spawn({
+            let log = log.clone();
+            move || copy_output(stderr, log, show.then(io::stderr))
+        }),
+    ];
+
+    let name = script.file_name().unwrap_or_default().to_string_lossy();
+    let spin =
+        !show && !nested::is_nested() && !interactive::is_quiet() && io::stderr().is_terminal();
+    let started = Instant::now();
+    let mut frame = 0;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+
+        if spin {
+            eprint!(
+                "\r{} {name} {}",
+                SPINNER[frame % SPINNER.len()].if_supports_color(Stderr, |t| t.cyan()),
+                format!("{}s", started.elapsed().as_secs())
+                    .if_supports_color(Stderr, |t| t.dimmed())
+            );
+            frame += 1;
+        }
+        thread::sleep(Duration::from_millis(100));
+    };
+
+    for copier in copiers {
+        if let Ok(Err(err)) = copier.join() {
+            log.lock().unwrap().write_all(err.to_string().as_bytes())?;
+        }
+    }
+
+    if spin {
+        // clears the spinner's line
+        eprint!("\r\x1b[2K");
+    }
+
+    if status.success() && !nested::is_nested() && !interactive::is_quiet() {
+        println!(
+            "{} {name} {}",
+            "✓".if_supports_color(Stdout, |t| t.green()),
+            format!("{:.1}s", started.elapsed().as_secs_f32())
+                .if_supports_color(Stdout, |t| t.dimmed())
+        );
+    }
+
+    Ok((status, log_path))
+}
+
+/// Runs a hook with its output going to its log, returns its exit status and where it was logged to
+pub fn run(command: &mut Command, group: &str, script: &Path) -> io::Result<(ExitStatus, PathBuf)> {
+    let logs_dir = get_logs_dir().ok_or_else(|| io::Error::other("no data directory found"))?;
+    run_in(&logs_dir, command, group, script)
+}
+
+/// Returns the log of the last time the group's hooks ran
+pub fn latest_log(group: &str) -> Option<PathBuf> {
+    let logs = fs::read_dir(get_logs_dir()?.join(group)).ok()?;
+
+    // logs are named after when they started
+    logs.flatten()
+        .map(|log| log.path())
+        .filter_map(|log| Some((log.file_stem()?.to_str()?.parse::<u64>().ok()?, log)))
+        .max_by_key(|(started, _)| *started)
+        .map(|(_, log)| log)
+}
+
+/// Returns the last `lines` lines that the script output to the log
+pub fn tail(log: &Path, script: &Path, lines: usize) -> Vec<String> {
+    let Ok(contents) = fs::read_to_string(log) else {
+        return Vec::new();
+    };
+
+    // the log might be shared with the group's other hooks
+    let header = format!("==> {} <==", script.display());
+    let output: Vec<_> = contents
+        .lines()
+        .rev()
+        .take_while(|line| *line != header)
+        .take(lines)
+        .collect();
+    output.into_iter().rev().map(String::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn hook_output_is_logged() {
+        let logs_dir = std::env::temp_dir()
+            .join("tuckr")
+            .join("hooklog")
+            .join(std::process::id().to_string());
+        _ = fs::remove_dir_all(&logs_dir);
+
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo out; echo err >&2; exit 3"]);
+        let (status, log) =
+            run_in(&logs_dir, &mut command, "zsh", Path::new("pre_install.sh")).unwrap();
+
+        assert_eq!(status.code(), Some(3));
+        assert!(log.starts_with(logs_dir.join("zsh")));
+        let mut lines = tail(&log, Path::new("pre_install.sh"), 5);
+        lines.sort();
+        assert_eq!(lines, ["err", "out"]);
+        assert_eq!(tail(&log, Path::new("pre_install.sh"), 1).len(), 1);
+
+        fs::remove_dir_all(&logs_dir).unwrap();
+    }
+}
@@ -2,18 +2,43 @@
 //!
 //! Hooks are run in a state machine.
 //! Hooking steps:
-//! 1. Setup scripts are run
+//! 1. Setup scripts are run (prefixed with `pre`)
 //! 2. Dotfiles are symlinked
-//! 3. Post setup scripts are run
+//! 3. Post setup scripts are run (prefixed with `post`)
+//!
+//! When groups are removed their remove scripts (prefixed with `rm`) are run before
+//! their symlinks are removed.
 
-use crate::dotfiles::{self, ReturnCode};
+use crate::audit::{HookRun, RunReport};
+use crate::cancel;
+use crate::config;
+use crate::dotfiles::{self, DotfileType};
+use crate::error;
+use crate::errors::{self, TuckrError};
+use crate::fileops;
+use crate::hooklog;
+use crate::interactive;
+use crate::nested;
+use crate::plan::{self, Step};
+use crate::preflight;
+use crate::secrets::EncryptedHooks;
+use crate::state;
 use crate::symlinks;
+use crate::table;
 use owo_colors::OwoColorize;
+use owo_colors::Stream::{Stderr, Stdout};
 use rust_i18n::t;
+use std::collections::BTreeSet;
+use std::ffi::OsString;
 use std::fs;
-use std::path::PathBuf;
-use std::process::{Command, ExitCode};
-use tabled::{Table, Tabled};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+use tabled::Tabled;
+
+/// how long to wait before running a hook that failed again
+const RETRY_DELAY: Duration = Duration::from_secs(1);
 
 /// Prints a single row info box with title on the left
 /// and content on the right
@@ -29,6 +54,19 @@ fn print_info_box(title: &str, content: &str) {
     println!("{hook_box}");
 }
 
+/// Prints what's being done to a group, tuckrs run by hooks leave it to the outer tuckr
+fn print_header(title: &str, group: &str) {
+    if !nested::is_nested() && !interactive::is_quiet() {
+        print_info_box(
+            title,
+            group
+                .if_supports_color(Stdout, |t| t.yellow())
+                .to_string()
+                .as_str(),
+        );
+    }
+}
+
 #[derive(Debug, PartialEq)]
 enum DeployStep {
     Initialize, // Default value before starting deployment
@@ -68,89 +106,363 @@ impl Iterator for DeployStages {
     }
 }
 
-/// Runs hooks of type PreHook or PostHook
-fn run_set_hook(
+/// The kinds of hooks a group can have
+///
+/// A hook's kind is determined by the prefix of its file name,
+/// e.g. `pre_install.sh` is a pre hook, `rm_cleanup.sh` is a remove hook and `teardown.sh` a teardown hook.
+/// The prefix can come after the number that orders the hook, e.g. `10-pre-install.sh`
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum HookType {
+    /// Runs before the group is symlinked
+    Pre,
+    /// Runs after the group is symlinked
+    Post,
+    /// Runs before the group's symlinks are removed
+    Remove,
+    /// Runs after the group's symlinks are removed, to undo what the other hooks set up
+    Teardown,
+}
+
+impl HookType {
+    fn prefix(self) -> &'static str {
+        match self {
+            HookType::Pre => "pre",
+            HookType::Post => "post",
+            HookType::Remove => "rm",
+            HookType::Teardown => "teardown",
+        }
+    }
+
+    const ALL: [HookType; 4] = [
+        HookType::Pre,
+        HookType::Post,
+        HookType::Remove,
+        HookType::Teardown,
+    ];
+
+    fn running_message(self) -> String {
+        match self {
+            HookType::Pre => t!("info.running_prehook"),
+            HookType::Post => t!("info.running_posthook"),
+            HookType::Remove => t!("info.running_rmhook"),
+            HookType::Teardown => t!("info.running_teardown_hook"),
+        }
+        .into_owned()
+    }
+}
+
+/// Returns the number a file name starts with along with what comes after it and its separator
+fn leading_number(name: &str) -> (Option<u64>, &str) {
+    let digits = name.len() - name.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    match name[..digits].parse() {
+        Ok(number) => (Some(number), name[digits..].trim_start_matches(['-', '_'])),
+        Err(_) => (None, name),
+    }
+}
+
+/// Returns a hook's kind and the number that orders it among the group's hooks of that kind
+///
+/// The number either comes first or right after the prefix: `10-pre-install.sh` and
+/// `pre_10-install.sh` are both pre hooks numbered 10
+fn parse_hook_name(name: &str) -> Option<(HookType, Option<u64>)> {
+    let (number, rest) = leading_number(name);
+    let hook_type = HookType::ALL
+        .into_iter()
+        .find(|hook| rest.starts_with(hook.prefix()))?;
+
+    let number = number.or_else(|| {
+        let rest = rest[hook_type.prefix().len()..].trim_start_matches(['-', '_']);
+        leading_number(rest).0
+    });
+    Some((hook_type, number))
+}
+
+/// Returns true if tuckr runs the file as a hook, i.e. its name starts with a hook's prefix
+pub fn is_hook(file: &Path) -> bool {
+    hook_kind(file).is_some()
+}
+
+/// Returns true if the file is numbered like a hook but doesn't say which kind it is, e.g. `10-install.sh`
+///
+/// tuckr never runs these, since it can't tell when they should be run
+pub fn is_numbered_without_kind(file: &Path) -> bool {
+    let Some(name) = file.file_name() else {
+        return false;
+    };
+    let name = name.to_string_lossy();
+    leading_number(&name).0.is_some() && parse_hook_name(&name).is_none()
+}
+
+/// Returns the prefix of the kind of hook the file is, e.g. "pre" for `10-pre-install.sh`
+pub fn hook_kind(file: &Path) -> Option<&'static str> {
+    let name = file.file_name()?.to_string_lossy();
+    parse_hook_name(&name).map(|(hook_type, _)| hook_type.prefix())
+}
+
+/// Returns the group's hooks of `hook_type` in the order they're run
+///
+/// Numbered hooks run first, from the lowest number to the highest, followed by the rest by name
+///
+/// encrypted: the group's encrypted hooks, they're run in the same order along with the others
+fn sorted_hooks(
+    group_dir: Option<&Path>,
+    encrypted: &[PathBuf],
+    hook_type: HookType,
+) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = encrypted.to_vec();
+    if let Some(group_dir) = group_dir {
+        for file in fs::read_dir(group_dir)? {
+            files.push(file?.path());
+        }
+    }
+
+    let mut hooks = Vec::new();
+    for file in files {
+        let Some(name) = file.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+
+        // make sure it will only run for their specific hooks
+        if let Some((kind, number)) = parse_hook_name(name)
+            && kind == hook_type
+        {
+            hooks.push((number.unwrap_or(u64::MAX), name.to_string(), file));
+        }
+    }
+
+    hooks.sort();
+    Ok(hooks.into_iter().map(|(_, _, file)| file).collect())
+}
+
+/// Returns the command that runs a hook
+///
+/// The group's or the global hook_shell runs it if there is one, otherwise it's executed directly.
+/// Windows can't execute scripts directly, so there PowerShell runs `.ps1` hooks and other
+/// hooks are run with the interpreter in their shebang.
+fn hook_command(group: &str, hook: &Path) -> Command {
+    let interpreter = match config::get().hook_shell(group) {
+        Some(shell) => Some(vec![shell.to_string()]),
+        None if cfg!(windows) && hook.extension().is_some_and(|ext| ext == "ps1") => Some(
+            [
+                "powershell",
+                "-NoProfile",
+                "-ExecutionPolicy",
+                "Bypass",
+                "-File",
+            ]
+            .map(String::from)
+            .to_vec(),
+        ),
+        None if cfg!(windows) => shebang(hook),
+        None => None,
+    };
+
+    match interpreter {
+        Some(interpreter) => {
+            let mut command = Command::new(&interpreter[0]);
+            command.args(&interpreter[1..]).arg(hook);
+            command
+        }
+        None => Command::new(hook),
+    }
+}
+
+/// Returns the interpreter and its arguments from a script's shebang
+///
+/// Only the interpreter's name is kept since unix paths like /bin/sh don't exist on Windows,
+/// `#!/usr/bin/env python3` and `#!/bin/python3` both become `python3`
+fn shebang(script: &Path) -> Option<Vec<String>> {
+    let script = fs::read(script).ok()?;
+    let line = script.strip_prefix(b"#!")?.split(|c| *c == b'\n').next()?;
+    let line = String::from_utf8_lossy(line);
+    let mut words = line.split_whitespace();
+
+    let mut interpreter = Path::new(words.next()?).file_name()?.to_str()?.to_string();
+    if interpreter == "env" {
+        interpreter = words.find(|word| !word.starts_with('-'))?.to_string();
+    }
+
+    Some(
+        std::iter::once(interpreter)
+            .chain(words.map(String::from))
+            .collect(),
+    )
+}
+
+/// Returns the environment variables a group's hooks are run with
+///
+/// The ones from tuckr.toml come first so that they can't change where tuckr's own point to.
+/// $TUCKR_HOME is only set when a directory can be found that a tuckr run by the hook would find
+/// the same dotfiles in, $TUCKR_DOTFILES always points to them.
+fn hook_env(profile: Option<String>, dry_run: bool, group: &str) -> Vec<(String, OsString)> {
+    let mut env: Vec<(String, OsString)> = config::get()
+        .hook_env(group)
+        .into_iter()
+        .map(|(name, value)| (name.into(), value.into()))
+        .collect();
+
+    env.push(("TUCKR_GROUP".into(), group.into()));
+    env.push((
+        "TUCKR_PROFILE".into(),
+        profile.clone().unwrap_or_default().into(),
+    ));
+    env.push((
+        "TUCKR_DRY_RUN".into(),
+        if dry_run { "1" } else { "0" }.into(),
+    ));
+
+    if let Ok(target) = dotfiles::get_dotfiles_target_dir_path() {
+        env.push(("TUCKR_TARGET".into(), target.into()));
+    }
+
+    if let Ok(dotfiles_dir) = dotfiles::get_dotfiles_path(profile.clone()) {
+        // ~/.dotfiles can't be pointed to with $TUCKR_HOME, which holds a `dotfiles` directory
+        let dir_name = match profile {
+            Some(profile) => format!("dotfiles_{profile}"),
+            None => "dotfiles".into(),
+        };
+        if dotfiles_dir
+            .file_name()
+            .is_some_and(|name| name == dir_name.as_str())
+            && let Some(home) = dotfiles_dir.parent()
+        {
+            env.push(("TUCKR_HOME".into(), home.into()));
+        }
+        env.push(("TUCKR_DOTFILES".into(), dotfiles_dir.into()));
+    }
+
+    env
+}
+
+/// Runs all of the group's hooks of type `hook_type`, returns how many of them were run
+///
+/// Hooks that fail are run again as many times as hook_retries in tuckr.toml says
+///
+/// runs: every hook that was run gets recorded into it
+/// encrypted: the group's encrypted hooks, decrypted unless it's a dry run
+fn run_hook(
     profile: Option<String>,
     dry_run: bool,
     group: &str,
-    hook_type: DeployStep,
-) -> Result<(), ExitCode> {
-    let dotfiles_dir = match dotfiles::get_dotfiles_path(profile) {
-        Ok(dir) => dir,
-        Err(e) => {
-            eprintln!("{e}");
-            return Err(ReturnCode::CouldntFindDotfiles.into());
-        }
-    };
+    hook_type: HookType,
+    runs: &mut Vec<HookRun>,
+    encrypted: &EncryptedHooks,
+) -> Result<usize, TuckrError> {
+    if let Err(e) = dotfiles::get_dotfiles_path(profile.clone()) {
+        errors::print(e);
+        return Err(TuckrError::CouldntFindDotfiles);
+    }
 
-    let group_dir = dotfiles_dir.join("Hooks").join(group);
     // a hook might just be a `tuckr add` meaning, so a corresponding hooks group dir might just not exist at all
-    if !group_dir.exists() {
-        return Ok(());
+    let group_dir = dotfiles::find_group_dir(profile.clone(), DotfileType::Hooks, group);
+    if group_dir.is_none() && encrypted.is_empty() {
+        return Ok(0);
     }
 
-    let Ok(group_dir) = fs::read_dir(group_dir) else {
-        eprintln!("{}", t!("errors.could_not_read_hooks").red());
-        return Err(ReturnCode::NoSetupFolder.into());
+    let Ok(hooks) = sorted_hooks(group_dir.as_deref(), &encrypted.files(), hook_type) else {
+        errors::print(error!("could_not_read_hooks"));
+        return Err(TuckrError::NoSetupFolder);
     };
 
-    for file in group_dir {
-        let file = file.unwrap().path();
+    let mut ran = 0;
+    for file in hooks {
         let filename = file.file_name().unwrap().to_str().unwrap();
 
-        // make sure it will only run for their specific hooks
-        match hook_type {
-            DeployStep::PreHook => {
-                if !filename.starts_with("pre") {
-                    continue;
-                }
-                print_info_box(
-                    &t!("info.running_prehook"),
-                    group.yellow().to_string().as_str(),
-                );
-            }
-
-            DeployStep::PostHook => {
-                if !filename.starts_with("post") {
-                    continue;
-                }
-                print_info_box(
-                    &t!("info.running_posthook"),
-                    group.yellow().to_string().as_str(),
-                );
-            }
-            _ => (),
-        }
+        print_header(&hook_type.running_message(), group);
 
         if dry_run {
+            plan::record(Step::RunHook {
+                group: group.into(),
+                script: file.clone(),
+            });
             continue;
         }
 
-        let mut output = match Command::new(&file).spawn() {
-            Ok(output) => output,
-            Err(e) => {
-                eprintln!("{e}");
-                return Err(ExitCode::FAILURE);
+        let retries = config::get().hook_retries(group, filename);
+        let mut attempt = 0;
+        let (run, status, log) = loop {
+            let mut command = hook_command(group, &file);
+            command.envs(hook_env(profile.clone(), dry_run, group));
+            nested::prepare_hook(&mut command);
+
+            // the script is hashed before running it in case it modifies itself
+            let mut run = HookRun {
+                group: group.into(),
+                kind: hook_type.prefix().into(),
+                sha256: fileops::hash_file(&file).ok(),
+                // decrypted hooks are shredded afterwards, so the encrypted one is recorded instead
+                script: encrypted.source(&file).unwrap_or(&file).to_path_buf(),
+                exit_code: None,
+                duration_ms: 0,
+                log: None,
+            };
+            let start = Instant::now();
+
+            let (status, log) = match hooklog::run(&mut command, group, &file) {
+                Ok(finished) => finished,
+                Err(e) => {
+                    errors::print(format!("{}: {e}", file.display()));
+                    runs.push(run);
+                    return Err(TuckrError::Failed);
+                }
+            };
+
+            run.exit_code = status.code();
+            run.duration_ms = start.elapsed().as_millis();
+            run.log = Some(log.clone());
+
+            if status.success() || attempt == retries {
+                break (run, status, log);
             }
+
+            // only the last attempt is recorded, so a hook that succeeded on a retry didn't fail
+            attempt += 1;
+            eprintln!(
+                "{}",
+                t!(
+                    "warn.retrying_hook",
+                    hook = filename,
+                    group = group,
+                    attempt = attempt,
+                    retries = retries
+                )
+                .if_supports_color(Stderr, |t| t.yellow())
+            );
+            cancel::check()?;
+            thread::sleep(RETRY_DELAY);
         };
+        runs.push(run);
+        ran += 1;
 
-        if !output.wait().unwrap().success() {
+        if !status.success() {
             print_info_box(
-                t!("errors.failed_to_hook").red().to_string().as_str(),
+                t!("errors.failed_to_hook")
+                    .if_supports_color(Stdout, |t| t.red())
+                    .to_string()
+                    .as_str(),
                 format!("{group} {filename}").as_str(),
             );
-            return Err(ExitCode::FAILURE);
+            eprintln!("{}", t!("info.hook_output_logged", file = log.display()));
+            return Err(TuckrError::HookFailed);
         }
     }
 
-    Ok(())
+    Ok(ran)
+}
+
+/// Returns true if the group has hooks, in Hooks or encrypted in Secrets
+pub fn has_hooks(profile: Option<String>, group: &str) -> bool {
+    dotfiles::dotfile_contains(profile.clone(), DotfileType::Hooks, group)
+        || !EncryptedHooks::find(profile, group).is_empty()
 }
 
 macro_rules! get_hooks_dir_if_exists_or_run_cmd {
     ($profile:ident, $groups:ident, $cmd:expr) => {{
-        if let Some(invalid_groups) =
-            dotfiles::check_invalid_groups($profile.clone(), dotfiles::DotfileType::Hooks, $groups)
-        {
+        let invalid_groups: Vec<_> = $groups
+            .iter()
+            .filter(|group| *group != "*" && !has_hooks($profile.clone(), group))
+            .collect();
+        if !invalid_groups.is_empty() {
             if dotfiles::check_invalid_groups(
                 $profile.clone(),
                 dotfiles::DotfileType::Configs,
@@ -159,94 +471,199 @@ macro_rules! get_hooks_dir_if_exists_or_run_cmd {
             .is_some()
             {
                 for group in invalid_groups {
-                    println!("{}", t!("errors.x_doesnt_exist", x = group).red());
+                    println!(
+                        "{}",
+                        t!("errors.x_doesnt_exist", x = group)
+                            .if_supports_color(Stdout, |t| t.red())
+                    );
                 }
 
-                return Err(ReturnCode::NoSuchFileOrDir.into());
+                return Err(TuckrError::NoSuchFileOrDir);
             } else {
                 return $cmd;
             }
         }
 
+        // the dotfiles without a profile share their hooks with the profiles
         match dotfiles::get_dotfiles_path($profile.clone()) {
-            Ok(dir) => dir.join("Hooks"),
+            Ok(_) => dotfiles::get_group_dirs($profile.clone(), DotfileType::Hooks),
             Err(err) => {
-                eprintln!("{}", err.red());
-                return Err(ReturnCode::NoSetupFolder.into());
+                errors::print(err);
+                return Err(TuckrError::NoSetupFolder);
             }
         }
     }};
 }
 
+/// How setting a group went, listed at the end of `tuckr set`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    Succeeded,
+    /// some of the group's hooks ran or it was symlinked before it failed
+    Partial,
+    Failed,
+    /// an earlier group failed and `--keep-going` wasn't used
+    NotRun,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Succeeded => write!(
+                f,
+                "{}",
+                "succeeded".if_supports_color(Stdout, |t| t.green())
+            ),
+            Outcome::Partial => write!(
+                f,
+                "{}",
+                "partially succeeded".if_supports_color(Stdout, |t| t.yellow())
+            ),
+            Outcome::Failed => write!(f, "{}", "failed".if_supports_color(Stdout, |t| t.red())),
+            Outcome::NotRun => write!(f, "{}", "not run".if_supports_color(Stdout, |t| t.dimmed())),
+        }
+    }
+}
+
 /// Runs hooks for specified groups and symlinks them
+///
+/// audit_log: a report of the hooks that were run is appended to it, falls back to the config's audit_log
+/// keep_going: the rest of the groups are still set after one of them fails
 #[allow(clippy::too_many_arguments)]
 pub fn set_cmd(
     profile: Option<String>,
     dry_run: bool,
     only_files: bool,
+    copy: bool,
     groups: &[String],
     exclude: &[String],
     force: bool,
     adopt: bool,
     assume_yes: bool,
-) -> Result<(), ExitCode> {
-    let hooks_dir = get_hooks_dir_if_exists_or_run_cmd!(profile, groups, {
-        println!("{}", "No hooks exist. Running `tuckr add`".yellow());
+    audit_log: Option<PathBuf>,
+    keep_going: bool,
+) -> Result<(), TuckrError> {
+    let hooks_dirs = get_hooks_dir_if_exists_or_run_cmd!(profile, groups, {
+        if !nested::is_nested() && !interactive::is_quiet() {
+            println!(
+                "{}",
+                t!("info.no_hooks_running_add").if_supports_color(Stdout, |t| t.yellow())
+            );
+        }
         symlinks::add_cmd(
-            profile, dry_run, only_files, groups, exclude, force, adopt, assume_yes,
+            profile, dry_run, only_files, copy, groups, exclude, force, adopt, assume_yes,
         )
     });
 
-    let run_deploy_steps = |stages: DeployStages, group: String| -> Result<(), ExitCode> {
-        if !dotfiles::group_is_valid_target(&group) || exclude.contains(&group) {
-            return Ok(());
-        }
+    // asked once up front instead of once for every group
+    if !symlinks::confirm_conflicts(force, adopt, assume_yes) {
+        return Ok(());
+    }
+
+    let mut report = RunReport::new(profile.clone(), groups);
 
-        for step in stages {
-            match step {
-                DeployStep::Initialize => return Ok(()),
+    // held groups are left as they are, their hooks included
+    let saved = state::load_or_report(profile.clone())?;
+    let is_skipped = |group: &str| {
+        !dotfiles::group_is_valid_target(group)
+            || dotfiles::is_excluded(group, exclude)
+            || saved.is_held(group)
+    };
 
-                DeployStep::PreHook => {
-                    run_set_hook(profile.clone(), dry_run, &group, step)?;
+    // progressed: set once a step changed something, so that a failure after it is a partial one
+    let mut run_deploy_steps =
+        |stages: DeployStages, group: String, progressed: &mut bool| -> Result<(), TuckrError> {
+            if is_skipped(&group) {
+                if saved.is_held(&group) {
+                    eprintln!(
+                        "{}",
+                        t!("info.skipping_held_group", group = group)
+                            .if_supports_color(Stderr, |t| t.yellow())
+                    );
                 }
+                return Ok(());
+            }
+
+            // decrypted once for both kinds of hooks and shredded once the group is set
+            let mut encrypted = EncryptedHooks::find(profile.clone(), &group);
+            if !dry_run {
+                encrypted.decrypt(profile.clone())?;
+            }
+
+            for step in stages {
+                // the hook or symlinking that was running when Ctrl-C was pressed is the last one
+                cancel::check()?;
 
-                DeployStep::Symlink => {
-                    if dotfiles::check_invalid_groups(
-                        profile.clone(),
-                        dotfiles::DotfileType::Configs,
-                        &[&group],
-                    )
-                    .is_some()
-                    {
-                        continue;
+                match step {
+                    DeployStep::Initialize => return Ok(()),
+
+                    DeployStep::PreHook => {
+                        let ran = run_hook(
+                            profile.clone(),
+                            dry_run,
+                            &group,
+                            HookType::Pre,
+                            &mut report.hooks,
+                            &encrypted,
+                        )?;
+                        *progressed |= ran > 0;
                     }
 
-                    print_info_box(
-                        &t!("info.symlinking_group"),
-                        group.yellow().to_string().as_str(),
-                    );
-                    symlinks::add_cmd(
-                        profile.clone(),
-                        dry_run,
-                        only_files,
-                        &[group.clone()],
-                        exclude,
-                        force,
-                        adopt,
-                        assume_yes,
-                    )?;
-                }
+                    DeployStep::Symlink => {
+                        if dotfiles::check_invalid_groups(
+                            profile.clone(),
+                            dotfiles::DotfileType::Configs,
+                            &[&group],
+                        )
+                        .is_some()
+                        {
+                            continue;
+                        }
+
+                        print_header(&t!("info.symlinking_group"), &group);
+                        // the post hooks rely on the group's files being in place
+                        symlinks::add_cmd(
+                            profile.clone(),
+                            dry_run,
+                            only_files,
+                            copy,
+                            std::slice::from_ref(&group),
+                            exclude,
+                            force,
+                            adopt,
+                            true,
+                        )
+                        .inspect_err(|_| {
+                            eprintln!(
+                                "{}",
+                                t!("warn.skipping_post_hooks", group = group)
+                                    .if_supports_color(Stderr, |t| t.yellow())
+                            );
+                        })?;
+                        *progressed = true;
+                    }
 
-                DeployStep::PostHook => run_set_hook(profile.clone(), dry_run, &group, step)?,
+                    DeployStep::PostHook => {
+                        let ran = run_hook(
+                            profile.clone(),
+                            dry_run,
+                            &group,
+                            HookType::Post,
+                            &mut report.hooks,
+                            &encrypted,
+                        )?;
+                        *progressed |= ran > 0;
+                    }
+                }
             }
-        }
 
-        Ok(())
-    };
+            Ok(())
+        };
 
-    let mut groups = if groups.contains(&'*'.to_string()) {
+    let all_groups = groups.contains(&'*'.to_string());
+    let mut groups = if all_groups {
         let mut groups = Vec::new();
-        let mut add_group_dotfiles = |dir: PathBuf| -> Result<(), ExitCode> {
+        let mut add_group_dotfiles = |dir: PathBuf| -> Result<(), TuckrError> {
             for folder in fs::read_dir(dir).unwrap() {
                 let folder = folder.unwrap();
                 groups.push(folder.file_name().into_string().unwrap());
@@ -255,7 +672,9 @@ pub fn set_cmd(
             Ok(())
         };
 
-        add_group_dotfiles(hooks_dir)?;
+        for hooks_dir in hooks_dirs {
+            add_group_dotfiles(hooks_dir)?;
+        }
 
         let configs_dir = dotfiles::get_dotfiles_path(profile.clone())
             .unwrap()
@@ -269,7 +688,7 @@ pub fn set_cmd(
         // groups with their related conditional groups added
         let mut expanded_groups = groups.to_vec();
 
-        for file in hooks_dir.read_dir().unwrap() {
+        for file in hooks_dirs.iter().flat_map(|dir| dir.read_dir().unwrap()) {
             let filename = file.unwrap().file_name().into_string().unwrap();
             let base_group = dotfiles::group_without_target(&filename);
 
@@ -286,114 +705,513 @@ pub fn set_cmd(
     // sorting is necessary to ensure that the conditional groups are run right after their base group
     groups.sort();
     groups.dedup();
+    // groups for software this machine doesn't have are only set when they're named
+    if all_groups {
+        groups.retain(|group| {
+            !dotfiles::group_is_valid_target(group)
+                || dotfiles::is_excluded(group, exclude)
+                || preflight::requirements_met(group)
+        });
+    }
     // trick to restore immutability
     let groups = groups;
 
     #[derive(Tabled)]
-    struct RunStatus<'a> {
+    struct RunStatus {
         #[tabled(rename = "Hook")]
         group: String,
-        #[tabled(rename = "Success")]
-        succeeded: &'a str,
+        #[tabled(rename = "Result")]
+        outcome: String,
     }
 
-    let true_symbol = "✓".green().to_string();
-    let false_symbol = "✗".red().to_string();
-    let get_symbol = |success: bool| -> &str { if success { &true_symbol } else { &false_symbol } };
-
     let mut hooks_summary: Vec<RunStatus> = Vec::new();
-    for group in &groups {
+    let mut nested_hooks = Vec::new();
+    let mut all_succeeded = true;
+    let mut any_succeeded = false;
+    let mut failure = None;
+    let deferred = cancel::defer();
+    for (idx, group) in groups.iter().enumerate() {
+        if cancel::is_cancelled() {
+            break;
+        }
+
+        // without --keep-going the groups after the first one that failed aren't set
+        if failure.is_some() && !keep_going {
+            for group in groups[idx..].iter().filter(|group| !is_skipped(group)) {
+                hooks_summary.push(RunStatus {
+                    outcome: Outcome::NotRun.to_string(),
+                    group: group.clone(),
+                });
+            }
+            eprintln!(
+                "{}",
+                t!("warn.stopped_after_failure", flag = "--keep-going")
+                    .if_supports_color(Stderr, |t| t.yellow())
+            );
+            break;
+        }
+
+        let mut progressed = false;
+        let result = run_deploy_steps(DeployStages::new(), group.clone(), &mut progressed);
+        let succeeded = result.is_ok();
+        all_succeeded &= succeeded;
+        any_succeeded |= succeeded;
+        let outcome = match result {
+            Ok(()) => Outcome::Succeeded,
+            Err(err) => {
+                failure.get_or_insert(err);
+                match progressed {
+                    true => Outcome::Partial,
+                    false => Outcome::Failed,
+                }
+            }
+        };
+
         hooks_summary.push(RunStatus {
-            succeeded: get_symbol(run_deploy_steps(DeployStages::new(), group.clone()).is_ok()),
+            outcome: outcome.to_string(),
             group: group.clone(),
-        })
-    }
+        });
 
-    if groups.len() > 1 {
-        use tabled::{Alignment, Margin, Modify, Style, object::Segment};
+        // what the group's hooks ran with tuckr is listed right below it
+        for operation in nested::take_operations() {
+            hooks_summary.push(RunStatus {
+                outcome: match operation.succeeded {
+                    true => Outcome::Succeeded,
+                    false => Outcome::Failed,
+                }
+                .to_string(),
+                group: format!(
+                    "  ↳ tuckr {} {}",
+                    operation.command,
+                    operation.groups.join(" ")
+                ),
+            });
+            nested_hooks.extend(operation.hooks);
+        }
+    }
+    drop(deferred);
+    report.hooks.extend(nested_hooks);
+    let failed_hooks: Vec<_> = report
+        .hooks
+        .iter()
+        .filter(|run| run.exit_code != Some(0))
+        .cloned()
+        .collect();
 
-        let mut hooks_list = Table::new(hooks_summary);
-        hooks_list
-            .with(Style::rounded())
-            .with(Margin::new(2, 4, 1, 1))
-            .with(Modify::new(Segment::new(1.., 1..)).with(Alignment::center()));
+    // a nested `tuckr set` ends up in the outer tuckr's audit log
+    if nested::is_nested() {
+        nested::add_hooks(report.hooks);
+    } else if let Some(audit_log) = audit_log.or_else(|| config::get().audit_log.clone())
+        && !dry_run
+        && let Err(err) = report.finish(all_succeeded, &audit_log)
+    {
+        errors::print(err);
+    }
 
+    if hooks_summary.len() > 1 && !nested::is_nested() && !interactive::is_quiet() {
         println!(
-            "{}",
-            "\n\n Hooks have finished running. Here's a summary:".green()
+            "\n\n {}",
+            t!("info.hooks_summary").if_supports_color(Stdout, |t| t.green())
         );
-        println!("{hooks_list}");
+        println!("{}", table::render(&hooks_summary, 2));
     }
 
-    Ok(())
+    if !nested::is_nested() {
+        print_failed_hooks(&failed_hooks);
+    }
+
+    cancel::check()?;
+
+    // the hooks that failed are what gets retried, so they take precedence over how the rest went
+    if !failed_hooks.is_empty() {
+        return Err(TuckrError::HookFailed);
+    }
+
+    match failure {
+        Some(_) if any_succeeded => Err(TuckrError::PartialDeploy),
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Lists the hooks that failed with their exit codes and the end of their output
+fn print_failed_hooks(failed: &[HookRun]) {
+    if failed.is_empty() {
+        return;
+    }
+
+    if table::format() == table::Format::Json {
+        for run in failed {
+            let exit_code = match run.exit_code {
+                Some(code) => code.to_string(),
+                None => "-".into(),
+            };
+            errors::print(error!(
+                "hook_nonzero",
+                hook = run.script.file_name().unwrap_or_default().to_string_lossy(),
+                group = run.group,
+                code = exit_code
+            ));
+        }
+        return;
+    }
+
+    eprintln!(
+        "\n {}",
+        t!("errors.failed_hooks").if_supports_color(Stderr, |t| t.red())
+    );
+    for run in failed {
+        let script = run.script.file_name().unwrap_or_default().to_string_lossy();
+        let exit_code = match run.exit_code {
+            Some(code) => code.to_string(),
+            None => "-".into(),
+        };
+        eprintln!(
+            "  {} {}/{script} ({})",
+            "✗".if_supports_color(Stderr, |t| t.red()),
+            run.group,
+            t!("errors.exit_code_x", code = exit_code)
+        );
+
+        let Some(log) = &run.log else {
+            continue;
+        };
+        for line in hooklog::tail(log, &run.script, 5) {
+            eprintln!("    {}", line.if_supports_color(Stderr, |t| t.dimmed()));
+        }
+        eprintln!(
+            "    {}",
+            t!("info.hook_output_logged", file = log.display())
+        );
+    }
 }
 
-/// Runs cleanup hooks for groups and then removes all their symlinks
+/// Runs remove hooks for groups, removes all their symlinks and then runs their teardown hooks
+///
+/// run_hooks: the groups are only removed when false, leaving what their hooks set up behind
 pub fn unset_cmd(
     profile: Option<String>,
     dry_run: bool,
     groups: &[String],
     exclude: &[String],
-) -> Result<(), ExitCode> {
-    let hooks_dir = get_hooks_dir_if_exists_or_run_cmd!(profile, groups, {
-        println!("{}", "No hooks exist. Running `tuckr rm`".yellow());
-        symlinks::remove_cmd(profile, dry_run, groups, exclude)
-    });
+    run_hooks: bool,
+) -> Result<(), TuckrError> {
+    if let Err(err) = dotfiles::get_dotfiles_path(profile.clone()) {
+        errors::print(err);
+        return Err(TuckrError::CouldntFindDotfiles);
+    }
+
+    // Ctrl-C stops once the current hook or group is done
+    let _deferred = cancel::defer();
 
     let wildcard = String::from("*");
     if groups.contains(&wildcard) {
-        return symlinks::remove_cmd(profile, dry_run, &[wildcard], exclude);
-    }
+        // groups that are both in the profile and shared only run the profile's hooks
+        let hook_groups: BTreeSet<_> = match run_hooks {
+            true => dotfiles::get_group_dirs(profile.clone(), DotfileType::Hooks)
+                .into_iter()
+                .flat_map(|dir| dir.read_dir().into_iter().flatten())
+                .map(|group| group.unwrap().file_name().into_string().unwrap())
+                .filter(|group| {
+                    !dotfiles::is_excluded(group, exclude) && dotfiles::group_is_valid_target(group)
+                })
+                .collect(),
+            false => BTreeSet::new(),
+        };
 
-    for group in groups {
-        let group_dir = hooks_dir.join(group);
+        // the encrypted hooks are kept decrypted until the teardown hooks ran
+        let mut decrypted = Vec::new();
+        for group in hook_groups {
+            cancel::check()?;
+            let mut encrypted = EncryptedHooks::find(profile.clone(), &group);
+            if !dry_run {
+                encrypted.decrypt(profile.clone())?;
+            }
 
-        for file in group_dir.read_dir().unwrap() {
-            let file = file.unwrap().path();
-            let filename = file.file_name().unwrap().to_str().unwrap();
+            run_hook(
+                profile.clone(),
+                dry_run,
+                &group,
+                HookType::Remove,
+                &mut Vec::new(),
+                &encrypted,
+            )?;
+            decrypted.push((group, encrypted));
+        }
 
-            if filename.starts_with("rm") {
-                print_info_box("Running cleanup hook", group.yellow().to_string().as_str());
+        cancel::check()?;
+        symlinks::remove_cmd(profile.clone(), dry_run, &[wildcard], exclude)?;
 
-                if dry_run {
-                    continue;
-                }
+        for (group, encrypted) in &decrypted {
+            cancel::check()?;
+            run_hook(
+                profile.clone(),
+                dry_run,
+                group,
+                HookType::Teardown,
+                &mut Vec::new(),
+                encrypted,
+            )?;
+        }
 
-                let hook = Command::new(&file).spawn();
+        return Ok(());
+    }
 
-                let mut output = match hook {
-                    Ok(out) => out,
-                    Err(err) => {
-                        eprintln!("{err}");
-                        return Err(ReturnCode::NoSuchFileOrDir.into());
-                    }
-                };
+    // groups that are gone from the dotfiles can still be removed if the state knows what they deployed
+    let state = state::load_or_report(profile.clone())?;
+    let orphaned = state.orphaned_groups();
+    let has_configs = |group: &str| {
+        dotfiles::dotfile_contains(profile.clone(), DotfileType::Configs, group)
+            || orphaned.contains_key(group)
+    };
 
-                if !output.wait().unwrap().success() {
-                    print_info_box(
-                        t!("errors.failed_to_hook").red().to_string().as_str(),
-                        format!("{group} {filename}").as_str(),
-                    );
-                    return Err(ExitCode::FAILURE);
-                }
+    // groups might only have hooks, only have configs or both
+    let invalid_groups: Vec<_> = groups
+        .iter()
+        .filter(|group| {
+            !dotfiles::dotfile_contains(profile.clone(), DotfileType::Hooks, group)
+                && !has_configs(group)
+        })
+        .collect();
+
+    if !invalid_groups.is_empty() {
+        for group in invalid_groups {
+            errors::print(error!("x_doesnt_exist", x = group));
+        }
+        return Err(TuckrError::NoSuchFileOrDir);
+    }
+
+    for group in groups {
+        cancel::check()?;
+
+        if dotfiles::is_excluded(group, exclude) {
+            continue;
+        }
+
+        let mut encrypted = EncryptedHooks::default();
+        if run_hooks {
+            encrypted = EncryptedHooks::find(profile.clone(), group);
+            if !dry_run {
+                encrypted.decrypt(profile.clone())?;
             }
+
+            run_hook(
+                profile.clone(),
+                dry_run,
+                group,
+                HookType::Remove,
+                &mut Vec::new(),
+                &encrypted,
+            )?;
+            cancel::check()?;
         }
 
-        print_info_box(
-            "Removing symlinked group",
-            group.yellow().to_string().as_str(),
-        );
+        if has_configs(group) {
+            print_header(&t!("info.removing_group"), group);
+            symlinks::remove_cmd(profile.clone(), dry_run, &[group.to_owned()], exclude)?;
+        }
 
-        symlinks::remove_cmd(profile.clone(), dry_run, &[group.to_owned()], exclude)?;
+        // what the hooks set up is only torn down once the group's files are gone
+        if run_hooks {
+            cancel::check()?;
+            run_hook(
+                profile.clone(),
+                dry_run,
+                group,
+                HookType::Teardown,
+                &mut Vec::new(),
+                &encrypted,
+            )?;
+        }
     }
 
     Ok(())
 }
 
+/// Unsets every group that's at least partially deployed after listing what will be unlinked
+pub fn unset_all_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    exclude: &[String],
+    assume_yes: bool,
+    run_hooks: bool,
+) -> Result<(), TuckrError> {
+    let deployed: Vec<_> = symlinks::get_deployed_files(profile.clone())?
+        .into_iter()
+        .filter(|(group, _)| !dotfiles::is_excluded(group, exclude))
+        .collect();
+
+    if deployed.is_empty() {
+        println!(
+            "{}",
+            t!("info.nothing_deployed").if_supports_color(Stdout, |t| t.yellow())
+        );
+        return Ok(());
+    }
+
+    println!("{}:", t!("info.groups_will_be_unlinked"));
+    for (group, files) in &deployed {
+        println!(
+            "\t{} ({})",
+            group.if_supports_color(Stdout, |t| t.yellow()),
+            t!("info.x_files", count = files.len())
+        );
+    }
+
+    if !assume_yes && !interactive::confirm(format!("\n{}", t!("warn.want_to_proceed"))) {
+        return Ok(());
+    }
+
+    let groups: Vec<_> = deployed.into_iter().map(|(group, _)| group).collect();
+    unset_cmd(profile, dry_run, &groups, exclude, run_hooks)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn hooks_are_matched_by_prefix() {
+        let hooks_dir = dotfiles::get_dotfiles_path(None)
+            .unwrap()
+            .join("Hooks")
+            .join("group");
+        fs::create_dir_all(&hooks_dir).unwrap();
+
+        // a hook that always fails, so it only passes if the hook is never run
+        let hook = hooks_dir.join("rm_cleanup.sh");
+        fs::write(&hook, "#!/bin/sh\nexit 1\n").unwrap();
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let mut runs = Vec::new();
+        let no_encrypted_hooks = EncryptedHooks::default();
+        let mut run = |hook_type| {
+            run_hook(
+                None,
+                false,
+                "group",
+                hook_type,
+                &mut runs,
+                &no_encrypted_hooks,
+            )
+        };
+        assert!(run(HookType::Pre).is_ok());
+        assert!(run(HookType::Post).is_ok());
+        #[cfg(target_family = "unix")]
+        {
+            assert!(run(HookType::Remove).is_err());
+            assert_eq!(runs.len(), 1);
+            assert_eq!(runs[0].exit_code, Some(1));
+        }
+
+        fs::remove_dir_all(dotfiles::get_dotfiles_path(None).unwrap()).unwrap();
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn held_groups_hooks_dont_run() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let hooks_dir = dotfiles_dir.join("Hooks").join("frozen");
+        fs::create_dir_all(&hooks_dir).unwrap();
+
+        let ran = dotfiles_dir.join("pre_ran");
+        let hook = hooks_dir.join("pre.sh");
+        fs::write(&hook, format!("#!/bin/sh\ntouch '{}'\n", ran.display())).unwrap();
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut saved = state::State::load(None).unwrap();
+        saved.held.insert("frozen".into());
+        saved.save().unwrap();
+
+        let groups = ["frozen".to_string()];
+        set_cmd(
+            None,
+            false,
+            false,
+            false,
+            &groups,
+            &[],
+            false,
+            false,
+            true,
+            None,
+            false,
+        )
+        .unwrap();
+        assert!(!ran.exists());
+
+        fs::remove_dir_all(dotfiles_dir).unwrap();
+    }
+
+    #[test]
+    fn hooks_are_run_in_order() {
+        assert_eq!(
+            parse_hook_name("10-pre-install.sh"),
+            Some((HookType::Pre, Some(10)))
+        );
+        assert_eq!(
+            parse_hook_name("post_20_configure.sh"),
+            Some((HookType::Post, Some(20)))
+        );
+        assert_eq!(parse_hook_name("rm.sh"), Some((HookType::Remove, None)));
+        assert_eq!(
+            parse_hook_name("20-teardown-systemd.sh"),
+            Some((HookType::Teardown, Some(20)))
+        );
+        assert_eq!(parse_hook_name("10-install.sh"), None);
+
+        let hooks_dir = dotfiles::get_dotfiles_path(None)
+            .unwrap()
+            .join("Hooks")
+            .join("ordered");
+        fs::create_dir_all(&hooks_dir).unwrap();
+        for hook in ["pre_setup.sh", "pre_10.sh", "2-pre.sh", "post_1.sh"] {
+            fs::write(hooks_dir.join(hook), "").unwrap();
+        }
+
+        // encrypted hooks are run in between the others
+        let encrypted_dir = hooks_dir.join("encrypted");
+        let encrypted = [
+            encrypted_dir.join("pre_5-token.sh"),
+            encrypted_dir.join("post_2.sh"),
+        ];
+        let hooks: Vec<_> = sorted_hooks(Some(&hooks_dir), &encrypted, HookType::Pre)
+            .unwrap()
+            .into_iter()
+            .map(|hook| hook.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            hooks,
+            ["2-pre.sh", "pre_5-token.sh", "pre_10.sh", "pre_setup.sh"]
+        );
+
+        fs::remove_dir_all(dotfiles::get_dotfiles_path(None).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn interpreter_from_shebang() {
+        let dir = dotfiles::get_dotfiles_path(None).unwrap();
+        fs::create_dir_all(&dir).unwrap();
+
+        let hook = dir.join("pre.py");
+        fs::write(&hook, "#!/usr/bin/env -S python3 -u\r\nprint()\n").unwrap();
+        assert_eq!(shebang(&hook).unwrap(), ["python3", "-u"]);
+
+        fs::write(&hook, "#!/bin/sh -e\nexit 0\n").unwrap();
+        assert_eq!(shebang(&hook).unwrap(), ["sh", "-e"]);
+
+        fs::write(&hook, "exit 0\n").unwrap();
+        assert_eq!(shebang(&hook), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
     #[test]
     fn run_deploy_steps() {
         let mut steps = DeployStages::new();
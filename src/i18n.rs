@@ -0,0 +1,172 @@
+//! Loads translations that aren't built into tuckr
+//!
+//! The locales in tuckr's `locales` directory are compiled in, so to add a translation or to change
+//! one without recompiling tuckr, a `<lang>.toml` file with the same sections as theirs can be put in
+//! `$TUCKR_HOME/locales` or `$XDG_DATA_HOME/tuckr/locales` (e.g. `~/.local/share/tuckr/locales`).
+//! The files in $TUCKR_HOME take priority over the others and both of them over the built-in
+//! locales, the keys they leave out are taken from the built-in locale of the same language or
+//! from English.
+//!
+//! The language is picked by `--lang`, then by $TUCKR_LANG and then by the system's locale.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// translations by their locale and then by their key, e.g. `info.no_packages_to_install`
+type Translations = HashMap<String, HashMap<String, String>>;
+
+static EXTERNAL: OnceLock<Translations> = OnceLock::new();
+
+/// Makes the translations that were loaded by [`load`] available to `t!`
+pub struct ExternalLocales;
+
+impl rust_i18n::Backend for ExternalLocales {
+    fn available_locales(&self) -> Vec<&str> {
+        EXTERNAL
+            .get()
+            .map(|locales| locales.keys().map(String::as_str).collect())
+            .unwrap_or_default()
+    }
+
+    fn translate(&self, locale: &str, key: &str) -> Option<&str> {
+        EXTERNAL.get()?.get(locale)?.get(key).map(String::as_str)
+    }
+}
+
+/// Returns the directories translations are loaded from, the first one takes priority
+fn get_locales_dirs() -> Vec<PathBuf> {
+    let tuckr_home = std::env::var("TUCKR_HOME")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| PathBuf::from(dir).join("locales"));
+    let data_dir = dirs::data_dir().map(|dir| dir.join("tuckr").join("locales"));
+
+    tuckr_home.into_iter().chain(data_dir).collect()
+}
+
+/// Adds the strings of a locale's sections under their dotted keys, e.g. `[info] key` as `info.key`
+fn flatten(prefix: &str, table: &toml::Table, translations: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        let key = match prefix {
+            "" => key.clone(),
+            prefix => format!("{prefix}.{key}"),
+        };
+
+        match value {
+            toml::Value::String(text) => {
+                translations.insert(key, text.clone());
+            }
+            toml::Value::Table(table) => flatten(&key, table, translations),
+            _ => (),
+        }
+    }
+}
+
+/// Reads a locale file, its name is the locale it translates to, e.g. `fr-FR.toml`
+fn read_locale_file(file: &Path) -> Result<HashMap<String, String>, String> {
+    let contents = fs::read_to_string(file).map_err(|e| format!("{}: {e}", file.display()))?;
+    let table: toml::Table =
+        toml::from_str(&contents).map_err(|e| format!("{}: {e}", file.display()))?;
+
+    let mut translations = HashMap::new();
+    flatten("", &table, &mut translations);
+    Ok(translations)
+}
+
+/// Reads the locale files of every directory, the ones that come first override the ones after them
+fn read_locales(dirs: &[PathBuf]) -> (Translations, Vec<String>) {
+    let mut locales = Translations::new();
+    let mut errors = Vec::new();
+
+    for dir in dirs.iter().rev() {
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+
+        let mut files: Vec<_> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|file| file.extension().is_some_and(|ext| ext == "toml"))
+            .collect();
+        files.sort();
+
+        for file in files {
+            let Some(locale) = file.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            match read_locale_file(&file) {
+                Ok(translations) => locales
+                    .entry(locale.to_string())
+                    .or_default()
+                    .extend(translations),
+                Err(err) => errors.push(err),
+            }
+        }
+    }
+
+    (locales, errors)
+}
+
+/// Loads the translations from the locales directories, returns the files that couldn't be read
+///
+/// It has to be called before anything is translated, since the built-in locales are used until then
+pub fn load() -> Vec<String> {
+    let (locales, errors) = read_locales(&get_locales_dirs());
+    _ = EXTERNAL.set(locales);
+    errors
+}
+
+/// Picks the language tuckr is used in, `lang` is the one given with `--lang`
+pub fn set_language(lang: Option<String>) {
+    let lang = lang
+        .or_else(|| std::env::var("TUCKR_LANG").ok())
+        .filter(|lang| !lang.is_empty())
+        .or_else(sys_locale::get_locale)
+        .unwrap_or_default();
+
+    rust_i18n::set_locale(&lang);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn external_locales_override_each_other() {
+        let dotfiles_dir = crate::dotfiles::get_dotfiles_path(None).unwrap();
+        let tuckr_home = dotfiles_dir.with_file_name("locales");
+        let data_dir = dotfiles_dir.with_file_name("data");
+        fs::create_dir_all(&tuckr_home).unwrap();
+        fs::create_dir_all(&data_dir).unwrap();
+
+        fs::write(
+            data_dir.join("fr-FR.toml"),
+            "[info]\nx_isnt_managed = \"`%{x}` n'est pas géré\"\nno_modified_copies = \"Aucune copie modifiée.\"",
+        )
+        .unwrap();
+        fs::write(
+            tuckr_home.join("fr-FR.toml"),
+            "[info]\nx_isnt_managed = \"`%{x}` n'est pas géré par tuckr.\"",
+        )
+        .unwrap();
+        fs::write(tuckr_home.join("de-DE.toml"), "[info\nbroken").unwrap();
+        fs::write(tuckr_home.join("README.md"), "not a locale").unwrap();
+
+        let (locales, errors) = read_locales(&[tuckr_home, data_dir]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(locales.len(), 1);
+        assert_eq!(
+            locales["fr-FR"]["info.x_isnt_managed"],
+            "`%{x}` n'est pas géré par tuckr."
+        );
+        assert_eq!(
+            locales["fr-FR"]["info.no_modified_copies"],
+            "Aucune copie modifiée."
+        );
+
+        fs::remove_dir_all(dotfiles_dir.parent().unwrap()).unwrap();
+    }
+}
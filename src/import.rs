@@ -0,0 +1,1224 @@
+//! Imports dotfiles from chezmoi, yadm and GNU Stow
+//!
+//! Both keep every dotfile in a single tree, so the files are split into groups the way they'd be
+//! set up by hand: `.config/<app>` goes into the `<app>` group and everything else into a group
+//! named after its top level file or directory, e.g. `.zshrc` into `zshrc`. With `--group`
+//! everything goes into a single group instead.
+//!
+//! `tuckr from-chezmoi` reads chezmoi's source directory:
+//! - `dot_`, `private_`, `readonly_`, `executable_` and `empty_` become the files' names and modes
+//! - encrypted files are copied into Secrets as they are, tuckr's age and gpg backends decrypt them
+//! - templates that only use variables become tuckr templates and chezmoi's `[data]` becomes vars.toml
+//! - scripts become hooks of the `chezmoi` group
+//!
+//! `tuckr from-yadm` reads yadm's repository. Alternate files for an OS or a host become conditional
+//! groups, e.g. `.zshrc##os.Darwin` goes into `zshrc_macos`, and the bootstrap program becomes a hook.
+//!
+//! `tuckr from-stow` reads a stow directory, every package is already laid out like a group so it
+//! becomes one as it is, leaving out what stow ignores by default.
+//!
+//! Whatever couldn't be translated, or behaves differently with tuckr, is listed at the end.
+
+use crate::config::{self, SecretsBackend};
+use crate::dotfiles::{self, DotfileType};
+use crate::error;
+use crate::errors::{self, TuckrError};
+use crate::fileops;
+use crate::history;
+use crate::progress::Progress;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::{Stderr, Stdout};
+use rust_i18n::t;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A file that gets written into the dotfiles
+#[derive(Debug, PartialEq)]
+struct Entry {
+    dtype: DotfileType,
+    group: String,
+    /// relative to the group
+    path: PathBuf,
+    kind: Kind,
+}
+
+#[derive(Debug, PartialEq)]
+enum Kind {
+    File { contents: Vec<u8>, mode: u32 },
+    Dir { mode: u32 },
+    Symlink(PathBuf),
+}
+
+/// What couldn't be imported as it was
+#[derive(Debug, Default)]
+struct Report {
+    /// files that weren't imported along with why
+    skipped: Vec<(String, String)>,
+    /// files that were imported but behave differently with tuckr
+    notes: Vec<(String, String)>,
+}
+
+impl Report {
+    fn skip(&mut self, path: impl AsRef<Path>, reason: impl Into<String>) {
+        self.skipped
+            .push((path.as_ref().display().to_string(), reason.into()));
+    }
+
+    fn note(&mut self, path: impl AsRef<Path>, note: impl Into<String>) {
+        self.notes
+            .push((path.as_ref().display().to_string(), note.into()));
+    }
+}
+
+/// Turns a file name into a group name, e.g. `.zshrc` -> `zshrc`
+///
+/// `_` and `%` are replaced since they'd turn the group into a conditional one
+fn group_name(name: &str) -> String {
+    let name: String = name
+        .trim_start_matches('.')
+        .chars()
+        .map(|c| match c {
+            '_' | '%' => '-',
+            c => c,
+        })
+        .collect();
+
+    match dotfiles::is_valid_groupname(&name) {
+        Ok(()) if !name.is_empty() => name,
+        _ => "dotfiles".into(),
+    }
+}
+
+/// Returns the group that a file deployed to `target`, relative to the target dir, is imported into
+fn group_for(target: &Path, group: Option<&str>) -> String {
+    if let Some(group) = group {
+        return group.into();
+    }
+
+    let components: Vec<_> = target
+        .components()
+        .filter_map(|c| c.as_os_str().to_str())
+        .collect();
+    match components[..] {
+        [".config", app, _, ..] => group_name(app),
+        [first, ..] => group_name(first),
+        [] => "dotfiles".into(),
+    }
+}
+
+/// Returns where the data of chezmoi and yadm is kept
+fn data_home() -> Option<PathBuf> {
+    match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => Some(dir.into()),
+        _ => Some(dirs::home_dir()?.join(".local").join("share")),
+    }
+}
+
+#[cfg(target_family = "unix")]
+fn set_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(target_family = "unix"))]
+fn set_mode(_path: &Path, _mode: u32) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(target_family = "unix")]
+fn mode_of(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+#[cfg(not(target_family = "unix"))]
+fn mode_of(metadata: &fs::Metadata) -> u32 {
+    if metadata.is_dir() { 0o755 } else { 0o644 }
+}
+
+/// What chezmoi's prefixes and suffixes say about a file or directory
+#[derive(Debug, Default)]
+struct Attributes {
+    encrypted: Option<SecretsBackend>,
+    private: bool,
+    readonly: bool,
+    empty: bool,
+    executable: bool,
+    template: bool,
+    create: bool,
+    modify: bool,
+    remove: bool,
+    exact: bool,
+    external: bool,
+    symlink: bool,
+    script: bool,
+    /// scripts that chezmoi only runs once or when they change
+    once: bool,
+    /// scripts that run before the files are deployed
+    before: bool,
+}
+
+impl Attributes {
+    /// The mode of a file or directory with these attributes
+    fn mode(&self, is_dir: bool) -> u32 {
+        let mut mode = match is_dir || self.executable || self.script {
+            true => 0o755,
+            false => 0o644,
+        };
+        if self.private {
+            mode &= !0o077;
+        }
+        if self.readonly {
+            mode &= !0o222;
+        }
+        mode
+    }
+}
+
+/// Splits a name in chezmoi's source directory into the target's name and its attributes
+fn parse_chezmoi_name(name: &str, is_dir: bool) -> (String, Attributes) {
+    let mut attrs = Attributes::default();
+    let mut rest = name;
+    let mut dot = false;
+
+    loop {
+        if let Some(literal) = rest.strip_prefix("literal_") {
+            rest = literal;
+            break;
+        }
+        let Some((prefix, after)) = rest.split_once('_') else {
+            break;
+        };
+        match prefix {
+            "encrypted" if !is_dir => attrs.encrypted = Some(SecretsBackend::Age),
+            "private" => attrs.private = true,
+            "readonly" => attrs.readonly = true,
+            "empty" if !is_dir => attrs.empty = true,
+            "executable" if !is_dir => attrs.executable = true,
+            "create" if !is_dir => attrs.create = true,
+            "modify" if !is_dir => attrs.modify = true,
+            "remove" => attrs.remove = true,
+            "exact" if is_dir => attrs.exact = true,
+            "external" if is_dir => attrs.external = true,
+            "symlink" if !is_dir => attrs.symlink = true,
+            "run" if !is_dir => attrs.script = true,
+            "once" | "onchange" if attrs.script => attrs.once = true,
+            "before" if attrs.script => attrs.before = true,
+            "after" if attrs.script => (),
+            "dot" => dot = true,
+            _ => break,
+        }
+        rest = after;
+
+        // the name follows right after dot_
+        if dot {
+            break;
+        }
+    }
+
+    let mut name = rest.to_string();
+    if !is_dir {
+        if attrs.encrypted.is_some() {
+            if let Some(stripped) = name.strip_suffix(".age") {
+                name = stripped.into();
+            } else if let Some(stripped) = name.strip_suffix(".asc") {
+                name = stripped.into();
+                attrs.encrypted = Some(SecretsBackend::Gpg);
+            }
+        }
+
+        if let Some(stripped) = name.strip_suffix(".literal") {
+            name = stripped.into();
+        } else if let Some(stripped) = name.strip_suffix(".tmpl") {
+            name = stripped.into();
+            attrs.template = true;
+        }
+    }
+
+    match dot {
+        true => (format!(".{name}"), attrs),
+        false => (name, attrs),
+    }
+}
+
+/// Turns a chezmoi template into a tuckr one, None if it uses anything besides variables
+fn convert_template(template: &str) -> Option<String> {
+    let mut converted = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        converted.push_str(&rest[..start]);
+
+        let expr = &rest[start + 2..];
+        let end = expr.find("}}")?;
+        let var = expr[..end].trim().strip_prefix('.')?;
+        let is_variable = var
+            .split('.')
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_alphanumeric() || c == '_'));
+        if !is_variable {
+            return None;
+        }
+
+        let var = match var {
+            "chezmoi.hostname" => "tuckr.hostname",
+            // e.g. chezmoi's os is "darwin" where tuckr's is "macos"
+            var if var.starts_with("chezmoi.") => return None,
+            var => var,
+        };
+        converted.push_str(&format!("{{{{ {var} }}}}"));
+        rest = &expr[end + 2..];
+    }
+
+    converted.push_str(rest);
+    Some(converted)
+}
+
+/// Turns a chezmoi script into a hook
+fn chezmoi_script(
+    file: &Path,
+    source: &Path,
+    name: &str,
+    attrs: &Attributes,
+    group: Option<&str>,
+    entries: &mut Vec<Entry>,
+    report: &mut Report,
+) {
+    let path = file.strip_prefix(source).unwrap_or(file);
+    if attrs.template {
+        report.skip(path, t!("warn.import_script_template"));
+        return;
+    }
+    let Ok(contents) = fs::read(file) else {
+        return;
+    };
+
+    let group = group.unwrap_or("chezmoi");
+    let hook = match attrs.before {
+        true => format!("pre_{name}"),
+        false => format!("post_{name}"),
+    };
+    if attrs.once {
+        report.note(
+            path,
+            t!(
+                "warn.import_script_runs_always",
+                group = group,
+                hook = &hook
+            ),
+        );
+    }
+
+    entries.push(Entry {
+        dtype: DotfileType::Hooks,
+        group: group.into(),
+        path: hook.into(),
+        kind: Kind::File {
+            contents,
+            mode: 0o755,
+        },
+    });
+}
+
+/// Translates a file or directory in chezmoi's source directory, `target` is where its parent is deployed to
+fn chezmoi_entries(
+    source: &Path,
+    dir: &Path,
+    target: &Path,
+    group: Option<&str>,
+    entries: &mut Vec<Entry>,
+    report: &mut Report,
+) {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut files: Vec<_> = read_dir.flatten().map(|entry| entry.path()).collect();
+    files.sort();
+
+    let is_root = dir == source;
+    let is_scripts_dir = dir.starts_with(source.join(".chezmoiscripts"));
+
+    for file in files {
+        let Some(file_name) = file.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let path = file.strip_prefix(source).unwrap_or(&file);
+        let is_dir = file.is_dir();
+
+        if is_root && file_name == ".chezmoiscripts" {
+            chezmoi_entries(source, &file, target, group, entries, report);
+            continue;
+        }
+        // chezmoi ignores the other files starting with a dot, e.g. .git
+        if file_name.starts_with(".chezmoi") {
+            if !matches!(file_name, ".chezmoiroot" | ".chezmoiversion")
+                && !file_name.starts_with(".chezmoidata")
+            {
+                report.skip(path, t!("warn.import_special", tool = "chezmoi"));
+            }
+            continue;
+        }
+        if file_name.starts_with('.') {
+            continue;
+        }
+
+        let (name, attrs) = parse_chezmoi_name(file_name, is_dir);
+        let target_path = target.join(&name);
+
+        if is_scripts_dir && is_dir {
+            chezmoi_entries(source, &file, target, group, entries, report);
+            continue;
+        }
+        if attrs.script {
+            chezmoi_script(&file, source, &name, &attrs, group, entries, report);
+            continue;
+        }
+        if is_scripts_dir {
+            continue;
+        }
+
+        let group_name = group_for(&target_path, group);
+        if is_dir {
+            if attrs.remove {
+                report.skip(path, t!("warn.import_remove"));
+                continue;
+            }
+            if attrs.external {
+                report.skip(path, t!("warn.import_external"));
+                continue;
+            }
+            if attrs.exact {
+                report.note(path, t!("warn.import_exact"));
+            }
+            if attrs.private || attrs.readonly {
+                entries.push(Entry {
+                    dtype: DotfileType::Configs,
+                    group: group_name,
+                    path: target_path.clone(),
+                    kind: Kind::Dir {
+                        mode: attrs.mode(true),
+                    },
+                });
+            }
+
+            chezmoi_entries(source, &file, &target_path, group, entries, report);
+            continue;
+        }
+
+        if attrs.modify {
+            report.skip(path, t!("warn.import_modify"));
+            continue;
+        }
+        let Ok(mut contents) = fs::read(&file) else {
+            continue;
+        };
+        // empty files remove their target unless they're marked as empty
+        if attrs.remove || (contents.is_empty() && !attrs.empty && !attrs.symlink) {
+            report.skip(path, t!("warn.import_remove"));
+            continue;
+        }
+        if attrs.create {
+            report.note(path, t!("warn.import_create"));
+        }
+
+        if attrs.symlink {
+            if attrs.template {
+                report.skip(path, t!("warn.import_template"));
+                continue;
+            }
+            let link = String::from_utf8_lossy(&contents).trim().to_string();
+            entries.push(Entry {
+                dtype: DotfileType::Configs,
+                group: group_name,
+                path: target_path,
+                kind: Kind::Symlink(link.into()),
+            });
+            continue;
+        }
+
+        if let Some(backend) = attrs.encrypted {
+            if attrs.template {
+                report.skip(path, t!("warn.import_template"));
+                continue;
+            }
+
+            if config::get().secrets_backend(&group_name) != backend {
+                let backend = match backend {
+                    SecretsBackend::Gpg => "gpg",
+                    _ => "age",
+                };
+                report.note(path, t!("warn.import_backend", backend = backend));
+            }
+            entries.push(Entry {
+                dtype: DotfileType::Secrets,
+                group: group_name,
+                path: target_path,
+                kind: Kind::File {
+                    contents,
+                    mode: 0o600,
+                },
+            });
+            continue;
+        }
+
+        let mut path_in_group = target_path;
+        if attrs.template {
+            let converted = String::from_utf8(contents)
+                .ok()
+                .and_then(|template| convert_template(&template));
+            let Some(converted) = converted else {
+                report.skip(path, t!("warn.import_template"));
+                continue;
+            };
+            contents = converted.into_bytes();
+            path_in_group.as_mut_os_string().push(".tmpl");
+        }
+
+        entries.push(Entry {
+            dtype: DotfileType::Configs,
+            group: group_name,
+            path: path_in_group,
+            kind: Kind::File {
+                contents,
+                mode: attrs.mode(false),
+            },
+        });
+    }
+}
+
+/// Returns the variables of chezmoi's templates, from chezmoi.toml's `[data]` and .chezmoidata.toml
+fn chezmoi_vars(source: &Path) -> toml::Table {
+    let config = dirs::home_dir()
+        .map(|home| home.join(".config").join("chezmoi").join("chezmoi.toml"))
+        .and_then(|config| fs::read_to_string(config).ok())
+        .and_then(|config| config.parse::<toml::Table>().ok())
+        .and_then(|mut config| match config.remove("data") {
+            Some(toml::Value::Table(data)) => Some(data),
+            _ => None,
+        });
+    let data_file = fs::read_to_string(source.join(".chezmoidata.toml"))
+        .ok()
+        .and_then(|data| data.parse::<toml::Table>().ok());
+
+    let mut vars = data_file.unwrap_or_default();
+    vars.extend(config.unwrap_or_default());
+    vars
+}
+
+/// Where an alternate of yadm is deployed, e.g. `_linux%laptop`
+///
+/// Returns Err with the condition that can't be translated
+fn yadm_alternate(conditions: &str) -> Result<Option<String>, String> {
+    let mut os = String::new();
+    let mut host = String::new();
+
+    for condition in conditions.split(',') {
+        let (key, value) = condition.split_once('.').unwrap_or((condition, ""));
+        match key {
+            "default" | "extension" | "e" => (),
+            "os" | "o" => {
+                os = match value.to_lowercase().as_str() {
+                    "linux" => "_linux".into(),
+                    "darwin" => "_macos".into(),
+                    os @ ("freebsd" | "openbsd" | "netbsd") => format!("_{os}"),
+                    _ => return Err(condition.into()),
+                }
+            }
+            "hostname" | "h" => host = format!("%{value}"),
+            _ => return Err(condition.into()),
+        }
+    }
+
+    let suffix = os + &host;
+    Ok((!suffix.is_empty()).then_some(suffix))
+}
+
+/// Translates the files in yadm's repository
+fn yadm_entries(
+    repo: &Path,
+    group: Option<&str>,
+    entries: &mut Vec<Entry>,
+    report: &mut Report,
+) -> Result<(), String> {
+    let tree = history::git(repo, &["ls-tree", "-r", "-z", "--full-tree", "HEAD"], None)?;
+    // the alternates that were imported, so that two of them don't end up in the same group
+    let mut imported = BTreeSet::new();
+
+    for line in tree.split('\0').filter(|line| !line.is_empty()) {
+        let Some((info, path)) = line.split_once('\t') else {
+            continue;
+        };
+        let mut info = info.split(' ');
+        let (Some(mode), Some(_), Some(object)) = (info.next(), info.next(), info.next()) else {
+            continue;
+        };
+
+        if mode == "160000" {
+            report.skip(path, t!("warn.import_submodule"));
+            continue;
+        }
+
+        let group_override = if path == ".config/yadm/bootstrap" {
+            report.note(
+                path,
+                t!(
+                    "warn.import_script_runs_always",
+                    group = group.unwrap_or("yadm"),
+                    hook = "post_bootstrap"
+                ),
+            );
+            Some(DotfileType::Hooks)
+        } else if path == ".config/yadm/encrypt" || path.starts_with(".local/share/yadm/") {
+            report.skip(path, t!("warn.import_yadm_encrypt"));
+            continue;
+        } else if path.starts_with(".config/yadm/hooks/") {
+            report.skip(path, t!("warn.import_yadm_hooks"));
+            continue;
+        } else if path.starts_with(".config/yadm/") {
+            report.skip(path, t!("warn.import_special", tool = "yadm"));
+            continue;
+        } else {
+            None
+        };
+
+        // alternates can be files or directories, e.g. .config/nvim##os.Darwin/init.lua
+        let mut target = PathBuf::new();
+        let mut suffix = None;
+        let mut untranslatable = None;
+        for component in path.split('/') {
+            let Some((name, conditions)) = component.split_once("##") else {
+                target.push(component);
+                continue;
+            };
+            target.push(name);
+
+            let is_template = conditions
+                .split(',')
+                .any(|c| matches!(c.split('.').next(), Some("template" | "t")));
+            match yadm_alternate(conditions) {
+                _ if is_template => untranslatable = Some(t!("warn.import_yadm_template")),
+                Ok(alternate) => suffix = alternate.or(suffix),
+                Err(condition) => {
+                    untranslatable = Some(t!("warn.import_condition", condition = condition))
+                }
+            }
+        }
+        if let Some(reason) = untranslatable {
+            report.skip(path, reason);
+            continue;
+        }
+
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(["cat-file", "blob", object])
+            .output()
+            .map_err(|e| format!("git: {e}"))?;
+        if !output.status.success() {
+            return Err(String::from_utf8_lossy(&output.stderr).trim().into());
+        }
+        let contents = output.stdout;
+
+        if let Some(dtype) = group_override {
+            entries.push(Entry {
+                dtype,
+                group: group.unwrap_or("yadm").into(),
+                path: "post_bootstrap".into(),
+                kind: Kind::File {
+                    contents,
+                    mode: 0o755,
+                },
+            });
+            continue;
+        }
+
+        let group_name = group_for(&target, group) + suffix.as_deref().unwrap_or_default();
+        if !imported.insert((group_name.clone(), target.clone())) {
+            report.skip(path, t!("warn.import_alternate_taken", group = &group_name));
+            continue;
+        }
+
+        let kind = match mode {
+            "120000" => Kind::Symlink(String::from_utf8_lossy(&contents).into_owned().into()),
+            "100755" => Kind::File {
+                contents,
+                mode: 0o755,
+            },
+            _ => Kind::File {
+                contents,
+                mode: 0o644,
+            },
+        };
+        entries.push(Entry {
+            dtype: DotfileType::Configs,
+            group: group_name,
+            path: target,
+            kind,
+        });
+    }
+
+    Ok(())
+}
+
+/// Returns true if stow leaves the file out of its package by default
+///
+/// top_level: the file is at the top of its package, where READMEs and licenses are ignored as well
+fn stow_ignores(name: &str, top_level: bool) -> bool {
+    matches!(
+        name,
+        "RCS"
+            | "CVS"
+            | ".cvsignore"
+            | ".svn"
+            | "_darcs"
+            | ".hg"
+            | ".git"
+            | ".gitignore"
+            | ".gitmodules"
+    ) || name.ends_with(",v")
+        || name.ends_with('~')
+        || name.starts_with(".#")
+        || (name.len() > 1 && name.starts_with('#') && name.ends_with('#'))
+        || (top_level
+            && (name.starts_with("README") || name.starts_with("LICENSE") || name == "COPYING"))
+}
+
+/// Translates what's in a stow package's directory, `target` is where the directory is deployed to
+///
+/// dot_prefix: `dot-` at the start of a name stands for a dot, like stow's `--dotfiles`
+fn stow_entries(
+    stow_dir: &Path,
+    dir: &Path,
+    target: &Path,
+    group: &str,
+    dot_prefix: bool,
+    entries: &mut Vec<Entry>,
+    report: &mut Report,
+) {
+    let Ok(read_dir) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut files: Vec<_> = read_dir.flatten().map(|entry| entry.path()).collect();
+    files.sort();
+
+    for file in files {
+        let Some(file_name) = file.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let path = file.strip_prefix(stow_dir).unwrap_or(&file);
+        let Ok(metadata) = file.symlink_metadata() else {
+            continue;
+        };
+
+        if file_name == ".stow-local-ignore" {
+            report.skip(path, t!("warn.import_stow_ignore_list"));
+            continue;
+        }
+        if stow_ignores(file_name, target.as_os_str().is_empty()) {
+            report.skip(path, t!("warn.import_stow_ignored"));
+            continue;
+        }
+
+        let name = match file_name.strip_prefix("dot-") {
+            Some(name) if dot_prefix => format!(".{name}"),
+            _ => file_name.to_string(),
+        };
+        let target_path = target.join(name);
+
+        let kind = if metadata.is_symlink() {
+            let Ok(link) = fs::read_link(&file) else {
+                continue;
+            };
+            Kind::Symlink(link)
+        } else if metadata.is_dir() {
+            entries.push(Entry {
+                dtype: DotfileType::Configs,
+                group: group.into(),
+                path: target_path.clone(),
+                kind: Kind::Dir {
+                    mode: mode_of(&metadata),
+                },
+            });
+            stow_entries(
+                stow_dir,
+                &file,
+                &target_path,
+                group,
+                dot_prefix,
+                entries,
+                report,
+            );
+            continue;
+        } else {
+            let Ok(contents) = fs::read(&file) else {
+                continue;
+            };
+            Kind::File {
+                contents,
+                mode: mode_of(&metadata),
+            }
+        };
+
+        entries.push(Entry {
+            dtype: DotfileType::Configs,
+            group: group.into(),
+            path: target_path,
+            kind,
+        });
+    }
+}
+
+/// Writes the entries into the dotfiles, files that are already there are left alone
+fn write_entries(
+    dotfiles_dir: &Path,
+    dry_run: bool,
+    entries: Vec<Entry>,
+    report: &mut Report,
+) -> BTreeMap<String, usize> {
+    // the number of files imported into each group
+    let mut groups = BTreeMap::new();
+
+    let progress = Progress::new(entries.len(), "importing");
+    for entry in entries {
+        let relative = Path::new(entry.dtype.dir_name())
+            .join(&entry.group)
+            .join(&entry.path);
+        let dest = dotfiles_dir.join(&relative);
+        progress.start(relative.display());
+        progress.inc();
+
+        if let Kind::Dir { mode } = entry.kind {
+            if !dry_run
+                && let Err(err) = fs::create_dir_all(&dest).and_then(|_| set_mode(&dest, mode))
+            {
+                errors::print(format!("{}: {err}", dest.display()));
+            }
+            continue;
+        }
+
+        if dest.symlink_metadata().is_ok() {
+            report.skip(&relative, t!("warn.import_exists"));
+            continue;
+        }
+
+        if dry_run {
+            eprintln!(
+                "{} `{}`",
+                "creating".if_supports_color(Stderr, |t| t.green()),
+                dest.display()
+            );
+        } else {
+            let written =
+                fs::create_dir_all(dest.parent().unwrap()).and_then(|_| match &entry.kind {
+                    Kind::File { contents, mode } => {
+                        fs::write(&dest, contents).and_then(|_| set_mode(&dest, *mode))
+                    }
+                    Kind::Symlink(link) => fileops::symlink(link, &dest),
+                    Kind::Dir { .. } => Ok(()),
+                });
+            if let Err(err) = written {
+                errors::print(format!("{}: {err}", dest.display()));
+                continue;
+            }
+        }
+
+        *groups.entry(entry.group).or_default() += 1;
+    }
+
+    groups
+}
+
+/// Writes what was imported into the dotfiles and prints what couldn't be
+///
+/// Returns the groups that files were imported into
+fn import(
+    dotfiles_dir: &Path,
+    dry_run: bool,
+    entries: Vec<Entry>,
+    mut report: Report,
+) -> Result<Vec<String>, TuckrError> {
+    let groups = write_entries(dotfiles_dir, dry_run, entries, &mut report);
+
+    let count: usize = groups.values().sum();
+    let groups: Vec<_> = groups.into_keys().collect();
+    println!(
+        "{}",
+        t!(
+            "info.imported_files",
+            count = count,
+            groups = groups.join(", ")
+        )
+        .if_supports_color(Stdout, |t| t.green())
+    );
+
+    for (title, list) in [
+        (t!("warn.import_notes"), &report.notes),
+        (t!("warn.import_not_translated"), &report.skipped),
+    ] {
+        if list.is_empty() {
+            continue;
+        }
+
+        eprintln!("\n{}", title.if_supports_color(Stderr, |t| t.yellow()));
+        for (path, reason) in list {
+            eprintln!("  {path}: {reason}");
+        }
+    }
+
+    Ok(groups)
+}
+
+/// Checks the group passed with --group and finds the dotfiles to import into
+fn prepare(profile: Option<String>, group: Option<&str>) -> Result<PathBuf, TuckrError> {
+    if let Some(group) = group
+        && let Err(err) = dotfiles::is_valid_groupname(group)
+    {
+        errors::print(err);
+        return Err(TuckrError::Failed);
+    }
+
+    dotfiles::get_dotfiles_path(profile).map_err(|err| {
+        errors::print(err);
+        TuckrError::CouldntFindDotfiles
+    })
+}
+
+/// Writes the variables of the imported templates to vars.toml unless there already is one
+fn write_vars(dotfiles_dir: &Path, dry_run: bool, vars: &toml::Table) {
+    let vars_file = dotfiles_dir.join("vars.toml");
+    if vars_file.exists() {
+        eprintln!(
+            "{}",
+            t!("warn.import_vars_exist", file = vars_file.display())
+                .if_supports_color(Stderr, |t| t.yellow())
+        );
+        return;
+    }
+
+    if dry_run {
+        eprintln!(
+            "{} `{}`",
+            "creating".if_supports_color(Stderr, |t| t.green()),
+            vars_file.display()
+        );
+        return;
+    }
+
+    let written = toml::to_string(vars)
+        .map_err(|e| e.to_string())
+        .and_then(|vars| fs::write(&vars_file, vars).map_err(|e| e.to_string()));
+    match written {
+        Ok(()) => println!("{}", t!("info.imported_vars", file = vars_file.display())),
+        Err(err) => errors::print(format!("{}: {err}", vars_file.display())),
+    }
+}
+
+/// Imports the dotfiles from chezmoi's source directory, ~/.local/share/chezmoi by default
+pub fn from_chezmoi_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    source: Option<PathBuf>,
+    group: Option<String>,
+) -> Result<(), TuckrError> {
+    let dotfiles_dir = prepare(profile, group.as_deref())?;
+
+    let Some(source) = source.or_else(|| Some(data_home()?.join("chezmoi"))) else {
+        return Err(TuckrError::NoSuchFileOrDir);
+    };
+    if !source.is_dir() {
+        errors::print(error!("import_source_missing", dir = source.display()));
+        return Err(TuckrError::NoSuchFileOrDir);
+    }
+
+    // the files can be kept in a subdirectory of the repository
+    let root = fs::read_to_string(source.join(".chezmoiroot")).unwrap_or_default();
+    let root = source.join(root.trim());
+
+    let mut entries = Vec::new();
+    let mut report = Report::default();
+    chezmoi_entries(
+        &root,
+        &root,
+        Path::new(""),
+        group.as_deref(),
+        &mut entries,
+        &mut report,
+    );
+
+    let vars = chezmoi_vars(&root);
+    if !vars.is_empty() {
+        write_vars(&dotfiles_dir, dry_run, &vars);
+    }
+
+    import(&dotfiles_dir, dry_run, entries, report).map(|_| ())
+}
+
+/// Imports the dotfiles from yadm's repository, ~/.local/share/yadm/repo.git by default
+pub fn from_yadm_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    repo: Option<PathBuf>,
+    group: Option<String>,
+) -> Result<(), TuckrError> {
+    let dotfiles_dir = prepare(profile, group.as_deref())?;
+
+    let Some(repo) = repo.or_else(|| Some(data_home()?.join("yadm").join("repo.git"))) else {
+        return Err(TuckrError::NoSuchFileOrDir);
+    };
+    if !repo.is_dir() {
+        errors::print(error!("import_source_missing", dir = repo.display()));
+        return Err(TuckrError::NoSuchFileOrDir);
+    }
+
+    let mut entries = Vec::new();
+    let mut report = Report::default();
+    if let Err(err) = yadm_entries(&repo, group.as_deref(), &mut entries, &mut report) {
+        errors::print(err);
+        return Err(TuckrError::Failed);
+    }
+
+    import(&dotfiles_dir, dry_run, entries, report).map(|_| ())
+}
+
+/// Imports the packages of a stow directory, the current directory by default, each into a group of its own
+///
+/// dot_prefix: `dot-` at the start of a name stands for a dot, like stow's `--dotfiles`
+///
+/// Returns the groups that files were imported into
+pub fn from_stow_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    stow_dir: Option<PathBuf>,
+    dot_prefix: bool,
+) -> Result<Vec<String>, TuckrError> {
+    let dotfiles_dir = prepare(profile, None)?;
+
+    let stow_dir = stow_dir.unwrap_or_else(|| ".".into());
+    let Ok(read_dir) = fs::read_dir(&stow_dir) else {
+        errors::print(error!("import_source_missing", dir = stow_dir.display()));
+        return Err(TuckrError::NoSuchFileOrDir);
+    };
+    let mut packages: Vec<_> = read_dir.flatten().map(|entry| entry.path()).collect();
+    packages.sort();
+
+    let mut entries = Vec::new();
+    let mut report = Report::default();
+    for package in packages {
+        let Some(name) = package.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        // e.g. .git and .stowrc
+        if name.starts_with('.') {
+            continue;
+        }
+        if !package.is_dir() {
+            report.skip(name, t!("warn.import_stow_not_package"));
+            continue;
+        }
+
+        let group = group_name(name);
+        stow_entries(
+            &stow_dir,
+            &package,
+            Path::new(""),
+            &group,
+            dot_prefix,
+            &mut entries,
+            &mut report,
+        );
+    }
+
+    import(&dotfiles_dir, dry_run, entries, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn group_names() {
+        assert_eq!(group_for(Path::new(".zshrc"), None), "zshrc");
+        assert_eq!(group_for(Path::new(".config/nvim/init.lua"), None), "nvim");
+        assert_eq!(
+            group_for(Path::new(".config/starship.toml"), None),
+            "config"
+        );
+        assert_eq!(group_for(Path::new(".zshrc_old"), None), "zshrc-old");
+        assert_eq!(group_for(Path::new(".zshrc"), Some("shell")), "shell");
+    }
+
+    #[test]
+    fn parse_chezmoi_names() {
+        let (name, attrs) = parse_chezmoi_name("private_executable_dot_script", false);
+        assert_eq!(name, ".script");
+        assert_eq!(attrs.mode(false), 0o700);
+
+        let (name, attrs) = parse_chezmoi_name("encrypted_private_dot_netrc.asc", false);
+        assert_eq!(name, ".netrc");
+        assert_eq!(attrs.encrypted, Some(SecretsBackend::Gpg));
+
+        let (name, attrs) = parse_chezmoi_name("dot_gitconfig.tmpl", false);
+        assert_eq!(name, ".gitconfig");
+        assert!(attrs.template);
+
+        let (name, attrs) = parse_chezmoi_name("run_once_before_install.sh", false);
+        assert_eq!(name, "install.sh");
+        assert!(attrs.script && attrs.once && attrs.before);
+
+        assert_eq!(parse_chezmoi_name("literal_run_me", false).0, "run_me");
+        assert_eq!(parse_chezmoi_name("exact_dot_config", true).0, ".config");
+        assert_eq!(
+            parse_chezmoi_name("init.tmpl.literal", false).0,
+            "init.tmpl"
+        );
+    }
+
+    #[test]
+    fn convert_templates() {
+        assert_eq!(
+            convert_template("email = {{ .git.email }} on {{.chezmoi.hostname}}").as_deref(),
+            Some("email = {{ git.email }} on {{ tuckr.hostname }}")
+        );
+        assert_eq!(
+            convert_template("no variables").as_deref(),
+            Some("no variables")
+        );
+        assert_eq!(convert_template("{{ if eq .chezmoi.os \"linux\" }}"), None);
+        assert_eq!(convert_template("{{- .name }}"), None);
+        assert_eq!(convert_template("{{ .chezmoi.os }}"), None);
+    }
+
+    #[test]
+    fn yadm_alternates() {
+        assert_eq!(yadm_alternate("default"), Ok(None));
+        assert_eq!(yadm_alternate("os.Linux"), Ok(Some("_linux".into())));
+        assert_eq!(
+            yadm_alternate("o.Darwin,hostname.laptop"),
+            Ok(Some("_macos%laptop".into()))
+        );
+        assert_eq!(yadm_alternate("class.Work"), Err("class.Work".into()));
+    }
+
+    #[test]
+    fn import_chezmoi_source_dir() {
+        let dir = std::env::temp_dir()
+            .join("tuckr")
+            .join("import")
+            .join(std::process::id().to_string());
+        _ = fs::remove_dir_all(&dir);
+        let source = dir.join("chezmoi");
+        fs::create_dir_all(source.join("private_dot_ssh")).unwrap();
+        fs::create_dir_all(source.join("dot_config").join("nvim")).unwrap();
+        fs::create_dir_all(source.join(".git")).unwrap();
+        fs::write(source.join("dot_zshrc"), "zsh").unwrap();
+        fs::write(source.join("private_dot_ssh").join("config"), "Host *").unwrap();
+        fs::write(
+            source.join("private_dot_ssh").join("encrypted_id.age"),
+            "age",
+        )
+        .unwrap();
+        fs::write(source.join("dot_config/nvim/init.lua.tmpl"), "{{ .name }}").unwrap();
+        fs::write(source.join("dot_config/nvim/modify_state"), "x").unwrap();
+        fs::write(source.join("run_onchange_setup.sh"), "echo").unwrap();
+        fs::write(source.join(".chezmoiignore"), "README.md").unwrap();
+
+        let mut entries = Vec::new();
+        let mut report = Report::default();
+        chezmoi_entries(
+            &source,
+            &source,
+            Path::new(""),
+            None,
+            &mut entries,
+            &mut report,
+        );
+
+        let paths: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                Path::new(entry.dtype.dir_name())
+                    .join(&entry.group)
+                    .join(&entry.path)
+            })
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new("Configs/nvim/.config/nvim/init.lua.tmpl"),
+                Path::new("Configs/zshrc/.zshrc"),
+                Path::new("Configs/ssh/.ssh"),
+                Path::new("Configs/ssh/.ssh/config"),
+                Path::new("Secrets/ssh/.ssh/id"),
+                Path::new("Hooks/chezmoi/post_setup.sh"),
+            ]
+        );
+        assert_eq!(entries[2].kind, Kind::Dir { mode: 0o700 });
+        assert_eq!(
+            report
+                .skipped
+                .iter()
+                .map(|(path, _)| path.as_str())
+                .collect::<Vec<_>>(),
+            [".chezmoiignore", "dot_config/nvim/modify_state"]
+        );
+        assert_eq!(report.notes.len(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn import_stow_package() {
+        let package = std::env::temp_dir()
+            .join("tuckr")
+            .join("import-stow")
+            .join(std::process::id().to_string())
+            .join("nvim");
+        _ = fs::remove_dir_all(&package);
+        fs::create_dir_all(package.join("dot-config").join("nvim")).unwrap();
+        fs::create_dir_all(package.join(".git")).unwrap();
+        fs::write(package.join("dot-config/nvim/init.lua"), "vim").unwrap();
+        fs::write(package.join("dot-config/nvim/init.lua~"), "old").unwrap();
+        fs::write(package.join("README.md"), "readme").unwrap();
+        fs::write(package.join(".stow-local-ignore"), "notes").unwrap();
+
+        let stow_dir = package.parent().unwrap();
+        let mut entries = Vec::new();
+        let mut report = Report::default();
+        stow_entries(
+            stow_dir,
+            &package,
+            Path::new(""),
+            "nvim",
+            true,
+            &mut entries,
+            &mut report,
+        );
+
+        let paths: Vec<_> = entries.iter().map(|entry| entry.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            [
+                Path::new(".config"),
+                Path::new(".config/nvim"),
+                Path::new(".config/nvim/init.lua"),
+            ]
+        );
+        assert!(entries.iter().all(|entry| entry.group == "nvim"));
+        assert_eq!(
+            report
+                .skipped
+                .iter()
+                .map(|(path, _)| path.replace('\\', "/"))
+                .collect::<Vec<_>>(),
+            [
+                "nvim/.git",
+                "nvim/.stow-local-ignore",
+                "nvim/README.md",
+                "nvim/dot-config/nvim/init.lua~",
+            ]
+        );
+
+        fs::remove_dir_all(stow_dir).unwrap();
+    }
+}
@@ -0,0 +1,116 @@
+//! How tuckr behaves when nobody's watching it
+//!
+//! Configuration management tools and scripts can't answer prompts, `--yes` answers yes to every
+//! one of them. `--quiet` leaves out the progress bars, spinners and headers that are only meant for
+//! whoever's looking at the terminal, errors and what was changed are still printed.
+//! `--interactive` goes the other way and asks what to do with every file that's in the way of a dotfile.
+
+use crate::config;
+use crate::progress;
+use rust_i18n::t;
+use std::fmt::Display;
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+static QUIET: AtomicBool = AtomicBool::new(false);
+static ASK_ON_CONFLICT: AtomicBool = AtomicBool::new(false);
+
+/// Answers yes to every prompt, set with `--yes`
+pub fn assume_yes() {
+    ASSUME_YES.store(true, Ordering::Relaxed);
+}
+
+/// Leaves out what's only meant for whoever's looking at the terminal, set with `--quiet`
+pub fn set_quiet() {
+    QUIET.store(true, Ordering::Relaxed);
+    progress::disable();
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Asks a yes or no question, returns true without asking if `--yes` was used
+///
+/// Anything but "y" or "yes" is a no, as is stdin being closed
+pub fn confirm(question: impl Display) -> bool {
+    if ASSUME_YES.load(Ordering::Relaxed) {
+        return true;
+    }
+
+    progress::suspend(|| {
+        print!("{question} ");
+        std::io::stdout()
+            .flush()
+            .expect("Could not print to stdout");
+
+        let mut answer = String::new();
+        std::io::stdin()
+            .read_line(&mut answer)
+            .expect("Could not read from stdin");
+
+        matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+    })
+}
+
+/// Asks what to do with every conflicting file instead of failing, set with `--interactive`
+pub fn ask_on_conflict() {
+    ASK_ON_CONFLICT.store(true, Ordering::Relaxed);
+}
+
+/// Returns true if conflicting files are asked about one by one
+///
+/// `ask_on_conflict = true` in tuckr.toml only asks when stdin is a terminal, and nothing's asked
+/// with `--yes`, so that scripts still fail on conflicts
+pub fn asks_on_conflict() -> bool {
+    let asked = ASK_ON_CONFLICT.load(Ordering::Relaxed)
+        || (config::get().ask_on_conflict && std::io::stdin().is_terminal());
+
+    asked && !ASSUME_YES.load(Ordering::Relaxed)
+}
+
+/// What's done with a file that's in the way of a dotfile
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resolution {
+    /// it's backed up and replaced by the dotfile, like with `--force`
+    Overwrite,
+    /// it's moved into the dotfiles in place of the dotfile, like with `--adopt`
+    Adopt,
+    /// it's left alone and reported as a conflict
+    Skip,
+    /// how it differs from the dotfile is shown before asking again
+    Diff,
+    /// it's overwritten and so is every conflicting file after it
+    All,
+}
+
+/// Asks what to do with a file that's in the way of a dotfile until one of the answers is given
+///
+/// The file is skipped when stdin is closed
+pub fn ask_resolution(file: &Path) -> Resolution {
+    progress::suspend(|| {
+        loop {
+            print!("{} ", t!("warn.resolve_conflict", file = file.display()));
+            std::io::stdout()
+                .flush()
+                .expect("Could not print to stdout");
+
+            let mut answer = String::new();
+            if std::io::stdin().read_line(&mut answer).unwrap_or_default() == 0 {
+                return Resolution::Skip;
+            }
+
+            // `a` adopts and `A` overwrites all of them, so the answer's case matters
+            match answer.trim() {
+                "o" => return Resolution::Overwrite,
+                "a" => return Resolution::Adopt,
+                "s" => return Resolution::Skip,
+                "d" => return Resolution::Diff,
+                "A" => return Resolution::All,
+                _ => continue,
+            }
+        }
+    })
+}
@@ -0,0 +1,70 @@
+//! Tuckr is a set of tools to manage dotfiles
+//!
+//! Tuckr separates dotfiles into 3 different directories:
+//! - dotfiles/Configs - stores config files
+//! - dotfiles/Hooks - stores scripts that configure the environment
+//! - dotfiles/Secrets - stores encrypted/sensitive files
+//!
+//! Each of these of these directories contain directories with the name of the groups or logical
+//! groups which contains all user scripts, configs and scripts, these are used to label them on tuckr
+//! so you can add or remove them anytime
+//!
+//! The commands are the `*_cmd` functions of the modules, they print what they do along with their
+//! errors and fail with an [`errors::TuckrError`], so other tools can embed tuckr by calling them.
+
+pub mod archive;
+pub mod audit;
+pub mod backups;
+pub mod cancel;
+pub mod clean;
+pub mod completions;
+pub mod config;
+pub mod daemon;
+pub mod deprecated;
+pub mod diff;
+pub mod dotfiles;
+pub mod errors;
+pub mod escalate;
+pub mod experiments;
+pub mod export;
+pub mod fileops;
+// the file tree is still a work in progress and is not wired into the commands yet
+#[allow(dead_code, clippy::collapsible_if, clippy::bool_assert_comparison)]
+mod filetree;
+pub mod history;
+pub mod hooklog;
+pub mod hooks;
+pub mod i18n;
+pub mod import;
+pub mod interactive;
+pub mod lock;
+pub mod nested;
+pub mod packages;
+pub mod permissions;
+pub mod plan;
+pub mod preflight;
+pub mod profiles;
+pub mod progress;
+pub mod safe;
+pub mod secrets;
+pub mod snapshot;
+pub mod stash;
+pub mod state;
+pub mod stats;
+pub mod statuscache;
+pub mod symlinks;
+pub mod table;
+pub mod templates;
+pub mod transaction;
+pub mod ui;
+pub mod verify;
+pub mod watch;
+pub mod which;
+
+// translations from the locales directories take priority over the built-in ones
+rust_i18n::i18n!(
+    "locales",
+    minify_key = true,
+    fallback = "en",
+    backend = i18n::ExternalLocales
+);
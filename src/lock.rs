@@ -0,0 +1,167 @@
+//! Keeps two tuckrs from changing the deployed dotfiles at the same time
+//!
+//! Commands that deploy, remove or change the machine's state hold an advisory lock on
+//! `$TUCKR_HOME/tuckr.lock` (next to tuckr.toml when $TUCKR_HOME isn't set) while they run, it
+//! contains the pid of the tuckr holding it. The file is never deleted, the system releases the
+//! lock once its holder exits, even if it was killed. Tuckrs that are run by the holder's hooks
+//! share its lock instead of failing, see [`crate::nested`].
+//!
+//! With `--wait` a tuckr waits for the lock to be released instead of failing right away, e.g. when
+//! a provisioning script and someone at the terminal run tuckr at the same time.
+
+use crate::error;
+use crate::errors;
+use crate::interactive;
+use crate::nested;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::Stderr;
+use rust_i18n::t;
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Seek, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// how often a waiting tuckr checks whether the lock was released
+const WAIT_INTERVAL: Duration = Duration::from_millis(250);
+
+const LOCK_FILE: &str = "tuckr.lock";
+
+fn get_lock_path() -> PathBuf {
+    match std::env::var("TUCKR_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir).join(LOCK_FILE),
+        _ => dirs::config_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join(LOCK_FILE),
+    }
+}
+
+/// Releases the lock once it's dropped, unless it's shared with the tuckr that holds it
+#[derive(Debug)]
+pub struct Lock {
+    /// closing the file releases the lock
+    _file: Option<File>,
+}
+
+/// Why the lock couldn't be taken
+#[derive(Debug)]
+enum LockError {
+    /// another tuckr holds it, its pid is unknown until it wrote it to the lock
+    Held(Option<u32>),
+    Failed(errors::Error),
+}
+
+fn try_lock(path: &Path) -> Result<Lock, LockError> {
+    let failed =
+        |err: std::io::Error| LockError::Failed(format!("{}: {err}", path.display()).into());
+
+    // it isn't truncated before being locked since that would erase the holder's pid
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .map_err(failed)?;
+
+    match file.try_lock() {
+        Ok(()) => {
+            file.set_len(0)
+                .and_then(|_| file.rewind())
+                .and_then(|_| file.write_all(std::process::id().to_string().as_bytes()))
+                .map_err(failed)?;
+            Ok(Lock { _file: Some(file) })
+        }
+        Err(TryLockError::WouldBlock) => {
+            let holder = fs::read_to_string(path)
+                .ok()
+                .and_then(|holder| holder.trim().parse::<u32>().ok());
+            match holder {
+                Some(pid) if Some(pid) == nested::session_owner() => Ok(Lock { _file: None }),
+                holder => Err(LockError::Held(holder)),
+            }
+        }
+        Err(TryLockError::Error(err)) => Err(LockError::Failed(error!(
+            "couldnt_lock",
+            file = path.display(),
+            err = err
+        ))),
+    }
+}
+
+/// Takes the lock, fails if another tuckr holds it
+///
+/// wait: how long to wait for the other tuckr to release it, `Some(None)` waits for as long as it takes
+pub fn acquire(wait: Option<Option<Duration>>) -> Result<Lock, errors::Error> {
+    let path = get_lock_path();
+    fs::create_dir_all(path.parent().unwrap()).map_err(|e| format!("{}: {e}", path.display()))?;
+
+    let started = Instant::now();
+    let mut waiting_for = None;
+    loop {
+        let pid = match try_lock(&path) {
+            Ok(lock) => return Ok(lock),
+            Err(LockError::Failed(err)) => return Err(err),
+            Err(LockError::Held(pid)) => pid,
+        };
+        let shown_pid = pid.map_or_else(|| "?".to_string(), |pid| pid.to_string());
+
+        let timed_out = match wait {
+            Some(Some(timeout)) => started.elapsed() >= timeout,
+            Some(None) => false,
+            None => true,
+        };
+        if timed_out {
+            return Err(error!("tuckr_is_running", pid = shown_pid));
+        }
+
+        // told again if another tuckr got the lock in the meantime
+        if waiting_for != Some(pid) && !interactive::is_quiet() {
+            eprintln!(
+                "{}",
+                t!("info.waiting_for_lock", pid = shown_pid)
+                    .if_supports_color(Stderr, |t| t.yellow())
+            );
+        }
+        waiting_for = Some(pid);
+        thread::sleep(WAIT_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_is_exclusive() {
+        let dir = std::env::temp_dir()
+            .join("tuckr")
+            .join("lock")
+            .join(std::process::id().to_string());
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(LOCK_FILE);
+        _ = fs::remove_file(&path);
+
+        let lock = try_lock(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            std::process::id().to_string()
+        );
+        assert!(
+            matches!(try_lock(&path), Err(LockError::Held(Some(pid))) if pid == std::process::id())
+        );
+        drop(lock);
+        assert!(path.exists());
+
+        // the pid left behind by a tuckr that was killed doesn't keep the lock taken
+        fs::write(&path, i32::MAX.to_string()).unwrap();
+        let lock = try_lock(&path).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            std::process::id().to_string()
+        );
+        drop(lock);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,25 +1,19 @@
-//! Tuckr is a set of tools to manage dotfiles
+//! Tuckr's command line, the commands themselves are in the tuckr library
 //!
-//! Tuckr separates dotfiles into 3 different directories:
-//! - dotfiles/Configs - stores config files
-//! - dotfiles/Hooks - stores scripts that configure the environment
-//! - dotfiles/Secrets - stores encrypted/sensitive files
-//!
-//! Each of these of these directories contain directories with the name of the groups or logical
-//! groups which contains all user scripts, configs and scripts, these are used to label them on tuckr
-//! so you can add or remove them anytime
-
-mod dotfiles;
-mod fileops;
-mod filetree;
-mod hooks;
-mod secrets;
-mod symlinks;
-
-use clap::{Parser, Subcommand};
-use std::process::ExitCode;
+//! The errors that the commands fail with are turned into exit codes here, see `exit_code`
 
-rust_i18n::i18n!("locales", minify_key = true, fallback = "en");
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+use tuckr::errors::{self, TuckrError};
+use tuckr::{
+    backups, cancel, clean, completions, config, daemon, deprecated, diff, dotfiles, escalate,
+    experiments, export, fileops, history, hooklog, hooks, i18n, import, interactive, lock, nested,
+    packages, plan, preflight, profiles, progress, safe, secrets, snapshot, stash, state, stats,
+    symlinks, table, transaction, ui, verify, watch, which,
+};
 
 /// style similar to cargo's
 const fn tuckr_color_styles() -> clap::builder::Styles {
@@ -35,13 +29,66 @@ const fn tuckr_color_styles() -> clap::builder::Styles {
 #[command(about, author, version, propagate_version = true, styles = tuckr_color_styles())]
 struct Cli {
     /// Choose which dotfile profile to use
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     profile: Option<String>,
 
     /// No filesystem operations. Only print what would happen
     #[arg(short = 'n', long)]
     dry_run: bool,
 
+    /// Number of threads used to go through and deploy dotfiles, defaults to the number of CPUs
+    #[arg(short, long, global = true, value_name = "N")]
+    jobs: Option<NonZeroUsize>,
+
+    /// Read the password for secrets from the first line of a file instead of asking for it
+    #[arg(long, global = true, value_name = "FILE")]
+    password_file: Option<PathBuf>,
+
+    /// Keep what was deployed when deploying fails partway through instead of rolling it back
+    #[arg(long, global = true)]
+    no_rollback: bool,
+
+    /// Print what hooks output as they run, it's always written to their logs in $TUCKR_HOME/.logs
+    #[arg(long, global = true)]
+    show_hook_output: bool,
+
+    /// How tables are printed, csv and tsv print their rows without borders nor colors, json also
+    /// prints errors as objects with a stable code, see `tuckr errors list`
+    #[arg(long, global = true, value_name = "FORMAT", default_value = "table")]
+    format: table::Format,
+
+    /// Fail instead of warning when a command or flag that was renamed is used by its old name
+    #[arg(long, global = true)]
+    strict_cli: bool,
+
+    /// Don't show progress bars, spinners nor headers, errors and what changed are still printed
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Answer yes to every prompt, for scripts and configuration management tools
+    #[arg(long, global = true)]
+    yes: bool,
+
+    /// Wait for another tuckr that's changing the dotfiles to finish instead of failing,
+    /// for at most the given time if there is one, e.g. --wait=5m
+    #[arg(long, global = true, value_name = "TIME", num_args = 0..=1, require_equals = true, value_parser = parse_wait)]
+    wait: Option<Option<Duration>>,
+
+    /// Log what's being done and why on stderr: -v which files are deployed or skipped, -vv how
+    /// each dotfile compares with its target and which groups target this machine, -vvv everything
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Language to print messages in, e.g. pt-PT, defaults to $TUCKR_LANG or the system's language.
+    /// Translations can be added in $TUCKR_HOME/locales
+    #[arg(long, global = true, value_name = "LANG")]
+    lang: Option<String>,
+
+    /// Directory to deploy the dotfiles to instead of $TUCKR_TARGET or the home directory,
+    /// e.g. a container image's filesystem or another user's home
+    #[arg(long, global = true, value_name = "DIR")]
+    target: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Command,
 }
@@ -53,8 +100,33 @@ enum Command {
     Status {
         #[arg(value_name = "group")]
         groups: Option<Vec<String>>,
+
+        /// Show what changed in the deployed dotfiles since a git revision of the dotfiles
+        #[arg(long, value_name = "REV")]
+        at: Option<String>,
+
+        /// Exclude certain groups from the status
+        #[arg(
+            short,
+            long,
+            value_name = "group",
+            use_value_delimiter = true,
+            conflicts_with = "at"
+        )]
+        exclude: Vec<String>,
+
+        /// Re-point dangling symlinks to the dotfiles that replaced them or remove them
+        #[arg(long, conflicts_with = "at")]
+        fix: bool,
+
+        /// Check every dotfile and its target again instead of reusing what the last status found out
+        #[arg(long, conflicts_with = "at")]
+        no_cache: bool,
     },
 
+    /// Browse the groups interactively, selecting which ones to add, remove or set
+    Ui,
+
     /// Deploy dotfiles for the supplied groups (alias: a)
     #[command(alias = "a")]
     Add {
@@ -65,7 +137,7 @@ enum Command {
         #[arg(short, long, value_name = "group", use_value_delimiter = true)]
         exclude: Vec<String>,
 
-        /// Override conflicting dotfiles
+        /// Override conflicting dotfiles (they're backed up to dotfiles/.backups)
         #[arg(short, long)]
         force: bool,
 
@@ -80,16 +152,56 @@ enum Command {
         /// Only add files and ignore directories
         #[arg(long)]
         only_files: bool,
+
+        /// Copy dotfiles instead of symlinking them, for systems where symlinks can't be used
+        #[arg(long)]
+        copy: bool,
+
+        /// Deploy into an empty directory as if it was the filesystem's root instead of the real system
+        #[arg(long, value_name = "DIR")]
+        into: Option<PathBuf>,
+
+        /// Commit the dotfiles adopted with --adopt with git
+        #[arg(long)]
+        commit: bool,
+
+        /// Don't decrypt the groups' secrets
+        #[arg(long)]
+        no_secrets: bool,
+
+        /// Only create what's missing, never delete, move or overwrite anything and report what was left alone
+        #[arg(long, conflicts_with_all = ["force", "adopt"])]
+        safe: bool,
+
+        /// Ask whether to overwrite, adopt or skip every conflicting file
+        #[arg(short, long, conflicts_with_all = ["force", "adopt", "assume_yes", "safe"])]
+        interactive: bool,
+
+        /// Symlink the dotfiles with relative paths, so they keep working wherever the home directory is mounted
+        #[arg(long)]
+        relative: bool,
     },
 
-    /// Remove dotfiles for the supplied groups
+    /// Remove dotfiles for the supplied groups and run their remove and teardown hooks
     Rm {
-        #[arg(required = true, value_name = "group")]
+        #[arg(required_unless_present = "all", value_name = "group")]
         groups: Vec<String>,
 
         /// Exclude certain groups from being removed
         #[arg(short, long, value_name = "group", use_value_delimiter = true)]
         exclude: Vec<String>,
+
+        /// Remove every group that's deployed, after showing what will be unlinked
+        #[arg(long, conflicts_with = "groups")]
+        all: bool,
+
+        /// Automatically answer yes on every prompt
+        #[arg(short = 'y', long)]
+        assume_yes: bool,
+
+        /// Don't run the groups' remove and teardown hooks
+        #[arg(long)]
+        no_hooks: bool,
     },
 
     /// Setup groups and run their hooks
@@ -116,16 +228,102 @@ enum Command {
         /// Only add files and ignore directories
         #[arg(long)]
         only_files: bool,
+
+        /// Copy dotfiles instead of symlinking them, for systems where symlinks can't be used
+        #[arg(long)]
+        copy: bool,
+
+        /// Append a JSON report of the hooks that were run to a file
+        #[arg(long, value_name = "FILE")]
+        audit_log: Option<PathBuf>,
+
+        /// Don't decrypt the groups' secrets
+        #[arg(long)]
+        no_secrets: bool,
+
+        /// Deploy the groups even if their preflight checks fail
+        #[arg(long)]
+        skip_preflight: bool,
+
+        /// Keep setting the rest of the groups after one of them fails
+        #[arg(short, long)]
+        keep_going: bool,
+
+        /// Ask whether to overwrite, adopt or skip every conflicting file
+        #[arg(short, long, conflicts_with_all = ["force", "adopt", "assume_yes"])]
+        interactive: bool,
+
+        /// Symlink the dotfiles with relative paths, so they keep working wherever the home directory is mounted
+        #[arg(long)]
+        relative: bool,
     },
 
-    /// Remove groups and run their cleanup hooks
-    Unset {
+    /// Show everything `tuckr set` would do with the groups without changing anything
+    ///
+    /// Lists the dotfiles that would be deployed, the links and files that would be replaced, the
+    /// directories that would be created, the hooks that would run, the secrets that would be
+    /// decrypted and the conflicts that would be left alone
+    Check {
         #[arg(required = true, value_name = "group")]
         groups: Vec<String>,
 
+        /// Exclude certain groups from being checked
+        #[arg(short, long, value_name = "group", use_value_delimiter = true)]
+        exclude: Vec<String>,
+
+        /// Plan to override conflicting dotfiles
+        #[arg(short, long)]
+        force: bool,
+
+        /// Plan to adopt conflicting dotfiles
+        #[arg(short, long)]
+        adopt: bool,
+
+        /// Only add files and ignore directories
+        #[arg(long)]
+        only_files: bool,
+
+        /// Copy dotfiles instead of symlinking them, for systems where symlinks can't be used
+        #[arg(long)]
+        copy: bool,
+
+        /// Leave the groups' secrets out of the plan
+        #[arg(long)]
+        no_secrets: bool,
+    },
+
+    /// Install the system packages that groups list in their packages.toml
+    ///
+    /// The package manager is found in $PATH: apt, dnf, pacman, brew or winget
+    Install {
+        #[arg(required = true, value_name = "group")]
+        groups: Vec<String>,
+
+        /// Install them with this package manager, only the command is printed if it isn't installed
+        #[arg(long, value_name = "apt|dnf|pacman|brew|winget")]
+        manager: Option<packages::Manager>,
+    },
+
+    /// Remove groups and run their remove hooks (same as rm)
+    Unset {
+        #[arg(required_unless_present = "all", value_name = "group")]
+        groups: Vec<String>,
+
         /// Exclude certain groups from being removed
         #[arg(short, long, value_name = "group", use_value_delimiter = true)]
         exclude: Vec<String>,
+
+        /// Remove every group that's deployed, after showing what will be unlinked
+        #[arg(long, conflicts_with = "groups")]
+        all: bool,
+
+        /// Automatically answer yes on every prompt
+        #[arg(short = 'y', long)]
+        assume_yes: bool,
+
+        /// Don't run the groups' remove and teardown hooks
+        #[arg(long)]
+        no_hooks: bool,
     },
 
     /// Encrypt files and move them to dotfiles/Secrets (alias: e)
@@ -134,6 +332,18 @@ enum Command {
         group: String,
         #[arg(required = true, value_name = "FILE")]
         dotfiles: Vec<String>,
+
+        /// Pack the files into a single encrypted archive so that not even their names are in the repo
+        #[arg(long)]
+        archive: bool,
+
+        /// Encrypt the files as hooks of the group, which are only decrypted while `tuckr set` runs them
+        #[arg(long, conflicts_with = "archive")]
+        hook: bool,
+
+        /// Commit the changes made to the dotfiles with git
+        #[arg(long)]
+        commit: bool,
     },
 
     /// Decrypt files (alias: d)
@@ -152,6 +362,18 @@ enum Command {
         assume_yes: bool,
         #[arg(required = true)]
         files: Vec<String>,
+
+        /// Commit the changes made to the dotfiles with git
+        #[arg(long)]
+        commit: bool,
+    },
+
+    /// Open a group or one of its files in $EDITOR, files that don't exist yet are created or adopted
+    Edit {
+        group: String,
+
+        /// Path relative to the group or to the file it's deployed to
+        file: Option<String>,
     },
 
     /// Remove groups from dotfiles/Configs
@@ -160,20 +382,326 @@ enum Command {
         groups: Vec<String>,
         #[arg(short = 'y', long)]
         assume_yes: bool,
+
+        /// Commit the changes made to the dotfiles with git
+        #[arg(long)]
+        commit: bool,
     },
 
-    /// List dotfiles hooks, secrets, profiles
+    /// Show how the deployed dotfiles differ from the ones in dotfiles/Configs
+    Diff {
+        #[arg(value_name = "group")]
+        groups: Option<Vec<String>>,
+
+        /// Only show how many lines changed in each file
+        #[arg(long)]
+        stat: bool,
+
+        /// Deploy the dotfiles over the copies that were modified on the target, backing the copies up
+        #[arg(long, conflicts_with_all = ["stat", "pull_back"])]
+        overwrite: bool,
+
+        /// Write the edits of the copies that were modified on the target back to their dotfiles
+        #[arg(long, conflicts_with = "stat")]
+        pull_back: bool,
+    },
+
+    /// Merge a conflicting file with its dotfile in a merge tool and deploy the result
+    Resolve {
+        path: PathBuf,
+
+        /// Merge tool to use: meld, vimdiff, nvim, code or a command with {dotfile} and {target} in it
+        #[arg(long = "with", value_name = "tool")]
+        tool: Option<String>,
+    },
+
+    /// List dotfiles hooks, secrets, profiles, groups
     #[command(subcommand, arg_required_else_help = true)]
     Ls(ListType),
 
+    /// Manage dotfile profiles
+    #[command(subcommand, arg_required_else_help = true)]
+    Profile(ProfileCmd),
+
+    /// Manage encrypted files
+    #[command(subcommand, arg_required_else_help = true)]
+    Secrets(SecretsCmd),
+
+    /// Find the commit that broke a group with git bisect, the group is redeployed at every step
+    Bisect {
+        group: String,
+
+        /// A revision where the group still worked
+        #[arg(long, value_name = "REV")]
+        good: String,
+
+        /// A revision where the group is broken
+        #[arg(long, value_name = "REV", default_value = "HEAD")]
+        bad: String,
+    },
+
+    /// Clone a dotfiles repository to where `tuckr init` would create the dotfiles
+    #[command(arg_required_else_help = true)]
+    Clone { url: String },
+
+    /// Pull the dotfiles from their git remote and show which deployed groups changed
+    Sync,
+
+    /// Keep the dotfiles up to date in the background, groups with conflicts are left for you to resolve
+    Daemon {
+        /// How often to pull the dotfiles and deploy the deployed groups that changed, e.g. "30m" or "24h"
+        #[arg(long, value_name = "INTERVAL", value_parser = config::parse_age)]
+        auto_update: u64,
+
+        /// Only apply commits once they're this old, so that bad ones can be reverted before every machine deploys them
+        #[arg(long, value_name = "AGE", value_parser = config::parse_age, default_value = "0")]
+        grace_period: u64,
+
+        /// Set the changed groups up with `tuckr set` so that their hooks run, failed hooks are notified about
+        #[arg(long)]
+        run_hooks: bool,
+    },
+
+    /// Deploy the groups again whenever their files in Configs change, the deployed groups if none are given
+    ///
+    /// New files are symlinked, copies and templates are updated and the symlinks to deleted files are removed
+    Watch {
+        #[arg(value_name = "group")]
+        groups: Vec<String>,
+
+        /// Exclude certain groups from being deployed
+        #[arg(short, long, value_name = "group", use_value_delimiter = true)]
+        exclude: Vec<String>,
+
+        /// How many milliseconds files have to stay the same before they're deployed
+        #[arg(long, value_name = "MS", default_value_t = 500)]
+        debounce: u64,
+
+        /// Exit after deploying the first changes
+        #[arg(long)]
+        once: bool,
+    },
+
+    /// Commit every change to the dotfiles and push them to their git remote
+    PushRemote {
+        /// Commit message, defaults to one mentioning this machine's hostname
+        #[arg(short, long)]
+        message: Option<String>,
+    },
+
+    /// Deploy a group from another git branch to try it out, `tuckr try --end` deploys the dotfiles' version again
+    #[command(arg_required_else_help = true)]
+    Try {
+        /// The group and branch to try or, with --end, the group whose experiment should end
+        #[arg(value_name = "group@branch", required_unless_present = "end")]
+        experiment: Option<String>,
+
+        /// End the experiment, every experiment is ended if no group is given
+        #[arg(long)]
+        end: bool,
+    },
+
     /// Initialize dotfile directory
     ///
-    /// Creates the files that are necessary to use Tuckr
-    Init,
+    /// Creates the files that are necessary to use Tuckr, files that already exist are left alone
+    Init {
+        /// Make the dotfiles a git repository, unless they're already inside of one
+        #[arg(long)]
+        git: bool,
+
+        /// Write a starter tuckr.toml with the most common settings commented out
+        #[arg(long)]
+        config: bool,
+
+        /// Write a .gitignore that keeps this machine's state, backups and hook logs out of the repository
+        #[arg(long)]
+        gitignore: bool,
+    },
+
+    /// Create a group with empty directories in Configs and Hooks
+    #[command(arg_required_else_help = true)]
+    New {
+        #[arg(value_name = "group")]
+        group: String,
+    },
+
+    /// Export the groups as GNU Stow packages, e.g. to migrate away or to share them with stow users
+    ///
+    /// Templates are rendered and files get the names they're deployed with, secrets and hooks are
+    /// left out. The directory has to be empty or not exist yet.
+    #[command(arg_required_else_help = true)]
+    ToStow {
+        dir: PathBuf,
+
+        /// Export the groups deployed on this machine as a chezmoi source directory instead
+        #[arg(long)]
+        chezmoi: bool,
+
+        /// Exclude certain groups from being exported
+        #[arg(short, long, value_name = "group", use_value_delimiter = true)]
+        exclude: Vec<String>,
+    },
+
+    /// Pack the whole dotfiles directory into a compressed archive, e.g. to move it to a machine without git access
+    ///
+    /// The state, backups and hooks' logs are only meant for this machine and are left out
+    #[command(arg_required_else_help = true)]
+    Export {
+        /// The archive to write, e.g. dotfiles.tar.zst
+        file: PathBuf,
+
+        /// Leave the encrypted secrets out of the archive
+        #[arg(long)]
+        no_secrets: bool,
+    },
+
+    /// Unpack an archive written by `tuckr export` to where `tuckr init` would create the dotfiles
+    ///
+    /// Files that are already there and differ are only replaced once confirmed
+    #[command(arg_required_else_help = true)]
+    Import { file: PathBuf },
+
+    /// Import the dotfiles from chezmoi's source directory, reporting what couldn't be translated
+    ///
+    /// Files are split into groups named after their top level file or directory, or `.config/<app>`
+    FromChezmoi {
+        /// chezmoi's source directory, defaults to ~/.local/share/chezmoi
+        dir: Option<PathBuf>,
+
+        /// Import every file into this group
+        #[arg(short, long)]
+        group: Option<String>,
+    },
+
+    /// Import the dotfiles from yadm's repository, reporting what couldn't be translated
+    ///
+    /// Files are split into groups named after their top level file or directory, or `.config/<app>`.
+    /// Alternates for an OS or a host are imported into conditional groups
+    FromYadm {
+        /// yadm's repository, defaults to ~/.local/share/yadm/repo.git
+        repo: Option<PathBuf>,
+
+        /// Import every file into this group
+        #[arg(short, long)]
+        group: Option<String>,
+    },
+
+    /// Import the packages of a GNU Stow directory, each package becomes a group
+    ///
+    /// What stow ignores by default is left out, e.g. READMEs and .git
+    FromStow {
+        /// the stow directory that the packages are in, defaults to the current directory
+        dir: Option<PathBuf>,
+
+        /// Turn `dot-` at the start of names into dots, like stow's --dotfiles
+        #[arg(long)]
+        dotfiles: bool,
+
+        /// Commit the imported groups with git
+        #[arg(long)]
+        commit: bool,
+    },
+
+    /// Hold groups at their current deployment so that `add` and `set` skip them
+    #[command(arg_required_else_help = true)]
+    Hold { groups: Vec<String> },
+
+    /// Release held groups
+    #[command(arg_required_else_help = true)]
+    Unhold { groups: Vec<String> },
+
+    /// Attach a machine-local note to a group
+    ///
+    /// Lists the group's notes if no note is supplied
+    Note {
+        group: String,
+        note: Vec<String>,
+
+        /// Remove all notes attached to the group
+        #[arg(long, conflicts_with = "note")]
+        clear: bool,
+    },
+
+    /// Temporarily remove groups without running their hooks, `tuckr stash pop` deploys them again
+    #[command(args_conflicts_with_subcommands = true, arg_required_else_help = true)]
+    Stash {
+        #[command(subcommand)]
+        cmd: Option<StashCmd>,
+
+        #[arg(value_name = "group")]
+        groups: Vec<String>,
+    },
+
+    /// List, prune or restore the files that were backed up when overriding conflicts
+    #[command(arg_required_else_help = true)]
+    Backups {
+        #[command(subcommand)]
+        cmd: BackupsCmd,
+    },
+
+    /// Remove the symlinks into the dotfiles that were left behind by groups that were renamed or removed
+    Clean {
+        /// How many directories deep the target directory is searched when the state has no record of what's deployed
+        #[arg(long, default_value_t = clean::DEFAULT_DEPTH)]
+        depth: usize,
+    },
 
     /// Return the group files belongs to
     #[command(name = "groupis", arg_required_else_help = true)]
     GroupIs { files: Vec<String> },
+
+    /// Show which group and dotfile a deployed file comes from, or where a dotfile is deployed to
+    #[command(arg_required_else_help = true)]
+    Which { paths: Vec<PathBuf> },
+
+    /// Check the dotfiles for invalid group names, empty groups, non-executable hooks, unencrypted secrets, shadowed groups and colliding files
+    VerifyRepo {
+        /// Only check what's staged in the dotfiles' git repository and only report problems involving the staged changes, for pre-commit hooks
+        #[arg(long)]
+        staged: bool,
+    },
+
+    /// Check the dotfiles and how they're deployed for problems, suggesting how to fix each of them
+    Doctor,
+
+    /// Show how often commands ran, failed and came across conflicts, needs `stats = true` in tuckr.toml
+    Stats {
+        /// How many weeks to show
+        #[arg(short, long, default_value_t = 8)]
+        weeks: usize,
+    },
+
+    /// List the codes that errors are printed with in `--format json`, wrappers can rely on them
+    #[command(arg_required_else_help = true)]
+    Errors {
+        #[command(subcommand)]
+        cmd: ErrorsCmd,
+    },
+
+    /// Print a shell completion script that also completes group and profile names
+    Completions { shell: clap_complete::Shell },
+
+    /// Print the group or profile names, run by the completion scripts
+    #[command(name = "__complete", hide = true)]
+    Complete { names: completions::Names },
+
+    /// Run the last command that failed for lack of permissions again as administrator, or the given one (Windows)
+    Elevate {
+        #[arg(
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            value_name = "command"
+        )]
+        args: Vec<String>,
+    },
+
+    /// Run a command as administrator, run by tuckr itself with UAC
+    #[command(long_flag = "run-elevated", hide = true)]
+    RunElevated { file: PathBuf },
+
+    /// Apply the changes that need root, run by tuckr itself with sudo or doas
+    #[command(long_flag = "apply-plan", hide = true)]
+    ApplyPlan { file: PathBuf },
 }
 
 #[derive(Debug, Subcommand)]
@@ -187,14 +715,358 @@ enum ListType {
     /// Lists which hooks exists for each group (alias: h)
     #[command(alias = "h")]
     Hooks,
+    /// Lists every group with where it's set up, its deployment state and file count (alias: g)
+    #[command(alias = "g")]
+    Groups {
+        /// Only list groups that are fully deployed
+        #[arg(long, conflicts_with = "not_deployed")]
+        deployed: bool,
+
+        /// Only list groups that aren't deployed or are only partially deployed
+        #[arg(long)]
+        not_deployed: bool,
+
+        /// Only list groups that don't target this machine
+        #[arg(long)]
+        invalid_target: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum StashCmd {
+    /// Deploy the most recently stashed groups again
+    Pop,
+    /// List the stashed groups, the most recent stash comes first (alias: ls)
+    #[command(alias = "ls")]
+    List,
+}
+
+#[derive(Debug, Subcommand)]
+enum ErrorsCmd {
+    /// List every error code along with its message (alias: ls)
+    #[command(alias = "ls")]
+    List,
+}
+
+#[derive(Debug, Subcommand)]
+enum BackupsCmd {
+    /// List the backups with when they were made and how much space they take up (alias: ls)
+    #[command(alias = "ls")]
+    List,
+    /// Remove old backups, the limits that aren't supplied are taken from `[backups]` in tuckr.toml
+    ///
+    /// The most recent backup is always kept
+    Prune {
+        /// Only keep the N most recent backups
+        #[arg(long, value_name = "N")]
+        keep_last: Option<usize>,
+
+        /// Remove backups older than this, e.g. "90d", "12w" or "1y"
+        #[arg(long, value_name = "AGE", value_parser = config::parse_age)]
+        max_age: Option<u64>,
+
+        /// Remove the oldest backups until all of them take up less than this, e.g. "500M" or "2G"
+        #[arg(long, value_name = "SIZE", value_parser = config::parse_size)]
+        max_size: Option<u64>,
+    },
+    /// Restore the files from a backup, lists the available backups if none is supplied
+    Restore { backup: Option<String> },
+}
+
+#[derive(Debug, Subcommand)]
+enum SecretsCmd {
+    /// List the secrets, where they're decrypted to and whether they're deployed, without decrypting them
+    Status {
+        #[arg(value_name = "group", default_value = "*")]
+        groups: Vec<String>,
+        #[arg(short, long, value_name = "group", use_value_delimiter = true)]
+        exclude: Vec<String>,
+    },
+
+    /// Encrypt secrets that are in an outdated format again, e.g. from before passwords were hashed with Argon2id
+    Migrate {
+        #[arg(value_name = "group", default_value = "*")]
+        groups: Vec<String>,
+        #[arg(short, long, value_name = "group", use_value_delimiter = true)]
+        exclude: Vec<String>,
+    },
+
+    /// Encrypt every secret that's encrypted with a password again with a new password
+    Rekey {
+        /// Commit the changes made to the dotfiles with git
+        #[arg(long)]
+        commit: bool,
+    },
+
+    /// Check that the deployed secrets can't be read or replaced by other users
+    Audit {
+        #[arg(value_name = "group", default_value = "*")]
+        groups: Vec<String>,
+        #[arg(short, long, value_name = "group", use_value_delimiter = true)]
+        exclude: Vec<String>,
+
+        /// Tighten the permissions of the secrets and their directories
+        #[arg(long)]
+        fix: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ProfileCmd {
+    /// Lists the available profiles, which are dotfiles directories with a suffix _<profile> (alias: ls)
+    #[command(alias = "ls")]
+    List,
+
+    /// Shows which groups and files differ between two profiles, use `default` for the dotfiles directory without a profile
+    Diff {
+        profile: String,
+
+        #[arg(required_unless_present = "deployed")]
+        other: Option<String>,
+
+        /// Compares the profile with the dotfiles that are currently deployed instead
+        #[arg(long, conflicts_with = "other")]
+        deployed: bool,
+    },
+}
+
+/// Lists the names of the files for commit messages, e.g. `.zshrc, .zprofile`
+fn file_names(files: &[String]) -> String {
+    files
+        .iter()
+        .map(|file| {
+            std::path::Path::new(file)
+                .file_name()
+                .map_or(file.into(), |name| name.to_string_lossy())
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Replaces the bundles and glob patterns in the command's groups with the groups they stand for
+///
+/// Bundles are expanded first so that they can contain patterns too
+fn expand_group_patterns(
+    profile: Option<String>,
+    command: &mut Command,
+) -> Result<(), errors::Error> {
+    use dotfiles::DotfileType::{Configs, Hooks, Secrets};
+
+    let (groups, dtypes) = match command {
+        Command::Add { groups, .. } => (groups, &[Configs, Secrets][..]),
+        Command::Status {
+            groups: Some(groups),
+            ..
+        } => (groups, &[Configs][..]),
+        Command::Set { groups, .. } | Command::Check { groups, .. } => {
+            (groups, &[Configs, Hooks, Secrets][..])
+        }
+        Command::Unset { groups, .. } | Command::Rm { groups, .. } => {
+            (groups, &[Configs, Hooks][..])
+        }
+        Command::Install { groups, .. } => (groups, &[Hooks][..]),
+        Command::Decrypt { groups, .. }
+        | Command::Secrets(SecretsCmd::Status { groups, .. })
+        | Command::Secrets(SecretsCmd::Migrate { groups, .. })
+        | Command::Secrets(SecretsCmd::Audit { groups, .. }) => (groups, &[Secrets][..]),
+        _ => return Ok(()),
+    };
+
+    let bundled = config::get().expand_bundles(groups)?;
+    *groups = dotfiles::expand_groups(profile, dtypes, &bundled);
+    Ok(())
+}
+
+/// Returns true if the command deploys, removes or changes the machine's state
+fn changes_deployment(command: &Command) -> bool {
+    matches!(
+        command,
+        Command::Set { .. }
+            | Command::Unset { .. }
+            | Command::Add { .. }
+            | Command::Rm { .. }
+            | Command::Decrypt { .. }
+            | Command::Status { fix: true, .. }
+            | Command::Diff {
+                overwrite: true,
+                ..
+            }
+            | Command::Diff {
+                pull_back: true,
+                ..
+            }
+            | Command::Try { .. }
+            | Command::Hold { .. }
+            | Command::Unhold { .. }
+            | Command::Note { .. }
+            | Command::Resolve { .. }
+            | Command::Clean { .. }
+            | Command::Import { .. }
+            | Command::FromStow { .. }
+            | Command::Encrypt { .. }
+            | Command::Push { .. }
+            | Command::Pop { .. }
+            | Command::Secrets(SecretsCmd::Rekey { .. })
+            | Command::Secrets(SecretsCmd::Migrate { .. })
+            | Command::Secrets(SecretsCmd::Audit { fix: true, .. })
+    ) || matches!(command, Command::Stash { cmd, .. } if !matches!(cmd, Some(StashCmd::List)))
+        || matches!(command, Command::Backups { cmd } if !matches!(cmd, BackupsCmd::List))
+}
+
+/// Parses how long `--wait` waits for, e.g. "30s" or "5m"
+fn parse_wait(time: &str) -> Result<Duration, String> {
+    config::parse_age(time).map(Duration::from_secs)
+}
+
+/// Prints the logs of `-v` and up on stderr, progress bars are left out so they don't get in the way
+fn init_logging(verbosity: u8) {
+    let level = match verbosity {
+        1 => tracing::Level::INFO,
+        2 => tracing::Level::DEBUG,
+        _ => tracing::Level::TRACE,
+    };
+
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .with_target(false)
+        .without_time()
+        .init();
+    progress::disable();
+}
+
+/// The exit code for each way that a command can fail, scripts rely on them so they never change
+fn exit_code(err: TuckrError) -> ExitCode {
+    ExitCode::from(match err {
+        TuckrError::Failed => 1,
+        TuckrError::CouldntFindDotfiles => 2,
+        TuckrError::NoSetupFolder => 3,
+        TuckrError::NoSuchFileOrDir => 4,
+        TuckrError::EncryptionFailed => 5,
+        TuckrError::DecryptionFailed => 6,
+        TuckrError::InvalidConfig => 7,
+        TuckrError::RepoHasErrors => 8,
+        TuckrError::RepoHasWarnings => 9,
+        TuckrError::PreflightFailed => 10,
+        TuckrError::Locked => 11,
+        TuckrError::Conflicts => 12,
+        TuckrError::HookFailed => 13,
+        TuckrError::PartialDeploy => 14,
+        // follows the shell's convention of 128 + SIGINT
+        TuckrError::Cancelled => 130,
+        TuckrError::Exited(code) => code,
+    })
 }
 
 fn main() -> ExitCode {
-    let cli = Cli::parse();
+    // commands and flags that were renamed are replaced with their new names before parsing
+    let mut args: Vec<_> = std::env::args_os().collect();
+    let valued_options: Vec<_> = Cli::command()
+        .get_arguments()
+        .filter(|arg| arg.get_action().takes_values())
+        .flat_map(|arg| {
+            let long = arg.get_long().map(|long| format!("--{long}"));
+            let short = arg.get_short().map(|short| format!("-{short}"));
+            long.into_iter().chain(short)
+        })
+        .collect();
+    let deprecated_usage = deprecated::rewrite(&mut args, &valued_options);
+
+    let matches = Cli::command().get_matches_from(args);
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    // the subcommand's name is kept for the statistics
+    let command = matches.subcommand_name().unwrap_or_default().to_string();
+    let dry_run = cli.dry_run;
+
+    let external_locale_errors = i18n::load();
+    i18n::set_language(cli.lang.take());
+    cancel::install_handler();
+    secrets::remove_stale_hooks();
+
+    // errors are printed the way --format asks for from the start
+    table::set_format(cli.format);
+    for err in external_locale_errors {
+        errors::print(err);
+    }
+
+    if let Err(err) = deprecated::report(&deprecated_usage, cli.strict_cli) {
+        return exit_code(err);
+    }
+
+    if let Err(err) = config::load() {
+        errors::print(err);
+        return exit_code(TuckrError::InvalidConfig);
+    }
+    config::get().colors.apply();
+
+    if let Some(file) = cli.password_file.take() {
+        secrets::set_password_file(file);
+    }
+
+    if let Some(dir) = cli.target.take()
+        && let Err(err) = dotfiles::set_target_dir(&dir)
+    {
+        errors::print(err);
+        return exit_code(TuckrError::NoSuchFileOrDir);
+    }
+
+    if let Some(jobs) = cli.jobs {
+        fileops::set_jobs(jobs);
+    }
+
+    if cli.no_rollback {
+        transaction::disable_rollback();
+    }
+
+    if cli.show_hook_output {
+        hooklog::show_output();
+    }
+
+    if cli.quiet {
+        interactive::set_quiet();
+    }
+
+    // dry runs list every change instead
+    if dry_run {
+        progress::disable();
+    }
+
+    if cli.yes {
+        interactive::assume_yes();
+    }
+
+    if cli.verbose > 0 {
+        init_logging(cli.verbose);
+    }
+
+    if let Err(err) = expand_group_patterns(cli.profile.clone(), &mut cli.command) {
+        errors::print(err);
+        return exit_code(TuckrError::NoSuchFileOrDir);
+    }
+
+    // hooks that run tuckr share this lock instead of waiting for it
+    let _lock = match !dry_run && changes_deployment(&cli.command) {
+        true => match lock::acquire(cli.wait) {
+            Ok(lock) => Some(lock),
+            Err(err) => {
+                errors::print(err);
+                return exit_code(TuckrError::Locked);
+            }
+        },
+        false => None,
+    };
 
-    rust_i18n::set_locale(sys_locale::get_locale().unwrap_or_default().as_str());
+    // kept for the summary of the tuckr whose hook ran this one
+    let groups = match &cli.command {
+        Command::Add { groups, .. }
+        | Command::Set { groups, .. }
+        | Command::Rm { groups, .. }
+        | Command::Unset { groups, .. } => groups.clone(),
+        _ => Vec::new(),
+    };
 
-    let exit_code = match cli.command {
+    let started = Instant::now();
+    let result = match cli.command {
         Command::Set {
             groups,
             exclude,
@@ -202,19 +1074,68 @@ fn main() -> ExitCode {
             adopt,
             assume_yes,
             only_files,
-        } => hooks::set_cmd(
-            cli.profile,
-            cli.dry_run,
-            only_files,
-            &groups,
-            &exclude,
-            force,
-            adopt,
-            assume_yes,
-        ),
+            copy,
+            audit_log,
+            no_secrets,
+            skip_preflight,
+            keep_going,
+            interactive,
+            relative,
+        } => {
+            if interactive {
+                interactive::ask_on_conflict();
+            }
+            if relative {
+                fileops::use_relative_symlinks();
+            }
+
+            let exclude = config::with_excluded_groups(&groups, &exclude);
+
+            // fails before any hook runs or anything gets deployed
+            let preflight = match skip_preflight {
+                true => Ok(()),
+                false => preflight::check_cmd(&groups, &exclude),
+            };
+
+            preflight.and_then(|_| {
+                // groups that only have secrets have nothing to symlink nor hooks to run
+                let config_groups: Vec<_> = groups
+                    .iter()
+                    .filter(|group| {
+                        no_secrets
+                            || !secrets::only_has_secrets(cli.profile.clone(), group)
+                            || hooks::has_hooks(cli.profile.clone(), group)
+                    })
+                    .cloned()
+                    .collect();
 
-        Command::Unset { groups, exclude } => {
-            hooks::unset_cmd(cli.profile, cli.dry_run, &groups, &exclude)
+                let set = match config_groups.is_empty() {
+                    true => Ok(()),
+                    false => hooks::set_cmd(
+                        cli.profile.clone(),
+                        cli.dry_run,
+                        only_files,
+                        copy,
+                        &config_groups,
+                        &exclude,
+                        force,
+                        adopt,
+                        assume_yes,
+                        audit_log,
+                        keep_going,
+                    ),
+                };
+
+                // all of the secrets are decrypted at once so that the password is only asked for once
+                let decrypted = match no_secrets || cancel::is_cancelled() {
+                    true => Ok(()),
+                    false => {
+                        secrets::deploy_cmd(cli.profile, cli.dry_run, &groups, &exclude, force)
+                    }
+                };
+
+                set.and(decrypted)
+            })
         }
 
         Command::Add {
@@ -224,48 +1145,453 @@ fn main() -> ExitCode {
             adopt,
             assume_yes,
             only_files,
-        } => symlinks::add_cmd(
+            copy,
+            into,
+            commit,
+            no_secrets,
+            safe,
+            interactive,
+            relative,
+        } => {
+            if safe {
+                safe::enable();
+            }
+            if interactive {
+                interactive::ask_on_conflict();
+            }
+            if relative {
+                fileops::use_relative_symlinks();
+            }
+
+            if let Some(dir) = into
+                && let Err(err) = dotfiles::set_sandbox_dir(&dir, dry_run)
+            {
+                errors::print(err);
+                return exit_code(TuckrError::NoSuchFileOrDir);
+            }
+
+            let exclude = config::with_excluded_groups(&groups, &exclude);
+
+            // groups that only have secrets have nothing to symlink
+            let config_groups: Vec<_> = groups
+                .iter()
+                .filter(|group| {
+                    no_secrets || !secrets::only_has_secrets(cli.profile.clone(), group)
+                })
+                .cloned()
+                .collect();
+
+            let added = match config_groups.is_empty() {
+                true => Ok(()),
+                false => symlinks::add_cmd(
+                    cli.profile.clone(),
+                    cli.dry_run,
+                    only_files,
+                    copy,
+                    &config_groups,
+                    &exclude,
+                    force,
+                    adopt,
+                    assume_yes,
+                ),
+            };
+
+            // secrets are deployed even if some of the groups had conflicts
+            let decrypted = match no_secrets || cancel::is_cancelled() {
+                true => Ok(()),
+                false => {
+                    secrets::deploy_cmd(cli.profile.clone(), cli.dry_run, &groups, &exclude, force)
+                }
+            };
+
+            // what was adopted is committed even if some secrets had conflicts
+            added
+                .and_then(|_| {
+                    if !adopt {
+                        return Ok(());
+                    }
+
+                    let paths = if groups.iter().any(|group| group == "*") {
+                        vec!["Configs".into()]
+                    } else {
+                        groups
+                            .iter()
+                            .map(|group| format!("Configs/{group}"))
+                            .collect()
+                    };
+
+                    history::auto_commit(
+                        cli.profile,
+                        cli.dry_run,
+                        commit,
+                        &paths,
+                        &format!("Adopt existing dotfiles into {}", groups.join(", ")),
+                    )
+                })
+                .and(decrypted)
+        }
+
+        Command::Rm {
+            groups,
+            exclude,
+            all,
+            assume_yes,
+            no_hooks,
+        }
+        | Command::Unset {
+            groups,
+            exclude,
+            all,
+            assume_yes,
+            no_hooks,
+        } => match all {
+            // groups excluded by the config are removed too since they're only deployed when asked for
+            true => hooks::unset_all_cmd(cli.profile, cli.dry_run, &exclude, assume_yes, !no_hooks),
+            false => hooks::unset_cmd(
+                cli.profile,
+                cli.dry_run,
+                &groups,
+                &config::with_excluded_groups(&groups, &exclude),
+                !no_hooks,
+            ),
+        },
+        Command::Status {
+            groups,
+            at: None,
+            exclude,
+            fix,
+            no_cache,
+        } => symlinks::status_cmd(cli.profile, cli.dry_run, groups, &exclude, fix, no_cache),
+        Command::Status {
+            groups,
+            at: Some(rev),
+            ..
+        } => history::status_at_cmd(cli.profile, &rev, &groups.unwrap_or_default()),
+        Command::Encrypt {
+            group,
+            dotfiles,
+            archive,
+            hook,
+            commit,
+        } => secrets::encrypt_cmd(
+            cli.profile.clone(),
+            cli.dry_run,
+            &group,
+            &dotfiles,
+            archive,
+            hook,
+        )
+        .and_then(|_| {
+            // an archive that was renamed after switching backends is committed along with it
+            let encrypted = match archive {
+                true => format!(":(glob)Secrets/{group}.tar.*"),
+                false => format!("Secrets/{group}"),
+            };
+            history::auto_commit(
+                cli.profile,
+                cli.dry_run,
+                commit,
+                &[encrypted],
+                &format!("Encrypt {} into {group}", file_names(&dotfiles)),
+            )
+        }),
+        Command::Decrypt { groups, exclude } => secrets::decrypt_cmd(
+            cli.profile,
+            cli.dry_run,
+            &groups,
+            &config::with_excluded_groups(&groups, &exclude),
+        ),
+        Command::Bisect { group, good, bad } => {
+            history::bisect_cmd(cli.profile, &group, &good, &bad)
+        }
+        Command::Diff {
+            groups,
+            stat,
+            overwrite,
+            pull_back,
+        } => {
+            let resolution = match (overwrite, pull_back) {
+                (true, _) => Some(diff::DriftResolution::Overwrite),
+                (_, true) => Some(diff::DriftResolution::PullBack),
+                _ => None,
+            };
+            diff::diff_cmd(cli.profile, cli.dry_run, groups, stat, resolution)
+        }
+        Command::Resolve { path, tool } => diff::resolve_cmd(cli.profile, cli.dry_run, &path, tool),
+        Command::Clone { url } => history::clone_cmd(cli.profile, cli.dry_run, &url),
+        Command::Sync => history::sync_cmd(cli.profile, cli.dry_run),
+        Command::Daemon {
+            auto_update,
+            grace_period,
+            run_hooks,
+        } => daemon::daemon_cmd(
+            cli.profile,
+            cli.dry_run,
+            auto_update,
+            grace_period,
+            run_hooks,
+        ),
+        Command::Watch {
+            groups,
+            exclude,
+            debounce,
+            once,
+        } => watch::watch_cmd(
             cli.profile,
             cli.dry_run,
-            only_files,
             &groups,
             &exclude,
-            force,
-            adopt,
-            assume_yes,
+            Duration::from_millis(debounce),
+            once,
         ),
-
-        Command::Rm { groups, exclude } => {
-            symlinks::remove_cmd(cli.profile, cli.dry_run, &groups, &exclude)
+        Command::PushRemote { message } => {
+            history::push_remote_cmd(cli.profile, cli.dry_run, message)
+        }
+        Command::Try {
+            experiment,
+            end: true,
+        } => experiments::end_cmd(cli.profile, cli.dry_run, experiment),
+        Command::Try {
+            experiment,
+            end: false,
+        } => experiments::try_cmd(cli.profile, cli.dry_run, &experiment.unwrap()),
+        Command::Init {
+            git,
+            config,
+            gitignore,
+        } => fileops::init_cmd(
+            cli.profile,
+            cli.dry_run,
+            fileops::InitOptions {
+                git,
+                config,
+                gitignore,
+            },
+        ),
+        Command::New { group } => fileops::new_group_cmd(cli.profile, cli.dry_run, &group),
+        Command::ToStow {
+            dir,
+            chezmoi,
+            exclude,
+        } => export::to_stow_cmd(cli.profile, cli.dry_run, &dir, chezmoi, &exclude),
+        Command::Export { file, no_secrets } => {
+            snapshot::export_cmd(cli.profile, cli.dry_run, &file, no_secrets)
         }
-        Command::Status { groups } => symlinks::status_cmd(cli.profile, groups),
-        Command::Encrypt { group, dotfiles } => {
-            secrets::encrypt_cmd(cli.profile, cli.dry_run, &group, &dotfiles)
+        Command::Import { file } => snapshot::import_cmd(cli.profile, cli.dry_run, &file),
+        Command::FromChezmoi { dir, group } => {
+            import::from_chezmoi_cmd(cli.profile, cli.dry_run, dir, group)
         }
-        Command::Decrypt { groups, exclude } => {
-            secrets::decrypt_cmd(cli.profile, cli.dry_run, &groups, &exclude)
+        Command::FromYadm { repo, group } => {
+            import::from_yadm_cmd(cli.profile, cli.dry_run, repo, group)
         }
-        Command::Init => fileops::init_cmd(cli.profile, cli.dry_run),
+        Command::FromStow {
+            dir,
+            dotfiles,
+            commit,
+        } => import::from_stow_cmd(cli.profile.clone(), cli.dry_run, dir, dotfiles).and_then(
+            |groups| {
+                let paths: Vec<_> = groups
+                    .iter()
+                    .map(|group| format!("Configs/{group}"))
+                    .collect();
+                history::auto_commit(
+                    cli.profile,
+                    cli.dry_run,
+                    commit,
+                    &paths,
+                    &format!("Import {} from stow", groups.join(", ")),
+                )
+            },
+        ),
 
         Command::Ls(ls_type) => match ls_type {
             ListType::Profiles => fileops::ls_profiles_cmd(),
             ListType::Secrets => fileops::ls_secrets_cmd(cli.profile),
             ListType::Hooks => fileops::ls_hooks_cmd(cli.profile),
+            ListType::Groups {
+                deployed,
+                not_deployed,
+                invalid_target,
+            } => fileops::ls_groups_cmd(cli.profile, deployed, not_deployed, invalid_target),
+        },
+
+        Command::Profile(profile_cmd) => match profile_cmd {
+            ProfileCmd::List => fileops::ls_profiles_cmd(),
+            ProfileCmd::Diff { profile, other, .. } => {
+                profiles::diff_cmd(&profile, other.as_deref())
+            }
         },
 
+        Command::Secrets(SecretsCmd::Status { groups, exclude }) => secrets::status_cmd(
+            cli.profile,
+            &groups,
+            &config::with_excluded_groups(&groups, &exclude),
+        ),
+        Command::Secrets(SecretsCmd::Migrate { groups, exclude }) => secrets::migrate_cmd(
+            cli.profile,
+            cli.dry_run,
+            &groups,
+            &config::with_excluded_groups(&groups, &exclude),
+        ),
+        Command::Secrets(SecretsCmd::Rekey { commit }) => {
+            secrets::rekey_cmd(cli.profile.clone(), cli.dry_run).and_then(|_| {
+                history::auto_commit(
+                    cli.profile,
+                    cli.dry_run,
+                    commit,
+                    &["Secrets".to_string()],
+                    "Encrypt the secrets with a new password",
+                )
+            })
+        }
+        Command::Secrets(SecretsCmd::Audit {
+            groups,
+            exclude,
+            fix,
+        }) => secrets::audit_cmd(
+            cli.profile,
+            cli.dry_run,
+            &groups,
+            &config::with_excluded_groups(&groups, &exclude),
+            fix,
+        ),
+
         Command::Push {
             group,
             files,
             assume_yes,
-        } => fileops::push_cmd(cli.profile, cli.dry_run, group, &files, assume_yes),
-        Command::Pop { groups, assume_yes } => {
-            fileops::pop_cmd(cli.profile, cli.dry_run, &groups, assume_yes)
+            commit,
+        } => fileops::push_cmd(
+            cli.profile.clone(),
+            cli.dry_run,
+            group.clone(),
+            &files,
+            assume_yes,
+        )
+        .and_then(|_| {
+            history::auto_commit(
+                cli.profile,
+                cli.dry_run,
+                commit,
+                &[format!("Configs/{group}")],
+                &format!("Add {} to {group}", file_names(&files)),
+            )
+        }),
+        Command::Edit { group, file } => fileops::edit_cmd(cli.profile, cli.dry_run, &group, file),
+        Command::Pop {
+            groups,
+            assume_yes,
+            commit,
+        } => {
+            fileops::pop_cmd(cli.profile.clone(), cli.dry_run, &groups, assume_yes).and_then(|_| {
+                history::auto_commit(
+                    cli.profile,
+                    cli.dry_run,
+                    commit,
+                    &groups
+                        .iter()
+                        .map(|group| format!("Configs/{group}"))
+                        .collect::<Vec<_>>(),
+                    &format!("Remove {}", groups.join(", ")),
+                )
+            })
+        }
+        Command::Hold { groups } => state::hold_cmd(cli.profile, cli.dry_run, &groups, true),
+        Command::Unhold { groups } => state::hold_cmd(cli.profile, cli.dry_run, &groups, false),
+        Command::Note { group, note, clear } => {
+            state::note_cmd(cli.profile, cli.dry_run, &group, &note, clear)
         }
+        Command::Stash { cmd, groups } => match cmd {
+            Some(StashCmd::Pop) => stash::pop_cmd(cli.profile, cli.dry_run),
+            Some(StashCmd::List) => stash::list_cmd(cli.profile),
+            None => stash::stash_cmd(cli.profile, cli.dry_run, &groups),
+        },
+        Command::Backups { cmd } => match cmd {
+            BackupsCmd::List => backups::list_cmd(cli.profile),
+            BackupsCmd::Prune {
+                keep_last,
+                max_age,
+                max_size,
+            } => backups::prune_cmd(
+                cli.profile,
+                cli.dry_run,
+                config::BackupsConfig {
+                    keep_last,
+                    max_age,
+                    max_size,
+                },
+            ),
+            BackupsCmd::Restore { backup } => {
+                backups::restore_cmd(cli.profile, cli.dry_run, backup)
+            }
+        },
         Command::GroupIs { files } => fileops::groupis_cmd(cli.profile, &files),
+        Command::Which { paths } => which::which_cmd(cli.profile, &paths),
+        Command::VerifyRepo { staged } => verify::verify_repo_cmd(cli.profile, staged),
+        Command::Ui => ui::ui_cmd(cli.profile, cli.dry_run),
+        Command::Clean { depth } => clean::clean_cmd(cli.profile, cli.dry_run, depth),
+        Command::Check {
+            groups,
+            exclude,
+            force,
+            adopt,
+            only_files,
+            copy,
+            no_secrets,
+        } => {
+            let exclude = config::with_excluded_groups(&groups, &exclude);
+            plan::check_cmd(
+                cli.profile,
+                only_files,
+                copy,
+                &groups,
+                &exclude,
+                force,
+                adopt,
+                no_secrets,
+            )
+        }
+        Command::Install { groups, manager } => {
+            packages::install_cmd(cli.profile, cli.dry_run, &groups, manager)
+        }
+        Command::Doctor => verify::doctor_cmd(cli.profile),
+        Command::Stats { weeks } => stats::stats_cmd(weeks),
+        Command::Errors { cmd } => match cmd {
+            ErrorsCmd::List => errors::list_cmd(),
+        },
+        Command::ApplyPlan { file } => escalate::apply_plan_cmd(&file),
+        Command::Elevate { args } => escalate::elevate_cmd(cli.dry_run, args),
+        Command::RunElevated { file } => escalate::run_elevated_cmd(&file),
+        Command::Completions { shell } => completions::completions_cmd(Cli::command(), shell),
+        Command::Complete { names } => completions::complete_cmd(cli.profile, names),
     };
 
-    match exit_code {
+    // dry runs don't change anything, looking at the statistics shouldn't skew them, plans and
+    // elevated commands count as part of the tuckr that ran them and completions run on every tab
+    if !dry_run
+        && !matches!(
+            command.as_str(),
+            "stats" | "apply-plan" | "run-elevated" | "__complete"
+        )
+    {
+        stats::record(&command, started, result.is_ok());
+    }
+
+    if !dry_run && let Err(err) = nested::record(&command, groups, result.is_ok()) {
+        errors::print(err);
+    }
+    nested::end_session();
+
+    // `tuckr elevate` runs the command again as administrator
+    if result.is_err()
+        && escalate::needs_elevation()
+        && let Err(err) = escalate::save_failed_command()
+    {
+        errors::print(err);
+    }
+
+    match result {
         Ok(_) => ExitCode::SUCCESS,
-        Err(e) => e,
+        Err(err) => exit_code(err),
     }
 }
@@ -0,0 +1,174 @@
+//! Lets hooks run tuckr themselves
+//!
+//! Hooks are run with $TUCKR_NESTED set to the session file of the tuckr running them, e.g. for a
+//! hook that calls `tuckr add othergroup`. A tuckr that finds it set is nested:
+//! - it shares the lock of the outer tuckr instead of failing because it's taken
+//! - it leaves out the headers and summaries that the outer tuckr already prints
+//! - it doesn't ask again whether to override conflicts if the outer tuckr's user already agreed to
+//! - what it did is appended to the session file, so that the outer tuckr can add it to its summary
+//!   and audit log
+//!
+//! `tuckr daemon` runs tuckr the same way to find out which hooks failed.
+
+use crate::audit::HookRun;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const SESSION_VAR: &str = "TUCKR_NESTED";
+/// set when the outer tuckr's user agreed to override or adopt conflicts
+const CONFIRMED_VAR: &str = "TUCKR_CONFIRMED";
+
+static CONFIRMED: AtomicBool = AtomicBool::new(false);
+/// the hooks run by a nested `tuckr set`
+static HOOKS: Mutex<Vec<HookRun>> = Mutex::new(Vec::new());
+
+/// A tuckr command that was run by a hook
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Operation {
+    pub command: String,
+    pub groups: Vec<String>,
+    pub succeeded: bool,
+    #[serde(default)]
+    pub hooks: Vec<HookRun>,
+}
+
+/// Returns the outer tuckr's session file if this tuckr was run by a hook
+fn parent_session() -> Option<PathBuf> {
+    env::var_os(SESSION_VAR)
+        .filter(|session| !session.is_empty())
+        .map(PathBuf::from)
+}
+
+pub fn is_nested() -> bool {
+    parent_session().is_some()
+}
+
+/// Returns the session file that's handed to hooks, nested tuckrs hand down the outer tuckr's one
+///
+/// It's named after the pid of the outermost tuckr, which is the one holding the lock
+fn session() -> PathBuf {
+    parent_session().unwrap_or_else(|| {
+        dirs::cache_dir()
+            .unwrap_or_else(env::temp_dir)
+            .join("tuckr")
+            .join("nested")
+            .join(format!("{}.jsonl", std::process::id()))
+    })
+}
+
+fn owner_of(session: &Path) -> Option<u32> {
+    session.file_stem()?.to_str()?.parse().ok()
+}
+
+/// Returns the pid of the outermost tuckr if this one is nested
+pub fn session_owner() -> Option<u32> {
+    owner_of(&parent_session()?)
+}
+
+/// Notes that the user agreed to override or adopt conflicts, so that hooks don't ask again
+pub fn confirm() {
+    CONFIRMED.store(true, Ordering::SeqCst);
+}
+
+/// Returns true if the outer tuckr's user already agreed to override or adopt conflicts
+pub fn is_confirmed() -> bool {
+    env::var_os(CONFIRMED_VAR).is_some()
+}
+
+/// Sets up a hook, or a tuckr run by the daemon, so that the tuckrs it runs know that they're nested
+pub fn prepare_hook(command: &mut Command) {
+    command.env(SESSION_VAR, session());
+    if CONFIRMED.load(Ordering::SeqCst) {
+        command.env(CONFIRMED_VAR, "1");
+    }
+}
+
+/// Keeps the hooks a nested `tuckr set` ran, they end up in the outer tuckr's audit log
+pub fn add_hooks(hooks: Vec<HookRun>) {
+    HOOKS.lock().unwrap().extend(hooks);
+}
+
+/// Appends what this tuckr did to the outer tuckr's session file, does nothing if it isn't nested
+pub fn record(command: &str, groups: Vec<String>, succeeded: bool) -> Result<(), String> {
+    let Some(session) = parent_session() else {
+        return Ok(());
+    };
+
+    let operation = Operation {
+        command: command.into(),
+        groups,
+        succeeded,
+        hooks: std::mem::take(&mut *HOOKS.lock().unwrap()),
+    };
+    let mut line = serde_json::to_string(&operation).map_err(|e| e.to_string())?;
+    line.push('\n');
+
+    fs::create_dir_all(session.parent().unwrap())
+        .and_then(|_| {
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&session)
+                .and_then(|mut file| file.write_all(line.as_bytes()))
+        })
+        .map_err(|e| format!("{}: {e}", session.display()))
+}
+
+/// Parses a session file, lines that can't be parsed are skipped
+fn parse_operations(session: &str) -> Vec<Operation> {
+    session
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Returns what the hooks ran since the last call, only the outermost tuckr collects them
+pub fn take_operations() -> Vec<Operation> {
+    if is_nested() {
+        return Vec::new();
+    }
+
+    let session = session();
+    let Ok(operations) = fs::read_to_string(&session) else {
+        return Vec::new();
+    };
+    _ = fs::remove_file(&session);
+
+    parse_operations(&operations)
+}
+
+/// Removes what's left of the session file once the outermost tuckr is done
+pub fn end_session() {
+    if !is_nested() {
+        _ = fs::remove_file(session());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sessions() {
+        assert_eq!(owner_of(Path::new("/tmp/tuckr/nested/42.jsonl")), Some(42));
+        assert_eq!(owner_of(Path::new("/tmp/tuckr/nested/session")), None);
+
+        let operations = parse_operations(concat!(
+            r#"{"command":"add","groups":["zsh"],"succeeded":true}"#,
+            "\nnot json\n",
+            r#"{"command":"set","groups":["nvim"],"succeeded":false,"hooks":[{"group":"nvim","kind":"post","script":"post.sh","sha256":null,"exit_code":1,"duration_ms":3}]}"#,
+        ));
+
+        assert_eq!(operations.len(), 2);
+        assert_eq!(operations[0].groups, ["zsh"]);
+        assert!(operations[0].hooks.is_empty());
+        assert!(!operations[1].succeeded);
+        assert_eq!(operations[1].hooks[0].exit_code, Some(1));
+    }
+}
@@ -0,0 +1,396 @@
+//! Installs the system packages that groups need
+//!
+//! A group lists the packages it needs in `Hooks/<group>/packages.toml`, with the names they go by
+//! for the package managers that call them something else:
+//!
+//! ```toml
+//! packages = ["ripgrep", "fd", "neovim"]
+//!
+//! [names.fd]
+//! apt = "fd-find"
+//! winget = "sharkdp.fd"
+//!
+//! # an empty name leaves the package out, e.g. when the package manager doesn't have it
+//! [names.neovim]
+//! winget = "Neovim.Neovim"
+//! pacman = ""
+//! ```
+//!
+//! `tuckr install <group>` finds the package manager and installs them with it, or prints the
+//! command that would when the package manager picked with `--manager` isn't on this machine.
+
+use crate::dotfiles::{self, DotfileType};
+use crate::error;
+use crate::errors::{self, TuckrError};
+use crate::escalate;
+use crate::preflight;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::Stdout;
+use rust_i18n::t;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+pub const PACKAGES_FILE: &str = "packages.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Packages {
+    /// the packages the group needs, named the way most package managers call them
+    packages: Vec<String>,
+
+    /// what the packages are called by the package managers that call them something else
+    names: BTreeMap<String, Names>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Names {
+    apt: Option<String>,
+    dnf: Option<String>,
+    pacman: Option<String>,
+    brew: Option<String>,
+    winget: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Manager {
+    Apt,
+    Dnf,
+    Pacman,
+    Brew,
+    Winget,
+}
+
+impl Manager {
+    /// the order they're looked for in, the distros' own package managers come before brew
+    const ALL: [Manager; 5] = [
+        Manager::Apt,
+        Manager::Dnf,
+        Manager::Pacman,
+        Manager::Brew,
+        Manager::Winget,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Manager::Apt => "apt",
+            Manager::Dnf => "dnf",
+            Manager::Pacman => "pacman",
+            Manager::Brew => "brew",
+            Manager::Winget => "winget",
+        }
+    }
+
+    fn program(self) -> &'static str {
+        match self {
+            Manager::Apt => "apt-get",
+            _ => self.name(),
+        }
+    }
+
+    fn install_args(self) -> &'static [&'static str] {
+        match self {
+            Manager::Apt | Manager::Dnf | Manager::Brew => &["install"],
+            Manager::Pacman => &["-S", "--needed"],
+            Manager::Winget => &["install", "--exact", "--id"],
+        }
+    }
+
+    /// brew refuses to run as root and winget installs for the user
+    fn needs_root(self) -> bool {
+        matches!(self, Manager::Apt | Manager::Dnf | Manager::Pacman)
+    }
+
+    /// Returns the first package manager that's on this machine
+    pub fn detect() -> Option<Manager> {
+        Manager::ALL
+            .into_iter()
+            .find(|manager| preflight::command_exists(manager.program()))
+    }
+
+    /// Returns the commands that install the packages, winget only installs one package at a time
+    fn install_commands(self, packages: &[String]) -> Vec<Vec<String>> {
+        let mut base = Vec::new();
+        if self.needs_root() && !escalate::is_root() {
+            base.extend(escalate::find_program());
+        }
+        base.push(self.program().to_string());
+        base.extend(self.install_args().iter().map(|arg| arg.to_string()));
+
+        let batches = match self {
+            Manager::Winget => packages.chunks(1).collect(),
+            _ => vec![packages],
+        };
+        batches
+            .into_iter()
+            .map(|batch| base.iter().chain(batch).cloned().collect())
+            .collect()
+    }
+}
+
+impl std::str::FromStr for Manager {
+    type Err = errors::Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Manager::ALL
+            .into_iter()
+            .find(|manager| manager.name() == name)
+            .ok_or_else(|| error!("unknown_package_manager", name = name))
+    }
+}
+
+impl Packages {
+    /// Returns the names of the packages for `manager`, leaving out the ones it doesn't have
+    fn resolve(&self, manager: Manager) -> Vec<String> {
+        self.packages
+            .iter()
+            .filter_map(|package| {
+                let names = self.names.get(package);
+                let name = names.and_then(|names| match manager {
+                    Manager::Apt => names.apt.as_ref(),
+                    Manager::Dnf => names.dnf.as_ref(),
+                    Manager::Pacman => names.pacman.as_ref(),
+                    Manager::Brew => names.brew.as_ref(),
+                    Manager::Winget => names.winget.as_ref(),
+                });
+
+                match name {
+                    Some(name) if name.is_empty() => None,
+                    Some(name) => Some(name.clone()),
+                    None => Some(package.clone()),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Reads a group's packages.toml, None if it doesn't have one
+fn read_packages(group_dir: &Path) -> Result<Option<Packages>, String> {
+    let file = group_dir.join(PACKAGES_FILE);
+    let Ok(contents) = fs::read_to_string(&file) else {
+        return Ok(None);
+    };
+
+    toml::from_str(&contents)
+        .map(Some)
+        .map_err(|err| format!("{}: {err}", file.display()))
+}
+
+/// Returns the packages of every group, each of them once
+fn collect_packages(
+    profile: Option<String>,
+    groups: &[String],
+    manager: Manager,
+) -> Result<Vec<String>, String> {
+    let mut installed = Vec::new();
+    for group in groups {
+        let packages = dotfiles::find_group_dir(profile.clone(), DotfileType::Hooks, group)
+            .map(|dir| read_packages(&dir))
+            .transpose()?
+            .flatten();
+
+        let Some(packages) = packages else {
+            println!(
+                "{}",
+                t!("info.group_has_no_packages", group = group)
+                    .if_supports_color(Stdout, |t| t.yellow())
+            );
+            continue;
+        };
+
+        for package in packages.resolve(manager) {
+            if !installed.contains(&package) {
+                installed.push(package);
+            }
+        }
+    }
+
+    Ok(installed)
+}
+
+/// Returns every group that has a packages.toml
+fn groups_with_packages(profile: Option<String>) -> Vec<String> {
+    let mut groups: Vec<String> = dotfiles::get_group_dirs(profile, DotfileType::Hooks)
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .flatten()
+        .filter(|group| group.path().join(PACKAGES_FILE).is_file())
+        .filter_map(|group| group.file_name().into_string().ok())
+        .filter(|group| dotfiles::group_is_valid_target(group))
+        .collect();
+    groups.sort();
+    groups.dedup();
+    groups
+}
+
+/// Installs the packages that the groups need with the package manager of this machine
+///
+/// manager: the package manager to install them with instead of the one that's found,
+/// the command is only printed if it isn't on this machine
+pub fn install_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    groups: &[String],
+    manager: Option<Manager>,
+) -> Result<(), TuckrError> {
+    if let Err(err) = dotfiles::get_dotfiles_path(profile.clone()) {
+        errors::print(err);
+        return Err(TuckrError::CouldntFindDotfiles);
+    }
+
+    let Some(manager) = manager.or_else(Manager::detect) else {
+        errors::print(error!("no_package_manager"));
+        return Err(TuckrError::Failed);
+    };
+
+    let groups = match groups.iter().any(|group| group == "*") {
+        true => groups_with_packages(profile.clone()),
+        false => groups.to_vec(),
+    };
+
+    let packages = match collect_packages(profile, &groups, manager) {
+        Ok(packages) => packages,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::Failed);
+        }
+    };
+
+    if packages.is_empty() {
+        println!(
+            "{}",
+            t!("info.no_packages_to_install").if_supports_color(Stdout, |t| t.green())
+        );
+        return Ok(());
+    }
+
+    let commands = manager.install_commands(&packages);
+    let available = preflight::command_exists(manager.program());
+    if dry_run || !available {
+        if !available {
+            println!(
+                "{}",
+                t!("info.install_packages_with", manager = manager.name())
+                    .if_supports_color(Stdout, |t| t.yellow())
+            );
+        }
+        for command in &commands {
+            println!("$ {}", command.join(" "));
+        }
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        t!(
+            "info.installing_packages",
+            count = packages.len(),
+            manager = manager.name()
+        )
+        .if_supports_color(Stdout, |t| t.yellow())
+    );
+
+    for command in &commands {
+        println!(
+            "$ {}",
+            command.join(" ").if_supports_color(Stdout, |t| t.dimmed())
+        );
+        let status = Command::new(&command[0]).args(&command[1..]).status();
+        match status {
+            Ok(status) if status.success() => (),
+            Ok(_) => {
+                errors::print(error!(
+                    "failed_to_install_packages",
+                    manager = manager.name()
+                ));
+                return Err(TuckrError::Failed);
+            }
+            Err(err) => {
+                errors::print(format!("{}: {err}", command[0]));
+                return Err(TuckrError::Failed);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_package_names() {
+        let packages: Packages = toml::from_str(
+            r#"
+            packages = ["ripgrep", "fd", "neovim"]
+
+            [names.fd]
+            apt = "fd-find"
+            winget = "sharkdp.fd"
+
+            [names.neovim]
+            pacman = ""
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            packages.resolve(Manager::Apt),
+            ["ripgrep", "fd-find", "neovim"]
+        );
+        assert_eq!(packages.resolve(Manager::Pacman), ["ripgrep", "fd"]);
+        assert_eq!(
+            packages.resolve(Manager::Winget),
+            ["ripgrep", "sharkdp.fd", "neovim"]
+        );
+
+        assert!(toml::from_str::<Packages>("[names.fd]\nzypper = \"fd\"").is_err());
+        assert_eq!("dnf".parse::<Manager>(), Ok(Manager::Dnf));
+        assert!("zypper".parse::<Manager>().is_err());
+
+        let winget = Manager::Winget.install_commands(&packages.resolve(Manager::Winget));
+        assert_eq!(winget.len(), 3);
+        assert_eq!(
+            winget[1],
+            ["winget", "install", "--exact", "--id", "sharkdp.fd"]
+        );
+        let brew = Manager::Brew.install_commands(&packages.resolve(Manager::Brew));
+        assert_eq!(brew, [["brew", "install", "ripgrep", "fd", "neovim"]]);
+    }
+
+    #[test]
+    fn collect_group_packages() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        for (group, packages) in [
+            ("zsh", "[\"zsh\", \"fzf\"]"),
+            ("tmux", "[\"tmux\", \"fzf\"]"),
+        ] {
+            let group_dir = dotfiles_dir.join("Hooks").join(group);
+            fs::create_dir_all(&group_dir).unwrap();
+            fs::write(
+                group_dir.join(PACKAGES_FILE),
+                format!("packages = {packages}"),
+            )
+            .unwrap();
+        }
+        fs::create_dir_all(dotfiles_dir.join("Hooks").join("nvim")).unwrap();
+
+        assert_eq!(groups_with_packages(None), ["tmux", "zsh"]);
+        assert_eq!(
+            collect_packages(
+                None,
+                &["zsh".into(), "tmux".into(), "nvim".into()],
+                Manager::Dnf
+            )
+            .unwrap(),
+            ["zsh", "fzf", "tmux"]
+        );
+
+        fs::remove_dir_all(dotfiles_dir.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,376 @@
+//! Checks that deployed secrets can only be used by their owner
+//!
+//! Programs such as ssh refuse keys that other users can read, and a secret in a directory that
+//! others can write to can be swapped for a file of their own. Only unix-like systems are checked.
+//!
+//! Deployed files are also given the permissions their group asks for with `permissions` in
+//! tuckr.toml, and the ones that drifted away from them are found here. Symlinks can't have
+//! permissions of their own, so for those it's their dotfile that's changed.
+
+use crate::config;
+use crate::fileops::DirWalk;
+use crate::state::State;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::Stderr;
+use rust_i18n::t;
+use std::path::{Path, PathBuf};
+
+/// Something that lets other users get at a deployed secret
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Issue {
+    /// the secret can be accessed by its group or by everyone
+    OpenMode { file: PathBuf, mode: u32 },
+    /// the secret is owned by another user
+    WrongOwner { file: PathBuf, uid: u32 },
+    /// one of the secret's directories can be written to by others, so the secret can be replaced
+    WritableDir { dir: PathBuf, mode: u32 },
+    /// the directory the secret is in can be read by everyone
+    PublicDir { dir: PathBuf, mode: u32 },
+}
+
+impl Issue {
+    pub fn path(&self) -> &Path {
+        match self {
+            Issue::OpenMode { file, .. } | Issue::WrongOwner { file, .. } => file,
+            Issue::WritableDir { dir, .. } | Issue::PublicDir { dir, .. } => dir,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let path = self.path().display();
+        match self {
+            Issue::OpenMode { mode, .. } => {
+                t!(
+                    "warn.secret_mode_too_open",
+                    file = path,
+                    mode = format!("{mode:o}")
+                )
+            }
+            Issue::WrongOwner { uid, .. } => t!("warn.secret_wrong_owner", file = path, uid = uid),
+            Issue::WritableDir { mode, .. } => {
+                t!(
+                    "warn.secret_dir_writable",
+                    dir = path,
+                    mode = format!("{mode:o}")
+                )
+            }
+            Issue::PublicDir { mode, .. } => {
+                t!(
+                    "warn.secret_dir_public",
+                    dir = path,
+                    mode = format!("{mode:o}")
+                )
+            }
+        }
+        .into_owned()
+    }
+
+    /// Returns the permission bits that are taken away, None if it's the owner that's wrong
+    fn revoked_bits(&self) -> Option<u32> {
+        match self {
+            // the owner keeps whatever it could do, e.g. run a secret script
+            Issue::OpenMode { .. } => Some(0o077),
+            Issue::WritableDir { .. } => Some(0o022),
+            Issue::PublicDir { .. } => Some(0o007),
+            Issue::WrongOwner { .. } => None,
+        }
+    }
+
+    /// Tightens the permissions, files owned by someone else are given to the current user
+    #[cfg(target_family = "unix")]
+    pub fn fix(&self, dry_run: bool) -> std::io::Result<()> {
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::fs::PermissionsExt;
+
+        // a directory can have several issues, so they're fixed from its current mode
+        let path = self.path();
+        let mode = path.metadata()?.permissions().mode() & 0o7777;
+        let fixed_mode = self.revoked_bits().map(|bits| mode & !bits);
+
+        if dry_run {
+            match fixed_mode {
+                Some(mode) => eprintln!(
+                    "{} `{}` to {mode:o}",
+                    "changing the mode of".if_supports_color(Stderr, |t| t.yellow()),
+                    path.display()
+                ),
+                None => eprintln!(
+                    "{} `{}`",
+                    "taking over".if_supports_color(Stderr, |t| t.yellow()),
+                    path.display()
+                ),
+            }
+            return Ok(());
+        }
+
+        if let Some(mode) = fixed_mode {
+            return std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode));
+        }
+
+        let path =
+            std::ffi::CString::new(path.as_os_str().as_bytes()).map_err(std::io::Error::other)?;
+        // SAFETY: path is a valid nul terminated string, getuid and getgid can't fail
+        match unsafe { libc::chown(path.as_ptr(), libc::getuid(), libc::getgid()) } {
+            0 => Ok(()),
+            _ => Err(std::io::Error::last_os_error()),
+        }
+    }
+
+    #[cfg(not(target_family = "unix"))]
+    pub fn fix(&self, _dry_run: bool) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A deployed file whose permissions differ from the ones its group asks for in tuckr.toml
+#[derive(Debug, Clone, PartialEq)]
+pub struct Drift {
+    pub file: PathBuf,
+    pub group: String,
+    pub mode: u32,
+    pub wanted: u32,
+}
+
+impl Drift {
+    /// Gives the file the permissions its group asks for
+    #[cfg(target_family = "unix")]
+    pub fn fix(&self, dry_run: bool) -> std::io::Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        if dry_run {
+            eprintln!(
+                "{} `{}` to {:o}",
+                "changing the mode of".if_supports_color(Stderr, |t| t.yellow()),
+                self.file.display(),
+                self.wanted
+            );
+            return Ok(());
+        }
+
+        std::fs::set_permissions(&self.file, std::fs::Permissions::from_mode(self.wanted))
+    }
+
+    #[cfg(not(target_family = "unix"))]
+    pub fn fix(&self, _dry_run: bool) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns the deployed files of the groups that `is_wanted` accepts whose permissions differ
+/// from the ones in tuckr.toml
+#[cfg(target_family = "unix")]
+pub fn find_drift(state: &State, is_wanted: impl Fn(&str) -> bool) -> Vec<Drift> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let config = config::get();
+    let mut drift = Vec::new();
+    for (target, deployed) in &state.deployed {
+        if !is_wanted(&deployed.group) {
+            continue;
+        }
+
+        // the rules are written by the file's path in the group
+        let Some(group_dir) = deployed.source.ancestors().find(|dir| {
+            dir.file_name() == Some(deployed.group.as_ref())
+                && dir.parent().and_then(Path::file_name) == Some("Configs".as_ref())
+        }) else {
+            continue;
+        };
+
+        // the files in folded directories are deployed along with them
+        let files = std::iter::once(deployed.source.clone()).chain(
+            deployed
+                .source
+                .is_dir()
+                .then(|| DirWalk::new(&deployed.source))
+                .into_iter()
+                .flatten(),
+        );
+        for file in files {
+            let (Ok(path), Ok(relative)) = (
+                file.strip_prefix(group_dir),
+                file.strip_prefix(&deployed.source),
+            ) else {
+                continue;
+            };
+            let Some(file_mode) = config.file_mode(&deployed.group, path) else {
+                continue;
+            };
+
+            let target = match relative.as_os_str().is_empty() {
+                true => target.clone(),
+                false => target.join(relative),
+            };
+            let Ok(metadata) = target.metadata() else {
+                continue;
+            };
+            let mode = metadata.permissions().mode() & 0o7777;
+            let wanted = file_mode.apply(mode);
+            if mode != wanted {
+                drift.push(Drift {
+                    file: target,
+                    group: deployed.group.clone(),
+                    mode,
+                    wanted,
+                });
+            }
+        }
+    }
+
+    drift
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn find_drift(_state: &State, _is_wanted: impl Fn(&str) -> bool) -> Vec<Drift> {
+    Vec::new()
+}
+
+/// Returns what's wrong with the permissions of a secret that was deployed into `target_dir`
+///
+/// The directories are checked up to the target dir, which is expected to be readable by others,
+/// e.g. a home directory with mode 755
+#[cfg(target_family = "unix")]
+pub fn check(secret: &Path, target_dir: &Path, uid: u32) -> Vec<Issue> {
+    use std::os::unix::fs::MetadataExt;
+
+    let Ok(metadata) = secret.symlink_metadata() else {
+        return Vec::new();
+    };
+    if metadata.is_symlink() {
+        return Vec::new();
+    }
+
+    let mut issues = Vec::new();
+    let mode = metadata.mode() & 0o7777;
+    if mode & 0o077 != 0 {
+        issues.push(Issue::OpenMode {
+            file: secret.to_path_buf(),
+            mode,
+        });
+    }
+    if metadata.uid() != uid {
+        issues.push(Issue::WrongOwner {
+            file: secret.to_path_buf(),
+            uid: metadata.uid(),
+        });
+    }
+
+    for dir in secret
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(target_dir))
+    {
+        let Ok(metadata) = dir.metadata() else {
+            continue;
+        };
+        let mode = metadata.mode() & 0o7777;
+
+        // sticky directories such as /tmp only let owners replace their files
+        if mode & 0o022 != 0 && mode & 0o1000 == 0 {
+            issues.push(Issue::WritableDir {
+                dir: dir.to_path_buf(),
+                mode,
+            });
+        }
+        if mode & 0o007 != 0 && dir != target_dir && Some(dir) == secret.parent() {
+            issues.push(Issue::PublicDir {
+                dir: dir.to_path_buf(),
+                mode,
+            });
+        }
+    }
+
+    issues
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn check(_secret: &Path, _target_dir: &Path, _uid: u32) -> Vec<Issue> {
+    Vec::new()
+}
+
+/// Returns the id of the user that runs tuckr
+#[cfg(target_family = "unix")]
+pub fn current_uid() -> u32 {
+    // SAFETY: getuid can't fail
+    unsafe { libc::getuid() }
+}
+
+#[cfg(not(target_family = "unix"))]
+pub fn current_uid() -> u32 {
+    0
+}
+
+#[cfg(all(test, target_family = "unix"))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn set_mode(path: &Path, mode: u32) {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[test]
+    fn check_and_fix_permissions() {
+        let target_dir = std::env::temp_dir()
+            .join("tuckr")
+            .join("permissions")
+            .join(std::process::id().to_string());
+        _ = fs::remove_dir_all(&target_dir);
+        let ssh_dir = target_dir.join(".ssh");
+        let key = ssh_dir.join("id_ed25519");
+        fs::create_dir_all(&ssh_dir).unwrap();
+        fs::write(&key, "key").unwrap();
+        set_mode(&target_dir, 0o755);
+        set_mode(&ssh_dir, 0o775);
+        set_mode(&key, 0o644);
+
+        let uid = current_uid();
+        let issues = check(&key, &target_dir, uid);
+        assert_eq!(
+            issues,
+            [
+                Issue::OpenMode {
+                    file: key.clone(),
+                    mode: 0o644
+                },
+                Issue::WritableDir {
+                    dir: ssh_dir.clone(),
+                    mode: 0o775
+                },
+                Issue::PublicDir {
+                    dir: ssh_dir.clone(),
+                    mode: 0o775
+                },
+            ]
+        );
+        assert!(
+            check(&key, &target_dir, uid + 1).contains(&Issue::WrongOwner {
+                file: key.clone(),
+                uid
+            })
+        );
+
+        for issue in &issues {
+            issue.fix(false).unwrap();
+        }
+        assert!(check(&key, &target_dir, uid).is_empty());
+        assert_eq!(
+            fs::metadata(&ssh_dir).unwrap().permissions().mode() & 0o777,
+            0o750
+        );
+
+        // the target dir is only checked for others being able to write to it
+        set_mode(&target_dir, 0o777);
+        assert_eq!(
+            check(&key, &target_dir, uid),
+            [Issue::WritableDir {
+                dir: target_dir.clone(),
+                mode: 0o777
+            }]
+        );
+        set_mode(&target_dir, 0o755);
+
+        fs::remove_dir_all(&target_dir).unwrap();
+    }
+}
@@ -0,0 +1,386 @@
+//! `tuckr check`, what `tuckr set` would do written down as a plan before anything is done
+//!
+//! The plan is made by the same dry run that `--dry-run` does: while it's being made, what the dry
+//! run would print is recorded as steps instead, which are then sorted into what gets deployed,
+//! replaced, backed up or adopted, which directories get created, which hooks run, which secrets
+//! get decrypted and which files are in the way.
+
+use crate::errors::TuckrError;
+use crate::hooks;
+use crate::interactive;
+use crate::secrets;
+use crate::state::DeployMethod;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::Stdout;
+use rust_i18n::t;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static RECORDING: AtomicBool = AtomicBool::new(false);
+
+static STEPS: Mutex<Vec<Step>> = Mutex::new(Vec::new());
+
+/// Something the dry run would do
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// a dotfile is deployed where nothing is yet
+    Deploy {
+        source: PathBuf,
+        target: PathBuf,
+        method: DeployMethod,
+        as_root: bool,
+    },
+    /// an out of date copy or rendered template is written again
+    Update {
+        source: PathBuf,
+        target: PathBuf,
+    },
+    /// a directory is created for a group whose directory can't be symlinked as a whole
+    CreateDir {
+        dir: PathBuf,
+    },
+    /// another group's directory that's symlinked as a whole is replaced by a directory of symlinks
+    Unfold {
+        dir: PathBuf,
+    },
+    /// what's in place of a dotfile is backed up, e.g. with `--force`
+    Backup {
+        file: PathBuf,
+    },
+    /// what's in place of a dotfile replaces the dotfile, with `--adopt`
+    Adopt {
+        file: PathBuf,
+        dotfile: PathBuf,
+    },
+    /// what's in place of a dotfile is removed without backing it up, for assets and collisions the group wins
+    Remove {
+        file: PathBuf,
+    },
+    /// something is in place of a dotfile, so the dotfile is left out
+    Conflict {
+        file: PathBuf,
+    },
+    RunHook {
+        group: String,
+        script: PathBuf,
+    },
+    Decrypt {
+        secret: PathBuf,
+        target: PathBuf,
+    },
+}
+
+/// Records a step of the plan, returns false if no plan is being made so that the dry run prints it instead
+pub fn record(step: Step) -> bool {
+    if !is_recording() {
+        return false;
+    }
+
+    STEPS.lock().unwrap().push(step);
+    true
+}
+
+pub fn is_recording() -> bool {
+    RECORDING.load(Ordering::Relaxed)
+}
+
+/// Returns the files that are in the way and that nothing is done about
+fn unresolved_conflicts(steps: &[Step]) -> Vec<&Path> {
+    let resolved: BTreeSet<&Path> = steps
+        .iter()
+        .filter_map(|step| match step {
+            Step::Backup { file } | Step::Adopt { file, .. } | Step::Remove { file } => {
+                Some(file.as_path())
+            }
+            _ => None,
+        })
+        .collect();
+
+    let conflicts: BTreeSet<&Path> = steps
+        .iter()
+        .filter_map(|step| match step {
+            Step::Conflict { file } if !resolved.contains(file.as_path()) => Some(file.as_path()),
+            _ => None,
+        })
+        .collect();
+    conflicts.into_iter().collect()
+}
+
+/// Returns the directories that have to be created, only the outermost missing one of each path
+fn missing_dirs(steps: &[Step]) -> Vec<PathBuf> {
+    let mut dirs = BTreeSet::new();
+    for step in steps {
+        let dir = match step {
+            Step::CreateDir { dir } => Some(dir.as_path()),
+            Step::Deploy { target, .. } | Step::Decrypt { target, .. } => target.parent(),
+            _ => None,
+        };
+
+        let outermost = dir
+            .into_iter()
+            .flat_map(Path::ancestors)
+            .take_while(|dir| !dir.exists())
+            .last();
+        dirs.extend(outermost.map(Path::to_path_buf));
+    }
+
+    // directories inside of another missing one are created along with it
+    let mut missing: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        if !missing.iter().any(|parent| dir.starts_with(parent)) {
+            missing.push(dir);
+        }
+    }
+    missing
+}
+
+fn print_section(title: impl std::fmt::Display, lines: &[String]) {
+    if lines.is_empty() {
+        return;
+    }
+
+    println!("{title}:");
+    for line in lines {
+        println!("\t{line}");
+    }
+    println!();
+}
+
+fn print_plan(steps: &[Step]) {
+    let mut deploy = Vec::new();
+    let mut update = Vec::new();
+    let mut replace_links = Vec::new();
+    let mut backup = Vec::new();
+    let mut remove = Vec::new();
+    let mut adopt = Vec::new();
+    let mut unfold = Vec::new();
+    let mut hooks = Vec::new();
+    let mut decrypt = Vec::new();
+
+    for step in steps {
+        match step {
+            Step::Deploy {
+                source,
+                target,
+                method,
+                as_root,
+            } => {
+                let method = match method {
+                    DeployMethod::Symlink => "symlink",
+                    DeployMethod::Copy => "copy",
+                    DeployMethod::Template => "template",
+                    DeployMethod::Junction => "junction",
+                    DeployMethod::Hardlink => "hardlink",
+                };
+                deploy.push(format!(
+                    "{} -> {} ({method}{})",
+                    target.display().if_supports_color(Stdout, |t| t.green()),
+                    source.display(),
+                    if *as_root { ", as root" } else { "" }
+                ));
+            }
+            Step::Update { source, target } => update.push(format!(
+                "{} -> {}",
+                target.display().if_supports_color(Stdout, |t| t.yellow()),
+                source.display()
+            )),
+            Step::Backup { file } | Step::Remove { file } if file.is_symlink() => {
+                let points_to = std::fs::read_link(file).unwrap_or_default();
+                replace_links.push(format!(
+                    "{} -> {}",
+                    file.display().if_supports_color(Stdout, |t| t.yellow()),
+                    points_to.display()
+                ));
+            }
+            Step::Backup { file } => backup.push(
+                file.display()
+                    .if_supports_color(Stdout, |t| t.yellow())
+                    .to_string(),
+            ),
+            Step::Remove { file } => remove.push(
+                file.display()
+                    .if_supports_color(Stdout, |t| t.red())
+                    .to_string(),
+            ),
+            Step::Adopt { file, dotfile } => adopt.push(format!(
+                "{} -> {}",
+                file.display().if_supports_color(Stdout, |t| t.yellow()),
+                dotfile.display()
+            )),
+            Step::Unfold { dir } => unfold.push(
+                dir.display()
+                    .if_supports_color(Stdout, |t| t.yellow())
+                    .to_string(),
+            ),
+            Step::RunHook { group, script } => {
+                hooks.push(format!("{} ({group})", script.display()))
+            }
+            Step::Decrypt { secret, target } => decrypt.push(format!(
+                "{} -> {}",
+                target.display().if_supports_color(Stdout, |t| t.green()),
+                secret.display()
+            )),
+            Step::CreateDir { .. } | Step::Conflict { .. } => (),
+        }
+    }
+
+    let dirs: Vec<_> = missing_dirs(steps)
+        .iter()
+        .map(|dir| {
+            dir.display()
+                .if_supports_color(Stdout, |t| t.green())
+                .to_string()
+        })
+        .collect();
+    let conflicts: Vec<_> = unresolved_conflicts(steps)
+        .into_iter()
+        .map(|file| {
+            file.display()
+                .if_supports_color(Stdout, |t| t.red())
+                .to_string()
+        })
+        .collect();
+
+    print_section(t!("info.plan_hooks"), &hooks);
+    print_section(t!("info.plan_create_dirs"), &dirs);
+    print_section(t!("info.plan_unfold"), &unfold);
+    print_section(t!("info.plan_deploy"), &deploy);
+    print_section(t!("info.plan_update"), &update);
+    print_section(t!("info.plan_replace_links"), &replace_links);
+    print_section(t!("info.plan_backup"), &backup);
+    print_section(t!("info.plan_remove"), &remove);
+    print_section(t!("info.plan_adopt"), &adopt);
+    print_section(t!("info.plan_decrypt"), &decrypt);
+    print_section(t!("info.plan_conflicts"), &conflicts);
+
+    if !conflicts.is_empty() {
+        println!(
+            "{}",
+            t!("info.learn_how_to_fix_symlinks", cmd = "tuckr help add")
+        );
+    }
+}
+
+/// Prints what `tuckr set` would do with the groups without changing anything
+///
+/// Fails with [`TuckrError::Conflicts`] if there are files in the way that would be left alone
+#[allow(clippy::too_many_arguments)]
+pub fn check_cmd(
+    profile: Option<String>,
+    only_files: bool,
+    copy: bool,
+    groups: &[String],
+    exclude: &[String],
+    force: bool,
+    adopt: bool,
+    no_secrets: bool,
+) -> Result<(), TuckrError> {
+    RECORDING.store(true, Ordering::Relaxed);
+    // the plan is all that's printed, not the headers of every group
+    interactive::set_quiet();
+
+    // groups that only have secrets have nothing to symlink nor hooks to run, the same as for `tuckr set`
+    let config_groups: Vec<_> = groups
+        .iter()
+        .filter(|group| {
+            no_secrets
+                || !secrets::only_has_secrets(profile.clone(), group)
+                || hooks::has_hooks(profile.clone(), group)
+        })
+        .cloned()
+        .collect();
+
+    let set = match config_groups.is_empty() {
+        true => Ok(()),
+        false => hooks::set_cmd(
+            profile.clone(),
+            true,
+            only_files,
+            copy,
+            &config_groups,
+            exclude,
+            force,
+            adopt,
+            true,
+            None,
+            true,
+        ),
+    };
+    let decrypted = match no_secrets {
+        true => Ok(()),
+        false => secrets::deploy_cmd(profile, true, groups, exclude, force),
+    };
+
+    RECORDING.store(false, Ordering::Relaxed);
+    let steps = std::mem::take(&mut *STEPS.lock().unwrap());
+
+    // conflicts are reported by the plan itself
+    match set.and(decrypted) {
+        Ok(()) | Err(TuckrError::Conflicts) => (),
+        Err(err) => return Err(err),
+    }
+
+    if steps.is_empty() {
+        println!(
+            "{}",
+            t!("info.plan_empty").if_supports_color(Stdout, |t| t.green())
+        );
+        return Ok(());
+    }
+
+    print_plan(&steps);
+
+    match unresolved_conflicts(&steps).is_empty() {
+        true => Ok(()),
+        false => Err(TuckrError::Conflicts),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dotfiles;
+    use std::fs;
+
+    #[test]
+    fn sort_out_conflicts_and_directories() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let dir = dotfiles_dir.with_file_name("target");
+        fs::create_dir_all(&dir).unwrap();
+
+        let deploy = |target: &str| Step::Deploy {
+            source: PathBuf::from("/dotfiles/Configs/group/file"),
+            target: dir.join(target),
+            method: DeployMethod::Symlink,
+            as_root: false,
+        };
+        let steps = [
+            deploy("file"),
+            deploy(".config/nvim/init.lua"),
+            deploy(".config/nvim/lua/plugins.lua"),
+            deploy(".local/bin/script"),
+            Step::CreateDir {
+                dir: dir.join(".config/app"),
+            },
+            Step::Conflict {
+                file: dir.join(".zshrc"),
+            },
+            Step::Conflict {
+                file: dir.join(".bashrc"),
+            },
+            Step::Backup {
+                file: dir.join(".bashrc"),
+            },
+        ];
+
+        assert_eq!(unresolved_conflicts(&steps), [dir.join(".zshrc")]);
+        assert_eq!(
+            missing_dirs(&steps),
+            [dir.join(".config"), dir.join(".local")]
+        );
+
+        fs::remove_dir_all(dotfiles_dir.parent().unwrap()).unwrap();
+    }
+}
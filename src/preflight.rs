@@ -0,0 +1,398 @@
+//! Checks that the machine is ready for the groups before `tuckr set` runs their hooks
+//!
+//! Groups declare what they need under `[groups.<name>.preflight]` in tuckr.toml:
+//! free space where the dotfiles are deployed, programs in $PATH, addresses that have to be
+//! reachable and paths that have to be writable.
+//! Every check of every group is run before anything is deployed, so that all of the problems are
+//! reported at once instead of a long `tuckr set` failing partway through.
+//!
+//! Groups can also say what they're for with `requires_env` and `requires`, e.g. a Wayland
+//! compositor's config. Unlike failed preflight checks, groups whose requirements aren't met are
+//! skipped by `add '*'` and `set '*'` since there's nothing on this machine that would use them.
+
+use crate::config::{self, GroupConfig, Preflight};
+use crate::dotfiles;
+use crate::error;
+use crate::errors::{self, TuckrError};
+use owo_colors::OwoColorize;
+use owo_colors::Stream::{Stderr, Stdout};
+use rust_i18n::t;
+use std::env;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, PartialEq)]
+pub struct Failure {
+    pub group: String,
+    /// one of `free-space`, `command`, `reachable` or `writable`
+    pub check: &'static str,
+    pub message: String,
+}
+
+/// Formats a size in bytes with the largest binary unit that fits, e.g. "1.5 GiB"
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.1} {}", UNITS[unit]),
+    }
+}
+
+/// Returns the closest ancestor of the path that exists, which is where it'll be created
+fn existing_ancestor(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|path| path.exists())
+}
+
+#[cfg(target_family = "unix")]
+fn free_space(path: &Path) -> Result<u64, String> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|e| e.to_string())?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+
+    // space that unprivileged users can use, the space reserved for root is left out
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Free space is only checked on unix-like systems for now, so it's never lacking elsewhere
+#[cfg(not(target_family = "unix"))]
+fn free_space(_path: &Path) -> Result<u64, String> {
+    Ok(u64::MAX)
+}
+
+#[cfg(target_family = "unix")]
+fn is_writable(path: &Path) -> bool {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    CString::new(path.as_os_str().as_bytes())
+        .is_ok_and(|path| unsafe { libc::access(path.as_ptr(), libc::W_OK) } == 0)
+}
+
+#[cfg(not(target_family = "unix"))]
+fn is_writable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|metadata| !metadata.permissions().readonly())
+}
+
+/// Returns true if the program is in one of the directories in $PATH
+pub fn command_exists(command: &str) -> bool {
+    let Some(paths) = env::var_os("PATH") else {
+        return false;
+    };
+
+    // windows finds programs without their extension
+    let extensions: Vec<String> = match cfg!(target_family = "windows") {
+        true => env::var("PATHEXT")
+            .unwrap_or(".EXE;.BAT;.CMD".into())
+            .split(';')
+            .map(str::to_string)
+            .chain([String::new()])
+            .collect(),
+        false => vec![String::new()],
+    };
+
+    env::split_paths(&paths).any(|dir| {
+        extensions.iter().any(|ext| {
+            let program = dir.join(format!("{command}{ext}"));
+
+            #[cfg(target_family = "unix")]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                program.metadata().is_ok_and(|metadata| {
+                    metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+                })
+            }
+
+            #[cfg(not(target_family = "unix"))]
+            program.is_file()
+        })
+    })
+}
+
+fn check_reachable(address: &str) -> Result<(), String> {
+    let addrs: Vec<_> = address
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .collect();
+
+    let mut last_err = String::new();
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+            Ok(_) => return Ok(()),
+            Err(err) => last_err = err.to_string(),
+        }
+    }
+
+    Err(last_err)
+}
+
+/// Runs the checks of a single group
+fn check_group(group: &str, preflight: &Preflight, target_dir: &Path) -> Vec<Failure> {
+    let mut failures = Vec::new();
+    let mut fail = |check, message: String| {
+        failures.push(Failure {
+            group: group.to_string(),
+            check,
+            message,
+        })
+    };
+
+    if let Some(needed) = preflight.free_space {
+        let path = existing_ancestor(target_dir).unwrap_or(target_dir);
+        match free_space(path) {
+            Ok(free) if free < needed => fail(
+                "free-space",
+                t!(
+                    "errors.not_enough_space",
+                    free = format_size(free),
+                    path = path.display(),
+                    needed = format_size(needed)
+                )
+                .into_owned(),
+            ),
+            Ok(_) => (),
+            Err(err) => fail("free-space", format!("{}: {err}", path.display())),
+        }
+    }
+
+    for command in &preflight.commands {
+        if !command_exists(command) {
+            fail(
+                "command",
+                t!("errors.command_not_found", command = command).into_owned(),
+            );
+        }
+    }
+
+    for path in &preflight.writable {
+        let path: PathBuf = target_dir.join(path);
+        let writable = existing_ancestor(&path).is_some_and(is_writable);
+        if !writable {
+            fail(
+                "writable",
+                t!("errors.not_writable", path = path.display()).into_owned(),
+            );
+        }
+    }
+
+    // connecting can take a while so every address is tried at once
+    let unreachable: Vec<_> = thread::scope(|scope| {
+        let checks: Vec<_> = preflight
+            .reachable
+            .iter()
+            .map(|address| (address, scope.spawn(|| check_reachable(address))))
+            .collect();
+
+        checks
+            .into_iter()
+            .filter_map(|(address, check)| Some((address, check.join().unwrap().err()?)))
+            .collect()
+    });
+
+    for (address, err) in unreachable {
+        fail(
+            "reachable",
+            t!("errors.unreachable", address = address, err = err).into_owned(),
+        );
+    }
+
+    failures
+}
+
+/// Returns the first of the group's `requires_env` and `requires` that this machine is missing
+fn unmet_requirement(group: &GroupConfig) -> Option<String> {
+    let unset = group
+        .requires_env
+        .iter()
+        .find(|var| env::var_os(var).is_none_or(|value| value.is_empty()));
+    if let Some(var) = unset {
+        return Some(format!("${var}"));
+    }
+
+    group.requires.iter().find_map(|requirement| {
+        let (name, met) = match requirement.to_lowercase().as_str() {
+            "wayland" => ("Wayland", env::var_os("WAYLAND_DISPLAY").is_some()),
+            "x11" => ("X11", env::var_os("DISPLAY").is_some()),
+            "systemd" => ("systemd", Path::new("/run/systemd/system").is_dir()),
+            _ => (requirement.as_str(), command_exists(requirement)),
+        };
+        (!met).then(|| name.to_string())
+    })
+}
+
+/// Returns what the group requires that this machine is missing, e.g. `Wayland`, `add '*'` skips these groups
+///
+/// Conditional groups fall back to their base group's requirements
+pub fn missing_requirement(group: &str) -> Option<String> {
+    let config = config::get();
+    [group, dotfiles::group_without_target(group)]
+        .into_iter()
+        .find_map(|group| config.groups.get(group))
+        .and_then(unmet_requirement)
+}
+
+/// Returns true if the group's requirements are met, otherwise says what's missing since the group is skipped
+pub fn requirements_met(group: &str) -> bool {
+    let Some(requirement) = missing_requirement(group) else {
+        return true;
+    };
+
+    println!(
+        "{}",
+        t!(
+            "info.skipped_requires",
+            group = group,
+            requirement = requirement
+        )
+        .if_supports_color(Stdout, |t| t.yellow())
+    );
+    false
+}
+
+/// Runs the preflight checks of the groups and of their conditional groups, every group is checked for `*`
+///
+/// Only the groups that are deployed to this machine and aren't excluded are checked
+pub fn run(groups: &[String], exclude: &[String], target_dir: &Path) -> Vec<Failure> {
+    let config = config::get();
+
+    thread::scope(|scope| {
+        let checks: Vec<_> = config
+            .groups
+            .iter()
+            .filter(|(name, _)| {
+                // `set '*'` skips the groups whose requirements aren't met
+                let picked = groups
+                    .iter()
+                    .any(|group| group == *name || dotfiles::group_without_target(name) == group)
+                    || groups.iter().any(|group| group == "*")
+                        && missing_requirement(name).is_none();
+
+                picked
+                    && dotfiles::group_is_valid_target(name)
+                    && !dotfiles::is_excluded(name, exclude)
+            })
+            .filter_map(|(name, group)| {
+                let preflight = group.preflight.as_ref()?;
+                Some(scope.spawn(move || check_group(name, preflight, target_dir)))
+            })
+            .collect();
+
+        checks
+            .into_iter()
+            .flat_map(|check| check.join().unwrap())
+            .collect()
+    })
+}
+
+/// Runs the groups' preflight checks and reports every failure at once, used by `tuckr set`
+pub fn check_cmd(groups: &[String], exclude: &[String]) -> Result<(), TuckrError> {
+    let target_dir = match dotfiles::get_dotfiles_target_dir_path() {
+        Ok(dir) => dir,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::NoSuchFileOrDir);
+        }
+    };
+
+    let failures = run(groups, exclude, &target_dir);
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    errors::print(error!("preflight_failed"));
+    for failure in failures {
+        eprintln!(
+            "  {}[{}]: {}",
+            failure.group.if_supports_color(Stderr, |t| t.yellow()),
+            failure.check,
+            failure.message
+        );
+    }
+
+    Err(TuckrError::PreflightFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    fn format_sizes() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(500 << 20), "500.0 MiB");
+    }
+
+    #[test]
+    fn preflight_checks() {
+        let target_dir = env::temp_dir();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap().to_string();
+
+        let ready = Preflight {
+            free_space: Some(1),
+            commands: vec!["cargo".into()],
+            reachable: vec![open],
+            writable: vec![target_dir.join("tuckr").join("preflight")],
+        };
+        assert_eq!(check_group("ready", &ready, &target_dir), []);
+
+        let unready = Preflight {
+            free_space: Some(u64::MAX),
+            commands: vec!["tuckr-preflight-missing".into()],
+            reachable: vec!["invalid address".into()],
+            writable: vec![],
+        };
+        let checks: Vec<_> = check_group("unready", &unready, &target_dir)
+            .into_iter()
+            .map(|failure| failure.check)
+            .collect();
+
+        let mut expected = vec!["command", "reachable"];
+        if cfg!(target_family = "unix") {
+            expected.insert(0, "free-space");
+        }
+        assert_eq!(checks, expected);
+    }
+
+    #[test]
+    fn requirements() {
+        let mut group = GroupConfig {
+            requires_env: vec!["PATH".into()],
+            requires: vec!["cargo".into()],
+            ..Default::default()
+        };
+        assert_eq!(unmet_requirement(&group), None);
+
+        group.requires.push("tuckr-requirement-missing".into());
+        assert_eq!(
+            unmet_requirement(&group).as_deref(),
+            Some("tuckr-requirement-missing")
+        );
+
+        // environment variables are checked first
+        group.requires_env.push("TUCKR_REQUIREMENT_UNSET".into());
+        assert_eq!(
+            unmet_requirement(&group).as_deref(),
+            Some("$TUCKR_REQUIREMENT_UNSET")
+        );
+    }
+}
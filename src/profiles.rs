@@ -0,0 +1,299 @@
+//! Compares dotfile profiles
+//!
+//! Profiles can either be compared with each other or with what is currently deployed on the system
+
+use crate::dotfiles::{self, Dotfile};
+use crate::errors::{self, TuckrError};
+use crate::fileops::DirWalk;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::Stdout;
+use rust_i18n::t;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The name used to refer to the dotfiles directory without a profile
+const DEFAULT_PROFILE: &str = "default";
+
+/// Returns the profile a name refers to, None for the dotfiles without a profile
+pub fn to_profile(name: &str) -> Option<String> {
+    if name == DEFAULT_PROFILE {
+        None
+    } else {
+        Some(name.into())
+    }
+}
+
+/// Differences between the Configs directory of two profiles
+#[derive(Debug, Default, PartialEq)]
+struct ProfileDiff {
+    groups_only_in_a: BTreeSet<String>,
+    groups_only_in_b: BTreeSet<String>,
+    // the files below are relative to the Configs directory, e.g. `zsh/.zshrc`
+    files_only_in_a: BTreeSet<PathBuf>,
+    files_only_in_b: BTreeSet<PathBuf>,
+    modified_files: BTreeSet<PathBuf>,
+}
+
+impl ProfileDiff {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Returns the groups and the files in a Configs directory, the files are relative to the directory
+fn list_configs(configs_dir: &Path) -> (BTreeSet<String>, BTreeSet<PathBuf>) {
+    let mut groups = BTreeSet::new();
+    let mut files = BTreeSet::new();
+
+    let Ok(dir) = configs_dir.read_dir() else {
+        return (groups, files);
+    };
+
+    for group in dir {
+        let group = group.unwrap();
+        if group.path().is_dir() {
+            groups.insert(group.file_name().into_string().unwrap());
+        }
+    }
+
+    for file in DirWalk::new(configs_dir) {
+        if file.is_dir() {
+            continue;
+        }
+
+        files.insert(file.strip_prefix(configs_dir).unwrap().to_path_buf());
+    }
+
+    (groups, files)
+}
+
+fn diff_configs(configs_a: &Path, configs_b: &Path) -> ProfileDiff {
+    let (groups_a, files_a) = list_configs(configs_a);
+    let (groups_b, files_b) = list_configs(configs_b);
+
+    // files from groups that only exist on one side are already reported by the group
+    let in_group = |file: &PathBuf, groups: &BTreeSet<String>| {
+        let group = file.iter().next().unwrap().to_str().unwrap();
+        groups.contains(group)
+    };
+
+    let groups_only_in_a: BTreeSet<_> = groups_a.difference(&groups_b).cloned().collect();
+    let groups_only_in_b: BTreeSet<_> = groups_b.difference(&groups_a).cloned().collect();
+
+    ProfileDiff {
+        files_only_in_a: files_a
+            .difference(&files_b)
+            .filter(|f| !in_group(f, &groups_only_in_a))
+            .cloned()
+            .collect(),
+        files_only_in_b: files_b
+            .difference(&files_a)
+            .filter(|f| !in_group(f, &groups_only_in_b))
+            .cloned()
+            .collect(),
+        modified_files: files_a
+            .intersection(&files_b)
+            .filter(|f| fs::read(configs_a.join(f)).ok() != fs::read(configs_b.join(f)).ok())
+            .cloned()
+            .collect(),
+        groups_only_in_a,
+        groups_only_in_b,
+    }
+}
+
+fn get_configs_dir(profile: &str) -> Result<PathBuf, TuckrError> {
+    match dotfiles::get_dotfiles_path(to_profile(profile)) {
+        Ok(dir) => Ok(dir.join("Configs")),
+        Err(err) => {
+            errors::print(err);
+            Err(TuckrError::CouldntFindDotfiles)
+        }
+    }
+}
+
+fn print_list<T: std::fmt::Display>(title: &str, items: impl IntoIterator<Item = T>) {
+    let mut items = items.into_iter().peekable();
+    if items.peek().is_none() {
+        return;
+    }
+
+    println!("{title}:");
+    for item in items {
+        println!("\t{item}");
+    }
+    println!();
+}
+
+/// Prints the differences between two profiles
+///
+/// If `profile_b` is None the profile is compared against the deployed dotfiles instead
+pub fn diff_cmd(profile_a: &str, profile_b: Option<&str>) -> Result<(), TuckrError> {
+    let configs_a = get_configs_dir(profile_a)?;
+
+    let Some(profile_b) = profile_b else {
+        return diff_deployed(profile_a, &configs_a);
+    };
+
+    let configs_b = get_configs_dir(profile_b)?;
+    let diff = diff_configs(&configs_a, &configs_b);
+
+    if diff.is_empty() {
+        println!(
+            "{}",
+            t!("info.profiles_are_identical").if_supports_color(Stdout, |t| t.green())
+        );
+        return Ok(());
+    }
+
+    let only_in_a = t!("info.only_in_x", x = profile_a);
+    let only_in_b = t!("info.only_in_x", x = profile_b);
+
+    print_list(
+        &format!("{} ({only_in_a})", t!("info.groups")),
+        diff.groups_only_in_a
+            .iter()
+            .map(|g| g.if_supports_color(Stdout, |t| t.green())),
+    );
+    print_list(
+        &format!("{} ({only_in_b})", t!("info.groups")),
+        diff.groups_only_in_b
+            .iter()
+            .map(|g| g.if_supports_color(Stdout, |t| t.red())),
+    );
+    print_list(
+        &format!("{} ({only_in_a})", t!("info.files")),
+        diff.files_only_in_a.iter().map(|f| {
+            f.display()
+                .if_supports_color(Stdout, |t| t.green())
+                .to_string()
+        }),
+    );
+    print_list(
+        &format!("{} ({only_in_b})", t!("info.files")),
+        diff.files_only_in_b.iter().map(|f| {
+            f.display()
+                .if_supports_color(Stdout, |t| t.red())
+                .to_string()
+        }),
+    );
+    print_list(
+        &t!("info.modified_files"),
+        diff.modified_files.iter().map(|f| {
+            f.display()
+                .if_supports_color(Stdout, |t| t.yellow())
+                .to_string()
+        }),
+    );
+
+    Err(TuckrError::Failed)
+}
+
+/// Compares a profile with the dotfiles currently deployed on the system
+fn diff_deployed(profile: &str, configs_dir: &Path) -> Result<(), TuckrError> {
+    let mut not_deployed = Vec::new();
+    let mut deployed_elsewhere = Vec::new();
+
+    for file in DirWalk::new(configs_dir) {
+        if file.is_dir() {
+            continue;
+        }
+
+        let Ok(dotfile) = Dotfile::try_from(file.clone()) else {
+            continue;
+        };
+
+        if !dotfile.is_valid_target() {
+            continue;
+        }
+
+        let Ok(target) = dotfile.to_target_path() else {
+            continue;
+        };
+
+        let relative_path = file.strip_prefix(configs_dir).unwrap().to_path_buf();
+
+        // symlinks might be on any of the target's parent directories so the paths are resolved
+        match target.canonicalize() {
+            Ok(resolved) if resolved == file.canonicalize().unwrap() => (),
+            Ok(resolved) => deployed_elsewhere.push((relative_path, resolved)),
+            Err(_) => not_deployed.push(relative_path),
+        }
+    }
+
+    if not_deployed.is_empty() && deployed_elsewhere.is_empty() {
+        println!(
+            "{}",
+            t!("info.profile_is_deployed", profile = profile)
+                .if_supports_color(Stdout, |t| t.green())
+        );
+        return Ok(());
+    }
+
+    print_list(
+        &t!("table-column.not_symlinked"),
+        not_deployed.iter().map(|f| {
+            f.display()
+                .if_supports_color(Stdout, |t| t.red())
+                .to_string()
+        }),
+    );
+    print_list(
+        &t!("info.deployed_from_elsewhere"),
+        deployed_elsewhere.iter().map(|(f, resolved)| {
+            format!(
+                "{} -> {}",
+                f.display().if_supports_color(Stdout, |t| t.yellow()),
+                resolved.display()
+            )
+        }),
+    );
+
+    Err(TuckrError::Failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_two_configs_dirs() {
+        let root = std::env::temp_dir().join(format!(
+            "tuckr_profile_diff-{}",
+            std::thread::current().name().unwrap()
+        ));
+        let (a, b) = (root.join("a"), root.join("b"));
+
+        for (dir, files) in [
+            (&a, &["zsh/.zshrc", "zsh/.zprofile", "nvim/init.lua"][..]),
+            (&b, &["zsh/.zshrc", "zsh/.zlogin", "tmux/tmux.conf"][..]),
+        ] {
+            for file in files {
+                let file = dir.join(file);
+                fs::create_dir_all(file.parent().unwrap()).unwrap();
+                fs::write(file, "content").unwrap();
+            }
+        }
+        fs::write(b.join("zsh/.zshrc"), "different content").unwrap();
+
+        let diff = diff_configs(&a, &b);
+        assert_eq!(diff.groups_only_in_a, BTreeSet::from(["nvim".to_string()]));
+        assert_eq!(diff.groups_only_in_b, BTreeSet::from(["tmux".to_string()]));
+        assert_eq!(
+            diff.files_only_in_a,
+            BTreeSet::from([PathBuf::from("zsh/.zprofile")])
+        );
+        assert_eq!(
+            diff.files_only_in_b,
+            BTreeSet::from([PathBuf::from("zsh/.zlogin")])
+        );
+        assert_eq!(
+            diff.modified_files,
+            BTreeSet::from([PathBuf::from("zsh/.zshrc")])
+        );
+
+        assert!(diff_configs(&a, &a).is_empty());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,78 @@
+//! Progress bars for commands that go through many groups or files
+//!
+//! Bars are only shown on terminals, so that they never end up in logs or in what scripts read,
+//! and `--quiet` turns them off altogether. Errors are printed above the bar that's being shown.
+
+use indicatif::{ProgressBar, ProgressStyle};
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// the bar that's currently shown, so that errors can be printed above it
+static SHOWN: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// Stops progress bars from being shown, set with `--quiet` and for dry runs which list every change instead
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+fn is_enabled() -> bool {
+    !DISABLED.load(Ordering::Relaxed)
+        && std::io::stdout().is_terminal()
+        && std::io::stderr().is_terminal()
+}
+
+/// A progress bar that's cleared once it's dropped
+pub struct Progress(ProgressBar);
+
+impl Progress {
+    /// Creates a bar for `len` steps, `action` is shown before it like cargo does, e.g. "adding"
+    ///
+    /// Nothing is drawn until the first step starts, so that prompts for passwords come before it
+    pub fn new(len: usize, action: &str) -> Self {
+        if !is_enabled() || len < 2 {
+            return Self(ProgressBar::hidden());
+        }
+
+        let bar = ProgressBar::new(len as u64).with_prefix(action.to_string());
+        bar.set_style(
+            ProgressStyle::with_template(
+                "{prefix:>12.green.bold} [{bar:30}] {pos}/{len} {wide_msg}",
+            )
+            .unwrap()
+            .progress_chars("=> "),
+        );
+
+        *SHOWN.lock().unwrap() = Some(bar.clone());
+        Self(bar)
+    }
+
+    /// Shows what's being worked on
+    pub fn start(&self, item: impl Display) {
+        self.0.set_message(item.to_string());
+    }
+
+    /// Marks a step as done
+    pub fn inc(&self) {
+        self.0.inc(1);
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.0.finish_and_clear();
+        SHOWN.lock().unwrap().take();
+    }
+}
+
+/// Runs `print` with the bar that's shown cleared away, so that what's printed goes above it
+pub fn suspend<T>(print: impl FnOnce() -> T) -> T {
+    let shown = SHOWN.lock().unwrap().clone();
+    match shown {
+        Some(bar) => bar.suspend(print),
+        None => print(),
+    }
+}
@@ -0,0 +1,98 @@
+//! `tuckr add --safe`, deploying only ever adds to what's there
+//!
+//! Symlinks and copies are only created where nothing exists yet. Whatever would delete, move or
+//! overwrite something (winning a collision, replacing what's in place of an asset, unfolding
+//! another group's directory, updating an out of date copy) is left alone and reported once
+//! everything else has been deployed, so that it can be run from automated jobs.
+
+use crate::table;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::Stdout;
+use rust_i18n::t;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tabled::Tabled;
+
+static ENABLED: OnceLock<bool> = OnceLock::new();
+
+static SKIPPED: Mutex<Vec<(PathBuf, Reason)>> = Mutex::new(Vec::new());
+
+/// Why something was left alone
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reason {
+    /// something that isn't the group's is in its place
+    Exists,
+    /// the group wins the collision with the group that deployed it
+    Collision(String),
+    /// it's in place of an asset's file
+    Asset,
+    /// another group's directory is symlinked there as a whole
+    Folded(String),
+    /// it's an out of date copy or rendered template
+    Stale,
+}
+
+impl Reason {
+    fn describe(&self) -> String {
+        match self {
+            Reason::Exists => t!("info.safe_exists"),
+            Reason::Collision(group) => t!("info.safe_collision", group = group),
+            Reason::Asset => t!("info.safe_asset"),
+            Reason::Folded(group) => t!("info.safe_folded", group = group),
+            Reason::Stale => t!("info.safe_stale"),
+        }
+        .into_owned()
+    }
+}
+
+/// Nothing is deleted, moved or overwritten from now on, set with `tuckr add --safe`
+pub fn enable() {
+    _ = ENABLED.set(true);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.get().copied().unwrap_or_default()
+}
+
+/// Returns true if the change to `path` has to be skipped, in which case it's reported later on
+pub fn skip(path: &Path, reason: Reason) -> bool {
+    if !is_enabled() {
+        return false;
+    }
+
+    let mut skipped = SKIPPED.lock().unwrap();
+    if !skipped.iter().any(|(skipped, _)| skipped == path) {
+        skipped.push((path.to_path_buf(), reason));
+    }
+    true
+}
+
+/// Prints what was left alone
+pub fn report() {
+    #[derive(Tabled)]
+    struct SkippedRow {
+        #[tabled(rename = "File")]
+        file: String,
+        #[tabled(rename = "Reason")]
+        reason: String,
+    }
+
+    let skipped = std::mem::take(&mut *SKIPPED.lock().unwrap());
+    if skipped.is_empty() {
+        return;
+    }
+
+    let rows: Vec<_> = skipped
+        .into_iter()
+        .map(|(file, reason)| SkippedRow {
+            file: file.display().to_string(),
+            reason: reason.describe(),
+        })
+        .collect();
+
+    println!(
+        "{}",
+        t!("info.safe_skipped").if_supports_color(Stdout, |t| t.yellow())
+    );
+    table::print(&rows);
+}
@@ -1,132 +1,957 @@
 //! Manages encrypted files
 //!
-//! Encrypts files into dotfiles/Secrets using the chacha20poly1305 algorithm
+//! Encrypts files into dotfiles/Secrets with the backend chosen in tuckr.toml:
+//! - password: the chacha20poly1305 algorithm with a key derived from a password with Argon2id (default)
+//! - age: encrypts to the age recipients of every machine so that each one decrypts with its own key
+//! - gpg: encrypts to gpg keys
+//!
+//! age and gpg are run as external programs so they have to be installed to be used.
+//!
+//! A group can also keep its secrets in a single encrypted tar archive, `Secrets/<group>.tar.<ext>`,
+//! so that not even the names of its files are in the repo.
+//!
+//! Secrets keep the permissions and modification time of the file they were encrypted from, for
+//! archives in their tar headers and for other secrets in a header that's encrypted along with them.
+//! The Root group's secrets are stored relative to `/` instead of $HOME, e.g. for files in /etc.
+//!
+//! The password is taken from the first of these that's set up, so that secrets can be used from scripts:
+//! `--password-file`, $TUCKR_SECRET_PASSWORD, the OS keyring (`keyring = true` in tuckr.toml),
+//! stdin if it isn't a terminal and otherwise a prompt.
 
-use crate::dotfiles::{self, Dotfile, ReturnCode};
-use crate::fileops::DirWalk;
-use chacha20poly1305::{AeadCore, KeyInit, XChaCha20Poly1305, aead::Aead};
+use crate::archive;
+use crate::backups::{self, BackupSet};
+use crate::cancel;
+use crate::config::{self, SecretsBackend};
+use crate::dotfiles;
+use crate::error;
+use crate::errors::{self, TuckrError};
+use crate::fileops::{self, DirWalk};
+use crate::hooks;
+use crate::permissions;
+use crate::plan::{self, Step};
+use crate::progress::Progress;
+use crate::state::{self, DecryptedFile, State};
+use crate::stats;
+use crate::table;
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{AeadCore, KeyInit, XChaCha20Poly1305};
 use owo_colors::OwoColorize;
-use rand::rngs;
+use owo_colors::Stream::{Stderr, Stdout};
+use rand::{RngCore, rngs};
 use rust_i18n::t;
 use sha2::{Digest, Sha256};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs;
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
-use std::process::ExitCode;
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use std::sync::{OnceLock, mpsc};
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+use tabled::Tabled;
+use zeroize::Zeroizing;
+
+/// Encrypts and decrypts the contents of secrets
+trait Backend {
+    fn encrypt(&self, contents: &[u8]) -> Result<Vec<u8>, errors::Error>;
+    fn decrypt(&self, contents: &[u8]) -> Result<Vec<u8>, errors::Error>;
+}
+
+/// Returns true if the secret was encrypted in a format that should be migrated away from
+pub fn is_outdated(kind: SecretsBackend, contents: &[u8]) -> bool {
+    match kind {
+        SecretsBackend::Password => !contents.starts_with(MAGIC),
+        SecretsBackend::Age | SecretsBackend::Gpg => false,
+    }
+}
+
+/// Returns false if the secret looks like plain text that was never encrypted
+///
+/// Every backend writes binary data except for the armored formats of age and gpg,
+/// which start with a header that's checked for instead
+pub fn looks_encrypted(contents: &[u8]) -> bool {
+    const HEADERS: &[&[u8]] = &[
+        MAGIC,
+        b"age-encryption.org/",
+        b"-----BEGIN AGE ENCRYPTED FILE-----",
+        b"-----BEGIN PGP MESSAGE-----",
+    ];
+
+    HEADERS.iter().any(|header| contents.starts_with(header))
+        || std::str::from_utf8(contents).is_err()
+}
+
+const NONCE_LEN: usize = 24;
+const SALT_LEN: usize = 16;
+
+/// secrets encrypted with a password start with this, older ones start right away with their nonce
+const MAGIC: &[u8] = b"tuckr\0v1";
+/// magic, Argon2id's cost (m_cost: u32, t_cost: u32, p_cost: u32), salt and nonce
+const HEADER_LEN: usize = MAGIC.len() + 4 + 4 + 4 + SALT_LEN + NONCE_LEN;
+
+/// How expensive it is to derive the key from the password with Argon2id, it's stored in every
+/// secret so that it can be raised later on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct KdfParams {
+    /// memory in KiB
+    m_cost: u32,
+    /// iterations
+    t_cost: u32,
+    /// lanes
+    p_cost: u32,
+}
+
+impl KdfParams {
+    /// 64 MiB of memory per key, the second recommendation of RFC 9106
+    const DEFAULT: Self = KdfParams {
+        m_cost: 64 * 1024,
+        t_cost: 3,
+        p_cost: 4,
+    };
+
+    /// Secrets could be tampered with to make deriving their key take up all the memory
+    fn is_sane(self) -> bool {
+        (1..=16).contains(&self.p_cost)
+            && (8 * self.p_cost..=4 * 1024 * 1024).contains(&self.m_cost)
+            && (1..=16).contains(&self.t_cost)
+    }
+
+    fn argon2(self) -> Result<Argon2<'static>, errors::Error> {
+        let params = argon2::Params::new(
+            self.m_cost,
+            self.t_cost,
+            self.p_cost,
+            Some(chacha20poly1305::Key::default().len()),
+        )
+        .map_err(|e| e.to_string())?;
+
+        Ok(Argon2::new(
+            argon2::Algorithm::Argon2id,
+            argon2::Version::V0x13,
+            params,
+        ))
+    }
+}
+
+struct PasswordBackend {
+    password: Zeroizing<String>,
+    params: KdfParams,
+    /// salt of the secrets that are encrypted, secrets encrypted together share it so that the key is only derived once
+    salt: [u8; SALT_LEN],
+    /// keys that have already been derived
+    keys: RefCell<HashMap<([u8; SALT_LEN], KdfParams), chacha20poly1305::Key>>,
+}
+
+impl PasswordBackend {
+    fn new(password: Zeroizing<String>, params: KdfParams) -> Self {
+        let mut salt = [0; SALT_LEN];
+        rngs::OsRng.fill_bytes(&mut salt);
+
+        PasswordBackend {
+            password,
+            params,
+            salt,
+            keys: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn key(
+        &self,
+        salt: [u8; SALT_LEN],
+        params: KdfParams,
+    ) -> Result<chacha20poly1305::Key, errors::Error> {
+        if let Some(key) = self.keys.borrow().get(&(salt, params)) {
+            return Ok(*key);
+        }
+
+        let mut key = chacha20poly1305::Key::default();
+        params
+            .argon2()?
+            .hash_password_into(self.password.as_bytes(), &salt, &mut key)
+            .map_err(|e| e.to_string())?;
+
+        self.keys.borrow_mut().insert((salt, params), key);
+        Ok(key)
+    }
+
+    /// Decrypts secrets from before the key was derived with Argon2id, their key is the sha256 of the password
+    fn decrypt_legacy(&self, contents: &[u8]) -> Result<Vec<u8>, errors::Error> {
+        if contents.len() < NONCE_LEN {
+            return Err(error!("wrong_password"));
+        }
+
+        let cipher = XChaCha20Poly1305::new(&Sha256::digest(self.password.as_bytes()));
+        let (nonce, contents) = contents.split_at(NONCE_LEN);
+
+        cipher
+            .decrypt(nonce.into(), contents)
+            .map_err(|_| error!("wrong_password"))
+    }
+}
+
+impl Backend for PasswordBackend {
+    fn encrypt(&self, contents: &[u8]) -> Result<Vec<u8>, errors::Error> {
+        // every file gets its own nonce, reusing one with the same key would weaken the encryption
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut rngs::OsRng);
+
+        let mut secret = MAGIC.to_vec();
+        secret.extend(self.params.m_cost.to_le_bytes());
+        secret.extend(self.params.t_cost.to_le_bytes());
+        secret.extend(self.params.p_cost.to_le_bytes());
+        secret.extend(self.salt);
+        secret.extend(nonce);
+
+        // the header is authenticated so that its cost and salt can't be changed
+        let cipher = XChaCha20Poly1305::new(&self.key(self.salt, self.params)?);
+        let encrypted = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: contents,
+                    aad: &secret,
+                },
+            )
+            .map_err(|e| e.to_string())?;
+
+        secret.extend(encrypted);
+        Ok(secret)
+    }
+
+    fn decrypt(&self, contents: &[u8]) -> Result<Vec<u8>, errors::Error> {
+        if !contents.starts_with(MAGIC) {
+            return self.decrypt_legacy(contents);
+        }
+
+        if contents.len() < HEADER_LEN {
+            return Err(error!("wrong_password"));
+        }
+
+        let (header, encrypted) = contents.split_at(HEADER_LEN);
+        let fields = &header[MAGIC.len()..];
+        let field = |i: usize| u32::from_le_bytes(fields[i * 4..i * 4 + 4].try_into().unwrap());
+        let params = KdfParams {
+            m_cost: field(0),
+            t_cost: field(1),
+            p_cost: field(2),
+        };
+        let salt: [u8; SALT_LEN] = fields[12..12 + SALT_LEN].try_into().unwrap();
+        let nonce = &fields[12 + SALT_LEN..];
+
+        if !params.is_sane() {
+            return Err(error!("wrong_password"));
+        }
+
+        let cipher = XChaCha20Poly1305::new(&self.key(salt, params)?);
+        cipher
+            .decrypt(
+                nonce.into(),
+                Payload {
+                    msg: encrypted,
+                    aad: header,
+                },
+            )
+            .map_err(|_| error!("wrong_password"))
+    }
+}
+
+const PASSWORD_ENV: &str = "TUCKR_SECRET_PASSWORD";
+const NEW_PASSWORD_ENV: &str = "TUCKR_NEW_SECRET_PASSWORD";
+/// what's appended to a secret's name while `tuckr secrets rekey` encrypts it again, and while
+/// its old version is kept until every secret was replaced
+pub const REKEYED_SUFFIX: &str = ".tuckr-rekey";
+pub const OLD_SUFFIX: &str = ".tuckr-old";
+
+/// the service and user the password is stored under in the OS keyring
+const KEYRING_SERVICE: &str = "tuckr";
+const KEYRING_USER: &str = "secrets";
+
+static PASSWORD_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Reads the password from `file` instead of asking for it
+pub fn set_password_file(file: PathBuf) {
+    _ = PASSWORD_FILE.set(file);
+}
+
+/// Removes the line break that ends the password, if there's one
+fn trim_line_break(mut password: Zeroizing<String>) -> Zeroizing<String> {
+    let len = password.trim_end_matches(['\r', '\n']).len();
+    password.truncate(len);
+    password
+}
+
+/// Looks the password up in the OS keyring
+fn keyring_password() -> Result<Zeroizing<String>, errors::Error> {
+    let has_keyring = cfg!(any(
+        target_os = "linux",
+        target_os = "freebsd",
+        target_os = "openbsd",
+        target_os = "macos",
+        target_os = "windows"
+    ));
+    if !has_keyring {
+        return Err(error!("keyring_unsupported"));
+    }
+
+    let password = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+        .and_then(|entry| entry.get_password())
+        .map(Zeroizing::new);
+
+    match password {
+        Ok(password) if !password.is_empty() => Ok(password),
+        Ok(_) | Err(keyring::Error::NoEntry) => {
+            Err(error!("no_keyring_password", service = KEYRING_SERVICE))
+        }
+        Err(err) => Err(err.to_string().into()),
+    }
+}
+
+/// Gets the password from the first source that's set up, see the module's documentation
+fn read_password() -> Result<Zeroizing<String>, String> {
+    if let Some(file) = PASSWORD_FILE.get() {
+        let contents = Zeroizing::new(
+            fs::read_to_string(file).map_err(|e| format!("{}: {e}", file.display()))?,
+        );
+        // only the first line is used so that the file can end with a line break
+        let password = contents.lines().next().unwrap_or_default();
+        return Ok(Zeroizing::new(password.to_string()));
+    }
+
+    if let Ok(password) = std::env::var(PASSWORD_ENV) {
+        return Ok(Zeroizing::new(password));
+    }
+
+    if config::get().secrets.keyring {
+        match keyring_password() {
+            Ok(password) => return Ok(password),
+            Err(err) => eprintln!(
+                "{}",
+                t!("warn.keyring_failed", err_msg = err).if_supports_color(Stderr, |t| t.yellow())
+            ),
+        }
+    }
+
+    if !std::io::stdin().is_terminal() {
+        let mut password = Zeroizing::new(String::new());
+        std::io::stdin()
+            .read_line(&mut password)
+            .map_err(|e| e.to_string())?;
+        return Ok(trim_line_break(password));
+    }
+
+    rpassword::prompt_password(format!("{}: ", t!("info.password")))
+        .map(Zeroizing::new)
+        .map_err(|e| e.to_string())
+}
+
+/// Gets the password that secrets are encrypted with again by `tuckr secrets rekey`
+///
+/// It's taken from $TUCKR_NEW_SECRET_PASSWORD or the next line of stdin if it isn't a terminal,
+/// otherwise it's asked for twice so that a typo doesn't lock the secrets away
+fn read_new_password() -> Result<Zeroizing<String>, errors::Error> {
+    let password = if let Ok(password) = std::env::var(NEW_PASSWORD_ENV) {
+        Zeroizing::new(password)
+    } else if !std::io::stdin().is_terminal() {
+        let mut password = Zeroizing::new(String::new());
+        std::io::stdin()
+            .read_line(&mut password)
+            .map_err(|e| e.to_string())?;
+        trim_line_break(password)
+    } else {
+        let prompt = |message| {
+            rpassword::prompt_password(format!("{message}: "))
+                .map(Zeroizing::new)
+                .map_err(|e| e.to_string())
+        };
+        let password = prompt(t!("info.new_password"))?;
+        if *prompt(t!("info.repeat_new_password"))? != *password {
+            return Err(error!("passwords_dont_match"));
+        }
+        password
+    };
+
+    match password.is_empty() {
+        true => Err(error!("empty_password")),
+        false => Ok(password),
+    }
+}
+
+/// Runs a program with `input` as its stdin and returns what it wrote to stdout
+fn run_filter(program: &mut Command, input: &[u8]) -> Result<Vec<u8>, errors::Error> {
+    let name = program.get_program().to_string_lossy().into_owned();
+    let mut child = program
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("{name}: {e}"))?;
+
+    // stdin is written from another thread so that a full stdout pipe can't block the program
+    let mut stdin = child.stdin.take().unwrap();
+    let output = thread::scope(|s| {
+        s.spawn(move || stdin.write_all(input));
+        child.wait_with_output()
+    })
+    .map_err(|e| format!("{name}: {e}"))?;
+
+    if !output.status.success() {
+        return Err(format!("{name}: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+
+    Ok(output.stdout)
+}
+
+struct AgeBackend {
+    recipients: Vec<String>,
+    identity: PathBuf,
+}
+
+impl Backend for AgeBackend {
+    fn encrypt(&self, contents: &[u8]) -> Result<Vec<u8>, errors::Error> {
+        if self.recipients.is_empty() {
+            return Err(error!("no_recipients", backend = "age"));
+        }
+
+        let mut age = Command::new("age");
+        age.arg("--encrypt");
+        for recipient in &self.recipients {
+            age.args(["--recipient", recipient]);
+        }
+        run_filter(&mut age, contents)
+    }
+
+    fn decrypt(&self, contents: &[u8]) -> Result<Vec<u8>, errors::Error> {
+        run_filter(
+            Command::new("age")
+                .arg("--decrypt")
+                .arg("--identity")
+                .arg(&self.identity),
+            contents,
+        )
+    }
+}
+
+struct GpgBackend {
+    recipients: Vec<String>,
+}
+
+impl Backend for GpgBackend {
+    fn encrypt(&self, contents: &[u8]) -> Result<Vec<u8>, errors::Error> {
+        if self.recipients.is_empty() {
+            return Err(error!("no_recipients", backend = "gpg"));
+        }
+
+        let mut gpg = Command::new("gpg");
+        gpg.args(["--batch", "--yes", "--encrypt"]);
+        for recipient in &self.recipients {
+            gpg.args(["--recipient", recipient]);
+        }
+        run_filter(&mut gpg, contents)
+    }
+
+    fn decrypt(&self, contents: &[u8]) -> Result<Vec<u8>, errors::Error> {
+        run_filter(Command::new("gpg").args(["--quiet", "--decrypt"]), contents)
+    }
+}
+
+/// The file extension of a group archive, it tells which backend the archive was encrypted with
+fn archive_extension(kind: SecretsBackend) -> &'static str {
+    match kind {
+        SecretsBackend::Password => "tar.enc",
+        SecretsBackend::Age => "tar.age",
+        SecretsBackend::Gpg => "tar.gpg",
+    }
+}
+
+/// Returns the group that a file in Secrets is the archive of, e.g. `ssh` for `ssh.tar.age`
+pub fn archive_group(file_name: &str) -> Option<&str> {
+    [
+        SecretsBackend::Password,
+        SecretsBackend::Age,
+        SecretsBackend::Gpg,
+    ]
+    .into_iter()
+    .find_map(|kind| file_name.strip_suffix(&format!(".{}", archive_extension(kind))))
+    .filter(|group| !group.is_empty())
+}
+
+/// Returns the path to the group's archive in the Secrets directory if it has one
+pub fn find_archive(secrets_dir: &Path, group: &str) -> Option<PathBuf> {
+    fs::read_dir(secrets_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .find(|entry| {
+            entry.path().is_file()
+                && entry
+                    .file_name()
+                    .to_str()
+                    .and_then(archive_group)
+                    .is_some_and(|name| name == group)
+        })
+        .map(|entry| entry.path())
+}
+
+/// A group in Secrets
+///
+/// Its secrets are either files in its directory, or packed into a single encrypted archive so
+/// that not even their names are in the repo, or both
+struct SecretGroup {
+    name: String,
+    dir: PathBuf,
+    archive: Option<PathBuf>,
+}
+
+impl SecretGroup {
+    /// Returns the group's encrypted files, its archive included and its encrypted hooks left out
+    fn encrypted_files(&self) -> Vec<PathBuf> {
+        let hooks_dir = self.dir.join(HOOKS_DIR);
+        let mut files: Vec<PathBuf> = match self.dir.is_dir() {
+            true => DirWalk::new(&self.dir)
+                .sorted(true)
+                .filter(|file| !file.is_dir() && !file.starts_with(&hooks_dir))
+                .collect(),
+            false => Vec::new(),
+        };
+        files.extend(self.archive.clone());
+        files
+    }
+
+    /// Returns the group's encrypted hooks, see [`EncryptedHooks`]
+    fn encrypted_hooks(&self) -> Vec<PathBuf> {
+        list_hooks(&self.dir.join(HOOKS_DIR))
+    }
+
+    /// Returns the directory that the group's secrets are decrypted into, `/` for the Root group
+    fn target_dir(&self, target_dir: &Path) -> PathBuf {
+        match self.name == "Root" {
+            true => dotfiles::get_root_target_dir_path(),
+            false => target_dir.to_path_buf(),
+        }
+    }
+
+    /// Returns where an encrypted file is decrypted to, an archive is unpacked into the target dir
+    fn destination(&self, secret: &Path, target_dir: &Path) -> PathBuf {
+        let target_dir = self.target_dir(target_dir);
+        match secret.strip_prefix(&self.dir) {
+            Ok(base_secret_path) => target_dir.join(base_secret_path),
+            Err(_) => target_dir,
+        }
+    }
+}
+
+/// Comes before the contents of a secret to store the metadata of the file it was encrypted from,
+/// secrets encrypted by older versions of tuckr don't have it
+const METADATA_MAGIC: &[u8] = b"tuckr\0m1";
+const METADATA_LEN: usize = METADATA_MAGIC.len() + 4 + 8;
+
+/// The permissions and modification time of the file that a secret was encrypted from
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SecretMetadata {
+    mode: u32,
+    /// seconds since the unix epoch, 0 if it isn't known
+    mtime: u64,
+}
+
+impl SecretMetadata {
+    fn of(file: &Path) -> std::io::Result<Self> {
+        let metadata = fs::metadata(file)?;
+
+        #[cfg(target_family = "unix")]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            metadata.permissions().mode() & 0o7777
+        };
+        #[cfg(not(target_family = "unix"))]
+        let mode = 0o600;
+
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|mtime| mtime.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |mtime| mtime.as_secs());
+
+        Ok(Self { mode, mtime })
+    }
+
+    /// Puts the metadata in front of the contents
+    fn prepend(&self, contents: &[u8]) -> Zeroizing<Vec<u8>> {
+        let mut with_metadata = Zeroizing::new(Vec::with_capacity(METADATA_LEN + contents.len()));
+        with_metadata.extend_from_slice(METADATA_MAGIC);
+        with_metadata.extend_from_slice(&self.mode.to_le_bytes());
+        with_metadata.extend_from_slice(&self.mtime.to_le_bytes());
+        with_metadata.extend_from_slice(contents);
+        with_metadata
+    }
+
+    /// Splits the metadata off of decrypted contents, if they have any
+    fn split(contents: &[u8]) -> (Option<Self>, &[u8]) {
+        if contents.len() < METADATA_LEN || !contents.starts_with(METADATA_MAGIC) {
+            return (None, contents);
+        }
+
+        let (header, contents) = contents.split_at(METADATA_LEN);
+        let header = &header[METADATA_MAGIC.len()..];
+        let metadata = Self {
+            mode: u32::from_le_bytes(header[..4].try_into().unwrap()),
+            mtime: u64::from_le_bytes(header[4..].try_into().unwrap()),
+        };
+        (Some(metadata), contents)
+    }
+
+    /// Gives a decrypted secret its original modification time and permissions
+    ///
+    /// Only the owner's permissions are kept so that other users never get to read a secret, the
+    /// owner can always read and write it so that it can be decrypted over again.
+    fn restore(&self, path: &Path) -> std::io::Result<()> {
+        if self.mtime != 0 {
+            let mtime = UNIX_EPOCH + Duration::from_secs(self.mtime);
+            fs::File::options()
+                .write(true)
+                .open(path)?
+                .set_modified(mtime)?;
+        }
+
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(
+                path,
+                fs::Permissions::from_mode((self.mode & 0o700) | 0o600),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A decrypted secret along with where it goes and the encrypted file it came from
+struct DecryptedSecret {
+    dest: PathBuf,
+    contents: Zeroizing<Vec<u8>>,
+    source: PathBuf,
+    metadata: Option<SecretMetadata>,
+}
+
+impl DecryptedSecret {
+    /// Writes the secret to where it goes, see [`write_secret`]
+    fn write(&self) -> std::io::Result<()> {
+        write_secret(&self.dest, &self.contents)?;
+        match &self.metadata {
+            Some(metadata) => metadata.restore(&self.dest),
+            None => Ok(()),
+        }
+    }
+}
 
 struct SecretsHandler {
+    profile: Option<String>,
     dotfiles_dir: PathBuf,
-    key: chacha20poly1305::Key,
-    nonce: chacha20poly1305::XNonce,
+    /// backends are only set up once a group needs them, e.g. so that the password is only asked
+    /// for if there are groups that use it
+    backends: RefCell<BTreeMap<SecretsBackend, Rc<dyn Backend>>>,
 }
 
 impl SecretsHandler {
-    fn try_new(profile: Option<String>) -> Result<Self, ExitCode> {
-        let dotfiles_dir = match dotfiles::get_dotfiles_path(profile) {
+    fn try_new(profile: Option<String>) -> Result<Self, TuckrError> {
+        let dotfiles_dir = match dotfiles::get_dotfiles_path(profile.clone()) {
             Ok(path) => path,
             Err(e) => {
-                eprintln!("{e}");
-                return Err(ReturnCode::CouldntFindDotfiles.into());
+                errors::print(e);
+                return Err(TuckrError::CouldntFindDotfiles);
             }
         };
 
-        // makes a hash of the password so that it can fit on the 256 bit buffer used by the
-        // algorithm
-        let input_key = rpassword::prompt_password(format!("{}: ", t!("info.password"))).unwrap();
-        let input_hash = Sha256::digest(input_key);
-
         Ok(SecretsHandler {
+            profile,
             dotfiles_dir,
-            key: input_hash,
-            nonce: XChaCha20Poly1305::generate_nonce(&mut rngs::OsRng),
+            backends: RefCell::new(BTreeMap::new()),
         })
     }
 
-    /// takes a path to a file and returns its encrypted content
-    fn encrypt(&self, dotfile: &Path) -> Result<Vec<u8>, ExitCode> {
-        let cipher = XChaCha20Poly1305::new(&self.key);
-        let Ok(dotfile) = fs::read(dotfile) else {
-            eprintln!(
-                "{}",
-                t!("errors.x_doesnt_exist", x = dotfile.display()).red()
-            );
-            return Err(ReturnCode::NoSuchFileOrDir.into());
+    /// Returns the backend that the group's secrets are encrypted with
+    ///
+    /// The password is only read once the first secret that's encrypted with one is used
+    fn backend(&self, group: &str) -> Result<Rc<dyn Backend>, errors::Error> {
+        let kind = config::get().secrets_backend(group);
+        if let Some(backend) = self.backends.borrow().get(&kind) {
+            return Ok(backend.clone());
+        }
+
+        let secrets = &config::get().secrets;
+        let backend: Rc<dyn Backend> = match kind {
+            SecretsBackend::Password => {
+                Rc::new(PasswordBackend::new(read_password()?, KdfParams::DEFAULT))
+            }
+            SecretsBackend::Age => Rc::new(AgeBackend {
+                recipients: secrets.age.recipients.clone(),
+                identity: secrets.age.identity.clone().unwrap_or_else(|| {
+                    dirs::config_dir()
+                        .unwrap_or_default()
+                        .join("age")
+                        .join("keys.txt")
+                }),
+            }),
+            SecretsBackend::Gpg => Rc::new(GpgBackend {
+                recipients: secrets.gpg.recipients.clone(),
+            }),
         };
 
-        match cipher.encrypt(&self.nonce, dotfile.as_slice()) {
-            Ok(f) => Ok(f),
-            Err(e) => {
-                eprintln!("{}", e.red());
-                Err(ReturnCode::EncryptionFailed.into())
+        self.backends.borrow_mut().insert(kind, backend.clone());
+        Ok(backend)
+    }
+
+    /// Returns the groups in Secrets, every group is returned for `*`
+    ///
+    /// Profiles get the groups they don't have from the dotfiles without a profile
+    fn get_groups(&self, groups: &[String]) -> Vec<SecretGroup> {
+        let secrets_dirs =
+            dotfiles::get_group_dirs(self.profile.clone(), dotfiles::DotfileType::Secrets);
+        let names: BTreeSet<String> = if groups.contains(&"*".to_string()) {
+            secrets_dirs
+                .iter()
+                .flat_map(fs::read_dir)
+                .flatten()
+                .flatten()
+                .filter_map(|entry| {
+                    let name = entry.file_name().into_string().ok()?;
+                    match entry.path().is_dir() {
+                        true => Some(name),
+                        false => archive_group(&name).map(str::to_string),
+                    }
+                })
+                .collect()
+        } else {
+            groups.iter().cloned().collect()
+        };
+
+        let own_secrets_dir = self
+            .dotfiles_dir
+            .join(dotfiles::DotfileType::Secrets.dir_name());
+        names
+            .into_iter()
+            .map(|name| {
+                let secrets_dir = secrets_dirs
+                    .iter()
+                    .find(|dir| dir.join(&name).is_dir() || find_archive(dir, &name).is_some())
+                    .unwrap_or(&own_secrets_dir);
+
+                SecretGroup {
+                    dir: secrets_dir.join(&name),
+                    archive: find_archive(secrets_dir, &name),
+                    name,
+                }
+            })
+            .collect()
+    }
+
+    /// Decrypts the group's secrets, returning where each one goes along with its contents
+    ///
+    /// The group's archive is unpacked, so its files are returned as well
+    fn decrypt_group(
+        &self,
+        group: &SecretGroup,
+        target_dir: &Path,
+    ) -> Result<Vec<DecryptedSecret>, TuckrError> {
+        let mut secrets = Vec::new();
+        for secret in group.encrypted_files() {
+            let decrypted = Zeroizing::new(self.decrypt(&group.name, &secret)?);
+            if group.archive.as_ref() != Some(&secret) {
+                let (metadata, contents) = SecretMetadata::split(&decrypted);
+                secrets.push(DecryptedSecret {
+                    dest: group.destination(&secret, target_dir),
+                    contents: Zeroizing::new(contents.to_vec()),
+                    source: secret,
+                    metadata,
+                });
+                continue;
             }
+
+            let entries = archive::unpack(&decrypted).map_err(|err| {
+                errors::print(format!("{}: {err}", secret.display()));
+                TuckrError::DecryptionFailed
+            })?;
+            let group_target_dir = group.target_dir(target_dir);
+            // only files are ever packed into the group's archive
+            let files = entries
+                .into_iter()
+                .filter(|entry| entry.kind == archive::EntryKind::File);
+            secrets.extend(files.map(|entry| DecryptedSecret {
+                dest: group_target_dir.join(entry.path),
+                contents: entry.contents,
+                source: secret.clone(),
+                metadata: Some(SecretMetadata {
+                    mode: entry.mode,
+                    mtime: entry.mtime,
+                }),
+            }));
         }
+
+        Ok(secrets)
+    }
+
+    /// takes a path to a file and returns its encrypted content along with its metadata
+    fn encrypt(&self, group: &str, dotfile: &Path) -> Result<Vec<u8>, TuckrError> {
+        let (Ok(contents), Ok(metadata)) = (fs::read(dotfile), SecretMetadata::of(dotfile)) else {
+            errors::print(error!("x_doesnt_exist", x = dotfile.display()));
+            return Err(TuckrError::NoSuchFileOrDir);
+        };
+        let contents = metadata.prepend(&Zeroizing::new(contents));
+
+        let backend = self.backend(group);
+        backend
+            .and_then(|backend| backend.encrypt(&contents))
+            .map_err(|e| {
+                errors::print(e);
+                TuckrError::EncryptionFailed
+            })
     }
 
     /// takes a path to a file and returns its decrypted content
-    fn decrypt(&self, dotfile: &str) -> Result<Vec<u8>, ExitCode> {
-        let cipher = XChaCha20Poly1305::new(&self.key);
+    fn decrypt(&self, group: &str, dotfile: &Path) -> Result<Vec<u8>, TuckrError> {
         let dotfile = fs::read(dotfile).expect("Couldn't read dotfile");
 
-        // extracts the nonce from the first 24 bytes in the file
-        let (nonce, contents) = dotfile.split_at(24);
+        let backend = self.backend(group);
+        backend
+            .and_then(|backend| backend.decrypt(&dotfile))
+            .map_err(|e| {
+                errors::print(e);
+                TuckrError::DecryptionFailed
+            })
+    }
+}
+
+/// Returns the path that a hook is stored at in the group, hooks are stored by their name so they
+/// have to be named like the group's other hooks, e.g. `post_api_token.sh`
+fn hook_path(file: &Path) -> Result<PathBuf, TuckrError> {
+    match hooks::is_hook(file) {
+        true => Ok(Path::new(HOOKS_DIR).join(file.file_name().unwrap())),
+        false => {
+            errors::print(error!("not_a_hook", file = file.display()));
+            Err(TuckrError::Failed)
+        }
+    }
+}
+
+/// Returns the path that a file is stored at in the group, relative to where the group is decrypted to
+///
+/// Files outside of the target dir can only be stored in the Root group, with their full path
+fn secret_path(group: &str, file: &Path, target_dir: &Path) -> Result<PathBuf, TuckrError> {
+    if group == "Root" {
+        return Ok(dotfiles::strip_root(file));
+    }
 
-        match cipher.decrypt(nonce.into(), contents) {
-            Ok(f) => Ok(f),
-            Err(_) => {
-                eprintln!("{}", t!("errors.wrong_password").red());
-                Err(ReturnCode::DecryptionFailed.into())
-            }
+    match file.strip_prefix(target_dir) {
+        Ok(path) => Ok(path.to_path_buf()),
+        Err(_) => {
+            errors::print(error!(
+                "secret_not_in_target",
+                file = file.display(),
+                target = target_dir.display()
+            ));
+            Err(TuckrError::NoSuchFileOrDir)
         }
     }
 }
 
-/// Encrypts secrets
+/// Encrypts secrets, directories are encrypted with all of their files
+///
+/// With `archive` the files are packed into the group's archive instead, which is created if the
+/// group doesn't have one yet. With `hook` they're encrypted as the group's hooks, see [`EncryptedHooks`]
 pub fn encrypt_cmd(
     profile: Option<String>,
     dry_run: bool,
     group: &str,
     dotfiles: &[String],
-) -> Result<(), ExitCode> {
+    archive: bool,
+    hook: bool,
+) -> Result<(), TuckrError> {
     {
         let mut invalid_dotfiles = false;
         for dotfile in dotfiles {
             if !Path::new(dotfile).exists() {
-                eprintln!("{}", t!("errors.x_doesnt_exist", x = dotfile).red());
+                errors::print(error!("x_doesnt_exist", x = dotfile));
                 invalid_dotfiles = true;
             }
         }
 
         if invalid_dotfiles {
-            return Err(ExitCode::FAILURE);
+            return Err(TuckrError::Failed);
         }
     }
 
     let handler = SecretsHandler::try_new(profile)?;
 
-    let dest_dir = handler.dotfiles_dir.join("Secrets").join(group);
-    if !dest_dir.exists() {
-        fs::create_dir_all(&dest_dir).unwrap();
-    }
-
     let target_dir = match dotfiles::get_dotfiles_target_dir_path() {
         Ok(dir) => dir,
         Err(err) => {
-            eprintln!("{}", err.red());
-            return Err(ReturnCode::NoSuchFileOrDir.into());
+            errors::print(err);
+            return Err(TuckrError::NoSuchFileOrDir);
         }
     };
 
-    let encrypt_file = |dotfile: &Path| -> Result<(), ExitCode> {
-        let target_file = dotfile.strip_prefix(&target_dir).unwrap();
+    let mut files = Vec::new();
+    for dotfile in dotfiles {
+        let dotfile = match Path::new(dotfile).canonicalize() {
+            Ok(dotfile) => dotfile,
+            Err(err) => {
+                errors::print(format!("{dotfile}: {err}"));
+                return Err(TuckrError::EncryptionFailed);
+            }
+        };
+
+        if dotfile.is_dir() {
+            let (walk_errors, unreadable_dirs) = mpsc::channel();
+
+            // symlinked directories are left out so that nothing outside of the directory gets encrypted
+            files.extend(
+                DirWalk::new(&dotfile)
+                    .follow_symlinks(false)
+                    .errors(walk_errors)
+                    .filter(|file| file.is_file()),
+            );
+
+            if let Some(err) = unreadable_dirs.try_iter().next() {
+                errors::print(err.to_string());
+                return Err(TuckrError::Failed);
+            }
+        } else if dotfile.is_file() {
+            files.push(dotfile);
+        }
+    }
+
+    // every file is checked before anything is encrypted
+    let files = files
+        .into_iter()
+        .map(|file| match hook {
+            true => Ok((hook_path(&file)?, file)),
+            false => Ok((secret_path(group, &file, &target_dir)?, file)),
+        })
+        .collect::<Result<Vec<_>, TuckrError>>()?;
+
+    if archive {
+        return encrypt_into_archive(&handler, dry_run, group, &files);
+    }
+
+    let dest_dir = handler.dotfiles_dir.join("Secrets").join(group);
+    if !dry_run && let Err(err) = fs::create_dir_all(&dest_dir) {
+        errors::print(format!("{}: {err}", dest_dir.display()));
+        return Err(TuckrError::EncryptionFailed);
+    }
 
-        let encrypted_file_path = dest_dir.join(target_file);
+    let progress = Progress::new(files.len(), "encrypting");
+    for (target_file, dotfile) in files {
+        let encrypted_file_path = dest_dir.join(&target_file);
         if dry_run {
             eprintln!(
                 "{} `{}` into `{}`",
-                "encrypting".green(),
+                "encrypting".if_supports_color(Stderr, |t| t.green()),
                 dotfile.display(),
                 encrypted_file_path.display()
             );
-            return Ok(());
+            continue;
         }
 
         let dir_path = {
@@ -135,34 +960,105 @@ pub fn encrypt_cmd(
             tf
         };
 
-        let mut encrypted = handler.encrypt(dotfile)?;
-        let mut encrypted_file = handler.nonce.to_vec();
-        // appends a 24 byte nonce to the beginning of the file
-        encrypted_file.append(&mut encrypted);
+        let encrypted_file = handler.encrypt(group, &dotfile)?;
 
         // makes sure all parent directories of the dotfile are created
-        fs::create_dir_all(dest_dir.join(dir_path)).unwrap();
-        fs::write(encrypted_file_path, encrypted_file).unwrap();
+        let written = fs::create_dir_all(dest_dir.join(dir_path))
+            .and_then(|_| fs::write(&encrypted_file_path, encrypted_file));
+        if let Err(err) = written {
+            errors::print(format!("{}: {err}", encrypted_file_path.display()));
+            return Err(TuckrError::EncryptionFailed);
+        }
+        progress.inc();
+    }
 
-        Ok(())
+    Ok(())
+}
+
+/// Returns the name of the archive that the group's files are packed into
+pub fn archive_file_name(group: &str) -> String {
+    let kind = config::get().secrets_backend(group);
+    format!("{group}.{}", archive_extension(kind))
+}
+
+/// Packs the files into the group's archive, files that are already in it are replaced
+///
+/// files: the path that each file is stored at in the archive along with the file
+fn encrypt_into_archive(
+    handler: &SecretsHandler,
+    dry_run: bool,
+    group: &str,
+    files: &[(PathBuf, PathBuf)],
+) -> Result<(), TuckrError> {
+    let secrets_dir = handler.dotfiles_dir.join("Secrets");
+    let archive_path = secrets_dir.join(archive_file_name(group));
+
+    if dry_run {
+        for (_, file) in files {
+            eprintln!(
+                "{} `{}` into `{}`",
+                "encrypting".if_supports_color(Stderr, |t| t.green()),
+                file.display(),
+                archive_path.display()
+            );
+        }
+        return Ok(());
+    }
+
+    let existing = find_archive(&secrets_dir, group);
+    let mut entries = match &existing {
+        Some(existing) => {
+            let decrypted = Zeroizing::new(handler.decrypt(group, existing)?);
+            archive::unpack(&decrypted).map_err(|err| {
+                errors::print(format!("{}: {err}", existing.display()));
+                TuckrError::DecryptionFailed
+            })?
+        }
+        None => Vec::new(),
     };
 
-    for dotfile in dotfiles {
-        let dotfile = Path::new(dotfile).canonicalize().unwrap();
+    for (path, file) in files {
+        let (Ok(contents), Ok(metadata)) = (fs::read(file), SecretMetadata::of(file)) else {
+            errors::print(error!("x_doesnt_exist", x = file.display()));
+            return Err(TuckrError::NoSuchFileOrDir);
+        };
 
-        if dotfile.is_dir() {
-            let Ok(dir) = dotfile.read_dir() else {
-                eprintln!("{}", t!("errors.x_doesnt_exist", x = dotfile.display()));
-                return Err(ExitCode::FAILURE);
-            };
+        entries.retain(|entry| entry.path != *path);
+        entries.push(archive::Entry {
+            path: path.clone(),
+            kind: archive::EntryKind::File,
+            mode: metadata.mode,
+            mtime: metadata.mtime,
+            contents: Zeroizing::new(contents),
+        });
+    }
 
-            for file in dir {
-                let file = file.unwrap().path();
-                encrypt_file(&file)?;
-            }
-        } else if dotfile.is_file() {
-            encrypt_file(&dotfile)?;
+    let encrypted = archive::pack(&entries).and_then(|packed| {
+        let backend = handler.backend(group)?;
+        Ok(backend.encrypt(&packed)?)
+    });
+    let encrypted = match encrypted {
+        Ok(encrypted) => encrypted,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::EncryptionFailed);
         }
+    };
+
+    if let Err(err) =
+        fs::create_dir_all(&secrets_dir).and_then(|_| fs::write(&archive_path, encrypted))
+    {
+        errors::print(format!("{}: {err}", archive_path.display()));
+        return Err(TuckrError::EncryptionFailed);
+    }
+
+    // the archive is renamed if the group's backend was changed
+    if let Some(existing) = existing
+        && existing != archive_path
+        && let Err(err) = fs::remove_file(&existing)
+    {
+        errors::print(format!("{}: {err}", existing.display()));
+        return Err(TuckrError::Failed);
     }
 
     Ok(())
@@ -174,81 +1070,1283 @@ pub fn decrypt_cmd(
     dry_run: bool,
     groups: &[String],
     exclude: &[String],
-) -> Result<(), ExitCode> {
+) -> Result<(), TuckrError> {
     let handler = SecretsHandler::try_new(profile.clone())?;
 
     if let Some(invalid_groups) =
-        dotfiles::check_invalid_groups(profile, dotfiles::DotfileType::Secrets, groups)
+        dotfiles::check_invalid_groups(profile.clone(), dotfiles::DotfileType::Secrets, groups)
     {
         for group in invalid_groups {
-            eprintln!("{}", t!("errors.no_group", group = group).red());
+            errors::print(error!("no_group", group = group));
         }
-        return Err(ReturnCode::DecryptionFailed.into());
+        return Err(TuckrError::DecryptionFailed);
     }
 
+    // what was decrypted is recorded for `tuckr secrets status`
+    let state = RefCell::new(state::load_or_report(profile)?);
+
     let target_dir = match dotfiles::get_dotfiles_target_dir_path() {
         Ok(dir) => dir,
         Err(err) => {
-            eprintln!("{}", err.red());
-            return Err(ReturnCode::NoSuchFileOrDir.into());
+            errors::print(err);
+            return Err(TuckrError::NoSuchFileOrDir);
+        }
+    };
+
+    let decrypt_group = |group: SecretGroup| -> Result<(), TuckrError> {
+        if dotfiles::is_excluded(&group.name, exclude)
+            || !dotfiles::group_is_valid_target(&group.name)
+        {
+            return Ok(());
+        }
+
+        if dry_run {
+            for secret in group.encrypted_files() {
+                eprintln!(
+                    "{} `{}` into `{}`",
+                    "decrypting".if_supports_color(Stderr, |t| t.green()),
+                    secret.display(),
+                    group.destination(&secret, &target_dir).display()
+                );
+            }
+            return Ok(());
+        }
+
+        for secret in handler.decrypt_group(&group, &target_dir)? {
+            let written = match secret.dest.parent() {
+                Some(dir) => fs::create_dir_all(dir).and_then(|_| secret.write()),
+                None => secret.write(),
+            };
+            if let Err(err) = written {
+                errors::print(format!("{}: {err}", secret.dest.display()));
+                return Err(TuckrError::DecryptionFailed);
+            }
+            state.borrow_mut().secrets.insert(
+                secret.dest.clone(),
+                DecryptedFile::new(&group.name, &secret.source, &secret.contents),
+            );
         }
+
+        Ok(())
     };
 
-    let decrypt_group = |group: Dotfile| -> Result<(), ExitCode> {
-        if exclude.contains(&group.group_name) || !group.is_valid_target() {
+    // the bar is only drawn once the first group is decrypted, after the password was asked for
+    let groups = handler.get_groups(groups);
+    let progress = Progress::new(groups.len(), "decrypting");
+    let decrypted = groups.into_iter().try_for_each(|group| {
+        decrypt_group(group)?;
+        progress.inc();
+        Ok(())
+    });
+
+    // the groups that were decrypted before one failed are recorded too
+    if !dry_run && let Err(err) = state.into_inner().save() {
+        errors::print(err);
+        return Err(TuckrError::Failed);
+    }
+
+    decrypted
+}
+
+/// Writes a decrypted secret so that only its owner can read it
+fn write_secret(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+
+    // the mode is only used when the file is created
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+
+    file.write_all(contents)
+}
+
+/// Where a group keeps its encrypted hooks, `Secrets/<group>/hooks`
+pub const HOOKS_DIR: &str = "hooks";
+
+/// Returns the files in a directory of encrypted hooks, sorted by their names
+fn list_hooks(dir: &Path) -> Vec<PathBuf> {
+    let mut hooks: Vec<PathBuf> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|file| file.is_file())
+        .collect();
+    hooks.sort();
+    hooks
+}
+
+/// Creates a directory that only its owner can use, it mustn't exist yet so that nobody else could have made it
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+
+    builder.create(dir)
+}
+
+/// Overwrites a file with zeros before removing it, so that what it contained doesn't stay on the disk
+fn shred(file: &Path) -> std::io::Result<()> {
+    let len = fs::metadata(file)?.len();
+    let mut contents = fs::OpenOptions::new().write(true).open(file)?;
+    contents.write_all(&vec![0; len as usize])?;
+    contents.sync_all()?;
+    fs::remove_file(file)
+}
+
+/// Prefix of the temporary directories that encrypted hooks are decrypted into, followed by the
+/// pid of the tuckr that decrypted them and the group
+const DECRYPTED_HOOKS_PREFIX: &str = "tuckr-hooks-";
+
+/// Returns the directory that encrypted hooks are decrypted into
+///
+/// The temporary directory is often mounted with noexec, which would keep the hooks from running,
+/// so `$XDG_RUNTIME_DIR` is used instead since it's kept in memory and only the user can get into
+/// it. Where there's none they go into tuckr's cache directory
+fn decrypted_hooks_parent() -> PathBuf {
+    dirs::runtime_dir()
+        .or_else(|| dirs::cache_dir().map(|dir| dir.join("tuckr")))
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+/// A group's hooks that are encrypted in `Secrets/<group>/hooks`, e.g. because they use API keys
+///
+/// They're decrypted into a directory that only the user can read, run along with the
+/// group's other hooks and shredded once they're dropped. If tuckr is interrupted before then
+/// they're removed by the Ctrl-C handler, and what a tuckr that was killed left behind is removed
+/// by the next one, see [`remove_stale_hooks`].
+#[derive(Default)]
+pub struct EncryptedHooks {
+    group: String,
+    hooks: Vec<PathBuf>,
+    /// where the hooks were decrypted to
+    decrypted_dir: Option<PathBuf>,
+}
+
+impl EncryptedHooks {
+    /// Returns the group's encrypted hooks, profiles use the shared ones if they don't have their own
+    pub fn find(profile: Option<String>, group: &str) -> Self {
+        let hooks = dotfiles::find_group_dir(profile, dotfiles::DotfileType::Secrets, group)
+            .map(|dir| list_hooks(&dir.join(HOOKS_DIR)))
+            .unwrap_or_default();
+
+        EncryptedHooks {
+            group: group.into(),
+            hooks,
+            decrypted_dir: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Decrypts the hooks so that they can be run, the password is only asked for if there are any
+    pub fn decrypt(&mut self, profile: Option<String>) -> Result<(), TuckrError> {
+        if self.hooks.is_empty() || self.decrypted_dir.is_some() {
             return Ok(());
         }
 
-        let group_dir = handler.dotfiles_dir.join("Secrets").join(&group.group_path);
-        for secret in DirWalk::new(&group_dir) {
-            if secret.is_dir() {
+        let handler = SecretsHandler::try_new(profile)?;
+        let parent = decrypted_hooks_parent();
+        let dir = parent.join(format!(
+            "{DECRYPTED_HOOKS_PREFIX}{}-{}",
+            std::process::id(),
+            self.group
+        ));
+        if let Err(err) = fs::create_dir_all(&parent).and_then(|_| create_private_dir(&dir)) {
+            errors::print(format!("{}: {err}", dir.display()));
+            return Err(TuckrError::DecryptionFailed);
+        }
+        // set right away so that the hooks decrypted before one that fails are shredded too
+        self.decrypted_dir = Some(dir.clone());
+        cancel::remove_on_interrupt(&dir);
+
+        for hook in &self.hooks {
+            let decrypted = Zeroizing::new(handler.decrypt(&self.group, hook)?);
+            let (_, contents) = SecretMetadata::split(&decrypted);
+            let path = dir.join(hook.file_name().unwrap());
+            cancel::remove_on_interrupt(&path);
+
+            let written = write_secret(&path, contents).and_then(|_| {
+                #[cfg(target_family = "unix")]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    fs::set_permissions(&path, fs::Permissions::from_mode(0o700))?;
+                }
+                Ok(())
+            });
+            if let Err(err) = written {
+                errors::print(format!("{}: {err}", path.display()));
+                return Err(TuckrError::DecryptionFailed);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the hooks to run, the encrypted ones if they weren't decrypted, e.g. for dry runs
+    pub fn files(&self) -> Vec<PathBuf> {
+        match &self.decrypted_dir {
+            Some(dir) => self
+                .hooks
+                .iter()
+                .map(|hook| dir.join(hook.file_name().unwrap()))
+                .collect(),
+            None => self.hooks.clone(),
+        }
+    }
+
+    /// Returns the encrypted hook that a decrypted hook comes from
+    pub fn source(&self, hook: &Path) -> Option<&Path> {
+        let name = hook.strip_prefix(self.decrypted_dir.as_ref()?).ok()?;
+        self.hooks
+            .iter()
+            .find(|source| source.file_name() == Some(name.as_os_str()))
+            .map(PathBuf::as_path)
+    }
+}
+
+impl Drop for EncryptedHooks {
+    fn drop(&mut self) {
+        let Some(dir) = self.decrypted_dir.take() else {
+            return;
+        };
+
+        for hook in list_hooks(&dir) {
+            if let Err(err) = shred(&hook) {
+                errors::print(format!("{}: {err}", hook.display()));
+            }
+            cancel::forget_on_interrupt(&hook);
+        }
+        _ = fs::remove_dir_all(&dir);
+        cancel::forget_on_interrupt(&dir);
+    }
+}
+
+/// Shreds the hooks that tuckrs which were killed left decrypted, see [`decrypted_hooks_parent`]
+///
+/// Only the directories of the user's own tuckrs that aren't running anymore are removed. Whether
+/// a process is running can't be looked up everywhere, so this only happens on unix-like systems
+pub fn remove_stale_hooks() {
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::MetadataExt;
+
+        let Ok(entries) = fs::read_dir(decrypted_hooks_parent()) else {
+            return;
+        };
+        // SAFETY: geteuid can't fail
+        let uid = unsafe { libc::geteuid() };
+
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let Some(pid) = name
+                .to_str()
+                .and_then(|name| name.strip_prefix(DECRYPTED_HOOKS_PREFIX))
+                .and_then(|rest| rest.split_once('-'))
+                .and_then(|(pid, _)| pid.parse::<libc::pid_t>().ok())
+            else {
+                continue;
+            };
+
+            // symlinks aren't followed so that nothing outside of the directory gets removed
+            let is_own_dir = entry
+                .path()
+                .symlink_metadata()
+                .is_ok_and(|meta| meta.is_dir() && meta.uid() == uid);
+            // SAFETY: signal 0 only checks whether the process exists
+            let is_running = unsafe { libc::kill(pid, 0) } == 0
+                || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM);
+            if !is_own_dir || is_running {
                 continue;
             }
 
-            let base_secret_path = secret.strip_prefix(&group_dir).unwrap();
-            let decrypted_dest = target_dir.join(base_secret_path);
+            let dir = entry.path();
+            tracing::debug!(dir = %dir.display(), "removing hooks that a killed tuckr left decrypted");
+            for hook in list_hooks(&dir) {
+                _ = shred(&hook);
+            }
+            _ = fs::remove_dir_all(&dir);
+        }
+    }
+}
+
+/// Returns true if the group only has secrets, so there's nothing to symlink for it
+pub fn only_has_secrets(profile: Option<String>, group: &str) -> bool {
+    let Ok(dotfiles_dir) = dotfiles::get_dotfiles_path(profile.clone()) else {
+        return false;
+    };
+
+    let has_configs = fs::read_dir(dotfiles_dir.join("Configs"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .any(|config| {
+            let name = config.file_name();
+            let name = name.to_string_lossy();
+            name == group || dotfiles::group_without_target(&name) == group
+        });
+
+    group != "*"
+        && !has_configs
+        && dotfiles::dotfile_contains(profile, dotfiles::DotfileType::Secrets, group)
+}
+
+/// Decrypts the groups' secrets to where they belong, used by `tuckr add`
+///
+/// Just like configs, secrets that would replace a different file are only deployed with `force`,
+/// in which case the file is backed up first. Fails with [`TuckrError::Conflicts`] if any weren't.
+/// The password is only asked for if any of the groups have secrets
+pub fn deploy_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    groups: &[String],
+    exclude: &[String],
+    force: bool,
+) -> Result<(), TuckrError> {
+    let handler = SecretsHandler::try_new(profile.clone())?;
+    let mut state = state::load_or_report(profile.clone())?;
+
+    let target_dir = match dotfiles::get_dotfiles_target_dir_path() {
+        Ok(dir) => dir,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::NoSuchFileOrDir);
+        }
+    };
+
+    let mut backup_set = match BackupSet::try_new(profile.clone()) {
+        Ok(backup_set) => backup_set,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::CouldntFindDotfiles);
+        }
+    };
+
+    // conditional groups come along with their base group
+    let secret_groups = handler
+        .get_groups(&["*".to_string()])
+        .into_iter()
+        .filter(|group| {
+            let name = &group.name;
+            groups.iter().any(|group| {
+                group == "*" || group == name || dotfiles::group_without_target(name) == group
+            }) && dotfiles::group_is_valid_target(name)
+                && !dotfiles::is_excluded(name, exclude)
+                && !state.is_held(name)
+        })
+        .collect::<Vec<_>>();
+
+    let progress = Progress::new(secret_groups.len(), "decrypting");
+    let mut conflicts = Vec::new();
+    for group in secret_groups {
+        if dry_run {
+            for secret in group.encrypted_files() {
+                let target = group.destination(&secret, &target_dir);
+                if !plan::record(Step::Decrypt {
+                    secret: secret.clone(),
+                    target: target.clone(),
+                }) {
+                    eprintln!(
+                        "{} `{}` into `{}`",
+                        "decrypting".if_supports_color(Stderr, |t| t.green()),
+                        secret.display(),
+                        target.display()
+                    );
+                }
+            }
+            continue;
+        }
+
+        cancel::check()?;
+
+        for secret in handler.decrypt_group(&group, &target_dir)? {
+            cancel::check()?;
+            let dest = &secret.dest;
+
+            // symlinks are never written through since they might point into the dotfiles
+            let is_deployed = !dest.is_symlink()
+                && fs::read(dest).is_ok_and(|current| *current == **secret.contents);
+            let exists = dest.exists() || dest.is_symlink();
+
+            if exists && !is_deployed {
+                if !force {
+                    conflicts.push(dest.clone());
+                    continue;
+                }
+
+                if let Err(err) = backup_set.backup(dry_run, dest) {
+                    errors::print(err);
+                    conflicts.push(dest.clone());
+                    continue;
+                }
+            }
+
+            let written = match dest.parent() {
+                Some(dir) => fs::create_dir_all(dir).and_then(|_| secret.write()),
+                None => secret.write(),
+            };
+            if let Err(err) = written {
+                errors::print(format!("{}: {err}", dest.display()));
+                return Err(TuckrError::DecryptionFailed);
+            }
+            state.secrets.insert(
+                dest.clone(),
+                DecryptedFile::new(&group.name, &secret.source, &secret.contents),
+            );
+        }
+
+        progress.inc();
+    }
+
+    if !dry_run && let Err(err) = state.save() {
+        errors::print(err);
+        return Err(TuckrError::Failed);
+    }
+
+    if !backup_set.is_empty() {
+        println!(
+            "{}",
+            t!("info.conflicts_backed_up", backup = backup_set.name())
+                .if_supports_color(Stdout, |t| t.yellow())
+        );
+        backups::apply_retention(profile);
+    }
+
+    if !conflicts.is_empty() {
+        for conflict in &conflicts {
+            eprintln!(
+                "{}",
+                t!("warn.secret_conflict", file = conflict.display())
+                    .if_supports_color(Stderr, |t| t.yellow())
+            );
+        }
+        stats::record_conflicts(conflicts.len());
+        return Err(TuckrError::Conflicts);
+    }
+
+    Ok(())
+}
+
+/// How a deployed secret compares with what was decrypted to it
+#[derive(Debug, PartialEq)]
+enum SecretStatus {
+    /// what was decrypted is still there and the secret hasn't changed since
+    Deployed,
+    /// nothing is where the secret goes
+    Missing,
+    /// the decrypted file was changed after it was decrypted
+    Modified,
+    /// the secret was encrypted again since it was decrypted, e.g. after pulling the dotfiles
+    Outdated,
+    /// something is where the secret goes that tuckr doesn't know of, it can't be compared
+    /// with the secret without decrypting it
+    Unknown,
+}
+
+impl SecretStatus {
+    fn of(state: &State, secret: &Path, target: &Path) -> Self {
+        if !target.exists() {
+            return SecretStatus::Missing;
+        }
+
+        let Some(decrypted) = state
+            .secrets
+            .get(target)
+            .filter(|decrypted| decrypted.source == secret)
+        else {
+            return SecretStatus::Unknown;
+        };
+
+        if fileops::hash_file(target).ok().as_ref() != Some(&decrypted.hash) {
+            SecretStatus::Modified
+        } else if decrypted.source_hash != fileops::hash_file(secret).ok() {
+            SecretStatus::Outdated
+        } else {
+            SecretStatus::Deployed
+        }
+    }
+
+    fn colored(&self) -> String {
+        match self {
+            SecretStatus::Deployed => "deployed"
+                .if_supports_color(Stdout, |t| t.green())
+                .to_string(),
+            SecretStatus::Missing => "not deployed"
+                .if_supports_color(Stdout, |t| t.red())
+                .to_string(),
+            SecretStatus::Modified => "modified"
+                .if_supports_color(Stdout, |t| t.yellow())
+                .to_string(),
+            SecretStatus::Outdated => "outdated"
+                .if_supports_color(Stdout, |t| t.yellow())
+                .to_string(),
+            SecretStatus::Unknown => "unknown"
+                .if_supports_color(Stdout, |t| t.dimmed())
+                .to_string(),
+        }
+    }
+}
+
+/// Lists the groups' secrets along with where they're decrypted to and whether they're deployed
+///
+/// Nothing is decrypted so no password is needed, the deployed secrets are compared with the hashes
+/// that were recorded when they were decrypted. The files of an archive are only known once it was
+/// decrypted on this machine.
+pub fn status_cmd(
+    profile: Option<String>,
+    groups: &[String],
+    exclude: &[String],
+) -> Result<(), TuckrError> {
+    if let Some(invalid_groups) =
+        dotfiles::check_invalid_groups(profile.clone(), dotfiles::DotfileType::Secrets, groups)
+    {
+        for group in invalid_groups {
+            errors::print(error!("no_group", group = group));
+        }
+        return Err(TuckrError::NoSuchFileOrDir);
+    }
+
+    let handler = SecretsHandler::try_new(profile.clone())?;
+    let state = state::load_or_report(profile)?;
+    let target_dir = match dotfiles::get_dotfiles_target_dir_path() {
+        Ok(dir) => dir,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::NoSuchFileOrDir);
+        }
+    };
+
+    #[derive(Tabled)]
+    struct StatusRow {
+        #[tabled(rename = "Group")]
+        group: String,
+        #[tabled(rename = "Secret")]
+        secret: String,
+        #[tabled(rename = "Target")]
+        target: String,
+        #[tabled(rename = "Status")]
+        status: String,
+    }
+
+    let mut rows = Vec::new();
+    for group in handler.get_groups(groups) {
+        if dotfiles::is_excluded(&group.name, exclude)
+            || !dotfiles::group_is_valid_target(&group.name)
+        {
+            continue;
+        }
+
+        for secret in group.encrypted_files() {
+            let is_archive = group.archive.as_ref() == Some(&secret);
+            let name = match is_archive {
+                true => secret.file_name().unwrap_or_default().to_string_lossy(),
+                false => secret.strip_prefix(&group.dir).unwrap().to_string_lossy(),
+            };
+
+            let targets: Vec<_> = match is_archive {
+                true => state
+                    .secrets
+                    .iter()
+                    .filter(|(_, decrypted)| decrypted.source == secret)
+                    .map(|(target, _)| target.clone())
+                    .collect(),
+                false => vec![group.destination(&secret, &target_dir)],
+            };
+
+            if targets.is_empty() {
+                rows.push(StatusRow {
+                    group: group.name.clone(),
+                    secret: name.to_string(),
+                    target: "-".into(),
+                    status: SecretStatus::Missing.colored(),
+                });
+            }
+
+            for target in targets {
+                rows.push(StatusRow {
+                    group: group.name.clone(),
+                    secret: name.to_string(),
+                    status: SecretStatus::of(&state, &secret, &target).colored(),
+                    target: target.display().to_string(),
+                });
+            }
+        }
+    }
+
+    if rows.is_empty() {
+        println!(
+            "{}",
+            t!("errors.no_x_setup_yet", x = "secrets").if_supports_color(Stdout, |t| t.yellow())
+        );
+        return Ok(());
+    }
+
+    table::print(&rows);
+    Ok(())
+}
+
+/// Checks that the deployed secrets can only be used by their owner, `fix` tightens their permissions
+///
+/// Archives have to be decrypted to know which files they deployed, so the password is only
+/// asked for if any of the groups have one
+pub fn audit_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    groups: &[String],
+    exclude: &[String],
+    fix: bool,
+) -> Result<(), TuckrError> {
+    if let Some(invalid_groups) =
+        dotfiles::check_invalid_groups(profile.clone(), dotfiles::DotfileType::Secrets, groups)
+    {
+        for group in invalid_groups {
+            errors::print(error!("no_group", group = group));
+        }
+        return Err(TuckrError::NoSuchFileOrDir);
+    }
+
+    let handler = SecretsHandler::try_new(profile)?;
+    let target_dir = match dotfiles::get_dotfiles_target_dir_path() {
+        Ok(dir) => dir,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::NoSuchFileOrDir);
+        }
+    };
+
+    let mut secrets = BTreeSet::new();
+    for group in handler.get_groups(groups) {
+        if dotfiles::is_excluded(&group.name, exclude)
+            || !dotfiles::group_is_valid_target(&group.name)
+        {
+            continue;
+        }
+
+        match &group.archive {
+            Some(_) => {
+                let decrypted = handler.decrypt_group(&group, &target_dir)?;
+                secrets.extend(decrypted.into_iter().map(|secret| secret.dest));
+            }
+            None => secrets.extend(
+                group
+                    .encrypted_files()
+                    .iter()
+                    .map(|secret| group.destination(secret, &target_dir)),
+            ),
+        }
+    }
+
+    // secrets in the same directory share its issues
+    let uid = permissions::current_uid();
+    let issues: BTreeSet<_> = secrets
+        .iter()
+        .flat_map(|secret| permissions::check(secret, &target_dir, uid))
+        .collect();
+
+    if issues.is_empty() {
+        println!(
+            "{}",
+            t!("info.no_permission_issues").if_supports_color(Stdout, |t| t.green())
+        );
+        return Ok(());
+    }
+
+    if !fix {
+        for issue in &issues {
+            eprintln!(
+                "{}",
+                issue.describe().if_supports_color(Stderr, |t| t.yellow())
+            );
+        }
+        println!(
+            "\n{}",
+            t!(
+                "info.learn_how_to_fix_permissions",
+                count = issues.len(),
+                cmd = "tuckr secrets audit --fix"
+            )
+        );
+        return Err(TuckrError::Failed);
+    }
+
+    let mut failed = false;
+    for issue in &issues {
+        if let Err(err) = issue.fix(dry_run) {
+            errors::print(format!("{}: {err}", issue.path().display()));
+            failed = true;
+        }
+    }
+
+    if failed {
+        return Err(TuckrError::Failed);
+    }
+    if !dry_run {
+        println!(
+            "{}",
+            t!("info.permissions_fixed", count = issues.len())
+                .if_supports_color(Stdout, |t| t.green())
+        );
+    }
+
+    Ok(())
+}
+
+/// Encrypts secrets that are in an outdated format again, e.g. password encrypted secrets from before Argon2id was used
+pub fn migrate_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    groups: &[String],
+    exclude: &[String],
+) -> Result<(), TuckrError> {
+    if let Some(invalid_groups) =
+        dotfiles::check_invalid_groups(profile.clone(), dotfiles::DotfileType::Secrets, groups)
+    {
+        for group in invalid_groups {
+            errors::print(error!("no_group", group = group));
+        }
+        return Err(TuckrError::NoSuchFileOrDir);
+    }
+
+    let handler = SecretsHandler::try_new(profile)?;
+    let mut migrated = 0;
+
+    for group in handler.get_groups(groups) {
+        if dotfiles::is_excluded(&group.name, exclude) {
+            continue;
+        }
+
+        let kind = config::get().secrets_backend(&group.name);
+
+        // archives are encrypted as a whole so they're migrated like any other secret
+        for secret in group
+            .encrypted_files()
+            .into_iter()
+            .chain(group.encrypted_hooks())
+        {
+            let contents = fs::read(&secret).map_err(|err| {
+                errors::print(format!("{}: {err}", secret.display()));
+                TuckrError::Failed
+            })?;
+
+            if !is_outdated(kind, &contents) {
+                continue;
+            }
 
             if dry_run {
                 eprintln!(
-                    "{} `{}` into `{}`",
-                    "decrypting".green(),
-                    secret.display(),
-                    decrypted_dest.display()
+                    "{} `{}`",
+                    "migrating".if_supports_color(Stderr, |t| t.green()),
+                    secret.display()
                 );
                 continue;
             }
 
-            let decrypted_parent_dir = decrypted_dest.parent().unwrap();
-            fs::create_dir_all(decrypted_parent_dir).unwrap();
+            // only asks for the password once there's something to migrate
+            let migrate = |contents: &[u8]| -> Result<(), errors::Error> {
+                let backend = handler.backend(&group.name)?;
+                let decrypted = Zeroizing::new(backend.decrypt(contents)?);
+                let encrypted = backend.encrypt(&decrypted)?;
+                fs::write(&secret, encrypted)
+                    .map_err(|e| format!("{}: {e}", secret.display()).into())
+            };
 
-            let decrypted = handler.decrypt(secret.to_str().unwrap())?;
-            fs::write(decrypted_dest, decrypted).unwrap();
+            if let Err(err) = migrate(&contents) {
+                errors::print(err);
+                return Err(TuckrError::EncryptionFailed);
+            }
+            migrated += 1;
         }
+    }
 
-        Ok(())
-    };
+    if !dry_run {
+        println!(
+            "{}",
+            t!("info.migrated_secrets", count = migrated).if_supports_color(Stdout, |t| t.green())
+        );
+    }
 
-    if groups.contains(&"*".to_string()) {
-        let groups_dir = handler.dotfiles_dir.join("Secrets");
-        for group in fs::read_dir(groups_dir).unwrap() {
-            let Ok(group) = Dotfile::try_from(group.unwrap().path()) else {
-                eprintln!("{}", t!("errors.got_invalid_group").red());
-                return Err(ExitCode::FAILURE);
-            };
-            decrypt_group(group)?;
+    Ok(())
+}
+
+/// Encrypts every secret that's encrypted with a password again with a new one
+///
+/// Every secret is encrypted into a file next to it first, which is decrypted again to check it.
+/// Only once all of them made it are the secrets replaced, so that nothing is changed if the
+/// password is wrong or a secret can't be decrypted. The old secrets are put back if replacing any
+/// of them fails.
+pub fn rekey_cmd(profile: Option<String>, dry_run: bool) -> Result<(), TuckrError> {
+    let handler = SecretsHandler::try_new(profile.clone())?;
+
+    // age and gpg secrets are encrypted to keys, which are rotated by changing their recipients
+    let secrets: Vec<_> = handler
+        .get_groups(&["*".to_string()])
+        .into_iter()
+        .filter(|group| config::get().secrets_backend(&group.name) == SecretsBackend::Password)
+        .flat_map(|group| {
+            group
+                .encrypted_files()
+                .into_iter()
+                .chain(group.encrypted_hooks())
+                .map(move |secret| (group.name.clone(), secret))
+        })
+        .collect();
+
+    if dry_run {
+        for (_, secret) in &secrets {
+            eprintln!(
+                "{} `{}`",
+                "rekeying".if_supports_color(Stderr, |t| t.green()),
+                secret.display()
+            );
         }
+        return Ok(());
+    }
 
+    if secrets.is_empty() {
+        println!(
+            "{}",
+            t!("errors.no_x_setup_yet", x = "secrets").if_supports_color(Stdout, |t| t.yellow())
+        );
         return Ok(());
     }
 
-    for group in groups {
-        let group = handler.dotfiles_dir.join("Secrets").join(group);
-        let Ok(group) = Dotfile::try_from(group) else {
-            eprintln!("{}", t!("errors.got_invalid_group").red());
-            return Err(ExitCode::FAILURE);
-        };
-        decrypt_group(group)?;
+    let old_backend = handler.backend(&secrets[0].0).map_err(|err| {
+        errors::print(err);
+        TuckrError::DecryptionFailed
+    })?;
+    let new_backend = match read_new_password() {
+        Ok(password) => PasswordBackend::new(password, KdfParams::DEFAULT),
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::EncryptionFailed);
+        }
+    };
+
+    let with_suffix = |secret: &Path, suffix: &str| {
+        let mut name = secret.file_name().unwrap_or_default().to_os_string();
+        name.push(suffix);
+        secret.with_file_name(name)
+    };
+    let rekeyed_path = |secret: &Path| with_suffix(secret, REKEYED_SUFFIX);
+    let old_path = |secret: &Path| with_suffix(secret, OLD_SUFFIX);
+
+    let rekey = |secret: &Path| -> Result<(), TuckrError> {
+        let contents = fs::read(secret).map_err(|err| {
+            errors::print(format!("{}: {err}", secret.display()));
+            TuckrError::Failed
+        })?;
+        let decrypted = old_backend.decrypt(&contents).map(Zeroizing::new);
+        let decrypted = decrypted.map_err(|err| {
+            errors::print(err.prefixed(secret.display()));
+            TuckrError::DecryptionFailed
+        })?;
+
+        let encrypted = new_backend
+            .encrypt(&decrypted)
+            .and_then(|encrypted| match new_backend.decrypt(&encrypted) {
+                Ok(roundtrip) if roundtrip == **decrypted => Ok(encrypted),
+                _ => Err(error!("rekey_verification_failed")),
+            })
+            .map_err(|err| {
+                errors::print(err.prefixed(secret.display()));
+                TuckrError::EncryptionFailed
+            })?;
+
+        fs::write(rekeyed_path(secret), encrypted).map_err(|err| {
+            errors::print(format!("{}: {err}", secret.display()));
+            TuckrError::EncryptionFailed
+        })
+    };
+
+    let progress = Progress::new(secrets.len(), "rekeying");
+    let rekeyed = secrets.iter().try_for_each(|(_, secret)| {
+        progress.start(secret.display());
+        rekey(secret)?;
+        progress.inc();
+        cancel::check()
+    });
+    drop(progress);
+
+    if let Err(err) = rekeyed {
+        for (_, secret) in &secrets {
+            _ = fs::remove_file(rekeyed_path(secret));
+        }
+        return Err(err);
+    }
+
+    // the old secrets are kept until every one of them was replaced, so that they can be put back
+    let replaced = secrets.iter().try_for_each(|(_, secret)| {
+        fs::rename(secret, old_path(secret))
+            .and_then(|_| fs::rename(rekeyed_path(secret), secret))
+            .map_err(|err| {
+                errors::print(format!("{}: {err}", secret.display()));
+                TuckrError::EncryptionFailed
+            })
+    });
+    if let Err(err) = replaced {
+        for (_, secret) in &secrets {
+            if old_path(secret).exists()
+                && let Err(err) = fs::rename(old_path(secret), secret)
+            {
+                errors::print(format!("{}: {err}", secret.display()));
+            }
+            _ = fs::remove_file(rekeyed_path(secret));
+        }
+        return Err(err);
+    }
+
+    // the secrets that were decrypted are still the same, only their encrypted files changed
+    let mut state = state::load_or_report(profile)?;
+    for (_, secret) in &secrets {
+        let old_hash = fileops::hash_file(&old_path(secret)).ok();
+        let new_hash = fileops::hash_file(secret).ok();
+        for decrypted in state.secrets.values_mut() {
+            if decrypted.source == *secret && decrypted.source_hash == old_hash {
+                decrypted.source_hash = new_hash.clone();
+            }
+        }
+
+        if let Err(err) = fs::remove_file(old_path(secret)) {
+            errors::print(format!("{}: {err}", old_path(secret).display()));
+        }
+    }
+
+    if let Err(err) = state.save() {
+        errors::print(err);
+    }
+
+    println!(
+        "{}",
+        t!("info.rekeyed_secrets", count = secrets.len()).if_supports_color(Stdout, |t| t.green())
+    );
+    if config::get().secrets.keyring {
+        println!(
+            "{}",
+            t!("warn.keyring_has_old_password", service = KEYRING_SERVICE)
+                .if_supports_color(Stdout, |t| t.yellow())
+        );
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// cheap enough for debug builds
+    const TEST_PARAMS: KdfParams = KdfParams {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    #[test]
+    fn password_backend_roundtrip() {
+        let backend = PasswordBackend::new(Zeroizing::new("hunter2".into()), TEST_PARAMS);
+
+        let first = backend.encrypt(b"secret").unwrap();
+        let second = backend.encrypt(b"secret").unwrap();
+        assert!(first.starts_with(MAGIC));
+        assert_ne!(
+            first[HEADER_LEN - NONCE_LEN..],
+            second[HEADER_LEN - NONCE_LEN..]
+        );
+        assert_eq!(backend.decrypt(&first).unwrap(), b"secret");
+        assert!(!is_outdated(SecretsBackend::Password, &first));
+
+        let wrong_password = PasswordBackend::new(Zeroizing::new("hunter3".into()), TEST_PARAMS);
+        assert!(wrong_password.decrypt(&first).is_err());
+        assert!(backend.decrypt(b"too short").is_err());
+
+        // the header is authenticated
+        let mut tampered = first.clone();
+        tampered[MAGIC.len() + 1] ^= 1;
+        assert!(backend.decrypt(&tampered).is_err());
+    }
+
+    #[test]
+    fn secret_status() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let secrets_dir = dotfiles_dir.join("Secrets").join("ssh");
+        fs::create_dir_all(&secrets_dir).unwrap();
+
+        let secret = secrets_dir.join("id_ed25519");
+        let target = dotfiles_dir.join("id_ed25519");
+        fs::write(&secret, "encrypted").unwrap();
+
+        let mut state = State::default();
+        assert_eq!(
+            SecretStatus::of(&state, &secret, &target),
+            SecretStatus::Missing
+        );
+
+        fs::write(&target, "key").unwrap();
+        assert_eq!(
+            SecretStatus::of(&state, &secret, &target),
+            SecretStatus::Unknown
+        );
+
+        state
+            .secrets
+            .insert(target.clone(), DecryptedFile::new("ssh", &secret, b"key"));
+        assert_eq!(
+            SecretStatus::of(&state, &secret, &target),
+            SecretStatus::Deployed
+        );
+
+        fs::write(&secret, "encrypted again").unwrap();
+        assert_eq!(
+            SecretStatus::of(&state, &secret, &target),
+            SecretStatus::Outdated
+        );
+
+        fs::write(&target, "edited key").unwrap();
+        assert_eq!(
+            SecretStatus::of(&state, &secret, &target),
+            SecretStatus::Modified
+        );
+
+        fs::remove_dir_all(&dotfiles_dir).unwrap();
+    }
+
+    #[test]
+    fn secret_metadata() {
+        let metadata = SecretMetadata {
+            mode: 0o750,
+            mtime: 1_700_000_000,
+        };
+        let contents = metadata.prepend(b"#!/bin/sh");
+        assert_eq!(
+            SecretMetadata::split(&contents),
+            (Some(metadata), &b"#!/bin/sh"[..])
+        );
+
+        // secrets from older versions don't have a header
+        assert_eq!(SecretMetadata::split(b"key"), (None, &b"key"[..]));
+
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        fs::create_dir_all(&dotfiles_dir).unwrap();
+        let secret = dotfiles_dir.join("script");
+        write_secret(&secret, b"#!/bin/sh").unwrap();
+        metadata.restore(&secret).unwrap();
+
+        let restored = SecretMetadata::of(&secret).unwrap();
+        assert_eq!(restored.mtime, metadata.mtime);
+        #[cfg(target_family = "unix")]
+        assert_eq!(restored.mode, 0o700);
+
+        fs::remove_dir_all(&dotfiles_dir).unwrap();
+    }
+
+    #[test]
+    fn root_secrets_keep_their_full_path() {
+        let target_dir = Path::new("/home/user");
+        assert_eq!(
+            secret_path("ssh", Path::new("/home/user/.ssh/id_ed25519"), target_dir).unwrap(),
+            Path::new(".ssh/id_ed25519")
+        );
+        assert_eq!(
+            secret_path("Root", Path::new("/etc/wireguard/wg0.conf"), target_dir).unwrap(),
+            Path::new("etc/wireguard/wg0.conf")
+        );
+        assert!(secret_path("ssh", Path::new("/etc/wireguard/wg0.conf"), target_dir).is_err());
+    }
+
+    #[test]
+    fn encrypting_missing_files_fails() {
+        let missing = std::env::temp_dir()
+            .join("tuckr_no_such_secret")
+            .join("id_ed25519");
+        let files = [missing.to_string_lossy().into_owned()];
+
+        assert!(encrypt_cmd(None, false, "Root", &files, false, false).is_err());
+        assert!(encrypt_cmd(None, false, "Root", &files, true, false).is_err());
+    }
+
+    #[test]
+    fn unresolved_secret_conflicts_fail() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let secrets_dir = dotfiles_dir.join("Secrets").join("ConflictGroup");
+        fs::create_dir_all(&secrets_dir).unwrap();
+
+        let backend = PasswordBackend::new(Zeroizing::new("hunter2".into()), TEST_PARAMS);
+        fs::write(
+            secrets_dir.join(".tuckr_secret_conflict_test"),
+            backend.encrypt(b"secret").unwrap(),
+        )
+        .unwrap();
+        // SAFETY: no other test reads the password from the environment
+        unsafe { std::env::set_var(PASSWORD_ENV, "hunter2") };
+
+        let target = dirs::home_dir()
+            .unwrap()
+            .join(".tuckr_secret_conflict_test");
+        fs::write(&target, "local").unwrap();
+
+        let groups = ["ConflictGroup".to_string()];
+        assert!(matches!(
+            deploy_cmd(None, false, &groups, &[], false),
+            Err(TuckrError::Conflicts)
+        ));
+        assert_eq!(fs::read_to_string(&target).unwrap(), "local");
+
+        // forcing it backs the file up instead
+        deploy_cmd(None, false, &groups, &[], true).unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "secret");
+
+        fs::remove_file(&target).unwrap();
+        fs::remove_dir_all(&dotfiles_dir).unwrap();
+    }
+
+    #[test]
+    fn secrets_only_groups() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        fs::create_dir_all(dotfiles_dir.join("Configs").join("zsh_unix")).unwrap();
+        fs::create_dir_all(dotfiles_dir.join("Secrets").join("zsh")).unwrap();
+        fs::create_dir_all(dotfiles_dir.join("Secrets").join("ssh")).unwrap();
+
+        let secret = dotfiles_dir.join("Secrets").join("ssh").join("id_ed25519");
+        fs::write(&secret, "readable").unwrap();
+        write_secret(&secret, b"key").unwrap();
+        assert_eq!(fs::read(&secret).unwrap(), b"key");
+
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&secret).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        assert!(only_has_secrets(None, "ssh"));
+        assert!(!only_has_secrets(None, "zsh"));
+        assert!(!only_has_secrets(None, "nvim"));
+        assert!(!only_has_secrets(None, "*"));
+
+        fs::remove_dir_all(&dotfiles_dir).unwrap();
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn remove_hooks_left_by_killed_tuckrs() {
+        let parent = decrypted_hooks_parent();
+        let stale = parent.join(format!("{DECRYPTED_HOOKS_PREFIX}{}-api-token", i32::MAX));
+        let running = parent.join(format!(
+            "{DECRYPTED_HOOKS_PREFIX}{}-stale-test",
+            std::process::id()
+        ));
+        for dir in [&stale, &running] {
+            fs::create_dir_all(dir).unwrap();
+            fs::write(dir.join("post_token.sh"), "echo $TOKEN").unwrap();
+        }
+
+        remove_stale_hooks();
+        assert!(!stale.exists());
+        assert!(running.join("post_token.sh").exists());
+
+        fs::remove_dir_all(&running).unwrap();
+    }
+
+    #[test]
+    fn encrypted_hooks_stay_out_of_the_secrets() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let group_dir = dotfiles_dir.join("Secrets").join("backup");
+        fs::create_dir_all(group_dir.join(HOOKS_DIR)).unwrap();
+        fs::write(group_dir.join("token"), "").unwrap();
+        fs::write(group_dir.join(HOOKS_DIR).join("pre_restic.sh"), "").unwrap();
+
+        let group = SecretGroup {
+            name: "backup".into(),
+            dir: group_dir.clone(),
+            archive: None,
+        };
+        assert_eq!(group.encrypted_files(), [group_dir.join("token")]);
+        assert_eq!(
+            group.encrypted_hooks(),
+            [group_dir.join(HOOKS_DIR).join("pre_restic.sh")]
+        );
+
+        let hooks = EncryptedHooks::find(None, "backup");
+        assert!(!hooks.is_empty());
+        assert_eq!(hooks.files(), group.encrypted_hooks());
+        assert!(EncryptedHooks::find(None, "nvim").is_empty());
+
+        let decrypted = dotfiles_dir.join("decrypted.sh");
+        fs::write(&decrypted, "echo token").unwrap();
+        shred(&decrypted).unwrap();
+        assert!(!decrypted.exists());
+
+        fs::remove_dir_all(&dotfiles_dir).unwrap();
+    }
+
+    #[test]
+    fn find_group_archives() {
+        assert_eq!(archive_group("ssh.tar.age"), Some("ssh"));
+        assert_eq!(archive_group("ssh_linux.tar.enc"), Some("ssh_linux"));
+        assert_eq!(archive_group("ssh.tar.gz"), None);
+        assert_eq!(archive_group(".tar.gpg"), None);
+
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let secrets_dir = dotfiles_dir.join("Secrets");
+        fs::create_dir_all(secrets_dir.join("ssh.tar.enc")).unwrap();
+        fs::create_dir_all(secrets_dir.join("gpg")).unwrap();
+        fs::write(secrets_dir.join("gpg.tar.gpg"), "").unwrap();
+
+        // directories aren't archives, even if they're named like one
+        assert_eq!(find_archive(&secrets_dir, "ssh"), None);
+        assert_eq!(
+            find_archive(&secrets_dir, "gpg"),
+            Some(secrets_dir.join("gpg.tar.gpg"))
+        );
+        assert!(only_has_secrets(None, "gpg"));
+        assert!(dotfiles::dotfile_contains(
+            None,
+            dotfiles::DotfileType::Secrets,
+            "gpg"
+        ));
+
+        fs::remove_dir_all(&dotfiles_dir).unwrap();
+    }
+
+    #[test]
+    fn trim_password_line_breaks() {
+        for password in ["hunter2", "hunter2\n", "hunter2\r\n"] {
+            let password = trim_line_break(Zeroizing::new(password.into()));
+            assert_eq!(password.as_str(), "hunter2");
+        }
+
+        // only the line break is removed, the password might end with whitespace
+        let password = trim_line_break(Zeroizing::new("hunter2 \n".into()));
+        assert_eq!(password.as_str(), "hunter2 ");
+    }
+
+    #[test]
+    fn decrypt_legacy_secrets() {
+        let backend = PasswordBackend::new(Zeroizing::new("hunter2".into()), TEST_PARAMS);
+
+        let cipher = XChaCha20Poly1305::new(&Sha256::digest("hunter2"));
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut rngs::OsRng);
+        let mut legacy = nonce.to_vec();
+        legacy.extend(cipher.encrypt(&nonce, b"secret".as_slice()).unwrap());
+
+        assert!(is_outdated(SecretsBackend::Password, &legacy));
+        assert_eq!(backend.decrypt(&legacy).unwrap(), b"secret");
+    }
+}
@@ -0,0 +1,334 @@
+//! Packs the whole dotfiles directory into a single file and unpacks it on another machine
+//!
+//! `tuckr export <file>` writes the dotfiles, git repository included, as a zstd compressed tar
+//! archive, which is how they can be moved to machines that can't reach their git remote.
+//! What's only meant for this machine, i.e. the state, the backups and the hooks' logs, is left out,
+//! as are the secrets with `--no-secrets`. `tuckr import <file>` unpacks the archive to where
+//! `tuckr init` would create the dotfiles, asking before it replaces any file that's different.
+
+use crate::archive::{self, Entry, EntryKind};
+use crate::dotfiles;
+use crate::errors::{self, TuckrError};
+use crate::fileops::{self, DirWalk};
+use crate::interactive;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::{Stderr, Stdout};
+use rust_i18n::t;
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use zeroize::Zeroizing;
+
+/// how hard the archive is compressed, zstd's default
+const COMPRESSION_LEVEL: i32 = 3;
+
+fn with_path(path: &Path) -> impl Fn(std::io::Error) -> String + '_ {
+    move |err| format!("{}: {err}", path.display())
+}
+
+/// Reads the files of the dotfiles that get exported, symlinks are stored as symlinks instead of
+/// being followed and directories are only stored if they're empty
+fn collect_entries(dotfiles_dir: &Path, no_secrets: bool) -> Result<Vec<Entry>, String> {
+    let root = dotfiles_dir.to_path_buf();
+    let files = DirWalk::new(dotfiles_dir)
+        .follow_symlinks(false)
+        .sorted(true)
+        .ignore(move |file| {
+            let Ok(file) = file.strip_prefix(&root) else {
+                return false;
+            };
+            let is_machine_local = dotfiles::MACHINE_LOCAL
+                .iter()
+                .any(|(local, _)| local.matches(file));
+            is_machine_local || (no_secrets && file.starts_with("Secrets"))
+        });
+
+    let mut entries = Vec::new();
+    for file in files {
+        let metadata = file.symlink_metadata().map_err(with_path(&file))?;
+        let (kind, contents) = if metadata.is_symlink() {
+            let target = fs::read_link(&file).map_err(with_path(&file))?;
+            (EntryKind::Symlink(target), Vec::new())
+        } else if metadata.is_dir() {
+            let mut children = fs::read_dir(&file).map_err(with_path(&file))?;
+            if children.next().is_some() {
+                continue;
+            }
+            (EntryKind::Dir, Vec::new())
+        } else if metadata.is_file() {
+            (EntryKind::File, fs::read(&file).map_err(with_path(&file))?)
+        } else {
+            continue;
+        };
+
+        #[cfg(target_family = "unix")]
+        let mode = {
+            use std::os::unix::fs::PermissionsExt;
+            metadata.permissions().mode()
+        };
+        #[cfg(not(target_family = "unix"))]
+        let mode = if metadata.is_dir() { 0o755 } else { 0o644 };
+
+        entries.push(Entry {
+            path: file.strip_prefix(dotfiles_dir).unwrap().to_path_buf(),
+            kind,
+            mode,
+            mtime: metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|time| time.as_secs())
+                .unwrap_or_default(),
+            contents: Zeroizing::new(contents),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Writes the dotfiles directory to `file` as a compressed archive
+pub fn export_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    file: &Path,
+    no_secrets: bool,
+) -> Result<(), TuckrError> {
+    let dotfiles_dir = dotfiles::get_dotfiles_path(profile).map_err(|err| {
+        errors::print(err);
+        TuckrError::CouldntFindDotfiles
+    })?;
+
+    let exported = collect_entries(&dotfiles_dir, no_secrets)
+        .and_then(|entries| {
+            let archive = archive::pack(&entries)?;
+            let compressed = zstd::encode_all(archive.as_slice(), COMPRESSION_LEVEL)
+                .map_err(|err| err.to_string())?;
+            Ok((entries.len(), compressed))
+        })
+        .and_then(|(count, compressed)| {
+            if dry_run {
+                eprintln!(
+                    "{} file `{}`",
+                    "creating".if_supports_color(Stderr, |t| t.green()),
+                    file.display()
+                );
+            } else {
+                fs::write(file, compressed).map_err(with_path(file))?;
+            }
+            Ok(count)
+        });
+
+    match exported {
+        Ok(count) => {
+            println!(
+                "{}",
+                t!(
+                    "info.dotfiles_exported",
+                    count = count,
+                    file = file.display()
+                )
+                .if_supports_color(Stdout, |t| t.green())
+            );
+            Ok(())
+        }
+        Err(err) => {
+            errors::print(err);
+            Err(TuckrError::Failed)
+        }
+    }
+}
+
+/// Returns true if what's at `dest` is the same as the unpacked entry
+fn is_unchanged(dest: &Path, entry: &Entry) -> bool {
+    match &entry.kind {
+        EntryKind::File => {
+            !dest.is_symlink() && fs::read(dest).is_ok_and(|existing| existing == *entry.contents)
+        }
+        EntryKind::Dir => !dest.is_symlink() && dest.is_dir(),
+        EntryKind::Symlink(target) => fs::read_link(dest).is_ok_and(|existing| existing == *target),
+    }
+}
+
+/// Writes an unpacked file, directory or symlink, returns false if it was left alone
+fn import_entry(dry_run: bool, dotfiles_dir: &Path, entry: &Entry) -> Result<bool, String> {
+    let dest = dotfiles_dir.join(&entry.path);
+
+    if is_unchanged(&dest, entry) {
+        return Ok(false);
+    }
+
+    let exists = dest.symlink_metadata().is_ok();
+    if exists {
+        println!(
+            "{}",
+            t!("info.x_already_exists", x = dest.display())
+                .if_supports_color(Stdout, |t| t.yellow())
+        );
+        if !interactive::confirm(t!("warn.want_to_override")) {
+            return Ok(false);
+        }
+    }
+
+    if dry_run {
+        eprintln!(
+            "{} file `{}`",
+            "creating".if_supports_color(Stderr, |t| t.green()),
+            dest.display()
+        );
+        return Ok(true);
+    }
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(with_path(parent))?;
+    }
+    // what's there is replaced as a whole when it's a different kind of file
+    let is_dir = dest.is_dir() && !dest.is_symlink();
+    if exists && (entry.kind != EntryKind::File || dest.is_symlink() || is_dir) {
+        let removed = match is_dir {
+            true => fs::remove_dir_all(&dest),
+            false => fs::remove_file(&dest),
+        };
+        removed.map_err(with_path(&dest))?;
+    }
+
+    match &entry.kind {
+        EntryKind::File => fs::write(&dest, &*entry.contents).map_err(with_path(&dest))?,
+        EntryKind::Dir => fs::create_dir_all(&dest).map_err(with_path(&dest))?,
+        EntryKind::Symlink(target) => {
+            return fileops::symlink(target, &dest)
+                .map(|_| true)
+                .map_err(with_path(&dest));
+        }
+    }
+
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&dest, fs::Permissions::from_mode(entry.mode & 0o7777))
+            .map_err(with_path(&dest))?;
+    }
+
+    Ok(true)
+}
+
+/// Unpacks an archive written by `tuckr export` into the dotfiles directory
+pub fn import_cmd(profile: Option<String>, dry_run: bool, file: &Path) -> Result<(), TuckrError> {
+    let dotfiles_dir = fileops::get_new_dotfiles_path(profile);
+
+    let entries = fs::read(file)
+        .map_err(with_path(file))
+        .and_then(|compressed| {
+            let archive = zstd::decode_all(compressed.as_slice()).map_err(with_path(file))?;
+            archive::unpack(&archive)
+        });
+    let entries = match entries {
+        Ok(entries) => entries,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::Failed);
+        }
+    };
+
+    let mut failed = false;
+    let mut imported = 0;
+    for entry in &entries {
+        match import_entry(dry_run, &dotfiles_dir, entry) {
+            Ok(true) => imported += 1,
+            Ok(false) => (),
+            Err(err) => {
+                errors::print(err);
+                failed = true;
+            }
+        }
+    }
+
+    println!(
+        "{}",
+        t!(
+            "info.dotfiles_imported",
+            count = imported,
+            dir = dotfiles_dir.display()
+        )
+        .if_supports_color(Stdout, |t| t.green())
+    );
+
+    if failed {
+        Err(TuckrError::Failed)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state;
+    use std::path::PathBuf;
+
+    #[test]
+    fn export_and_import_dotfiles() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let files = [
+            "Configs/zsh/.zshrc",
+            "Hooks/zsh/post.sh",
+            "Secrets/ssh/id_ed25519",
+            state::STATE_FILE,
+        ];
+        for file in files {
+            let file = dotfiles_dir.join(file);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, "Some random content on file").unwrap();
+        }
+        fs::create_dir_all(dotfiles_dir.join("Configs/zsh/.cache")).unwrap();
+        fileops::symlink(
+            Path::new(".zshrc"),
+            &dotfiles_dir.join("Configs/zsh/.zprofile"),
+        )
+        .unwrap();
+
+        let paths = |no_secrets| -> Vec<PathBuf> {
+            collect_entries(&dotfiles_dir, no_secrets)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.path)
+                .collect()
+        };
+        assert_eq!(
+            paths(false),
+            [
+                Path::new("Configs/zsh/.cache"),
+                Path::new("Configs/zsh/.zprofile"),
+                Path::new("Configs/zsh/.zshrc"),
+                Path::new("Hooks/zsh/post.sh"),
+                Path::new("Secrets/ssh/id_ed25519")
+            ]
+        );
+        assert_eq!(
+            paths(true),
+            [
+                Path::new("Configs/zsh/.cache"),
+                Path::new("Configs/zsh/.zprofile"),
+                Path::new("Configs/zsh/.zshrc"),
+                Path::new("Hooks/zsh/post.sh")
+            ]
+        );
+
+        let archive = dotfiles_dir.with_file_name("dotfiles.tar.zst");
+        export_cmd(None, false, &archive, true).unwrap();
+        fs::remove_dir_all(&dotfiles_dir).unwrap();
+
+        import_cmd(None, false, &archive).unwrap();
+        assert_eq!(
+            fs::read_to_string(dotfiles_dir.join("Configs/zsh/.zshrc")).unwrap(),
+            "Some random content on file"
+        );
+        assert!(dotfiles_dir.join("Configs/zsh/.cache").is_dir());
+        assert_eq!(
+            fs::read_link(dotfiles_dir.join("Configs/zsh/.zprofile")).unwrap(),
+            Path::new(".zshrc")
+        );
+        assert!(!dotfiles_dir.join("Secrets").exists());
+
+        fs::remove_dir_all(dotfiles_dir.parent().unwrap()).unwrap();
+    }
+}
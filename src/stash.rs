@@ -0,0 +1,201 @@
+//! Temporarily removes deployed groups
+//!
+//! `tuckr stash <group...>` removes the groups' dotfiles from the system without running their hooks
+//! and records them in the state, `tuckr stash pop` deploys the most recently stashed groups the
+//! same way they were deployed before. This is useful to try out a program's default configuration
+//! without losing track of what was deployed.
+
+use crate::dotfiles;
+use crate::error;
+use crate::errors::{self, TuckrError};
+use crate::state::{self, Stash};
+use crate::symlinks;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::Stdout;
+use rust_i18n::t;
+use std::collections::BTreeMap;
+
+fn save(state: &state::State) -> Result<(), TuckrError> {
+    state.save().map_err(|err| {
+        errors::print(err);
+        TuckrError::Failed
+    })
+}
+
+/// Returns the deployed groups that belong to `group`, conditional groups included
+fn related_groups<'a>(
+    deployed: &'a BTreeMap<String, bool>,
+    group: &'a str,
+) -> impl Iterator<Item = (&'a String, &'a bool)> {
+    deployed
+        .iter()
+        .filter(move |(g, _)| *g == group || dotfiles::group_without_target(g) == group)
+}
+
+/// Removes the groups' dotfiles and records them so that they can be deployed again
+pub fn stash_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    groups: &[String],
+) -> Result<(), TuckrError> {
+    let deployed = symlinks::get_deployed_groups(profile.clone())?;
+
+    let not_deployed: Vec<_> = groups
+        .iter()
+        .filter(|group| related_groups(&deployed, group).next().is_none())
+        .collect();
+    if !not_deployed.is_empty() {
+        for group in not_deployed {
+            errors::print(error!("group_is_not_deployed", group = group));
+        }
+        return Err(TuckrError::NoSetupFolder);
+    }
+
+    let stash = Stash {
+        groups: groups.to_vec(),
+        copied: groups
+            .iter()
+            .filter(|group| related_groups(&deployed, group).any(|(_, copied)| *copied))
+            .cloned()
+            .collect(),
+    };
+
+    symlinks::remove_cmd(profile.clone(), dry_run, groups, &[])?;
+
+    if dry_run {
+        return Ok(());
+    }
+
+    // removing the groups saves the state so it has to be loaded afterwards
+    let mut state = state::load_or_report(profile)?;
+    state.stashes.push(stash);
+    save(&state)?;
+
+    println!(
+        "{}",
+        t!(
+            "info.groups_stashed",
+            groups = groups.join(", "),
+            cmd = "tuckr stash pop"
+        )
+        .if_supports_color(Stdout, |t| t.green())
+    );
+    Ok(())
+}
+
+/// Deploys the most recently stashed groups again
+///
+/// The stash is kept if any of its groups could not be deployed, e.g. because of conflicts
+pub fn pop_cmd(profile: Option<String>, dry_run: bool) -> Result<(), TuckrError> {
+    let Some(stash) = state::load_or_report(profile.clone())?.stashes.pop() else {
+        println!(
+            "{}",
+            t!("info.no_stashes").if_supports_color(Stdout, |t| t.yellow())
+        );
+        return Ok(());
+    };
+
+    let (copied, symlinked): (Vec<_>, Vec<_>) = stash
+        .groups
+        .iter()
+        .cloned()
+        .partition(|group| stash.copied.contains(group));
+
+    for (groups, copy) in [(symlinked, false), (copied, true)] {
+        if groups.is_empty() {
+            continue;
+        }
+
+        // conflicts are reported by add, whether everything got deployed is checked afterwards
+        _ = symlinks::add_cmd(
+            profile.clone(),
+            dry_run,
+            false,
+            copy,
+            &groups,
+            &[],
+            false,
+            false,
+            true,
+        );
+    }
+
+    if dry_run {
+        return Ok(());
+    }
+
+    let deployed = symlinks::get_deployed_groups(profile.clone())?;
+    if stash
+        .groups
+        .iter()
+        .any(|group| related_groups(&deployed, group).next().is_none())
+    {
+        errors::print(error!("stash_not_fully_deployed"));
+        return Err(TuckrError::Failed);
+    }
+
+    // deploying the groups saves the state so it has to be loaded afterwards
+    let mut state = state::load_or_report(profile)?;
+    state.stashes.pop();
+    save(&state)
+}
+
+/// Lists the stashed groups, the most recent stash comes first
+pub fn list_cmd(profile: Option<String>) -> Result<(), TuckrError> {
+    let state = state::load_or_report(profile)?;
+
+    if state.stashes.is_empty() {
+        println!(
+            "{}",
+            t!("info.no_stashes").if_supports_color(Stdout, |t| t.yellow())
+        );
+        return Ok(());
+    }
+
+    for (i, stash) in state.stashes.iter().rev().enumerate() {
+        println!(
+            "{}: {}",
+            i.if_supports_color(Stdout, |t| t.yellow()),
+            stash.groups.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dotfiles::Dotfile;
+    use crate::symlinks::tests::Test;
+    use std::fs;
+
+    #[test]
+    fn stash_and_pop_group() {
+        let mut test = Test::start();
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let file = dotfiles_dir
+            .join("Configs")
+            .join("StashGroup")
+            .join(".tuckr_stash_test");
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(&file, "stashed").unwrap();
+        test.track(file.clone());
+        let target = Dotfile::try_from(file).unwrap().to_target_path().unwrap();
+
+        let groups = ["StashGroup".to_string()];
+        assert!(stash_cmd(None, false, &groups).is_err());
+
+        symlinks::add_cmd(None, false, false, false, &groups, &[], false, false, false).unwrap();
+        stash_cmd(None, false, &groups).unwrap();
+        assert!(!target.is_symlink());
+        assert_eq!(state::State::load(None).unwrap().stashes.len(), 1);
+
+        pop_cmd(None, false).unwrap();
+        assert!(target.is_symlink());
+        assert!(state::State::load(None).unwrap().stashes.is_empty());
+
+        symlinks::remove_cmd(None, false, &groups, &[]).unwrap();
+        assert!(!target.exists());
+    }
+}
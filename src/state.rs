@@ -0,0 +1,414 @@
+//! Keeps track of machine-local state
+//!
+//! The state is stored in dotfiles/.tuckr-state.json and holds information that only makes sense
+//! for the machine it was created on, so it should not be shared along with the dotfiles.
+//!
+//! Every file that's deployed is recorded in it along with the group and the dotfile it came from,
+//! so that what was deployed can still be cleaned up after its group was renamed or deleted.
+
+use crate::dotfiles::{self, DotfileType};
+use crate::error;
+use crate::errors::{self, TuckrError};
+use crate::fileops;
+use crate::history;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::{Stderr, Stdout};
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// the state is machine-local so it must never be committed to the dotfiles repo
+pub const STATE_FILE: &str = ".tuckr-state.json";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct State {
+    #[serde(skip)]
+    path: PathBuf,
+
+    /// freeform notes attached to groups
+    pub notes: BTreeMap<String, Vec<String>>,
+
+    /// groups that are frozen at their current deployment
+    pub held: BTreeSet<String>,
+
+    /// dotfiles that were deployed by copying them, indexed by the path they were copied to
+    pub copies: BTreeMap<PathBuf, CopiedFile>,
+
+    /// groups that were temporarily removed with `tuckr stash`, the most recent one is last
+    pub stashes: Vec<Stash>,
+
+    /// groups deployed from another branch with `tuckr try`
+    pub experiments: BTreeMap<String, Experiment>,
+
+    /// groups that changed upstream which `tuckr daemon` couldn't deploy yet, e.g. because of conflicts
+    pub pending_updates: BTreeSet<String>,
+
+    /// every file that's deployed, indexed by the path it was deployed to
+    pub deployed: BTreeMap<PathBuf, DeployedFile>,
+
+    /// junctions and hardlinks deployed where symlinks can't be created, indexed by the path they were created at
+    pub links: BTreeMap<PathBuf, LinkedFile>,
+
+    /// secrets that were decrypted, indexed by the path they were decrypted to
+    pub secrets: BTreeMap<PathBuf, DecryptedFile>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeployMethod {
+    Symlink,
+    Copy,
+    Template,
+    /// directory junction, used on Windows when symlinks can't be created
+    Junction,
+    /// used on Windows when symlinks can't be created
+    Hardlink,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeployedFile {
+    pub group: String,
+    /// the dotfile it was deployed from
+    pub source: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub profile: Option<String>,
+    pub method: DeployMethod,
+    /// seconds since the unix epoch
+    pub deployed_at: u64,
+}
+
+impl DeployedFile {
+    pub fn new(group: &str, source: &Path, profile: Option<String>, method: DeployMethod) -> Self {
+        Self {
+            group: group.into(),
+            source: source.into(),
+            profile,
+            method,
+            deployed_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Returns true if the dotfile it was deployed from is no longer in the dotfiles,
+    /// e.g. because its group was renamed or deleted
+    pub fn is_orphaned(&self) -> bool {
+        !self.source.exists()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Experiment {
+    pub branch: String,
+    /// git worktree the branch was checked out to
+    pub worktree: PathBuf,
+    /// symlinks that point into the worktree
+    pub files: Vec<PathBuf>,
+    /// how the group was deployed before the experiment, None if it wasn't deployed
+    pub copied: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stash {
+    pub groups: Vec<String>,
+    /// groups that were deployed by copying them so that they're copied again
+    pub copied: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinkedFile {
+    /// the dotfile that was linked
+    pub source: PathBuf,
+    /// either a junction or a hardlink
+    pub method: DeployMethod,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CopiedFile {
+    /// the dotfile that was copied
+    pub source: PathBuf,
+    /// sha256 of the copy at the time it was deployed, directories don't have one
+    pub hash: Option<String>,
+}
+
+impl CopiedFile {
+    /// Returns true if the copy was edited since it was deployed, e.g. by an app that saved its settings
+    pub fn was_modified(&self, target: &Path) -> bool {
+        self.hash.is_some() && fileops::hash_file(target).ok() != self.hash
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DecryptedFile {
+    pub group: String,
+    /// the encrypted file, or the archive it was unpacked from
+    pub source: PathBuf,
+    /// sha256 of the encrypted file at the time it was decrypted
+    pub source_hash: Option<String>,
+    /// sha256 of the decrypted contents, so that changes to the deployed secret can be told apart
+    /// without decrypting it again
+    pub hash: String,
+}
+
+impl DecryptedFile {
+    pub fn new(group: &str, source: &Path, contents: &[u8]) -> Self {
+        Self {
+            group: group.into(),
+            source: source.into(),
+            source_hash: fileops::hash_file(source).ok(),
+            hash: fileops::hash_bytes(contents),
+        }
+    }
+}
+
+impl State {
+    /// Returns true if the group or the base group of a conditional group is held
+    pub fn is_held(&self, group: &str) -> bool {
+        self.held.contains(group) || self.held.contains(dotfiles::group_without_target(group))
+    }
+
+    /// Loads the state for the profile, an empty state is returned if none has been saved yet
+    pub fn load(profile: Option<String>) -> Result<Self, errors::Error> {
+        let path = dotfiles::get_dotfiles_path(profile)?.join(STATE_FILE);
+
+        let mut state: State = match fs::read_to_string(&path) {
+            Ok(state) => {
+                serde_json::from_str(&state).map_err(|e| format!("{}: {e}", path.display()))?
+            }
+            Err(_) => State::default(),
+        };
+
+        state.path = path;
+        Ok(state)
+    }
+
+    /// Returns the groups that files were deployed from but that aren't in the dotfiles anymore,
+    /// along with their deployed files
+    pub fn orphaned_groups(&self) -> BTreeMap<&str, Vec<&Path>> {
+        let configs_dir = self.path.with_file_name("Configs");
+        let mut orphaned: BTreeMap<&str, Vec<&Path>> = BTreeMap::new();
+        for (target, file) in &self.deployed {
+            if file.is_orphaned()
+                && !configs_dir.join(&file.group).exists()
+                && fs::symlink_metadata(target).is_ok()
+            {
+                orphaned.entry(&file.group).or_default().push(target);
+            }
+        }
+        orphaned
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let first_write = !self.path.exists();
+        let state = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(&self.path, state).map_err(|e| format!("{}: {e}", self.path.display()))?;
+
+        // the state is only meant for this machine
+        if first_write && let Some(dotfiles_dir) = self.path.parent() {
+            history::exclude_locally(dotfiles_dir);
+        }
+        Ok(())
+    }
+}
+
+/// Loads the state and prints the error if it fails
+pub fn load_or_report(profile: Option<String>) -> Result<State, TuckrError> {
+    State::load(profile).map_err(|err| {
+        errors::print(err);
+        TuckrError::CouldntFindDotfiles
+    })
+}
+
+/// Attaches a note to a group, lists its notes if no note is supplied
+pub fn note_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    group: &str,
+    note: &[String],
+    clear: bool,
+) -> Result<(), TuckrError> {
+    let group_exists = [
+        DotfileType::Configs,
+        DotfileType::Hooks,
+        DotfileType::Secrets,
+    ]
+    .into_iter()
+    .any(|dtype| dotfiles::dotfile_contains(profile.clone(), dtype, group));
+
+    if !group_exists {
+        errors::print(error!("no_group", group = group));
+        return Err(TuckrError::NoSuchFileOrDir);
+    }
+
+    let mut state = load_or_report(profile)?;
+
+    if clear {
+        if dry_run {
+            eprintln!(
+                "{} notes from `{}`",
+                "removing".if_supports_color(Stderr, |t| t.red()),
+                group
+            );
+            return Ok(());
+        }
+
+        state.notes.remove(group);
+    } else if note.is_empty() {
+        print_notes(&state, group);
+        return Ok(());
+    } else {
+        let note = note.join(" ");
+        if dry_run {
+            eprintln!(
+                "{} note `{}` to `{}`",
+                "adding".if_supports_color(Stderr, |t| t.green()),
+                note,
+                group
+            );
+            return Ok(());
+        }
+
+        state.notes.entry(group.into()).or_default().push(note);
+    }
+
+    state.save().map_err(|err| {
+        errors::print(err);
+        TuckrError::Failed
+    })
+}
+
+/// Holds or releases groups
+///
+/// Held groups are skipped by `tuckr add` so that they stay at their current deployment
+pub fn hold_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    groups: &[String],
+    hold: bool,
+) -> Result<(), TuckrError> {
+    if let Some(invalid_groups) =
+        dotfiles::check_invalid_groups(profile.clone(), DotfileType::Configs, groups)
+    {
+        for group in invalid_groups {
+            errors::print(error!("no_group", group = group));
+        }
+        return Err(TuckrError::NoSuchFileOrDir);
+    }
+
+    let mut state = load_or_report(profile)?;
+
+    for group in groups {
+        if dry_run {
+            eprintln!(
+                "{} `{}`",
+                if hold {
+                    "holding"
+                        .if_supports_color(Stdout, |t| t.yellow())
+                        .to_string()
+                } else {
+                    "releasing"
+                        .if_supports_color(Stdout, |t| t.green())
+                        .to_string()
+                },
+                group
+            );
+            continue;
+        }
+
+        if hold {
+            state.held.insert(group.clone());
+        } else {
+            state.held.remove(group);
+        }
+    }
+
+    if dry_run {
+        return Ok(());
+    }
+
+    state.save().map_err(|err| {
+        errors::print(err);
+        TuckrError::Failed
+    })
+}
+
+/// Prints all the notes attached to the group
+pub fn print_notes(state: &State, group: &str) {
+    let Some(notes) = state.notes.get(group) else {
+        return;
+    };
+
+    println!(
+        "{} ({}):",
+        t!("info.notes"),
+        group.if_supports_color(Stdout, |t| t.yellow())
+    );
+    for note in notes {
+        println!("\t- {note}");
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attach_notes_to_group() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        fs::create_dir_all(dotfiles_dir.join("Configs").join("nvim")).unwrap();
+
+        note_cmd(
+            None,
+            false,
+            "nvim",
+            &["held back".into(), "at v0.9".into()],
+            false,
+        )
+        .unwrap();
+        note_cmd(
+            None,
+            false,
+            "nvim",
+            &["until plugin X is fixed".into()],
+            false,
+        )
+        .unwrap();
+        assert!(note_cmd(None, false, "not_a_group", &["note".into()], false).is_err());
+
+        let state = State::load(None).unwrap();
+        assert_eq!(
+            state.notes["nvim"],
+            ["held back at v0.9", "until plugin X is fixed"]
+        );
+
+        note_cmd(None, false, "nvim", &[], true).unwrap();
+        assert!(State::load(None).unwrap().notes.is_empty());
+
+        fs::remove_dir_all(dotfiles_dir).unwrap();
+    }
+
+    #[test]
+    fn hold_and_release_groups() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        fs::create_dir_all(dotfiles_dir.join("Configs").join("nvim")).unwrap();
+        fs::create_dir_all(dotfiles_dir.join("Configs").join("nvim_linux")).unwrap();
+
+        hold_cmd(None, false, &["nvim".into()], true).unwrap();
+        let state = State::load(None).unwrap();
+        assert!(state.is_held("nvim"));
+        assert!(state.is_held("nvim_linux"));
+
+        hold_cmd(None, false, &["nvim".into()], false).unwrap();
+        assert!(!State::load(None).unwrap().is_held("nvim"));
+
+        assert!(hold_cmd(None, false, &["zsh".into()], true).is_err());
+
+        fs::remove_dir_all(dotfiles_dir).unwrap();
+    }
+}
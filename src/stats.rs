@@ -0,0 +1,331 @@
+//! Keeps local usage statistics
+//!
+//! Statistics are opt-in with `stats = true` in tuckr.toml. Every command then appends a line of
+//! JSON to stats.jsonl in the machine's local data directory (e.g. ~/.local/share/tuckr) with how
+//! long it took, whether it succeeded and how many conflicting and undeployed groups it came across.
+//! The file is never uploaded anywhere, `tuckr stats` summarizes it.
+
+use crate::config;
+use crate::errors::TuckrError;
+use crate::table;
+use owo_colors::OwoColorize;
+use owo_colors::Stream::{Stderr, Stdout};
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tabled::Tabled;
+
+pub const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// what the running command found out about the dotfiles, the highest count is kept
+#[derive(Default)]
+struct Findings {
+    conflicts: Option<usize>,
+    drifted: Option<usize>,
+}
+
+static FINDINGS: Mutex<Findings> = Mutex::new(Findings {
+    conflicts: None,
+    drifted: None,
+});
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    /// seconds since the unix epoch
+    time: u64,
+    command: String,
+    duration_ms: u128,
+    succeeded: bool,
+    /// groups with conflicting files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    conflicts: Option<usize>,
+    /// groups that aren't fully deployed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    drifted: Option<usize>,
+}
+
+/// Returns where the statistics are stored
+pub fn get_stats_path() -> Option<PathBuf> {
+    Some(dirs::data_local_dir()?.join("tuckr").join("stats.jsonl"))
+}
+
+/// Notes how many groups have conflicts
+pub fn record_conflicts(groups: usize) {
+    let mut findings = FINDINGS.lock().unwrap();
+    findings.conflicts = findings.conflicts.max(Some(groups));
+}
+
+/// Notes how many groups aren't fully deployed
+pub fn record_drift(groups: usize) {
+    let mut findings = FINDINGS.lock().unwrap();
+    findings.drifted = findings.drifted.max(Some(groups));
+}
+
+/// Appends the command's statistics to the stats file if statistics are enabled
+pub fn record(command: &str, started: Instant, succeeded: bool) {
+    if !config::get().stats {
+        return;
+    }
+
+    let Some(path) = get_stats_path() else {
+        return;
+    };
+
+    let findings = std::mem::take(&mut *FINDINGS.lock().unwrap());
+    let entry = Entry {
+        time: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default(),
+        command: command.into(),
+        duration_ms: started.elapsed().as_millis(),
+        succeeded,
+        conflicts: findings.conflicts,
+        drifted: findings.drifted,
+    };
+
+    let Ok(mut line) = serde_json::to_string(&entry) else {
+        return;
+    };
+    line.push('\n');
+
+    let written = fs::create_dir_all(path.parent().unwrap()).and_then(|_| {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut stats| stats.write_all(line.as_bytes()))
+    });
+
+    if let Err(err) = written {
+        eprintln!(
+            "{}",
+            t!(
+                "warn.couldnt_save_stats",
+                file = path.display(),
+                err_msg = err
+            )
+            .if_supports_color(Stderr, |t| t.yellow())
+        );
+    }
+}
+
+/// Parses the stats file, lines that can't be parsed are skipped
+fn parse_entries(stats: &str) -> Vec<Entry> {
+    stats
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// Formats days since the unix epoch as YYYY-MM-DD
+pub fn format_date(days: u64) -> String {
+    // converts to a civil date, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Returns the day the week of `time` started on as days since the unix epoch, weeks start on monday
+fn week_start(time: u64) -> u64 {
+    let days = time / SECS_PER_DAY;
+    // the unix epoch was on a thursday
+    days.saturating_sub((days + 3) % 7)
+}
+
+#[derive(Tabled)]
+struct CommandRow {
+    #[tabled(rename = "Command")]
+    command: String,
+    #[tabled(rename = "Runs")]
+    runs: usize,
+    #[tabled(rename = "Failed")]
+    failed: usize,
+    #[tabled(rename = "Average time")]
+    average: String,
+}
+
+#[derive(Debug, PartialEq, Tabled)]
+struct WeekRow {
+    #[tabled(rename = "Week")]
+    week: String,
+    #[tabled(rename = "Runs")]
+    runs: usize,
+    #[tabled(rename = "Failed")]
+    failed: usize,
+    /// runs that came across conflicts
+    #[tabled(rename = "Conflicts")]
+    conflicts: usize,
+    /// runs that found groups that weren't fully deployed
+    #[tabled(rename = "Drifted")]
+    drifted: usize,
+}
+
+fn summarize_commands(entries: &[Entry]) -> Vec<CommandRow> {
+    let mut commands: BTreeMap<&str, (usize, usize, u128)> = BTreeMap::new();
+    for entry in entries {
+        let (runs, failed, duration) = commands.entry(&entry.command).or_default();
+        *runs += 1;
+        *failed += usize::from(!entry.succeeded);
+        *duration += entry.duration_ms;
+    }
+
+    commands
+        .into_iter()
+        .map(|(command, (runs, failed, duration))| CommandRow {
+            command: command.into(),
+            runs,
+            failed,
+            average: format!("{}ms", duration / runs as u128),
+        })
+        .collect()
+}
+
+/// Summarizes the last `weeks` weeks up to `now`, weeks without any runs are kept to show the trend
+fn summarize_weeks(entries: &[Entry], weeks: usize, now: u64) -> Vec<WeekRow> {
+    let last_week = week_start(now);
+    let first_week = last_week.saturating_sub(7 * weeks.saturating_sub(1) as u64);
+
+    let mut rows: BTreeMap<u64, WeekRow> = (0..weeks as u64)
+        .map(|week| first_week + week * 7)
+        .filter(|&week| week <= last_week)
+        .map(|week| {
+            let row = WeekRow {
+                week: format_date(week),
+                runs: 0,
+                failed: 0,
+                conflicts: 0,
+                drifted: 0,
+            };
+            (week, row)
+        })
+        .collect();
+
+    for entry in entries {
+        let Some(row) = rows.get_mut(&week_start(entry.time)) else {
+            continue;
+        };
+
+        row.runs += 1;
+        row.failed += usize::from(!entry.succeeded);
+        row.conflicts += usize::from(entry.conflicts.unwrap_or_default() > 0);
+        row.drifted += usize::from(entry.drifted.unwrap_or_default() > 0);
+    }
+
+    rows.into_values().collect()
+}
+
+/// Shows how tuckr has been used over the last `weeks` weeks
+pub fn stats_cmd(weeks: usize) -> Result<(), TuckrError> {
+    if !config::get().stats {
+        println!(
+            "{}",
+            t!("info.stats_disabled").if_supports_color(Stdout, |t| t.yellow())
+        );
+    }
+
+    let entries = get_stats_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .map(|stats| parse_entries(&stats))
+        .unwrap_or_default();
+
+    if entries.is_empty() {
+        println!(
+            "{}",
+            t!("info.no_stats_yet").if_supports_color(Stdout, |t| t.yellow())
+        );
+        return Ok(());
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    let cutoff = week_start(now).saturating_sub(7 * weeks.saturating_sub(1) as u64) * SECS_PER_DAY;
+    let recent: Vec<_> = entries
+        .into_iter()
+        .filter(|entry| entry.time >= cutoff)
+        .collect();
+
+    table::print(&summarize_commands(&recent));
+    table::print(&summarize_weeks(&recent, weeks, now));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_dates() {
+        assert_eq!(format_date(0), "1970-01-01");
+        assert_eq!(format_date(11_016), "2000-02-29");
+        assert_eq!(format_date(20_742), "2026-10-16");
+    }
+
+    #[test]
+    fn summarize_stats_by_week() {
+        let entry = |days: u64, succeeded: bool, conflicts: Option<usize>, drifted| Entry {
+            time: days * SECS_PER_DAY + 60,
+            command: "status".into(),
+            duration_ms: 10,
+            succeeded,
+            conflicts,
+            drifted,
+        };
+
+        let stats = [
+            // 2026-10-05 is a monday
+            entry(20_731, true, None, Some(0)),
+            entry(20_735, false, Some(2), Some(1)),
+            entry(20_738, true, None, Some(3)),
+        ]
+        .iter()
+        .map(|entry| serde_json::to_string(entry).unwrap())
+        .collect::<Vec<_>>()
+        .join("\n");
+        let entries = parse_entries(&format!("{stats}\nnot json"));
+        assert_eq!(entries.len(), 3);
+
+        let weeks = summarize_weeks(&entries, 3, 20_742 * SECS_PER_DAY);
+        let summary: Vec<_> = weeks
+            .iter()
+            .map(|week| {
+                (
+                    week.week.as_str(),
+                    week.runs,
+                    week.failed,
+                    week.conflicts,
+                    week.drifted,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("2026-09-28", 0, 0, 0, 0),
+                ("2026-10-05", 2, 1, 1, 1),
+                ("2026-10-12", 1, 0, 0, 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! Caches what `tuckr status` found out about each dotfile, so that on large dotfiles only the
+//! dotfiles and targets that changed since the last time are looked at again
+//!
+//! Every dotfile's status is stored along with the metadata of the dotfile and of its target:
+//! when they were modified, their size and where they point to if they're symlinks. A status is
+//! only reused while both are the same, and the whole cache is thrown away once what the statuses
+//! were worked out against changed, that is the dotfiles' git revision, the state or tuckr.toml.
+//!
+//! The cache is only meant for this machine so it's kept outside of the dotfiles.
+
+use crate::config;
+use crate::fileops;
+use crate::history;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// files modified this recently might still change within the same modification time, like git's
+/// racily clean entries, so their status isn't cached
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// What's compared to tell whether a file changed without reading it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct FileMeta {
+    /// nanoseconds since the unix epoch
+    modified: u128,
+    size: u64,
+    /// where the file points to if it's a symlink
+    link: Option<PathBuf>,
+}
+
+impl FileMeta {
+    /// Reads the metadata without following symlinks, None if there's nothing at `path`
+    fn read(path: &Path) -> Option<Self> {
+        let meta = fs::symlink_metadata(path).ok()?;
+        let link = match meta.is_symlink() {
+            true => Some(fs::read_link(path).ok()?),
+            false => None,
+        };
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .unwrap_or_default();
+
+        Some(FileMeta {
+            modified: modified.as_nanos(),
+            size: meta.len(),
+            link,
+        })
+    }
+
+    fn is_racy(&self) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        now.as_nanos().saturating_sub(self.modified) < RACY_WINDOW.as_nanos()
+    }
+}
+
+/// The metadata of a dotfile and of its target, read before working out the dotfile's status
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stamp {
+    source: FileMeta,
+    target: Option<FileMeta>,
+}
+
+impl Stamp {
+    /// Returns None if the dotfile's status can't be cached: it's missing, it's a symlink whose
+    /// contents could change without it changing, or it or its target were just modified
+    pub fn read(source: &Path, target: &Path) -> Option<Self> {
+        let source = FileMeta::read(source).filter(|meta| meta.link.is_none())?;
+        let target = FileMeta::read(target);
+
+        let is_racy = source.is_racy() || target.as_ref().is_some_and(FileMeta::is_racy);
+        (!is_racy).then_some(Stamp { source, target })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Entry<T> {
+    stamp: Stamp,
+    status: T,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StatusCache<T> {
+    #[serde(skip)]
+    path: PathBuf,
+    /// what the statuses were worked out against
+    key: String,
+    files: HashMap<PathBuf, Entry<T>>,
+}
+
+impl<T: Clone + Serialize + DeserializeOwned> StatusCache<T> {
+    /// Loads the cache of the dotfiles, it's empty if there's none or if it was made against
+    /// another revision, state or configuration
+    pub fn load(dotfiles_dir: &Path, target_dir: &Path) -> Self {
+        let path = cache_path(dotfiles_dir);
+        let key = cache_key(dotfiles_dir, target_dir);
+
+        let cache = fs::read(&path)
+            .ok()
+            .and_then(|cache| serde_json::from_slice::<StatusCache<T>>(&cache).ok())
+            .filter(|cache| cache.key == key);
+
+        tracing::debug!(path = %path.display(), hit = cache.is_some(), "loaded the status cache");
+        match cache {
+            Some(cache) => StatusCache { path, ..cache },
+            None => StatusCache {
+                path,
+                key,
+                files: HashMap::new(),
+            },
+        }
+    }
+
+    /// Returns the dotfile's status if neither it nor its target changed since it was cached
+    pub fn get(&self, source: &Path, stamp: &Stamp) -> Option<T> {
+        self.files
+            .get(source)
+            .filter(|entry| entry.stamp == *stamp)
+            .map(|entry| entry.status.clone())
+    }
+
+    /// Replaces what's cached with the statuses that were just worked out, so that dotfiles that
+    /// are gone don't stay in it
+    pub fn replace(&mut self, files: impl IntoIterator<Item = (PathBuf, Stamp, T)>) {
+        self.files = files
+            .into_iter()
+            .map(|(source, stamp, status)| (source, Entry { stamp, status }))
+            .collect();
+    }
+
+    /// Writes the cache, failing to do so only means that the next status checks everything again
+    pub fn save(&self) {
+        let written = fs::create_dir_all(self.path.parent().unwrap()).and_then(|_| {
+            let cache = serde_json::to_vec(self).map_err(std::io::Error::other)?;
+            fs::write(&self.path, cache)
+        });
+
+        if let Err(err) = written {
+            tracing::debug!(path = %self.path.display(), %err, "couldn't write the status cache");
+        }
+    }
+}
+
+/// Returns where the cache of the dotfiles is kept, each dotfiles directory has its own
+fn cache_path(dotfiles_dir: &Path) -> PathBuf {
+    let id = fileops::hash_bytes(dotfiles_dir.as_os_str().as_encoded_bytes());
+
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("tuckr")
+        .join("status")
+        .join(format!("{}.json", &id[..16]))
+}
+
+/// Returns what the statuses depend on besides the dotfiles and their targets
+fn cache_key(dotfiles_dir: &Path, target_dir: &Path) -> String {
+    let revision = history::git(dotfiles_dir, &["rev-parse", "--verify", "HEAD"], None);
+    let state = fs::read(dotfiles_dir.join(crate::state::STATE_FILE));
+    let config = config::get_config_path().and_then(|path| fs::read(path).ok());
+
+    let mut key = Vec::new();
+    for part in [
+        env!("CARGO_PKG_VERSION").as_bytes(),
+        target_dir.as_os_str().as_encoded_bytes(),
+        revision.as_deref().unwrap_or_default().as_bytes(),
+        state.as_deref().unwrap_or_default(),
+        config.as_deref().unwrap_or_default(),
+    ] {
+        key.extend(fileops::hash_bytes(part).into_bytes());
+    }
+    fileops::hash_bytes(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dotfiles;
+
+    #[test]
+    fn reuse_statuses_until_files_change() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let target_dir = dotfiles_dir.with_file_name("target");
+        fs::create_dir_all(&dotfiles_dir).unwrap();
+        fs::create_dir_all(&target_dir).unwrap();
+
+        let source = dotfiles_dir.join("file");
+        let target = target_dir.join("file");
+        fs::write(&source, "contents").unwrap();
+
+        // files that were just written aren't cached
+        assert_eq!(Stamp::read(&source, &target), None);
+        let old = SystemTime::now() - Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        let stamp = Stamp::read(&source, &target).unwrap();
+
+        let mut cache: StatusCache<String> = StatusCache {
+            path: dotfiles_dir.join("cache.json"),
+            key: String::new(),
+            files: HashMap::new(),
+        };
+        cache.replace([(source.clone(), stamp.clone(), "not deployed".to_string())]);
+        assert_eq!(cache.get(&source, &stamp).as_deref(), Some("not deployed"));
+
+        // deploying the dotfile changes its target
+        fileops::symlink(&source, &target).unwrap();
+        let deployed = FileMeta::read(&target).unwrap();
+        assert_eq!(deployed.link.as_deref(), Some(source.as_path()));
+        let stamp = Stamp {
+            target: Some(deployed),
+            ..stamp
+        };
+        assert_eq!(cache.get(&source, &stamp), None);
+
+        fs::remove_dir_all(dotfiles_dir.parent().unwrap()).unwrap();
+    }
+}
@@ -9,85 +9,602 @@
 //!
 //! This information is retrieved by walking through dotfiles/Configs and checking whether their
 //! $TUCKR_TARGET equivalents are pointing to them and categorizing them accordingly.
-
-use crate::dotfiles::{self, Dotfile, DotfileType, ReturnCode};
+//!
+//! Dotfiles deployed in copy mode can't be identified by following symlinks, so they're tracked
+//! in the state file instead and count as symlinked for as long as their copies exist.
+//!
+//! Where symlinks can't be created (Windows without Developer Mode) directories are deployed as
+//! junctions and files as hardlinks, or copies when they can't be hardlinked. These are tracked in
+//! the state file as well and count as symlinked too.
+
+use crate::backups::{self, BackupSet};
+use crate::cancel;
+use crate::config::{self, DeployMode};
+use crate::diff;
+use crate::dotfiles::{self, Dotfile, DotfileType};
+use crate::error;
+use crate::errors::{self, TuckrError};
+use crate::escalate::{self, Operation};
+use crate::experiments;
+use crate::fileops::{self, DirWalk};
+use crate::interactive::{self, Resolution};
+use crate::nested;
+use crate::permissions;
+use crate::plan::{self, Step};
+use crate::preflight;
+use crate::progress::Progress;
+use crate::safe;
+use crate::state::{self, CopiedFile, DeployMethod, DeployedFile, LinkedFile, State};
+use crate::stats;
+use crate::statuscache::{Stamp, StatusCache};
+use crate::table;
+use crate::templates;
+use crate::transaction::{self, Transaction};
 use enumflags2::{BitFlags, make_bitflags};
 use owo_colors::OwoColorize;
+use owo_colors::Stream::{Stderr, Stdout};
 use rust_i18n::t;
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs;
-use std::io::Write;
-use std::path::PathBuf;
-use std::process::ExitCode;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
 use tabled::{Table, Tabled};
 
-fn symlink_file(dry_run: bool, f: PathBuf) {
-    match Dotfile::try_from(f.clone()) {
-        Ok(group) => {
-            let target_path = match group.to_target_path() {
-                Ok(t) => t,
-                Err(err) => {
-                    eprintln!("{err}");
-                    return;
+/// Symlinks a dotfile to its target
+///
+/// Errors are returned instead of printed since files are symlinked in parallel,
+/// returns what was created: the target's missing parent directories and the symlink
+fn symlink_file(dry_run: bool, dotfile: &Dotfile) -> Result<Vec<PathBuf>, errors::Error> {
+    let f = dotfile.path.as_path();
+    let target_path = dotfile.to_target_path()?;
+
+    if target_path.exists() {
+        tracing::info!(path = %target_path.display(), "not symlinking, something is already there");
+        if dry_run && !plan::is_recording() {
+            eprintln!(
+                "{} `{}` as it already exists",
+                "ignoring".if_supports_color(Stderr, |t| t.yellow()),
+                target_path.display()
+            );
+        }
+        return Ok(Vec::new());
+    }
+
+    let privileged = escalate::needs_privileges(&target_path);
+    tracing::info!(
+        dotfile = %f.display(),
+        path = %target_path.display(),
+        privileged,
+        "symlinking"
+    );
+
+    if dry_run {
+        let recorded = plan::record(Step::Deploy {
+            source: f.to_path_buf(),
+            target: target_path.clone(),
+            method: DeployMethod::Symlink,
+            as_root: privileged,
+        });
+        if !recorded {
+            eprintln!(
+                "{} `{}` to `{}`{}",
+                "symlinking".if_supports_color(Stderr, |t| t.green()),
+                f.display(),
+                target_path.display(),
+                if privileged { " (as root)" } else { "" }
+            );
+        }
+        return Ok(Vec::new());
+    }
+
+    // it's symlinked as root once everything else has been deployed
+    if privileged {
+        escalate::defer(Operation::Symlink {
+            source: f.to_path_buf(),
+            target: target_path,
+        });
+        return Ok(Vec::new());
+    }
+
+    // on Windows the permissions are checked first so that the error says how to get them
+    escalate::check_link_permissions(&target_path)?;
+
+    // the target's parent might not exist, e.g. when deploying into a sandbox directory
+    let mut created = Vec::new();
+    if let Some(parent) = target_path.parent()
+        && !parent.exists()
+    {
+        created.extend(
+            transaction::create_dirs(parent)
+                .map_err(|err| err.if_supports_color(Stderr, |t| t.red()).to_string())?,
+        );
+    }
+
+    fileops::symlink_dotfile(f, &target_path).map_err(|err| {
+        error!(
+            "failed_to_symlink_x",
+            groupname = dotfile.group_name,
+            err_msg = err.if_supports_color(Stderr, |t| t.red())
+        )
+    })?;
+
+    created.push(target_path);
+    Ok(created)
+}
+
+/// Symlinks a dotfile to its target, or links it some other way if symlinks can't be created
+///
+/// Used for the dotfiles that are deployed one at a time, the changes are recorded in the transaction
+fn link_file(
+    dry_run: bool,
+    f: &Path,
+    state: &mut State,
+    transaction: &mut Transaction,
+) -> Result<(), errors::Error> {
+    if !escalate::symlinks_allowed() {
+        return link_without_symlink(dry_run, f, state, transaction);
+    }
+
+    let dotfile = Dotfile::try_from(f.to_path_buf())
+        .map_err(|err| error!("failed_to_link_file", file = f.display()).caused_by(err))?;
+    let created = symlink_file(dry_run, &dotfile)?;
+    created
+        .into_iter()
+        .for_each(|path| transaction.created(path));
+    Ok(())
+}
+
+/// Deploys a dotfile without symlinking it, for when symlinks can't be created
+///
+/// Directories become junctions and files hardlinks, files that can't be hardlinked (e.g. because
+/// the target is on another volume) are copied instead. What was used is recorded in the state.
+fn link_without_symlink(
+    dry_run: bool,
+    f: &Path,
+    state: &mut State,
+    transaction: &mut Transaction,
+) -> Result<(), errors::Error> {
+    let dotfile = Dotfile::try_from(f.to_path_buf())
+        .map_err(|err| error!("failed_to_link_file", file = f.display()).caused_by(err))?;
+
+    let target_path = dotfile.to_target_path()?;
+
+    if target_path.exists() {
+        if dry_run && !plan::is_recording() {
+            eprintln!(
+                "{} `{}` as it already exists",
+                "ignoring".if_supports_color(Stderr, |t| t.yellow()),
+                target_path.display()
+            );
+        }
+        return Ok(());
+    }
+
+    let method = match f.is_dir() {
+        true => DeployMethod::Junction,
+        false => DeployMethod::Hardlink,
+    };
+
+    if dry_run {
+        let recorded = plan::record(Step::Deploy {
+            source: f.to_path_buf(),
+            target: target_path.clone(),
+            method,
+            as_root: false,
+        });
+        if !recorded {
+            eprintln!(
+                "{} `{}` to `{}`",
+                match method {
+                    DeployMethod::Junction => "junctioning",
+                    _ => "hardlinking",
                 }
+                .if_supports_color(Stderr, |t| t.green()),
+                f.display(),
+                target_path.display()
+            );
+        }
+        return Ok(());
+    }
+
+    if let Some(parent) = target_path.parent() {
+        transaction
+            .create_dir_all(parent)
+            .map_err(|err| err.if_supports_color(Stderr, |t| t.red()).to_string())?;
+    }
+
+    let linked = match method {
+        DeployMethod::Junction => fileops::junction(f, &target_path),
+        _ => fs::hard_link(f, &target_path),
+    };
+
+    match linked {
+        Ok(()) => {
+            transaction.created(&target_path);
+            state.links.insert(
+                target_path,
+                LinkedFile {
+                    source: f.to_path_buf(),
+                    method,
+                },
+            );
+            Ok(())
+        }
+        Err(_) if method == DeployMethod::Hardlink => {
+            copy_file(false, f.to_path_buf(), state, transaction)
+        }
+        Err(err) => Err(error!(
+            "failed_to_symlink_x",
+            groupname = dotfile.group_name,
+            err_msg = err.if_supports_color(Stderr, |t| t.red())
+        )),
+    }
+}
+
+/// Removes a junction or hardlink deployed by `link_without_symlink`
+///
+/// Returns false if the target isn't one of them, so that it can be removed some other way
+fn remove_link(dry_run: bool, dotfile: &Dotfile, state: &mut State) -> bool {
+    let target = dotfile.to_target_path().unwrap();
+    let Some(link) = state.links.get(&target) else {
+        return false;
+    };
+
+    if link.source != dotfile.path {
+        return false;
+    }
+
+    // it was already deleted or replaced by someone else
+    if !is_linked(&target, link) {
+        if !dry_run {
+            state.links.remove(&target);
+        }
+        return true;
+    }
+
+    if dry_run {
+        eprintln!(
+            "{} `{}`",
+            "removing".if_supports_color(Stderr, |t| t.red()),
+            target.display()
+        );
+        return true;
+    }
+
+    // only the junction itself is removed, never what it points to
+    let removed = match link.method {
+        DeployMethod::Junction => fs::remove_dir(&target),
+        _ => fs::remove_file(&target),
+    };
+
+    match removed {
+        Ok(()) => {
+            state.links.remove(&target);
+        }
+        Err(err) => errors::print(format!("{}: {err}", target.display())),
+    }
+    true
+}
+
+/// Returns true if `target` is still the junction or hardlink to its dotfile
+fn is_linked(target: &Path, link: &LinkedFile) -> bool {
+    match link.method {
+        DeployMethod::Junction => {
+            target.is_symlink()
+                && target.canonicalize().is_ok_and(|target| {
+                    link.source
+                        .canonicalize()
+                        .is_ok_and(|source| target == source)
+                })
+        }
+        _ => fileops::is_same_file(target, &link.source),
+    }
+}
+
+/// Copies a dotfile to its target and records the copy in the state so that it can be tracked
+fn copy_file(
+    dry_run: bool,
+    f: PathBuf,
+    state: &mut State,
+    transaction: &mut Transaction,
+) -> Result<(), errors::Error> {
+    let dotfile = Dotfile::try_from(f.clone())
+        .map_err(|err| error!("failed_to_link_file", file = f.display()).caused_by(err))?;
+
+    let target_path = dotfile.to_target_path()?;
+
+    if target_path.exists() {
+        tracing::info!(path = %target_path.display(), "not copying, something is already there");
+        if dry_run && !plan::is_recording() {
+            eprintln!(
+                "{} `{}` as it already exists",
+                "ignoring".if_supports_color(Stderr, |t| t.yellow()),
+                target_path.display()
+            );
+        }
+        return Ok(());
+    }
+
+    tracing::info!(dotfile = %f.display(), path = %target_path.display(), "copying");
+    if dry_run {
+        let recorded = plan::record(Step::Deploy {
+            source: f.clone(),
+            target: target_path.clone(),
+            method: match dotfile.is_template() {
+                true => DeployMethod::Template,
+                false => DeployMethod::Copy,
+            },
+            as_root: false,
+        });
+        if !recorded {
+            let action = if dotfile.is_template() {
+                "rendering"
+            } else {
+                "copying"
             };
+            eprintln!(
+                "{} `{}` to `{}`",
+                action.if_supports_color(Stderr, |t| t.green()),
+                f.display(),
+                target_path.display()
+            );
+        }
+        return Ok(());
+    }
 
-            if target_path.exists() {
-                if dry_run {
-                    eprintln!(
-                        "{} `{}` as it already exists",
-                        "ignoring".yellow(),
-                        target_path.display()
-                    );
-                }
-                return;
+    if let Some(parent) = target_path.parent() {
+        transaction
+            .create_dir_all(parent)
+            .map_err(|err| err.if_supports_color(Stderr, |t| t.red()).to_string())?;
+    }
+
+    // directories are copied along with everything inside of them
+    let mut files = vec![(f.clone(), target_path.clone())];
+    if f.is_dir() {
+        files.extend(DirWalk::new(&f).map(|file| {
+            let mut target = target_path.join(file.strip_prefix(&f).unwrap());
+            if templates::is_template(&file) {
+                target.set_extension("");
             }
+            (file, target)
+        }));
+    }
 
-            if dry_run {
-                eprintln!(
-                    "{} `{}` to `{}`",
-                    "symlinking".green(),
-                    f.display(),
-                    target_path.display()
-                );
-                return;
+    for (file, target) in files {
+        let hash = if file.is_dir() {
+            transaction.create_dir_all(&target).map(|_| None)
+        } else if templates::is_template(&file) {
+            let rendered = templates::render_file(&file, dotfile.dotfiles_dir())
+                .map_err(|err| err.if_supports_color(Stderr, |t| t.red()).to_string())?;
+            transaction
+                .write(&target, &rendered)
+                .map(|_| Some(fileops::hash_bytes(rendered)))
+        } else {
+            fs::copy(&file, &target).and_then(|_| {
+                transaction.created(&target);
+                fileops::hash_file(&target).map(Some)
+            })
+        };
+
+        match hash {
+            Ok(hash) => {
+                state
+                    .copies
+                    .insert(target, CopiedFile { source: file, hash });
             }
+            Err(err) => {
+                return Err(error!(
+                    "failed_to_copy_x",
+                    groupname = dotfile.group_name,
+                    err_msg = err.if_supports_color(Stderr, |t| t.red())
+                ));
+            }
+        }
+    }
 
-            let result = {
-                #[cfg(target_family = "unix")]
-                {
-                    std::os::unix::fs::symlink(f, target_path)
-                }
+    Ok(())
+}
 
-                #[cfg(target_family = "windows")]
-                {
-                    if f.is_dir() {
-                        std::os::windows::fs::symlink_dir(f, target_path)
-                    } else {
-                        std::os::windows::fs::symlink_file(f, target_path)
-                    }
-                }
-            };
+/// Creates a directory that has templates or ignored files in it instead of symlinking it, so that
+/// the rest of its files can be symlinked one by one next to the rendered templates
+fn create_dir(
+    dry_run: bool,
+    dotfile: &Dotfile,
+    transaction: &mut Transaction,
+) -> Result<(), errors::Error> {
+    let target_path = dotfile.to_target_path().unwrap();
+    if target_path.exists() {
+        return Ok(());
+    }
 
-            if let Err(err) = result {
-                eprintln!(
-                    "{}",
-                    t!(
-                        "errors.failed_to_symlink_x",
-                        groupname = group.group_name,
-                        err_msg = err.red()
-                    )
-                );
-            }
+    if dry_run {
+        if !plan::record(Step::CreateDir {
+            dir: target_path.clone(),
+        }) {
+            eprintln!(
+                "{} `{}`",
+                "creating".if_supports_color(Stderr, |t| t.green()),
+                target_path.display()
+            );
         }
+        return Ok(());
+    }
 
-        Err(err) => {
-            eprintln!("{}", err);
+    transaction
+        .create_dir_all(&target_path)
+        .map_err(|err| err.to_string().into())
+}
+
+/// Replaces a symlink to another group's directory with a directory of symlinks to each of its
+/// files, so that the files of the group being added can go next to them
+fn unfold_dir(
+    dry_run: bool,
+    target: &Path,
+    folded: &Path,
+    transaction: &mut Transaction,
+) -> Result<(), errors::Error> {
+    if dry_run {
+        if !plan::record(Step::Unfold {
+            dir: target.to_path_buf(),
+        }) {
             eprintln!(
-                "{}",
-                t!("errors.failed_to_link_file", file = f.to_str().unwrap())
+                "{} `{}`",
+                "unfolding".if_supports_color(Stderr, |t| t.green()),
+                target.display()
+            );
+        }
+        return Ok(());
+    }
+
+    let unfold = |transaction: &mut Transaction| -> std::io::Result<()> {
+        let files: Vec<_> = fs::read_dir(folded)?
+            .map(|file| file.map(|file| file.path()))
+            .collect::<Result<_, _>>()?;
+
+        transaction.remove(target)?;
+        transaction.create_dir_all(target)?;
+        for file in files.iter().filter(|file| !fileops::is_ignored_file(file)) {
+            let link = target.join(file.file_name().unwrap());
+            fileops::symlink_dotfile(file, &link)?;
+            transaction.created(link);
+        }
+        Ok(())
+    };
+
+    unfold(transaction).map_err(|err| {
+        error!(
+            "failed_to_unfold",
+            dir = target.display(),
+            err_msg = err.if_supports_color(Stderr, |t| t.red())
+        )
+    })
+}
+
+/// Renders a template again after its template or variables changed, copies of dotfiles that changed
+/// and of assets are copied again
+///
+/// The deployed file is left alone if it was modified after being deployed, unless it's an asset
+fn rerender_template(
+    dry_run: bool,
+    dotfile: &Dotfile,
+    state: &mut State,
+    transaction: &mut Transaction,
+) -> Result<(), errors::Error> {
+    let target = dotfile.to_target_path().unwrap();
+    let Some(copy) = state.copies.get_mut(&target) else {
+        return Ok(());
+    };
+
+    if !config::get().is_asset(&dotfile.group_name) && copy.was_modified(&target) {
+        let warning = match dotfile.is_template() {
+            true => t!(
+                "warn.rendered_template_was_modified",
+                file = target.display()
+            ),
+            false => t!(
+                "warn.copy_was_modified_not_updated",
+                file = target.display()
+            ),
+        };
+        eprintln!("{}", warning.if_supports_color(Stderr, |t| t.yellow()));
+        return Ok(());
+    }
+
+    let rendered = match dotfile.is_template() {
+        true => templates::render_file(&dotfile.path, dotfile.dotfiles_dir())
+            .map(String::into_bytes)
+            .map_err(|err| err.if_supports_color(Stderr, |t| t.red()).to_string())?,
+        false => fs::read(&dotfile.path)
+            .map_err(|err| err.if_supports_color(Stderr, |t| t.red()).to_string())?,
+    };
+
+    if dry_run {
+        let recorded = plan::record(Step::Update {
+            source: dotfile.path.clone(),
+            target: target.clone(),
+        });
+        if !recorded {
+            eprintln!(
+                "{} `{}` to `{}`",
+                if dotfile.is_template() {
+                    "rendering"
+                } else {
+                    "updating"
+                }
+                .if_supports_color(Stderr, |t| t.green()),
+                dotfile.path.display(),
+                target.display()
             );
         }
+        return Ok(());
+    }
+
+    transaction
+        .write(&target, &rendered)
+        .map_err(|err| err.if_supports_color(Stderr, |t| t.red()).to_string())?;
+    copy.hash = Some(fileops::hash_bytes(rendered));
+    Ok(())
+}
+
+/// Removes a copy deployed by `copy_file` unless it was modified after being copied
+fn remove_copy(dry_run: bool, file: PathBuf, state: &mut State) {
+    let dotfile = Dotfile::try_from(file).unwrap();
+    let target = dotfile.to_target_path().unwrap();
+
+    let Some(copy) = state.copies.get(&target) else {
+        return;
+    };
+
+    if copy.source != dotfile.path {
+        return;
+    }
+
+    remove_copied_file(dry_run, target, state);
+}
+
+/// Removes a copy from the target unless it was modified since it was deployed
+fn remove_copied_file(dry_run: bool, target: PathBuf, state: &mut State) {
+    let Some(copy) = state.copies.get(&target) else {
+        return;
+    };
+
+    // the copy was already deleted by someone else
+    if !target.exists() && !target.is_symlink() {
+        if !dry_run {
+            state.copies.remove(&target);
+        }
+        return;
+    }
+
+    if copy.was_modified(&target) {
+        eprintln!(
+            "{}",
+            t!("warn.copy_was_modified", file = target.display())
+                .if_supports_color(Stderr, |t| t.yellow())
+        );
+        return;
+    }
+
+    if dry_run {
+        eprintln!(
+            "{} `{}`",
+            "removing".if_supports_color(Stderr, |t| t.red()),
+            target.display()
+        );
+        return;
+    }
+
+    let result = if copy.hash.is_none() {
+        // directories are left alone if they still have files that could not be removed
+        fs::remove_dir(&target)
+    } else {
+        fs::remove_file(&target)
+    };
+
+    if result.is_ok() {
+        state.copies.remove(&target);
     }
 }
 
@@ -102,35 +619,87 @@ enum SymlinkType {
 
 type HashCache = HashMap<String, HashSet<Dotfile>>;
 
+/// Where a dotfile is in its deployment, `SymlinkHandler::validate` sorts dotfiles into caches by it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum FileStatus {
+    Symlinked,
+    NotOwned,
+    NotSymlinked,
+    /// stale: whether the copy is a template that has to be rendered again
+    /// modified: whether the copy was edited on the target since it was deployed
+    Copied {
+        stale: bool,
+        modified: bool,
+    },
+}
+
 /// Handles dotfile symlinking and their current status
 struct SymlinkHandler {
-    dotfiles_dir: PathBuf,    // path to the dotfiles directory
-    symlinked: HashCache,     // dotfiles that have been symlinked from Dotfiles/Configs
-    not_symlinked: HashCache, // dotfiles that haven't been symlinked to $TUCKR_TARGET yet
+    dotfiles_dir: PathBuf,                 // path to the dotfiles directory
+    configs_dirs: Vec<PathBuf>, // Configs directories of the profile and of the profiles it builds on, in the order groups are looked up in
+    symlinked: HashCache,       // dotfiles that have been symlinked from Dotfiles/Configs
+    not_symlinked: HashCache,   // dotfiles that haven't been symlinked to $TUCKR_TARGET yet
     not_owned: HashCache, // dotfiles that are symlinks but points somewhere outside of their respective Dotfiles/Configs's group dir
+    copies: BTreeMap<PathBuf, CopiedFile>, // dotfiles that were deployed by copying them
+    links: BTreeMap<PathBuf, LinkedFile>, // dotfiles deployed as junctions or hardlinks since symlinks couldn't be created
+    experiments: HashSet<String>, // groups deployed from another branch with `tuckr try`, they're left alone
+    stale: HashCache, // templates whose rendered files are outdated compared to their template or variables
+    modified: HashCache, // copies and rendered templates that were edited on the target since they were deployed
+    status_cache: Option<StatusCache<Option<FileStatus>>>, // statuses found out by the last `tuckr status`, only it uses them
 }
 
 impl SymlinkHandler {
     /// Initializes SymlinkHandler and fills it dotfiles' status information
-    fn try_new(profile: Option<String>) -> Result<Self, ExitCode> {
-        let dotfiles_dir = match dotfiles::get_dotfiles_path(profile) {
-            Ok(dir) => dir,
+    fn try_new(profile: Option<String>) -> Result<Self, TuckrError> {
+        Self::load(profile, false)
+    }
+
+    /// Same as `try_new`, but the status of the dotfiles that didn't change since the last time
+    /// is taken from the status cache instead of being found out again
+    fn try_new_cached(profile: Option<String>) -> Result<Self, TuckrError> {
+        Self::load(profile, true)
+    }
+
+    fn load(profile: Option<String>, cached: bool) -> Result<Self, TuckrError> {
+        let search_path = match dotfiles::get_dotfiles_search_path(profile.clone()) {
+            Ok(search_path) => search_path,
             Err(e) => {
-                eprintln!("{e}");
-                return Err(ReturnCode::CouldntFindDotfiles.into());
+                errors::print(e);
+                return Err(TuckrError::CouldntFindDotfiles);
             }
         };
+        let dotfiles_dir = search_path[0].clone();
+        // the profile's own Configs directory is kept even when it's missing so that it's reported
+        let configs_dirs = search_path
+            .iter()
+            .map(|dir| dir.join(DotfileType::Configs.dir_name()))
+            .enumerate()
+            .filter(|(layer, dir)| *layer == 0 || dir.is_dir())
+            .map(|(_, dir)| dir)
+            .collect();
 
-        if let Err(err) = dotfiles::get_dotfiles_target_dir_path() {
-            eprintln!("{err}");
-            return Err(ReturnCode::NoSuchFileOrDir.into());
-        }
+        let target_dir = match dotfiles::get_dotfiles_target_dir_path() {
+            Ok(dir) => dir,
+            Err(err) => {
+                errors::print(err);
+                return Err(TuckrError::NoSuchFileOrDir);
+            }
+        };
 
+        let state = state::load_or_report(profile)?;
+        let status_cache = cached.then(|| StatusCache::load(&dotfiles_dir, &target_dir));
         let symlinker = SymlinkHandler {
             dotfiles_dir,
+            configs_dirs,
             symlinked: HashCache::new(),
             not_symlinked: HashCache::new(),
             not_owned: HashCache::new(),
+            copies: state.copies,
+            links: state.links,
+            experiments: state.experiments.into_keys().collect(),
+            stale: HashCache::new(),
+            modified: HashCache::new(),
+            status_cache,
         };
 
         // this fills the symlinker with dotfile status information
@@ -143,7 +712,7 @@ impl SymlinkHandler {
     /// into the struct
     ///
     /// Returns a copy of self with all the fields set accordingly
-    fn validate(mut self) -> Result<Self, ExitCode> {
+    fn validate(mut self) -> Result<Self, TuckrError> {
         let configs_dir = Dotfile::try_from(self.dotfiles_dir.join("Configs")).unwrap();
 
         if !configs_dir.path.exists() && !configs_dir.path.is_dir() {
@@ -151,54 +720,103 @@ impl SymlinkHandler {
                 "There is no Configs directory in dotfiles ({})",
                 configs_dir.path.display()
             );
-            return Err(ReturnCode::CouldntFindDotfiles.into());
+            return Err(TuckrError::CouldntFindDotfiles);
         }
 
         let mut symlinked = HashCache::new();
         let mut not_symlinked = HashCache::new();
         let mut not_owned = HashCache::new();
+        let mut stale_templates = HashCache::new();
+        let mut modified_copies = HashCache::new();
+        let mut errors = Vec::new();
+
+        let jobs = fileops::jobs();
+        let (walk_errors, unreadable_dirs) = mpsc::channel();
+        let mut files: Vec<Dotfile> = Vec::new();
+        for (layer, configs_path) in self.configs_dirs.iter().enumerate() {
+            let experiments = self.experiments.clone();
+            let shadowed = shadowed_groups(&self.configs_dirs, layer);
+            let root = configs_path.clone();
+            let walked = DirWalk::new(configs_path)
+                // groups that are being tried out from another branch aren't walked through, and
+                // neither are the groups of a base profile that the profile has too
+                .ignore(move |f| {
+                    let group = f
+                        .parent()
+                        .filter(|dir| *dir == root)
+                        .and_then(|_| f.file_name()?.to_str());
+                    let is_experiment = group.is_some_and(|group| {
+                        experiments.contains(group)
+                            || experiments.contains(dotfiles::group_without_target(group))
+                    });
+                    let is_shadowed = group.is_some_and(|group| shadowed.contains(group));
+
+                    is_experiment || is_shadowed || fileops::is_ignored_file(f)
+                })
+                .errors(walk_errors.clone())
+                .par_walk(jobs);
+
+            files.extend(
+                walked
+                    .into_iter()
+                    .map(|f| Dotfile::try_from(f).unwrap())
+                    // skip group directories otherwise it would try to link dotfiles/Configs/Groups to the users home
+                    .filter(|f| f.path != f.group_path),
+            );
+        }
 
-        // iterates over every file inside dotfiles/Config and determines their symlink status
-        for f in configs_dir.try_iter().unwrap() {
-            // skip group directories otherwise it would try to link dotfiles/Configs/Groups to the users home
-            if f.path == f.group_path {
-                continue;
-            }
-
-            let target = f.to_target_path().unwrap();
-
-            if target.is_symlink() {
-                let link = match fs::read_link(target) {
-                    Ok(link) => link,
-                    Err(err) => {
-                        eprintln!("{err}");
-                        continue;
-                    }
-                };
+        errors.extend(unreadable_dirs.try_iter().map(|err| err.to_string().into()));
 
-                if link == f.path {
-                    symlinked.entry(f.group_name.clone()).or_default();
+        // determines the symlink status of every file inside dotfiles/Configs
+        let statuses = match &self.status_cache {
+            Some(cache) => fileops::par_map(jobs, &files, |f| self.get_cached_status(cache, f)),
+            None => fileops::par_map(jobs, &files, |f| (self.get_status(f), None)),
+        };
 
-                    let group = symlinked.get_mut(&f.group_name).unwrap();
-                    group.insert(f);
-                } else {
-                    not_owned.entry(f.group_name.clone()).or_default();
+        if let Some(cache) = &mut self.status_cache {
+            cache.replace(
+                files
+                    .iter()
+                    .zip(&statuses)
+                    .filter_map(|(f, (status, stamp))| {
+                        Some((f.path.clone(), stamp.clone()?, status.clone().ok()?))
+                    }),
+            );
+            cache.save();
+        }
 
-                    let group = not_owned.get_mut(&f.group_name).unwrap();
-                    group.insert(f);
+        for (f, (status, _)) in files.into_iter().zip(statuses) {
+            let cache = match status {
+                Ok(Some(FileStatus::Symlinked)) => &mut symlinked,
+                Ok(Some(FileStatus::NotOwned)) => &mut not_owned,
+                Ok(Some(FileStatus::NotSymlinked)) => &mut not_symlinked,
+                Ok(Some(FileStatus::Copied { stale, modified })) => {
+                    if stale {
+                        stale_templates
+                            .entry(f.group_name.clone())
+                            .or_default()
+                            .insert(f.clone());
+                    }
+                    if modified {
+                        modified_copies
+                            .entry(f.group_name.clone())
+                            .or_default()
+                            .insert(f.clone());
+                    }
+                    &mut symlinked
                 }
-            } else {
-                if target.is_dir() {
+                Ok(None) => continue,
+                Err(err) => {
+                    errors.push(err.into());
                     continue;
                 }
+            };
 
-                not_symlinked.entry(f.group_name.clone()).or_default();
-
-                let group = not_symlinked.get_mut(&f.group_name).unwrap();
-                group.insert(f);
-            }
+            cache.entry(f.group_name.clone()).or_default().insert(f);
         }
 
+        report_errors(errors);
+
         fn remove_empty_groups(group_type: HashCache) -> HashCache {
             group_type
                 .iter()
@@ -211,17 +829,12 @@ impl SymlinkHandler {
         // this procedure makes so that symlinks are shallow.
         //
         // shallow symlinking: only symlinking files/directories that don't exist already
+        //
+        // parents are looked up instead of comparing every pair of files since groups can have thousands of them
         fn canonicalize_groups(groups: &mut HashCache) {
             for files in groups.values_mut() {
-                let files_copy = files.clone();
-
-                for file in &files_copy {
-                    for file2 in &files_copy {
-                        if file2.path != file.path && file2.path.starts_with(&file.path) {
-                            files.remove(file2);
-                        }
-                    }
-                }
+                let paths: HashSet<PathBuf> = files.iter().map(|f| f.path.clone()).collect();
+                files.retain(|f| !f.path.ancestors().skip(1).any(|dir| paths.contains(dir)));
             }
         }
 
@@ -234,15 +847,9 @@ impl SymlinkHandler {
                 continue;
             };
 
-            let unsymlinked_group_copy = unsymlinked_group.clone();
-
-            for file1 in files {
-                for file2 in unsymlinked_group_copy.iter() {
-                    if file2.path.starts_with(&file1.path) {
-                        unsymlinked_group.remove(file2);
-                    }
-                }
-            }
+            let symlinked_paths: HashSet<&Path> = files.iter().map(|f| f.path.as_path()).collect();
+            unsymlinked_group
+                .retain(|f| !f.path.ancestors().any(|dir| symlinked_paths.contains(dir)));
         }
 
         canonicalize_groups(&mut symlinked);
@@ -252,24 +859,226 @@ impl SymlinkHandler {
         self.symlinked = remove_empty_groups(symlinked);
         self.not_symlinked = remove_empty_groups(not_symlinked);
         self.not_owned = remove_empty_groups(not_owned);
+        self.stale = stale_templates;
+        self.modified = modified_copies;
 
         Ok(self)
     }
 
-    fn is_empty(&self) -> bool {
-        self.symlinked.is_empty() && self.not_symlinked.is_empty() && self.not_owned.is_empty()
+    /// Finds out whether a dotfile is deployed, None is returned for directories that are only partially deployed
+    ///
+    /// What was found is logged with `-vv` so that it's clear why a file was or wasn't deployed
+    fn get_status(&self, f: &Dotfile) -> Result<Option<FileStatus>, String> {
+        let target = f.to_target_path()?;
+        let status = self.compare(f, &target);
+        tracing::debug!(
+            dotfile = %f.path.display(),
+            path = %target.display(),
+            ?status,
+            "compared the dotfile with its target"
+        );
+        status
     }
 
-    /// only meant for internal use
-    fn get_related_cond_groups(
+    /// Same as `get_status`, but the cached status is taken if neither the dotfile nor its target
+    /// changed since it was cached, along with what's needed to cache it again
+    ///
+    /// Templates are always rendered again since their variables can change without them changing
+    fn get_cached_status(
         &self,
-        target_group: &str,
-        cache: &HashCache,
-    ) -> Option<Vec<String>> {
-        if dotfiles::group_ends_with_target_name(target_group) && !cache.contains_key(target_group)
-        {
-            return None;
-        }
+        cache: &StatusCache<Option<FileStatus>>,
+        f: &Dotfile,
+    ) -> (Result<Option<FileStatus>, String>, Option<Stamp>) {
+        let stamp = match f.to_target_path() {
+            Ok(target) if !f.is_template() => Stamp::read(&f.path, &target),
+            _ => None,
+        };
+
+        if let Some(status) = stamp.as_ref().and_then(|stamp| cache.get(&f.path, stamp)) {
+            tracing::trace!(dotfile = %f.path.display(), ?status, "reused the cached status");
+            return (Ok(status), stamp);
+        }
+
+        let status = self.get_status(f);
+        let stamp = stamp.filter(|_| status.is_ok());
+        (status, stamp)
+    }
+
+    /// Compares the dotfile with what's at its target
+    fn compare(&self, f: &Dotfile, target: &Path) -> Result<Option<FileStatus>, String> {
+        // junctions are symlinks as well but they don't point to the dotfile's exact path
+        if let Some(link) = self.links.get(target)
+            && link.source == f.path
+            && is_linked(target, link)
+        {
+            return Ok(Some(FileStatus::Symlinked));
+        }
+
+        if target.is_symlink() {
+            // relative symlinks are as much the dotfile's as absolute ones
+            let owned = fileops::links_to(target, &f.path);
+
+            // another group's directory is folded into the target, what's inside of it decides
+            // since it's unfolded when this group is added
+            if !owned && f.path.is_dir() && self.folded_dir(target).is_some() {
+                return Ok(None);
+            }
+
+            return Ok(Some(if owned {
+                FileStatus::Symlinked
+            } else {
+                FileStatus::NotOwned
+            }));
+        }
+
+        if let Some(copy) = self.copies.get(target)
+            && copy.source == f.path
+            && target.exists()
+        {
+            // templates that fail to render are also stale so that the error shows up when adding them
+            let stale = f.is_template()
+                && templates::render_file(&f.path, f.dotfiles_dir())
+                    .map(fileops::hash_bytes)
+                    .ok()
+                    != copy.hash;
+
+            // copies of dotfiles that changed since they were copied are updated by adding them again
+            let stale = stale || self.source_changed(f, copy);
+
+            // modified copies of assets are stale too, so that adding them again overwrites them
+            let modified = copy.was_modified(target);
+            let is_asset = config::get().is_asset(&f.group_name);
+            let stale = stale || (is_asset && modified);
+
+            return Ok(Some(FileStatus::Copied {
+                stale,
+                modified: modified && !is_asset,
+            }));
+        }
+
+        if target.is_dir() {
+            return Ok(None);
+        }
+
+        Ok(Some(FileStatus::NotSymlinked))
+    }
+
+    /// Returns the directory in dotfiles/Configs that `target` is a symlink to, when a group's
+    /// directory was symlinked as a whole (folded) there
+    fn folded_dir(&self, target: &Path) -> Option<PathBuf> {
+        let link = fileops::read_link(target).ok()?;
+
+        (link.is_dir() && self.configs_dirs.iter().any(|dir| link.starts_with(dir))).then_some(link)
+    }
+
+    /// Returns the directory of a group, from the first Configs directory that has it
+    fn group_dir(&self, group: &str) -> PathBuf {
+        self.configs_dirs
+            .iter()
+            .map(|dir| dir.join(group))
+            .find(|dir| dir.exists())
+            .unwrap_or_else(|| self.dotfiles_dir.join("Configs").join(group))
+    }
+
+    /// Returns the groups' directories in every Configs directory, leaving out the ones that are
+    /// shadowed by a group of the same name in an earlier one
+    fn group_dirs(&self) -> Vec<PathBuf> {
+        self.configs_dirs
+            .iter()
+            .enumerate()
+            .flat_map(|(layer, configs_dir)| {
+                let shadowed = shadowed_groups(&self.configs_dirs, layer);
+                fs::read_dir(configs_dir)
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .filter(move |entry| {
+                        !shadowed.contains(entry.file_name().to_string_lossy().as_ref())
+                    })
+                    .map(|entry| entry.path())
+                    .filter(|dir| dir.is_dir())
+            })
+            .collect()
+    }
+
+    /// Returns the dotfiles directory that a file of the Configs directories is in
+    fn dotfiles_dir_of(&self, file: &Path) -> &Path {
+        self.configs_dirs
+            .iter()
+            .find(|dir| file.starts_with(dir))
+            .and_then(|dir| dir.parent())
+            .unwrap_or(&self.dotfiles_dir)
+    }
+
+    /// Records what's deployed in the state, returns true if the state changed
+    ///
+    /// Files deployed from dotfiles that are gone are kept until they're removed,
+    /// since the state is the only way of knowing where they came from
+    fn record_deployed(&self, profile: Option<String>, state: &mut State) -> bool {
+        let mut deployed = BTreeMap::new();
+        for f in self.symlinked.values().flatten() {
+            let Ok(target) = f.to_target_path() else {
+                continue;
+            };
+
+            let method = match (state.copies.contains_key(&target), state.links.get(&target)) {
+                (true, _) if f.is_template() => DeployMethod::Template,
+                (true, _) => DeployMethod::Copy,
+                (false, Some(link)) => link.method,
+                (false, None) => DeployMethod::Symlink,
+            };
+            let file = match state.deployed.get(&target) {
+                Some(file) if file.source == f.path && file.method == method => file.clone(),
+                _ => DeployedFile::new(&f.group_name, &f.path, profile.clone(), method),
+            };
+            deployed.insert(target, file);
+        }
+
+        for (target, file) in &state.deployed {
+            if file.is_orphaned() && fs::symlink_metadata(target).is_ok() {
+                deployed
+                    .entry(target.clone())
+                    .or_insert_with(|| file.clone());
+            }
+        }
+
+        let changed = deployed != state.deployed;
+        state.deployed = deployed;
+        changed
+    }
+
+    /// Returns true if the dotfile changed since it was copied, templates are compared once rendered instead
+    fn source_changed(&self, f: &Dotfile, copy: &CopiedFile) -> bool {
+        copy.hash.is_some() && !f.is_template() && fileops::hash_file(&f.path).ok() != copy.hash
+    }
+
+    /// Forgets about the excluded groups so that they're left alone
+    fn exclude_groups(&mut self, exclude: &[String]) {
+        for cache in [
+            &mut self.symlinked,
+            &mut self.not_symlinked,
+            &mut self.not_owned,
+            &mut self.stale,
+            &mut self.modified,
+        ] {
+            cache.retain(|group, _| !dotfiles::is_excluded(group, exclude));
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.symlinked.is_empty() && self.not_symlinked.is_empty() && self.not_owned.is_empty()
+    }
+
+    /// only meant for internal use
+    fn get_related_cond_groups(
+        &self,
+        target_group: &str,
+        cache: &HashCache,
+    ) -> Option<Vec<String>> {
+        if dotfiles::group_ends_with_target_name(target_group) && !cache.contains_key(target_group)
+        {
+            return None;
+        }
 
         let cond_groups: Vec<String> = cache
             .iter()
@@ -330,13 +1139,62 @@ impl SymlinkHandler {
         }
     }
 
+    /// Returns the files that groups of different base groups deploy to the same place, along with
+    /// the groups that deploy them ordered by which one wins
+    fn find_collisions(&self) -> BTreeMap<PathBuf, Vec<String>> {
+        let (tx, _) = mpsc::channel();
+        let mut deployed: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+        for f in self
+            .group_dirs()
+            .into_iter()
+            .flat_map(|group_dir| {
+                DirWalk::new(group_dir)
+                    .errors(tx.clone())
+                    .ignore(|f| fileops::is_ignored_file(f))
+            })
+            .filter(|f| !f.is_dir())
+        {
+            let Ok(f) = Dotfile::try_from(f) else {
+                continue;
+            };
+            if !f.is_valid_target() {
+                continue;
+            }
+            if let Ok(target) = f.to_target_path() {
+                deployed.entry(target).or_default().push(f.group_name);
+            }
+        }
+
+        deployed
+            .into_iter()
+            .filter_map(|(target, mut groups)| {
+                groups.sort_by(
+                    |group, other| match dotfiles::wins_collision(group, other) {
+                        true => std::cmp::Ordering::Less,
+                        false => std::cmp::Ordering::Greater,
+                    },
+                );
+                groups.dedup();
+
+                let base_group = dotfiles::group_without_target(&groups[0]);
+                groups
+                    .iter()
+                    .any(|group| dotfiles::group_without_target(group) != base_group)
+                    .then_some((target, groups))
+            })
+            .collect()
+    }
+
     /// returns a cache with files in dotfiles that already exist in $TUCKR_TARGET
     fn get_conflicts_in_cache(&self) -> HashCache {
         let mut conflicts = HashCache::new();
 
+        // assets overwrite whatever is in their place so they never conflict
+        let is_asset = |file: &&Dotfile| config::get().is_asset(&file.group_name);
+
         // mark group as conflicting if at least one value already exists in $TUCKR_TARGET
         for files in self.not_symlinked.values() {
-            for file in files {
+            for file in files.iter().filter(|file| !is_asset(file)) {
                 if file.to_target_path().unwrap().exists() && file.is_valid_target() {
                     conflicts.entry(file.group_name.clone()).or_default();
                     let curr_entry = conflicts.get_mut(&file.group_name).unwrap();
@@ -345,27 +1203,17 @@ impl SymlinkHandler {
             }
         }
 
-        // doesn't mark not owned dotfiles as conflicts if a higher priority dotfile
-        // with the same file is already symlinked. this allows dotfile fallbacks to
-        // work properly instead of falsely flagged as conflicts
+        // not owned dotfiles that are symlinked from another group aren't conflicts: conditional
+        // groups fall back to each other and otherwise the group that wins the collision deploys
+        // the file, see dotfiles::wins_collision
         for files in self.not_owned.values() {
-            for file in files {
-                conflicts.entry(file.group_name.clone()).or_default();
-                let curr_entry = conflicts.get_mut(&file.group_name).unwrap();
-
-                let dotfile_source = file.to_target_path().unwrap().read_link().unwrap();
-                let Ok(dotfile) = Dotfile::try_from(dotfile_source) else {
-                    curr_entry.insert(file.clone());
-                    continue;
-                };
-
-                let target_has_higher_priority = dotfiles::get_group_priority(&dotfile.group_name)
-                    > dotfiles::get_group_priority(&file.group_name);
-                let not_same_base_group = dotfiles::group_without_target(&file.group_name)
-                    != dotfiles::group_without_target(&dotfile.group_name);
-
-                if dotfile.path != file.path && target_has_higher_priority && not_same_base_group {
-                    curr_entry.insert(file.clone());
+            for file in files.iter().filter(|file| !is_asset(file)) {
+                let dotfile_source = fileops::read_link(&file.to_target_path().unwrap()).unwrap();
+                if Dotfile::try_from(dotfile_source).is_err() {
+                    conflicts
+                        .entry(file.group_name.clone())
+                        .or_default()
+                        .insert(file.clone());
                 }
             }
         }
@@ -374,22 +1222,75 @@ impl SymlinkHandler {
     }
 
     /// Symlinks all the files of a group to the user's $TUCKR_TARGET
-    fn add(&self, dry_run: bool, only_files: bool, group: &str) {
+    ///
+    /// copy: copies the files instead, groups can also be set to be copied in the config
+    ///
+    /// Every change is recorded in the transaction so that it can be rolled back,
+    /// returns false if any of the files couldn't be deployed
+    fn add(
+        &self,
+        dry_run: bool,
+        only_files: bool,
+        copy: bool,
+        state: &mut State,
+        transaction: &mut Transaction,
+        group: &str,
+    ) -> bool {
         let Some(mut groups) =
             self.get_related_conditional_groups(group, SymlinkType::NotSymlinked.into())
         else {
-            return;
+            return true;
         };
 
-        loop {
-            let Some(idx) = dotfiles::get_highest_priority_target_idx(&groups) else {
-                break;
-            };
+        let mut errors = Vec::new();
 
+        while let Some(idx) = dotfiles::get_highest_priority_target_idx(&groups) {
             let group = &groups[idx];
-            let group = Dotfile::try_from(self.dotfiles_dir.join("Configs").join(group)).unwrap();
+            tracing::debug!(group = %group, "deploying the group with the highest priority");
+            let group = Dotfile::try_from(self.group_dir(group)).unwrap();
+            let copy = copy || config::get().deploy_mode(&group.group_name) == DeployMode::Copy;
+            // suffixed files go into the same directories as the other platforms' ones
+            let only_files = only_files
+                || group.target_suffix().is_some()
+                || !config::get().folds(&group.group_name);
+
             if group.path.exists() {
-                for f in group.try_iter().unwrap() {
+                // directories that are symlinked as a whole, the files inside of them come along with them
+                let mut linked_dirs = HashSet::new();
+                let mut to_symlink = Vec::new();
+
+                for f in DirWalk::new(&group.path).par_walk(fileops::jobs()) {
+                    let f = Dotfile::try_from(f).unwrap();
+
+                    // the group's files can't go into another group's folded directory
+                    // without ending up in that group's dotfiles
+                    if f.path.is_dir() {
+                        let target = f.to_target_path().unwrap();
+                        if let Some(folded) = self.folded_dir(&target)
+                            && folded != f.path
+                        {
+                            let folded_group = Dotfile::try_from(folded.clone())
+                                .map(|folded| folded.group_name)
+                                .unwrap_or_default();
+                            tracing::info!(
+                                dir = %target.display(),
+                                group = %folded_group,
+                                "the directory is symlinked as a whole by another group"
+                            );
+
+                            // nothing inside of it is deployed since it would go into the other group's dotfiles
+                            if safe::skip(&target, safe::Reason::Folded(folded_group)) {
+                                linked_dirs.insert(f.path.clone());
+                                continue;
+                            }
+
+                            if let Err(err) = unfold_dir(dry_run, &target, &folded, transaction) {
+                                errors.push(err);
+                            }
+                            continue;
+                        }
+                    }
+
                     if only_files {
                         if f.path.is_dir() {
                             continue;
@@ -399,51 +1300,131 @@ impl SymlinkHandler {
                         let f_target = f.to_target_path().unwrap();
                         let target_parent = f_target.parent().unwrap();
 
-                        if !target_parent.exists() {
-                            println!("creating parent dir for {group:?}");
-                            fs::create_dir_all(target_parent).unwrap();
+                        // a dry run doesn't create anything
+                        if !dry_run
+                            && !target_parent.exists()
+                            && let Err(err) = transaction.create_dir_all(target_parent)
+                        {
+                            errors.push(err.to_string().into());
+                            continue;
+                        }
+                    }
+
+                    if f.path
+                        .ancestors()
+                        .skip(1)
+                        .any(|dir| linked_dirs.contains(dir))
+                    {
+                        tracing::trace!(
+                            dotfile = %f.path.display(),
+                            "skipping, its directory is symlinked as a whole"
+                        );
+                        continue;
+                    }
+
+                    if cancel::is_cancelled() {
+                        break;
+                    }
+
+                    let deployed = if copy || f.is_template() {
+                        copy_file(dry_run, f.path.clone(), state, transaction)
+                    } else if templates::contains_templates(&f.path)
+                        || dotfiles::contains_ignored_files(&f.path)
+                    {
+                        create_dir(dry_run, &f, transaction)
+                    } else if !escalate::symlinks_allowed() {
+                        if f.path.is_dir() && !f.to_target_path().unwrap().exists() {
+                            linked_dirs.insert(f.path.clone());
+                        }
+                        link_without_symlink(dry_run, &f.path, state, transaction)
+                    } else {
+                        if f.path.is_dir() && !f.to_target_path().unwrap().exists() {
+                            linked_dirs.insert(f.path.clone());
                         }
+                        to_symlink.push(f);
+                        Ok(())
+                    };
+
+                    if let Err(err) = deployed {
+                        errors.push(err);
                     }
+                }
 
-                    symlink_file(dry_run, f.path);
+                // none of the files are inside of one another so they can be symlinked in any order,
+                // dry runs are kept sequential so that what would happen is printed in order
+                let jobs = if dry_run { 1 } else { fileops::jobs() };
+                let results = fileops::par_map(jobs, &to_symlink, |f| {
+                    (!cancel::is_cancelled()).then(|| symlink_file(dry_run, f))
+                });
+
+                for result in results.into_iter().flatten() {
+                    match result {
+                        Ok(created) => created
+                            .into_iter()
+                            .for_each(|path| transaction.created(path)),
+                        Err(err) => errors.push(err),
+                    }
                 }
             } else {
-                eprintln!(
-                    "{}",
-                    t!("errors.no_dotfiles_for_group", group = group.group_name).red()
-                );
+                errors::print(error!("no_dotfiles_for_group", group = group.group_name));
+            }
+
+            if cancel::is_cancelled() {
+                break;
             }
 
             groups.remove(idx);
         }
+
+        let succeeded = errors.is_empty();
+        report_errors(errors);
+        succeeded
     }
 
-    /// Deletes symlinks from $TUCKR_TARGET if they're owned by dotfiles dir
-    fn remove(&self, dry_run: bool, group: &str) {
-        fn remove_symlink(dry_run: bool, file: PathBuf) {
+    /// Deletes symlinks and copies from $TUCKR_TARGET if they're owned by dotfiles dir
+    fn remove(&self, dry_run: bool, state: &mut State, group: &str) {
+        let remove_symlink = |dry_run: bool, file: PathBuf| {
             let dotfile = Dotfile::try_from(file).unwrap();
             let target_dotfile = dotfile.to_target_path().unwrap();
-            let Ok(linked) = fs::read_link(&target_dotfile) else {
-                return;
-            };
 
-            if dotfile.path != linked {
+            let dotfiles_dir = self.dotfiles_dir_of(&dotfile.path);
+            if !links_to_dotfile(&target_dotfile, &dotfile.path, dotfiles_dir) {
+                tracing::info!(
+                    path = %target_dotfile.display(),
+                    "not removing, it doesn't link to the dotfile"
+                );
                 return;
             }
+            tracing::info!(path = %target_dotfile.display(), "removing the symlink");
+
+            let privileged = escalate::needs_privileges(&target_dotfile);
 
             if dry_run {
-                eprintln!("{} `{}`", "removing".red(), target_dotfile.display());
+                eprintln!(
+                    "{} `{}`{}",
+                    "removing".if_supports_color(Stderr, |t| t.red()),
+                    target_dotfile.display(),
+                    if privileged { " (as root)" } else { "" }
+                );
                 return;
             }
 
-            if target_dotfile.is_dir() {
-                fs::remove_dir_all(&target_dotfile).unwrap();
-            } else {
-                fs::remove_file(&target_dotfile)
-                    .map_err(|err| format!("error with path `{}`: {err}", target_dotfile.display()))
-                    .unwrap();
+            if privileged {
+                escalate::defer(Operation::Remove {
+                    path: target_dotfile,
+                });
+                return;
             }
-        }
+
+            // only the symlink itself is removed, never what it points to
+            let removed = match cfg!(target_family = "windows") && target_dotfile.is_dir() {
+                true => fs::remove_dir(&target_dotfile),
+                false => fs::remove_file(&target_dotfile),
+            };
+            removed
+                .map_err(|err| format!("error with path `{}`: {err}", target_dotfile.display()))
+                .unwrap();
+        };
 
         let Some(groups) =
             self.get_related_conditional_groups(group, SymlinkType::Symlinked.into())
@@ -452,21 +1433,95 @@ impl SymlinkHandler {
         };
 
         for group in groups {
-            let group = Dotfile::try_from(self.dotfiles_dir.join("Configs").join(&group)).unwrap();
+            let group = Dotfile::try_from(self.group_dir(&group)).unwrap();
 
             if !group.path.exists() {
-                eprintln!("{}", t!("errors.no_group", group = group.group_name).red());
+                errors::print(error!("no_group", group = group.group_name));
                 continue;
             }
 
-            group
-                .try_iter()
-                .unwrap()
-                .for_each(|f| remove_symlink(dry_run, f.path));
+            let files: Vec<_> = group.try_iter().unwrap().collect();
+
+            // files come after their parent directory so they're removed in reverse
+            // to empty copied directories before trying to remove them
+            for f in files.into_iter().rev() {
+                if remove_link(dry_run, &f, state) {
+                    continue;
+                }
+
+                if f.to_target_path().unwrap().is_symlink() {
+                    remove_symlink(dry_run, f.path);
+                } else {
+                    remove_copy(dry_run, f.path, state);
+                }
+            }
+        }
+    }
+}
+
+/// Returns the groups in the Configs directories before `layer`, they shadow the groups of the
+/// same name in the Configs directory at `layer`
+fn shadowed_groups(configs_dirs: &[PathBuf], layer: usize) -> HashSet<String> {
+    configs_dirs[..layer]
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect()
+}
+
+/// Returns true if `target` is a symlink to the dotfile, relative symlinks are resolved from where they are
+///
+/// Symlinks that lead outside of the dotfiles directory never count,
+/// so that removing dotfiles can't remove files that aren't tuckr's
+fn links_to_dotfile(target: &Path, dotfile: &Path, dotfiles_dir: &Path) -> bool {
+    let Ok(link) = fs::read_link(target) else {
+        return false;
+    };
+
+    let link = match target.parent() {
+        Some(dir) => dir.join(link),
+        None => link,
+    };
+
+    if link == dotfile {
+        return dotfile.starts_with(dotfiles_dir);
+    }
+
+    match (
+        link.canonicalize(),
+        dotfile.canonicalize(),
+        dotfiles_dir.canonicalize(),
+    ) {
+        (Ok(link), Ok(dotfile), Ok(dotfiles_dir)) => {
+            link == dotfile && link.starts_with(dotfiles_dir)
+        }
+        _ => false,
+    }
+}
+
+/// Prints the errors in the order they happened, errors that were already printed are skipped
+///
+/// A group that can't be deployed fails the same way for each of its files so it's only reported once
+fn report_errors(errors: impl IntoIterator<Item = errors::Error>) {
+    let mut reported = HashSet::new();
+
+    for err in errors {
+        if reported.insert(err.to_string()) {
+            errors::print(err);
         }
     }
 }
 
+/// Prints the changes that couldn't be undone by rolling back
+fn report_rollback_errors(errors: Vec<String>) {
+    for err in errors {
+        errors::print(error!("failed_to_roll_back", err = err));
+    }
+}
+
 /// groups: the groups that will be iterated
 ///
 /// exclude: the groups that will be ignored
@@ -480,9 +1535,11 @@ fn foreach_group<F: Fn(&SymlinkHandler, &String)>(
     exclude: &[String],
     symlinked: bool,
     func: F,
-) -> Result<(), ExitCode> {
+) -> Result<(), TuckrError> {
     // loads the runtime information needed to carry out actions
-    let sym = SymlinkHandler::try_new(profile.clone())?;
+    let mut sym = SymlinkHandler::try_new(profile.clone())?;
+    // excluded groups are also left out when they're picked up as conditional groups of another group
+    sym.exclude_groups(exclude);
 
     let groups = {
         // detect if user provided an invalid group
@@ -512,7 +1569,7 @@ fn foreach_group<F: Fn(&SymlinkHandler, &String)>(
                     }
 
                     for group in groups_checked_as_invalid {
-                        eprintln!("{}", t!("errors.x_doesnt_exist", x = group).red());
+                        errors::print(error!("x_doesnt_exist", x = group));
                     }
 
                     valid_groups
@@ -522,21 +1579,38 @@ fn foreach_group<F: Fn(&SymlinkHandler, &String)>(
             };
 
         if valid_groups.is_empty() {
-            return Err(ReturnCode::NoSetupFolder.into());
+            return Err(TuckrError::NoSetupFolder);
         }
 
         valid_groups
     };
 
+    // Ctrl-C stops once the current group is done
+    let _deferred = cancel::defer();
+    let action = if symlinked { "adding" } else { "removing" };
+
     if groups.contains(&"*".to_string()) {
-        let symgroups = if symlinked {
-            &sym.not_symlinked
+        // groups with stale templates are added again so that their templates get rendered
+        let symgroups: HashSet<_> = if symlinked {
+            sym.not_symlinked
+                .keys()
+                .chain(sym.stale.keys())
+                .chain(
+                    sym.not_owned
+                        .keys()
+                        .filter(|group| config::get().is_asset(group)),
+                )
+                .collect()
         } else {
-            &sym.symlinked
+            sym.symlinked.keys().collect()
         };
 
-        for group in symgroups.keys() {
-            if exclude.contains(group) {
+        let progress = Progress::new(symgroups.len(), action);
+        for group in symgroups {
+            cancel::check()?;
+            progress.inc();
+
+            if dotfiles::is_excluded(group, exclude) {
                 continue;
             }
 
@@ -544,153 +1618,1331 @@ fn foreach_group<F: Fn(&SymlinkHandler, &String)>(
                 continue;
             }
 
+            // groups for software this machine doesn't have are only added when they're named
+            if symlinked && !preflight::requirements_met(group) {
+                continue;
+            }
+
             // do something with the group name
             // passing the sym context
+            progress.start(group);
             func(&sym, group);
         }
 
-        return Ok(());
+        return cancel::check();
     }
 
+    let progress = Progress::new(groups.len(), action);
     for group in groups {
-        if exclude.contains(&group) {
+        cancel::check()?;
+        progress.inc();
+
+        if dotfiles::is_excluded(&group, exclude) {
             continue;
         }
+        progress.start(&group);
         func(&sym, &group);
     }
 
-    Ok(())
+    cancel::check()
+}
+
+/// Returns the groups that are deployed and whether they were deployed by copying them
+pub fn get_deployed_groups(profile: Option<String>) -> Result<BTreeMap<String, bool>, TuckrError> {
+    let sym = SymlinkHandler::try_new(profile)?;
+
+    Ok(sym
+        .symlinked
+        .iter()
+        .map(|(group, files)| {
+            // rendered templates are tracked as copies no matter how the group was deployed
+            let copied = files.iter().any(|file| {
+                !file.is_template() && sym.copies.contains_key(&file.to_target_path().unwrap())
+            });
+            (group.clone(), copied)
+        })
+        .collect())
+}
+
+/// How much of a group is deployed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeployState {
+    Deployed,
+    Partial,
+    NotDeployed,
+}
+
+/// Returns the deployment state of every group in Configs, conditional groups are kept separate
+pub fn get_deploy_states(
+    profile: Option<String>,
+) -> Result<BTreeMap<String, DeployState>, TuckrError> {
+    let sym = SymlinkHandler::try_new(profile)?;
+
+    let groups: BTreeSet<&String> = sym
+        .symlinked
+        .keys()
+        .chain(sym.not_symlinked.keys())
+        .chain(sym.not_owned.keys())
+        .collect();
+
+    Ok(groups
+        .into_iter()
+        .map(|group| {
+            let missing =
+                sym.not_symlinked.contains_key(group) || sym.not_owned.contains_key(group);
+            let state = match (sym.symlinked.contains_key(group), missing) {
+                (true, false) => DeployState::Deployed,
+                (true, true) => DeployState::Partial,
+                (false, _) => DeployState::NotDeployed,
+            };
+            (group.clone(), state)
+        })
+        .collect())
+}
+
+/// Returns where the dotfiles of every group that's at least partially deployed were deployed to
+pub fn get_deployed_files(
+    profile: Option<String>,
+) -> Result<BTreeMap<String, Vec<PathBuf>>, TuckrError> {
+    let sym = SymlinkHandler::try_new(profile.clone())?;
+    let state = state::load_or_report(profile)?;
+
+    let mut deployed: BTreeMap<_, _> = sym
+        .symlinked
+        .iter()
+        .map(|(group, files)| {
+            let mut targets: Vec<_> = files
+                .iter()
+                .filter_map(|f| f.to_target_path().ok())
+                .collect();
+            targets.sort();
+            (group.clone(), targets)
+        })
+        .collect();
+
+    // groups that are gone from the dotfiles are only known through the state
+    for (group, files) in state.orphaned_groups() {
+        deployed.insert(
+            group.into(),
+            files.into_iter().map(Path::to_path_buf).collect(),
+        );
+    }
+
+    Ok(deployed)
+}
+
+/// Asks whether conflicts should be overridden or adopted when `force` or `adopt` is used
+///
+/// Returns false if the user didn't agree to it, tuckrs run by hooks don't ask again once they did
+pub fn confirm_conflicts(force: bool, adopt: bool, assume_yes: bool) -> bool {
+    if !force && !adopt {
+        return true;
+    }
+
+    if !assume_yes && !nested::is_confirmed() {
+        let question = match force {
+            true => t!("warn.want_to_override_conflicts"),
+            false => t!("warn.want_to_adopt_conflicts"),
+        };
+
+        if !interactive::confirm(question) {
+            return false;
+        }
+    }
+
+    nested::confirm();
+    true
 }
 
 /// Adds symlinks
+///
+/// copy: deploys the dotfiles by copying them instead of symlinking
 #[allow(clippy::too_many_arguments)]
 pub fn add_cmd(
     profile: Option<String>,
     dry_run: bool,
     only_files: bool,
+    copy: bool,
     groups: &[String],
     exclude: &[String],
     force: bool,
     adopt: bool,
     assume_yes: bool,
-) -> Result<(), ExitCode> {
-    if !assume_yes {
-        if force {
-            print!("Are you sure you want to override conflicts? (N/y) ");
-        } else if adopt {
-            print!("Are you sure you want to adopt conflicts? (N/y) ");
+) -> Result<(), TuckrError> {
+    if !confirm_conflicts(force, adopt, assume_yes) {
+        return Ok(());
+    }
+
+    // conflicting files are backed up instead of deleted so that they can be restored with `tuckr backups restore`
+    let backup_set = match BackupSet::try_new(profile.clone()) {
+        Ok(backup_set) => RefCell::new(backup_set),
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::CouldntFindDotfiles);
+        }
+    };
+
+    let state = RefCell::new(state::load_or_report(profile.clone())?);
+    let copies_before = state.borrow().copies.clone();
+    let links_before = state.borrow().links.clone();
+    let transaction = RefCell::new(Transaction::new());
+    let failed = Cell::new(false);
+    // answering [A]ll overwrites the rest of the conflicts, the other groups' ones included
+    let overwrite_all = Cell::new(false);
+
+    let result = foreach_group(profile.clone(), groups, exclude, true, |sym, group| {
+        // the rest of the groups would only be rolled back as well
+        if failed.get() && transaction::rollback_enabled() {
+            return;
         }
 
-        if force || adopt {
-            std::io::stdout()
-                .flush()
-                .expect("Could not print to stdout");
+        if state.borrow().is_held(group) {
+            eprintln!(
+                "{}",
+                t!("info.skipping_held_group", group = group)
+                    .if_supports_color(Stderr, |t| t.yellow())
+            );
+            return;
+        }
 
-            let mut answer = String::new();
-            std::io::stdin()
-                .read_line(&mut answer)
-                .expect("Could not read from stdin");
+        let transaction = &mut *transaction.borrow_mut();
+        let checkpoint = transaction.checkpoint();
 
-            match answer.trim().to_lowercase().as_str() {
-                "y" | "yes" => (),
-                _ => return Ok(()),
+        let backup_file = |file: &Dotfile, transaction: &mut Transaction| -> bool {
+            let target_file = file.to_target_path().unwrap();
+            if !target_file.exists() && !target_file.is_symlink() {
+                return true;
             }
-        }
-    }
 
-    foreach_group(profile.clone(), groups, exclude, true, |sym, group| {
-        let remove_files_and_decide_if_adopt = |status_group: &HashCache, adopt: bool| {
-            let group = status_group.get(group);
-            if let Some(group_files) = group {
-                for file in group_files {
-                    let target_file = file.to_target_path().unwrap();
+            match backup_set.borrow_mut().backup(dry_run, &target_file) {
+                Ok(backup) if !dry_run => transaction.moved(target_file, backup),
+                Ok(_) => (),
+                Err(err) => {
+                    errors::print(err);
+                    return false;
+                }
+            }
 
-                    let deleted_file = if adopt { &file.path } else { &target_file };
+            true
+        };
 
-                    if dry_run {
-                        eprintln!("{} `{}`", "removing".red(), deleted_file.display());
-                    } else if target_file.is_dir() {
-                        fs::remove_dir_all(deleted_file).unwrap();
-                    } else if target_file.is_file() {
-                        fs::remove_file(deleted_file).unwrap();
-                    }
+        let backup_files = |status_group: &HashCache, transaction: &mut Transaction| -> bool {
+            let Some(group_files) = status_group.get(group) else {
+                return true;
+            };
 
-                    if adopt {
-                        if dry_run {
-                            eprintln!(
-                                "{} `{}` to `{}`",
-                                "moving".yellow(),
-                                target_file.display(),
-                                file.path.display()
-                            );
-                        } else {
-                            fs::rename(target_file, &file.path).unwrap();
-                        }
-                    }
-                }
+            let mut succeeded = true;
+            for file in group_files {
+                succeeded &= backup_file(file, transaction);
             }
+
+            succeeded
         };
 
-        // Symlink dotfile by force
-        if force {
-            remove_files_and_decide_if_adopt(&sym.not_owned, false);
-            remove_files_and_decide_if_adopt(&sym.not_symlinked, false);
-        }
+        let adopt_file = |file: &Dotfile, transaction: &mut Transaction| -> bool {
+            let target_file = file.to_target_path().unwrap();
+
+            if dry_run {
+                let recorded = plan::record(Step::Adopt {
+                    file: target_file.clone(),
+                    dotfile: file.path.clone(),
+                });
+                if !recorded {
+                    eprintln!(
+                        "{} `{}`",
+                        "removing".if_supports_color(Stderr, |t| t.red()),
+                        file.path.display()
+                    );
+                    eprintln!(
+                        "{} `{}` to `{}`",
+                        "moving".if_supports_color(Stderr, |t| t.yellow()),
+                        target_file.display(),
+                        file.path.display()
+                    );
+                }
+                return true;
+            }
+
+            // the dotfile is only moved aside so that it can be put back when rolling back
+            let mut adopt = || -> std::io::Result<()> {
+                if file.path.exists() {
+                    transaction.remove(&file.path)?;
+                }
+                fs::rename(&target_file, &file.path)?;
+                transaction.moved(&target_file, &file.path);
+                Ok(())
+            };
+
+            if let Err(err) = adopt() {
+                errors::print(format!("{}: {err}", target_file.display()));
+                return false;
+            }
+
+            true
+        };
+
+        let adopt_files = |status_group: &HashCache, transaction: &mut Transaction| -> bool {
+            let Some(group_files) = status_group.get(group) else {
+                return true;
+            };
+
+            let mut succeeded = true;
+            // templates can't be replaced by the files they render to
+            for file in group_files.iter().filter(|file| !file.is_template()) {
+                succeeded &= adopt_file(file, transaction);
+            }
+
+            succeeded
+        };
+
+        // every file that's in the way is asked about, the ones that are skipped stay conflicts
+        let ask_conflicts = |transaction: &mut Transaction| -> bool {
+            let files = [&sym.not_owned, &sym.not_symlinked]
+                .into_iter()
+                .filter_map(|cache| cache.get(group))
+                .flatten();
+
+            let mut succeeded = true;
+            for file in files {
+                let target = file.to_target_path().unwrap();
+                // winning a collision already replaced what was in the way
+                if target.symlink_metadata().is_err() || fileops::links_to(&target, &file.path) {
+                    continue;
+                }
+
+                let resolution = loop {
+                    if overwrite_all.get() {
+                        break Resolution::Overwrite;
+                    }
+
+                    match interactive::ask_resolution(&target) {
+                        Resolution::Diff => diff::print_conflict(file),
+                        Resolution::All => overwrite_all.set(true),
+                        resolution => break resolution,
+                    }
+                };
+
+                succeeded &= match resolution {
+                    Resolution::Overwrite => backup_file(file, transaction),
+                    Resolution::Adopt if file.is_template() => {
+                        println!(
+                            "{}",
+                            t!("info.template_not_adopted", file = target.display())
+                                .if_supports_color(Stdout, |t| t.yellow())
+                        );
+                        true
+                    }
+                    Resolution::Adopt => adopt_file(file, transaction),
+                    Resolution::Skip | Resolution::Diff | Resolution::All => true,
+                };
+            }
+
+            succeeded
+        };
+
+        // the files in place of the asset's are replaced without backing them up
+        let replace_files = |status_group: &HashCache, transaction: &mut Transaction| -> bool {
+            let Some(group_files) = status_group.get(group) else {
+                return true;
+            };
+
+            let mut succeeded = true;
+            for file in group_files {
+                let target_file = file.to_target_path().unwrap();
+                if !target_file.exists() && !target_file.is_symlink() {
+                    continue;
+                }
+
+                if safe::skip(&target_file, safe::Reason::Asset) {
+                    continue;
+                }
+
+                if dry_run {
+                    if !plan::record(Step::Remove {
+                        file: target_file.clone(),
+                    }) {
+                        eprintln!(
+                            "{} `{}`",
+                            "removing".if_supports_color(Stderr, |t| t.red()),
+                            target_file.display()
+                        );
+                    }
+                    continue;
+                }
+
+                if let Err(err) = transaction.remove(&target_file) {
+                    errors::print(format!("{}: {err}", target_file.display()));
+                    succeeded = false;
+                }
+            }
+
+            succeeded
+        };
+
+        // files that another group deploys go to whichever group wins the collision, see dotfiles::wins_collision
+        let resolve_collisions = |transaction: &mut Transaction| -> bool {
+            let mut succeeded = true;
+            let files = [&sym.not_symlinked, &sym.not_owned]
+                .into_iter()
+                .filter_map(|cache| cache.get(group))
+                .flatten();
+
+            for file in files {
+                let target = file.to_target_path().unwrap();
+                // the other group might have been added earlier on by this same command
+                let Some(other) = fileops::read_link(&target)
+                    .ok()
+                    .and_then(|source| Dotfile::try_from(source).ok())
+                else {
+                    continue;
+                };
+
+                if dotfiles::group_without_target(&other.group_name)
+                    == dotfiles::group_without_target(&file.group_name)
+                {
+                    continue;
+                }
+
+                if !dotfiles::wins_collision(&file.group_name, &other.group_name) {
+                    println!(
+                        "{}",
+                        t!(
+                            "info.collision_lost",
+                            file = target.display(),
+                            group = other.group_name
+                        )
+                        .if_supports_color(Stdout, |t| t.yellow())
+                    );
+                    continue;
+                }
+
+                if safe::skip(&target, safe::Reason::Collision(other.group_name.clone())) {
+                    continue;
+                }
+
+                if dry_run {
+                    if !plan::record(Step::Remove {
+                        file: target.clone(),
+                    }) {
+                        eprintln!(
+                            "{} `{}`",
+                            "removing".if_supports_color(Stderr, |t| t.red()),
+                            target.display()
+                        );
+                    }
+                    continue;
+                }
+
+                let copy = copy
+                    || file.is_template()
+                    || config::get().deploy_mode(&file.group_name) == DeployMode::Copy;
+                let deployed = transaction
+                    .remove(&target)
+                    .map_err(|err| format!("{}: {err}", target.display()).into())
+                    .and_then(|_| match copy {
+                        true => copy_file(
+                            false,
+                            file.path.clone(),
+                            &mut state.borrow_mut(),
+                            transaction,
+                        ),
+                        false => link_file(false, &file.path, &mut state.borrow_mut(), transaction),
+                    });
+
+                match deployed {
+                    Ok(()) => println!(
+                        "{}",
+                        t!(
+                            "info.collision_won",
+                            file = target.display(),
+                            group = file.group_name,
+                            other = other.group_name
+                        )
+                    ),
+                    Err(err) => {
+                        errors::print(err);
+                        succeeded = false;
+                    }
+                }
+            }
+
+            succeeded
+        };
+
+        let mut succeeded = true;
+        succeeded &= resolve_collisions(transaction);
+
+        if config::get().is_asset(group) && !force && !adopt {
+            succeeded &= replace_files(&sym.not_owned, transaction);
+            succeeded &= replace_files(&sym.not_symlinked, transaction);
+        }
+
+        if !force && !adopt && !safe::is_enabled() && interactive::asks_on_conflict() {
+            succeeded &= ask_conflicts(transaction);
+        }
+
+        // Symlink dotfile by force
+        if force {
+            succeeded &= backup_files(&sym.not_owned, transaction);
+            succeeded &= backup_files(&sym.not_symlinked, transaction);
+        }
+
+        // Discard dotfile and adopt the conflicting dotfile
+        if adopt {
+            succeeded &= adopt_files(&sym.not_owned, transaction);
+            succeeded &= adopt_files(&sym.not_symlinked, transaction);
+        }
+
+        succeeded &= sym.add(
+            dry_run,
+            only_files,
+            copy,
+            &mut state.borrow_mut(),
+            transaction,
+            group,
+        );
+
+        for (_, templates) in sym.stale.iter().filter(|(stale_group, _)| {
+            *stale_group == group || dotfiles::group_without_target(stale_group) == group
+        }) {
+            for template in templates {
+                if let Ok(target) = template.to_target_path()
+                    && safe::skip(&target, safe::Reason::Stale)
+                {
+                    continue;
+                }
+
+                if let Err(err) =
+                    rerender_template(dry_run, template, &mut state.borrow_mut(), transaction)
+                {
+                    errors::print(err);
+                    succeeded = false;
+                }
+            }
+        }
+
+        // if tuckr is cancelled halfway through a group, what was deployed for it is removed again
+        if cancel::is_cancelled() {
+            report_rollback_errors(transaction.rollback_to(checkpoint));
+            let state = &mut *state.borrow_mut();
+            state.copies.retain(|target, _| {
+                copies_before.contains_key(target) || fs::symlink_metadata(target).is_ok()
+            });
+            state.links.retain(|target, _| {
+                links_before.contains_key(target) || fs::symlink_metadata(target).is_ok()
+            });
+        } else if !succeeded {
+            failed.set(true);
+        }
+    });
+
+    // what needs root is deployed last and all at once, so that the password is only asked for once
+    if !failed.get()
+        && !cancel::is_cancelled()
+        && let Err(err) = escalate::apply_deferred()
+    {
+        errors::print(err);
+        failed.set(true);
+    }
+    escalate::discard();
+
+    let mut state = state.into_inner();
+    let mut transaction = transaction.into_inner();
+    if failed.get() {
+        if transaction::rollback_enabled() {
+            report_rollback_errors(transaction.rollback());
+            state.copies = copies_before.clone();
+            state.links = links_before.clone();
+            errors::print(error!("deploy_rolled_back"));
+        } else {
+            eprintln!(
+                "{}",
+                t!("warn.deploy_not_rolled_back").if_supports_color(Stderr, |t| t.yellow())
+            );
+        }
+    }
+    transaction.commit();
+
+    let mut backup_set = backup_set.into_inner();
+    // forgets the backups that were put back by rolling back
+    if !dry_run && let Err(err) = backup_set.prune() {
+        errors::print(err);
+    }
+
+    // the groups that were deployed before cancelling are kept
+    save_copies(&state, &copies_before, &links_before)?;
+    result?;
+
+    // without rolling back, the groups that were deployed before the failure are kept
+    if failed.get() {
+        return match transaction::rollback_enabled() {
+            true => Err(TuckrError::Failed),
+            false => Err(TuckrError::PartialDeploy),
+        };
+    }
+
+    if !backup_set.is_empty() {
+        println!(
+            "{}",
+            t!("info.conflicts_backed_up", backup = backup_set.name())
+                .if_supports_color(Stdout, |t| t.yellow())
+        );
+        backups::apply_retention(profile.clone());
+    }
+
+    let added = |group: &str| {
+        !dotfiles::is_excluded(group, exclude)
+            && groups
+                .iter()
+                .any(|g| g == "*" || g == group || g == dotfiles::group_without_target(group))
+    };
+
+    let post_add_sym = SymlinkHandler::try_new(profile.clone())?;
+    if !dry_run {
+        save_deployed(profile.clone(), &post_add_sym)?;
+    }
+    apply_permissions(profile.clone(), dry_run, added)?;
+
+    let potential_conflicts = post_add_sym.get_conflicts_in_cache();
+    stats::record_conflicts(potential_conflicts.len());
+
+    if safe::is_enabled() {
+        for file in potential_conflicts
+            .iter()
+            .filter(|(group, _)| added(group))
+            .flat_map(|(_, files)| files)
+        {
+            safe::skip(&file.to_target_path().unwrap(), safe::Reason::Exists);
+        }
+        safe::report();
+    }
+
+    // `tuckr check` reports the conflicts in its plan instead of the status
+    if plan::is_recording() {
+        for file in potential_conflicts
+            .iter()
+            .filter(|(group, _)| added(group))
+            .flat_map(|(_, files)| files)
+        {
+            plan::record(Step::Conflict {
+                file: file.to_target_path().unwrap(),
+            });
+        }
+        return Ok(());
+    }
+
+    // wrappers get an error per conflicting file instead of the status
+    if table::format() == table::Format::Json {
+        let requested = |group: &str| {
+            groups
+                .iter()
+                .any(|g| g == "*" || g == group || g == dotfiles::group_without_target(group))
+        };
+        let mut conflicts: Vec<_> = potential_conflicts
+            .iter()
+            .filter(|(group, _)| requested(group))
+            .flat_map(|(group, files)| files.iter().map(move |file| (group, file)))
+            .collect();
+        conflicts.sort_by(|(_, a), (_, b)| a.path.cmp(&b.path));
+
+        for (group, file) in &conflicts {
+            errors::print(error!(
+                "conflict_file_exists",
+                file = file.to_target_path().unwrap().display(),
+                group = group
+            ));
+        }
+
+        // exits the same way as without --format json, only the groups asked for by name fail it
+        return match groups.iter().any(|g| potential_conflicts.contains_key(g)) {
+            true => Err(TuckrError::Conflicts),
+            false => Ok(()),
+        };
+    }
+
+    if !potential_conflicts.is_empty() {
+        if groups.iter().any(|g| g == "*") {
+            println!(
+                "{}",
+                "Conflicts were detected. Run `tuckr status` to learn more."
+                    .if_supports_color(Stdout, |t| t.yellow())
+            );
+        }
+
+        if groups.iter().any(|g| potential_conflicts.contains_key(g)) {
+            println!(
+                "{}\n",
+                "Conflicts were detected. Conflicting groups won't be added until conflicts are resolved.".if_supports_color(Stdout, |t| t.yellow())
+            );
+            // how the status exits only says whether the groups exist
+            _ = print_groups_status(profile, &post_add_sym, groups.into(), &[]);
+            return Err(TuckrError::Conflicts);
+        }
+    }
+    Ok(())
+}
+
+/// Removes symlinks
+pub fn remove_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    groups: &[String],
+    exclude: &[String],
+) -> Result<(), TuckrError> {
+    let state = RefCell::new(state::load_or_report(profile.clone())?);
+    let copies_before = state.borrow().copies.clone();
+    let links_before = state.borrow().links.clone();
+
+    // groups that are gone from the dotfiles are only known through the state
+    let orphaned = remove_orphaned(dry_run, &mut state.borrow_mut(), groups, exclude);
+    let groups: Vec<_> = groups
+        .iter()
+        .filter(|group| !orphaned.contains(*group))
+        .cloned()
+        .collect();
+
+    let mut result = match groups.is_empty() {
+        true => Ok(()),
+        false => foreach_group(profile.clone(), &groups, exclude, false, |sym, p| {
+            sym.remove(dry_run, &mut state.borrow_mut(), p)
+        }),
+    };
+
+    // the symlinks that need root are removed all at once
+    if let Err(err) = escalate::apply_deferred() {
+        errors::print(err);
+        result = result.and(Err(TuckrError::Failed));
+    }
+
+    let state = state.into_inner();
+    save_copies(&state, &copies_before, &links_before)?;
+    if !dry_run {
+        save_deployed(profile.clone(), &SymlinkHandler::try_new(profile)?)?;
+    }
+    result
+}
+
+/// Removes what the state says was deployed from dotfiles that aren't in the dotfiles anymore
+///
+/// Returns the groups that are gone from the dotfiles altogether
+fn remove_orphaned(
+    dry_run: bool,
+    state: &mut State,
+    groups: &[String],
+    exclude: &[String],
+) -> BTreeSet<String> {
+    let picked = |group: &str| {
+        !dotfiles::is_excluded(group, exclude)
+            && groups
+                .iter()
+                .any(|g| g == "*" || g == group || g == dotfiles::group_without_target(group))
+    };
+
+    let gone: BTreeSet<String> = state
+        .orphaned_groups()
+        .into_keys()
+        .filter(|group| picked(group))
+        .map(String::from)
+        .collect();
+
+    let orphaned: Vec<_> = state
+        .deployed
+        .iter()
+        .filter(|(_, file)| file.is_orphaned() && picked(&file.group))
+        .map(|(target, file)| (target.clone(), file.clone()))
+        .collect();
+
+    for (target, file) in &orphaned {
+        match file.method {
+            DeployMethod::Symlink => {
+                // it's only removed if it still points to where it was deployed from
+                if !fileops::links_to(target, &file.source) {
+                    continue;
+                }
+
+                if dry_run {
+                    eprintln!(
+                        "{} `{}`",
+                        "removing".if_supports_color(Stderr, |t| t.red()),
+                        target.display()
+                    );
+                    continue;
+                }
+
+                if escalate::needs_privileges(target) {
+                    escalate::defer(Operation::Remove {
+                        path: target.clone(),
+                    });
+                } else if let Err(err) = fs::remove_file(target) {
+                    errors::print(format!("{}: {err}", target.display()));
+                }
+            }
+            DeployMethod::Junction | DeployMethod::Hardlink => {
+                // it's only removed if it's still what was deployed there
+                if state.links.get(target).map(|link| &link.source) != Some(&file.source) {
+                    continue;
+                }
+
+                if dry_run {
+                    eprintln!(
+                        "{} `{}`",
+                        "removing".if_supports_color(Stderr, |t| t.red()),
+                        target.display()
+                    );
+                    continue;
+                }
+
+                let removed = match file.method {
+                    DeployMethod::Junction => fs::remove_dir(target),
+                    _ => fs::remove_file(target),
+                };
+                match removed {
+                    Ok(()) => {
+                        state.links.remove(target);
+                    }
+                    Err(err) => errors::print(format!("{}: {err}", target.display())),
+                }
+            }
+            DeployMethod::Copy | DeployMethod::Template => {
+                // what was copied into directories is removed before the directories themselves
+                let copies: Vec<_> = state
+                    .copies
+                    .keys()
+                    .filter(|copy| copy.starts_with(target))
+                    .rev()
+                    .cloned()
+                    .collect();
+                for copy in copies {
+                    remove_copied_file(dry_run, copy, state);
+                }
+            }
+        }
+    }
+
+    gone
+}
+
+/// Saves what's deployed into the state
+fn save_deployed(profile: Option<String>, sym: &SymlinkHandler) -> Result<(), TuckrError> {
+    let mut state = state::load_or_report(profile.clone())?;
+    if !sym.record_deployed(profile, &mut state) {
+        return Ok(());
+    }
+
+    state.save().map_err(|err| {
+        errors::print(err);
+        TuckrError::Failed
+    })
+}
+
+/// Gives the deployed files of the groups the permissions that tuckr.toml asks for
+fn apply_permissions(
+    profile: Option<String>,
+    dry_run: bool,
+    is_added: impl Fn(&str) -> bool,
+) -> Result<(), TuckrError> {
+    let state = state::load_or_report(profile)?;
+    let mut failed = false;
+    for drift in permissions::find_drift(&state, is_added) {
+        if let Err(err) = drift.fix(dry_run) {
+            errors::print(format!("{}: {err}", drift.file.display()));
+            failed = true;
+        }
+    }
+
+    match failed {
+        true => Err(TuckrError::Failed),
+        false => Ok(()),
+    }
+}
+
+/// Saves the state if any copies, junctions or hardlinks were deployed or removed
+fn save_copies(
+    state: &State,
+    copies_before: &BTreeMap<PathBuf, CopiedFile>,
+    links_before: &BTreeMap<PathBuf, LinkedFile>,
+) -> Result<(), TuckrError> {
+    if state.copies == *copies_before && state.links == *links_before {
+        return Ok(());
+    }
+
+    state.save().map_err(|err| {
+        errors::print(err);
+        TuckrError::Failed
+    })
+}
+
+/// Lists the rendered templates and the copies that are out of date, only for `groups` if any are given
+///
+/// Returns true if any were found
+fn print_stale_templates(sym: &SymlinkHandler, groups: Option<&[String]>) -> bool {
+    let stale: Vec<_> = sym
+        .stale
+        .iter()
+        .filter(|(group, _)| {
+            groups.is_none_or(|groups| {
+                groups
+                    .iter()
+                    .any(|g| *g == **group || dotfiles::group_without_target(group) == g.as_str())
+            })
+        })
+        .flat_map(|(_, templates)| templates)
+        .collect();
+
+    let mut templates: Vec<_> = stale.iter().filter(|f| f.is_template()).collect();
+    // modified assets are overwritten without asking, so only copies whose dotfile changed are worth mentioning
+    let mut copies: Vec<_> = stale
+        .iter()
+        .filter(|f| {
+            let target = f.to_target_path().unwrap();
+            sym.copies
+                .get(&target)
+                .is_some_and(|copy| sym.source_changed(f, copy))
+        })
+        .collect();
+
+    if templates.is_empty() && copies.is_empty() {
+        return false;
+    }
+
+    for (title, learn, files) in [
+        (
+            t!("info.stale_templates"),
+            t!(
+                "info.learn_how_to_render_templates",
+                cmd = "tuckr add <group...>"
+            ),
+            &mut templates,
+        ),
+        (
+            t!("info.outdated_copies"),
+            t!(
+                "info.learn_how_to_update_copies",
+                cmd = "tuckr add <group...>"
+            ),
+            &mut copies,
+        ),
+    ] {
+        if files.is_empty() {
+            continue;
+        }
+
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        println!("{title}:");
+        for file in files.iter() {
+            println!(
+                "\t{} ({})",
+                file.to_target_path()
+                    .unwrap()
+                    .display()
+                    .if_supports_color(Stdout, |t| t.yellow()),
+                file.group_name
+            );
+        }
+        println!("\n{learn}");
+    }
+
+    true
+}
+
+/// Lists the copies and rendered templates that were edited on the target since they were deployed,
+/// returns true if there are any
+fn print_modified_copies(sym: &SymlinkHandler, groups: Option<&[String]>) -> bool {
+    let mut modified: Vec<_> = sym
+        .modified
+        .iter()
+        .filter(|(group, _)| {
+            groups.is_none_or(|groups| {
+                groups
+                    .iter()
+                    .any(|g| *g == **group || dotfiles::group_without_target(group) == g.as_str())
+            })
+        })
+        .flat_map(|(_, files)| files)
+        .collect();
+
+    if modified.is_empty() {
+        return false;
+    }
+
+    modified.sort_by(|a, b| a.path.cmp(&b.path));
+    println!("{}:", t!("info.modified_on_target"));
+    for file in &modified {
+        println!(
+            "\t{} ({})",
+            file.to_target_path()
+                .unwrap()
+                .display()
+                .if_supports_color(Stdout, |t| t.yellow()),
+            file.group_name
+        );
+    }
+    println!(
+        "\n{}",
+        t!(
+            "info.learn_how_to_resolve_modified",
+            cmd = "tuckr diff <group...>"
+        )
+    );
+
+    true
+}
+
+/// A symlink in the target directory that points into the dotfiles at a file that no longer exists
+#[derive(Debug, PartialEq)]
+struct DanglingSymlink {
+    path: PathBuf,
+    /// the dotfile the symlink points to
+    source: PathBuf,
+    group: String,
+    /// a dotfile that's deployed to the same place, usually because the file was moved to another group
+    replacement: Option<PathBuf>,
+}
+
+impl SymlinkHandler {
+    /// Looks for dangling symlinks in the directories that the dotfiles are deployed to
+    ///
+    /// Only the directories that still exist in the dotfiles are searched, symlinks left behind
+    /// in directories that were removed from every group aren't found
+    fn find_dangling_symlinks(&self) -> Vec<DanglingSymlink> {
+        let canonical_dotfiles: Vec<PathBuf> = self
+            .configs_dirs
+            .iter()
+            .filter_map(|dir| dir.parent()?.canonicalize().ok())
+            .collect();
+        let group_dirs = self.group_dirs();
+
+        let (tx, _) = mpsc::channel();
+        let mut target_dirs: BTreeSet<PathBuf> = group_dirs
+            .iter()
+            .flat_map(|group_dir| {
+                std::iter::once(group_dir.clone()).chain(DirWalk::new(group_dir).errors(tx.clone()))
+            })
+            .filter(|dir| dir.is_dir())
+            .filter_map(|dir| Dotfile::try_from(dir).ok()?.to_target_path().ok())
+            .collect();
+        target_dirs.extend(dotfiles::get_dotfiles_target_dir_path());
+
+        let mut groups: Vec<String> = group_dirs
+            .iter()
+            .map(|dir| dir.file_name().unwrap().to_string_lossy().into_owned())
+            .filter(|group| dotfiles::group_is_valid_target(group))
+            .collect();
+        groups.sort_by_key(|group| std::cmp::Reverse(dotfiles::get_group_priority(group)));
+
+        let mut dangling = Vec::new();
+        for dir in target_dirs {
+            // directories that are symlinked into the dotfiles hold the dotfiles themselves
+            if dir.canonicalize().is_ok_and(|dir| {
+                canonical_dotfiles
+                    .iter()
+                    .any(|dotfiles_dir| dir.starts_with(dotfiles_dir))
+            }) {
+                continue;
+            }
+
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let Ok(source) = fileops::read_link(&path) else {
+                    continue;
+                };
+
+                if source.symlink_metadata().is_ok() {
+                    continue;
+                }
+
+                let Some(group) = self
+                    .configs_dirs
+                    .iter()
+                    .find_map(|dir| source.strip_prefix(dir).ok())
+                    .and_then(|source| source.components().next())
+                    .map(|group| group.as_os_str().to_string_lossy().into_owned())
+                else {
+                    continue;
+                };
+
+                // groups of the same base group are preferred, e.g. when a conditional group was renamed
+                let base_group = dotfiles::group_without_target(&group);
+                let replacement = groups
+                    .iter()
+                    .filter(|g| dotfiles::group_without_target(g) == base_group)
+                    .chain(
+                        groups
+                            .iter()
+                            .filter(|g| dotfiles::group_without_target(g) != base_group),
+                    )
+                    .filter_map(|g| {
+                        let group_dir = self.group_dir(g);
+                        let basepath = dotfiles::get_group_basepath(&group_dir, &path)?;
+                        Some(group_dir.join(basepath))
+                    })
+                    .find(|candidate| candidate.symlink_metadata().is_ok());
+
+                dangling.push(DanglingSymlink {
+                    path,
+                    source,
+                    group,
+                    replacement,
+                });
+            }
+        }
+
+        dangling
+    }
+}
+
+/// Returns the groups that can't be added without overriding or adopting conflicting files
+pub fn conflicting_groups(profile: Option<String>) -> Result<BTreeSet<String>, TuckrError> {
+    let sym = SymlinkHandler::try_new(profile)?;
+    Ok(sym.get_conflicts_in_cache().into_keys().collect())
+}
+
+/// Returns the dotfile that conflicts with the file at `target`, used by `tuckr resolve`
+///
+/// Files inside of conflicting directories are looked up in them,
+/// the group that wins the collision is picked if several groups deploy the file
+pub fn conflicting_dotfile(
+    profile: Option<String>,
+    target: &Path,
+) -> Result<Option<Dotfile>, TuckrError> {
+    let sym = SymlinkHandler::try_new(profile)?;
+    let mut candidates: Vec<Dotfile> = sym
+        .get_conflicts_in_cache()
+        .into_values()
+        .flatten()
+        .filter_map(|file| {
+            let relative = target.strip_prefix(file.to_target_path().ok()?).ok()?;
+            if relative.as_os_str().is_empty() {
+                return Some(file);
+            }
+
+            let inner = file.path.join(relative);
+            if !inner.exists() {
+                return None;
+            }
+            Dotfile::try_from(inner).ok()
+        })
+        .collect();
+
+    candidates.sort_by(|file, other| {
+        if dotfiles::wins_collision(&file.group_name, &other.group_name) {
+            std::cmp::Ordering::Less
+        } else if dotfiles::wins_collision(&other.group_name, &file.group_name) {
+            std::cmp::Ordering::Greater
+        } else {
+            file.group_name.cmp(&other.group_name)
+        }
+    });
+
+    Ok(candidates.into_iter().next())
+}
+
+/// Backs up the file that's in the place of a dotfile and deploys the dotfile instead
+pub fn replace_conflict(
+    profile: Option<String>,
+    dry_run: bool,
+    dotfile: &Dotfile,
+) -> Result<(), TuckrError> {
+    let mut backup_set = BackupSet::try_new(profile.clone()).map_err(|err| {
+        errors::print(err);
+        TuckrError::CouldntFindDotfiles
+    })?;
+
+    let target = dotfile.to_target_path().map_err(|err| {
+        errors::print(err);
+        TuckrError::Failed
+    })?;
+
+    let copy =
+        dotfile.is_template() || config::get().deploy_mode(&dotfile.group_name) == DeployMode::Copy;
+
+    if dry_run {
+        backup_set.backup(true, &target).ok();
+        eprintln!(
+            "{} `{}` to `{}`",
+            if copy { "copying" } else { "symlinking" }.if_supports_color(Stderr, |t| t.green()),
+            dotfile.path.display(),
+            target.display()
+        );
+        return Ok(());
+    }
+
+    let mut state = state::load_or_report(profile.clone())?;
+    let copies_before = state.copies.clone();
+    let links_before = state.links.clone();
+    let mut transaction = Transaction::new();
+
+    let deployed = backup_set
+        .backup(false, &target)
+        .and_then(|backup| {
+            transaction.moved(&target, backup);
+            match copy {
+                true => copy_file(false, dotfile.path.clone(), &mut state, &mut transaction),
+                false => link_file(false, &dotfile.path, &mut state, &mut transaction),
+            }
+        })
+        .and_then(|_| escalate::apply_deferred());
+    escalate::discard();
+
+    if let Err(err) = deployed {
+        errors::print(err);
+        report_rollback_errors(transaction.rollback());
+        transaction.commit();
+        if let Err(err) = backup_set.prune() {
+            errors::print(err);
+        }
+        return Err(TuckrError::Failed);
+    }
+    transaction.commit();
+
+    save_copies(&state, &copies_before, &links_before)?;
+    println!(
+        "{}",
+        t!("info.conflicts_backed_up", backup = backup_set.name())
+            .if_supports_color(Stdout, |t| t.yellow())
+    );
+    backups::apply_retention(profile);
+
+    Ok(())
+}
+
+/// Returns the dangling symlinks along with the dotfile each one points to, used by `tuckr doctor`
+pub fn dangling_symlinks(profile: Option<String>) -> Result<Vec<(PathBuf, PathBuf)>, TuckrError> {
+    let sym = SymlinkHandler::try_new(profile)?;
+    Ok(sym
+        .find_dangling_symlinks()
+        .into_iter()
+        .map(|link| (link.path, link.source))
+        .collect())
+}
+
+/// Keeps the dangling symlinks whose group is one of `groups`, all of them are kept if no groups are given
+fn filter_dangling_symlinks(
+    dangling: Vec<DanglingSymlink>,
+    groups: Option<&[String]>,
+    exclude: &[String],
+) -> Vec<DanglingSymlink> {
+    dangling
+        .into_iter()
+        .filter(|link| !dotfiles::is_excluded(&link.group, exclude))
+        .filter(|link| {
+            groups.is_none_or(|groups| {
+                groups.iter().any(|g| {
+                    *g == link.group || dotfiles::group_without_target(&link.group) == g.as_str()
+                })
+            })
+        })
+        .collect()
+}
+
+/// Prints the files that several groups deploy, only those of `groups` if any are given
+fn print_collisions(sym: &SymlinkHandler, groups: Option<&[String]>) -> bool {
+    let collisions: Vec<_> = sym
+        .find_collisions()
+        .into_iter()
+        .filter(|(_, colliding)| {
+            groups.is_none_or(|groups| {
+                colliding.iter().any(|group| {
+                    groups
+                        .iter()
+                        .any(|g| g == group || dotfiles::group_without_target(group) == g.as_str())
+                })
+            })
+        })
+        .collect();
+
+    if collisions.is_empty() {
+        return false;
+    }
+
+    println!("{}:", t!("info.colliding_files"));
+    for (target, groups) in &collisions {
+        println!(
+            "\t{}: {} ({})",
+            target.display().if_supports_color(Stdout, |t| t.yellow()),
+            groups.join(", "),
+            t!("info.collision_winner", group = groups[0])
+        );
+    }
+    println!("\n{}", t!("info.learn_how_to_prioritize_groups"));
+
+    true
+}
+
+/// Prints the dangling symlinks, returns true if there were any
+fn print_dangling_symlinks(dangling: &[DanglingSymlink]) -> bool {
+    if dangling.is_empty() {
+        return false;
+    }
 
-        // Discard dotfile and adopt the conflicting dotfile
-        if adopt {
-            remove_files_and_decide_if_adopt(&sym.not_owned, true);
-            remove_files_and_decide_if_adopt(&sym.not_symlinked, true);
-        }
+    println!("{}:", t!("info.dangling_symlinks"));
+    for link in dangling {
+        println!(
+            "\t{} -> {} ({})",
+            link.path
+                .display()
+                .if_supports_color(Stdout, |t| t.yellow()),
+            link.source.display(),
+            link.group
+        );
+    }
+    println!(
+        "\n{}",
+        t!("info.learn_how_to_fix_dangling", cmd = "tuckr status --fix")
+    );
 
-        sym.add(dry_run, only_files, group)
-    })?;
+    true
+}
 
-    let post_add_sym = SymlinkHandler::try_new(profile.clone())?;
-    let potential_conflicts = post_add_sym.get_conflicts_in_cache();
+/// Re-points dangling symlinks to the dotfile that replaced their source or removes them if there's none
+fn fix_dangling_symlinks(dry_run: bool, dangling: &[DanglingSymlink]) -> Result<(), TuckrError> {
+    let mut failed = false;
 
-    if !potential_conflicts.is_empty() {
-        if groups.iter().any(|g| g == "*") {
-            println!(
-                "{}",
-                "Conflicts were detected. Run `tuckr status` to learn more.".yellow()
-            );
+    for link in dangling {
+        if dry_run {
+            match &link.replacement {
+                Some(replacement) => eprintln!(
+                    "{} `{}` to `{}`",
+                    "re-pointing".if_supports_color(Stderr, |t| t.green()),
+                    link.path.display(),
+                    replacement.display()
+                ),
+                None => eprintln!(
+                    "{} `{}`",
+                    "removing".if_supports_color(Stderr, |t| t.red()),
+                    link.path.display()
+                ),
+            }
+            continue;
         }
 
-        if groups.iter().any(|g| potential_conflicts.contains_key(g)) {
-            println!(
-                "{}\n",
-                "Conflicts were detected. Conflicting groups won't be added until conflicts are resolved.".yellow()
-            );
-            return print_groups_status(profile, &post_add_sym, groups.into());
+        let result = fs::remove_file(&link.path).and_then(|_| match &link.replacement {
+            Some(replacement) => fileops::symlink_dotfile(replacement, &link.path),
+            None => Ok(()),
+        });
+
+        if let Err(err) = result {
+            errors::print(format!("{}: {err}", link.path.display()));
+            failed = true;
         }
     }
-    Ok(())
-}
-
-/// Removes symlinks
-pub fn remove_cmd(
-    profile: Option<String>,
-    dry_run: bool,
-    groups: &[String],
-    exclude: &[String],
-) -> Result<(), ExitCode> {
-    foreach_group(profile, groups, exclude, false, |sym, p| {
-        sym.remove(dry_run, p)
-    })?;
-    Ok(())
-}
-
-fn print_global_status(sym: &SymlinkHandler) -> Result<(), ExitCode> {
-    #[derive(Tabled, Debug)]
-    struct SymlinkRow<'a> {
-        #[tabled(rename = "Symlinked")]
-        symlinked: &'a str,
 
-        #[tabled(rename = "Not Symlinked")]
-        not_symlinked: &'a str,
+    if failed {
+        Err(TuckrError::Failed)
+    } else {
+        Ok(())
     }
+}
 
+fn print_global_status(
+    profile: Option<String>,
+    sym: &SymlinkHandler,
+    dangling: &[DanglingSymlink],
+) -> Result<(), TuckrError> {
     // --- process status from symlink ---
     // groups that are both in symlinked and not_symlinked
     // will be marked as not_symlinked only
@@ -732,6 +2984,39 @@ fn print_global_status(sym: &SymlinkHandler) -> Result<(), ExitCode> {
         (symlinked, not_symlinked)
     };
 
+    // groups for software this machine doesn't have aren't missing, they're skipped
+    let (skipped, not_symlinked): (Vec<_>, Vec<_>) = not_symlinked
+        .into_iter()
+        .map(|group| (group, preflight::missing_requirement(group)))
+        .partition(|(_, requirement)| requirement.is_some());
+    let not_symlinked: Vec<_> = not_symlinked.into_iter().map(|(group, _)| group).collect();
+    let is_skipped = |group: &str| {
+        skipped
+            .iter()
+            .any(|(skipped, _)| *skipped == dotfiles::group_without_target(group))
+    };
+
+    // --- labels held groups ---
+    let state = State::load(profile).unwrap_or_default();
+    let label_held = |groups: Vec<&str>| -> Vec<String> {
+        groups
+            .into_iter()
+            .map(|group| {
+                // CSV/TSV rows have their own held column
+                if state.is_held(group) && table::format() == table::Format::Table {
+                    format!("{group} ({})", t!("info.held"))
+                } else {
+                    group.to_string()
+                }
+            })
+            .collect()
+    };
+    let (symlinked, not_symlinked) = (label_held(symlinked), label_held(not_symlinked));
+    let (symlinked, not_symlinked): (Vec<&str>, Vec<&str>) = (
+        symlinked.iter().map(String::as_str).collect(),
+        not_symlinked.iter().map(String::as_str).collect(),
+    );
+
     let status_rows: Vec<SymlinkRow> = {
         let (longest, shortest, symlinked_is_longest) = if symlinked.len() >= not_symlinked.len() {
             (&symlinked, &not_symlinked, true)
@@ -760,23 +3045,197 @@ fn print_global_status(sym: &SymlinkHandler) -> Result<(), ExitCode> {
 
     // --- detect conflicts ---
     let conflicts = sym.get_conflicts_in_cache();
-    let conflicts: HashSet<_> = conflicts.keys().collect();
+    let conflicts: HashSet<_> = conflicts
+        .keys()
+        .filter(|group| !is_skipped(group))
+        .collect();
+    stats::record_conflicts(conflicts.len());
+    stats::record_drift(not_symlinked.len());
 
-    // --- Creates all the tables and prints them ---
-    use tabled::{
-        Alignment, Margin, Modify, Style, col, format::Format, object::Columns, object::Rows,
-    };
+    let orphaned = state.orphaned_groups();
+    let drift = permissions::find_drift(&state, |_| true);
+
+    if table::format() != table::Format::Table {
+        // one row per group is easier to use in scripts than the two columns of the table
+        let is_conflicting = |group: &str| {
+            conflicts
+                .iter()
+                .any(|conflict| dotfiles::group_without_target(conflict) == group)
+        };
+        let rows: Vec<_> = symlinked
+            .iter()
+            .map(|group| (group, "symlinked"))
+            .chain(
+                not_symlinked
+                    .iter()
+                    .map(|group| match is_conflicting(group) {
+                        true => (group, "conflicting"),
+                        false => (group, "not symlinked"),
+                    }),
+            )
+            .chain(skipped.iter().map(|(group, _)| (group, "skipped")))
+            .chain(orphaned.keys().map(|group| (group, "orphaned")))
+            .map(|(group, status)| GroupStatusRow {
+                group: group.to_string(),
+                status,
+                held: table::check(state.is_held(group)),
+            })
+            .collect();
+        table::print(&rows);
+    } else {
+        print_status_tables(status_rows, &conflicts);
+    }
+
+    if table::format() == table::Format::Table {
+        print_skipped_groups(&skipped);
+    }
+
+    if !sym.stale.is_empty() || !sym.modified.is_empty() || !state.experiments.is_empty() {
+        println!();
+    }
+    if print_collisions(sym, None) {
+        println!();
+    }
+    let has_stale_templates = print_stale_templates(sym, None);
+    if has_stale_templates && !sym.modified.is_empty() {
+        println!();
+    }
+    let has_modified_copies = print_modified_copies(sym, None);
+    experiments::print_experiments(&state, None);
+    let has_dangling_symlinks = print_dangling_symlinks(dangling);
+    if table::format() == table::Format::Table {
+        print_orphaned_groups(&orphaned);
+        print_permission_drift(&drift);
+    }
+
+    // Determines exit code for the command based on the dotfiles' status
+    if !symlinked.is_empty()
+        && not_symlinked.is_empty()
+        && conflicts.is_empty()
+        && !has_stale_templates
+        && !has_modified_copies
+        && !has_dangling_symlinks
+        && orphaned.is_empty()
+        && drift.is_empty()
+    {
+        Ok(())
+    } else {
+        Err(TuckrError::Failed)
+    }
+}
+
+/// Lists the groups that are still deployed even though they aren't in the dotfiles anymore
+fn print_orphaned_groups(orphaned: &BTreeMap<&str, Vec<&Path>>) {
+    if orphaned.is_empty() {
+        return;
+    }
+
+    println!("{}:", t!("info.orphaned_groups"));
+    for (group, files) in orphaned {
+        println!(
+            "\t{} ({})",
+            group.if_supports_color(Stdout, |t| t.yellow()),
+            t!("info.x_files", count = files.len())
+        );
+    }
+    println!(
+        "\n{}",
+        t!(
+            "info.learn_how_to_remove_orphaned",
+            cmd = "tuckr rm <group>"
+        )
+    );
+}
+
+/// Lists the deployed files whose permissions aren't the ones tuckr.toml asks for
+fn print_permission_drift(drift: &[permissions::Drift]) {
+    if drift.is_empty() {
+        return;
+    }
+
+    println!("\n{}:", t!("info.permission_drift"));
+    for drift in drift {
+        println!(
+            "\t{} {}",
+            drift
+                .file
+                .display()
+                .if_supports_color(Stdout, |t| t.yellow()),
+            t!(
+                "info.mode_should_be",
+                mode = format!("{:o}", drift.mode),
+                wanted = format!("{:o}", drift.wanted)
+            )
+        );
+    }
+
+    let mut groups: Vec<_> = drift.iter().map(|drift| drift.group.as_str()).collect();
+    groups.sort();
+    groups.dedup();
+    println!(
+        "\n{}",
+        t!(
+            "info.learn_how_to_apply_permissions",
+            cmd = format!("tuckr add {}", groups.join(" "))
+        )
+    );
+}
+
+/// Lists the groups that are skipped on this machine along with what they require
+fn print_skipped_groups(skipped: &[(impl AsRef<str>, Option<String>)]) {
+    if skipped.is_empty() {
+        return;
+    }
+
+    println!("{}:", t!("info.skipped_groups"));
+    for (group, requirement) in skipped {
+        let requirement = requirement.as_deref().unwrap_or_default();
+        println!(
+            "\t{} ({})",
+            group.as_ref().if_supports_color(Stdout, |t| t.yellow()),
+            t!("info.requires", requirement = requirement)
+        );
+    }
+    println!();
+}
+
+#[derive(Tabled)]
+struct GroupStatusRow {
+    #[tabled(rename = "Group")]
+    group: String,
+    #[tabled(rename = "Status")]
+    status: &'static str,
+    #[tabled(rename = "Held")]
+    held: String,
+}
+
+#[derive(Tabled, Debug)]
+struct SymlinkRow<'a> {
+    #[tabled(rename = "Symlinked")]
+    symlinked: &'a str,
+
+    #[tabled(rename = "Not Symlinked")]
+    not_symlinked: &'a str,
+}
+
+/// Prints the symlinked and not symlinked groups side by side with the conflicting groups below them
+fn print_status_tables(status_rows: Vec<SymlinkRow>, conflicts: &HashSet<&String>) {
+    use tabled::{Alignment, Modify, Style, col, format::Format, object::Columns, object::Rows};
 
     let mut sym_table = Table::new(status_rows);
-    sym_table
-        .with(Style::rounded())
-        .with(Margin::new(4, 4, 1, 1))
+    table::style(&mut sym_table, 4)
         .with(Modify::new(Rows::first()).with(Format::new(|s| s.default_color().to_string())))
-        .with(Modify::new(Columns::single(0)).with(Format::new(|s| s.green().to_string())))
-        .with(Modify::new(Columns::single(1)).with(Format::new(|s| s.red().to_string())));
-
-    let mut conflict_table = Table::builder(&conflicts)
-        .set_columns(["Conflicting Dotfiles".yellow().to_string()])
+        .with(Modify::new(Columns::single(0)).with(Format::new(|s| {
+            s.if_supports_color(Stdout, |t| t.green()).to_string()
+        })))
+        .with(Modify::new(Columns::single(1)).with(Format::new(|s| {
+            s.if_supports_color(Stdout, |t| t.red()).to_string()
+        })));
+
+    let mut conflict_table = Table::builder(conflicts)
+        .set_columns(["Conflicting Dotfiles"
+            .if_supports_color(Stdout, |t| t.yellow())
+            .to_string()])
         .clone()
         .build();
     conflict_table
@@ -802,12 +3261,116 @@ fn print_global_status(sym: &SymlinkHandler) -> Result<(), ExitCode> {
             )
         );
     }
+}
 
-    // Determines exit code for the command based on the dotfiles' status
-    if !symlinked.is_empty() && not_symlinked.is_empty() && conflicts.is_empty() {
-        Ok(())
-    } else {
-        Err(ExitCode::FAILURE)
+#[derive(Tabled)]
+struct FileStatusRow {
+    #[tabled(rename = "Group")]
+    group: String,
+    #[tabled(rename = "File")]
+    file: String,
+    #[tabled(rename = "Status")]
+    status: String,
+}
+
+/// Describes what's at the target of one of a group's files
+fn describe_file_status(sym: &SymlinkHandler, f: &Dotfile) -> String {
+    if fileops::is_ignored_file(&f.path) {
+        return "ignored"
+            .if_supports_color(Stdout, |t| t.yellow())
+            .to_string();
+    }
+
+    let target = match f.to_target_path() {
+        Ok(target) => target,
+        Err(err) => return err.if_supports_color(Stdout, |t| t.red()).to_string(),
+    };
+
+    // files inside of a folded directory are deployed through it
+    let is_linked = !target.is_symlink()
+        && target
+            .canonicalize()
+            .is_ok_and(|target| f.path.canonicalize().is_ok_and(|path| path == target));
+    if is_linked {
+        return "symlinked"
+            .if_supports_color(Stdout, |t| t.green())
+            .to_string();
+    }
+
+    match sym.compare(f, &target) {
+        Ok(Some(FileStatus::Symlinked)) => "symlinked"
+            .if_supports_color(Stdout, |t| t.green())
+            .to_string(),
+        Ok(Some(FileStatus::Copied { modified: true, .. })) => "modified on target"
+            .if_supports_color(Stdout, |t| t.yellow())
+            .to_string(),
+        Ok(Some(FileStatus::Copied { stale: false, .. })) => "copied"
+            .if_supports_color(Stdout, |t| t.green())
+            .to_string(),
+        Ok(Some(FileStatus::Copied { stale: true, .. })) => "outdated copy"
+            .if_supports_color(Stdout, |t| t.yellow())
+            .to_string(),
+        Ok(Some(FileStatus::NotOwned)) => {
+            let link = fs::read_link(&target).unwrap_or_default();
+            format!("points elsewhere: {}", link.display())
+                .if_supports_color(Stdout, |t| t.red())
+                .to_string()
+        }
+        Ok(Some(FileStatus::NotSymlinked) | None) if target.symlink_metadata().is_ok() => {
+            format!("conflicting file: {}", target.display())
+                .if_supports_color(Stdout, |t| t.red())
+                .to_string()
+        }
+        Ok(Some(FileStatus::NotSymlinked) | None)
+            if target.parent().is_some_and(|dir| !dir.exists()) =>
+        {
+            "missing parent directory"
+                .if_supports_color(Stdout, |t| t.yellow())
+                .to_string()
+        }
+        Ok(Some(FileStatus::NotSymlinked) | None) => "not symlinked"
+            .if_supports_color(Stdout, |t| t.red())
+            .to_string(),
+        Err(err) => err.if_supports_color(Stdout, |t| t.red()).to_string(),
+    }
+}
+
+/// Prints what's at the target of every file of the groups and of their conditional groups, so
+/// that the file that keeps a group from being symlinked can be found
+fn print_files_status(sym: &SymlinkHandler, groups: &[String]) {
+    let mut group_dirs: Vec<_> = sym
+        .group_dirs()
+        .into_iter()
+        .filter(|dir| {
+            let name = dir.file_name().unwrap().to_string_lossy();
+            groups.iter().any(|group| {
+                *group == name
+                    || (dotfiles::group_without_target(&name) == group
+                        && dotfiles::group_is_valid_target(&name))
+            })
+        })
+        .collect();
+    group_dirs.sort_by(|dir, other| dir.file_name().cmp(&other.file_name()));
+
+    let rows: Vec<_> = group_dirs
+        .iter()
+        .flat_map(|group_dir| {
+            DirWalk::new(group_dir)
+                .ignore(|_| false)
+                .sorted(true)
+                .filter(|file| !file.is_dir())
+                .filter_map(|file| Dotfile::try_from(file).ok())
+        })
+        .map(|f| FileStatusRow {
+            status: describe_file_status(sym, &f),
+            file: f.relative_path().display().to_string(),
+            group: f.group_name,
+        })
+        .collect();
+
+    if !rows.is_empty() {
+        table::print(&rows);
+        println!();
     }
 }
 
@@ -815,7 +3378,8 @@ fn print_groups_status(
     profile: Option<String>,
     sym: &SymlinkHandler,
     groups: Vec<String>,
-) -> Result<(), ExitCode> {
+    dangling: &[DanglingSymlink],
+) -> Result<(), TuckrError> {
     let get_related_groups =
         |sym: &SymlinkHandler, not_symlinked_groups: Option<&Vec<String>>| -> Vec<String> {
             let mut related_groups = Vec::new();
@@ -863,10 +3427,20 @@ fn print_groups_status(
     let not_symlinked = get_related_groups(sym, None);
     let symlinked = get_related_groups(sym, Some(&not_symlinked));
 
+    // groups for software this machine doesn't have aren't missing, they're skipped
+    let (skipped, not_symlinked): (Vec<_>, Vec<_>) = not_symlinked
+        .into_iter()
+        .map(|group| {
+            let requirement = preflight::missing_requirement(&group);
+            (group, requirement)
+        })
+        .partition(|(_, requirement)| requirement.is_some());
+    let not_symlinked: Vec<_> = not_symlinked.into_iter().map(|(group, _)| group).collect();
+
     let unsupported = {
         let mut unsupported = groups
             .iter()
-            .map(|group| Dotfile::try_from(sym.dotfiles_dir.join("Configs").join(group)).unwrap())
+            .map(|group| Dotfile::try_from(sym.group_dir(group)).unwrap())
             .filter(|group| !group.is_valid_target())
             .map(|group| group.group_name)
             .collect::<Vec<_>>();
@@ -879,7 +3453,7 @@ fn print_groups_status(
     let file_conflicts: HashCache = sym
         .get_conflicts_in_cache()
         .into_iter()
-        .filter(|(g, _)| groups.contains(g))
+        .filter(|(g, _)| groups.contains(g) && !skipped.iter().any(|(skipped, _)| skipped == g))
         .collect();
 
     if !file_conflicts.is_empty() || !not_symlinked.is_empty() {
@@ -893,7 +3467,8 @@ fn print_groups_status(
                 let msg = if !conflict.is_symlink() {
                     t!("errors.already_exists")
                 } else {
-                    let conflict_dotfile = Dotfile::try_from(conflict.read_link().unwrap());
+                    let conflict_dotfile =
+                        Dotfile::try_from(fileops::read_link(&conflict).unwrap());
 
                     match conflict_dotfile {
                         Ok(conflict) => {
@@ -916,11 +3491,11 @@ fn print_groups_status(
             if file_conflicts.contains_key(&group) {
                 continue;
             }
-            println!("\t{}", group.red());
+            println!("\t{}", group.if_supports_color(Stdout, |t| t.red()));
         }
 
         for group in file_conflicts.keys() {
-            print!("\t{}", group.red());
+            print!("\t{}", group.if_supports_color(Stdout, |t| t.red()));
             print_conflicts(&file_conflicts, group);
         }
 
@@ -930,24 +3505,61 @@ fn print_groups_status(
     if !symlinked.is_empty() {
         println!("{}:", t!("table-column.symlinked"));
         for group in symlinked {
-            println!("\t{}", group.green());
+            println!("\t{}", group.if_supports_color(Stdout, |t| t.green()));
         }
         println!();
     }
 
+    print_files_status(sym, &groups);
+
+    if print_collisions(sym, Some(&groups)) {
+        println!();
+    }
+
+    if print_stale_templates(sym, Some(&groups)) {
+        println!();
+    }
+
+    if print_modified_copies(sym, Some(&groups)) {
+        println!();
+    }
+
     if !unsupported.is_empty() {
-        println!("{}:", t!("errors.not_supported_on_this_platform"));
+        println!("{}:", error!("not_supported_on_this_platform"));
         for group in unsupported {
-            println!("\t{}", group.yellow());
+            println!("\t{}", group.if_supports_color(Stdout, |t| t.yellow()));
         }
         println!();
     }
 
+    print_skipped_groups(&skipped);
+
+    if let Ok(state) = State::load(profile.clone()) {
+        let held: Vec<_> = groups.iter().filter(|g| state.is_held(g)).collect();
+        if !held.is_empty() {
+            println!("{}:", t!("info.held_groups"));
+            for group in held {
+                println!("\t{}", group.if_supports_color(Stdout, |t| t.yellow()));
+            }
+            println!();
+        }
+
+        experiments::print_experiments(&state, Some(&groups));
+
+        for group in &groups {
+            state::print_notes(&state, group);
+        }
+
+        if print_dangling_symlinks(dangling) {
+            println!();
+        }
+    }
+
     let invalid_groups = dotfiles::check_invalid_groups(profile, DotfileType::Configs, &groups);
     if let Some(invalid_groups) = &invalid_groups {
-        eprintln!("{}:", t!("errors.following_groups_dont_exist"));
+        eprintln!("{}:", error!("following_groups_dont_exist"));
         for group in invalid_groups {
-            eprintln!("\t{}", group.red());
+            eprintln!("\t{}", group.if_supports_color(Stderr, |t| t.red()));
         }
         println!();
     }
@@ -960,18 +3572,51 @@ fn print_groups_status(
     }
 
     if invalid_groups.is_none() {
-        return Err(ReturnCode::NoSetupFolder.into());
+        return Err(TuckrError::NoSetupFolder);
     }
 
     Ok(())
 }
 
 /// Prints symlinking status
-pub fn status_cmd(profile: Option<String>, groups: Option<Vec<String>>) -> Result<(), ExitCode> {
-    let sym = SymlinkHandler::try_new(profile.clone())?;
+/// Prints the status of the groups, every group's status is printed if none are given
+///
+/// exclude: groups that are left out of the status
+/// fix: dangling symlinks are re-pointed or removed before the status is printed
+/// no_cache: every dotfile is compared with its target again instead of reusing the cached statuses
+pub fn status_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    groups: Option<Vec<String>>,
+    exclude: &[String],
+    fix: bool,
+    no_cache: bool,
+) -> Result<(), TuckrError> {
+    let load = |profile| match no_cache {
+        true => SymlinkHandler::try_new(profile),
+        false => SymlinkHandler::try_new_cached(profile),
+    };
+
+    let mut sym = load(profile.clone())?;
+    sym.exclude_groups(exclude);
+
+    let mut dangling =
+        filter_dangling_symlinks(sym.find_dangling_symlinks(), groups.as_deref(), exclude);
+    if fix && !dangling.is_empty() {
+        fix_dangling_symlinks(dry_run, &dangling)?;
+
+        if !dry_run {
+            sym = load(profile.clone())?;
+            sym.exclude_groups(exclude);
+            dangling.clear();
+        }
+    }
 
     if sym.is_empty() {
-        println!("{}", t!("errors.no_x_setup_yet", x = "dotfiles").yellow());
+        println!(
+            "{}",
+            t!("errors.no_x_setup_yet", x = "dotfiles").if_supports_color(Stdout, |t| t.yellow())
+        );
         println!(
             "{}",
             t!(
@@ -979,7 +3624,7 @@ pub fn status_cmd(profile: Option<String>, groups: Option<Vec<String>>) -> Resul
                 dotfiles_config_dir = sym.dotfiles_dir.join("Configs").display()
             )
         );
-        return Err(ReturnCode::NoSetupFolder.into());
+        return Err(TuckrError::NoSetupFolder);
     }
 
     match groups {
@@ -988,6 +3633,7 @@ pub fn status_cmd(profile: Option<String>, groups: Option<Vec<String>>) -> Resul
 
             let groups: Vec<_> = groups
                 .into_iter()
+                .filter(|g| !dotfiles::is_excluded(g, exclude))
                 .filter_map(|g| match dotfiles::is_valid_groupname(&g) {
                     Ok(()) => Some(g),
                     Err(err) => {
@@ -997,25 +3643,25 @@ pub fn status_cmd(profile: Option<String>, groups: Option<Vec<String>>) -> Resul
                 })
                 .collect();
 
-            let ret = print_groups_status(profile, &sym, groups);
+            let ret = print_groups_status(profile, &sym, groups, &dangling);
 
             if !invalid_group_errs.is_empty() {
                 for err in invalid_group_errs {
-                    eprintln!("{}", err.red());
+                    errors::print(err);
                 }
             }
 
             return ret;
         }
 
-        None => print_global_status(&sym)?,
+        None => print_global_status(profile, &sym, &dangling)?,
     }
 
     Ok(())
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use std::{
         fs::{self, File},
         io::Write,
@@ -1031,13 +3677,13 @@ mod tests {
     /// note: every new file or group that is added to the test ought to be added to the filepaths array in Self::start().
     /// this ensures that the tests never fail with weird random panics
     #[must_use = "must be initialized before every test"]
-    struct Test {
+    pub(crate) struct Test {
         files_used: Vec<path::PathBuf>,
     }
 
     impl Test {
-        fn start() -> Self {
-            crate::fileops::init_cmd(None, false).unwrap();
+        pub(crate) fn start() -> Self {
+            crate::fileops::init_cmd(None, false, Default::default()).unwrap();
             let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
             let group_dir = dotfiles_dir.join("Configs").join("Group1");
             let new_config_dir = group_dir.join(".config");
@@ -1060,6 +3706,11 @@ mod tests {
                 files_used: filepaths.to_vec(),
             }
         }
+
+        /// Makes sure the target of a dotfile the test created is cleaned up even if the test fails
+        pub(crate) fn track(&mut self, file: path::PathBuf) {
+            self.files_used.push(file);
+        }
     }
 
     impl Drop for Test {
@@ -1097,6 +3748,7 @@ mod tests {
             None,
             false,
             false,
+            false,
             &["Group1".to_string()],
             &[],
             false,
@@ -1116,6 +3768,7 @@ mod tests {
             None,
             false,
             false,
+            false,
             &["Group1".to_string()],
             &[],
             false,
@@ -1141,4 +3794,420 @@ mod tests {
         test_adding_symlink();
         test_removing_symlink();
     }
+
+    #[test]
+    fn add_and_remove_copy() {
+        let mut test = Test::start();
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let file = dotfiles_dir
+            .join("Configs")
+            .join("CopyGroup")
+            .join(".tuckr_copy_test");
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(&file, "Some random content on file").unwrap();
+        test.track(file.clone());
+        let target = Dotfile::try_from(file.clone())
+            .unwrap()
+            .to_target_path()
+            .unwrap();
+
+        let groups = ["CopyGroup".to_string()];
+        super::add_cmd(None, false, false, true, &groups, &[], false, false, false).unwrap();
+        assert!(target.is_file() && !target.is_symlink());
+        assert!(
+            SymlinkHandler::try_new(None)
+                .unwrap()
+                .symlinked
+                .contains_key("CopyGroup")
+        );
+
+        // changing the dotfile makes the copy stale until it's added again
+        fs::write(&file, "Some other content").unwrap();
+        assert!(
+            SymlinkHandler::try_new(None)
+                .unwrap()
+                .stale
+                .contains_key("CopyGroup")
+        );
+        super::add_cmd(None, false, false, true, &groups, &[], false, false, false).unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "Some other content");
+        assert!(SymlinkHandler::try_new(None).unwrap().stale.is_empty());
+
+        // copies that were modified are left alone
+        fs::write(&target, "modified").unwrap();
+        super::remove_cmd(None, false, &groups, &[]).unwrap();
+        assert!(target.exists());
+
+        fs::write(&target, "Some other content").unwrap();
+        super::remove_cmd(None, false, &groups, &[]).unwrap();
+        assert!(!target.exists());
+        assert!(
+            SymlinkHandler::try_new(None)
+                .unwrap()
+                .not_symlinked
+                .contains_key("CopyGroup")
+        );
+    }
+
+    #[test]
+    fn hardlinks_count_as_deployed() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let file = dotfiles_dir
+            .join("Configs")
+            .join("HardlinkGroup")
+            .join(".tuckr_hardlink_test");
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(&file, "Some random content on file").unwrap();
+        let target = Dotfile::try_from(file.clone())
+            .unwrap()
+            .to_target_path()
+            .unwrap();
+
+        // what's deployed where symlinks can't be created
+        fs::hard_link(&file, &target).unwrap();
+        let mut state = crate::state::State::load(None).unwrap();
+        state.links.insert(
+            target.clone(),
+            crate::state::LinkedFile {
+                source: file.clone(),
+                method: crate::state::DeployMethod::Hardlink,
+            },
+        );
+        state.save().unwrap();
+
+        let sym = SymlinkHandler::try_new(None).unwrap();
+        assert!(sym.symlinked.contains_key("HardlinkGroup"));
+        assert!(sym.get_conflicts_in_cache().is_empty());
+
+        let groups = ["HardlinkGroup".to_string()];
+        super::remove_cmd(None, false, &groups, &[]).unwrap();
+        assert!(!target.exists());
+        assert!(file.exists());
+        assert!(crate::state::State::load(None).unwrap().links.is_empty());
+
+        // a file that replaced the hardlink is a conflict
+        fs::write(&target, "Some random content on file").unwrap();
+        let mut state = crate::state::State::load(None).unwrap();
+        state.links.insert(
+            target.clone(),
+            crate::state::LinkedFile {
+                source: file.clone(),
+                method: crate::state::DeployMethod::Hardlink,
+            },
+        );
+        state.save().unwrap();
+        assert!(
+            SymlinkHandler::try_new(None)
+                .unwrap()
+                .get_conflicts_in_cache()
+                .contains_key("HardlinkGroup")
+        );
+
+        fs::remove_file(&target).unwrap();
+        fs::remove_dir_all(dotfiles_dir).unwrap();
+    }
+
+    #[test]
+    fn add_colliding_groups() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let configs = dotfiles_dir.join("Configs");
+        for group in ["CollisionB", "CollisionA"] {
+            let file = configs.join(group).join(".tuckr_collision_test");
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(&file, group).unwrap();
+        }
+        let target = Dotfile::try_from(configs.join("CollisionA").join(".tuckr_collision_test"))
+            .unwrap()
+            .to_target_path()
+            .unwrap();
+
+        // the winner is the same whatever order the groups are added in
+        let add = |group: &str| {
+            super::add_cmd(
+                None,
+                false,
+                false,
+                false,
+                &[group.to_string()],
+                &[],
+                false,
+                false,
+                false,
+            )
+            .unwrap();
+        };
+        add("CollisionB");
+        add("CollisionA");
+        assert_eq!(fs::read_to_string(&target).unwrap(), "CollisionA");
+        add("CollisionB");
+        assert_eq!(fs::read_to_string(&target).unwrap(), "CollisionA");
+
+        let collisions = SymlinkHandler::try_new(None).unwrap().find_collisions();
+        assert_eq!(
+            collisions.values().collect::<Vec<_>>(),
+            [&["CollisionA".to_string(), "CollisionB".to_string()]]
+        );
+
+        super::remove_cmd(None, false, &["*".to_string()], &[]).unwrap();
+        assert!(!target.exists());
+        fs::remove_dir_all(dotfiles_dir).unwrap();
+    }
+
+    #[test]
+    fn unfold_folded_dirs() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let configs = dotfiles_dir.join("Configs");
+        for group in ["FoldA", "FoldB"] {
+            let file = configs.join(group).join(".tuckr_fold_test").join(group);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(&file, group).unwrap();
+        }
+        let folded = configs.join("FoldA").join(".tuckr_fold_test");
+        let target = Dotfile::try_from(folded.clone())
+            .unwrap()
+            .to_target_path()
+            .unwrap();
+
+        let add = |group: &str| {
+            super::add_cmd(
+                None,
+                false,
+                false,
+                false,
+                &[group.to_string()],
+                &[],
+                false,
+                false,
+                false,
+            )
+            .unwrap();
+        };
+        add("FoldA");
+        assert_eq!(fs::read_link(&target).unwrap(), folded);
+
+        // FoldB's file would end up in FoldA's dotfiles if it was put into the folded directory
+        let sym = SymlinkHandler::try_new(None).unwrap();
+        assert!(sym.not_symlinked.contains_key("FoldB"));
+        assert!(!sym.not_owned.contains_key("FoldB"));
+        add("FoldB");
+        assert!(target.is_dir() && !target.is_symlink());
+        assert_eq!(
+            fs::read_link(target.join("FoldA")).unwrap(),
+            folded.join("FoldA")
+        );
+        assert_eq!(fs::read_to_string(target.join("FoldB")).unwrap(), "FoldB");
+        assert!(!folded.join("FoldB").exists());
+
+        let sym = SymlinkHandler::try_new(None).unwrap();
+        assert!(sym.symlinked.contains_key("FoldA") && sym.symlinked.contains_key("FoldB"));
+        assert!(!sym.not_symlinked.contains_key("FoldB"));
+
+        super::remove_cmd(None, false, &["*".to_string()], &[]).unwrap();
+        fs::remove_dir(&target).unwrap();
+        fs::remove_dir_all(dotfiles_dir).unwrap();
+    }
+
+    #[test]
+    fn describe_files_status() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let group_dir = dotfiles_dir.join("Configs").join("FileStatus");
+        let files = [
+            ".tuckr_status_missing",
+            ".tuckr_status_conflict",
+            ".tuckr_status_dir/file",
+            "ignored.swp",
+        ];
+        for file in files {
+            let file = group_dir.join(file);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(file, "Some random content on file").unwrap();
+        }
+        fs::write(group_dir.join(dotfiles::IGNORE_FILE), "*.swp\n").unwrap();
+
+        let dotfile = |file: &str| Dotfile::try_from(group_dir.join(file)).unwrap();
+        let conflict = dotfile(".tuckr_status_conflict").to_target_path().unwrap();
+        fs::write(&conflict, "Some other content").unwrap();
+
+        let sym = SymlinkHandler::try_new(None).unwrap();
+        let status = |file: &str| {
+            crate::table::strip_colors(&super::describe_file_status(&sym, &dotfile(file)))
+        };
+        assert_eq!(status(".tuckr_status_missing"), "not symlinked");
+        assert_eq!(
+            status(".tuckr_status_conflict"),
+            format!("conflicting file: {}", conflict.display())
+        );
+        assert_eq!(status(".tuckr_status_dir/file"), "missing parent directory");
+        assert_eq!(status("ignored.swp"), "ignored");
+
+        fs::remove_file(conflict).unwrap();
+        fs::remove_dir_all(dotfiles_dir).unwrap();
+    }
+
+    #[test]
+    fn remove_deleted_group() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let group_dir = dotfiles_dir.join("Configs").join("DeletedGroup");
+        let file = group_dir.join(".tuckr_deleted_test");
+        fs::create_dir_all(&group_dir).unwrap();
+        fs::write(&file, "Some random content on file").unwrap();
+        let target = Dotfile::try_from(file.clone())
+            .unwrap()
+            .to_target_path()
+            .unwrap();
+
+        let groups = ["DeletedGroup".to_string()];
+        super::add_cmd(None, false, false, false, &groups, &[], false, false, false).unwrap();
+        let state = crate::state::State::load(None).unwrap();
+        assert_eq!(state.deployed[&target].source, file);
+        assert_eq!(state.deployed[&target].group, "DeletedGroup");
+
+        // the group is only known through the state once it's gone from the dotfiles
+        fs::remove_dir_all(&group_dir).unwrap();
+        let state = crate::state::State::load(None).unwrap();
+        assert_eq!(state.orphaned_groups()["DeletedGroup"], [target.as_path()]);
+
+        super::remove_cmd(None, false, &groups, &[]).unwrap();
+        assert!(!target.is_symlink());
+        let state = crate::state::State::load(None).unwrap();
+        assert!(!state.deployed.contains_key(&target));
+
+        fs::remove_dir_all(dotfiles_dir).unwrap();
+    }
+
+    #[test]
+    fn add_and_render_template() {
+        let mut test = Test::start();
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let template = dotfiles_dir
+            .join("Configs")
+            .join("TemplateGroup")
+            .join(".tuckr_template_test.tmpl");
+        fs::create_dir_all(template.parent().unwrap()).unwrap();
+        fs::write(&template, "name = {{ name }}").unwrap();
+        test.track(template.clone());
+        fs::write(dotfiles_dir.join("vars.toml"), "name = \"tuckr\"").unwrap();
+
+        let target = Dotfile::try_from(template.clone())
+            .unwrap()
+            .to_target_path()
+            .unwrap();
+        assert_eq!(target.file_name().unwrap(), ".tuckr_template_test");
+
+        let groups = ["TemplateGroup".to_string()];
+        super::add_cmd(None, false, false, false, &groups, &[], false, false, false).unwrap();
+        assert!(target.is_file() && !target.is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "name = tuckr");
+        assert!(SymlinkHandler::try_new(None).unwrap().stale.is_empty());
+
+        // changing the variables makes the rendered file stale until it's added again
+        fs::write(dotfiles_dir.join("vars.toml"), "name = \"Tuckr\"").unwrap();
+        assert!(
+            SymlinkHandler::try_new(None)
+                .unwrap()
+                .stale
+                .contains_key("TemplateGroup")
+        );
+
+        super::add_cmd(None, false, false, false, &groups, &[], false, false, false).unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "name = Tuckr");
+        assert!(SymlinkHandler::try_new(None).unwrap().stale.is_empty());
+
+        super::remove_cmd(None, false, &groups, &[]).unwrap();
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn find_and_fix_dangling_symlinks() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let configs_dir = dotfiles_dir.join("Configs");
+        let dotfile = configs_dir.join("OldGroup").join(".tuckr_dangling_test");
+        fs::create_dir_all(dotfile.parent().unwrap()).unwrap();
+        fs::write(&dotfile, "dangling").unwrap();
+
+        let target = Dotfile::try_from(dotfile.clone())
+            .unwrap()
+            .to_target_path()
+            .unwrap();
+        let groups = ["OldGroup".to_string()];
+        super::add_cmd(None, false, false, false, &groups, &[], false, false, false).unwrap();
+
+        let find_dangling = || {
+            SymlinkHandler::try_new(None)
+                .unwrap()
+                .find_dangling_symlinks()
+                .into_iter()
+                .filter(|link| link.path == target)
+                .collect::<Vec<_>>()
+        };
+
+        // the group was renamed so the symlink is re-pointed to its new name
+        let moved = configs_dir.join("NewGroup").join(".tuckr_dangling_test");
+        fs::create_dir_all(moved.parent().unwrap()).unwrap();
+        fs::rename(&dotfile, &moved).unwrap();
+        fs::remove_dir(dotfile.parent().unwrap()).unwrap();
+
+        let dangling = find_dangling();
+        assert_eq!(
+            dangling,
+            [super::DanglingSymlink {
+                path: target.clone(),
+                source: dotfile.clone(),
+                group: "OldGroup".into(),
+                replacement: Some(moved.clone()),
+            }]
+        );
+        super::fix_dangling_symlinks(false, &dangling).unwrap();
+        assert_eq!(fs::read_link(&target).unwrap(), moved);
+
+        // without a replacement the symlink is removed
+        fs::remove_file(&moved).unwrap();
+        let dangling = find_dangling();
+        assert_eq!(dangling.len(), 1);
+        assert_eq!(dangling[0].replacement, None);
+        super::fix_dangling_symlinks(false, &dangling).unwrap();
+        assert!(target.symlink_metadata().is_err());
+
+        fs::remove_dir_all(dotfiles_dir).unwrap();
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn only_symlinks_into_the_dotfiles_are_removed() {
+        use std::os::unix::fs::symlink;
+
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let dotfile = dotfiles_dir.join("Configs").join("Group").join(".file");
+        let target_dir = dotfiles_dir.with_extension("target");
+        fs::create_dir_all(dotfile.parent().unwrap()).unwrap();
+        fs::create_dir_all(&target_dir).unwrap();
+        fs::write(&dotfile, "dotfile").unwrap();
+
+        let absolute = target_dir.join("absolute");
+        symlink(&dotfile, &absolute).unwrap();
+        assert!(super::links_to_dotfile(&absolute, &dotfile, &dotfiles_dir));
+
+        let relative = target_dir.join("relative");
+        let relative_dotfile = std::path::Path::new("..")
+            .join(dotfiles_dir.file_name().unwrap())
+            .join("Configs/Group/.file");
+        symlink(relative_dotfile, &relative).unwrap();
+        assert!(super::links_to_dotfile(&relative, &dotfile, &dotfiles_dir));
+
+        // the user's own file that happens to be where the dotfile would be deployed
+        let own_file = target_dir.join("own");
+        fs::write(&own_file, "mine").unwrap();
+        let elsewhere = target_dir.join("elsewhere");
+        symlink(&own_file, &elsewhere).unwrap();
+        assert!(!super::links_to_dotfile(
+            &elsewhere,
+            &dotfile,
+            &dotfiles_dir
+        ));
+        assert!(!super::links_to_dotfile(&own_file, &dotfile, &dotfiles_dir));
+        assert!(!super::links_to_dotfile(&absolute, &dotfile, &target_dir));
+
+        fs::remove_dir_all(&target_dir).unwrap();
+        fs::remove_dir_all(&dotfiles_dir).unwrap();
+    }
 }
@@ -0,0 +1,208 @@
+//! Renders the tables that commands print
+//!
+//! Tables all look the same: rounded borders, indented by a margin, with values centered under
+//! their headers, and they're wrapped so that they fit the terminal. With `--format csv` or
+//! `--format tsv` the same rows are printed as plain delimited text without colors instead,
+//! which is easier to read in narrow CI logs and to use in scripts. `--format json` prints them as
+//! an array of objects keyed by the headers, and errors along with their codes.
+
+use owo_colors::OwoColorize;
+use owo_colors::Stream::Stdout;
+use std::sync::atomic::{AtomicU8, Ordering};
+use tabled::object::Segment;
+use tabled::peaker::PriorityMax;
+use tabled::{Alignment, Margin, Modify, Style, Table, Tabled, Width};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    #[default]
+    Table,
+    Csv,
+    Tsv,
+    Json,
+}
+
+static FORMAT: AtomicU8 = AtomicU8::new(Format::Table as u8);
+
+/// Sets how tables are printed
+pub fn set_format(format: Format) {
+    FORMAT.store(format as u8, Ordering::Relaxed);
+}
+
+pub fn format() -> Format {
+    match FORMAT.load(Ordering::Relaxed) {
+        1 => Format::Csv,
+        2 => Format::Tsv,
+        3 => Format::Json,
+        _ => Format::Table,
+    }
+}
+
+/// Returns a check mark for yes and a cross for no, or true and false otherwise
+pub fn check(yes: bool) -> String {
+    match (format(), yes) {
+        (Format::Table, true) => "✓".if_supports_color(Stdout, |t| t.green()).to_string(),
+        (Format::Table, false) => "✗".if_supports_color(Stdout, |t| t.red()).to_string(),
+        (_, yes) => yes.to_string(),
+    }
+}
+
+/// Returns the terminal's width, $COLUMNS takes precedence
+///
+/// None is returned if it can't be found out, e.g. when the output is piped
+fn terminal_width() -> Option<usize> {
+    if let Some(columns) = std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+    {
+        return Some(columns);
+    }
+
+    #[cfg(target_family = "unix")]
+    {
+        // SAFETY: winsize is plain data and it's a valid winsize for TIOCGWINSZ to fill in
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        let found = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+        (found && size.ws_col > 0).then_some(size.ws_col as usize)
+    }
+
+    #[cfg(not(target_family = "unix"))]
+    None
+}
+
+/// Gives a table tuckr's look, `indent` is the margin to its left
+pub fn style(table: &mut Table, indent: usize) -> &mut Table {
+    let (left, right) = (indent, 4);
+    table
+        .with(Style::rounded())
+        .with(Modify::new(Segment::new(1.., 1..)).with(Alignment::center()));
+
+    // the widest columns are wrapped first, the margin is left out of the width
+    if let Some(width) = terminal_width()
+        && width > left + right
+    {
+        table.with(
+            Width::wrap(width - left - right)
+                .keep_words()
+                .priority::<PriorityMax>(),
+        );
+    }
+
+    table.with(Margin::new(left, right, 1, 1))
+}
+
+/// Removes the colors from a value
+pub fn strip_colors(value: &str) -> String {
+    let mut stripped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // color codes look like ESC [ ... m
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+            continue;
+        }
+        stripped.push(c);
+    }
+    stripped
+}
+
+/// Formats a row of CSV or TSV, fields are quoted in CSV when needed and tabs are replaced in TSV
+fn delimited_row<'a>(format: Format, fields: impl IntoIterator<Item = &'a str>) -> String {
+    let fields = fields
+        .into_iter()
+        .map(strip_colors)
+        .map(|field| match format {
+            Format::Tsv => field.replace(['\t', '\n'], " "),
+            _ if field.contains([',', '"', '\n']) => format!("\"{}\"", field.replace('"', "\"\"")),
+            _ => field,
+        });
+
+    let delimiter = if format == Format::Tsv { "\t" } else { "," };
+    fields.collect::<Vec<_>>().join(delimiter)
+}
+
+/// Renders rows the way `--format` asks for, `indent` is the table's margin to its left
+pub fn render<T: Tabled>(rows: &[T], indent: usize) -> String {
+    match format() {
+        Format::Table => style(&mut Table::new(rows), indent).to_string(),
+        Format::Json => {
+            let headers = T::headers();
+            let objects: Vec<serde_json::Map<_, _>> = rows
+                .iter()
+                .map(|row| {
+                    headers
+                        .iter()
+                        .zip(row.fields())
+                        .map(|(header, field)| (header.to_string(), strip_colors(&field).into()))
+                        .collect()
+                })
+                .collect();
+            serde_json::to_string_pretty(&objects).unwrap()
+        }
+        format => {
+            let mut lines = vec![delimited_row(
+                format,
+                T::headers().iter().map(|header| header.as_ref()),
+            )];
+            lines.extend(
+                rows.iter()
+                    .map(|row| delimited_row(format, row.fields().iter().map(|f| f.as_ref()))),
+            );
+            lines.join("\n")
+        }
+    }
+}
+
+/// Prints rows as a table, or as CSV/TSV/JSON if `--format` asks for it
+pub fn print<T: Tabled>(rows: &[T]) {
+    println!("{}", render(rows, 4));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Tabled)]
+    struct Row {
+        #[tabled(rename = "Group")]
+        group: &'static str,
+        #[tabled(rename = "Note")]
+        note: String,
+    }
+
+    #[test]
+    fn delimited_output() {
+        let rows = [
+            Row {
+                group: "zsh",
+                note: "plain".green().to_string(),
+            },
+            Row {
+                group: "nvim",
+                note: "held, \"until\"\tfixed".into(),
+            },
+        ];
+
+        assert_eq!(
+            delimited_row(
+                Format::Csv,
+                rows[1].fields().iter().map(|field| field.as_ref())
+            ),
+            "nvim,\"held, \"\"until\"\"\tfixed\""
+        );
+        assert_eq!(
+            delimited_row(
+                Format::Tsv,
+                rows[1].fields().iter().map(|field| field.as_ref())
+            ),
+            "nvim\theld, \"until\" fixed"
+        );
+        assert_eq!(
+            delimited_row(
+                Format::Csv,
+                rows[0].fields().iter().map(|field| field.as_ref())
+            ),
+            "zsh,plain"
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Renders templates
+//!
+//! Files in Configs ending with `.tmpl` are rendered and deployed as regular files without the extension,
+//! e.g. `Configs/git/.gitconfig.tmpl` is deployed to `~/.gitconfig`.
+//!
+//! Templates are filled with the variables from dotfiles/vars.toml, each profile has its own vars.toml
+//! and variables can be overridden for a single machine in a `[hosts.<hostname>]` table.
+//! Tuckr also provides `tuckr.hostname`, `tuckr.os` and `tuckr.family`.
+//!
+//! Templates are rendered with minijinja, so anything jinja2 can do works:
+//! - `{{ email }}` or `{{ git.email }}` are replaced with the variable's value
+//! - `{% if tuckr.os == "macos" %}...{% endif %}` and `{% for ... %}` blocks, filters like `{{ name | upper }}`
+//! - `{{ "{{" }}` outputs a string as is, which can be used to escape braces
+//!
+//! Variables that aren't defined are an error instead of rendering as nothing.
+
+use crate::dotfiles;
+use crate::fileops::DirWalk;
+use minijinja::value::ValueKind;
+use minijinja::{Environment, UndefinedBehavior};
+use std::fs;
+use std::path::Path;
+use toml::{Table, Value};
+
+pub const TEMPLATE_EXTENSION: &str = "tmpl";
+const VARS_FILE: &str = "vars.toml";
+
+/// Checks whether a file is a template
+pub fn is_template(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == TEMPLATE_EXTENSION)
+        && !path.is_dir()
+}
+
+/// Checks whether a directory has templates inside of it
+///
+/// These directories can't be symlinked since the rendered files have to be placed inside of them
+pub fn contains_templates(dir: &Path) -> bool {
+    dir.is_dir() && DirWalk::new(dir).any(|file| is_template(&file))
+}
+
+/// Merges `overrides` into `vars`, tables are merged recursively instead of being replaced
+fn merge_vars(vars: &mut Table, overrides: Table) {
+    for (key, value) in overrides {
+        match (vars.get_mut(&key), value) {
+            (Some(Value::Table(vars)), Value::Table(overrides)) => merge_vars(vars, overrides),
+            (_, value) => {
+                vars.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Loads the variables available to templates from a vars.toml
+fn load_vars(vars_file: &str, hostname: Option<&str>) -> Result<Table, String> {
+    let mut tuckr = Table::new();
+    if let Some(hostname) = hostname {
+        tuckr.insert("hostname".into(), hostname.into());
+    }
+    tuckr.insert("os".into(), std::env::consts::OS.into());
+    tuckr.insert("family".into(), std::env::consts::FAMILY.into());
+
+    let mut vars = Table::new();
+    vars.insert("tuckr".into(), tuckr.into());
+
+    let mut file_vars: Table = toml::from_str(vars_file).map_err(|e| e.to_string())?;
+    let hosts = file_vars.remove("hosts");
+    merge_vars(&mut vars, file_vars);
+
+    let host_vars = match (hosts, hostname) {
+        (Some(Value::Table(mut hosts)), Some(hostname)) => hosts.remove(hostname),
+        _ => None,
+    };
+    if let Some(Value::Table(host_vars)) = host_vars {
+        merge_vars(&mut vars, host_vars);
+    }
+
+    Ok(vars)
+}
+
+/// Converts a variable from vars.toml to a value that templates can use, dates are used as text
+fn to_template_value(value: &Value) -> minijinja::Value {
+    match value {
+        Value::String(value) => value.as_str().into(),
+        Value::Integer(value) => (*value).into(),
+        Value::Float(value) => (*value).into(),
+        Value::Boolean(value) => (*value).into(),
+        Value::Datetime(value) => value.to_string().into(),
+        Value::Array(values) => values.iter().map(to_template_value).collect(),
+        Value::Table(table) => table
+            .iter()
+            .map(|(key, value)| (key.as_str(), to_template_value(value)))
+            .collect(),
+    }
+}
+
+/// Renders the template with the variables
+pub fn render(template: &str, vars: &Table) -> Result<String, String> {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    // the rendered files have to end the same way as their templates
+    env.set_keep_trailing_newline(true);
+    // booleans are written the way config files spell them instead of python's True and False
+    env.set_formatter(|out, state, value| match value.kind() {
+        ValueKind::Bool => write!(out, "{}", value.is_true()).map_err(minijinja::Error::from),
+        _ => minijinja::escape_formatter(out, state, value),
+    });
+
+    let vars: minijinja::Value = vars
+        .iter()
+        .map(|(key, value)| (key.as_str(), to_template_value(value)))
+        .collect();
+    env.render_str(template, vars)
+        .map_err(|err| match err.line() {
+            Some(line) => format!(
+                "line {line}: {}",
+                err.detail().unwrap_or(&err.kind().to_string())
+            ),
+            None => err.to_string(),
+        })
+}
+
+/// Renders a template from dotfiles/Configs with the variables of the dotfiles it belongs to
+///
+/// dotfiles_dir: the dotfiles the template is in, it's passed in since templates are rendered on
+/// worker threads as well
+pub fn render_file(template: &Path, dotfiles_dir: &Path) -> Result<String, String> {
+    let with_path = |err: String| format!("{}: {err}", template.display());
+
+    let vars_file = fs::read_to_string(dotfiles_dir.join(VARS_FILE)).unwrap_or_default();
+    let vars = load_vars(&vars_file, dotfiles::get_hostname())
+        .map_err(|err| format!("{}: {err}", dotfiles_dir.join(VARS_FILE).display()))?;
+
+    let template = fs::read_to_string(template).map_err(|e| with_path(e.to_string()))?;
+    render(&template, &vars).map_err(with_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_template() {
+        let vars = load_vars(
+            r#"
+            name = "Tuckr"
+            email = "tuckr@example.com"
+            git.signing = true
+
+            [hosts.worklaptop]
+            email = "tuckr@work.example.com"
+            "#,
+            Some("worklaptop"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            render(
+                "name = {{ name }}\nemail = {{email}}\nsign = {{ git.signing }}\n",
+                &vars
+            )
+            .unwrap(),
+            "name = Tuckr\nemail = tuckr@work.example.com\nsign = true\n"
+        );
+        assert_eq!(
+            render("{{ tuckr.hostname }} {{ \"{{\" }}", &vars).unwrap(),
+            "worklaptop {{"
+        );
+
+        assert_eq!(
+            render(
+                "{% if git.signing %}gpgsign = true{% endif %}\n{{ name | lower }}",
+                &vars
+            )
+            .unwrap(),
+            "gpgsign = true\ntuckr"
+        );
+
+        assert!(render("{{ missing }}", &vars).is_err());
+        assert!(render("{{ git.missing }}", &vars).is_err());
+        assert!(render("{{ name ", &vars).is_err());
+    }
+}
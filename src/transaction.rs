@@ -0,0 +1,223 @@
+//! Undoes deployments that fail partway through
+//!
+//! Every change that deploying makes to the filesystem is recorded in a transaction, so that when
+//! one of them fails (e.g. permission denied on a single file) all of them are undone again instead
+//! of leaving the target half deployed. `--no-rollback` keeps whatever was deployed instead.
+//!
+//! Files that deploying removes are moved aside until the transaction is committed, so that they
+//! can be put back as well.
+
+use crate::fileops;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+static NO_ROLLBACK: OnceLock<bool> = OnceLock::new();
+
+/// Keeps the changes of failed deployments, set with `--no-rollback`
+pub fn disable_rollback() {
+    _ = NO_ROLLBACK.set(true);
+}
+
+pub fn rollback_enabled() -> bool {
+    !NO_ROLLBACK.get().copied().unwrap_or_default()
+}
+
+/// Creates a directory along with its missing parents
+///
+/// Returns the directories that had to be created, parents come before their children
+pub fn create_dirs(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut missing: Vec<_> = dir
+        .ancestors()
+        .take_while(|dir| !dir.exists())
+        .map(Path::to_path_buf)
+        .collect();
+    missing.reverse();
+
+    fs::create_dir_all(dir)?;
+    Ok(missing)
+}
+
+enum Change {
+    /// a file, symlink or directory that didn't exist before
+    Created(PathBuf),
+    /// a file whose previous contents were replaced
+    Overwritten { path: PathBuf, contents: Vec<u8> },
+    /// a file that was moved out of the way, e.g. into a backup
+    Moved { from: PathBuf, to: PathBuf },
+}
+
+impl Change {
+    fn undo(&self) -> io::Result<()> {
+        match self {
+            Change::Created(path) => {
+                let metadata = match fs::symlink_metadata(path) {
+                    Ok(metadata) => metadata,
+                    // it was already removed by someone else
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+                    Err(err) => return Err(err),
+                };
+
+                // symlinks to directories are removed like directories on windows
+                #[cfg(target_family = "windows")]
+                if metadata.is_symlink() && path.is_dir() {
+                    return fs::remove_dir(path);
+                }
+
+                // what was created inside of a directory has already been undone by now,
+                // so directories are only removed if they're empty since anything left isn't ours
+                match metadata.is_dir() {
+                    true => fs::remove_dir(path),
+                    false => fs::remove_file(path),
+                }
+            }
+            Change::Overwritten { path, contents } => fs::write(path, contents),
+            Change::Moved { from, to } => fileops::move_path(to, from),
+        }
+    }
+}
+
+/// The changes made to the filesystem, in the order they were made
+#[derive(Default)]
+pub struct Transaction {
+    changes: Vec<Change>,
+    /// where removed files are kept until the transaction is committed
+    trash: Option<PathBuf>,
+}
+
+impl Transaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a file, symlink or directory that didn't exist before
+    pub fn created(&mut self, path: impl Into<PathBuf>) {
+        self.changes.push(Change::Created(path.into()));
+    }
+
+    /// Records that a file was moved from `from` to `to`
+    pub fn moved(&mut self, from: impl Into<PathBuf>, to: impl Into<PathBuf>) {
+        self.changes.push(Change::Moved {
+            from: from.into(),
+            to: to.into(),
+        });
+    }
+
+    /// Creates a directory along with its missing parents
+    pub fn create_dir_all(&mut self, dir: &Path) -> io::Result<()> {
+        for created in create_dirs(dir)? {
+            self.created(created);
+        }
+        Ok(())
+    }
+
+    /// Writes a file, keeping its previous contents if it already existed
+    pub fn write(&mut self, path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+        let change = match fs::read(path) {
+            Ok(previous) => Change::Overwritten {
+                path: path.to_path_buf(),
+                contents: previous,
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Change::Created(path.into()),
+            Err(err) => return Err(err),
+        };
+
+        fs::write(path, contents)?;
+        self.changes.push(change);
+        Ok(())
+    }
+
+    /// Moves a file or directory out of the way, it's only deleted once the transaction is committed
+    pub fn remove(&mut self, path: &Path) -> io::Result<()> {
+        let trash = self.trash.get_or_insert_with(|| {
+            dirs::cache_dir()
+                .unwrap_or_else(std::env::temp_dir)
+                .join("tuckr")
+                .join("rollback")
+                .join(std::process::id().to_string())
+        });
+        fs::create_dir_all(&*trash)?;
+
+        let removed = trash.join(self.changes.len().to_string());
+        fileops::move_path(path, &removed)?;
+        self.moved(path, removed);
+        Ok(())
+    }
+
+    /// Returns the point the transaction is at, rolling back to it undoes whatever comes after it
+    pub fn checkpoint(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Undoes the changes that were made after the checkpoint, the newest one first
+    ///
+    /// Every change is tried even if some of them fail, the errors are returned
+    pub fn rollback_to(&mut self, checkpoint: usize) -> Vec<String> {
+        let mut errors = Vec::new();
+        while self.changes.len() > checkpoint {
+            let change = self.changes.pop().unwrap();
+            if let Err(err) = change.undo() {
+                let path = match &change {
+                    Change::Created(path) | Change::Overwritten { path, .. } => path,
+                    Change::Moved { from, .. } => from,
+                };
+                errors.push(format!("{}: {err}", path.display()));
+            }
+        }
+
+        errors
+    }
+
+    /// Undoes every change
+    pub fn rollback(&mut self) -> Vec<String> {
+        self.rollback_to(0)
+    }
+
+    /// Keeps the changes and deletes the files that were removed
+    pub fn commit(self) {
+        if let Some(trash) = self.trash {
+            _ = fs::remove_dir_all(trash);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rollback_changes() {
+        let dir = std::env::temp_dir()
+            .join("tuckr")
+            .join("transaction")
+            .join(std::process::id().to_string());
+        _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let existing = dir.join("existing");
+        let replaced = dir.join("replaced");
+        fs::write(&existing, "before").unwrap();
+        fs::write(&replaced, "replaced").unwrap();
+
+        let mut transaction = Transaction::new();
+        transaction.create_dir_all(&dir.join("a/b/c")).unwrap();
+        transaction.write(&dir.join("a/b/c/new"), "new").unwrap();
+        transaction.write(&existing, "after").unwrap();
+        let checkpoint = transaction.checkpoint();
+        transaction.remove(&replaced).unwrap();
+        fs::write(&replaced, "adopted").unwrap();
+        transaction.created(&replaced);
+
+        assert!(transaction.rollback_to(checkpoint).is_empty());
+        assert_eq!(fs::read_to_string(&replaced).unwrap(), "replaced");
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "after");
+
+        assert!(transaction.rollback().is_empty());
+        assert_eq!(transaction.checkpoint(), 0);
+        assert!(!dir.join("a").exists());
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "before");
+
+        transaction.commit();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
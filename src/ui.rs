@@ -0,0 +1,429 @@
+//! Interactive mode
+//!
+//! `tuckr ui` lists the groups along with how much of them is deployed, they can be selected with
+//! the keyboard and then added, removed or set. The panes on the right show where the files of the
+//! group under the cursor are deployed to and what its hooks printed the last time they ran.
+//!
+//! The commands aren't run within the interface, it's put away while tuckr runs them like it would
+//! from the command line, so they take the same locks, ask the same questions and print the same.
+
+use crate::dotfiles::{self, Dotfile, DotfileType};
+use crate::error;
+use crate::errors::{self, TuckrError};
+use crate::hooklog;
+use crate::state::State;
+use crate::symlinks::{self, DeployState};
+use owo_colors::OwoColorize;
+use owo_colors::Stream::Stdout;
+use ratatui::backend::CrosstermBackend;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::crossterm::{cursor, execute};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Padding, Paragraph};
+use ratatui::{DefaultTerminal, Frame, Terminal};
+use rust_i18n::t;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+
+#[derive(Debug, PartialEq)]
+enum Key {
+    Up,
+    Down,
+    Toggle,
+    Add,
+    Remove,
+    Set,
+    Quit,
+    /// the terminal was resized, the screen only has to be drawn again
+    Resize,
+    Other,
+}
+
+impl From<KeyEvent> for Key {
+    fn from(key: KeyEvent) -> Key {
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => Key::Up,
+            KeyCode::Down | KeyCode::Char('j') => Key::Down,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Key::Quit,
+            KeyCode::Char(' ') => Key::Toggle,
+            KeyCode::Char('a') => Key::Add,
+            KeyCode::Char('r') => Key::Remove,
+            KeyCode::Char('s') => Key::Set,
+            KeyCode::Char('q') | KeyCode::Esc => Key::Quit,
+            _ => Key::Other,
+        }
+    }
+}
+
+fn read_key() -> io::Result<Key> {
+    loop {
+        match event::read()? {
+            // windows also sends an event when keys are released
+            Event::Key(key) if key.kind == KeyEventKind::Press => return Ok(Key::from(key)),
+            Event::Resize(..) => return Ok(Key::Resize),
+            _ => (),
+        }
+    }
+}
+
+/// The terminal the interface is drawn on, it's put back the way it was once it's dropped
+struct Screen {
+    terminal: DefaultTerminal,
+}
+
+impl Screen {
+    fn enter() -> io::Result<Self> {
+        let terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+        let mut screen = Screen { terminal };
+        screen.show(true)?;
+        Ok(screen)
+    }
+
+    /// Switches to the alternate screen with the input read key by key, or goes back to the
+    /// normal one
+    fn show(&mut self, shown: bool) -> io::Result<()> {
+        match shown {
+            true => {
+                terminal::enable_raw_mode()?;
+                execute!(io::stdout(), EnterAlternateScreen, cursor::Hide)?;
+                // whatever was drawn before is gone from the alternate screen
+                self.terminal.clear()
+            }
+            false => {
+                execute!(io::stdout(), cursor::Show, LeaveAlternateScreen)?;
+                terminal::disable_raw_mode()
+            }
+        }
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        _ = self.show(false);
+    }
+}
+
+struct Group {
+    name: String,
+    state: DeployState,
+    held: bool,
+}
+
+impl Group {
+    fn label(&self, selected: bool) -> String {
+        let state = match self.state {
+            DeployState::Deployed => '✓',
+            DeployState::Partial => '~',
+            DeployState::NotDeployed => '✗',
+        };
+        let selected = if selected { "[x]" } else { "[ ]" };
+        let held = match self.held {
+            true => format!(" ({})", t!("info.held")),
+            false => String::new(),
+        };
+        format!(" {selected} {state} {}{held}", self.name)
+    }
+}
+
+/// Returns where the group's files are deployed to, the home directory is shortened to ~
+fn deployed_files(profile: Option<String>, group: &str) -> Vec<String> {
+    let Ok(dotfiles_dir) = dotfiles::get_dotfiles_path(profile) else {
+        return Vec::new();
+    };
+    let group_dir = dotfiles_dir
+        .join(DotfileType::Configs.dir_name())
+        .join(group);
+    let Ok(files) = Dotfile::try_from(group_dir).and_then(|group| group.try_iter()) else {
+        return Vec::new();
+    };
+
+    let home = dirs::home_dir().unwrap_or_default();
+    let mut targets: Vec<_> = files
+        .filter(|file| !file.path.is_dir())
+        .filter_map(|file| file.to_target_path().ok())
+        .map(|target| match target.strip_prefix(&home) {
+            Ok(path) if home.components().count() > 1 => format!("~/{}", path.display()),
+            _ => target.display().to_string(),
+        })
+        .collect();
+    targets.sort();
+    targets
+}
+
+/// Returns the last lines of the log of the last time the group's hooks ran
+fn hook_output(group: &str, lines: usize) -> Vec<String> {
+    let Some(log) = hooklog::latest_log(group).and_then(|log| fs::read_to_string(log).ok()) else {
+        return vec![t!("info.ui_no_hook_output").into_owned()];
+    };
+
+    let output: Vec<_> = log.lines().rev().take(lines).map(String::from).collect();
+    output.into_iter().rev().collect()
+}
+
+struct Ui {
+    profile: Option<String>,
+    dry_run: bool,
+    groups: Vec<Group>,
+    cursor: usize,
+    selected: BTreeSet<String>,
+}
+
+impl Ui {
+    fn load_groups(&mut self) -> Result<(), TuckrError> {
+        let saved = State::load(self.profile.clone()).unwrap_or_default();
+
+        self.groups = symlinks::get_deploy_states(self.profile.clone())?
+            .into_iter()
+            .map(|(name, state)| Group {
+                held: saved.is_held(&name),
+                name,
+                state,
+            })
+            .collect();
+        self.cursor = self.cursor.min(self.groups.len().saturating_sub(1));
+        Ok(())
+    }
+
+    fn handle(&mut self, key: &Key) {
+        match key {
+            Key::Up => self.cursor = self.cursor.saturating_sub(1),
+            Key::Down if self.cursor + 1 < self.groups.len() => self.cursor += 1,
+            Key::Toggle => {
+                if let Some(group) = self.groups.get(self.cursor)
+                    && !self.selected.remove(&group.name)
+                {
+                    self.selected.insert(group.name.clone());
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Returns the selected groups, or the one under the cursor if none were selected
+    fn targets(&self) -> Vec<String> {
+        if !self.selected.is_empty() {
+            return self.selected.iter().cloned().collect();
+        }
+        self.groups
+            .get(self.cursor)
+            .map(|group| vec![group.name.clone()])
+            .unwrap_or_default()
+    }
+
+    /// Returns the arguments that tuckr is run with to run the command on the groups
+    fn command_args(&self, command: &str) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(profile) = &self.profile {
+            args.extend(["--profile".into(), profile.clone()]);
+        }
+        if self.dry_run {
+            args.push("--dry-run".into());
+        }
+        args.push(command.into());
+        args.extend(self.targets());
+        args
+    }
+
+    /// Renders the screen, the groups are on the left and the selected group's details on the right
+    fn render(&self, frame: &mut Frame) {
+        let [body, keys] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let width = usize::from(body.width);
+        let list_width = self
+            .groups
+            .iter()
+            .map(|group| group.label(true).chars().count() + 1)
+            .max()
+            .unwrap_or_default()
+            .clamp(20, (width / 2).max(20));
+        let [list, details] =
+            Layout::horizontal([Constraint::Length(list_width as u16), Constraint::Min(0)])
+                .areas(body);
+
+        let bold = Style::new().add_modifier(Modifier::BOLD);
+        let groups: Vec<_> = self
+            .groups
+            .iter()
+            .map(|group| {
+                let color = match group.state {
+                    DeployState::Deployed => Color::Green,
+                    DeployState::Partial => Color::Yellow,
+                    DeployState::NotDeployed => Color::Red,
+                };
+                ListItem::new(group.label(self.selected.contains(&group.name)))
+                    .style(Style::new().fg(color))
+            })
+            .collect();
+        let groups = List::new(groups)
+            .block(Block::new().title(Line::styled(format!(" {}", t!("info.ui_groups")), bold)))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        // the list scrolls along with the cursor
+        let mut cursor = ListState::default().with_selected(Some(self.cursor));
+        frame.render_stateful_widget(groups, list, &mut cursor);
+
+        let details_block = Block::new()
+            .borders(Borders::LEFT)
+            .padding(Padding::left(1));
+        let [files, hooks] = Layout::vertical([Constraint::Percentage(50), Constraint::Min(0)])
+            .areas(details_block.inner(details));
+        frame.render_widget(details_block, details);
+
+        let (deployed, hook_lines) = match self.groups.get(self.cursor) {
+            Some(group) => (
+                deployed_files(self.profile.clone(), &group.name),
+                // the title takes up a line
+                hook_output(&group.name, usize::from(hooks.height.saturating_sub(1))),
+            ),
+            None => Default::default(),
+        };
+        let deployed = Paragraph::new(deployed.into_iter().map(Line::from).collect::<Vec<_>>())
+            .block(Block::new().title(Line::styled(t!("info.ui_files"), bold)));
+        frame.render_widget(deployed, files);
+        let hook_lines = Paragraph::new(hook_lines.into_iter().map(Line::from).collect::<Vec<_>>())
+            .style(Style::new().add_modifier(Modifier::DIM))
+            .block(Block::new().title(Line::styled(t!("info.ui_hook_output"), bold)));
+        frame.render_widget(hook_lines, hooks);
+
+        let dim = Style::new().add_modifier(Modifier::DIM);
+        frame.render_widget(Paragraph::new(t!("info.ui_keys")).style(dim), keys);
+    }
+
+    /// Runs tuckr with the command on the groups, the interface is put away while it runs
+    fn run(&mut self, screen: &mut Screen, command: &str) -> Result<(), TuckrError> {
+        let args = self.command_args(command);
+        if args.last().is_some_and(|arg| arg == command) {
+            return Ok(());
+        }
+
+        screen.show(false).map_err(report_err)?;
+
+        println!(
+            "{} tuckr {}",
+            "$".if_supports_color(Stdout, |t| t.dimmed()),
+            args.join(" ")
+        );
+        let tuckr = std::env::current_exe().map_err(report_err)?;
+        let mut tuckr = std::process::Command::new(tuckr);
+        dotfiles::pass_target_dir(&mut tuckr);
+        if let Err(err) = tuckr.args(&args).status() {
+            errors::print(err);
+        }
+
+        print!(
+            "\n{}",
+            t!("info.ui_press_key").if_supports_color(Stdout, |t| t.dimmed())
+        );
+        _ = io::stdout().flush();
+        terminal::enable_raw_mode().map_err(report_err)?;
+        while read_key().map_err(report_err)? == Key::Resize {}
+        screen.show(true).map_err(report_err)?;
+
+        self.selected.clear();
+        self.load_groups()
+    }
+}
+
+fn report_err(err: io::Error) -> TuckrError {
+    errors::print(err);
+    TuckrError::Failed
+}
+
+/// Lets groups be picked and deployed from an interactive list
+pub fn ui_cmd(profile: Option<String>, dry_run: bool) -> Result<(), TuckrError> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        errors::print(error!("ui_needs_terminal"));
+        return Err(TuckrError::Failed);
+    }
+
+    let mut ui = Ui {
+        profile,
+        dry_run,
+        groups: Vec::new(),
+        cursor: 0,
+        selected: BTreeSet::new(),
+    };
+    ui.load_groups()?;
+
+    let mut screen = Screen::enter().map_err(report_err)?;
+    loop {
+        screen
+            .terminal
+            .draw(|frame| ui.render(frame))
+            .map_err(report_err)?;
+        match read_key().map_err(report_err)? {
+            Key::Quit => return Ok(()),
+            Key::Add => ui.run(&mut screen, "add")?,
+            Key::Remove => ui.run(&mut screen, "rm")?,
+            Key::Set => ui.run(&mut screen, "set")?,
+            key => ui.handle(&key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+
+    fn group(name: &str, state: DeployState) -> Group {
+        Group {
+            name: name.into(),
+            state,
+            held: false,
+        }
+    }
+
+    #[test]
+    fn keys() {
+        let key = |code| Key::from(KeyEvent::new(code, KeyModifiers::NONE));
+        assert_eq!(key(KeyCode::Up), Key::Up);
+        assert_eq!(key(KeyCode::Char('j')), Key::Down);
+        assert_eq!(key(KeyCode::Esc), Key::Quit);
+        assert_eq!(key(KeyCode::Right), Key::Other);
+        assert_eq!(
+            Key::from(KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL)),
+            Key::Quit
+        );
+    }
+
+    #[test]
+    fn selecting_groups() {
+        let mut ui = Ui {
+            profile: Some("work".into()),
+            dry_run: true,
+            groups: vec![
+                group("nvim", DeployState::Deployed),
+                group("zsh", DeployState::NotDeployed),
+            ],
+            cursor: 0,
+            selected: BTreeSet::new(),
+        };
+
+        // the group under the cursor is used when none were selected
+        assert_eq!(
+            ui.command_args("add"),
+            ["--profile", "work", "--dry-run", "add", "nvim"]
+        );
+
+        ui.handle(&Key::Down);
+        ui.handle(&Key::Down);
+        ui.handle(&Key::Toggle);
+        ui.handle(&Key::Up);
+        ui.handle(&Key::Toggle);
+        assert_eq!(ui.targets(), ["nvim", "zsh"]);
+
+        ui.handle(&Key::Toggle);
+        assert_eq!(ui.targets(), ["zsh"]);
+
+        let mut terminal = Terminal::new(TestBackend::new(60, 10)).unwrap();
+        terminal.draw(|frame| ui.render(frame)).unwrap();
+        let screen = terminal.backend().buffer();
+        let row = |y| (0..60).map(|x| screen[(x, y)].symbol()).collect::<String>();
+        assert!(row(2).contains("[x] ✗ zsh"));
+        assert!(row(9).contains("q quit"));
+    }
+}
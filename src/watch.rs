@@ -0,0 +1,283 @@
+//! Deploys the dotfiles as they're being edited
+//!
+//! `tuckr watch` is notified by the OS of the changes to the Configs directory. Once files stop
+//! changing for a moment, the groups they belong to are added again: new files are symlinked,
+//! copies and templates are updated and the symlinks to files that were deleted or moved are
+//! removed or re-pointed. Groups whose directory was deleted are removed altogether.
+//!
+//! Only deployed groups are redeployed unless groups are given, in which case those are deployed
+//! even if they weren't. The changes are deployed by running tuckr itself so that the lock is only
+//! held while deploying and other tuckrs can run in the meantime.
+
+use crate::daemon;
+use crate::dotfiles;
+use crate::error;
+use crate::errors::{self, TuckrError};
+use crate::history;
+use crate::nested;
+use crate::state;
+use notify_debouncer_full::notify::{self, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{DebounceEventResult, RecommendedCache, new_debouncer_opt};
+use owo_colors::OwoColorize;
+use owo_colors::Stream::{Stderr, Stdout};
+use rust_i18n::t;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+/// Returns the groups the paths that were added, changed or deleted belong to
+fn changed_groups(configs_dir: &Path, paths: &BTreeSet<PathBuf>) -> BTreeSet<String> {
+    paths
+        .iter()
+        .filter_map(|path| {
+            // files directly inside of Configs don't belong to any group
+            let mut components = path.strip_prefix(configs_dir).ok()?.components();
+            match (components.next()?, components.next()) {
+                (Component::Normal(group), Some(_)) => Some(group.to_string_lossy().into_owned()),
+                // a group's directory that was added or deleted as a whole
+                (Component::Normal(group), None)
+                    if fs::symlink_metadata(path).map_or(true, |meta| meta.is_dir()) =>
+                {
+                    Some(group.to_string_lossy().into_owned())
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Waits for files in Configs to change, returns the paths that changed once nothing changed for
+/// `debounce`
+fn wait_for_changes(
+    events: &Receiver<DebounceEventResult>,
+    debounce: Duration,
+) -> Result<BTreeSet<PathBuf>, TuckrError> {
+    let mut paths = BTreeSet::new();
+    // the watcher only stops sending events when it's dropped
+    let mut received = events.recv().map_err(|_| TuckrError::Failed)?;
+    loop {
+        match received {
+            // files being read, e.g. by the tuckrs deploying them, didn't change
+            Ok(events) => paths.extend(
+                events
+                    .into_iter()
+                    .filter(|event| !event.kind.is_access())
+                    .flat_map(|event| event.event.paths),
+            ),
+            // e.g. when too many files changed at once for the OS to keep track of them
+            Err(errs) => {
+                for err in errs {
+                    errors::print(error!("couldnt_watch", err = err));
+                }
+            }
+        }
+
+        // editors often write files in several steps, e.g. to a temporary file that's renamed
+        // after, so the changes are only deployed once all of them were made
+        received = match events.recv_timeout(debounce) {
+            Ok(received) => received,
+            Err(RecvTimeoutError::Timeout) if !paths.is_empty() => return Ok(paths),
+            Err(RecvTimeoutError::Timeout) => events.recv().map_err(|_| TuckrError::Failed)?,
+            Err(RecvTimeoutError::Disconnected) => return Err(TuckrError::Failed),
+        };
+    }
+}
+
+/// Deploys the groups that changed, the ones that were deleted are removed
+fn deploy(
+    profile: &Option<String>,
+    dry_run: bool,
+    dotfiles_dir: &Path,
+    changed: BTreeSet<String>,
+    groups: &[String],
+    exclude: &[String],
+) -> Result<(), String> {
+    let configs_dir = dotfiles_dir.join("Configs");
+    let changed: BTreeSet<_> = changed
+        .into_iter()
+        .filter(|group| !dotfiles::is_excluded(group, exclude))
+        .collect();
+
+    let (present, gone): (Vec<_>, Vec<_>) = changed
+        .into_iter()
+        .partition(|group| configs_dir.join(group).exists());
+
+    // only the groups that are gone but still deployed have something left to remove
+    let state = state::State::load(profile.clone())?;
+    let orphaned = state.orphaned_groups();
+    let gone: Vec<_> = gone
+        .iter()
+        .filter(|group| orphaned.contains_key(group.as_str()))
+        .map(String::as_str)
+        .collect();
+    if !gone.is_empty() {
+        println!("{}", t!("info.watch_removing", groups = gone.join(", ")));
+        let mut args = vec!["rm"];
+        args.extend(&gone);
+        daemon::run_tuckr(profile, dry_run, &args)?;
+    }
+
+    // the files that were deployed can be gone by now, so they're also looked up in the state
+    let wanted = match groups.is_empty() {
+        true => {
+            let mut deployed = history::deployed_groups(profile.clone(), dotfiles_dir)?;
+            deployed.extend(state.deployed.values().map(|file| file.group.clone()));
+            deployed
+        }
+        false => groups.iter().cloned().collect(),
+    };
+    let present: Vec<_> = present
+        .iter()
+        .filter(|group| {
+            wanted.contains(*group) || wanted.contains(dotfiles::group_without_target(group))
+        })
+        .map(String::as_str)
+        .collect();
+    if present.is_empty() {
+        return Ok(());
+    }
+
+    println!(
+        "{}",
+        t!("info.watch_deploying", groups = present.join(", "))
+    );
+    let mut args = vec!["add"];
+    args.extend(&present);
+    if !daemon::run_tuckr(profile, dry_run, &args)? {
+        return Ok(());
+    }
+
+    // symlinks to files that were deleted or moved are removed or re-pointed
+    let mut args = vec!["status", "--fix"];
+    args.extend(&present);
+    daemon::run_tuckr(profile, dry_run, &args)?;
+
+    Ok(())
+}
+
+/// Redeploys the groups whose files change until tuckr is stopped
+///
+/// groups: the groups to deploy when they change, the deployed groups if it's empty
+/// debounce: how long files have to stay the same before they're deployed
+/// once: stops after deploying the first changes
+pub fn watch_cmd(
+    profile: Option<String>,
+    dry_run: bool,
+    groups: &[String],
+    exclude: &[String],
+    debounce: Duration,
+    once: bool,
+) -> Result<(), TuckrError> {
+    let dotfiles_dir = match dotfiles::get_dotfiles_path(profile.clone()) {
+        Ok(dir) => dir,
+        Err(err) => {
+            errors::print(err);
+            return Err(TuckrError::CouldntFindDotfiles);
+        }
+    };
+
+    let configs_dir = dotfiles_dir.join("Configs");
+    if !configs_dir.is_dir() {
+        errors::print(error!("no_dir_setup_for_x", x = "Configs"));
+        return Err(TuckrError::NoSetupFolder);
+    }
+
+    if let Some(invalid_groups) =
+        dotfiles::check_invalid_groups(profile.clone(), dotfiles::DotfileType::Configs, groups)
+    {
+        errors::print(errors::Error::new(
+            "following_groups_dont_exist",
+            format!("{}:", t!("errors.following_groups_dont_exist")),
+        ));
+        for group in invalid_groups {
+            eprintln!("\t{}", group.if_supports_color(Stderr, |t| t.red()));
+        }
+        return Err(TuckrError::NoSuchFileOrDir);
+    }
+
+    let (sender, events) = mpsc::channel();
+    // symlinks in the dotfiles aren't followed, their target is what's deployed
+    let config = notify::Config::default().with_follow_symlinks(false);
+    let watching = new_debouncer_opt::<_, RecommendedWatcher, _>(
+        debounce,
+        None,
+        sender,
+        RecommendedCache::new(),
+        config,
+    )
+    .and_then(|mut debouncer| {
+        debouncer.watch(&configs_dir, RecursiveMode::Recursive)?;
+        Ok(debouncer)
+    });
+    // the configs directory is only watched while the debouncer is around
+    let _debouncer = match watching {
+        Ok(debouncer) => debouncer,
+        Err(err) => {
+            errors::print(error!("couldnt_watch", err = err));
+            return Err(TuckrError::Failed);
+        }
+    };
+
+    println!(
+        "{}",
+        t!("info.watch_started", dir = configs_dir.display())
+            .if_supports_color(Stdout, |t| t.green())
+    );
+
+    loop {
+        let changed = changed_groups(&configs_dir, &wait_for_changes(&events, debounce)?);
+        // deploying can change the dotfiles as well, e.g. when conflicts are adopted, those
+        // changes only deploy the group again which leaves it as it is
+        let deployed = deploy(&profile, dry_run, &dotfiles_dir, changed, groups, exclude);
+        // what the tuckrs that deployed the changes did isn't needed
+        _ = nested::take_operations();
+        if let Err(err) = deployed {
+            errors::print(err);
+            if once {
+                return Err(TuckrError::Failed);
+            }
+        }
+
+        if once {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_that_changed() {
+        let configs_dir = std::env::temp_dir()
+            .join("tuckr")
+            .join("watch")
+            .join(std::process::id().to_string());
+        _ = fs::remove_dir_all(&configs_dir);
+        for group in ["edited", "emptied", "added"] {
+            fs::create_dir_all(configs_dir.join(group)).unwrap();
+        }
+        fs::write(configs_dir.join("edited").join(".rc"), "edited").unwrap();
+        fs::write(configs_dir.join("README"), "not a group").unwrap();
+        assert!(changed_groups(&configs_dir, &BTreeSet::new()).is_empty());
+
+        let changed = BTreeSet::from([
+            configs_dir.join("edited").join(".rc"),
+            // deleted as a whole
+            configs_dir.join("deleted"),
+            configs_dir.join("emptied").join(".rc"),
+            configs_dir.join("added"),
+            configs_dir.join("README"),
+            std::env::temp_dir().join("elsewhere"),
+        ]);
+        assert_eq!(
+            changed_groups(&configs_dir, &changed),
+            BTreeSet::from(["added", "deleted", "edited", "emptied"].map(String::from))
+        );
+
+        fs::remove_dir_all(&configs_dir).unwrap();
+    }
+}
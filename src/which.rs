@@ -0,0 +1,190 @@
+//! Finds where a deployed file comes from, or where a dotfile gets deployed to
+//!
+//! `tuckr which <path>` looks a file in the target directory up in the state and, when it isn't
+//! recorded there, follows its symlink, or the symlink of the folded directory it's in, back to the
+//! dotfiles. The group and the profile are worked out from the dotfile's path. Given a file from
+//! `Configs` it prints the path it's deployed to instead.
+
+use crate::dotfiles::{Dotfile, DotfileType};
+use crate::errors::TuckrError;
+use crate::fileops;
+use crate::state::{self, State};
+use owo_colors::OwoColorize;
+use owo_colors::Stream::Stdout;
+use rust_i18n::t;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq)]
+enum Lookup {
+    /// the path is deployed from a dotfile
+    DeployedFrom {
+        group: String,
+        source: PathBuf,
+    },
+    /// the path is a dotfile that is deployed to target
+    DeploysTo {
+        group: String,
+        target: PathBuf,
+    },
+    Unmanaged,
+}
+
+/// Returns the dotfile in Configs that a path is or is inside of
+fn to_config_dotfile(path: PathBuf) -> Option<Dotfile> {
+    let dotfile = Dotfile::try_from(path).ok()?;
+    let is_config = dotfile
+        .group_path
+        .parent()
+        .is_some_and(|dir| dir.ends_with(DotfileType::Configs.dir_name()));
+    (is_config && dotfile.path != dotfile.group_path).then_some(dotfile)
+}
+
+/// Returns the group and the dotfile that a file in the target directory is deployed from
+fn find_source(state: &State, path: &Path) -> Option<(String, PathBuf)> {
+    // files inside of a folded directory are deployed through the symlink of one of their parents
+    for dir in path.ancestors() {
+        let rest = path.strip_prefix(dir).ok()?;
+
+        if let Some(deployed) = state.deployed.get(dir) {
+            return Some((deployed.group.clone(), deployed.source.join(rest)));
+        }
+
+        if let Ok(source) = fileops::read_link(dir)
+            && let Some(dotfile) = to_config_dotfile(source.join(rest))
+        {
+            return Some((dotfile.group_name, dotfile.path));
+        }
+    }
+
+    None
+}
+
+fn lookup(state: &State, path: &Path) -> Lookup {
+    if let Some(dotfile) = to_config_dotfile(path.to_path_buf())
+        && let Ok(target) = dotfile.to_target_path()
+    {
+        return Lookup::DeploysTo {
+            group: dotfile.group_name,
+            target,
+        };
+    }
+
+    match find_source(state, path) {
+        Some((group, source)) => Lookup::DeployedFrom { group, source },
+        None => Lookup::Unmanaged,
+    }
+}
+
+/// Prints which group and dotfile each path comes from, or where each dotfile is deployed to
+pub fn which_cmd(profile: Option<String>, paths: &[PathBuf]) -> Result<(), TuckrError> {
+    let state = state::load_or_report(profile)?;
+    let cwd = std::env::current_dir().unwrap_or_default();
+
+    let mut unmanaged = false;
+    for path in paths {
+        // the path isn't canonicalized since that would follow the symlink it might be
+        let path = fileops::normalize_path(&cwd.join(path));
+
+        match lookup(&state, &path) {
+            Lookup::DeployedFrom { group, source } => println!(
+                "{} -> {} ({})",
+                path.display(),
+                source.display().if_supports_color(Stdout, |t| t.green()),
+                group.if_supports_color(Stdout, |t| t.blue())
+            ),
+            Lookup::DeploysTo { group, target } => println!(
+                "{} ({}) -> {}",
+                path.display(),
+                group.if_supports_color(Stdout, |t| t.blue()),
+                target.display().if_supports_color(Stdout, |t| t.green())
+            ),
+            Lookup::Unmanaged => {
+                println!(
+                    "{}",
+                    t!("info.x_isnt_managed", x = path.display())
+                        .if_supports_color(Stdout, |t| t.yellow())
+                );
+                unmanaged = true;
+            }
+        }
+    }
+
+    if unmanaged {
+        Err(TuckrError::Failed)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dotfiles;
+    use crate::state::{DeployMethod, DeployedFile};
+    use std::fs;
+
+    #[test]
+    fn lookup_deployed_files_and_dotfiles() {
+        let dotfiles_dir = dotfiles::get_dotfiles_path(None).unwrap();
+        let target_dir = dotfiles_dir.with_file_name("target");
+        let zshrc = dotfiles_dir.join("Configs/zsh/.zshrc");
+        let nvim = dotfiles_dir.join("Configs/nvim/.config/nvim");
+        let gitconfig = dotfiles_dir.join("Configs/git/.gitconfig");
+        fs::create_dir_all(&nvim).unwrap();
+        fs::create_dir_all(zshrc.parent().unwrap()).unwrap();
+        fs::create_dir_all(gitconfig.parent().unwrap()).unwrap();
+        fs::create_dir_all(target_dir.join(".config")).unwrap();
+        fs::write(&zshrc, "").unwrap();
+        fs::write(nvim.join("init.lua"), "").unwrap();
+        fs::write(&gitconfig, "").unwrap();
+        fs::write(
+            dotfiles_dir.join("Configs/zsh").join(dotfiles::TARGET_FILE),
+            target_dir.to_str().unwrap(),
+        )
+        .unwrap();
+
+        fileops::symlink(&zshrc, &target_dir.join(".zshrc")).unwrap();
+        fileops::symlink(&nvim, &target_dir.join(".config/nvim")).unwrap();
+
+        let mut state = State::default();
+        state.deployed.insert(
+            target_dir.join(".gitconfig"),
+            DeployedFile::new("git", &gitconfig, None, DeployMethod::Copy),
+        );
+
+        assert_eq!(
+            lookup(&state, &target_dir.join(".zshrc")),
+            Lookup::DeployedFrom {
+                group: "zsh".into(),
+                source: zshrc.clone()
+            }
+        );
+        assert_eq!(
+            lookup(&state, &target_dir.join(".config/nvim/init.lua")),
+            Lookup::DeployedFrom {
+                group: "nvim".into(),
+                source: nvim.join("init.lua")
+            }
+        );
+        assert_eq!(
+            lookup(&state, &target_dir.join(".gitconfig")),
+            Lookup::DeployedFrom {
+                group: "git".into(),
+                source: gitconfig
+            }
+        );
+        assert_eq!(
+            lookup(&state, &zshrc),
+            Lookup::DeploysTo {
+                group: "zsh".into(),
+                target: target_dir.join(".zshrc")
+            }
+        );
+        assert_eq!(
+            lookup(&state, &target_dir.join(".bashrc")),
+            Lookup::Unmanaged
+        );
+
+        fs::remove_dir_all(dotfiles_dir.parent().unwrap()).unwrap();
+    }
+}